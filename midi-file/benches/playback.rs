@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use midi_file::PlaybackState;
+
+fn merged_track_iteration(c: &mut Criterion) {
+    let midi = midi_file::stress::black_midi(64, 5_000);
+    let track = &midi.merged_track;
+
+    let mut group = c.benchmark_group("merged_track");
+    group.sample_size(10);
+
+    group.bench_function("play_through_at_60fps", |b| {
+        let frame = Duration::from_secs_f64(1.0 / 60.0);
+        b.iter_batched(
+            || PlaybackState::new(Duration::ZERO, track),
+            |mut state| {
+                while state.percentage() < 1.0 {
+                    criterion::black_box(state.update(track, frame));
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.bench_function("notes_at", |b| {
+        let time = PlaybackState::new(Duration::ZERO, track).lenght() / 2;
+        b.iter(|| criterion::black_box(track.notes_at(time).count()))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, merged_track_iteration);
+criterion_main!(benches);
@@ -0,0 +1,799 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    ops::Range,
+    time::Duration,
+};
+
+use midly::{MetaMessage, TrackEvent, TrackEventKind};
+
+use crate::{pulses_to_duration, MidiNote, MidiTrack, TempoTrack};
+
+/// Notes starting within this window are treated as one chord
+const CHORD_WINDOW: Duration = Duration::from_millis(30);
+/// Measures per phrase, the unit sections are made of
+const PHRASE_LEN: usize = 4;
+/// Share of equal measures for two phrases to be repetitions of each other
+const PHRASE_SIMILARITY: f32 = 0.75;
+/// Track names that give away the melody, matched in lowercase
+const MELODY_NAMES: [&str; 7] = ["melody", "lead", "vocal", "voice", "solo", "sing", "tune"];
+/// Cue points of the repeat barlines, converted scores mark them with these
+pub(crate) const REPEAT_START: &str = "|:";
+pub(crate) const REPEAT_END: &str = ":|";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoChange {
+    pub time: Duration,
+    pub bpm: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSignature {
+    pub numerator: u8,
+    pub denominator: u8,
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self {
+            numerator: 4,
+            denominator: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measure {
+    pub start: Duration,
+    pub end: Duration,
+    pub signature: TimeSignature,
+    /// Start time of every beat in the measure
+    pub beats: Vec<Duration>,
+    pub note_count: usize,
+}
+
+impl Measure {
+    /// Notes per second
+    pub fn density(&self) -> f32 {
+        let len = (self.end - self.start).as_secs_f32();
+        if len > 0.0 {
+            self.note_count as f32 / len
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Named point of the song, like a section or a rehearsal mark
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    pub time: Duration,
+    pub name: String,
+}
+
+/// Repeat barline of a converted score
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepeatBarline {
+    pub time: Duration,
+    /// Repeated part starts here, it ends here otherwise
+    pub start: bool,
+}
+
+/// Syllable of the lyrics, sung from `time` until the next one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lyric {
+    pub time: Duration,
+    pub text: String,
+    /// First syllable of a line
+    pub new_line: bool,
+}
+
+/// Part of the song found by repetition analysis, repetitions of it share its letter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub start: Duration,
+    pub end: Duration,
+    pub measures: Range<usize>,
+    /// `A` for the first distinct section, `B` for the next one, and so on
+    pub letter: char,
+    /// `Verse`, `Chorus`, or the letter when the section is neither
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    /// Pitch class, `0` is C
+    pub tonic: u8,
+    pub minor: bool,
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Eb, Ab and Bb are the usual names of those keys, the rest are sharps
+        let flats = matches!(self.tonic % 12, 3 | 8 | 10);
+        let name = piano_math::names::pitch_class_name(self.tonic, flats);
+        let mode = if self.minor { "minor" } else { "major" };
+        write!(f, "{} {}", name, mode)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct HandSpan {
+    /// Widest chord in semitones
+    pub max: u8,
+    /// Average chord width in semitones
+    pub average: f32,
+    pub lowest_note: u8,
+    pub highest_note: u8,
+}
+
+/// Song wide data computed once at load time
+#[derive(Debug, Clone, Default)]
+pub struct SongAnalysis {
+    pub tempo_map: Vec<TempoChange>,
+    pub measures: Vec<Measure>,
+    /// Marker meta events of every track, sorted by time
+    pub markers: Vec<Marker>,
+    /// Sorted by time, only converted scores have them
+    pub repeats: Vec<RepeatBarline>,
+    /// Lyric meta events, or the text events of karaoke files, sorted by time
+    pub lyrics: Vec<Lyric>,
+    /// Sections covering every measure, in song order
+    pub sections: Vec<Section>,
+    pub key: Option<Key>,
+    pub hand_span: HandSpan,
+    /// Track most likely to carry the melody, `None` unless there are two pitched tracks
+    pub melody_track: Option<usize>,
+}
+
+impl SongAnalysis {
+    pub fn new(
+        track_events: &[Vec<TrackEvent>],
+        tempo_track: &TempoTrack,
+        pulses_per_quarter_note: u16,
+        merged_track: &MidiTrack,
+    ) -> Self {
+        let tempo_map = tempo_track
+            .iter()
+            .map(|e| TempoChange {
+                time: pulses_to_duration(tempo_track, e.absolute_pulses, pulses_per_quarter_note),
+                bpm: 60_000_000.0 / e.tempo as f64,
+            })
+            .collect();
+
+        let mut signatures = Vec::new();
+        let mut markers = Vec::new();
+        let mut repeats = Vec::new();
+        let mut lyric_events = Vec::new();
+        let mut text_events = Vec::new();
+        let mut key_signature = None;
+        let mut track_names = HashMap::new();
+
+        for (track_id, events) in track_events.iter().enumerate() {
+            let mut pulses: u64 = 0;
+            for event in events.iter() {
+                pulses += event.delta.as_int() as u64;
+
+                match event.kind {
+                    TrackEventKind::Meta(MetaMessage::TimeSignature(num, denom, _, _)) => {
+                        signatures.push((
+                            pulses,
+                            TimeSignature {
+                                numerator: num.max(1),
+                                denominator: 2u8.saturating_pow(denom as u32).max(1),
+                            },
+                        ));
+                    }
+                    TrackEventKind::Meta(MetaMessage::Marker(name)) => {
+                        let name = String::from_utf8_lossy(name).trim().to_string();
+                        if !name.is_empty() {
+                            markers.push(Marker {
+                                time: pulses_to_duration(
+                                    tempo_track,
+                                    pulses,
+                                    pulses_per_quarter_note,
+                                ),
+                                name,
+                            });
+                        }
+                    }
+                    TrackEventKind::Meta(MetaMessage::CuePoint(mark))
+                        if mark == REPEAT_START.as_bytes() || mark == REPEAT_END.as_bytes() =>
+                    {
+                        repeats.push(RepeatBarline {
+                            time: pulses_to_duration(tempo_track, pulses, pulses_per_quarter_note),
+                            start: mark == REPEAT_START.as_bytes(),
+                        });
+                    }
+                    TrackEventKind::Meta(MetaMessage::Lyric(text)) => {
+                        let time = pulses_to_duration(tempo_track, pulses, pulses_per_quarter_note);
+                        lyric_events.push((time, String::from_utf8_lossy(text).into_owned()));
+                    }
+                    // Karaoke files keep the lyrics in text events, `@` ones are the file header,
+                    // and the first track holds the comments of other files
+                    TrackEventKind::Meta(MetaMessage::Text(text))
+                        if track_id > 0 && !text.starts_with(b"@") =>
+                    {
+                        let time = pulses_to_duration(tempo_track, pulses, pulses_per_quarter_note);
+                        text_events.push((time, String::from_utf8_lossy(text).into_owned()));
+                    }
+                    TrackEventKind::Meta(MetaMessage::TrackName(name)) => {
+                        track_names
+                            .entry(track_id)
+                            .or_insert_with(|| String::from_utf8_lossy(name).to_lowercase());
+                    }
+                    TrackEventKind::Meta(MetaMessage::KeySignature(sharps, minor)) => {
+                        if key_signature.is_none() {
+                            key_signature = Some(key_from_signature(sharps, minor));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        signatures.sort_by_key(|(pulses, _)| *pulses);
+        signatures.dedup_by_key(|(pulses, _)| *pulses);
+        markers.sort_by_key(|marker| marker.time);
+        repeats.sort_by_key(|repeat| repeat.time);
+
+        let lyrics = if lyric_events.is_empty() {
+            lyrics(text_events)
+        } else {
+            lyrics(lyric_events)
+        };
+
+        let measures = build_measures(
+            &signatures,
+            tempo_track,
+            pulses_per_quarter_note,
+            &merged_track.notes,
+        );
+
+        let sections = find_sections(&measures, &merged_track.notes);
+
+        Self {
+            tempo_map,
+            measures,
+            markers,
+            repeats,
+            lyrics,
+            sections,
+            key: key_signature.or_else(|| estimate_key(&merged_track.notes)),
+            hand_span: hand_span(&merged_track.notes),
+            melody_track: melody_track(&merged_track.notes, &track_names),
+        }
+    }
+
+    /// Index of the measure at given time
+    pub fn measure_at(&self, time: Duration) -> Option<usize> {
+        let id = self.measures.partition_point(|m| m.start <= time);
+        id.checked_sub(1)
+    }
+
+    /// Index of the last syllable sung at given time
+    pub fn lyric_at(&self, time: Duration) -> Option<usize> {
+        let id = self.lyrics.partition_point(|l| l.time <= time);
+        id.checked_sub(1)
+    }
+
+    /// Index of the section at given time
+    pub fn section_at(&self, time: Duration) -> Option<usize> {
+        let id = self.sections.partition_point(|s| s.start <= time);
+        id.checked_sub(1)
+    }
+
+    pub fn bpm_at(&self, time: Duration) -> f64 {
+        self.tempo_map
+            .iter()
+            .take_while(|t| t.time <= time)
+            .last()
+            .map(|t| t.bpm)
+            .unwrap_or(120.0)
+    }
+}
+
+fn build_measures(
+    signatures: &[(u64, TimeSignature)],
+    tempo_track: &TempoTrack,
+    pulses_per_quarter_note: u16,
+    notes: &[MidiNote],
+) -> Vec<Measure> {
+    let last_note_end = notes.iter().map(|n| n.end).max().unwrap_or_default();
+    let to_duration =
+        |pulses: u64| pulses_to_duration(tempo_track, pulses, pulses_per_quarter_note);
+
+    let mut measures = Vec::new();
+    let mut pulses = 0u64;
+    let mut signature = TimeSignature::default();
+    let mut next_signature = 0;
+
+    loop {
+        while let Some((at, sig)) = signatures.get(next_signature) {
+            if *at <= pulses {
+                signature = *sig;
+                next_signature += 1;
+            } else {
+                break;
+            }
+        }
+
+        let beat_len = pulses_per_quarter_note as u64 * 4 / signature.denominator as u64;
+        let beat_len = beat_len.max(1);
+        let measure_len = beat_len * signature.numerator as u64;
+
+        let start = to_duration(pulses);
+        if start >= last_note_end && !measures.is_empty() {
+            break;
+        }
+
+        let beats = (0..signature.numerator as u64)
+            .map(|beat| to_duration(pulses + beat * beat_len))
+            .collect();
+
+        pulses += measure_len;
+        let end = to_duration(pulses);
+
+        let first = notes.partition_point(|n| n.start < start);
+        let note_count = notes[first..].iter().take_while(|n| n.start < end).count();
+
+        measures.push(Measure {
+            start,
+            end,
+            signature,
+            beats,
+            note_count,
+        });
+    }
+
+    measures
+}
+
+/// Hash of the rhythm and pitches of the measure, drums left out
+fn measure_fingerprint(measure: &Measure, notes: &[MidiNote]) -> u64 {
+    let len = (measure.end - measure.start)
+        .as_secs_f32()
+        .max(f32::EPSILON);
+    let first = notes.partition_point(|n| n.start < measure.start);
+
+    // Positions snap to a 48th of the measure, so humanized timing still matches
+    let mut onsets: Vec<(u32, u8)> = notes[first..]
+        .iter()
+        .take_while(|n| n.start < measure.end)
+        .filter(|n| n.channel != 9)
+        .map(|n| {
+            let position = (n.start - measure.start).as_secs_f32() / len;
+            ((position * 48.0).round() as u32, n.note)
+        })
+        .collect();
+    onsets.sort_unstable();
+    onsets.dedup();
+
+    let mut hasher = DefaultHasher::new();
+    onsets.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Splits the song into phrases, gives repeated phrases the same letter
+/// and merges runs of one letter into a section
+fn find_sections(measures: &[Measure], notes: &[MidiNote]) -> Vec<Section> {
+    let fingerprints: Vec<u64> = measures
+        .iter()
+        .map(|measure| measure_fingerprint(measure, notes))
+        .collect();
+
+    let phrases: Vec<Range<usize>> = (0..measures.len())
+        .step_by(PHRASE_LEN)
+        .map(|start| start..(start + PHRASE_LEN).min(measures.len()))
+        .collect();
+
+    let similar = |a: &Range<usize>, b: &Range<usize>| {
+        let equal = a
+            .clone()
+            .zip(b.clone())
+            .filter(|(a, b)| fingerprints[*a] == fingerprints[*b])
+            .count();
+        equal as f32 >= a.len().max(b.len()) as f32 * PHRASE_SIMILARITY
+    };
+
+    // Phrase each letter first appeared at, and the letter of every phrase
+    let mut firsts: Vec<usize> = Vec::new();
+    let mut letters: Vec<usize> = Vec::with_capacity(phrases.len());
+    for (id, phrase) in phrases.iter().enumerate() {
+        let letter = match firsts
+            .iter()
+            .position(|first| similar(&phrases[*first], phrase))
+        {
+            Some(letter) => letter,
+            None => {
+                firsts.push(id);
+                firsts.len() - 1
+            }
+        };
+        letters.push(letter);
+    }
+
+    let count = |letter: usize| letters.iter().filter(|l| **l == letter).count();
+
+    // The most repeated section, on a tie the one that comes later as choruses follow verses
+    let chorus = (0..firsts.len())
+        .filter(|letter| count(*letter) > 1)
+        .max_by_key(|letter| (count(*letter), *letter))
+        .filter(|_| firsts.len() > 1);
+    // The repeated section leading into the first chorus
+    let verse = chorus
+        .and_then(|chorus| firsts[chorus].checked_sub(1))
+        .map(|phrase| letters[phrase])
+        .filter(|letter| Some(*letter) != chorus && count(*letter) > 1);
+
+    let mut sections: Vec<Section> = Vec::new();
+    let mut previous = None;
+    for (phrase, letter) in phrases.into_iter().zip(letters) {
+        let char = (b'A' + (letter % 26) as u8) as char;
+
+        match sections.last_mut() {
+            Some(last) if previous == Some(letter) => {
+                last.measures.end = phrase.end;
+                last.end = measures[phrase.end - 1].end;
+            }
+            _ => {
+                let name = if Some(letter) == chorus {
+                    String::from("Chorus")
+                } else if Some(letter) == verse {
+                    String::from("Verse")
+                } else {
+                    char.to_string()
+                };
+
+                sections.push(Section {
+                    start: measures[phrase.start].start,
+                    end: measures[phrase.end - 1].end,
+                    measures: phrase,
+                    letter: char,
+                    name,
+                });
+            }
+        }
+        previous = Some(letter);
+    }
+
+    sections
+}
+
+fn key_from_signature(sharps: i8, minor: bool) -> Key {
+    // Every sharp moves the major tonic by a fifth
+    let major = (sharps as i32 * 7).rem_euclid(12) as u8;
+    if minor {
+        Key {
+            tonic: (major + 9) % 12,
+            minor: true,
+        }
+    } else {
+        Key {
+            tonic: major,
+            minor: false,
+        }
+    }
+}
+
+/// Krumhansl-Schmuckler key finding, weighted by note duration
+fn estimate_key(notes: &[MidiNote]) -> Option<Key> {
+    const MAJOR: [f32; 12] = [
+        6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+    ];
+    const MINOR: [f32; 12] = [
+        6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+    ];
+
+    let mut histogram = [0.0f32; 12];
+    for note in notes.iter().filter(|n| n.channel != 9) {
+        histogram[note.note as usize % 12] += note.duration.as_secs_f32();
+    }
+
+    if histogram.iter().all(|v| *v == 0.0) {
+        return None;
+    }
+
+    let correlation = |profile: &[f32; 12], tonic: usize| -> f32 {
+        let mean_h = histogram.iter().sum::<f32>() / 12.0;
+        let mean_p = profile.iter().sum::<f32>() / 12.0;
+
+        let (mut num, mut den_h, mut den_p) = (0.0, 0.0, 0.0);
+        for i in 0..12 {
+            let h = histogram[(i + tonic) % 12] - mean_h;
+            let p = profile[i] - mean_p;
+            num += h * p;
+            den_h += h * h;
+            den_p += p * p;
+        }
+
+        num / (den_h * den_p).sqrt().max(f32::EPSILON)
+    };
+
+    (0..12)
+        .flat_map(|tonic| {
+            [
+                (correlation(&MAJOR, tonic), tonic, false),
+                (correlation(&MINOR, tonic), tonic, true),
+            ]
+        })
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, tonic, minor)| Key {
+            tonic: tonic as u8,
+            minor,
+        })
+}
+
+/// Syllables of `events`, in the order they are sung. Karaoke files start a line with `/`
+/// and a paragraph with `\\`, lyric events end one with a line break instead
+fn lyrics(mut events: Vec<(Duration, String)>) -> Vec<Lyric> {
+    // Stable, syllables at the same time keep their order
+    events.sort_by_key(|(time, _)| *time);
+
+    let mut lyrics = Vec::new();
+    let mut new_line = true;
+    for (time, text) in events {
+        let mut text = text.as_str();
+        if let Some(rest) = text.strip_prefix(['/', '\\']) {
+            new_line = true;
+            text = rest;
+        }
+
+        let ends_line = text.ends_with(['\r', '\n']);
+        let text = text.trim_end_matches(['\r', '\n']);
+        if !text.is_empty() {
+            lyrics.push(Lyric {
+                time,
+                text: text.to_string(),
+                new_line,
+            });
+            new_line = false;
+        }
+
+        if ends_line {
+            new_line = true;
+        }
+    }
+    lyrics
+}
+
+fn hand_span(notes: &[MidiNote]) -> HandSpan {
+    let notes: Vec<&MidiNote> = notes.iter().filter(|n| n.channel != 9).collect();
+
+    let (lowest_note, highest_note) = notes.iter().fold((u8::MAX, u8::MIN), |(lo, hi), n| {
+        (lo.min(n.note), hi.max(n.note))
+    });
+
+    if notes.is_empty() {
+        return HandSpan::default();
+    }
+
+    let mut max = 0;
+    let mut total = 0u64;
+    let mut chords = 0u64;
+
+    let mut id = 0;
+    while id < notes.len() {
+        let start = notes[id].start;
+        let chord: Vec<u8> = notes[id..]
+            .iter()
+            .take_while(|n| n.start <= start + CHORD_WINDOW)
+            .map(|n| n.note)
+            .collect();
+        id += chord.len();
+
+        if chord.len() > 1 {
+            let span = chord.iter().max().unwrap() - chord.iter().min().unwrap();
+            max = max.max(span);
+            total += span as u64;
+            chords += 1;
+        }
+    }
+
+    HandSpan {
+        max,
+        average: if chords > 0 {
+            total as f32 / chords as f32
+        } else {
+            0.0
+        },
+        lowest_note,
+        highest_note,
+    }
+}
+
+/// A track named after the melody, or else the highest and least chordal one
+fn melody_track(notes: &[MidiNote], names: &HashMap<usize, String>) -> Option<usize> {
+    #[derive(Default)]
+    struct Voice {
+        keys: u64,
+        notes: u64,
+        /// Notes starting with the one before them
+        chord_notes: u64,
+        last_start: Option<Duration>,
+    }
+
+    let mut voices: HashMap<usize, Voice> = HashMap::new();
+    for note in notes.iter().filter(|n| n.channel != 9) {
+        let voice = voices.entry(note.track_id).or_default();
+        voice.keys += note.note as u64;
+        voice.notes += 1;
+        if voice
+            .last_start
+            .map_or(false, |start| note.start <= start + CHORD_WINDOW)
+        {
+            voice.chord_notes += 1;
+        }
+        voice.last_start = Some(note.start);
+    }
+
+    if voices.len() < 2 {
+        return None;
+    }
+
+    let mut ids: Vec<usize> = voices.keys().copied().collect();
+    ids.sort();
+
+    let named = ids.iter().find(|id| {
+        names
+            .get(id)
+            .map_or(false, |name| MELODY_NAMES.iter().any(|n| name.contains(n)))
+    });
+    if let Some(id) = named {
+        return Some(*id);
+    }
+
+    // Being mostly single notes counts for two octaves of pitch
+    let score = |voice: &Voice| {
+        let pitch = voice.keys as f32 / voice.notes as f32;
+        let single = 1.0 - voice.chord_notes as f32 / voice.notes as f32;
+        pitch + single * 24.0
+    };
+    ids.into_iter()
+        .map(|id| (id, score(&voices[&id])))
+        .fold(None, |best: Option<(usize, f32)>, (id, score)| match best {
+            Some((_, best_score)) if best_score >= score => best,
+            _ => Some((id, score)),
+        })
+        .map(|(id, _)| id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One second measures of 4/4, with the notes of `melodies[id]` in measure `id`
+    fn song(melodies: &[&[u8]]) -> (Vec<Measure>, Vec<MidiNote>) {
+        let second = |s: f32| Duration::from_secs_f32(s);
+
+        let measures = (0..melodies.len())
+            .map(|id| Measure {
+                start: second(id as f32),
+                end: second(id as f32 + 1.0),
+                signature: TimeSignature::default(),
+                beats: (0..4).map(|b| second(id as f32 + b as f32 / 4.0)).collect(),
+                note_count: melodies[id].len(),
+            })
+            .collect();
+
+        let mut notes = Vec::new();
+        for (measure, melody) in melodies.iter().enumerate() {
+            for (beat, note) in melody.iter().enumerate() {
+                let start = second(measure as f32 + beat as f32 / 4.0);
+                notes.push(MidiNote {
+                    start,
+                    end: start + second(0.25),
+                    duration: second(0.25),
+                    note: *note,
+                    velocity: 100,
+                    channel: 0,
+                    track_id: 0,
+                    track_color_id: 0,
+                    id: notes.len(),
+                });
+            }
+        }
+
+        (measures, notes)
+    }
+
+    #[test]
+    fn sections_of_verses_and_choruses() {
+        let verse: &[u8] = &[60, 62, 64, 65];
+        let chorus: &[u8] = &[67, 67, 69, 67];
+        let bridge: &[u8] = &[72, 71, 69, 67];
+
+        let mut melodies = Vec::new();
+        for phrase in [verse, chorus, verse, chorus, chorus, bridge] {
+            melodies.extend([phrase; PHRASE_LEN]);
+        }
+        let (measures, notes) = song(&melodies);
+
+        let sections = find_sections(&measures, &notes);
+        let names: Vec<&str> = sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Verse", "Chorus", "Verse", "Chorus", "C"]);
+
+        // Repeated choruses are one section
+        assert_eq!(sections[3].measures, 12..20);
+        assert!(sections.windows(2).all(|s| s[0].end == s[1].start));
+        assert_eq!(sections.last().unwrap().end, measures.last().unwrap().end);
+    }
+
+    #[test]
+    fn sections_without_repetitions() {
+        let melodies: Vec<Vec<u8>> = (0..8u8).map(|id| vec![60 + id]).collect();
+        let melodies: Vec<&[u8]> = melodies.iter().map(|m| m.as_slice()).collect();
+        let (measures, notes) = song(&melodies);
+
+        let sections = find_sections(&measures, &notes);
+        let names: Vec<&str> = sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["A", "B"]);
+    }
+
+    #[test]
+    fn melody_is_the_single_high_notes() {
+        let note = |track_id, note, start: u64| {
+            let start = Duration::from_millis(start);
+            MidiNote {
+                start,
+                end: start + Duration::from_millis(400),
+                duration: Duration::from_millis(400),
+                note,
+                velocity: 100,
+                channel: track_id as u8,
+                track_id,
+                track_color_id: track_id,
+                id: 0,
+            }
+        };
+
+        let mut notes = Vec::new();
+        for beat in 0..8 {
+            let start = beat * 500;
+            // Accompaniment chords on the beat, and a melody above them
+            notes.extend([48, 52, 55].map(|key| note(1, key, start)));
+            notes.extend([72, 74].map(|key| note(2, key, start)));
+        }
+        // Melody notes follow each other
+        for note in notes.iter_mut().filter(|n| n.note == 74) {
+            note.start += Duration::from_millis(250);
+        }
+        notes.sort_by_key(|n| n.start);
+
+        assert_eq!(melody_track(&notes, &HashMap::new()), Some(2));
+
+        let names = HashMap::from([(1, String::from("lead vocals"))]);
+        assert_eq!(melody_track(&notes, &names), Some(1));
+
+        notes.retain(|n| n.track_id == 1);
+        assert_eq!(melody_track(&notes, &HashMap::new()), None);
+    }
+
+    #[test]
+    fn lyrics_are_split_into_lines() {
+        let second = |s: u64| Duration::from_secs(s);
+        let events = vec![
+            (second(1), "Twin".to_string()),
+            (second(2), "kle ".to_string()),
+            (second(3), "/Lit".to_string()),
+            (second(4), "tle\r".to_string()),
+            (second(5), "star".to_string()),
+        ];
+
+        let lyrics = lyrics(events);
+        let lines: Vec<(&str, bool)> = lyrics
+            .iter()
+            .map(|l| (l.text.as_str(), l.new_line))
+            .collect();
+
+        assert_eq!(
+            lines,
+            [
+                ("Twin", true),
+                ("kle ", false),
+                ("Lit", true),
+                ("tle", false),
+                ("star", true),
+            ]
+        );
+    }
+}
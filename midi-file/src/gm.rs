@@ -0,0 +1,271 @@
+//! General MIDI instrument tables
+
+/// GM System On
+pub const GM_RESET: [u8; 6] = [0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7];
+/// Roland GS Reset
+pub const GS_RESET: [u8; 11] = [
+    0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7,
+];
+/// Yamaha XG System On
+pub const XG_RESET: [u8; 9] = [0xF0, 0x43, 0x10, 0x4C, 0x00, 0x00, 0x7E, 0x00, 0xF7];
+
+pub const PROGRAM_NAMES: [&str; 128] = [
+    "Acoustic Grand Piano",
+    "Bright Acoustic Piano",
+    "Electric Grand Piano",
+    "Honky-tonk Piano",
+    "Electric Piano 1",
+    "Electric Piano 2",
+    "Harpsichord",
+    "Clavinet",
+    "Celesta",
+    "Glockenspiel",
+    "Music Box",
+    "Vibraphone",
+    "Marimba",
+    "Xylophone",
+    "Tubular Bells",
+    "Dulcimer",
+    "Drawbar Organ",
+    "Percussive Organ",
+    "Rock Organ",
+    "Church Organ",
+    "Reed Organ",
+    "Accordion",
+    "Harmonica",
+    "Tango Accordion",
+    "Acoustic Guitar (nylon)",
+    "Acoustic Guitar (steel)",
+    "Electric Guitar (jazz)",
+    "Electric Guitar (clean)",
+    "Electric Guitar (muted)",
+    "Overdriven Guitar",
+    "Distortion Guitar",
+    "Guitar Harmonics",
+    "Acoustic Bass",
+    "Electric Bass (finger)",
+    "Electric Bass (pick)",
+    "Fretless Bass",
+    "Slap Bass 1",
+    "Slap Bass 2",
+    "Synth Bass 1",
+    "Synth Bass 2",
+    "Violin",
+    "Viola",
+    "Cello",
+    "Contrabass",
+    "Tremolo Strings",
+    "Pizzicato Strings",
+    "Orchestral Harp",
+    "Timpani",
+    "String Ensemble 1",
+    "String Ensemble 2",
+    "Synth Strings 1",
+    "Synth Strings 2",
+    "Choir Aahs",
+    "Voice Oohs",
+    "Synth Voice",
+    "Orchestra Hit",
+    "Trumpet",
+    "Trombone",
+    "Tuba",
+    "Muted Trumpet",
+    "French Horn",
+    "Brass Section",
+    "Synth Brass 1",
+    "Synth Brass 2",
+    "Soprano Sax",
+    "Alto Sax",
+    "Tenor Sax",
+    "Baritone Sax",
+    "Oboe",
+    "English Horn",
+    "Bassoon",
+    "Clarinet",
+    "Piccolo",
+    "Flute",
+    "Recorder",
+    "Pan Flute",
+    "Blown Bottle",
+    "Shakuhachi",
+    "Whistle",
+    "Ocarina",
+    "Lead 1 (square)",
+    "Lead 2 (sawtooth)",
+    "Lead 3 (calliope)",
+    "Lead 4 (chiff)",
+    "Lead 5 (charang)",
+    "Lead 6 (voice)",
+    "Lead 7 (fifths)",
+    "Lead 8 (bass + lead)",
+    "Pad 1 (new age)",
+    "Pad 2 (warm)",
+    "Pad 3 (polysynth)",
+    "Pad 4 (choir)",
+    "Pad 5 (bowed)",
+    "Pad 6 (metallic)",
+    "Pad 7 (halo)",
+    "Pad 8 (sweep)",
+    "FX 1 (rain)",
+    "FX 2 (soundtrack)",
+    "FX 3 (crystal)",
+    "FX 4 (atmosphere)",
+    "FX 5 (brightness)",
+    "FX 6 (goblins)",
+    "FX 7 (echoes)",
+    "FX 8 (sci-fi)",
+    "Sitar",
+    "Banjo",
+    "Shamisen",
+    "Koto",
+    "Kalimba",
+    "Bagpipe",
+    "Fiddle",
+    "Shanai",
+    "Tinkle Bell",
+    "Agogo",
+    "Steel Drums",
+    "Woodblock",
+    "Taiko Drum",
+    "Melodic Tom",
+    "Synth Drum",
+    "Reverse Cymbal",
+    "Guitar Fret Noise",
+    "Breath Noise",
+    "Seashore",
+    "Bird Tweet",
+    "Telephone Ring",
+    "Helicopter",
+    "Applause",
+    "Gunshot",
+];
+
+pub fn program_name(program: u8) -> &'static str {
+    PROGRAM_NAMES[program as usize % PROGRAM_NAMES.len()]
+}
+
+/// First note of the GM percussion key map, on channel 10
+pub const DRUM_NOTES_START: u8 = 35;
+
+pub const DRUM_NAMES: [&str; 47] = [
+    "Acoustic Bass Drum",
+    "Bass Drum 1",
+    "Side Stick",
+    "Acoustic Snare",
+    "Hand Clap",
+    "Electric Snare",
+    "Low Floor Tom",
+    "Closed Hi-Hat",
+    "High Floor Tom",
+    "Pedal Hi-Hat",
+    "Low Tom",
+    "Open Hi-Hat",
+    "Low-Mid Tom",
+    "Hi-Mid Tom",
+    "Crash Cymbal 1",
+    "High Tom",
+    "Ride Cymbal 1",
+    "Chinese Cymbal",
+    "Ride Bell",
+    "Tambourine",
+    "Splash Cymbal",
+    "Cowbell",
+    "Crash Cymbal 2",
+    "Vibraslap",
+    "Ride Cymbal 2",
+    "Hi Bongo",
+    "Low Bongo",
+    "Mute Hi Conga",
+    "Open Hi Conga",
+    "Low Conga",
+    "High Timbale",
+    "Low Timbale",
+    "High Agogo",
+    "Low Agogo",
+    "Cabasa",
+    "Maracas",
+    "Short Whistle",
+    "Long Whistle",
+    "Short Guiro",
+    "Long Guiro",
+    "Claves",
+    "Hi Wood Block",
+    "Low Wood Block",
+    "Mute Cuica",
+    "Open Cuica",
+    "Mute Triangle",
+    "Open Triangle",
+];
+
+/// Name of a percussion note, `None` outside of the GM key map
+pub fn drum_name(note: u8) -> Option<&'static str> {
+    let id = note.checked_sub(DRUM_NOTES_START)?;
+    DRUM_NAMES.get(id as usize).copied()
+}
+
+/// Drums that share a row in the percussion lane
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DrumGroup {
+    Kick,
+    Snare,
+    HiHat,
+    Tom,
+    Cymbal,
+    Percussion,
+}
+
+impl DrumGroup {
+    pub const ALL: [DrumGroup; 6] = [
+        Self::Kick,
+        Self::Snare,
+        Self::HiHat,
+        Self::Tom,
+        Self::Cymbal,
+        Self::Percussion,
+    ];
+
+    pub fn of(note: u8) -> Self {
+        match note {
+            35 | 36 => Self::Kick,
+            37 | 38 | 39 | 40 => Self::Snare,
+            42 | 44 | 46 => Self::HiHat,
+            41 | 43 | 45 | 47 | 48 | 50 => Self::Tom,
+            49 | 51 | 52 | 53 | 55 | 57 | 59 => Self::Cymbal,
+            _ => Self::Percussion,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Kick => "Kick",
+            Self::Snare => "Snare",
+            Self::HiHat => "Hi-Hat",
+            Self::Tom => "Toms",
+            Self::Cymbal => "Cymbals",
+            Self::Percussion => "Percussion",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drum_names() {
+        assert_eq!(drum_name(34), None);
+        assert_eq!(drum_name(35), Some("Acoustic Bass Drum"));
+        assert_eq!(drum_name(42), Some("Closed Hi-Hat"));
+        assert_eq!(drum_name(81), Some("Open Triangle"));
+        assert_eq!(drum_name(82), None);
+    }
+
+    #[test]
+    fn drum_groups() {
+        assert_eq!(DrumGroup::of(36), DrumGroup::Kick);
+        assert_eq!(DrumGroup::of(38), DrumGroup::Snare);
+        assert_eq!(DrumGroup::of(46), DrumGroup::HiHat);
+        assert_eq!(DrumGroup::of(49), DrumGroup::Cymbal);
+        assert_eq!(DrumGroup::of(70), DrumGroup::Percussion);
+    }
+}
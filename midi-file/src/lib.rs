@@ -1,9 +1,20 @@
+pub mod analysis;
+pub mod gm;
+mod metadata;
 mod midi;
+mod musicxml;
 pub mod playback;
+mod recording;
+#[cfg(feature = "stress")]
+pub mod stress;
+pub mod sustain;
 mod track;
 mod utils;
 
+pub use analysis::SongAnalysis;
+pub use metadata::Metadata;
 pub use midly;
+pub use recording::Recording;
 pub use {midi::*, playback::*, track::*, utils::*};
 
 #[cfg(test)]
@@ -18,4 +29,102 @@ mod tests {
             // println!("{id}: {}", note.start.as_micros(),);
         }
     }
+
+    #[test]
+    fn analysis() {
+        let midi = Midi::new("../test.mid").unwrap();
+        let analysis = &midi.analysis;
+
+        assert!(!analysis.measures.is_empty());
+        assert_eq!(analysis.measures[0].start, std::time::Duration::ZERO);
+        assert!(analysis.measures.windows(2).all(|m| m[0].end == m[1].start));
+
+        let notes: usize = analysis.measures.iter().map(|m| m.note_count).sum();
+        assert_eq!(notes, midi.merged_track.notes.len());
+
+        let sections = &analysis.sections;
+        assert_eq!(sections.first().map(|s| s.measures.start), Some(0));
+        assert_eq!(
+            sections.last().map(|s| s.measures.end),
+            Some(analysis.measures.len())
+        );
+        assert!(sections
+            .windows(2)
+            .all(|s| s[0].measures.end == s[1].measures.start));
+    }
+
+    #[test]
+    fn merge_tracks() {
+        let mut midi = Midi::new("../test.mid").unwrap();
+        let tracks: Vec<usize> = midi
+            .tracks
+            .iter()
+            .filter(|t| !t.notes.is_empty())
+            .map(|t| t.track_id)
+            .collect();
+        let notes = midi.merged_track.notes.len();
+
+        midi.merge_tracks(&tracks);
+
+        let target = &midi.tracks[tracks[0]];
+        assert_eq!(target.notes.len(), notes);
+        assert!(target.notes.windows(2).all(|n| n[0].start <= n[1].start));
+        assert!(midi
+            .merged_track
+            .notes
+            .iter()
+            .all(|n| n.track_id == target.track_id && n.track_color_id == target.track_color_id));
+        assert!(tracks[1..]
+            .iter()
+            .all(|id| midi.tracks[*id].notes.is_empty()));
+        assert!(midi.similar_tracks().is_empty());
+    }
+
+    #[test]
+    fn split_track() {
+        let mut midi = Midi::new("../test.mid").unwrap();
+        let track = midi
+            .tracks
+            .iter()
+            .find(|t| t.notes.iter().any(|n| n.note < 60) && t.notes.iter().any(|n| n.note >= 60))
+            .unwrap();
+        let (track_id, notes) = (track.track_id, track.notes.len());
+
+        let split = midi.split_track(track_id, 60).unwrap();
+
+        let (high, low) = (&midi.tracks[track_id], &midi.tracks[split]);
+        assert_eq!(high.notes.len() + low.notes.len(), notes);
+        assert!(high.notes.iter().all(|n| n.note >= 60));
+        assert!(low
+            .notes
+            .iter()
+            .all(|n| n.note < 60 && n.track_id == split && n.track_color_id == low.track_color_id));
+        assert_eq!(
+            midi.merged_track
+                .notes
+                .iter()
+                .filter(|n| n.track_id == split)
+                .count(),
+            low.notes.len()
+        );
+        assert_eq!(midi.split_track(track_id, 0), None);
+    }
+
+    #[test]
+    fn notes_at() {
+        let midi = Midi::new("../test.mid").unwrap();
+        let track = &midi.merged_track;
+
+        let note = track
+            .notes
+            .iter()
+            .skip(track.notes.len() / 2)
+            .find(|n| !n.duration.is_zero())
+            .unwrap();
+        let time = note.start + note.duration / 2;
+
+        let sounding: Vec<_> = track.notes_at(time).collect();
+        assert!(sounding.iter().any(|n| n.id == note.id));
+        assert!(sounding.iter().all(|n| n.start <= time && n.end > time));
+    }
 }
@@ -0,0 +1,238 @@
+//! Song information stored in the meta events of the first track
+
+use midly::{num::u28, MetaMessage, Smf, TrackEvent, TrackEventKind};
+
+/// There is no composer meta event, so it's stored as a text event with this prefix
+pub(crate) const COMPOSER_PREFIX: &str = "Composer: ";
+/// Largest 28 bit delta time
+const MAX_DELTA: u32 = (1 << 28) - 1;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub composer: Option<String>,
+    pub copyright: Option<String>,
+}
+
+impl Metadata {
+    pub(crate) fn from_track(track: &[TrackEvent]) -> Self {
+        let mut metadata = Self::default();
+
+        for event in track.iter() {
+            let meta = match event.kind {
+                TrackEventKind::Meta(meta) => meta,
+                _ => continue,
+            };
+
+            match meta {
+                MetaMessage::TrackName(name) if metadata.title.is_none() => {
+                    metadata.title = text(name);
+                }
+                MetaMessage::Copyright(copyright) if metadata.copyright.is_none() => {
+                    metadata.copyright = text(copyright);
+                }
+                MetaMessage::Text(data) if metadata.composer.is_none() => {
+                    metadata.composer = String::from_utf8_lossy(data)
+                        .strip_prefix(COMPOSER_PREFIX)
+                        .and_then(|composer| text(composer.as_bytes()));
+                }
+                _ => {}
+            }
+        }
+
+        metadata
+    }
+
+    /// Returns the file in `data` with its metadata replaced by `self`
+    pub fn write(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let composer = self
+            .composer
+            .as_ref()
+            .map(|composer| format!("{COMPOSER_PREFIX}{composer}"));
+
+        let mut smf = match Smf::parse(data) {
+            Ok(smf) => smf,
+            Err(_) => return Err(String::from("Midi Parsing Error (midly lib)")),
+        };
+
+        let track = match smf.tracks.first_mut() {
+            Some(track) => track,
+            None => return Err(String::from("Midi File Has No Tracks")),
+        };
+
+        let meta = |meta| TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(meta),
+        };
+
+        let mut events = Vec::with_capacity(track.len() + 3);
+        if let Some(title) = self.title.as_ref() {
+            events.push(meta(MetaMessage::TrackName(title.as_bytes())));
+        }
+        if let Some(composer) = composer.as_ref() {
+            events.push(meta(MetaMessage::Text(composer.as_bytes())));
+        }
+        if let Some(copyright) = self.copyright.as_ref() {
+            events.push(meta(MetaMessage::Copyright(copyright.as_bytes())));
+        }
+
+        // Time of the removed events is handed to the next one, so nothing moves
+        let mut carry = 0;
+        for event in track.drain(..) {
+            if is_metadata(&event.kind) {
+                carry += event.delta.as_int();
+                continue;
+            }
+
+            let delta = (event.delta.as_int() + carry).min(MAX_DELTA);
+            carry = 0;
+
+            events.push(TrackEvent {
+                delta: u28::new(delta),
+                ..event
+            });
+        }
+        *track = events;
+
+        let mut out = Vec::with_capacity(data.len());
+        match smf.write_std(&mut out) {
+            Ok(()) => Ok(out),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+}
+
+fn is_metadata(kind: &TrackEventKind) -> bool {
+    match kind {
+        TrackEventKind::Meta(MetaMessage::TrackName(_) | MetaMessage::Copyright(_)) => true,
+        TrackEventKind::Meta(MetaMessage::Text(data)) => {
+            data.starts_with(COMPOSER_PREFIX.as_bytes())
+        }
+        _ => false,
+    }
+}
+
+fn text(data: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(data).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midly::{
+        num::{u15, u4, u7},
+        Format, Header, MidiMessage, Timing,
+    };
+
+    fn song(first_track: Vec<TrackEvent>) -> Vec<u8> {
+        let smf = Smf {
+            header: Header::new(Format::Parallel, Timing::Metrical(u15::new(480))),
+            tracks: vec![first_track],
+        };
+
+        let mut data = Vec::new();
+        smf.write_std(&mut data).unwrap();
+        data
+    }
+
+    fn event(delta: u32, kind: TrackEventKind) -> TrackEvent {
+        TrackEvent {
+            delta: u28::new(delta),
+            kind,
+        }
+    }
+
+    fn note_on(delta: u32) -> TrackEvent<'static> {
+        event(
+            delta,
+            TrackEventKind::Midi {
+                channel: u4::new(0),
+                message: MidiMessage::NoteOn {
+                    key: u7::new(60),
+                    vel: u7::new(100),
+                },
+            },
+        )
+    }
+
+    fn read(data: &[u8]) -> (Metadata, Vec<u32>) {
+        let smf = Smf::parse(data).unwrap();
+        let track = &smf.tracks[0];
+
+        // Absolute time of every event that's not metadata
+        let mut time = 0;
+        let mut times = Vec::new();
+        for event in track {
+            time += event.delta.as_int();
+            if !is_metadata(&event.kind) {
+                times.push(time);
+            }
+        }
+
+        (Metadata::from_track(track), times)
+    }
+
+    #[test]
+    fn reads_metadata() {
+        let data = song(vec![
+            event(0, TrackEventKind::Meta(MetaMessage::TrackName(b"Nocturne"))),
+            event(
+                0,
+                TrackEventKind::Meta(MetaMessage::Text(b"Composer: Chopin")),
+            ),
+            event(
+                0,
+                TrackEventKind::Meta(MetaMessage::Text(b"Just a comment")),
+            ),
+            note_on(0),
+            event(0, TrackEventKind::Meta(MetaMessage::EndOfTrack)),
+        ]);
+
+        let (metadata, _) = read(&data);
+        assert_eq!(
+            metadata,
+            Metadata {
+                title: Some("Nocturne".into()),
+                composer: Some("Chopin".into()),
+                copyright: None,
+            }
+        );
+    }
+
+    #[test]
+    fn write_replaces_metadata() {
+        let data = song(vec![
+            event(0, TrackEventKind::Meta(MetaMessage::TrackName(b"Old"))),
+            note_on(10),
+            event(5, TrackEventKind::Meta(MetaMessage::Copyright(b"Old"))),
+            note_on(5),
+            event(20, TrackEventKind::Meta(MetaMessage::EndOfTrack)),
+        ]);
+        let (_, times) = read(&data);
+
+        let metadata = Metadata {
+            title: Some("New".into()),
+            composer: Some("Someone".into()),
+            copyright: Some("Public domain".into()),
+        };
+        let written = metadata.write(&data).unwrap();
+
+        let (read_back, written_times) = read(&written);
+        assert_eq!(read_back, metadata);
+        assert_eq!(written_times, times);
+    }
+
+    #[test]
+    fn write_removes_cleared_fields() {
+        let data = song(vec![
+            event(0, TrackEventKind::Meta(MetaMessage::TrackName(b"Old"))),
+            note_on(0),
+            event(0, TrackEventKind::Meta(MetaMessage::EndOfTrack)),
+        ]);
+
+        let written = Metadata::default().write(&data).unwrap();
+        let (read_back, _) = read(&written);
+        assert_eq!(read_back, Metadata::default());
+    }
+}
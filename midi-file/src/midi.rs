@@ -1,26 +1,254 @@
-use crate::{utils, MidiTrack};
-use midly::{Format, Smf, Timing};
-use std::{fs, path::Path};
+use crate::{musicxml, utils, Metadata, MidiTrack, SongAnalysis};
+use midly::{Format, MidiMessage, Smf, Timing};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Extensions of the files [`Midi::new`] can load
+pub const SONG_EXTENSIONS: &[&str] = &["mid", "midi", "musicxml", "xml", "mxl"];
+
+/// Scores are converted while loading, see [`Midi::from_musicxml`]
+fn is_musicxml(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| {
+        ["musicxml", "xml", "mxl"]
+            .iter()
+            .any(|musicxml| ext.eq_ignore_ascii_case(musicxml))
+    })
+}
 
 #[derive(Debug, Clone)]
 pub struct Midi {
+    /// File name without the extension, empty when parsed from memory
+    pub name: String,
+    /// `None` when parsed from memory
+    pub path: Option<PathBuf>,
     pub format: Format,
     pub tracks: Vec<MidiTrack>,
     pub merged_track: MidiTrack,
+    pub analysis: SongAnalysis,
+    pub metadata: Metadata,
 }
 
 impl Midi {
+    /// Repeats of scores are followed, see [`Midi::with_repeats`]
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        Self::with_repeats(path, true)
+    }
+
+    /// `expand_repeats` follows the repeats and jumps of scores, otherwise every measure is
+    /// played once as written. MIDI files have theirs written out already
+    pub fn with_repeats<P: AsRef<Path>>(path: P, expand_repeats: bool) -> Result<Self, String> {
+        let path = path.as_ref();
+
+        let mut midi = if is_musicxml(path) {
+            let data = match fs::read(path) {
+                Ok(data) => data,
+                Err(_) => return Err(String::from("Could Not Open File")),
+            };
+            Self::from_musicxml(&data, expand_repeats)?
+        } else {
+            Self::from_midi_file(path)?
+        };
+        midi.name = path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        midi.path = Some(path.to_owned());
+
+        Ok(midi)
+    }
+
+    fn from_midi_file(path: &Path) -> Result<Self, String> {
         let data = match fs::read(path) {
-            Ok(buff) => buff,
+            Ok(data) => data,
             Err(_) => return Err(String::from("Could Not Open File")),
         };
 
-        let smf = match Smf::parse(&data) {
+        Self::from_bytes(&data)
+    }
+
+    /// Writes `metadata` into the file the song was loaded from
+    pub fn save_metadata(&mut self, metadata: Metadata) -> Result<(), String> {
+        let path = match self.path.as_ref() {
+            Some(path) => path,
+            None => return Err(String::from("Midi Has No File")),
+        };
+        if is_musicxml(path) {
+            return Err(String::from("MusicXML Metadata Can Not Be Saved"));
+        }
+
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(_) => return Err(String::from("Could Not Open File")),
+        };
+        let data = metadata.write(&data)?;
+
+        // Written next to the file first, so a failed write can't leave it truncated
+        let tmp = path.with_extension("mid.tmp");
+        if fs::write(&tmp, data)
+            .and_then(|_| fs::rename(&tmp, path))
+            .is_err()
+        {
+            fs::remove_file(&tmp).ok();
+            return Err(String::from("Could Not Write File"));
+        }
+
+        self.metadata = metadata;
+        Ok(())
+    }
+
+    /// Tracks that look like one instrument split up, the tracks of a group have all of their
+    /// notes on the same channel. Only groups of more than one track are returned
+    pub fn similar_tracks(&self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<(u8, Vec<usize>)> = Vec::new();
+        for track in self.tracks.iter().filter(|t| !t.notes.is_empty()) {
+            let channel = track.notes[0].channel;
+            if track.notes.iter().any(|n| n.channel != channel) {
+                continue;
+            }
+
+            match groups.iter_mut().find(|(c, _)| *c == channel) {
+                Some((_, ids)) => ids.push(track.track_id),
+                None => groups.push((channel, vec![track.track_id])),
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|(_, ids)| ids)
+            .filter(|ids| ids.len() > 1)
+            .collect()
+    }
+
+    /// Moves the notes and events of `tracks` into the first of them, which lends them its
+    /// id and color. The others are left empty, so the ids of every track stay the same
+    pub fn merge_tracks(&mut self, tracks: &[usize]) {
+        let (target, color) = match tracks
+            .first()
+            .and_then(|id| self.tracks.iter().find(|t| t.track_id == *id))
+        {
+            Some(track) => (track.track_id, track.track_color_id),
+            None => return,
+        };
+        let merged = |track_id: usize| tracks.contains(&track_id);
+
+        let mut notes = Vec::new();
+        let mut events = Vec::new();
+        let mut sysex_events = Vec::new();
+        for track in self
+            .tracks
+            .iter_mut()
+            .filter(|t| t.track_id != target && merged(t.track_id))
+        {
+            notes.append(&mut track.notes);
+            events.append(&mut track.events);
+            sysex_events.append(&mut track.sysex_events);
+        }
+
+        if let Some(track) = self.tracks.iter_mut().find(|t| t.track_id == target) {
+            track.notes.append(&mut notes);
+            track.events.append(&mut events);
+            track.sysex_events.append(&mut sysex_events);
+
+            track.notes.sort_by_key(|n| n.start);
+            track.events.sort_by_key(|e| e.timestamp);
+            track.sysex_events.sort_by_key(|e| e.timestamp);
+        }
+
+        for track in self
+            .tracks
+            .iter_mut()
+            .filter(|t| t.track_id == target)
+            .chain(std::iter::once(&mut self.merged_track))
+        {
+            for note in track.notes.iter_mut().filter(|n| merged(n.track_id)) {
+                note.track_id = target;
+                note.track_color_id = color;
+            }
+            for event in track.events.iter_mut().filter(|e| merged(e.track_id)) {
+                event.track_id = target;
+                event.track_color_id = color;
+            }
+            for event in track.sysex_events.iter_mut().filter(|e| merged(e.track_id)) {
+                event.track_id = target;
+            }
+        }
+
+        if self.analysis.melody_track.map_or(false, merged) {
+            self.analysis.melody_track = Some(target);
+        }
+    }
+
+    /// Moves the notes of `track_id` below `pitch` into a new track at the end, with a color
+    /// of its own. Controllers and program changes stay in the split track. Returns the id of
+    /// the new track, `None` when one of the two would have no notes
+    pub fn split_track(&mut self, track_id: usize, pitch: u8) -> Option<usize> {
+        let new_id = self.tracks.len();
+        let new_color = self.tracks.iter().map(|t| t.track_color_id + 1).max()?;
+        let low = |key: u8| key < pitch;
+
+        let track = self.tracks.iter_mut().find(|t| t.track_id == track_id)?;
+        if !track.notes.iter().any(|n| low(n.note)) || track.notes.iter().all(|n| low(n.note)) {
+            return None;
+        }
+
+        let (notes, kept) = std::mem::take(&mut track.notes)
+            .into_iter()
+            .partition(|n| low(n.note));
+        track.notes = kept;
+        let (events, kept) = std::mem::take(&mut track.events)
+            .into_iter()
+            .partition(|e| event_key(&e.message).map_or(false, low));
+        track.events = kept;
+
+        let mut split = MidiTrack {
+            notes,
+            events,
+            sysex_events: Vec::new(),
+            track_id: new_id,
+            track_color_id: new_color,
+        };
+
+        for track in [&mut split, &mut self.merged_track] {
+            for note in track
+                .notes
+                .iter_mut()
+                .filter(|n| n.track_id == track_id && low(n.note))
+            {
+                note.track_id = new_id;
+                note.track_color_id = new_color;
+            }
+            for event in track
+                .events
+                .iter_mut()
+                .filter(|e| e.track_id == track_id && event_key(&e.message).map_or(false, low))
+            {
+                event.track_id = new_id;
+                event.track_color_id = new_color;
+            }
+        }
+
+        self.tracks.push(split);
+        Some(new_id)
+    }
+
+    /// MusicXML score in `data`, plain or a compressed `.mxl`. `expand_repeats` follows its
+    /// repeats and jumps
+    pub fn from_musicxml(data: &[u8], expand_repeats: bool) -> Result<Self, String> {
+        Self::from_bytes(&musicxml::to_smf(data, expand_repeats)?)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        let smf = match Smf::parse(data) {
             Ok(smf) => smf,
             Err(_) => return Err(String::from("Midi Parsing Error (midly lib)")),
         };
 
+        Self::from_smf(&smf)
+    }
+
+    pub(crate) fn from_smf(smf: &Smf) -> Result<Self, String> {
         let u_per_quarter_note: u16 = match smf.header.timing {
             Timing::Metrical(t) => t.as_int(),
             Timing::Timecode(_fps, _u) => {
@@ -60,20 +288,42 @@ impl Midi {
             for e in track.events.iter().cloned() {
                 merged_track.events.push(e);
             }
+            for e in track.sysex_events.iter().cloned() {
+                merged_track.sysex_events.push(e);
+            }
         }
 
         merged_track.notes.sort_by_key(|n| n.start);
         merged_track.events.sort_by_key(|n| n.timestamp);
+        merged_track.sysex_events.sort_by_key(|n| n.timestamp);
 
         // Assign Unique Id
         for (i, note) in merged_track.notes.iter_mut().enumerate() {
             note.id = i;
         }
 
+        let analysis =
+            SongAnalysis::new(&smf.tracks, &tempo_track, u_per_quarter_note, &merged_track);
+        let metadata = Metadata::from_track(&smf.tracks[0]);
+
         Ok(Self {
+            name: String::new(),
+            path: None,
             format: smf.header.format,
             tracks,
             merged_track,
+            analysis,
+            metadata,
         })
     }
 }
+
+/// Key of a message that belongs to a single note
+fn event_key(message: &MidiMessage) -> Option<u8> {
+    match message {
+        MidiMessage::NoteOn { key, .. }
+        | MidiMessage::NoteOff { key, .. }
+        | MidiMessage::Aftertouch { key, .. } => Some(key.as_int()),
+        _ => None,
+    }
+}
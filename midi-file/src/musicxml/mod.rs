@@ -0,0 +1,807 @@
+//! MusicXML scores, `.musicxml` and compressed `.mxl`, converted to a Standard MIDI File.
+//!
+//! Every voice of every part becomes a track of its own, so hands and voices can still be
+//! told apart. Repeats and D.C./D.S. jumps are either followed or every measure is played
+//! once as written, their barlines are kept as cue points. Grace and cue notes are left out
+
+mod mxl;
+mod repeats;
+mod xml;
+
+use std::collections::HashMap;
+
+use midly::{
+    num::{u15, u24, u28, u4, u7},
+    Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
+};
+
+use crate::{
+    analysis::{REPEAT_END, REPEAT_START},
+    metadata::COMPOSER_PREFIX,
+};
+use xml::Element;
+
+const PULSES_PER_QUARTER_NOTE: u16 = 480;
+/// `<sound dynamics="100"/>` is forte
+const DEFAULT_VELOCITY: u8 = 80;
+const PERCUSSION_CHANNEL: u8 = 9;
+
+/// SMF of the MusicXML score in `data`, `expand_repeats` follows its repeats and jumps
+pub(crate) fn to_smf(data: &[u8], expand_repeats: bool) -> Result<Vec<u8>, String> {
+    let text = if mxl::is_zip(data) {
+        mxl::score(data)?
+    } else {
+        match std::str::from_utf8(data) {
+            Ok(text) => text.to_string(),
+            Err(_) => return Err(String::from("MusicXML Is Not UTF-8")),
+        }
+    };
+
+    let root = xml::parse(text.trim_start_matches('\u{feff}'))?;
+    let score = Score::new(&root, expand_repeats)?;
+    Ok(score.to_smf())
+}
+
+#[derive(Debug, Default)]
+struct Score {
+    title: Option<String>,
+    composer: Option<String>,
+    copyright: Option<String>,
+    /// Pulses and microseconds per quarter note
+    tempos: Vec<(u64, u32)>,
+    /// Pulses, beats and beat type
+    time_signatures: Vec<(u64, u8, u8)>,
+    /// Pulses, sharps and minor
+    key_signatures: Vec<(u64, i8, bool)>,
+    /// Pulses of the repeat barlines, and whether the repeated part starts there
+    repeats: Vec<(u64, bool)>,
+    tracks: Vec<Track>,
+}
+
+#[derive(Debug, Default)]
+struct Track {
+    name: String,
+    channel: u8,
+    program: Option<u8>,
+    notes: Vec<Note>,
+    /// Pulses and syllable
+    lyrics: Vec<(u64, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Note {
+    start: u64,
+    end: u64,
+    key: u8,
+    vel: u8,
+}
+
+/// Sound of a `<score-part>`, from its `<midi-instrument>`
+#[derive(Debug, Default)]
+struct Instrument {
+    name: String,
+    channel: Option<u8>,
+    program: Option<u8>,
+    /// Keys of unpitched instruments, by instrument id
+    unpitched: HashMap<String, u8>,
+}
+
+impl Score {
+    fn new(root: &Element, expand_repeats: bool) -> Result<Self, String> {
+        let root = match root.name.as_str() {
+            "score-partwise" => root.clone(),
+            "score-timewise" => partwise(root),
+            _ => return Err(String::from("File Is Not A MusicXML Score")),
+        };
+
+        let mut score = Self::default();
+
+        let title = root
+            .child("work")
+            .and_then(|work| work.child_text("work-title"));
+        score.title = title.or_else(|| root.child_text("movement-title"));
+        if let Some(identification) = root.child("identification") {
+            score.composer = identification
+                .children("creator")
+                .find(|creator| creator.attribute("type") == Some("composer"))
+                .map(|creator| creator.text())
+                .filter(|composer| !composer.is_empty());
+            score.copyright = identification.child_text("rights");
+        }
+
+        let mut instruments: HashMap<&str, Instrument> = HashMap::new();
+        if let Some(part_list) = root.child("part-list") {
+            for part in part_list.children("score-part") {
+                let id = part.attribute("id").unwrap_or_default();
+                instruments.insert(id, instrument(part));
+            }
+        }
+
+        // Parts share their measures, the repeats of the first one are followed by all
+        let order = match root.child("part") {
+            Some(part) => {
+                let measures: Vec<&Element> = part.children("measure").collect();
+                if expand_repeats {
+                    repeats::measure_order(&measures)
+                } else {
+                    (0..measures.len()).collect()
+                }
+            }
+            None => Vec::new(),
+        };
+
+        let mut next_channel = 0;
+        for (id, part) in root.children("part").enumerate() {
+            let instrument = part
+                .attribute("id")
+                .and_then(|id| instruments.remove(id))
+                .unwrap_or_default();
+
+            // Parts without a channel of their own get the next free one
+            let channel = instrument.channel.unwrap_or_else(|| {
+                if next_channel == PERCUSSION_CHANNEL {
+                    next_channel += 1;
+                }
+                let channel = next_channel % 16;
+                next_channel += 1;
+                channel
+            });
+
+            score.read_part(part, &instrument, channel, &order, id == 0);
+        }
+
+        if score.tracks.iter().all(|track| track.notes.is_empty()) {
+            return Err(String::from("MusicXML Score Has No Notes"));
+        }
+
+        score.tempos.sort_by_key(|(pulses, _)| *pulses);
+        // Of the parts that set a tempo at the same time, the last one wins
+        score.tempos.reverse();
+        score.tempos.dedup_by_key(|(pulses, _)| *pulses);
+        score.tempos.reverse();
+
+        Ok(score)
+    }
+
+    /// Notes of a `<part>`, one track per voice, its measures are played in `order`.
+    /// Signatures and repeat barlines are taken from the first part
+    fn read_part(
+        &mut self,
+        part: &Element,
+        instrument: &Instrument,
+        channel: u8,
+        order: &[usize],
+        first: bool,
+    ) {
+        let mut voices: Vec<(String, Track)> = Vec::new();
+        // Notes waiting for the rest of their tie, by voice and key
+        let mut ties: HashMap<(String, u8), (usize, usize)> = HashMap::new();
+
+        let mut divisions = 1.0;
+        let mut transpose = 0;
+        let mut velocity = DEFAULT_VELOCITY;
+
+        // In quarter notes
+        let mut measure_start = 0.0;
+        let mut last_start = 0.0;
+
+        let measures: Vec<&Element> = part.children("measure").collect();
+        for measure in order.iter().filter_map(|id| measures.get(*id)) {
+            let mut measure_end = measure_start;
+            let mut pos: f64 = measure_start;
+            let mut repeat_end = false;
+
+            for element in measure.elements() {
+                match element.name.as_str() {
+                    "attributes" => {
+                        if let Some(value) = element.child_number("divisions") {
+                            if value > 0.0 {
+                                divisions = value;
+                            }
+                        }
+                        if let Some(shift) = element.child("transpose") {
+                            let chromatic = shift.child_number("chromatic").unwrap_or(0.0);
+                            let octaves = shift.child_number("octave-change").unwrap_or(0.0);
+                            transpose = (chromatic + octaves * 12.0) as i32;
+                        }
+
+                        if first {
+                            self.read_signatures(element, pulses(pos));
+                        }
+                    }
+                    "direction" => {
+                        let offset = element.child_number("offset").unwrap_or(0.0) / divisions;
+                        let at = pulses((pos + offset).max(0.0));
+
+                        match element.child("sound") {
+                            Some(sound) => self.read_sound(sound, at, &mut velocity),
+                            None => {
+                                let metronome = element
+                                    .children("direction-type")
+                                    .find_map(|kind| kind.child("metronome"));
+                                if let Some(tempo) = metronome.and_then(metronome_tempo) {
+                                    self.tempos.push((at, tempo));
+                                }
+                            }
+                        }
+                    }
+                    "sound" => self.read_sound(element, pulses(pos), &mut velocity),
+                    "barline" if first => {
+                        let direction = element
+                            .child("repeat")
+                            .and_then(|repeat| repeat.attribute("direction"));
+                        match direction {
+                            Some("forward") => self.repeats.push((pulses(measure_start), true)),
+                            Some("backward") => repeat_end = true,
+                            _ => {}
+                        }
+                    }
+                    "backup" => {
+                        let duration = element.child_number("duration").unwrap_or(0.0);
+                        pos = (pos - duration / divisions).max(measure_start);
+                    }
+                    "forward" => {
+                        let duration = element.child_number("duration").unwrap_or(0.0);
+                        pos += duration / divisions;
+                    }
+                    "note" => {
+                        // Graces take no time, cues are played by someone else
+                        if element.has("grace") {
+                            continue;
+                        }
+
+                        let duration = element.child_number("duration").unwrap_or(0.0) / divisions;
+                        let start = if element.has("chord") {
+                            last_start
+                        } else {
+                            let start = pos;
+                            pos += duration;
+                            start
+                        };
+                        last_start = start;
+                        measure_end = f64::max(measure_end, pos);
+
+                        if element.has("rest") || element.has("cue") {
+                            continue;
+                        }
+                        let key = match note_key(element, instrument, transpose) {
+                            Some(key) => key,
+                            None => continue,
+                        };
+
+                        let voice = element.child_text("voice").unwrap_or_else(|| "1".into());
+                        let track_id = match voices.iter().position(|(v, _)| *v == voice) {
+                            Some(id) => id,
+                            None => {
+                                voices.push((voice.clone(), Track::default()));
+                                voices.len() - 1
+                            }
+                        };
+                        let track = &mut voices[track_id].1;
+
+                        if !element.has("chord") {
+                            if let Some(syllable) = lyric(element) {
+                                track.lyrics.push((pulses(start), syllable));
+                            }
+                        }
+
+                        let (tie_start, tie_stop) = ties_of(element);
+                        let end = pulses(start + duration);
+
+                        let tied = ties.get(&(voice.clone(), key)).copied();
+                        match tied {
+                            Some((track_id, note_id)) if tie_stop => {
+                                let note = &mut voices[track_id].1.notes[note_id];
+                                note.end = note.end.max(end);
+                                if !tie_start {
+                                    ties.remove(&(voice, key));
+                                }
+                            }
+                            _ => {
+                                let vel = element
+                                    .attribute("dynamics")
+                                    .and_then(|dynamics| dynamics.parse().ok())
+                                    .map_or(velocity, dynamics_velocity);
+
+                                track.notes.push(Note {
+                                    start: pulses(start),
+                                    end,
+                                    key,
+                                    vel,
+                                });
+
+                                if tie_start {
+                                    ties.insert((voice, key), (track_id, track.notes.len() - 1));
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            measure_start = f64::max(measure_end, pos);
+            if repeat_end {
+                self.repeats.push((pulses(measure_start), false));
+            }
+        }
+
+        let voice_count = voices.len();
+        for (voice, mut track) in voices {
+            track.name = if voice_count > 1 {
+                format!("{}, voice {voice}", instrument.name)
+            } else {
+                instrument.name.clone()
+            };
+            track.channel = channel;
+            track.program = instrument.program;
+            // Zero length notes, eg. of a duration missing from the file
+            track.notes.retain(|note| note.end > note.start);
+            self.tracks.push(track);
+        }
+    }
+
+    fn read_signatures(&mut self, attributes: &Element, at: u64) {
+        if let Some(time) = attributes.child("time") {
+            // Compound signatures, like 3+2, are summed up
+            let beats: u32 = time
+                .child_text("beats")
+                .map(|beats| {
+                    beats
+                        .split('+')
+                        .filter_map(|b| b.trim().parse::<u32>().ok())
+                        .sum()
+                })
+                .unwrap_or(0);
+            let beat_type = time.child_number("beat-type").unwrap_or(0.0) as u32;
+
+            if beats > 0 && beat_type.is_power_of_two() {
+                self.time_signatures.push((
+                    at,
+                    beats.min(255) as u8,
+                    beat_type.trailing_zeros() as u8,
+                ));
+            }
+        }
+
+        if let Some(key) = attributes.child("key") {
+            if let Some(fifths) = key.child_number("fifths") {
+                let minor = key.child_text("mode").as_deref() == Some("minor");
+                self.key_signatures
+                    .push((at, (fifths as i8).clamp(-7, 7), minor));
+            }
+        }
+    }
+
+    fn read_sound(&mut self, sound: &Element, at: u64, velocity: &mut u8) {
+        let tempo = sound
+            .attribute("tempo")
+            .and_then(|tempo| tempo.parse::<f64>().ok())
+            .filter(|tempo| *tempo > 0.0);
+        if let Some(tempo) = tempo {
+            self.tempos.push((at, (60_000_000.0 / tempo) as u32));
+        }
+
+        if let Some(dynamics) = sound
+            .attribute("dynamics")
+            .and_then(|dynamics| dynamics.parse().ok())
+        {
+            *velocity = dynamics_velocity(dynamics);
+        }
+    }
+
+    fn to_smf(&self) -> Vec<u8> {
+        let composer = self
+            .composer
+            .as_ref()
+            .map(|composer| format!("{COMPOSER_PREFIX}{composer}"));
+
+        let mut conductor: Vec<(u64, TrackEventKind)> = Vec::new();
+        if let Some(title) = self.title.as_ref() {
+            conductor.push((
+                0,
+                TrackEventKind::Meta(MetaMessage::TrackName(title.as_bytes())),
+            ));
+        }
+        if let Some(copyright) = self.copyright.as_ref() {
+            conductor.push((
+                0,
+                TrackEventKind::Meta(MetaMessage::Copyright(copyright.as_bytes())),
+            ));
+        }
+        if let Some(composer) = composer.as_ref() {
+            conductor.push((
+                0,
+                TrackEventKind::Meta(MetaMessage::Text(composer.as_bytes())),
+            ));
+        }
+        for (at, tempo) in self.tempos.iter() {
+            let tempo = MetaMessage::Tempo(u24::new(*tempo));
+            conductor.push((*at, TrackEventKind::Meta(tempo)));
+        }
+        for (at, beats, beat_type) in self.time_signatures.iter() {
+            let signature = MetaMessage::TimeSignature(*beats, *beat_type, 24, 8);
+            conductor.push((*at, TrackEventKind::Meta(signature)));
+        }
+        for (at, sharps, minor) in self.key_signatures.iter() {
+            let signature = MetaMessage::KeySignature(*sharps, *minor);
+            conductor.push((*at, TrackEventKind::Meta(signature)));
+        }
+        for (at, start) in self.repeats.iter() {
+            let mark = if *start { REPEAT_START } else { REPEAT_END };
+            let cue = MetaMessage::CuePoint(mark.as_bytes());
+            conductor.push((*at, TrackEventKind::Meta(cue)));
+        }
+
+        let mut tracks = vec![track_events(conductor)];
+        for track in self.tracks.iter() {
+            let channel = u4::new(track.channel);
+            let mut events = vec![(
+                0,
+                TrackEventKind::Meta(MetaMessage::TrackName(track.name.as_bytes())),
+            )];
+            if let Some(program) = track.program {
+                let message = MidiMessage::ProgramChange {
+                    program: u7::new(program),
+                };
+                events.push((0, TrackEventKind::Midi { channel, message }));
+            }
+
+            let mut notes = Vec::with_capacity(track.notes.len() * 2 + track.lyrics.len());
+            for (at, syllable) in track.lyrics.iter() {
+                notes.push((
+                    *at,
+                    1,
+                    TrackEventKind::Meta(MetaMessage::Lyric(syllable.as_bytes())),
+                ));
+            }
+            for note in track.notes.iter() {
+                let (key, vel) = (u7::new(note.key), u7::new(note.vel));
+                let on = MidiMessage::NoteOn { key, vel };
+                let off = MidiMessage::NoteOff {
+                    key,
+                    vel: u7::new(0),
+                };
+                notes.push((
+                    note.start,
+                    1,
+                    TrackEventKind::Midi {
+                        channel,
+                        message: on,
+                    },
+                ));
+                notes.push((
+                    note.end,
+                    0,
+                    TrackEventKind::Midi {
+                        channel,
+                        message: off,
+                    },
+                ));
+            }
+            // Notes that end are released before the ones starting at the same time
+            notes.sort_by_key(|(at, order, _)| (*at, *order));
+            events.extend(notes.into_iter().map(|(at, _, kind)| (at, kind)));
+
+            tracks.push(track_events(events));
+        }
+
+        let smf = Smf {
+            header: Header::new(
+                Format::Parallel,
+                Timing::Metrical(u15::new(PULSES_PER_QUARTER_NOTE)),
+            ),
+            tracks,
+        };
+
+        let mut data = Vec::new();
+        // Writing to memory can't fail
+        smf.write_std(&mut data).ok();
+        data
+    }
+}
+
+/// Delta timed track of `events`, which are stamped with absolute pulses
+fn track_events(mut events: Vec<(u64, TrackEventKind)>) -> Vec<TrackEvent> {
+    events.sort_by_key(|(at, _)| *at);
+
+    let mut last = 0;
+    let mut track: Vec<TrackEvent> = events
+        .into_iter()
+        .map(|(at, kind)| {
+            let delta = u28::new((at - last) as u32);
+            last = at;
+            TrackEvent { delta, kind }
+        })
+        .collect();
+
+    track.push(TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+    track
+}
+
+/// Quarter notes to pulses
+fn pulses(quarters: f64) -> u64 {
+    (quarters * PULSES_PER_QUARTER_NOTE as f64).round() as u64
+}
+
+/// `dynamics` is a percentage of forte
+fn dynamics_velocity(dynamics: f64) -> u8 {
+    (dynamics * 0.9).round().clamp(1.0, 127.0) as u8
+}
+
+/// Microseconds per quarter note of a `<metronome>` mark
+fn metronome_tempo(metronome: &Element) -> Option<u32> {
+    let per_minute = metronome.child_number("per-minute")?;
+    let mut quarters = match metronome.child_text("beat-unit")?.as_str() {
+        "whole" => 4.0,
+        "half" => 2.0,
+        "quarter" => 1.0,
+        "eighth" => 0.5,
+        "16th" => 0.25,
+        _ => return None,
+    };
+    if metronome.has("beat-unit-dot") {
+        quarters *= 1.5;
+    }
+
+    (per_minute > 0.0).then(|| (60_000_000.0 / (per_minute * quarters)) as u32)
+}
+
+fn instrument(part: &Element) -> Instrument {
+    let mut instrument = Instrument {
+        name: part.child_text("part-name").unwrap_or_default(),
+        ..Default::default()
+    };
+
+    for midi in part.children("midi-instrument") {
+        // 1 based in MusicXML
+        let channel = midi.child_number("midi-channel").map(|c| c as u8);
+        let program = midi.child_number("midi-program").map(|p| p as u8);
+        if instrument.channel.is_none() {
+            instrument.channel = channel.filter(|c| (1..=16).contains(c)).map(|c| c - 1);
+        }
+        if instrument.program.is_none() {
+            instrument.program = program.filter(|p| (1..=128).contains(p)).map(|p| p - 1);
+        }
+
+        if let Some(key) = midi.child_number("midi-unpitched") {
+            let id = midi.attribute("id").unwrap_or_default().to_string();
+            instrument
+                .unpitched
+                .insert(id, (key as u8).clamp(1, 128) - 1);
+        }
+    }
+
+    if !instrument.unpitched.is_empty() && instrument.channel.is_none() {
+        instrument.channel = Some(PERCUSSION_CHANNEL);
+    }
+
+    instrument
+}
+
+/// Sounding key of a `<note>`
+fn note_key(note: &Element, instrument: &Instrument, transpose: i32) -> Option<u8> {
+    let key = if let Some(pitch) = note.child("pitch") {
+        let step = step_key(&pitch.child_text("step")?)?;
+        let alter = pitch.child_number("alter").unwrap_or(0.0).round() as i32;
+        let octave = pitch.child_number("octave")? as i32;
+        (octave + 1) * 12 + step + alter + transpose
+    } else if let Some(unpitched) = note.child("unpitched") {
+        let key = note
+            .child("instrument")
+            .and_then(|id| id.attribute("id"))
+            .and_then(|id| instrument.unpitched.get(id));
+        match key {
+            Some(key) => *key as i32,
+            // Drawn on the staff, there is no sound to go by
+            None => {
+                let step = step_key(&unpitched.child_text("display-step")?)?;
+                let octave = unpitched.child_number("display-octave")? as i32;
+                (octave + 1) * 12 + step
+            }
+        }
+    } else {
+        return None;
+    };
+
+    (0..=127).contains(&key).then_some(key as u8)
+}
+
+fn step_key(step: &str) -> Option<i32> {
+    Some(match step {
+        "C" => 0,
+        "D" => 2,
+        "E" => 4,
+        "F" => 5,
+        "G" => 7,
+        "A" => 9,
+        "B" => 11,
+        _ => return None,
+    })
+}
+
+/// Whether the note starts and ends a tie
+fn ties_of(note: &Element) -> (bool, bool) {
+    let mut start = false;
+    let mut stop = false;
+    for tie in note.children("tie") {
+        match tie.attribute("type") {
+            Some("start") => start = true,
+            Some("stop") => stop = true,
+            _ => {}
+        }
+    }
+    (start, stop)
+}
+
+/// First verse syllable of the note, in the form the lyric parser expects
+fn lyric(note: &Element) -> Option<String> {
+    let lyric = note
+        .children("lyric")
+        .find(|lyric| lyric.attribute("number").map_or(true, |n| n == "1"))?;
+
+    let mut syllable = lyric.child_text("text")?;
+    // Words end with a space, syllables go on in the next one
+    if !matches!(
+        lyric.child_text("syllabic").as_deref(),
+        Some("begin" | "middle")
+    ) {
+        syllable.push(' ');
+    }
+    if lyric.has("end-line") || lyric.has("end-paragraph") {
+        syllable.push('\n');
+    }
+    Some(syllable)
+}
+
+/// Partwise version of a timewise score, where the parts are inside the measures
+fn partwise(root: &Element) -> Element {
+    let mut score = Element {
+        name: "score-partwise".into(),
+        ..Default::default()
+    };
+    let mut parts: Vec<Element> = Vec::new();
+
+    for element in root.elements() {
+        if element.name != "measure" {
+            score.children.push(xml::Node::Element(element.clone()));
+            continue;
+        }
+
+        for part in element.children("part") {
+            let measure = Element {
+                name: "measure".into(),
+                attributes: element.attributes.clone(),
+                children: part.children.clone(),
+            };
+
+            let id = part.attribute("id").unwrap_or_default();
+            match parts.iter_mut().find(|p| p.attribute("id") == Some(id)) {
+                Some(p) => p.children.push(xml::Node::Element(measure)),
+                None => parts.push(Element {
+                    name: "part".into(),
+                    attributes: part.attributes.clone(),
+                    children: vec![xml::Node::Element(measure)],
+                }),
+            }
+        }
+    }
+
+    score
+        .children
+        .extend(parts.into_iter().map(xml::Node::Element));
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCORE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="4.0">
+  <work><work-title>Test</work-title></work>
+  <part-list>
+    <score-part id="P1">
+      <part-name>Piano</part-name>
+      <midi-instrument id="P1-I1"><midi-channel>1</midi-channel><midi-program>1</midi-program></midi-instrument>
+    </score-part>
+  </part-list>
+  <part id="P1">
+    <measure number="1">
+      <attributes>
+        <divisions>2</divisions>
+        <key><fifths>-1</fifths></key>
+        <time><beats>2</beats><beat-type>4</beat-type></time>
+      </attributes>
+      <sound tempo="60"/>
+      <note><pitch><step>C</step><octave>4</octave></pitch><duration>2</duration><voice>1</voice>
+        <lyric><syllabic>begin</syllabic><text>Hel</text></lyric></note>
+      <note><chord/><pitch><step>E</step><octave>4</octave></pitch><duration>2</duration><voice>1</voice></note>
+      <note><pitch><step>B</step><alter>-1</alter><octave>4</octave></pitch><duration>2</duration><voice>1</voice>
+        <tie type="start"/></note>
+      <backup><duration>4</duration></backup>
+      <note><pitch><step>C</step><octave>3</octave></pitch><duration>4</duration><voice>2</voice></note>
+    </measure>
+    <measure number="2">
+      <note><pitch><step>B</step><alter>-1</alter><octave>4</octave></pitch><duration>1</duration><voice>1</voice>
+        <tie type="stop"/></note>
+      <note><grace/><pitch><step>D</step><octave>5</octave></pitch><voice>1</voice></note>
+      <note><rest/><duration>3</duration><voice>1</voice></note>
+    </measure>
+  </part>
+</score-partwise>"#;
+
+    #[test]
+    fn converts_voices_ties_and_signatures() {
+        let root = xml::parse(SCORE).unwrap();
+        let score = Score::new(&root, true).unwrap();
+
+        assert_eq!(score.title.as_deref(), Some("Test"));
+        assert_eq!(score.tempos, [(0, 1_000_000)]);
+        assert_eq!(score.time_signatures, [(0, 2, 2)]);
+        assert_eq!(score.key_signatures, [(0, -1, false)]);
+
+        let names: Vec<&str> = score.tracks.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["Piano, voice 1", "Piano, voice 2"]);
+
+        let notes: Vec<(u64, u64, u8)> = score.tracks[0]
+            .notes
+            .iter()
+            .map(|n| (n.start, n.end, n.key))
+            .collect();
+        // The tied B flat lasts into the second measure, the grace note is left out
+        assert_eq!(notes, [(0, 480, 60), (0, 480, 64), (480, 1200, 70)]);
+        assert_eq!(score.tracks[1].notes[0].start, 0);
+        assert_eq!(score.tracks[1].notes[0].end, 960);
+        assert_eq!(score.tracks[0].lyrics, [(0, "Hel".to_string())]);
+    }
+
+    #[test]
+    fn repeats_expand_or_collapse() {
+        let score = r#"<score-partwise>
+  <part-list><score-part id="P1"><part-name>Piano</part-name></score-part></part-list>
+  <part id="P1">
+    <measure number="1">
+      <attributes><divisions>1</divisions></attributes>
+      <note><pitch><step>C</step><octave>4</octave></pitch><duration>4</duration></note>
+      <barline location="right"><repeat direction="backward"/></barline>
+    </measure>
+    <measure number="2">
+      <note><pitch><step>D</step><octave>4</octave></pitch><duration>4</duration></note>
+    </measure>
+  </part>
+</score-partwise>"#;
+        let root = xml::parse(score).unwrap();
+        let starts = |score: &Score| -> Vec<(u64, u8)> {
+            score.tracks[0]
+                .notes
+                .iter()
+                .map(|n| (n.start, n.key))
+                .collect()
+        };
+
+        let expanded = Score::new(&root, true).unwrap();
+        assert_eq!(starts(&expanded), [(0, 60), (1920, 60), (3840, 62)]);
+        // Barline of every pass
+        assert_eq!(expanded.repeats, [(1920, false), (3840, false)]);
+
+        let collapsed = Score::new(&root, false).unwrap();
+        assert_eq!(starts(&collapsed), [(0, 60), (1920, 62)]);
+        assert_eq!(collapsed.repeats, [(1920, false)]);
+    }
+
+    #[test]
+    fn converted_score_loads() {
+        let midi = crate::Midi::from_musicxml(SCORE.as_bytes(), true).unwrap();
+
+        assert_eq!(midi.merged_track.notes.len(), 4);
+        assert_eq!(midi.metadata.title.as_deref(), Some("Test"));
+        assert_eq!(
+            midi.merged_track.notes.last().map(|n| n.end.as_millis()),
+            Some(2500)
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Compressed MusicXML, a zip archive with the score and `META-INF/container.xml` pointing at it
+
+use super::xml;
+
+const LOCAL_HEADER: u32 = 0x04034b50;
+const CENTRAL_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+
+/// Whether `data` looks like a zip archive
+pub fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(&LOCAL_HEADER.to_le_bytes())
+}
+
+/// Score text of the `.mxl` archive in `data`
+pub fn score(data: &[u8]) -> Result<String, String> {
+    let files = entries(data).ok_or_else(|| String::from("Compressed MusicXML Is Broken"))?;
+
+    let container = files
+        .iter()
+        .find(|file| file.name == "META-INF/container.xml")
+        .and_then(|file| file.read(data))
+        .and_then(|container| String::from_utf8(container).ok())
+        .and_then(|container| xml::parse(&container).ok());
+    // The first root file is the score, others are other renditions of it
+    let root = container.as_ref().and_then(|container| {
+        let rootfiles = container.child("rootfiles")?;
+        let rootfile = rootfiles.child("rootfile")?;
+        rootfile.attribute("full-path")
+    });
+
+    let file = match root {
+        Some(root) => files.iter().find(|file| file.name == root),
+        None => files.iter().find(|file| {
+            !file.name.starts_with("META-INF/")
+                && (file.name.ends_with(".xml") || file.name.ends_with(".musicxml"))
+        }),
+    };
+
+    let data = file
+        .and_then(|file| file.read(data))
+        .ok_or_else(|| String::from("Compressed MusicXML Has No Score"))?;
+    String::from_utf8(data).map_err(|_| String::from("MusicXML Is Not UTF-8"))
+}
+
+struct Entry {
+    name: String,
+    method: u16,
+    compressed_size: usize,
+    header_offset: usize,
+}
+
+impl Entry {
+    fn read(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let header = data.get(self.header_offset..)?;
+        if u32_at(header, 0)? != LOCAL_HEADER {
+            return None;
+        }
+
+        let start = 30 + u16_at(header, 26)? as usize + u16_at(header, 28)? as usize;
+        let data = header.get(start..start + self.compressed_size)?;
+        match self.method {
+            0 => Some(data.to_vec()),
+            8 => inflate(data),
+            _ => None,
+        }
+    }
+}
+
+/// Files listed in the central directory
+fn entries(data: &[u8]) -> Option<Vec<Entry>> {
+    // The end record is last, followed by a comment of at most 64KB
+    let end = (0..data.len().saturating_sub(21))
+        .rev()
+        .take(u16::MAX as usize + 22)
+        .find(|pos| u32_at(data, *pos) == Some(END_OF_CENTRAL_DIRECTORY))?;
+
+    let count = u16_at(data, end + 10)? as usize;
+    let mut pos = u32_at(data, end + 16)? as usize;
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if u32_at(data, pos)? != CENTRAL_HEADER {
+            return None;
+        }
+
+        let name_len = u16_at(data, pos + 28)? as usize;
+        let extra_len = u16_at(data, pos + 30)? as usize;
+        let comment_len = u16_at(data, pos + 32)? as usize;
+        let name = data.get(pos + 46..pos + 46 + name_len)?;
+
+        entries.push(Entry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method: u16_at(data, pos + 10)?,
+            compressed_size: u32_at(data, pos + 20)? as usize,
+            header_offset: u32_at(data, pos + 42)? as usize,
+        });
+
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+    Some(entries)
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Option<u16> {
+    let bytes = data.get(pos..pos + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Raw DEFLATE stream, as zip stores it
+fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    const LENGTH_BASE: [u16; 29] = [
+        3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+        131, 163, 195, 227, 258,
+    ];
+    const LENGTH_EXTRA: [u8; 29] = [
+        0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+    ];
+    const DISTANCE_BASE: [u16; 30] = [
+        1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+        2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+    ];
+    const DISTANCE_EXTRA: [u8; 30] = [
+        0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12,
+        13, 13,
+    ];
+    /// Order the code length code lengths are stored in
+    const CODE_LENGTH_ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+
+    let mut bits = BitReader { data, pos: 0 };
+    let mut out = Vec::new();
+
+    loop {
+        let last = bits.read(1)? == 1;
+        match bits.read(2)? {
+            0 => {
+                bits.align();
+                let len = bits.read(16)? as usize;
+                let _nlen = bits.read(16)?;
+                let start = bits.pos / 8;
+                out.extend_from_slice(data.get(start..start + len)?);
+                bits.pos += len * 8;
+            }
+            kind @ (1 | 2) => {
+                let (literals, distances) = if kind == 1 {
+                    let mut lengths = [0u8; 288];
+                    lengths[..144].fill(8);
+                    lengths[144..256].fill(9);
+                    lengths[256..280].fill(7);
+                    lengths[280..].fill(8);
+                    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+                } else {
+                    let literal_count = bits.read(5)? as usize + 257;
+                    let distance_count = bits.read(5)? as usize + 1;
+                    let code_length_count = bits.read(4)? as usize + 4;
+
+                    let mut code_lengths = [0u8; 19];
+                    for id in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+                        code_lengths[*id] = bits.read(3)? as u8;
+                    }
+                    let code_lengths = Huffman::new(&code_lengths);
+
+                    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+                    while lengths.len() < literal_count + distance_count {
+                        let (value, repeat) = match code_lengths.decode(&mut bits)? {
+                            symbol @ 0..=15 => (symbol as u8, 1),
+                            16 => (*lengths.last()?, 3 + bits.read(2)?),
+                            17 => (0, 3 + bits.read(3)?),
+                            _ => (0, 11 + bits.read(7)?),
+                        };
+                        for _ in 0..repeat {
+                            lengths.push(value);
+                        }
+                    }
+                    if lengths.len() != literal_count + distance_count {
+                        return None;
+                    }
+
+                    (
+                        Huffman::new(&lengths[..literal_count]),
+                        Huffman::new(&lengths[literal_count..]),
+                    )
+                };
+
+                loop {
+                    let symbol = literals.decode(&mut bits)? as usize;
+                    match symbol {
+                        0..=255 => out.push(symbol as u8),
+                        256 => break,
+                        _ => {
+                            let id = symbol - 257;
+                            let len = *LENGTH_BASE.get(id)? as usize
+                                + bits.read(LENGTH_EXTRA[id] as u32)? as usize;
+
+                            let id = distances.decode(&mut bits)? as usize;
+                            let distance = *DISTANCE_BASE.get(id)? as usize
+                                + bits.read(DISTANCE_EXTRA[id] as u32)? as usize;
+                            if distance > out.len() {
+                                return None;
+                            }
+
+                            // Byte by byte, the copy may overlap what it writes
+                            let start = out.len() - distance;
+                            for id in 0..len {
+                                out.push(out[start + id]);
+                            }
+                        }
+                    }
+                }
+            }
+            _ => return None,
+        }
+
+        if last {
+            return Some(out);
+        }
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// In bits
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    /// `count` bits, least significant first
+    fn read(&mut self, count: u32) -> Option<u32> {
+        let mut value = 0;
+        for bit in 0..count {
+            let byte = *self.data.get(self.pos / 8)?;
+            value |= ((byte as u32 >> (self.pos % 8)) & 1) << bit;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+
+    fn align(&mut self) {
+        self.pos = (self.pos + 7) / 8 * 8;
+    }
+}
+
+/// Canonical Huffman code, decoded a bit at a time
+struct Huffman {
+    /// Codes of each length
+    counts: [u16; 16],
+    /// Symbols ordered by their code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for len in lengths {
+            counts[*len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut symbols = Vec::with_capacity(lengths.len());
+        for len in 1..16 {
+            for (symbol, _) in lengths.iter().enumerate().filter(|(_, l)| **l == len) {
+                symbols.push(symbol as u16);
+            }
+        }
+
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut BitReader) -> Option<u16> {
+        // First code and symbol index of the current length
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for len in 1..16 {
+            code |= bits.read(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return self.symbols.get((index + code - first) as usize).copied();
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inflates_compressed_and_stored_blocks() {
+        // `zlib.compressobj(level, zlib.DEFLATED, -15)` output, at level 9 and 0
+        let compressed = [
+            0x2b, 0xc9, 0x57, 0x48, 0x4a, 0x55, 0xc8, 0x2f, 0x52, 0xc8, 0xcb, 0x2f, 0x51, 0x28,
+            0x01, 0x71, 0x74, 0x20, 0x14, 0x99, 0x62, 0x00,
+        ];
+        let stored = [0x01, 0x03, 0x00, 0xfc, 0xff, 0x6d, 0x78, 0x6c];
+
+        assert_eq!(
+            inflate(&compressed),
+            Some("to be or not to be, ".repeat(4).into_bytes())
+        );
+        assert_eq!(inflate(&stored), Some(b"mxl".to_vec()));
+    }
+}
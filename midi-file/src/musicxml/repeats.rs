@@ -0,0 +1,257 @@
+//! Order the measures of a score are played in, following its repeats and jumps
+
+use super::xml::Element;
+
+/// Longest expansion, in times the written measures, so a score that jumps in circles
+/// still loads
+const MAX_EXPANSION: usize = 16;
+
+/// Repeat and jump marks of a measure
+#[derive(Debug, Default, Clone)]
+struct Marks {
+    /// Repeated part starts here
+    forward: bool,
+    /// Times the repeated part ending with this measure is played, `0` when none does
+    backward: u32,
+    /// Numbers of the ending bracket over the measure, empty outside of one
+    ending: Vec<u32>,
+    segno: bool,
+    coda: bool,
+    to_coda: bool,
+    fine: bool,
+    da_capo: bool,
+    dal_segno: bool,
+}
+
+/// Indices into `measures` in the order they are played. Repeated parts are played as many
+/// times as written, each pass taking its ending. After a D.C. or D.S. they are played once,
+/// taking the last ending, up to the Fine or the jump to the coda
+pub(super) fn measure_order(measures: &[&Element]) -> Vec<usize> {
+    let marks = marks(measures);
+
+    // Endings are numbered within their group of neighbouring brackets
+    let mut last_ending = vec![0; marks.len()];
+    let mut start = 0;
+    for id in 0..=marks.len() {
+        let in_ending = marks.get(id).map_or(false, |m| !m.ending.is_empty());
+        if !in_ending {
+            let last = marks[start..id]
+                .iter()
+                .flat_map(|m| m.ending.iter().copied())
+                .max()
+                .unwrap_or(0);
+            last_ending[start..id].fill(last);
+            start = id + 1;
+        }
+    }
+
+    let mut order = Vec::new();
+    let mut repeat_start = 0;
+    let mut pass = 1;
+    let mut in_endings = false;
+    let mut jumped = false;
+
+    let mut id = 0;
+    while id < marks.len() && order.len() < marks.len() * MAX_EXPANSION {
+        let m = &marks[id];
+
+        if m.ending.is_empty() && in_endings {
+            // Past the last ending, on to the next repeated part
+            in_endings = false;
+            pass = 1;
+            repeat_start = id;
+        }
+        if m.forward && id != repeat_start {
+            repeat_start = id;
+            pass = 1;
+        }
+
+        if !m.ending.is_empty() {
+            in_endings = true;
+            let wanted = if jumped { last_ending[id] } else { pass };
+            if !m.ending.contains(&wanted) {
+                id += 1;
+                continue;
+            }
+        }
+
+        order.push(id);
+
+        if jumped && m.fine {
+            break;
+        }
+        if jumped && m.to_coda {
+            if let Some(coda) = marks[id + 1..].iter().position(|m| m.coda) {
+                id += 1 + coda;
+                continue;
+            }
+        }
+
+        if m.backward > 0 && !jumped {
+            if pass < m.backward {
+                pass += 1;
+                in_endings = false;
+                id = repeat_start;
+                continue;
+            }
+            pass = 1;
+            in_endings = false;
+            repeat_start = id + 1;
+        }
+
+        if (m.da_capo || m.dal_segno) && !jumped {
+            jumped = true;
+            in_endings = false;
+            id = if m.dal_segno {
+                marks.iter().position(|m| m.segno).unwrap_or(0)
+            } else {
+                0
+            };
+            repeat_start = id;
+            continue;
+        }
+
+        id += 1;
+    }
+
+    order
+}
+
+fn marks(measures: &[&Element]) -> Vec<Marks> {
+    // Numbers of the ending bracket still open from an earlier measure
+    let mut open: Vec<u32> = Vec::new();
+
+    measures
+        .iter()
+        .map(|measure| {
+            let mut marks = Marks::default();
+            let mut ending_start = None;
+            let mut ending_stop = false;
+
+            for element in measure.elements() {
+                match element.name.as_str() {
+                    "barline" => {
+                        if let Some(repeat) = element.child("repeat") {
+                            match repeat.attribute("direction") {
+                                Some("forward") => marks.forward = true,
+                                Some("backward") => {
+                                    marks.backward = repeat
+                                        .attribute("times")
+                                        .and_then(|times| times.parse().ok())
+                                        .unwrap_or(2)
+                                        .max(1);
+                                }
+                                _ => {}
+                            }
+                        }
+                        if let Some(ending) = element.child("ending") {
+                            match ending.attribute("type") {
+                                Some("start") => {
+                                    ending_start = Some(ending_numbers(
+                                        ending.attribute("number").unwrap_or_default(),
+                                    ));
+                                }
+                                Some("stop" | "discontinue") => ending_stop = true,
+                                _ => {}
+                            }
+                        }
+                        if let Some(sound) = element.child("sound") {
+                            sound_marks(sound, &mut marks);
+                        }
+                    }
+                    "direction" => {
+                        let segno = element
+                            .children("direction-type")
+                            .any(|kind| kind.has("segno"));
+                        marks.segno |= segno;
+                        if let Some(sound) = element.child("sound") {
+                            sound_marks(sound, &mut marks);
+                        }
+                    }
+                    "sound" => sound_marks(element, &mut marks),
+                    _ => {}
+                }
+            }
+
+            if let Some(numbers) = ending_start {
+                open = numbers;
+            }
+            marks.ending = open.clone();
+            if ending_stop {
+                open.clear();
+            }
+
+            marks
+        })
+        .collect()
+}
+
+/// Jumps of a `<sound>`, `dacapo` is a yes or no, the others name their target
+fn sound_marks(sound: &Element, marks: &mut Marks) {
+    marks.segno |= sound.attribute("segno").is_some();
+    marks.coda |= sound.attribute("coda").is_some();
+    marks.to_coda |= sound.attribute("tocoda").is_some();
+    marks.fine |= sound.attribute("fine").is_some();
+    marks.da_capo |= sound.attribute("dacapo") == Some("yes");
+    marks.dal_segno |= sound.attribute("dalsegno").is_some();
+}
+
+/// `"1, 2"` or `"1 2"`
+fn ending_numbers(number: &str) -> Vec<u32> {
+    number
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter_map(|n| n.trim().parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::musicxml::xml;
+
+    fn order(measures: &str) -> Vec<usize> {
+        let root = xml::parse(&format!("<part>{measures}</part>")).unwrap();
+        let measures: Vec<&Element> = root.children("measure").collect();
+        measure_order(&measures)
+    }
+
+    #[test]
+    fn repeats_with_endings() {
+        let order = order(
+            r#"<measure/>
+            <measure><barline location="left"><repeat direction="forward"/></barline></measure>
+            <measure><barline location="left"><ending number="1" type="start"/></barline>
+              <barline location="right"><ending number="1" type="stop"/><repeat direction="backward"/></barline></measure>
+            <measure><barline location="left"><ending number="2" type="start"/></barline>
+              <barline location="right"><ending number="2" type="discontinue"/></barline></measure>
+            <measure><barline location="right"><repeat direction="backward" times="3"/></barline></measure>"#,
+        );
+        // Second part repeats from the end of the endings, three times
+        assert_eq!(order, [0, 1, 2, 1, 3, 4, 4, 4]);
+    }
+
+    #[test]
+    fn da_capo_al_fine() {
+        let order = order(
+            r#"<measure/>
+            <measure><barline location="right"><repeat direction="backward"/></barline>
+              <sound fine="yes"/></measure>
+            <measure><direction><direction-type><segno/></direction-type></direction></measure>
+            <measure><sound dacapo="yes"/></measure>"#,
+        );
+        // The repeat isn't taken again after the D.C.
+        assert_eq!(order, [0, 1, 0, 1, 2, 3, 0, 1]);
+    }
+
+    #[test]
+    fn dal_segno_al_coda() {
+        let order = order(
+            r#"<measure/>
+            <measure><sound segno="s"/></measure>
+            <measure><sound tocoda="c"/></measure>
+            <measure><sound dalsegno="s"/></measure>
+            <measure><sound coda="c"/></measure>"#,
+        );
+        assert_eq!(order, [0, 1, 2, 3, 1, 2, 4]);
+    }
+}
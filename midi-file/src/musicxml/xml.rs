@@ -0,0 +1,304 @@
+//! Just enough of an XML parser for MusicXML, the document is read into a tree of elements.
+//! Declarations, comments and the doctype are skipped, namespaces are kept in the names
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|element| element.name == name)
+    }
+
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.elements().filter(move |element| element.name == name)
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.child(name).is_some()
+    }
+
+    /// Text of the element, trimmed
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for node in self.children.iter() {
+            if let Node::Text(t) = node {
+                text.push_str(t);
+            }
+        }
+        text.trim().to_string()
+    }
+
+    /// Text of the `name` child, `None` when it's missing or empty
+    pub fn child_text(&self, name: &str) -> Option<String> {
+        self.child(name)
+            .map(|child| child.text())
+            .filter(|text| !text.is_empty())
+    }
+
+    /// Text of the `name` child as a number
+    pub fn child_number(&self, name: &str) -> Option<f64> {
+        self.child_text(name)?.parse().ok()
+    }
+}
+
+/// Root element of `text`
+pub fn parse(text: &str) -> Result<Element, String> {
+    let mut parser = Parser { text, pos: 0 };
+    // The root and whatever comes before it, the rest of the document is ignored
+    let mut stack: Vec<Element> = Vec::new();
+
+    loop {
+        if parser.rest().is_empty() {
+            return Err(String::from("MusicXML Ends Early"));
+        }
+
+        if !parser.rest().starts_with('<') {
+            let end = parser.rest().find('<').unwrap_or(parser.rest().len());
+            let text = unescape(&parser.rest()[..end]);
+            parser.pos += end;
+            if let Some(parent) = stack.last_mut() {
+                parent.children.push(Node::Text(text));
+            }
+            continue;
+        }
+
+        if parser.skip("<?", "?>")? || parser.skip("<!--", "-->")? {
+            continue;
+        }
+        if parser.rest().starts_with("<![CDATA[") {
+            parser.pos += "<![CDATA[".len();
+            let text = parser.until("]]>")?.to_string();
+            if let Some(parent) = stack.last_mut() {
+                parent.children.push(Node::Text(text));
+            }
+            continue;
+        }
+        if parser.rest().starts_with("<!") {
+            parser.skip_doctype()?;
+            continue;
+        }
+
+        if parser.rest().starts_with("</") {
+            parser.pos += 2;
+            let name = parser.until(">")?.trim();
+            let element = match stack.pop() {
+                Some(element) if element.name == name => element,
+                _ => return Err(String::from("MusicXML Has Mismatched Tags")),
+            };
+
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(Node::Element(element)),
+                None => return Ok(element),
+            }
+            continue;
+        }
+
+        parser.pos += 1;
+        let (element, empty) = parser.start_tag()?;
+        if !empty {
+            stack.push(element);
+            continue;
+        }
+
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(Node::Element(element)),
+            None => return Ok(element),
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    /// Text up to `end`, the parser moves past it
+    fn until(&mut self, end: &str) -> Result<&'a str, String> {
+        let rest = self.rest();
+        match rest.find(end) {
+            Some(len) => {
+                self.pos += len + end.len();
+                Ok(&rest[..len])
+            }
+            None => Err(String::from("MusicXML Ends Early")),
+        }
+    }
+
+    /// Skips from `start` to `end`, if the text starts there
+    fn skip(&mut self, start: &str, end: &str) -> Result<bool, String> {
+        if !self.rest().starts_with(start) {
+            return Ok(false);
+        }
+        self.pos += start.len();
+        self.until(end)?;
+        Ok(true)
+    }
+
+    /// `<!DOCTYPE ...>`, which may hold declarations in brackets
+    fn skip_doctype(&mut self) -> Result<(), String> {
+        let mut depth = 0;
+        for (id, c) in self.rest().char_indices() {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                '>' if depth == 0 => {
+                    self.pos += id + 1;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        Err(String::from("MusicXML Ends Early"))
+    }
+
+    /// Name and attributes after a `<`, and whether the tag closes itself
+    fn start_tag(&mut self) -> Result<(Element, bool), String> {
+        let mut element = Element {
+            name: self.name().to_string(),
+            ..Default::default()
+        };
+
+        loop {
+            self.pos += self.rest().len() - self.rest().trim_start().len();
+
+            if self.rest().starts_with("/>") {
+                self.pos += 2;
+                return Ok((element, true));
+            }
+            if self.rest().starts_with('>') {
+                self.pos += 1;
+                return Ok((element, false));
+            }
+
+            let key = self.name().to_string();
+            if key.is_empty() {
+                return Err(String::from("MusicXML Has A Broken Tag"));
+            }
+
+            let rest = self.rest().trim_start();
+            let rest = match rest.strip_prefix('=') {
+                Some(rest) => rest.trim_start(),
+                None => return Err(String::from("MusicXML Has A Broken Tag")),
+            };
+            let quote = match rest.chars().next() {
+                Some(quote @ ('"' | '\'')) => quote,
+                _ => return Err(String::from("MusicXML Has A Broken Tag")),
+            };
+            self.pos = self.text.len() - rest.len() + 1;
+
+            let value = self.until(&quote.to_string())?;
+            element.attributes.push((key, unescape(value)));
+        }
+    }
+
+    fn name(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '='))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+}
+
+/// Replaces the predefined entities and character references
+fn unescape(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+            }
+        };
+
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            // Unknown entity, kept as it is
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_elements_and_text() {
+        let root = parse(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <!DOCTYPE score-partwise PUBLIC "-//Recordare//DTD MusicXML 4.0 Partwise//EN" "x.dtd">
+            <!-- comment -->
+            <score id='1'><title>Rock &amp; Roll &#x263A;</title><empty a = "b"/></score>"#,
+        )
+        .unwrap();
+
+        assert_eq!(root.name, "score");
+        assert_eq!(root.attribute("id"), Some("1"));
+        assert_eq!(
+            root.child_text("title").as_deref(),
+            Some("Rock & Roll \u{263A}")
+        );
+        assert_eq!(root.child("empty").unwrap().attribute("a"), Some("b"));
+        assert!(parse("<a><b></a>").is_err());
+    }
+}
@@ -2,7 +2,7 @@ use std::{collections::HashSet, time::Duration};
 
 use midly::MidiMessage;
 
-use crate::{MidiEvent, MidiTrack};
+use crate::{MidiEvent, MidiTrack, SysExEvent};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ActiveNote {
@@ -16,6 +16,7 @@ pub struct PlaybackState {
     running: Duration,
     leed_in: Duration,
     seen_events: usize,
+    seen_sysex_events: usize,
 
     first_note_start: Duration,
     last_note_end: Duration,
@@ -41,6 +42,7 @@ impl PlaybackState {
             running: Duration::ZERO,
             leed_in,
             seen_events: 0,
+            seen_sysex_events: 0,
 
             first_note_start,
             last_note_end,
@@ -82,6 +84,18 @@ impl PlaybackState {
             .collect()
     }
 
+    /// SysEx events reached since the last call, driven by the clock advanced in `update`
+    pub fn sysex_update<'a>(&mut self, track: &'a MidiTrack) -> &'a [SysExEvent] {
+        let start = self.seen_sysex_events.min(track.sysex_events.len());
+        let count = track.sysex_events[start..]
+            .iter()
+            .take_while(|event| event.timestamp + self.leed_in <= self.running)
+            .count();
+
+        self.seen_sysex_events = start + count;
+        &track.sysex_events[start..start + count]
+    }
+
     pub fn is_paused(&self) -> bool {
         self.is_paused
     }
@@ -130,6 +144,7 @@ impl PlaybackState {
     pub fn reset(&mut self) {
         self.running = Duration::ZERO;
         self.seen_events = 0;
+        self.seen_sysex_events = 0;
         self.active_notes.clear();
     }
 }
@@ -0,0 +1,186 @@
+//! Live input written out as a Standard MIDI File
+
+use std::{collections::HashSet, time::Duration};
+
+use midly::{
+    num::{u15, u24, u28, u4, u7},
+    Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
+};
+
+const PULSES_PER_QUARTER_NOTE: u16 = 480;
+/// 120 BPM, the file has no tempo of its own to follow
+const MICROS_PER_QUARTER_NOTE: u32 = 500_000;
+
+/// Events played live, stamped with the time since the start of the recording
+#[derive(Debug, Default, Clone)]
+pub struct Recording {
+    events: Vec<(Duration, u8, MidiMessage)>,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, time: Duration, channel: u8, message: MidiMessage) {
+        self.events.push((time, channel, message));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Time from the first note to the last one, zero without notes
+    pub fn notes_length(&self) -> Duration {
+        let times = self.events.iter().filter_map(|(time, _, message)| {
+            matches!(
+                message,
+                MidiMessage::NoteOn { .. } | MidiMessage::NoteOff { .. }
+            )
+            .then_some(*time)
+        });
+        let (first, last) = times.fold((Duration::MAX, Duration::ZERO), |(first, last), time| {
+            (first.min(time), last.max(time))
+        });
+        last.saturating_sub(first)
+    }
+
+    /// Single track SMF of the recording, notes still held at the end are released there
+    pub fn to_bytes(&self) -> Vec<u8> {
+        write_smf(Format::SingleTrack, vec![self.track(true)])
+    }
+
+    /// SMF with a track for each of `layers`, all of them starting together,
+    /// notes still held at the end of a layer are released there
+    pub fn layers_to_bytes(layers: &[Recording]) -> Vec<u8> {
+        let tracks = layers
+            .iter()
+            .enumerate()
+            // The tempo goes in the first track, the others follow it
+            .map(|(id, layer)| layer.track(id == 0))
+            .collect();
+        write_smf(Format::Parallel, tracks)
+    }
+
+    fn track(&self, tempo: bool) -> Vec<TrackEvent<'static>> {
+        let mut events = self.events.clone();
+        // Stable, events at the same time keep the order they were played in
+        events.sort_by_key(|(time, _, _)| *time);
+
+        let end = events.last().map_or(Duration::ZERO, |(time, _, _)| *time);
+        let mut held = HashSet::new();
+        for (_, channel, message) in events.iter() {
+            match *message {
+                MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                    held.insert((*channel, key.as_int()));
+                }
+                MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                    held.remove(&(*channel, key.as_int()));
+                }
+                _ => {}
+            }
+        }
+        let mut held: Vec<_> = held.into_iter().collect();
+        held.sort_unstable();
+        for (channel, key) in held {
+            let message = MidiMessage::NoteOff {
+                key: u7::new(key),
+                vel: u7::new(0),
+            };
+            events.push((end, channel, message));
+        }
+
+        let ticks_per_sec =
+            PULSES_PER_QUARTER_NOTE as f64 * 1_000_000.0 / MICROS_PER_QUARTER_NOTE as f64;
+
+        let mut track = Vec::new();
+        if tempo {
+            track.push(TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(MICROS_PER_QUARTER_NOTE))),
+            });
+        }
+
+        let mut last_tick = 0;
+        for (time, channel, message) in events {
+            let tick = (time.as_secs_f64() * ticks_per_sec).round() as u32;
+            track.push(TrackEvent {
+                delta: u28::new(tick - last_tick),
+                kind: TrackEventKind::Midi {
+                    channel: u4::new(channel),
+                    message,
+                },
+            });
+            last_tick = tick;
+        }
+
+        track.push(TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+
+        track
+    }
+}
+
+fn write_smf(format: Format, tracks: Vec<Vec<TrackEvent<'static>>>) -> Vec<u8> {
+    let smf = Smf {
+        header: Header::new(format, Timing::Metrical(u15::new(PULSES_PER_QUARTER_NOTE))),
+        tracks,
+    };
+
+    let mut data = Vec::new();
+    // Writing to memory can't fail
+    smf.write_std(&mut data).ok();
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Midi;
+
+    #[test]
+    fn recording_round_trips() {
+        let note = |key, on| {
+            let (key, vel) = (u7::new(key), u7::new(if on { 90 } else { 0 }));
+            if on {
+                MidiMessage::NoteOn { key, vel }
+            } else {
+                MidiMessage::NoteOff { key, vel }
+            }
+        };
+
+        let mut recording = Recording::new();
+        recording.push(Duration::from_millis(250), 0, note(60, true));
+        recording.push(Duration::from_millis(500), 0, note(64, true));
+        // 64 is still held when the recording stops
+        recording.push(Duration::from_millis(750), 0, note(60, false));
+
+        let midi = Midi::from_bytes(&recording.to_bytes()).unwrap();
+        let notes: Vec<_> = midi
+            .merged_track
+            .notes
+            .iter()
+            .map(|note| (note.note, note.start.as_millis(), note.end.as_millis()))
+            .collect();
+
+        assert_eq!(notes, vec![(60, 250, 750), (64, 500, 750)]);
+        assert_eq!(recording.notes_length(), Duration::from_millis(500));
+        assert_eq!(Recording::new().notes_length(), Duration::ZERO);
+
+        let mut layer = Recording::new();
+        layer.push(Duration::from_millis(0), 0, note(67, true));
+        layer.push(Duration::from_millis(500), 0, note(67, false));
+
+        let midi = Midi::from_bytes(&Recording::layers_to_bytes(&[recording, layer])).unwrap();
+        let notes: Vec<_> = midi
+            .merged_track
+            .notes
+            .iter()
+            .map(|note| (note.note, note.track_id, note.start.as_millis()))
+            .collect();
+
+        assert_eq!(notes, vec![(67, 1, 0), (60, 0, 250), (64, 0, 500)]);
+    }
+}
@@ -0,0 +1,69 @@
+//! Synthetic songs for benchmarks
+
+use midly::{
+    num::{u15, u28, u4, u7},
+    Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
+};
+
+use crate::Midi;
+
+const PULSES_PER_QUARTER_NOTE: u16 = 480;
+
+/// Dense "black MIDI" like song, every track plays a 32nd note stream with random keys
+pub fn black_midi(tracks: usize, notes_per_track: usize) -> Midi {
+    // Fixed seed, so every run measures the same song
+    let mut seed: u32 = 0x1234_5678;
+    let mut random_key = move || {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        21 + (seed >> 16) % 88
+    };
+
+    let step = PULSES_PER_QUARTER_NOTE as u32 / 8;
+
+    let tracks = (0..tracks)
+        .map(|track_id| {
+            // Skip the drum channel
+            let channel =
+                u4::new([0, 1, 2, 3, 4, 5, 6, 7, 8, 10, 11, 12, 13, 14, 15][track_id % 15]);
+            let event = |delta: u32, message| TrackEvent {
+                delta: u28::new(delta),
+                kind: TrackEventKind::Midi { channel, message },
+            };
+
+            let mut events = Vec::with_capacity(notes_per_track * 2 + 1);
+            for _ in 0..notes_per_track {
+                let key = u7::new(random_key() as u8);
+                events.push(event(
+                    0,
+                    MidiMessage::NoteOn {
+                        key,
+                        vel: u7::new(100),
+                    },
+                ));
+                events.push(event(
+                    step,
+                    MidiMessage::NoteOff {
+                        key,
+                        vel: u7::new(0),
+                    },
+                ));
+            }
+            events.push(TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+            });
+
+            events
+        })
+        .collect();
+
+    let smf = Smf {
+        header: Header::new(
+            Format::Parallel,
+            Timing::Metrical(u15::new(PULSES_PER_QUARTER_NOTE)),
+        ),
+        tracks,
+    };
+
+    Midi::from_smf(&smf).unwrap()
+}
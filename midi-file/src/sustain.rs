@@ -0,0 +1,147 @@
+//! How long notes are heard with the sustain pedal, controller 64, holding them
+
+use std::{collections::HashMap, time::Duration};
+
+use midly::MidiMessage;
+
+use crate::{MidiEvent, MidiNote};
+
+/// Controller number of the sustain pedal
+pub const SUSTAIN_CONTROLLER: u8 = 64;
+
+/// Whether a sustain pedal value holds the notes, `64` and up is down
+pub fn is_down(value: u8) -> bool {
+    value >= 64
+}
+
+/// Where each of `notes` stops sounding, in the same order. A note released while the
+/// pedal of its channel is down lasts until the pedal is let go, or until its key is
+/// struck again. `events` are sorted by time
+pub fn sustained_ends(notes: &[MidiNote], events: &[MidiEvent]) -> Vec<Duration> {
+    let end = notes.iter().map(|note| note.end).max().unwrap_or_default();
+
+    // Pedal down spans of each channel
+    let mut spans: HashMap<u8, Vec<(Duration, Duration)>> = HashMap::new();
+    let mut down: HashMap<u8, Duration> = HashMap::new();
+    for event in events.iter() {
+        if let MidiMessage::Controller { controller, value } = event.message {
+            if controller.as_int() != SUSTAIN_CONTROLLER {
+                continue;
+            }
+
+            if is_down(value.as_int()) {
+                down.entry(event.channel).or_insert(event.timestamp);
+            } else if let Some(start) = down.remove(&event.channel) {
+                spans
+                    .entry(event.channel)
+                    .or_default()
+                    .push((start, event.timestamp));
+            }
+        }
+    }
+    // Never let go of, held to the end of the song
+    for (channel, start) in down {
+        spans
+            .entry(channel)
+            .or_default()
+            .push((start, end.max(start)));
+    }
+
+    // Starts of every key, to cut notes short when it is struck again
+    let mut strikes: HashMap<(u8, u8), Vec<Duration>> = HashMap::new();
+    for note in notes.iter() {
+        strikes
+            .entry((note.channel, note.note))
+            .or_default()
+            .push(note.start);
+    }
+    for starts in strikes.values_mut() {
+        starts.sort_unstable();
+    }
+
+    notes
+        .iter()
+        .map(|note| {
+            let released = spans.get(&note.channel).and_then(|spans| {
+                spans
+                    .iter()
+                    .find(|(start, end)| (*start..*end).contains(&note.end))
+                    .map(|(_, end)| *end)
+            });
+            let released = match released {
+                Some(released) => released,
+                None => return note.end,
+            };
+
+            let next = strikes
+                .get(&(note.channel, note.note))
+                .and_then(|starts| starts.iter().find(|start| **start > note.start));
+            match next {
+                Some(next) => released.min(*next).max(note.end),
+                None => released,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midly::num::u7;
+
+    fn note(key: u8, start: u64, end: u64) -> MidiNote {
+        MidiNote {
+            start: Duration::from_millis(start),
+            end: Duration::from_millis(end),
+            duration: Duration::from_millis(end - start),
+            note: key,
+            velocity: 100,
+            channel: 0,
+            track_id: 0,
+            track_color_id: 0,
+            id: 0,
+        }
+    }
+
+    fn pedal(time: u64, value: u8) -> MidiEvent {
+        MidiEvent {
+            channel: 0,
+            delta: 0,
+            timestamp: Duration::from_millis(time),
+            message: MidiMessage::Controller {
+                controller: u7::new(SUSTAIN_CONTROLLER),
+                value: u7::new(value),
+            },
+            track_id: 0,
+            track_color_id: 0,
+        }
+    }
+
+    #[test]
+    fn pedal_holds_notes() {
+        let ms = Duration::from_millis;
+        let notes = [
+            note(60, 0, 100),
+            note(64, 0, 600),
+            note(60, 300, 400),
+            note(67, 700, 760),
+            note(72, 700, 900),
+        ];
+        let events = [pedal(50, 127), pedal(500, 0), pedal(750, 100)];
+
+        let ends = sustained_ends(&notes, &events);
+        // Held until the key is struck again, then until the pedal is let go
+        assert_eq!(ends[0], ms(300));
+        assert_eq!(ends[2], ms(500));
+        // Released after the pedal
+        assert_eq!(ends[1], ms(600));
+        // Pedal never let go of, held until the last note ends
+        assert_eq!(ends[3], ms(900));
+        assert_eq!(ends[4], ms(900));
+
+        assert_eq!(
+            sustained_ends(&notes, &[]),
+            [ms(100), ms(600), ms(400), ms(760), ms(900)]
+        );
+    }
+}
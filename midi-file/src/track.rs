@@ -17,6 +17,14 @@ pub struct MidiEvent {
     pub track_color_id: usize,
 }
 
+#[derive(Debug, Clone)]
+pub struct SysExEvent {
+    pub timestamp: Duration,
+    /// Complete message, including leading `0xF0`
+    pub data: Vec<u8>,
+    pub track_id: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct TempoEvent {
     pub absolute_pulses: u64,
@@ -44,6 +52,7 @@ pub struct MidiTrack {
     pub notes: Vec<MidiNote>,
 
     pub events: Vec<MidiEvent>,
+    pub sysex_events: Vec<SysExEvent>,
 
     pub track_id: usize,
     pub track_color_id: usize,
@@ -101,13 +110,73 @@ impl MidiTrack {
             })
             .collect();
 
+        let sysex_events = build_sysex_events(
+            track_id,
+            tempo_events,
+            track_events,
+            pulses_per_quarter_note,
+        );
+
         Self {
             track_id,
             track_color_id,
             notes,
             events,
+            sysex_events,
         }
     }
+
+    /// Notes that are sounding at given time, expects notes to be sorted by start time
+    pub fn notes_at(&self, time: Duration) -> impl Iterator<Item = &MidiNote> {
+        self.notes
+            .iter()
+            .take_while(move |note| note.start <= time)
+            .filter(move |note| note.end > time)
+    }
+
+    /// First program the track asks for, GM defaults to Acoustic Grand Piano
+    pub fn program(&self) -> u8 {
+        self.events
+            .iter()
+            .find_map(|event| match event.message {
+                MidiMessage::ProgramChange { program } => Some(program.as_int()),
+                _ => None,
+            })
+            .unwrap_or(0)
+    }
+}
+
+fn build_sysex_events(
+    track_id: usize,
+    tempo_events: &TempoTrack,
+    track_events: &[TrackEvent],
+    pulses_per_quarter_note: u16,
+) -> Vec<SysExEvent> {
+    let mut pulses: u64 = 0;
+    track_events
+        .iter()
+        .filter_map(|event| {
+            pulses += event.delta.as_int() as u64;
+            match event.kind {
+                TrackEventKind::SysEx(bytes) => {
+                    let mut data = Vec::with_capacity(bytes.len() + 1);
+                    data.push(0xF0);
+                    data.extend_from_slice(bytes);
+
+                    Some(SysExEvent {
+                        timestamp: pulses_to_duration(
+                            tempo_events,
+                            pulses,
+                            pulses_per_quarter_note,
+                        ),
+                        data,
+                        track_id,
+                    })
+                }
+                _ => None,
+            }
+        })
+        .collect()
 }
 
 fn build_notes(
@@ -0,0 +1,394 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use neothesia_core::{
+    config::{ClickChannel, ClickTrack, LoudnessNormalization},
+    utils::click,
+};
+
+use crate::{flac::FlacWriter, loudness};
+
+const SAMPLE_RATE: u32 = 44100;
+/// Frames rendered by the synth at once, between events
+const BLOCK_SIZE: usize = 512;
+/// Lets the release of the last notes ring out, in seconds
+const TAIL: f64 = 2.0;
+/// Length of a single click, in frames
+const CLICK_LEN: u64 = (click::CLICK_LEN * SAMPLE_RATE as f32) as u64;
+
+/// Renders the song with the SoundFont from the settings into a 16 bit stereo WAV or FLAC file,
+/// after the extension of `path`, with the click on the beats of the song
+/// and loudness normalization when enabled
+pub fn render_audio(
+    midi: &midi_file::Midi,
+    soundfont: &Path,
+    click: &ClickTrack,
+    normalization: &LoudnessNormalization,
+    path: &Path,
+) -> Result<(), String> {
+    let mut synth = oxisynth::Synth::new(oxisynth::SynthDescriptor {
+        sample_rate: SAMPLE_RATE as f32,
+        gain: 1.0,
+        ..Default::default()
+    })
+    .map_err(|err| format!("{err:?}"))?;
+
+    let font = File::open(soundfont)
+        .map_err(|err| format!("{}: {err}", soundfont.display()))
+        .and_then(|mut file| {
+            oxisynth::SoundFont::load(&mut file).map_err(|err| format!("{err:?}"))
+        })?;
+    synth.add_font(font, true);
+
+    let mut output = if normalization.enabled {
+        Output::measured(path)
+    } else {
+        Encoder::create(path).map(Output::Encoded)
+    }
+    .map_err(|err| err.to_string())?;
+
+    let mut rendered: u64 = 0;
+    let mut click = click.enabled.then(|| Click::new(midi, click));
+
+    let mut render_until = |synth: &mut oxisynth::Synth, frame: u64| -> std::io::Result<()> {
+        let mut buff = [0.0f32; BLOCK_SIZE * 2];
+        while rendered < frame {
+            let frames = (frame - rendered).min(BLOCK_SIZE as u64) as usize;
+            let buff = &mut buff[..frames * 2];
+            synth.write(&mut *buff).ok();
+            if let Some(click) = click.as_mut() {
+                click.mix(rendered, buff);
+            }
+            output.write_samples(buff)?;
+            rendered += frames as u64;
+        }
+        Ok(())
+    };
+
+    for event in midi.merged_track.events.iter() {
+        let frame = (event.timestamp.as_secs_f64() * SAMPLE_RATE as f64) as u64;
+        render_until(&mut synth, frame).map_err(|err| err.to_string())?;
+        synth.send_event(oxisynth_event(event)).ok();
+    }
+
+    let end = midi
+        .merged_track
+        .events
+        .last()
+        .map_or(0.0, |event| event.timestamp.as_secs_f64());
+    render_until(&mut synth, ((end + TAIL) * SAMPLE_RATE as f64) as u64)
+        .map_err(|err| err.to_string())?;
+
+    output
+        .finish(path, normalization)
+        .map_err(|err| err.to_string())
+}
+
+/// Where rendered samples go, straight into the file unless they have to be measured first
+enum Output {
+    Encoded(Encoder),
+    /// Full precision samples wait in a temporary file until the gain is known
+    Measured {
+        raw: BufWriter<File>,
+        raw_path: PathBuf,
+        meter: loudness::Meter,
+    },
+}
+
+impl Output {
+    fn measured(path: &Path) -> std::io::Result<Self> {
+        let raw_path = path.with_extension("raw");
+        Ok(Self::Measured {
+            raw: BufWriter::new(File::create(&raw_path)?),
+            raw_path,
+            meter: loudness::Meter::new(SAMPLE_RATE),
+        })
+    }
+
+    fn write_samples(&mut self, samples: &[f32]) -> std::io::Result<()> {
+        match self {
+            Self::Encoded(encoder) => encoder.write_samples(samples),
+            Self::Measured { raw, meter, .. } => {
+                meter.push(samples);
+                for sample in samples {
+                    raw.write_all(&sample.to_le_bytes())?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self, path: &Path, normalization: &LoudnessNormalization) -> std::io::Result<()> {
+        let (raw, raw_path, meter) = match self {
+            Self::Encoded(encoder) => return encoder.finish(),
+            Self::Measured {
+                raw,
+                raw_path,
+                meter,
+            } => (raw, raw_path, meter),
+        };
+        // Flushes and closes the file
+        drop(raw.into_inner()?);
+
+        let gain = normalization_gain(&meter, normalization);
+        log::info!(
+            "Loudness {:.1} LUFS, gain {:+.1} dB",
+            meter.integrated().unwrap_or(f64::NEG_INFINITY),
+            20.0 * gain.log10()
+        );
+
+        let res = write_scaled(&raw_path, path, gain);
+        std::fs::remove_file(&raw_path).ok();
+        res
+    }
+}
+
+/// Converts the raw samples at `raw_path` into the file at `path`, multiplied by `gain`
+fn write_scaled(raw_path: &Path, path: &Path, gain: f32) -> std::io::Result<()> {
+    let mut encoder = Encoder::create(path)?;
+    let mut raw = BufReader::new(File::open(raw_path)?);
+
+    let mut sample = [0; 4];
+    let mut samples = Vec::with_capacity(BLOCK_SIZE * 2);
+    loop {
+        samples.clear();
+        while samples.len() < BLOCK_SIZE * 2 {
+            match raw.read_exact(&mut sample) {
+                Ok(()) => samples.push(f32::from_le_bytes(sample) * gain),
+                Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        if samples.is_empty() {
+            break;
+        }
+        encoder.write_samples(&samples)?;
+    }
+
+    encoder.finish()
+}
+
+/// Gain that brings the audio to the target loudness, lowered to stay under the peak ceiling
+fn normalization_gain(meter: &loudness::Meter, normalization: &LoudnessNormalization) -> f32 {
+    let loudness = match meter.integrated() {
+        Some(loudness) => loudness as f32,
+        // Silence stays silent
+        None => return 1.0,
+    };
+
+    let gain = 10f32.powf((normalization.target - loudness) / 20.0);
+    let ceiling = 10f32.powf(normalization.peak_ceiling.min(0.0) / 20.0);
+
+    if meter.peak() * gain > ceiling {
+        ceiling / meter.peak()
+    } else {
+        gain
+    }
+}
+
+/// Metronome following the measures of the song
+struct Click {
+    /// Frame of every beat, and whether it's accented
+    beats: Vec<(u64, bool)>,
+    /// First beat that may still be ringing
+    next: usize,
+    config: ClickTrack,
+}
+
+impl Click {
+    fn new(midi: &midi_file::Midi, config: &ClickTrack) -> Self {
+        let mut beats = Vec::new();
+        for measure in midi.analysis.measures.iter() {
+            for (id, beat) in measure.beats.iter().enumerate() {
+                let frame = (beat.as_secs_f64() * SAMPLE_RATE as f64) as u64;
+                beats.push((frame, config.accent && id == 0));
+            }
+        }
+
+        Self {
+            beats,
+            next: 0,
+            config: config.clone(),
+        }
+    }
+
+    /// Mixes the clicks into interleaved stereo `buff`, which starts at frame `start`
+    fn mix(&mut self, start: u64, buff: &mut [f32]) {
+        let frames = buff.len() as u64 / 2;
+        let volume = self.config.volume.clamp(0.0, 1.0);
+
+        // Music moves to the side the click leaves free
+        let click_side = match self.config.channel {
+            ClickChannel::Both => None,
+            ClickChannel::Left => Some(0),
+            ClickChannel::Right => Some(1),
+        };
+        if let Some(side) = click_side {
+            for frame in buff.chunks_exact_mut(2) {
+                let music = (frame[0] + frame[1]) / 2.0;
+                frame[side] = 0.0;
+                frame[1 - side] = music;
+            }
+        }
+
+        while self
+            .beats
+            .get(self.next)
+            .map_or(false, |(beat, _)| beat + CLICK_LEN <= start)
+        {
+            self.next += 1;
+        }
+
+        for (beat, accent) in self.beats[self.next..]
+            .iter()
+            .take_while(|(beat, _)| *beat < start + frames)
+        {
+            let from = (*beat).max(start) - start;
+            let to = (beat + CLICK_LEN).min(start + frames) - start;
+
+            for frame in from..to {
+                let t = (start + frame - beat) as f32 / SAMPLE_RATE as f32;
+                let sample = click::click_sample(t, *accent) * volume;
+                let id = frame as usize * 2;
+                match click_side {
+                    Some(side) => buff[id + side] += sample,
+                    None => {
+                        buff[id] += sample;
+                        buff[id + 1] += sample;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// File format picked by the extension, WAV unless it's `.flac`
+enum Encoder {
+    Wav(WavWriter),
+    Flac(FlacWriter),
+}
+
+impl Encoder {
+    fn create(path: &Path) -> std::io::Result<Self> {
+        let flac = path
+            .extension()
+            .map_or(false, |ext| ext.eq_ignore_ascii_case("flac"));
+
+        if flac {
+            FlacWriter::create(path, SAMPLE_RATE).map(Self::Flac)
+        } else {
+            WavWriter::create(path).map(Self::Wav)
+        }
+    }
+
+    fn write_samples(&mut self, samples: &[f32]) -> std::io::Result<()> {
+        match self {
+            Self::Wav(wav) => wav.write_samples(samples),
+            Self::Flac(flac) => flac.write_samples(samples),
+        }
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            Self::Wav(wav) => wav.finish(),
+            Self::Flac(flac) => flac.finish(),
+        }
+    }
+}
+
+/// Writes the data chunk as it comes, sizes in the header are filled in at the end
+struct WavWriter {
+    file: BufWriter<File>,
+    data_len: u32,
+}
+
+impl WavWriter {
+    const CHANNELS: u16 = 2;
+    const BITS_PER_SAMPLE: u16 = 16;
+
+    fn create(path: &Path) -> std::io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        let block_align = Self::CHANNELS * Self::BITS_PER_SAMPLE / 8;
+
+        file.write_all(b"RIFF")?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+
+        file.write_all(b"fmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        // PCM
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&Self::CHANNELS.to_le_bytes())?;
+        file.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        file.write_all(&(SAMPLE_RATE * block_align as u32).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&Self::BITS_PER_SAMPLE.to_le_bytes())?;
+
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self { file, data_len: 0 })
+    }
+
+    fn write_samples(&mut self, samples: &[f32]) -> std::io::Result<()> {
+        for sample in samples {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_len = self.data_len.saturating_add(samples.len() as u32 * 2);
+        Ok(())
+    }
+
+    fn finish(mut self) -> std::io::Result<()> {
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file
+            .write_all(&(36u32.saturating_add(self.data_len)).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&self.data_len.to_le_bytes())?;
+        self.file.flush()
+    }
+}
+
+fn oxisynth_event(msg: &midi_file::MidiEvent) -> oxisynth::MidiEvent {
+    use midi_file::midly::MidiMessage;
+
+    let channel = msg.channel;
+    match msg.message {
+        MidiMessage::NoteOff { key, .. } => oxisynth::MidiEvent::NoteOff {
+            channel,
+            key: key.as_int(),
+        },
+        MidiMessage::NoteOn { key, vel } => oxisynth::MidiEvent::NoteOn {
+            channel,
+            key: key.as_int(),
+            vel: vel.as_int(),
+        },
+        MidiMessage::Aftertouch { key, vel } => oxisynth::MidiEvent::PolyphonicKeyPressure {
+            channel,
+            key: key.as_int(),
+            value: vel.as_int(),
+        },
+        MidiMessage::Controller { controller, value } => oxisynth::MidiEvent::ControlChange {
+            channel,
+            ctrl: controller.as_int(),
+            value: value.as_int(),
+        },
+        MidiMessage::ProgramChange { program } => oxisynth::MidiEvent::ProgramChange {
+            channel,
+            program_id: program.as_int(),
+        },
+        MidiMessage::ChannelAftertouch { vel } => oxisynth::MidiEvent::ChannelPressure {
+            channel,
+            value: vel.as_int(),
+        },
+        MidiMessage::PitchBend { bend } => oxisynth::MidiEvent::PitchBend {
+            channel,
+            value: bend.0.as_int(),
+        },
+    }
+}
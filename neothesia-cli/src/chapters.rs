@@ -0,0 +1,265 @@
+//! Chapters and note name subtitles of exported videos.
+//!
+//! They are written next to the video, in the formats YouTube takes on upload, and muxed
+//! into the mp4 with `ffmpeg` when it's installed, together with the audio of the song
+
+use std::{fmt::Write as _, path::Path, process::Command, time::Duration};
+
+/// Notes starting within this window share a subtitle
+const CHORD_WINDOW: Duration = Duration::from_millis(30);
+/// Longest time a subtitle stays up
+const MAX_CUE: Duration = Duration::from_secs(2);
+
+/// Where things are in the encoded video
+pub struct Timeline {
+    /// Time the song starts at, after the intro card and the lead-in
+    pub song_start: Duration,
+    pub outro_start: Option<Duration>,
+    pub end: Duration,
+}
+
+pub struct Chapter {
+    pub start: Duration,
+    pub title: String,
+}
+
+/// A chapter at every marker of the song, empty when it has none
+pub fn chapters(midi: &midi_file::Midi, timeline: &Timeline, has_intro: bool) -> Vec<Chapter> {
+    let markers = &midi.analysis.markers;
+    if markers.is_empty() {
+        return Vec::new();
+    }
+
+    let mut chapters = Vec::new();
+    if has_intro {
+        chapters.push(Chapter {
+            start: Duration::ZERO,
+            title: String::from("Intro"),
+        });
+    }
+
+    for marker in markers.iter() {
+        let start = timeline.song_start + marker.time;
+        if chapters.is_empty() {
+            // Videos have to start with a chapter, the lead-in belongs to the first one
+            let title = if marker.time.is_zero() {
+                marker.name.clone()
+            } else {
+                midi.metadata
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| midi.name.clone())
+            };
+            chapters.push(Chapter {
+                start: Duration::ZERO,
+                title,
+            });
+            if marker.time.is_zero() {
+                continue;
+            }
+        }
+
+        chapters.push(Chapter {
+            start,
+            title: marker.name.clone(),
+        });
+    }
+
+    if let Some(start) = timeline.outro_start {
+        chapters.push(Chapter {
+            start,
+            title: String::from("Credits"),
+        });
+    }
+
+    chapters
+}
+
+/// Timestamps to paste into a YouTube description, eg. `1:05 Chorus`
+pub fn youtube_description(chapters: &[Chapter]) -> String {
+    let mut out = String::new();
+    for chapter in chapters {
+        let secs = chapter.start.as_secs();
+        let time = if secs >= 3600 {
+            format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+        } else {
+            format!("{}:{:02}", secs / 60, secs % 60)
+        };
+        writeln!(out, "{time} {}", chapter.title).ok();
+    }
+    out
+}
+
+/// Chapters in the metadata format of ffmpeg
+fn ffmetadata(chapters: &[Chapter], end: Duration) -> String {
+    let escape = |text: &str| {
+        let mut out = String::with_capacity(text.len());
+        for c in text.chars() {
+            if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        out
+    };
+
+    let mut out = String::from(";FFMETADATA1\n");
+    for (id, chapter) in chapters.iter().enumerate() {
+        let chapter_end = chapters.get(id + 1).map_or(end, |next| next.start);
+        writeln!(out, "[CHAPTER]").ok();
+        writeln!(out, "TIMEBASE=1/1000").ok();
+        writeln!(out, "START={}", chapter.start.as_millis()).ok();
+        writeln!(out, "END={}", chapter_end.as_millis()).ok();
+        writeln!(out, "title={}", escape(&chapter.title)).ok();
+    }
+    out
+}
+
+/// SubRip subtitles with the names of the notes, a cue for every chord
+pub fn note_name_subtitles(midi: &midi_file::Midi, timeline: &Timeline) -> String {
+    let notes: Vec<&midi_file::MidiNote> = midi
+        .merged_track
+        .notes
+        .iter()
+        .filter(|note| note.channel != 9)
+        .collect();
+
+    let mut chords: Vec<Vec<&midi_file::MidiNote>> = Vec::new();
+    for note in notes {
+        match chords.last_mut() {
+            Some(chord) if note.start - chord[0].start <= CHORD_WINDOW => chord.push(note),
+            _ => chords.push(vec![note]),
+        }
+    }
+
+    let mut out = String::new();
+    for (id, chord) in chords.iter().enumerate() {
+        let start = chord[0].start;
+        let longest = chord
+            .iter()
+            .map(|note| note.duration)
+            .max()
+            .unwrap_or_default();
+        let mut end = start + longest.min(MAX_CUE);
+        if let Some(next) = chords.get(id + 1) {
+            end = end.min(next[0].start);
+        }
+
+        let mut keys: Vec<u8> = chord.iter().map(|note| note.note).collect();
+        keys.sort_unstable();
+        keys.dedup();
+        let names: Vec<String> = keys
+            .into_iter()
+            .map(|note| piano_math::names::letter_name(note, false))
+            .collect();
+
+        writeln!(out, "{}", id + 1).ok();
+        writeln!(
+            out,
+            "{} --> {}",
+            srt_time(timeline.song_start + start),
+            srt_time(timeline.song_start + end)
+        )
+        .ok();
+        writeln!(out, "{}\n", names.join(" ")).ok();
+    }
+    out
+}
+
+fn srt_time(time: Duration) -> String {
+    let millis = time.as_millis();
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Writes the chapters and subtitles next to `video`, then muxes them into it.
+/// `audio` is a file and the time into the video it starts at
+pub fn embed(
+    video: &Path,
+    chapters: &[Chapter],
+    subtitles: Option<&str>,
+    audio: Option<(&Path, Duration)>,
+    end: Duration,
+) -> Result<(), String> {
+    if chapters.is_empty() && subtitles.is_none() && audio.is_none() {
+        return Ok(());
+    }
+
+    let write = |path: &Path, contents: &str| {
+        std::fs::write(path, contents).map_err(|err| format!("{}: {err}", path.display()))
+    };
+
+    // Output options have to come after every input
+    let mut inputs = vec![video.to_owned()];
+    let mut options: Vec<String> = ["-map", "0", "-c", "copy"].map(String::from).into();
+
+    let metadata = video.with_extension("ffmetadata");
+    if !chapters.is_empty() {
+        write(
+            &video.with_extension("chapters.txt"),
+            &youtube_description(chapters),
+        )?;
+        write(&metadata, &ffmetadata(chapters, end))?;
+
+        options.extend(["-map_chapters".into(), inputs.len().to_string()]);
+        inputs.push(metadata.clone());
+    }
+
+    if let Some(subtitles) = subtitles {
+        let srt = video.with_extension("srt");
+        write(&srt, subtitles)?;
+
+        options.extend([
+            "-map".into(),
+            format!("{}:s", inputs.len()),
+            "-c:s".into(),
+            "mov_text".into(),
+        ]);
+        inputs.push(srt);
+    }
+
+    let mut command = Command::new("ffmpeg");
+    command.args(["-y", "-nostdin", "-loglevel", "error"]);
+    for input in inputs.iter() {
+        command.arg("-i").arg(input);
+    }
+
+    if let Some((audio, start)) = audio {
+        options.extend([
+            "-map".into(),
+            format!("{}:a", inputs.len()),
+            "-c:a".into(),
+            "aac".into(),
+            "-b:a".into(),
+            "192k".into(),
+        ]);
+        // Delays the input that follows it
+        command
+            .arg("-itsoffset")
+            .arg(format!("{:.3}", start.as_secs_f64()));
+        command.arg("-i").arg(audio);
+    }
+    command.args(&options);
+
+    let muxed = video.with_extension("muxed.mp4");
+    command.arg(&muxed);
+
+    let status = command.status();
+    std::fs::remove_file(&metadata).ok();
+
+    match status {
+        Ok(status) if status.success() => {
+            std::fs::rename(&muxed, video).map_err(|err| err.to_string())
+        }
+        Ok(status) => {
+            std::fs::remove_file(&muxed).ok();
+            Err(format!("ffmpeg failed to mux the video, {status}"))
+        }
+        Err(err) => Err(format!("ffmpeg is needed to mux the video, {err}")),
+    }
+}
@@ -0,0 +1,399 @@
+//! Minimal FLAC encoder for 16 bit stereo, fixed predictors with Rice coded residuals
+
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// Frames in a FLAC block, the last one may be shorter
+const BLOCK_SIZE: usize = 4096;
+const CHANNELS: usize = 2;
+const BITS_PER_SAMPLE: u32 = 16;
+/// Largest Rice parameter that fits its 4 bits, `15` means no Rice coding
+const MAX_RICE_PARAM: u32 = 14;
+
+/// Writes frames as full blocks come in, the sample count in the header is filled in at the end
+pub struct FlacWriter {
+    file: BufWriter<File>,
+    sample_rate: u32,
+    /// Interleaved samples that don't make a full block yet
+    pending: Vec<i16>,
+    frame_number: u32,
+    total_frames: u64,
+}
+
+impl FlacWriter {
+    pub fn create(path: &Path, sample_rate: u32) -> std::io::Result<Self> {
+        let mut writer = Self {
+            file: BufWriter::new(File::create(path)?),
+            sample_rate,
+            pending: Vec::with_capacity(BLOCK_SIZE * CHANNELS),
+            frame_number: 0,
+            total_frames: 0,
+        };
+
+        writer.file.write_all(b"fLaC")?;
+        // Last metadata block, STREAMINFO, 34 bytes
+        writer.file.write_all(&[0x80, 0, 0, 34])?;
+        writer.write_stream_info()?;
+
+        Ok(writer)
+    }
+
+    fn write_stream_info(&mut self) -> std::io::Result<()> {
+        let mut bits = BitWriter::default();
+        bits.write(BLOCK_SIZE as u64, 16);
+        bits.write(BLOCK_SIZE as u64, 16);
+        // Smallest and largest frame sizes are unknown
+        bits.write(0, 24);
+        bits.write(0, 24);
+        bits.write(self.sample_rate as u64, 20);
+        bits.write(CHANNELS as u64 - 1, 3);
+        bits.write(BITS_PER_SAMPLE as u64 - 1, 5);
+        bits.write(self.total_frames, 36);
+        // No MD5 signature
+        bits.write(0, 64);
+        bits.write(0, 64);
+
+        self.file.write_all(&bits.into_bytes())
+    }
+
+    /// `samples` are interleaved stereo
+    pub fn write_samples(&mut self, samples: &[f32]) -> std::io::Result<()> {
+        for sample in samples {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.pending.push(sample);
+
+            if self.pending.len() == BLOCK_SIZE * CHANNELS {
+                self.write_frame()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> std::io::Result<()> {
+        if !self.pending.is_empty() {
+            self.write_frame()?;
+        }
+
+        // Right after the 4 byte marker and the 4 byte block header
+        self.file.seek(SeekFrom::Start(8))?;
+        self.write_stream_info()?;
+        self.file.flush()
+    }
+
+    fn write_frame(&mut self) -> std::io::Result<()> {
+        let frames = self.pending.len() / CHANNELS;
+        let mut bits = BitWriter::default();
+
+        bits.write(0b11111111111110, 14);
+        // Reserved, fixed block size
+        bits.write(0, 2);
+        if frames == BLOCK_SIZE {
+            bits.write(0b1100, 4);
+        } else {
+            // Block size follows the header
+            bits.write(0b0111, 4);
+        }
+        bits.write(sample_rate_code(self.sample_rate), 4);
+        // Independent left and right
+        bits.write(CHANNELS as u64 - 1, 4);
+        // 16 bits per sample, reserved
+        bits.write(0b100, 3);
+        bits.write(0, 1);
+        bits.write_utf8(self.frame_number);
+        if frames != BLOCK_SIZE {
+            bits.write(frames as u64 - 1, 16);
+        }
+        if sample_rate_code(self.sample_rate) == 0b1100 {
+            bits.write(self.sample_rate as u64 / 1000, 8);
+        }
+        let crc = crc8(bits.bytes());
+        bits.write(crc as u64, 8);
+
+        for channel in 0..CHANNELS {
+            let samples: Vec<i32> = self
+                .pending
+                .iter()
+                .skip(channel)
+                .step_by(CHANNELS)
+                .map(|sample| *sample as i32)
+                .collect();
+            write_subframe(&mut bits, &samples);
+        }
+
+        let mut frame = bits.into_bytes();
+        let crc = crc16(&frame);
+        frame.extend_from_slice(&crc.to_be_bytes());
+        self.file.write_all(&frame)?;
+
+        self.pending.clear();
+        self.frame_number += 1;
+        self.total_frames += frames as u64;
+        Ok(())
+    }
+}
+
+/// Rates the header has a code for, the rest are written in kHz after it
+fn sample_rate_code(sample_rate: u32) -> u64 {
+    match sample_rate {
+        44100 => 0b1001,
+        48000 => 0b1010,
+        96000 => 0b1011,
+        _ => 0b1100,
+    }
+}
+
+fn write_subframe(bits: &mut BitWriter, samples: &[i32]) {
+    // Silence, or any other constant
+    if samples.iter().all(|sample| *sample == samples[0]) {
+        bits.write(0b0000_0000, 8);
+        bits.write_signed(samples[0], BITS_PER_SAMPLE);
+        return;
+    }
+
+    // Fixed predictor that leaves the smallest residuals
+    let (order, residuals) = (0..=4)
+        .filter(|order| *order < samples.len())
+        .map(|order| (order, fixed_residuals(samples, order)))
+        .min_by_key(|(_, residuals)| {
+            residuals
+                .iter()
+                .map(|r| r.unsigned_abs() as u64)
+                .sum::<u64>()
+        })
+        .unwrap();
+
+    bits.write(0b0001_0000 | ((order as u64) << 1), 8);
+    for sample in samples[..order].iter() {
+        bits.write_signed(*sample, BITS_PER_SAMPLE);
+    }
+
+    write_residuals(bits, &residuals);
+}
+
+/// Rice coded, with the parameter that takes the fewest bits
+fn write_residuals(bits: &mut BitWriter, residuals: &[i32]) {
+    let folded: Vec<u32> = residuals
+        .iter()
+        .map(|r| ((r << 1) ^ (r >> 31)) as u32)
+        .collect();
+    let param = (0..=MAX_RICE_PARAM)
+        .min_by_key(|k| {
+            folded
+                .iter()
+                .map(|u| (u >> k) as u64 + 1 + *k as u64)
+                .sum::<u64>()
+        })
+        .unwrap();
+
+    // Rice coding with 4 bit parameters, a single partition
+    bits.write(0b00, 2);
+    bits.write(0, 4);
+    bits.write(param as u64, 4);
+    for u in folded {
+        bits.write_unary(u >> param);
+        bits.write((u & ((1 << param) - 1)) as u64, param);
+    }
+}
+
+/// What a fixed predictor of `order` leaves after the warm-up samples
+fn fixed_residuals(samples: &[i32], order: usize) -> Vec<i32> {
+    samples
+        .windows(order + 1)
+        .map(|w| match order {
+            0 => w[0],
+            1 => w[1] - w[0],
+            2 => w[2] - 2 * w[1] + w[0],
+            3 => w[3] - 3 * w[2] + 3 * w[1] - w[0],
+            _ => w[4] - 4 * w[3] + 6 * w[2] - 4 * w[1] + w[0],
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    len: u32,
+}
+
+impl BitWriter {
+    /// Writes the low `count` bits of `value`, most significant first
+    fn write(&mut self, value: u64, count: u32) {
+        for bit in (0..count).rev() {
+            self.acc = (self.acc << 1) | ((value >> bit) & 1);
+            self.len += 1;
+            if self.len == 8 {
+                self.bytes.push(self.acc as u8);
+                self.acc = 0;
+                self.len = 0;
+            }
+        }
+    }
+
+    fn write_signed(&mut self, value: i32, count: u32) {
+        self.write(value as u64 & ((1 << count) - 1), count);
+    }
+
+    fn write_unary(&mut self, zeros: u32) {
+        for _ in 0..zeros {
+            self.write(0, 1);
+        }
+        self.write(1, 1);
+    }
+
+    /// Frame number, coded like an UTF-8 character
+    fn write_utf8(&mut self, value: u32) {
+        if value < 0x80 {
+            self.write(value as u64, 8);
+            return;
+        }
+
+        let mut continuation = 1;
+        while value >= 1 << (5 * continuation + 6) {
+            continuation += 1;
+        }
+
+        // As many leading ones as there are bytes
+        let marker = (0xFF00u32 >> (continuation + 1)) & 0xFF;
+        self.write((marker | (value >> (6 * continuation))) as u64, 8);
+        for id in (0..continuation).rev() {
+            self.write((0x80 | ((value >> (6 * id)) & 0x3F)) as u64, 8);
+        }
+    }
+
+    /// Bytes written so far, only whole ones
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Pads the last byte with zeros
+    fn into_bytes(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            let len = self.len;
+            self.write(0, 8 - len);
+        }
+        self.bytes
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(write: impl FnOnce(&mut BitWriter)) -> Vec<u8> {
+        let mut bits = BitWriter::default();
+        write(&mut bits);
+        bits.into_bytes()
+    }
+
+    #[test]
+    fn crc() {
+        // Check values of CRC-8 and CRC-16/BUYPASS, the ones FLAC uses
+        assert_eq!(crc8(b"123456789"), 0xF4);
+        assert_eq!(crc16(b"123456789"), 0xFEE8);
+        assert_eq!(crc8(&[]), 0);
+        assert_eq!(crc16(&[]), 0);
+    }
+
+    #[test]
+    fn utf8_frame_numbers() {
+        let utf8 = |value| bytes(|bits| bits.write_utf8(value));
+        assert_eq!(utf8(0), [0x00]);
+        assert_eq!(utf8(0x7F), [0x7F]);
+        assert_eq!(utf8(0x80), [0xC2, 0x80]);
+        assert_eq!(utf8(0x7FF), [0xDF, 0xBF]);
+        assert_eq!(utf8(0x800), [0xE0, 0xA0, 0x80]);
+        assert_eq!(utf8(0xFFFF), [0xEF, 0xBF, 0xBF]);
+        assert_eq!(utf8(0x10000), [0xF0, 0x90, 0x80, 0x80]);
+
+        for value in [0, 0x7F, 0x80, 0x7FF, 0x800, 0xFFFF, 0x10000, 0x10FFFF] {
+            let bytes = utf8(value);
+            let text = std::str::from_utf8(&bytes).unwrap();
+            assert_eq!(text.chars().next().map(u32::from), Some(value));
+        }
+    }
+
+    #[test]
+    fn rice_coded_residuals() {
+        // Folded to 6, 3, 0 and 10, a parameter of 2 takes the fewest bits
+        let coded = bytes(|bits| write_residuals(bits, &[3, -2, 0, 5]));
+        // 00 0000 0010, then 01 10, 1 11, 1 00 and 001 10 as quotients in unary and remainders
+        assert_eq!(coded, [0x00, 0x9B, 0xC3, 0x00]);
+    }
+
+    #[test]
+    fn ramp_has_no_residuals() {
+        let coded = bytes(|bits| write_subframe(bits, &[0, 1, 2, 3, 4, 5]));
+        // Order 2 predictor, its two warm-up samples, then four zero residuals at parameter 0
+        assert_eq!(coded, [0x14, 0x00, 0x00, 0x00, 0x01, 0x00, 0x3C]);
+    }
+
+    #[test]
+    fn decodes() {
+        let path = std::env::temp_dir().join(format!("neothesia-{}.flac", std::process::id()));
+
+        // More than a block, so the last frame is a short one
+        let frames = BLOCK_SIZE + 1000;
+        let samples: Vec<f32> = (0..frames)
+            .flat_map(|frame| {
+                let t = frame as f32 / 44100.0;
+                let left = (t * 440.0 * std::f32::consts::TAU).sin() * 0.5;
+                let right = if frame < 2000 { 0.0 } else { -left * 0.25 };
+                [left, right]
+            })
+            .collect();
+
+        let mut writer = FlacWriter::create(&path, 44100).unwrap();
+        writer.write_samples(&samples).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = claxon::FlacReader::open(&path).unwrap();
+        let info = reader.streaminfo();
+        assert_eq!(info.sample_rate, 44100);
+        assert_eq!(info.channels, 2);
+        assert_eq!(info.bits_per_sample, 16);
+        assert_eq!(info.samples, Some(frames as u64));
+
+        let decoded: Vec<i32> = reader.samples().map(|sample| sample.unwrap()).collect();
+        let expected: Vec<i32> = samples
+            .iter()
+            .map(|sample| (sample * i16::MAX as f32) as i16 as i32)
+            .collect();
+        assert_eq!(decoded, expected);
+
+        std::fs::remove_file(&path).ok();
+    }
+}
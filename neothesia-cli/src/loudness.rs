@@ -0,0 +1,188 @@
+//! Integrated loudness of stereo audio, as specified by EBU R128 / ITU-R BS.1770
+
+/// Gating blocks overlap by 75%, so they are summed from 100ms steps
+const STEPS_PER_BLOCK: usize = 4;
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Relative to the loudness of the blocks above the absolute gate
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Second order IIR filter, transposed direct form II
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// K-weighting: a high shelf modelling the head, then a high pass.
+/// Coefficients are derived for any sample rate, like libebur128 does
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    use std::f64::consts::PI;
+
+    let shelf = {
+        let f0 = 1681.974450955533;
+        let gain = 3.999843853973347;
+        let q = 0.7071752369554196;
+
+        let k = (PI * f0 / sample_rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            z: [0.0; 2],
+        }
+    };
+
+    let high_pass = {
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+
+        let k = (PI * f0 / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            z: [0.0; 2],
+        }
+    };
+
+    [shelf, high_pass]
+}
+
+pub struct Meter {
+    /// K-weighting of the left and right channel
+    filters: [[Biquad; 2]; 2],
+    step_len: usize,
+    /// Sum of the squared samples of every finished 100ms step
+    steps: Vec<f64>,
+    current: f64,
+    current_len: usize,
+    peak: f32,
+}
+
+impl Meter {
+    pub fn new(sample_rate: u32) -> Self {
+        let filters = k_weighting(sample_rate as f64);
+        Self {
+            filters: [filters, filters],
+            step_len: (sample_rate as usize / 10).max(1),
+            steps: Vec::new(),
+            current: 0.0,
+            current_len: 0,
+            peak: 0.0,
+        }
+    }
+
+    /// Measures interleaved stereo `samples`
+    pub fn push(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(2) {
+            for (channel, sample) in frame.iter().enumerate() {
+                self.peak = self.peak.max(sample.abs());
+
+                let [shelf, high_pass] = &mut self.filters[channel];
+                let weighted = high_pass.process(shelf.process(*sample as f64));
+                self.current += weighted * weighted;
+            }
+
+            self.current_len += 1;
+            if self.current_len == self.step_len {
+                self.steps.push(self.current);
+                self.current = 0.0;
+                self.current_len = 0;
+            }
+        }
+    }
+
+    /// Highest sample seen, `1.0` is full scale
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+
+    /// Gated loudness of everything pushed so far in LUFS, `None` for silence
+    pub fn integrated(&self) -> Option<f64> {
+        let block_len = (self.step_len * STEPS_PER_BLOCK) as f64;
+        let blocks: Vec<f64> = self
+            .steps
+            .windows(STEPS_PER_BLOCK)
+            .map(|steps| steps.iter().sum::<f64>() / block_len)
+            .collect();
+
+        let loudness = |power: f64| -0.691 + 10.0 * power.log10();
+        let gated_mean = |gate: f64| {
+            let gated: Vec<f64> = blocks
+                .iter()
+                .copied()
+                .filter(|power| *power > 0.0 && loudness(*power) > gate)
+                .collect();
+            (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+        };
+
+        let relative_gate = loudness(gated_mean(ABSOLUTE_GATE)?) + RELATIVE_GATE;
+        gated_mean(relative_gate.max(ABSOLUTE_GATE)).map(loudness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    /// Interleaved stereo 1 kHz sine, `level` is the peak in dBFS
+    fn sine(level: f32, secs: f32) -> Vec<f32> {
+        let amplitude = 10f32.powf(level / 20.0);
+        (0..(SAMPLE_RATE as f32 * secs) as usize)
+            .flat_map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let sample = amplitude * (2.0 * std::f32::consts::PI * 1000.0 * t).sin();
+                [sample, sample]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn level() {
+        let mut meter = Meter::new(SAMPLE_RATE);
+        assert_eq!(meter.integrated(), None);
+
+        // The reference tone of EBU R128, a stereo sine at -23 dBFS is -23 LUFS
+        meter.push(&sine(-23.0, 5.0));
+        let loudness = meter.integrated().unwrap();
+        assert!((loudness + 23.0).abs() < 0.1, "{loudness}");
+        assert!((meter.peak() - 10f32.powf(-23.0 / 20.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn decay_is_gated() {
+        let mut meter = Meter::new(SAMPLE_RATE);
+        meter.push(&sine(-23.0, 5.0));
+        // A tail 30 dB down is under the relative gate, silence under the absolute one
+        meter.push(&sine(-53.0, 5.0));
+        meter.push(&vec![0.0; SAMPLE_RATE as usize * 2 * 5]);
+
+        // Only the few blocks overlapping the end of the tone pull it down a little
+        let loudness = meter.integrated().unwrap();
+        assert!((loudness + 23.0).abs() < 0.2, "{loudness}");
+
+        let mut silence = Meter::new(SAMPLE_RATE);
+        silence.push(&vec![0.0; SAMPLE_RATE as usize * 2 * 5]);
+        assert_eq!(silence.integrated(), None);
+    }
+}
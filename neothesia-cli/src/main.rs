@@ -1,21 +1,50 @@
-use std::{default::Default, time::Duration};
+use std::{
+    default::Default,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
 use neothesia_core::{
     config::Config,
-    render::{KeyboardRenderer, TextRenderer, WaterfallRenderer},
+    render::{
+        BackgroundRenderer, Camera, KeyboardRenderer, TextRenderer, WaterfallRenderer,
+        WatermarkRenderer,
+    },
+    theme::ThemeWatcher,
+    utils::palette::note_color,
 };
 use wgpu_jumpstart::{wgpu, Gpu, TransformUniform, Uniform};
 
-struct Recorder {
-    gpu: Gpu,
+mod audio;
+mod chapters;
+mod flac;
+mod loudness;
+
+mod title_card;
+use title_card::TitleCard;
+
+/// Silence before the song, so the first notes can be seen falling
+const LEAD_IN: Duration = Duration::from_secs(3);
+const FPS: f32 = 60.0;
+
+struct Recorder<'a> {
+    /// Shared by every song of a batch, setting up the device takes a while
+    gpu: &'a mut Gpu,
     transform_uniform: Uniform<TransformUniform>,
+    /// Not affected by the camera
+    screen_uniform: Uniform<TransformUniform>,
 
     playback: midi_file::PlaybackState,
     midi: midi_file::Midi,
 
+    background: BackgroundRenderer,
     keyboard: KeyboardRenderer,
     waterfall: WaterfallRenderer,
     text: TextRenderer,
+    watermark: Option<WatermarkRenderer>,
+
+    keyboard_layout: piano_math::KeyboardLayout,
+    camera: Camera,
 
     config: Config,
     width: u32,
@@ -35,30 +64,8 @@ fn time_without_lead_in(playback: &midi_file::PlaybackState) -> f32 {
     playback.time().as_secs_f32() - playback.leed_in().as_secs_f32()
 }
 
-impl Recorder {
-    fn new() -> Self {
-        env_logger::Builder::from_env(
-            env_logger::Env::default().default_filter_or("neothesia=info"),
-        )
-        .init();
-
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu_jumpstart::default_backends(),
-            ..Default::default()
-        });
-        let gpu = futures::executor::block_on(Gpu::new(&instance, None)).unwrap();
-
-        let args: Vec<String> = std::env::args().collect();
-
-        let midi = if args.len() > 1 {
-            midi_file::Midi::new(&args[1]).ok()
-        } else {
-            None
-        }
-        .unwrap();
-
-        let config = Config::new();
-
+impl<'a> Recorder<'a> {
+    fn new(gpu: &'a mut Gpu, midi: midi_file::Midi, config: Config) -> Self {
         let width = 1920;
         let height = 1080;
 
@@ -70,32 +77,68 @@ impl Recorder {
             transform_uniform,
             wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
         );
+        let screen_uniform = Uniform::new(
+            &gpu.device,
+            transform_uniform.data,
+            wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+        );
 
         let keyboard_layout = get_layout(width as f32, height as f32);
 
-        let mut keyboard = KeyboardRenderer::new(&gpu, &transform_uniform, keyboard_layout.clone());
+        let mut keyboard =
+            KeyboardRenderer::new(&gpu, &transform_uniform, keyboard_layout.clone(), 1);
 
         keyboard.position_on_bottom_of_parent(height as f32);
+        keyboard.set_key_travel(config.key_travel);
+        keyboard.set_note_labels(config.note_labels.clone());
+        keyboard.set_key_colors(config.themed_key_colors());
+
+        let mut background =
+            BackgroundRenderer::new(&gpu, &screen_uniform, 1, &config.background_image);
+        background.update(
+            &gpu.queue,
+            config.themed_background_color(),
+            config.themed_background_gradient(),
+            [width as f32, height as f32],
+        );
 
-        let mut waterfall =
-            WaterfallRenderer::new(&gpu, &midi, &config, &transform_uniform, keyboard_layout);
+        let mut waterfall = WaterfallRenderer::new(
+            &gpu,
+            &midi,
+            &config,
+            &transform_uniform,
+            keyboard_layout.clone(),
+            Default::default(),
+            1,
+        );
 
-        let playback = midi_file::PlaybackState::new(Duration::from_secs(3), &midi.merged_track);
+        let playback = midi_file::PlaybackState::new(LEAD_IN, &midi.merged_track);
 
         waterfall.update(&gpu.queue, time_without_lead_in(&playback));
 
         let text = TextRenderer::new(&gpu);
 
+        let mut watermark = WatermarkRenderer::new(&gpu, &screen_uniform, &config.watermark);
+        if let Some(watermark) = watermark.as_mut() {
+            watermark.update(&gpu.queue, width as f32, height as f32);
+        }
+
         Self {
             gpu,
             transform_uniform,
+            screen_uniform,
 
             playback,
             midi,
 
+            background,
             keyboard,
             waterfall,
             text,
+            watermark,
+
+            keyboard_layout,
+            camera: Camera::new(),
 
             config,
             width,
@@ -106,10 +149,41 @@ impl Recorder {
     fn update(&mut self, delta: Duration) {
         let events = self.playback.update(&self.midi.merged_track, delta);
         file_midi_events(&mut self.keyboard, &self.config, &events);
+        self.background.animate(&mut self.gpu, delta);
+
+        self.camera.update(
+            &self.config.camera,
+            &self.midi,
+            &self.keyboard_layout,
+            time_without_lead_in(&self.playback),
+            delta,
+        );
+        let (width, height) = (self.width as f32, self.height as f32);
+        let layout_width = self.keyboard_layout.width;
+        self.camera.apply(
+            &mut self.transform_uniform.data,
+            width,
+            height,
+            layout_width,
+            1.0,
+        );
+        self.transform_uniform.update(&self.gpu.queue);
+        let offset = self.camera.offset(layout_width, height);
+        self.keyboard.set_view(self.camera.zoom(), offset);
+        self.waterfall.set_view(self.camera.zoom(), offset);
 
         self.waterfall
             .update(&self.gpu.queue, time_without_lead_in(&self.playback));
+        self.waterfall.update_labels(
+            self.text.glyph_brush(),
+            &self.midi,
+            &self.config.note_labels,
+            time_without_lead_in(&self.playback),
+            height,
+            1.0,
+        );
 
+        self.keyboard.animate(delta);
         self.keyboard
             .update(&self.gpu.queue, self.text.glyph_brush());
     }
@@ -120,8 +194,25 @@ impl Recorder {
         view: &wgpu::TextureView,
         texture_desc: &wgpu::TextureDescriptor<'_>,
         output_buffer: &wgpu::Buffer,
+        mut card: Option<&mut TitleCard>,
     ) {
-        self.gpu.clear(view, self.config.background_color.into());
+        self.gpu
+            .clear(view, self.config.themed_background_color().into());
+
+        if let Some(card) = card.as_mut() {
+            // Cards are not affected by the camera
+            self.transform_uniform
+                .data
+                .update(self.width as f32, self.height as f32, 1.0);
+            self.transform_uniform.update(&self.gpu.queue);
+
+            card.update(
+                &self.gpu.queue,
+                &mut self.text,
+                self.width as f32,
+                self.height as f32,
+            );
+        }
 
         {
             let mut render_pass = self
@@ -140,11 +231,21 @@ impl Recorder {
                     depth_stencil_attachment: None,
                 });
 
-            self.waterfall
-                .render(&self.transform_uniform, &mut render_pass);
+            if let Some(card) = card {
+                card.render(&self.transform_uniform, &mut render_pass);
+            } else {
+                self.background
+                    .render(&self.screen_uniform, &mut render_pass);
+                self.waterfall
+                    .render(&self.transform_uniform, &mut render_pass);
+
+                self.keyboard
+                    .render(&self.transform_uniform, &mut render_pass);
 
-            self.keyboard
-                .render(&self.transform_uniform, &mut render_pass);
+                if let Some(watermark) = self.watermark.as_ref() {
+                    watermark.render(&self.screen_uniform, &mut render_pass);
+                }
+            }
         }
 
         self.text
@@ -176,8 +277,23 @@ impl Recorder {
     }
 }
 
-fn main() {
-    let mut recorder = Recorder::new();
+fn card_frames(seconds: f32) -> usize {
+    (seconds.max(0.0) * FPS).round() as usize
+}
+
+fn frames_duration(frames: usize) -> Duration {
+    Duration::from_secs_f32(frames as f32 / FPS)
+}
+
+/// Renders `midi` into an mp4 file at `path`, `progress` is called with the fraction done
+fn render_video(
+    gpu: &mut Gpu,
+    midi: midi_file::Midi,
+    config: Config,
+    path: &Path,
+    mut progress: impl FnMut(f32),
+) -> chapters::Timeline {
+    let mut recorder = Recorder::new(gpu, midi, config);
 
     let texture_desc = wgpu::TextureDescriptor {
         size: wgpu::Extent3d {
@@ -215,25 +331,18 @@ fn main() {
         mapped_at_creation: false,
     };
 
-    std::fs::create_dir("./out").ok();
-    let mut encoder = mpeg_encoder::Encoder::new(
-        "./out/video.mp4",
-        recorder.width as usize,
-        recorder.height as usize,
-    );
+    let path = path.to_string_lossy().to_string();
+    let mut encoder =
+        mpeg_encoder::Encoder::new(&path, recorder.width as usize, recorder.height as usize);
 
     encoder.init(Some(0.0), Some("medium"));
 
-    let start = std::time::Instant::now();
+    let frame_time = Duration::from_secs_f32(1.0 / FPS);
 
-    println!("Encoding started:");
-    let mut n = 1;
-    while recorder.playback.percentage() * 100.0 < 101.0 {
+    let mut encode_frame = |recorder: &mut Recorder, card: Option<&mut TitleCard>| {
         let output_buffer = recorder.gpu.device.create_buffer(&output_buffer_desc);
 
-        let frame_time = Duration::from_secs(1) / 60;
-        recorder.update(frame_time);
-        recorder.render(&texture, view, &texture_desc, &output_buffer);
+        recorder.render(&texture, view, &texture_desc, &output_buffer, card);
 
         {
             let slice = output_buffer.slice(..);
@@ -252,17 +361,308 @@ fn main() {
 
                 let data: &[u8] = &mapping;
                 encoder.encode_bgra(1920, 1080, data, false);
-                print!(
-                    "\r Encoded {} frames ({}s, {}%) in {}s",
-                    n,
-                    (n as f32 / 60.0).round(),
-                    (recorder.playback.percentage() * 100.0).round().min(100.0),
-                    start.elapsed().as_secs()
-                );
+                progress(recorder.playback.percentage().clamp(0.0, 1.0));
             });
         }
+    };
+
+    let cards = recorder.config.title_cards.clone();
+    let mut frames = 0;
+
+    if cards.enabled {
+        let mut intro = TitleCard::intro(
+            &recorder.gpu,
+            &recorder.transform_uniform,
+            &cards,
+            &recorder.midi,
+        );
+        for _ in 0..card_frames(cards.intro_duration) {
+            encode_frame(&mut recorder, Some(&mut intro));
+            frames += 1;
+        }
+    }
+
+    let song_start = frames_duration(frames) + LEAD_IN;
+
+    while recorder.playback.percentage() * 100.0 < 101.0 {
+        recorder.update(frame_time);
+        encode_frame(&mut recorder, None);
+        frames += 1;
+    }
+
+    let mut outro_start = None;
+    if cards.enabled {
+        outro_start = Some(frames_duration(frames));
+
+        let mut outro = TitleCard::outro(&recorder.gpu, &recorder.transform_uniform, &cards);
+        for _ in 0..card_frames(cards.outro_duration) {
+            encode_frame(&mut recorder, Some(&mut outro));
+            frames += 1;
+        }
+    }
 
-        n += 1;
+    chapters::Timeline {
+        song_start,
+        outro_start,
+        end: frames_duration(frames),
+    }
+}
+
+/// What gets rendered for every song
+struct Outputs {
+    video: bool,
+    audio: bool,
+    /// Writes the audio as FLAC instead of WAV
+    flac: bool,
+    /// Mixes the click into the audio, even when disabled in the settings
+    click: bool,
+    /// Adds note name subtitles to the video, even when disabled in the settings
+    subtitles: bool,
+    /// Normalizes the loudness of the audio, even when disabled in the settings
+    normalize: bool,
+    /// Path of the video of a single song, otherwise they go in the out folder, named after the songs.
+    /// The audio goes next to it
+    video_path: Option<PathBuf>,
+}
+
+/// Songs to render, a single file or every MIDI and MusicXML file of a folder
+fn songs(path: &Path) -> Vec<PathBuf> {
+    if !path.is_dir() {
+        return vec![path.to_owned()];
+    }
+
+    let entries = match std::fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("Failed to read {}: {err}", path.display());
+            return Vec::new();
+        }
+    };
+
+    let mut songs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .map(|ext| {
+                    midi_file::SONG_EXTENSIONS
+                        .iter()
+                        .any(|song| ext.eq_ignore_ascii_case(song))
+                })
+                .unwrap_or(false)
+        })
+        .collect();
+    songs.sort();
+    songs
+}
+
+/// Renders a single song into `out`, named after the song
+fn render_song(
+    gpu: Option<&mut Gpu>,
+    song: &Path,
+    out: &Path,
+    outputs: &Outputs,
+    mut progress: impl FnMut(f32),
+) -> Result<(), String> {
+    let mut config = Config::new();
+    ThemeWatcher::default().poll(&mut config);
+    let midi = midi_file::Midi::with_repeats(song, config.expand_repeats)?;
+
+    let output_path = |extension: &str| match outputs.video_path.as_ref() {
+        Some(path) => path.with_extension(extension),
+        None => out.join(format!("{}.{extension}", midi.name)),
+    };
+
+    let soundfont = config
+        .soundfont_path
+        .clone()
+        .or_else(neothesia_core::utils::resources::default_sf2);
+
+    // Kept next to the video when asked for, the video gets its own copy otherwise
+    let audio = if outputs.audio {
+        let extension = if outputs.flac { "flac" } else { "wav" };
+        Some((output_path(extension), true))
+    } else if outputs.video && soundfont.is_some() {
+        Some((output_path("audio.wav"), false))
+    } else {
+        if outputs.video {
+            log::warn!("No SoundFont configured, the video has no sound");
+        }
+        None
+    };
+
+    if let Some((path, _)) = audio.as_ref() {
+        let soundfont = soundfont.ok_or_else(|| String::from("No SoundFont configured"))?;
+
+        let mut click = config.click_track.clone();
+        click.enabled |= outputs.click;
+        let mut normalization = config.loudness_normalization.clone();
+        normalization.enabled |= outputs.normalize;
+
+        audio::render_audio(&midi, &soundfont, &click, &normalization, path)?;
+    }
+
+    if let Some(gpu) = gpu.filter(|_| outputs.video) {
+        let path = output_path("mp4");
+        let has_intro = config.title_cards.enabled;
+        let video_chapters = config.video_chapters.clone();
+        let song = midi.clone();
+
+        // The encoder has no error reporting of its own, it panics
+        let timeline = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            render_video(gpu, midi, config, &path, &mut progress)
+        }))
+        .map_err(|err| {
+            err.downcast_ref::<String>()
+                .cloned()
+                .or_else(|| err.downcast_ref::<&str>().map(|err| err.to_string()))
+                .unwrap_or_else(|| String::from("Encoder failed"))
+        })?;
+
+        let chapters = if video_chapters.chapters {
+            chapters::chapters(&song, &timeline, has_intro)
+        } else {
+            Vec::new()
+        };
+        let subtitles = (video_chapters.note_names || outputs.subtitles)
+            .then(|| chapters::note_name_subtitles(&song, &timeline));
+        let sound = audio
+            .as_ref()
+            .map(|(path, _)| (path.as_path(), timeline.song_start));
+
+        let res = chapters::embed(&path, &chapters, subtitles.as_deref(), sound, timeline.end);
+        match (res, audio.as_ref()) {
+            (Ok(()), Some((path, false))) => {
+                std::fs::remove_file(path).ok();
+            }
+            (Ok(()), _) => {}
+            // A video without its sound is not what was asked for, the audio is kept so the
+            // render doesn't have to be redone to mux it by hand
+            (Err(err), Some((audio, _))) => {
+                return Err(format!("{err}, the audio is kept in {}", audio.display()));
+            }
+            // Only chapters and subtitles are missing, the video itself is fine
+            (Err(err), None) => log::warn!("{err}"),
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("neothesia=info"))
+        .init();
+
+    let mut input = None;
+    let mut out = PathBuf::from("./out");
+    let mut outputs = Outputs {
+        video: true,
+        audio: false,
+        flac: false,
+        click: false,
+        subtitles: false,
+        normalize: false,
+        video_path: None,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--audio" => outputs.audio = true,
+            "--flac" => {
+                outputs.audio = true;
+                outputs.flac = true;
+            }
+            "--no-video" => outputs.video = false,
+            "--subtitles" => outputs.subtitles = true,
+            "--normalize" => outputs.normalize = true,
+            // The click only exists in the audio
+            "--click" => {
+                outputs.audio = true;
+                outputs.click = true;
+            }
+            "--out" => {
+                if let Some(dir) = args.next() {
+                    out = PathBuf::from(dir);
+                }
+            }
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => outputs.video_path = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let input = match input {
+        Some(input) => input,
+        None => {
+            eprintln!(
+                "Usage: neothesia-cli <song.mid | folder> [out.mp4] [--audio] [--flac] [--click] [--normalize] [--subtitles] [--no-video] [--out <folder>]"
+            );
+            std::process::exit(2);
+        }
+    };
+
+    if let Some(video_path) = outputs.video_path.as_ref() {
+        if input.is_dir() {
+            eprintln!("A folder of songs is rendered into --out <folder>, not into a single video");
+            std::process::exit(2);
+        }
+        if let Some(parent) = video_path.parent() {
+            std::fs::create_dir_all(parent).ok();
+        }
+    }
+
+    let out = out.as_path();
+    std::fs::create_dir_all(out).ok();
+
+    let songs = songs(&input);
+    let mut failed = Vec::new();
+
+    let mut gpu = if outputs.video {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu_jumpstart::default_backends(),
+            ..Default::default()
+        });
+        match futures::executor::block_on(Gpu::new(&instance, None)) {
+            Ok(gpu) => Some(gpu),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    for (id, song) in songs.iter().enumerate() {
+        let name = song
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let prefix = format!("[{}/{}] {name}", id + 1, songs.len());
+        let start = Instant::now();
+
+        print!("{prefix}");
+        std::io::Write::flush(&mut std::io::stdout()).ok();
+
+        let res = render_song(gpu.as_mut(), song, out, &outputs, |progress| {
+            print!("\r{prefix}: {}%", (progress * 100.0).round());
+            std::io::Write::flush(&mut std::io::stdout()).ok();
+        });
+
+        match res {
+            Ok(()) => println!("\r{prefix}: done in {}s", start.elapsed().as_secs()),
+            Err(err) => {
+                println!("\r{prefix}: failed, {err}");
+                failed.push((name, err));
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        eprintln!("\n{} of {} songs failed:", failed.len(), songs.len());
+        for (name, err) in failed.iter() {
+            eprintln!("  {name}: {err}");
+        }
+        std::process::exit(1);
     }
 }
 
@@ -281,12 +681,18 @@ fn file_midi_events(
         };
 
         if keyboard.range().contains(key) && e.channel != 9 {
+            let color = note_color(
+                &config.color_schema,
+                &config.hand_colors,
+                &config.pitch_colors,
+                e.track_color_id,
+                key,
+            );
             let id = key as usize - 21;
             let key = &mut keyboard.key_states_mut()[id];
 
             if is_on {
-                let color = &config.color_schema[e.track_color_id % config.color_schema.len()];
-                key.pressed_by_file_on(color);
+                key.pressed_by_file_on(&color);
             } else {
                 key.pressed_by_file_off();
             }
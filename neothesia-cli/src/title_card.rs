@@ -0,0 +1,123 @@
+use std::path::Path;
+
+use neothesia_core::{
+    config::TitleCards,
+    render::{load_image, TextRenderer},
+};
+use neothesia_pipelines::image::ImagePipeline;
+use wgpu_jumpstart::{wgpu, Gpu, TransformUniform, Uniform};
+
+/// Full screen card with a logo and a few lines of centered text
+pub struct TitleCard {
+    /// Text and its size
+    lines: Vec<(String, f32)>,
+    logo: Option<ImagePipeline>,
+}
+
+impl TitleCard {
+    pub fn intro(
+        gpu: &Gpu,
+        transform_uniform: &Uniform<TransformUniform>,
+        cards: &TitleCards,
+        midi: &midi_file::Midi,
+    ) -> Self {
+        let title = cards
+            .title
+            .clone()
+            .or_else(|| midi.metadata.title.clone())
+            .unwrap_or_else(|| midi.name.clone());
+
+        let mut lines = vec![(title, 96.0)];
+        if let Some(composer) = cards
+            .composer
+            .clone()
+            .or_else(|| midi.metadata.composer.clone())
+        {
+            lines.push((composer, 48.0));
+        }
+        if let Some(text) = cards.text.clone() {
+            lines.push((text, 32.0));
+        }
+
+        Self::new(gpu, transform_uniform, cards.logo.as_deref(), lines)
+    }
+
+    pub fn outro(
+        gpu: &Gpu,
+        transform_uniform: &Uniform<TransformUniform>,
+        cards: &TitleCards,
+    ) -> Self {
+        let credits = cards
+            .credits
+            .clone()
+            .unwrap_or_else(|| "Thanks for watching".into());
+
+        Self::new(
+            gpu,
+            transform_uniform,
+            cards.logo.as_deref(),
+            vec![(credits, 48.0)],
+        )
+    }
+
+    fn new(
+        gpu: &Gpu,
+        transform_uniform: &Uniform<TransformUniform>,
+        logo: Option<&Path>,
+        lines: Vec<(String, f32)>,
+    ) -> Self {
+        let logo = logo.and_then(|path| load_image(gpu, transform_uniform, path));
+        Self { lines, logo }
+    }
+
+    /// Queues the text and places the logo, call it before every frame of the card
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        text: &mut TextRenderer,
+        width: f32,
+        height: f32,
+    ) {
+        let mut y = height * 0.35;
+
+        if let Some(logo) = self.logo.as_mut() {
+            let (w, h) = logo.image_size();
+            let logo_h = height * 0.2;
+            let logo_w = logo_h * w as f32 / h as f32;
+
+            logo.set_rect(
+                queue,
+                [(width - logo_w) / 2.0, height * 0.15],
+                [logo_w, logo_h],
+                1.0,
+            );
+
+            y = height * 0.45;
+        }
+
+        for (line, scale) in self.lines.iter() {
+            text.queue_text(wgpu_glyph::Section {
+                screen_position: (width / 2.0, y),
+                bounds: (width, f32::INFINITY),
+                text: vec![wgpu_glyph::Text::new(line)
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(*scale)],
+                layout: wgpu_glyph::Layout::default()
+                    .h_align(wgpu_glyph::HorizontalAlign::Center)
+                    .v_align(wgpu_glyph::VerticalAlign::Top),
+            });
+
+            y += scale * 1.4;
+        }
+    }
+
+    pub fn render<'rpass>(
+        &'rpass self,
+        transform_uniform: &'rpass Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+    ) {
+        if let Some(logo) = self.logo.as_ref() {
+            logo.render(transform_uniform, render_pass);
+        }
+    }
+}
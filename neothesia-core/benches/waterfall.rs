@@ -0,0 +1,43 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use neothesia_core::{
+    config::{ColorSchema, HandColors, NoteStyle, PitchColors},
+    render::waterfall_note_instances,
+};
+
+fn waterfall_layout(c: &mut Criterion) {
+    let midi = midi_file::stress::black_midi(64, 5_000);
+    let layout = piano_math::KeyboardLayout::standard_88_keys(20.0, 100.0);
+    let color_schema = vec![
+        ColorSchema {
+            base: (210, 89, 222),
+            dark: (125, 69, 134),
+        },
+        ColorSchema {
+            base: (93, 188, 255),
+            dark: (48, 124, 255),
+        },
+    ];
+
+    let mut group = c.benchmark_group("waterfall");
+    group.sample_size(20);
+
+    group.bench_function("note_instances", |b| {
+        b.iter(|| {
+            criterion::black_box(waterfall_note_instances(
+                &midi,
+                &color_schema,
+                &HandColors::default(),
+                &PitchColors::default(),
+                &NoteStyle::default(),
+                &layout,
+                &Default::default(),
+                0,
+            ))
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, waterfall_layout);
+criterion_main!(benches);
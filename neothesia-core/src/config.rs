@@ -1,13 +1,1470 @@
-use std::path::PathBuf;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Default, Clone)]
+pub use neothesia_engine::{ClickPattern, MasterVolume, NoteTolerance, OutputRemap, VelocityCurve};
+
+use crate::{theme::Theme, utils::sync};
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
 pub struct ColorSchema {
+    /// Notes on white keys
     pub base: (u8, u8, u8),
+    /// Notes on black keys
     pub dark: (u8, u8, u8),
 }
 
+/// Colors of the notes of each hand, used instead of the track colors when enabled.
+/// Notes from middle C up are played by the right hand
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct HandColors {
+    pub enabled: bool,
+    pub left: ColorSchema,
+    pub right: ColorSchema,
+}
+
+impl HandColors {
+    /// Colors of the hand that plays `key`
+    pub fn of(&self, key: u8) -> &ColorSchema {
+        if key >= 60 {
+            &self.right
+        } else {
+            &self.left
+        }
+    }
+}
+
+/// Colors of the notes of each pitch class, `C` to `B`, used instead of the track and hand
+/// colors when enabled. Notes on black keys are not darkened
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PitchColors {
+    pub enabled: bool,
+    pub colors: [(u8, u8, u8); 12],
+}
+
+impl PitchColors {
+    /// Colors of the pitch class of `key`
+    pub fn of(&self, key: u8) -> ColorSchema {
+        let color = self.colors[key as usize % 12];
+        ColorSchema {
+            base: color,
+            dark: color,
+        }
+    }
+}
+
+impl Default for PitchColors {
+    fn default() -> Self {
+        // Chromatic rainbow, red on C
+        Self {
+            enabled: false,
+            colors: [
+                (255, 56, 56),
+                (255, 110, 48),
+                (255, 160, 40),
+                (255, 205, 40),
+                (250, 240, 60),
+                (160, 230, 60),
+                (60, 210, 90),
+                (40, 200, 170),
+                (40, 170, 240),
+                (60, 110, 255),
+                (140, 80, 255),
+                (220, 70, 220),
+            ],
+        }
+    }
+}
+
+impl Default for HandColors {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            left: ColorSchema {
+                base: (93, 188, 255),
+                dark: (48, 124, 255),
+            },
+            right: ColorSchema {
+                base: (51, 255, 102),
+                dark: (0, 168, 2),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SystemReset {
+    #[default]
+    None,
+    Gm,
+    Gs,
+    Xg,
+}
+
+impl SystemReset {
+    pub const ALL: [SystemReset; 4] = [Self::None, Self::Gm, Self::Gs, Self::Xg];
+
+    pub fn sysex(&self) -> Option<&'static [u8]> {
+        match self {
+            Self::None => None,
+            Self::Gm => Some(&midi_file::gm::GM_RESET),
+            Self::Gs => Some(&midi_file::gm::GS_RESET),
+            Self::Xg => Some(&midi_file::gm::XG_RESET),
+        }
+    }
+}
+
+impl std::fmt::Display for SystemReset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "None"),
+            Self::Gm => write!(f, "GM"),
+            Self::Gs => write!(f, "GS"),
+            Self::Xg => write!(f, "XG"),
+        }
+    }
+}
+
+/// How the menu and the playing scene replace each other
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SceneTransition {
+    /// Switches right away
+    None,
+    #[default]
+    Fade,
+    /// A curtain moves in from the right and leaves to the left
+    Slide,
+}
+
+/// Multisample anti-aliasing of the waterfall and the keyboard
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Msaa {
+    #[default]
+    Off,
+    X2,
+    X4,
+    X8,
+}
+
+impl Msaa {
+    pub const ALL: [Msaa; 4] = [Self::Off, Self::X2, Self::X4, Self::X8];
+
+    pub fn samples(&self) -> u32 {
+        match self {
+            Self::Off => 1,
+            Self::X2 => 2,
+            Self::X4 => 4,
+            Self::X8 => 8,
+        }
+    }
+}
+
+impl std::fmt::Display for Msaa {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Off => write!(f, "Off"),
+            _ => write!(f, "{}x", self.samples()),
+        }
+    }
+}
+
+/// How a scene rendered below the window resolution is scaled up
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFilter {
+    #[default]
+    Linear,
+    Nearest,
+}
+
+impl TextureFilter {
+    pub const ALL: [TextureFilter; 2] = [Self::Linear, Self::Nearest];
+}
+
+impl std::fmt::Display for TextureFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Linear => write!(f, "Smooth"),
+            Self::Nearest => write!(f, "Sharp"),
+        }
+    }
+}
+
+/// Trades visual quality of the waterfall and the keyboard for performance, eg. on integrated GPUs.
+/// Applies the next time a song is started
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RenderQuality {
+    pub msaa: Msaa,
+    /// Fraction of the window resolution the scene is rendered at
+    pub resolution_scale: f32,
+    pub filter: TextureFilter,
+}
+
+impl Default for RenderQuality {
+    fn default() -> Self {
+        Self {
+            msaa: Msaa::Off,
+            resolution_scale: 1.0,
+            filter: TextureFilter::Linear,
+        }
+    }
+}
+
+impl RenderQuality {
+    pub fn resolution_scale(&self) -> f32 {
+        self.resolution_scale.clamp(0.25, 2.0)
+    }
+
+    /// Rendering at the window resolution without MSAA needs no offscreen pass
+    pub fn is_native(&self) -> bool {
+        self.msaa == Msaa::Off && self.resolution_scale() == 1.0
+    }
+}
+
+/// Outputs to an extended range (scRGB) surface where the platform offers one,
+/// eg. for capturing on HDR displays
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Hdr {
+    pub enabled: bool,
+    /// Brightness of white theme colors, in nits
+    pub paper_white: f32,
+}
+
+impl Default for Hdr {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            paper_white: 200.0,
+        }
+    }
+}
+
+/// How a keyboard with key lights gets told which keys to light
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum LightGuideProtocol {
+    /// Note on/off on a channel the keyboard reserves for its lights, 0 based.
+    /// Casio LK keyboards listen on channel 4, ie. `Channel(3)`
+    Channel(u8),
+    /// SysEx per key, written in hex with `kk` standing in for the key, eg. `F0 7D 01 kk 7F F7`.
+    /// The bytes are listed in the MIDI reference of the keyboard
+    SysEx { on: String, off: String },
+}
+
+impl Default for LightGuideProtocol {
+    fn default() -> Self {
+        Self::Channel(3)
+    }
+}
+
+impl LightGuideProtocol {
+    /// Message that turns the light of `key` on or off,
+    /// `None` when the SysEx template is not valid hex
+    pub fn message(&self, key: u8, on: bool) -> Option<Vec<u8>> {
+        match self {
+            Self::Channel(channel) => {
+                let channel = channel & 0x0F;
+                let (status, velocity) = if on { (0x90, 0x7F) } else { (0x80, 0x00) };
+                Some(vec![status | channel, key & 0x7F, velocity])
+            }
+            Self::SysEx {
+                on: on_template,
+                off,
+            } => {
+                let template = if on { on_template } else { off };
+                template
+                    .split_whitespace()
+                    .map(|byte| {
+                        if byte.eq_ignore_ascii_case("kk") {
+                            Some(key & 0x7F)
+                        } else {
+                            u8::from_str_radix(byte, 16).ok()
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Lights the keys to press on keyboards with a light guide, while waiting in play along
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct LightGuide {
+    pub enabled: bool,
+    /// Name of the MIDI output the keyboard is on, `None` uses the main output
+    pub output: Option<String>,
+    pub protocol: LightGuideProtocol,
+}
+
+/// Grid controller, its pads are numbered differently on every model
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PadDevice {
+    #[default]
+    LaunchpadMiniMk3,
+    LaunchpadX,
+    ApcMini,
+}
+
+/// How notes are laid out on the grid, lowest note at the bottom left
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PadLayout {
+    /// Every row continues where the one below ended
+    #[default]
+    Chromatic,
+    /// Every row starts `row_interval` semitones above the one below,
+    /// so a chord shape plays the same anywhere on the grid
+    Isomorphic,
+}
+
+/// Grid controllers used as a keyboard, with the notes to play lit on the pads
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PadGrid {
+    pub enabled: bool,
+    pub device: PadDevice,
+    pub layout: PadLayout,
+    /// Note of the bottom left pad
+    pub root_note: u8,
+    /// Semitones between rows of the isomorphic layout
+    pub row_interval: u8,
+    /// Name of the MIDI output the pads are lit through,
+    /// `None` uses the output named like the input
+    pub output: Option<String>,
+}
+
+impl Default for PadGrid {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            device: PadDevice::LaunchpadMiniMk3,
+            layout: PadLayout::Chromatic,
+            root_note: 36,
+            row_interval: 5,
+            output: None,
+        }
+    }
+}
+
+/// Key layouts of the computer keyboard piano
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VirtualPianoLayout {
+    /// Two octaves, the bottom letter row and the row above it, black keys a row higher
+    TwoRows,
+    /// Middle letter row for white keys and the row above it for black keys
+    OneRow,
+}
+
+impl VirtualPianoLayout {
+    pub const ALL: [Self; 2] = [Self::TwoRows, Self::OneRow];
+
+    /// Keys in the order of their notes, see [`VirtualPiano::rows`]
+    pub fn rows(self) -> Vec<String> {
+        let rows: &[&str] = match self {
+            Self::TwoRows => &["zsxdcvgbhnjm,l.;/", "q2w3er5t6y7ui9o0p[=]"],
+            Self::OneRow => &["awsedftgyhujkolp;'"],
+        };
+        rows.iter().map(|row| row.to_string()).collect()
+    }
+}
+
+impl std::fmt::Display for VirtualPianoLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TwoRows => write!(f, "Two rows"),
+            Self::OneRow => write!(f, "One row"),
+        }
+    }
+}
+
+/// Computer keyboard played as a piano, for when there's no MIDI keyboard.
+/// Keys of the layout play notes in place of their shortcuts while it's on, the shortcuts
+/// are still there along with Ctrl or Alt. It's off in the menu and in the metronome
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct VirtualPiano {
+    pub enabled: bool,
+    /// Characters of the keys as typed on a US layout, a semitone apart.
+    /// Every row starts an octave above the one before
+    pub rows: Vec<String>,
+    /// Note of the first key of the first row
+    pub base_note: u8,
+    pub velocity: u8,
+}
+
+impl VirtualPiano {
+    /// Preset the rows come from, `None` for a custom layout
+    pub fn layout(&self) -> Option<VirtualPianoLayout> {
+        VirtualPianoLayout::ALL
+            .into_iter()
+            .find(|layout| layout.rows() == self.rows)
+    }
+}
+
+impl Default for VirtualPiano {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rows: VirtualPianoLayout::TwoRows.rows(),
+            base_note: 48,
+            velocity: 100,
+        }
+    }
+}
+
+/// What a DMX channel follows
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DmxSource {
+    /// Velocity of the key while it's held
+    Key(u8),
+    /// Loudest held key of the range, inclusive
+    Keys(u8, u8),
+    /// How busy and loud the song is at the moment
+    Energy,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmxChannel {
+    /// 1 based, as printed on fixtures
+    pub channel: u16,
+    pub source: DmxSource,
+}
+
+/// Lets stage lights follow the song, over Art-Net (DMX over UDP)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ArtNet {
+    pub enabled: bool,
+    /// Address of the Art-Net node, the default broadcasts to the local network
+    pub address: String,
+    /// 15 bit port address, ie. net, sub-net and universe
+    pub universe: u16,
+    pub channels: Vec<DmxChannel>,
+}
+
+impl Default for ArtNet {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "255.255.255.255".into(),
+            universe: 0,
+            channels: vec![
+                DmxChannel {
+                    channel: 1,
+                    source: DmxSource::Energy,
+                },
+                DmxChannel {
+                    channel: 2,
+                    source: DmxSource::Keys(21, 59),
+                },
+                DmxChannel {
+                    channel: 3,
+                    source: DmxSource::Keys(60, 108),
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LowPowerMode {
+    /// Enabled while running on battery
+    Auto,
+    On,
+    /// Off unless asked for, battery detection isn't available everywhere
+    #[default]
+    Off,
+}
+
+/// Saves battery on laptops by capping the frame rate, rendering only a few frames a second
+/// while the song is paused, and leaving out the spark particles and the background blur
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LowPower {
+    pub mode: LowPowerMode,
+    pub max_fps: u32,
+}
+
+impl Default for LowPower {
+    fn default() -> Self {
+        Self {
+            mode: LowPowerMode::Off,
+            max_fps: 30,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NoteFill {
+    #[default]
+    Flat,
+    /// Darkens towards the tail of the note
+    Gradient,
+}
+
+/// Look of the waterfall notes
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct NoteStyle {
+    /// Fraction of the key width
+    pub corner_radius: f32,
+    /// In pixels, `0.0` disables the border
+    pub border_width: f32,
+    pub border_color: (u8, u8, u8),
+    pub fill: NoteFill,
+    /// Halo around the notes in their color, in pixels, `0.0` disables it
+    pub glow: f32,
+    /// Louder notes are brighter and wider, quieter ones dimmer and narrower
+    pub velocity: bool,
+    /// Notes last for as long as the sustain pedal holds them, as they are heard
+    pub sustain: bool,
+}
+
+impl Default for NoteStyle {
+    fn default() -> Self {
+        Self {
+            corner_radius: 0.2,
+            border_width: 0.0,
+            border_color: (0, 0, 0),
+            fill: NoteFill::Flat,
+            glow: 0.0,
+            velocity: false,
+            sustain: false,
+        }
+    }
+}
+
+impl NoteStyle {
+    /// Brightness and width of a note played at `velocity`, as multipliers.
+    /// Notes at a usual velocity of about 100 look as if it was off
+    pub fn velocity_scale(&self, velocity: u8) -> (f32, f32) {
+        if !self.velocity {
+            return (1.0, 1.0);
+        }
+
+        let loudness = velocity.min(127) as f32 / 127.0;
+        (0.6 + 0.5 * loudness, (0.75 + 0.3 * loudness).min(1.0))
+    }
+}
+
+/// Colors of the piano keys at rest and pressed by the user, file notes use the track colors
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct KeyColors {
+    pub white: (u8, u8, u8),
+    pub black: (u8, u8, u8),
+    /// Black keys pressed by the user are a darker shade of it
+    pub pressed: (u8, u8, u8),
+}
+
+impl Default for KeyColors {
+    fn default() -> Self {
+        Self {
+            white: (255, 255, 255),
+            black: (0, 0, 0),
+            pressed: (128, 128, 128),
+        }
+    }
+}
+
+/// Cinematic camera, mainly for video export
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct CameraConfig {
+    pub enabled: bool,
+    pub max_zoom: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_zoom: 2.0,
+        }
+    }
+}
+
+/// Cards rendered before and after the performance in exported videos
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct TitleCards {
+    pub enabled: bool,
+    /// Defaults to the title stored in the file, then to the file name
+    pub title: Option<String>,
+    /// Defaults to the composer stored in the file
+    pub composer: Option<String>,
+    /// Shown on the intro, under the composer
+    pub text: Option<String>,
+    /// Shown on the outro
+    pub credits: Option<String>,
+    pub logo: Option<PathBuf>,
+    /// Seconds
+    pub intro_duration: f32,
+    /// Seconds
+    pub outro_duration: f32,
+}
+
+impl Default for TitleCards {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            title: None,
+            composer: None,
+            text: None,
+            credits: None,
+            logo: None,
+            intro_duration: 4.0,
+            outro_duration: 4.0,
+        }
+    }
+}
+
+/// Navigation data embedded in exported videos
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct VideoChapters {
+    /// Chapter at every marker of the song
+    pub chapters: bool,
+    /// Subtitle track with the names of the notes being played
+    pub note_names: bool,
+}
+
+impl Default for VideoChapters {
+    fn default() -> Self {
+        Self {
+            chapters: true,
+            note_names: false,
+        }
+    }
+}
+
+/// Brings exported audio to the same loudness, measured like EBU R128 does
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LoudnessNormalization {
+    pub enabled: bool,
+    /// Integrated loudness in LUFS, streaming services aim for around -14
+    pub target: f32,
+    /// Highest sample level in dBFS, the gain is lowered instead of clipping
+    pub peak_ceiling: f32,
+}
+
+impl Default for LoudnessNormalization {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target: -14.0,
+            peak_ceiling: -1.0,
+        }
+    }
+}
+
+/// Where the click of exported audio goes
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ClickChannel {
+    /// Mixed into the music on both sides
+    #[default]
+    Both,
+    /// Alone on the left side, the music is downmixed to the right one
+    Left,
+    /// Alone on the right side, the music is downmixed to the left one
+    Right,
+}
+
+/// Metronome click mixed into exported audio, for practice backing tracks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ClickTrack {
+    pub enabled: bool,
+    pub channel: ClickChannel,
+    /// `0.0..=1.0`
+    pub volume: f32,
+    /// Accents the first beat of every measure
+    pub accent: bool,
+}
+
+impl Default for ClickTrack {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            channel: ClickChannel::default(),
+            volume: 0.3,
+            accent: true,
+        }
+    }
+}
+
+/// What the metronome sounds like while playing
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MetronomeSound {
+    /// Click sample of the built-in synth, silent on MIDI outputs
+    #[default]
+    Click,
+    /// Percussion notes on channel 10, heard on any output
+    Note,
+}
+
+/// Metronome following the tempo map of the song while playing
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Metronome {
+    pub enabled: bool,
+    pub sound: MetronomeSound,
+    /// GM percussion key of the beats, Low Wood Block by default
+    pub note: u8,
+    /// GM percussion key of the downbeats, Hi Wood Block by default
+    pub accent_note: u8,
+    /// `0.0..=1.0`
+    pub volume: f32,
+    /// Measures clicked before the song starts
+    pub count_in: u32,
+}
+
+impl Default for Metronome {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sound: MetronomeSound::default(),
+            note: 77,
+            accent_note: 76,
+            volume: 0.5,
+            count_in: 1,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Logo drawn over the waterfall, both live and in exported videos
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Watermark {
+    /// PNG or JPEG, `None` disables the watermark
+    pub path: Option<PathBuf>,
+    pub corner: Corner,
+    pub opacity: f32,
+    /// Height of the logo as a fraction of the window height
+    pub size: f32,
+}
+
+impl Default for Watermark {
+    fn default() -> Self {
+        Self {
+            path: None,
+            corner: Corner::default(),
+            opacity: 0.5,
+            size: 0.08,
+        }
+    }
+}
+
+/// What is drawn behind the waterfall
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BackgroundKind {
+    /// Background color, and its gradient
+    #[default]
+    Color,
+    /// PNG, JPEG, or an animated GIF which loops
+    Image,
+    /// Animation of the menu
+    Animated,
+}
+
+impl BackgroundKind {
+    pub const ALL: [BackgroundKind; 3] = [Self::Color, Self::Image, Self::Animated];
+}
+
+impl std::fmt::Display for BackgroundKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Color => write!(f, "Color"),
+            Self::Image => write!(f, "Image"),
+            Self::Animated => write!(f, "Animated"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct BackgroundImage {
+    pub kind: BackgroundKind,
+    pub path: Option<PathBuf>,
+    /// How much darker it is drawn, so the notes stand out, `0.0` to `1.0`
+    pub dim: f32,
+    /// Blur of the image, `1.0` blurs it by 2% of its size
+    pub blur: f32,
+}
+
+impl Default for BackgroundImage {
+    fn default() -> Self {
+        Self {
+            kind: BackgroundKind::Color,
+            path: None,
+            dim: 0.5,
+            blur: 0.0,
+        }
+    }
+}
+
+/// Look of the playback progress bar at the top of the window
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ProgressBar {
+    pub hidden: bool,
+    pub color: (u8, u8, u8),
+    /// Logical pixels
+    pub height: f32,
+}
+
+impl Default for ProgressBar {
+    fn default() -> Self {
+        Self {
+            hidden: false,
+            color: (56, 145, 255),
+            height: 5.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NotationView {
+    /// Falling notes only
+    #[default]
+    Off,
+    /// Staves above the falling notes
+    Split,
+    /// Staves in place of the falling notes
+    Only,
+}
+
+impl NotationView {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Split,
+            Self::Split => Self::Only,
+            Self::Only => Self::Off,
+        }
+    }
+}
+
+impl std::fmt::Display for NotationView {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Off => write!(f, "Off"),
+            Self::Split => write!(f, "Split"),
+            Self::Only => write!(f, "Only"),
+        }
+    }
+}
+
+/// Grand staff with the upcoming notes, read from left to right past a playback cursor
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Notation {
+    pub view: NotationView,
+    /// Logical pixels, in the split view
+    pub height: f32,
+    /// Seconds of music ahead of the cursor
+    pub seconds: f32,
+}
+
+impl Default for Notation {
+    fn default() -> Self {
+        Self {
+            view: NotationView::Off,
+            height: 220.0,
+            seconds: 4.0,
+        }
+    }
+}
+
+/// Balance preset that brings the melody forward, the melody track is the one marked in
+/// the track selection, or else the one the song analysis guesses
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct MelodyBoost {
+    pub enabled: bool,
+    /// Velocity multiplier of the melody track
+    pub melody: f32,
+    /// Velocity multiplier of the other tracks
+    pub accompaniment: f32,
+}
+
+impl Default for MelodyBoost {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            melody: 1.3,
+            accompaniment: 0.6,
+        }
+    }
+}
+
+/// Karaoke line at the top of the window, for songs with lyrics
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Lyrics {
+    pub enabled: bool,
+    pub color: (u8, u8, u8),
+    /// Color of the syllables already sung
+    pub sung_color: (u8, u8, u8),
+    /// Logical pixels
+    pub size: f32,
+}
+
+impl Default for Lyrics {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            color: (255, 255, 255),
+            sung_color: (255, 200, 60),
+            size: 28.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AudioVisualizer {
+    /// Log spaced frequency bands
+    #[default]
+    Spectrum,
+    /// Left and right channel loudness
+    LevelMeter,
+}
+
+/// Strip next to the waterfall showing the built-in synth output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AudioPane {
+    pub enabled: bool,
+    pub visualizer: AudioVisualizer,
+    /// Fraction of the window width
+    pub width: f32,
+    pub color: (u8, u8, u8),
+}
+
+impl Default for AudioPane {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            visualizer: AudioVisualizer::default(),
+            width: 0.15,
+            color: (56, 145, 255),
+        }
+    }
+}
+
+/// Unattended playback that starts the song over instead of finishing. There is no queue of
+/// songs to move on to, the end of a song always crossfades into its own start
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LoopPlayback {
+    pub enabled: bool,
+    /// Seconds the end of the song overlaps its start, `0.0` restarts right away
+    pub crossfade: f32,
+    /// Faint copy of the start of the song after its end, so the jump back isn't jarring
+    pub ghost_notes: bool,
+}
+
+impl Default for LoopPlayback {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            crossfade: 4.0,
+            ghost_notes: true,
+        }
+    }
+}
+
+/// Softly plays the notes play along waits for, once the user is stuck on them for a while
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PitchHint {
+    pub enabled: bool,
+    /// Seconds of waiting before the hint, and between hints while still waiting
+    pub delay: f32,
+    /// `0.0..=1.0`
+    pub volume: f32,
+}
+
+impl Default for PitchHint {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            delay: 3.0,
+            volume: 0.3,
+        }
+    }
+}
+
+/// Quiet mechanical click on every key press, for keyboards with their sound turned off
+/// that are played for the feedback on screen only
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct KeyClick {
+    pub enabled: bool,
+    /// `0.0..=1.0`, harder presses click louder
+    pub volume: f32,
+}
+
+impl Default for KeyClick {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            volume: 0.3,
+        }
+    }
+}
+
+/// Keys that don't work on the user's instrument, play along doesn't wait for their notes
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct DeadKeys {
+    pub keys: Vec<u8>,
+    /// Their notes are played an octave away instead of counting as played
+    pub octave_substitute: bool,
+}
+
+/// Hints for the chord play along is stuck on, more of them the longer it waits:
+/// first the keys light up, then their names show up on them, then the fingering
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct WaitHints {
+    pub enabled: bool,
+    pub difficulty: HintDifficulty,
+    pub beginner: HintDelays,
+    pub intermediate: HintDelays,
+    pub advanced: HintDelays,
+}
+
+impl WaitHints {
+    /// Delays of the selected difficulty
+    pub fn delays(&self) -> &HintDelays {
+        match self.difficulty {
+            HintDifficulty::Beginner => &self.beginner,
+            HintDifficulty::Intermediate => &self.intermediate,
+            HintDifficulty::Advanced => &self.advanced,
+        }
+    }
+}
+
+impl Default for WaitHints {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            difficulty: HintDifficulty::default(),
+            beginner: HintDelays {
+                highlight: 1.0,
+                note_names: 2.0,
+                fingering: 4.0,
+            },
+            intermediate: HintDelays {
+                highlight: 2.0,
+                note_names: 4.0,
+                fingering: 8.0,
+            },
+            advanced: HintDelays {
+                highlight: 4.0,
+                note_names: 8.0,
+                fingering: 15.0,
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HintDifficulty {
+    Beginner,
+    #[default]
+    Intermediate,
+    Advanced,
+}
+
+/// Seconds of waiting before each hint shows up
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct HintDelays {
+    pub highlight: f32,
+    pub note_names: f32,
+    pub fingering: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NoteNaming {
+    /// `C4`, `D#5`
+    #[default]
+    Letters,
+    /// Fixed do, `Do`, `Re`, `Mi`
+    Solfege,
+}
+
+impl NoteNaming {
+    pub const ALL: [NoteNaming; 2] = [Self::Letters, Self::Solfege];
+}
+
+impl std::fmt::Display for NoteNaming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Letters => write!(f, "Letters"),
+            Self::Solfege => write!(f, "Solfege"),
+        }
+    }
+}
+
+/// Note names written on the falling notes and on the keyboard keys
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct NoteLabels {
+    pub waterfall: bool,
+    pub keyboard: bool,
+    pub naming: NoteNaming,
+    /// Black keys are named as flats instead of sharps
+    pub flats: bool,
+}
+
+impl NoteLabels {
+    pub fn name(&self, note: u8) -> String {
+        match self.naming {
+            NoteNaming::Letters => piano_math::names::letter_name(note, self.flats),
+            NoteNaming::Solfege => piano_math::names::solfege_name(note, self.flats).to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleMode {
+    #[default]
+    Major,
+    NaturalMinor,
+    HarmonicMinor,
+    MelodicMinor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+}
+
+impl ScaleMode {
+    pub const ALL: [ScaleMode; 12] = [
+        Self::Major,
+        Self::NaturalMinor,
+        Self::HarmonicMinor,
+        Self::MelodicMinor,
+        Self::Dorian,
+        Self::Phrygian,
+        Self::Lydian,
+        Self::Mixolydian,
+        Self::Locrian,
+        Self::MajorPentatonic,
+        Self::MinorPentatonic,
+        Self::Blues,
+    ];
+
+    pub fn intervals(&self) -> &'static [u8] {
+        use piano_math::scale;
+
+        match self {
+            Self::Major => scale::MAJOR,
+            Self::NaturalMinor => scale::NATURAL_MINOR,
+            Self::HarmonicMinor => scale::HARMONIC_MINOR,
+            Self::MelodicMinor => scale::MELODIC_MINOR,
+            Self::Dorian => scale::DORIAN,
+            Self::Phrygian => scale::PHRYGIAN,
+            Self::Lydian => scale::LYDIAN,
+            Self::Mixolydian => scale::MIXOLYDIAN,
+            Self::Locrian => scale::LOCRIAN,
+            Self::MajorPentatonic => scale::MAJOR_PENTATONIC,
+            Self::MinorPentatonic => scale::MINOR_PENTATONIC,
+            Self::Blues => scale::BLUES,
+        }
+    }
+}
+
+impl std::fmt::Display for ScaleMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Major => write!(f, "Major"),
+            Self::NaturalMinor => write!(f, "Natural Minor"),
+            Self::HarmonicMinor => write!(f, "Harmonic Minor"),
+            Self::MelodicMinor => write!(f, "Melodic Minor"),
+            Self::Dorian => write!(f, "Dorian"),
+            Self::Phrygian => write!(f, "Phrygian"),
+            Self::Lydian => write!(f, "Lydian"),
+            Self::Mixolydian => write!(f, "Mixolydian"),
+            Self::Locrian => write!(f, "Locrian"),
+            Self::MajorPentatonic => write!(f, "Major Pentatonic"),
+            Self::MinorPentatonic => write!(f, "Minor Pentatonic"),
+            Self::Blues => write!(f, "Blues"),
+        }
+    }
+}
+
+/// Keys of a scale tinted on the keyboard, whatever song is loaded,
+/// for practicing scales between songs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ScaleOverlay {
+    pub enabled: bool,
+    /// Pitch class, `0` is C
+    pub tonic: u8,
+    pub mode: ScaleMode,
+    /// Tint of the keys in the scale, the tonic gets more of it
+    pub color: (u8, u8, u8),
+}
+
+impl Default for ScaleOverlay {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tonic: 0,
+            mode: ScaleMode::Major,
+            color: (93, 188, 255),
+        }
+    }
+}
+
+impl ScaleOverlay {
+    /// How strongly `key` is tinted, `None` when it's not in the scale or the overlay is off
+    pub fn tint(&self, key: u8) -> Option<f32> {
+        if !self.enabled {
+            None
+        } else if piano_math::scale::is_tonic(self.tonic, key) {
+            Some(0.6)
+        } else if piano_math::scale::contains(self.tonic, self.mode.intervals(), key) {
+            Some(0.3)
+        } else {
+            None
+        }
+    }
+}
+
+/// Speeds the song up a little every time the loop starts over,
+/// up to the target speed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SpeedTrainer {
+    pub enabled: bool,
+    /// Added to the speed on every loop
+    pub step: f32,
+    /// Speed the trainer stops at
+    pub target: f32,
+}
+
+impl Default for SpeedTrainer {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            step: 0.05,
+            target: 1.0,
+        }
+    }
+}
+
+/// Practice a few measures at a time: the waterfall stands still on a phrase,
+/// which repeats until it's played accurately, then snaps to the next one
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PhrasePractice {
+    pub enabled: bool,
+    /// Measures in a phrase, `2..=4`
+    pub bars: u32,
+    /// Share of the notes of the phrase that have to be played to move on, `0.0..=1.0`
+    pub accuracy: f32,
+}
+
+impl Default for PhrasePractice {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bars: 4,
+            accuracy: 0.9,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SessionFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl SessionFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// Saves per-note performance data of every play along session
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct SessionExport {
+    pub enabled: bool,
+    pub format: SessionFormat,
+    /// Defaults to `sessions` in the data directory
+    pub directory: Option<PathBuf>,
+}
+
+/// Saves what's played on the MIDI input as `.mid` files, along with the song
+/// or freestyle in the menu
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct InputRecording {
+    pub enabled: bool,
+    /// Defaults to `recordings` in the data directory
+    pub directory: Option<PathBuf>,
+}
+
+/// Leaves a finished song on its own after a while without input,
+/// for public installations and kids' setups
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct IdleMode {
+    pub enabled: bool,
+    /// Seconds without input after the song ends
+    pub timeout: f32,
+    /// Replays the song on its own instead of going back to the menu
+    pub attract: bool,
+}
+
+impl Default for IdleMode {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout: 30.0,
+            attract: false,
+        }
+    }
+}
+
+/// Daily practice goal, quitting the app needs the parent PIN until it's reached
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PracticeLock {
+    pub enabled: bool,
+    /// Required practice per day
+    pub minutes: f32,
+    /// The lock is only active with a PIN set
+    pub pin: String,
+    /// Days since the Unix epoch, `practiced` starts over on a new day
+    pub day: u64,
+    /// Seconds practiced on `day`
+    pub practiced: f32,
+}
+
+impl Default for PracticeLock {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            minutes: 20.0,
+            pin: String::new(),
+            day: 0,
+            practiced: 0.0,
+        }
+    }
+}
+
+impl PracticeLock {
+    fn today() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            / (24 * 60 * 60)
+    }
+
+    pub fn add_practice(&mut self, time: std::time::Duration) {
+        let today = Self::today();
+        if self.day != today {
+            self.day = today;
+            self.practiced = 0.0;
+        }
+        self.practiced += time.as_secs_f32();
+    }
+
+    /// Practice still required today, in seconds
+    pub fn remaining(&self) -> f32 {
+        if !self.enabled {
+            return 0.0;
+        }
+
+        let practiced = if self.day == Self::today() {
+            self.practiced
+        } else {
+            0.0
+        };
+
+        (self.minutes * 60.0 - practiced).max(0.0)
+    }
+
+    pub fn is_locked(&self) -> bool {
+        !self.pin.is_empty() && self.remaining() > 0.0
+    }
+
+    pub fn unlocks_with(&self, pin: &str) -> bool {
+        !self.pin.is_empty() && self.pin == pin
+    }
+
+    /// Whether the lock settings can be changed with `pin`, anyone can until a PIN is set
+    pub fn can_change_with(&self, pin: &str) -> bool {
+        self.pin.is_empty() || self.unlocks_with(pin)
+    }
+}
+
+/// System wide shortcuts that work while another app is focused, eg. a DAW or a PDF score.
+/// Shortcuts are written like `control+alt+Space`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct GlobalHotkeys {
+    pub enabled: bool,
+    pub play_pause: String,
+    pub rewind: String,
+    pub forward: String,
+    pub mute: String,
+}
+
+impl Default for GlobalHotkeys {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            play_pause: "control+alt+Space".into(),
+            rewind: "control+alt+ArrowLeft".into(),
+            forward: "control+alt+ArrowRight".into(),
+            mute: "control+alt+KeyM".into(),
+        }
+    }
+}
+
+/// Opt-in check for new releases
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct UpdateCheck {
+    pub enabled: bool,
+    /// Unix time of the last check, in seconds
+    pub last_check: u64,
+    /// Release tag the user doesn't want to hear about again
+    pub skipped_version: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     #[serde(default = "default_speed_multiplier")]
@@ -23,14 +1480,236 @@ pub struct Config {
     #[serde(default = "default_color_schema")]
     pub color_schema: Vec<ColorSchema>,
 
+    #[serde(default)]
+    pub hand_colors: HandColors,
+
+    #[serde(default)]
+    pub pitch_colors: PitchColors,
+
     #[serde(default)]
     pub background_color: (u8, u8, u8),
 
+    /// Color the background fades to at the bottom, `None` keeps it flat
+    #[serde(default)]
+    pub background_gradient: Option<(u8, u8, u8)>,
+
+    #[serde(default)]
+    pub background_image: BackgroundImage,
+
+    #[serde(default)]
+    pub key_colors: KeyColors,
+
+    /// Highlights of the menu
+    #[serde(default = "default_accent_color")]
+    pub accent_color: (u8, u8, u8),
+
+    /// Name of the theme file in the themes folder, see [`crate::theme`]
+    #[serde(default)]
+    pub theme: Option<String>,
+
+    /// Loaded from the file of `theme`, never saved
+    #[serde(skip)]
+    pub theme_overlay: Theme,
+
+    #[serde(default)]
+    pub note_style: NoteStyle,
+
+    /// How far keys visibly travel when pressed, in pixels, `0.0` only changes their color
+    #[serde(default)]
+    pub key_travel: f32,
+
+    /// How long before their notes keys start to light up, in seconds,
+    /// `0.0` only lights them once the note starts
+    #[serde(default)]
+    pub key_lookahead: f32,
+
+    #[serde(default)]
+    pub camera: CameraConfig,
+
+    #[serde(default)]
+    pub title_cards: TitleCards,
+
+    #[serde(default)]
+    pub video_chapters: VideoChapters,
+
+    #[serde(default)]
+    pub click_track: ClickTrack,
+
+    #[serde(default)]
+    pub loudness_normalization: LoudnessNormalization,
+
+    #[serde(default)]
+    pub metronome: Metronome,
+
+    /// Beats of the metronome scene, it sounds like `metronome`
+    #[serde(default)]
+    pub metronome_practice: ClickPattern,
+
+    /// Semitones the song is moved by while playing, for practicing it in another key
+    #[serde(default)]
+    pub transpose: i8,
+
+    #[serde(default)]
+    pub watermark: Watermark,
+
+    #[serde(default)]
+    pub progress_bar: ProgressBar,
+
+    #[serde(default)]
+    pub lyrics: Lyrics,
+
+    #[serde(default)]
+    pub audio_pane: AudioPane,
+
+    /// Shows channel 10 hits above the keyboard, grouped by GM drum kind
+    #[serde(default)]
+    pub drum_lane: bool,
+
+    #[serde(default)]
+    pub notation: Notation,
+
+    /// Tints the waterfall behind each measure by how many times it was played
+    #[serde(default)]
+    pub practice_heatmap: bool,
+
+    /// Sparks fly where notes hit the keyboard, more of them for louder notes
+    #[serde(default)]
+    pub impact_particles: bool,
+
+    /// Hides the progress bar, HUD and cursor while playing, for clean recordings
+    #[serde(default)]
+    pub clean_mode: bool,
+
+    /// Pauses the song while the window is unfocused or minimized
+    #[serde(default)]
+    pub pause_on_focus_loss: bool,
+
     #[serde(default = "default_output")]
     pub output: Option<String>,
     pub input: Option<String>,
+    /// Inputs connected along with `input`, what's played on them is merged with it,
+    /// eg. a second keyboard for duets or a pedal controller
+    #[serde(default)]
+    pub extra_inputs: Vec<String>,
 
     pub soundfont_path: Option<PathBuf>,
+
+    #[serde(default = "default_forward_controllers")]
+    pub forward_controllers: bool,
+
+    #[serde(default = "default_forward_program_changes")]
+    pub forward_program_changes: bool,
+
+    #[serde(default = "default_forward_sysex")]
+    pub forward_sysex: bool,
+
+    /// Repeats and D.C./D.S. jumps of scores are followed, otherwise every measure is played
+    /// once as written. Takes effect on the next song loaded
+    #[serde(default = "default_expand_repeats")]
+    pub expand_repeats: bool,
+
+    #[serde(default)]
+    pub system_reset: SystemReset,
+
+    #[serde(default)]
+    pub velocity_curve: VelocityCurve,
+
+    #[serde(default)]
+    pub melody_boost: MelodyBoost,
+
+    #[serde(default)]
+    pub master_volume: MasterVolume,
+
+    #[serde(default)]
+    pub loop_playback: LoopPlayback,
+
+    #[serde(default)]
+    pub phrase_practice: PhrasePractice,
+
+    #[serde(default)]
+    pub speed_trainer: SpeedTrainer,
+
+    #[serde(default)]
+    pub pitch_hint: PitchHint,
+
+    #[serde(default)]
+    pub wait_hints: WaitHints,
+
+    #[serde(default)]
+    pub dead_keys: DeadKeys,
+
+    #[serde(default)]
+    pub note_labels: NoteLabels,
+
+    #[serde(default)]
+    pub scale_overlay: ScaleOverlay,
+
+    #[serde(default)]
+    pub key_click: KeyClick,
+
+    #[serde(default)]
+    pub session_export: SessionExport,
+
+    #[serde(default)]
+    pub input_recording: InputRecording,
+
+    #[serde(default)]
+    pub idle_mode: IdleMode,
+
+    #[serde(default)]
+    pub practice_lock: PracticeLock,
+
+    #[serde(default)]
+    pub global_hotkeys: GlobalHotkeys,
+
+    #[serde(default)]
+    pub update_check: UpdateCheck,
+
+    #[serde(default)]
+    pub scene_transition: SceneTransition,
+
+    #[serde(default)]
+    pub low_power: LowPower,
+
+    #[serde(default)]
+    pub render_quality: RenderQuality,
+
+    #[serde(default)]
+    pub hdr: Hdr,
+
+    #[serde(default)]
+    pub light_guide: LightGuide,
+
+    #[serde(default)]
+    pub art_net: ArtNet,
+
+    #[serde(default)]
+    pub pad_grid: PadGrid,
+
+    #[serde(default)]
+    pub virtual_piano: VirtualPiano,
+
+    /// Keyed by output name
+    #[serde(default)]
+    pub output_remaps: HashMap<String, OutputRemap>,
+
+    /// What's played on the input is also played on the output while a song plays,
+    /// otherwise only free play sounds it
+    #[serde(default)]
+    pub play_input_notes: bool,
+
+    /// Names of inputs with a sound of their own, what's played on them is only shown
+    /// and scored, never played on the output
+    #[serde(default)]
+    pub muted_inputs: HashSet<String>,
+
+    /// Keyed by input name, everything played on the input is moved onto this channel
+    #[serde(default)]
+    pub input_channels: HashMap<String, u8>,
+
+    /// Keyed by input name, how close play along presses on the input have to be to the notes
+    #[serde(default)]
+    pub note_tolerances: HashMap<String, NoteTolerance>,
 }
 
 impl Default for Config {
@@ -41,49 +1720,240 @@ impl Default for Config {
 
 impl Config {
     pub fn new() -> Self {
-        let config: Option<Config> = if let Some(path) = crate::utils::resources::settings_ron() {
-            if let Ok(file) = std::fs::read_to_string(path) {
-                match ron::from_str(&file) {
-                    Ok(config) => Some(config),
-                    Err(err) => {
-                        log::error!("{:#?}", err);
-                        None
-                    }
-                }
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+        let config = crate::utils::resources::settings_ron().and_then(|path| Self::load(&path));
 
         config.unwrap_or_else(|| Self {
             speed_multiplier: default_speed_multiplier(),
             playback_offset: default_playback_offset(),
             play_along: default_play_along(),
             color_schema: default_color_schema(),
+            hand_colors: HandColors::default(),
+            pitch_colors: PitchColors::default(),
             background_color: Default::default(),
+            background_gradient: None,
+            background_image: BackgroundImage::default(),
+            key_colors: KeyColors::default(),
+            accent_color: default_accent_color(),
+            theme: None,
+            theme_overlay: Theme::default(),
+            note_style: NoteStyle::default(),
+            key_travel: 0.0,
+            key_lookahead: 0.0,
+            camera: CameraConfig::default(),
+            title_cards: TitleCards::default(),
+            video_chapters: VideoChapters::default(),
+            click_track: ClickTrack::default(),
+            loudness_normalization: LoudnessNormalization::default(),
+            metronome: Metronome::default(),
+            metronome_practice: ClickPattern::default(),
+            transpose: 0,
+            watermark: Watermark::default(),
+            progress_bar: ProgressBar::default(),
+            lyrics: Lyrics::default(),
+            audio_pane: AudioPane::default(),
+            drum_lane: false,
+            notation: Notation::default(),
+            practice_heatmap: false,
+            impact_particles: false,
+            clean_mode: false,
+            pause_on_focus_loss: false,
             output: default_output(),
             input: None,
+            extra_inputs: Vec::new(),
             soundfont_path: None,
+            forward_controllers: default_forward_controllers(),
+            forward_program_changes: default_forward_program_changes(),
+            forward_sysex: default_forward_sysex(),
+            expand_repeats: default_expand_repeats(),
+            system_reset: SystemReset::default(),
+            velocity_curve: VelocityCurve::default(),
+            melody_boost: MelodyBoost::default(),
+            master_volume: MasterVolume::default(),
+            loop_playback: LoopPlayback::default(),
+            phrase_practice: PhrasePractice::default(),
+            speed_trainer: SpeedTrainer::default(),
+            pitch_hint: PitchHint::default(),
+            wait_hints: WaitHints::default(),
+            dead_keys: DeadKeys::default(),
+            note_labels: NoteLabels::default(),
+            scale_overlay: ScaleOverlay::default(),
+            key_click: KeyClick::default(),
+            play_input_notes: false,
+            session_export: SessionExport::default(),
+            input_recording: InputRecording::default(),
+            idle_mode: IdleMode::default(),
+            practice_lock: PracticeLock::default(),
+            global_hotkeys: GlobalHotkeys::default(),
+            update_check: UpdateCheck::default(),
+            scene_transition: SceneTransition::default(),
+            low_power: LowPower::default(),
+            render_quality: RenderQuality::default(),
+            hdr: Hdr::default(),
+            light_guide: LightGuide::default(),
+            art_net: ArtNet::default(),
+            pad_grid: PadGrid::default(),
+            virtual_piano: VirtualPiano::default(),
+            output_remaps: HashMap::new(),
+            muted_inputs: HashSet::new(),
+            input_channels: HashMap::new(),
+            note_tolerances: HashMap::new(),
         })
     }
 
+    /// Reads the settings at `path`, merging in conflict copies left by sync clients.
+    ///
+    /// The newest file wins, except for per-output settings which are combined
+    fn load(path: &Path) -> Option<Self> {
+        let copies = sync::conflict_copies(path);
+
+        let mut files: Vec<PathBuf> = std::iter::once(path.to_owned())
+            .chain(copies.iter().cloned())
+            .filter(|path| path.exists())
+            .collect();
+        files.sort_by_key(|path| std::cmp::Reverse(sync::modified(path)));
+
+        let mut files = files.into_iter();
+        let mut config = files.by_ref().find_map(|path| {
+            let file = std::fs::read_to_string(&path).ok()?;
+            match Self::from_ron(&file) {
+                Ok(config) => Some(config),
+                Err(err) => {
+                    log::error!("{}: {:#?}", path.display(), err);
+                    None
+                }
+            }
+        })?;
+
+        for path in files {
+            let merged = std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|file| ron::from_str::<MergedSettings>(&file).ok());
+
+            if let Some(merged) = merged {
+                for (output, remap) in merged.output_remaps {
+                    config.output_remaps.entry(output).or_insert(remap);
+                }
+                config.muted_inputs.extend(merged.muted_inputs);
+                for (input, channel) in merged.input_channels {
+                    config.input_channels.entry(input).or_insert(channel);
+                }
+                for (input, tolerance) in merged.note_tolerances {
+                    config.note_tolerances.entry(input).or_insert(tolerance);
+                }
+            }
+        }
+
+        if !copies.is_empty() {
+            log::info!("Merged {} conflicting settings files", copies.len());
+
+            let saved = config
+                .to_ron()
+                .map(|s| sync::write_atomic(path, s).is_ok())
+                .unwrap_or(false);
+            if saved {
+                for copy in copies {
+                    std::fs::remove_file(copy).ok();
+                }
+            }
+        }
+
+        Some(config)
+    }
+
+    pub fn from_ron(s: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(s)
+    }
+
+    pub fn to_ron(&self) -> Option<String> {
+        ron::ser::to_string_pretty(self, Default::default()).ok()
+    }
+
     pub fn set_output(&mut self, output: Option<String>) {
         self.output = output;
     }
 
+    pub fn output_remap(&self, output: &str) -> OutputRemap {
+        self.output_remaps.get(output).copied().unwrap_or_default()
+    }
+
+    pub fn set_output_remap(&mut self, output: String, remap: OutputRemap) {
+        if remap == OutputRemap::default() {
+            self.output_remaps.remove(&output);
+        } else {
+            self.output_remaps.insert(output, remap);
+        }
+    }
+
+    pub fn is_input_muted(&self, input: &str) -> bool {
+        self.muted_inputs.contains(input)
+    }
+
+    pub fn set_input_muted(&mut self, input: String, muted: bool) {
+        if muted {
+            self.muted_inputs.insert(input);
+        } else {
+            self.muted_inputs.remove(&input);
+        }
+    }
+
     pub fn set_input<D: std::fmt::Display>(&mut self, v: Option<D>) {
         self.input = v.map(|v| v.to_string());
     }
+
+    pub fn is_extra_input(&self, input: &str) -> bool {
+        self.extra_inputs.iter().any(|name| name == input)
+    }
+
+    pub fn set_extra_input(&mut self, input: String, enabled: bool) {
+        self.extra_inputs.retain(|name| *name != input);
+        if enabled {
+            self.extra_inputs.push(input);
+        }
+    }
+
+    pub fn input_channel(&self, input: &str) -> Option<u8> {
+        self.input_channels.get(input).copied()
+    }
+
+    pub fn set_input_channel(&mut self, input: String, channel: Option<u8>) {
+        match channel {
+            Some(channel) => self.input_channels.insert(input, channel),
+            None => self.input_channels.remove(&input),
+        };
+    }
+
+    pub fn note_tolerance(&self, input: &str) -> NoteTolerance {
+        self.note_tolerances.get(input).copied().unwrap_or_default()
+    }
+
+    pub fn set_note_tolerance(&mut self, input: String, tolerance: NoteTolerance) {
+        if tolerance == NoteTolerance::default() {
+            self.note_tolerances.remove(&input);
+        } else {
+            self.note_tolerances.insert(input, tolerance);
+        }
+    }
+}
+
+/// Settings that are combined from every conflict copy, rather than taken from the newest
+#[derive(Deserialize)]
+struct MergedSettings {
+    #[serde(default)]
+    output_remaps: HashMap<String, OutputRemap>,
+    #[serde(default)]
+    muted_inputs: HashSet<String>,
+    #[serde(default)]
+    input_channels: HashMap<String, u8>,
+    #[serde(default)]
+    note_tolerances: HashMap<String, NoteTolerance>,
 }
 
 impl Drop for Config {
     fn drop(&mut self) {
-        if let Ok(s) = ron::ser::to_string_pretty(self, Default::default()) {
+        if let Some(s) = self.to_ron() {
             if let Some(path) = crate::utils::resources::settings_ron() {
                 std::fs::create_dir_all(path.parent().unwrap()).ok();
-                std::fs::write(path, s).ok();
+                sync::write_atomic(&path, s).ok();
             }
         }
     }
@@ -101,7 +1971,7 @@ fn default_play_along() -> bool {
     false
 }
 
-fn default_color_schema() -> Vec<ColorSchema> {
+pub fn default_color_schema() -> Vec<ColorSchema> {
     vec![
         ColorSchema {
             base: (210, 89, 222),
@@ -123,13 +1993,57 @@ fn default_color_schema() -> Vec<ColorSchema> {
             base: (255, 51, 129),
             dark: (48, 124, 255),
         },
-        ColorSchema {
-            base: (210, 89, 222),
-            dark: (125, 69, 134),
-        },
     ]
 }
 
+fn default_accent_color() -> (u8, u8, u8) {
+    (160, 81, 255)
+}
+
 fn default_output() -> Option<String> {
     Some("Buildin Synth".into())
 }
+
+fn default_forward_controllers() -> bool {
+    true
+}
+
+fn default_forward_program_changes() -> bool {
+    true
+}
+
+fn default_forward_sysex() -> bool {
+    false
+}
+
+fn default_expand_repeats() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn velocity_scale() {
+        let mut style = NoteStyle::default();
+        assert_eq!(style.velocity_scale(1), (1.0, 1.0));
+        assert_eq!(style.velocity_scale(127), (1.0, 1.0));
+
+        style.velocity = true;
+        let (brightness, width) = style.velocity_scale(0);
+        assert!((brightness - 0.6).abs() < 1e-6);
+        assert!((width - 0.75).abs() < 1e-6);
+
+        let (brightness, width) = style.velocity_scale(127);
+        assert!((brightness - 1.1).abs() < 1e-6);
+        assert_eq!(width, 1.0);
+
+        // Out of range velocities are loud ones
+        assert_eq!(style.velocity_scale(200), style.velocity_scale(127));
+
+        let quiet = style.velocity_scale(40);
+        let loud = style.velocity_scale(100);
+        assert!(quiet.0 < loud.0 && quiet.1 < loud.1);
+    }
+}
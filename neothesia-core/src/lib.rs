@@ -4,4 +4,5 @@ pub use wgpu_jumpstart::{Gpu, TransformUniform, Uniform};
 
 pub mod config;
 pub mod render;
+pub mod theme;
 pub mod utils;
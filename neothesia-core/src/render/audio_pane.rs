@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use neothesia_pipelines::quad::{QuadInstance, QuadPipeline};
+use wgpu_jumpstart::{wgpu, Color, Gpu, TransformUniform, Uniform};
+
+use crate::{
+    config::{AudioPane, AudioVisualizer},
+    utils::spectrum::{self, AudioTap},
+};
+
+const BANDS: usize = 32;
+/// Fraction of full scale a bar falls per second
+const FALL_SPEED: f32 = 1.5;
+const GAP: f32 = 2.0;
+
+/// Spectrum or level meter of the synth output
+pub struct AudioPaneRenderer {
+    quad_pipeline: QuadPipeline,
+    tap: AudioTap,
+    /// Smoothed bar heights
+    bars: Vec<f32>,
+}
+
+impl AudioPaneRenderer {
+    pub fn new(gpu: &Gpu, transform_uniform: &Uniform<TransformUniform>, tap: AudioTap) -> Self {
+        Self {
+            quad_pipeline: QuadPipeline::new(gpu, transform_uniform),
+            tap,
+            bars: Vec::new(),
+        }
+    }
+
+    /// Analyzes the latest audio and lays the bars out in the `position`, `size` rect
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        pane: &AudioPane,
+        delta: Duration,
+        position: [f32; 2],
+        size: [f32; 2],
+    ) {
+        let (frames, sample_rate) = self.tap.snapshot();
+
+        let levels = match pane.visualizer {
+            AudioVisualizer::Spectrum => spectrum::spectrum(&frames, sample_rate, BANDS),
+            AudioVisualizer::LevelMeter => {
+                let (l, r) = spectrum::levels(&frames);
+                vec![l, r]
+            }
+        };
+
+        // Bars jump up right away and fall slowly, so they don't flicker
+        self.bars.resize(levels.len(), 0.0);
+        let fall = FALL_SPEED * delta.as_secs_f32();
+        for (bar, level) in self.bars.iter_mut().zip(levels) {
+            *bar = level.max(*bar - fall);
+        }
+
+        let (r, g, b) = pane.color;
+        let color = Color::from_rgba8(r, g, b, 1.0).into_linear_rgba();
+
+        let [x, y] = position;
+        let [w, h] = size;
+        let bar_w = (w / self.bars.len() as f32 - GAP).max(1.0);
+
+        self.quad_pipeline.with_instances_mut(queue, |instances| {
+            instances.clear();
+
+            for (id, bar) in self.bars.iter().enumerate() {
+                let bar_h = h * bar;
+                instances.push(QuadInstance {
+                    position: [x + id as f32 * (bar_w + GAP) + GAP / 2.0, y + h - bar_h],
+                    size: [bar_w, bar_h],
+                    color,
+                    ..Default::default()
+                });
+            }
+        });
+    }
+
+    pub fn render<'rpass>(
+        &'rpass self,
+        transform_uniform: &'rpass Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+    ) {
+        self.quad_pipeline.render(transform_uniform, render_pass);
+    }
+}
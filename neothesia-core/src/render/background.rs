@@ -0,0 +1,270 @@
+use std::{path::Path, time::Duration};
+
+use ::image::{imageops::FilterType, AnimationDecoder, RgbaImage};
+use neothesia_pipelines::{
+    background_animation::BgPipeline,
+    image::ImagePipeline,
+    quad::{QuadInstance, QuadPipeline},
+};
+use wgpu_jumpstart::{wgpu, Color, Gpu, TransformUniform, Uniform};
+
+use crate::config::{BackgroundImage, BackgroundKind};
+
+/// Bands of the gradient, enough that the steps between them don't show
+const BANDS: usize = 64;
+/// Frames shorter than this are shown this long, as browsers do
+const MIN_FRAME_TIME: Duration = Duration::from_millis(20);
+/// Longest side of blurred images, see [`blurred`]
+const BLURRED_SIZE: u32 = 320;
+
+/// Image or animation drawn instead of the background color
+enum Media {
+    Image(Picture),
+    Animated(BgPipeline),
+}
+
+struct Picture {
+    pipeline: ImagePipeline,
+    /// More than one for animated GIFs, with how long each of them is shown
+    frames: Vec<(RgbaImage, Duration)>,
+    frame: usize,
+    elapsed: Duration,
+}
+
+impl Picture {
+    fn load(
+        gpu: &Gpu,
+        transform_uniform: &Uniform<TransformUniform>,
+        path: &Path,
+        blur: f32,
+        sample_count: u32,
+    ) -> Option<Self> {
+        let frames = match load_frames(path) {
+            Ok(frames) => frames,
+            Err(err) => {
+                log::error!("Failed to load image {}: {err}", path.display());
+                return None;
+            }
+        };
+        let frames: Vec<_> = frames
+            .into_iter()
+            .map(|(image, delay)| (blurred(image, blur), delay))
+            .collect();
+
+        let (first, _) = frames.first()?;
+        let pipeline = ImagePipeline::with_sample_count(
+            gpu,
+            transform_uniform,
+            first.as_raw(),
+            first.width(),
+            first.height(),
+            sample_count,
+        );
+
+        Some(Self {
+            pipeline,
+            frames,
+            frame: 0,
+            elapsed: Duration::ZERO,
+        })
+    }
+
+    /// Covers the whole view, what sticks out is cut off
+    fn place(&mut self, queue: &wgpu::Queue, size: [f32; 2]) {
+        let (w, h) = self.pipeline.image_size();
+        let [width, height] = size;
+
+        let scale = (width / w.max(1) as f32).max(height / h.max(1) as f32);
+        let image_size = [w as f32 * scale, h as f32 * scale];
+        let position = [
+            (width - image_size[0]) / 2.0,
+            (height - image_size[1]) / 2.0,
+        ];
+
+        self.pipeline.set_rect(queue, position, image_size, 1.0);
+    }
+
+    fn advance(&mut self, queue: &wgpu::Queue, delta: Duration) {
+        if self.frames.len() < 2 {
+            return;
+        }
+
+        self.elapsed += delta;
+        let mut changed = false;
+        while self.elapsed >= self.frames[self.frame].1 {
+            self.elapsed -= self.frames[self.frame].1;
+            self.frame = (self.frame + 1) % self.frames.len();
+            changed = true;
+        }
+
+        if changed {
+            self.pipeline
+                .set_pixels(queue, self.frames[self.frame].0.as_raw());
+        }
+    }
+}
+
+/// Frames of the image at `path`, a still image has one
+fn load_frames(path: &Path) -> ::image::ImageResult<Vec<(RgbaImage, Duration)>> {
+    let gif = path
+        .extension()
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("gif"));
+    if !gif {
+        return Ok(vec![(::image::open(path)?.to_rgba8(), Duration::ZERO)]);
+    }
+
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let frames = ::image::codecs::gif::GifDecoder::new(file)?
+        .into_frames()
+        .collect_frames()?;
+
+    Ok(frames
+        .into_iter()
+        .map(|frame| {
+            let delay = Duration::from(frame.delay()).max(MIN_FRAME_TIME);
+            (frame.into_buffer(), delay)
+        })
+        .collect())
+}
+
+/// Shrunk to [`BLURRED_SIZE`] first, the blur hides the detail that goes and it's stretched
+/// back over the view anyway. Keeps blurring every frame of a GIF quick
+fn blurred(image: RgbaImage, blur: f32) -> RgbaImage {
+    if blur <= 0.0 {
+        return image;
+    }
+
+    let (width, height) = image.dimensions();
+    let scale = (BLURRED_SIZE as f32 / width.max(height) as f32).min(1.0);
+    let image = if scale < 1.0 {
+        let width = ((width as f32 * scale).round() as u32).max(1);
+        let height = ((height as f32 * scale).round() as u32).max(1);
+        ::image::imageops::resize(&image, width, height, FilterType::Triangle)
+    } else {
+        image
+    };
+
+    let sigma = blur * 0.02 * image.width().max(image.height()) as f32;
+    ::image::imageops::blur(&image, sigma)
+}
+
+/// Background fading from the background color at the top to the gradient color at the
+/// bottom, drawn over the flat background color. An image or the animation of the menu
+/// takes its place when one is set
+pub struct BackgroundRenderer {
+    quad_pipeline: QuadPipeline,
+    media: Option<Media>,
+    /// Opacity of the black drawn over the media
+    dim: f32,
+}
+
+impl BackgroundRenderer {
+    pub fn new(
+        gpu: &Gpu,
+        transform_uniform: &Uniform<TransformUniform>,
+        sample_count: u32,
+        image: &BackgroundImage,
+    ) -> Self {
+        let media = match image.kind {
+            BackgroundKind::Color => None,
+            BackgroundKind::Image => image
+                .path
+                .as_deref()
+                .and_then(|path| {
+                    Picture::load(gpu, transform_uniform, path, image.blur, sample_count)
+                })
+                .map(Media::Image),
+            BackgroundKind::Animated => Some(Media::Animated(BgPipeline::with_sample_count(
+                gpu,
+                sample_count,
+            ))),
+        };
+
+        Self {
+            quad_pipeline: QuadPipeline::with_sample_count(gpu, transform_uniform, sample_count),
+            media,
+            dim: image.dim.clamp(0.0, 1.0),
+        }
+    }
+
+    /// `gradient` of `None` leaves the flat background alone
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        top: (u8, u8, u8),
+        gradient: Option<(u8, u8, u8)>,
+        size: [f32; 2],
+    ) {
+        let [width, height] = size;
+
+        if self.media.is_some() {
+            if let Some(Media::Image(picture)) = self.media.as_mut() {
+                picture.place(queue, size);
+            }
+
+            let dim = QuadInstance {
+                position: [0.0, 0.0],
+                size: [width, height],
+                color: Color::from_rgba8(0, 0, 0, self.dim).into_linear_rgba(),
+                ..Default::default()
+            };
+            self.quad_pipeline.update_instance_buffer(queue, vec![dim]);
+            return;
+        }
+
+        let bottom = match gradient {
+            Some(bottom) => bottom,
+            None => {
+                self.quad_pipeline.update_instance_buffer(queue, Vec::new());
+                return;
+            }
+        };
+
+        let band = height / BANDS as f32;
+        let mix = |a: u8, b: u8, t: f32| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+
+        let instances = (0..BANDS)
+            .map(|id| {
+                let t = id as f32 / (BANDS - 1) as f32;
+                let color = Color::from_rgba8(
+                    mix(top.0, bottom.0, t),
+                    mix(top.1, bottom.1, t),
+                    mix(top.2, bottom.2, t),
+                    1.0,
+                );
+
+                QuadInstance {
+                    position: [0.0, id as f32 * band],
+                    // Overlaps the next band, so no gaps open up between them
+                    size: [width, band + 1.0],
+                    color: color.into_linear_rgba(),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        self.quad_pipeline.update_instance_buffer(queue, instances);
+    }
+
+    /// Moves animated GIFs and the animation on
+    pub fn animate(&mut self, gpu: &mut Gpu, delta: Duration) {
+        match self.media.as_mut() {
+            Some(Media::Image(picture)) => picture.advance(&gpu.queue, delta),
+            Some(Media::Animated(animation)) => animation.update_time(gpu, delta),
+            None => {}
+        }
+    }
+
+    pub fn render<'rpass>(
+        &'rpass self,
+        transform_uniform: &'rpass Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+    ) {
+        match self.media.as_ref() {
+            Some(Media::Image(picture)) => picture.pipeline.render(transform_uniform, render_pass),
+            Some(Media::Animated(animation)) => animation.render(render_pass),
+            None => {}
+        }
+        self.quad_pipeline.render(transform_uniform, render_pass);
+    }
+}
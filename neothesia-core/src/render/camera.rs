@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use crate::{config::CameraConfig, TransformUniform};
+use midi_file::Midi;
+
+/// Notes from this far ahead decide where the camera goes
+const LOOKAHEAD: f32 = 3.0;
+/// More notes than that in the lookahead window is not a sparse passage
+const SPARSE_NOTE_COUNT: usize = 24;
+/// Time constant of the camera movement, in seconds
+const SMOOTHING: f32 = 1.5;
+
+/// Virtual camera that slowly zooms toward the active register of the keyboard
+/// during sparse passages
+#[derive(Debug, Clone)]
+pub struct Camera {
+    zoom: f32,
+    /// X of the point in the middle of the view, in logical pixels
+    center: Option<f32>,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Camera {
+    pub fn new() -> Self {
+        Self {
+            zoom: 1.0,
+            center: None,
+        }
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// `time` is the song time without lead-in, in seconds
+    pub fn update(
+        &mut self,
+        config: &CameraConfig,
+        midi: &Midi,
+        layout: &piano_math::KeyboardLayout,
+        time: f32,
+        delta: Duration,
+    ) {
+        let full_view = (1.0, layout.width / 2.0);
+
+        let (target_zoom, target_center) = if config.enabled {
+            active_register(midi, layout, time)
+                .map(|(start, end)| {
+                    let margin = layout.neutral_width * 2.0;
+                    let span = end - start + margin * 2.0;
+                    let zoom = (layout.width / span).clamp(1.0, config.max_zoom.max(1.0));
+                    (zoom, (start + end) / 2.0)
+                })
+                .unwrap_or(full_view)
+        } else {
+            full_view
+        };
+
+        let t = 1.0 - (-delta.as_secs_f32() / SMOOTHING).exp();
+        let center = self.center.unwrap_or(target_center);
+
+        self.zoom += (target_zoom - self.zoom) * t;
+        self.center = Some(center + (target_center - center) * t);
+    }
+
+    /// Top left corner of the view, keeps the bottom of the screen (the keyboard) in place.
+    /// `layout_width` is the width of the keyboard, which can be narrower than the window
+    pub fn offset(&self, layout_width: f32, height: f32) -> [f32; 2] {
+        let view_width = layout_width / self.zoom;
+        let center = self.center.unwrap_or(layout_width / 2.0);
+
+        let x = (center - view_width / 2.0).clamp(0.0, (layout_width - view_width).max(0.0));
+        let y = height - height / self.zoom;
+
+        [x, y]
+    }
+
+    pub fn apply(
+        &self,
+        uniform: &mut TransformUniform,
+        width: f32,
+        height: f32,
+        layout_width: f32,
+        scale: f32,
+    ) {
+        let offset = self.offset(layout_width, height);
+        uniform.update_with_camera(width, height, scale, self.zoom, offset);
+    }
+}
+
+/// Horizontal range of keys played in the next few seconds, `None` if the passage is not sparse
+fn active_register(
+    midi: &Midi,
+    layout: &piano_math::KeyboardLayout,
+    time: f32,
+) -> Option<(f32, f32)> {
+    let notes = &midi.merged_track.notes;
+    let from = Duration::from_secs_f32(time.max(0.0));
+    let to = Duration::from_secs_f32(time.max(0.0) + LOOKAHEAD);
+
+    let first = notes.partition_point(|n| n.start < from);
+    let upcoming = notes[first..]
+        .iter()
+        .take_while(|n| n.start < to)
+        .chain(midi.merged_track.notes_at(from))
+        .filter(|n| n.channel != 9 && layout.range.contains(n.note));
+
+    let mut count = 0;
+    let mut range: Option<(f32, f32)> = None;
+
+    for note in upcoming {
+        count += 1;
+        if count > SPARSE_NOTE_COUNT {
+            return None;
+        }
+
+        let key = &layout.keys[(note.note - layout.range.start()) as usize];
+        let (start, end) = (key.x(), key.x() + key.width());
+
+        range = Some(match range {
+            Some((s, e)) => (s.min(start), e.max(end)),
+            None => (start, end),
+        });
+    }
+
+    range
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_stays_over_the_keyboard() {
+        let mut camera = Camera::new();
+        assert_eq!(camera.offset(800.0, 600.0), [0.0, 0.0]);
+
+        camera.zoom = 2.0;
+        camera.center = Some(700.0);
+        // The view is 400 wide and stops at the end of the keyboard, not of the window
+        assert_eq!(camera.offset(800.0, 600.0), [400.0, 300.0]);
+
+        camera.center = Some(0.0);
+        assert_eq!(camera.offset(800.0, 600.0), [0.0, 300.0]);
+    }
+}
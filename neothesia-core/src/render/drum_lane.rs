@@ -0,0 +1,144 @@
+use midi_file::{gm::DrumGroup, Midi};
+use neothesia_pipelines::quad::{QuadInstance, QuadPipeline};
+use wgpu_jumpstart::{wgpu, Color, Gpu, TransformUniform, Uniform};
+
+use crate::{render::TextRenderer, utils::palette::generated_color};
+
+const ROW_HEIGHT: f32 = 18.0;
+/// Width of the row titles, hits are played when they reach its edge
+const LABEL_WIDTH: f32 = 110.0;
+const PIXELS_PER_SECOND: f32 = 200.0;
+/// How long a played hit keeps its name in the row title, in seconds
+const NAME_HOLD: f32 = 0.5;
+
+struct DrumHit {
+    /// Seconds
+    start: f32,
+    row: usize,
+    note: u8,
+}
+
+/// Channel 10 notes scrolling towards the keyboard, one row per drum group
+pub struct DrumLaneRenderer {
+    quad_pipeline: QuadPipeline,
+    rows: Vec<DrumGroup>,
+    /// Sorted by start
+    hits: Vec<DrumHit>,
+}
+
+impl DrumLaneRenderer {
+    /// Returns `None` when the file has no percussion
+    pub fn new(
+        gpu: &Gpu,
+        transform_uniform: &Uniform<TransformUniform>,
+        midi: &Midi,
+    ) -> Option<Self> {
+        let drums = midi.merged_track.notes.iter().filter(|n| n.channel == 9);
+
+        let mut rows: Vec<DrumGroup> = drums.clone().map(|n| DrumGroup::of(n.note)).collect();
+        rows.sort();
+        rows.dedup();
+
+        if rows.is_empty() {
+            return None;
+        }
+
+        let hits = drums
+            .map(|note| DrumHit {
+                start: note.start.as_secs_f32(),
+                row: rows.binary_search(&DrumGroup::of(note.note)).unwrap(),
+                note: note.note,
+            })
+            .collect();
+
+        Some(Self {
+            quad_pipeline: QuadPipeline::new(gpu, transform_uniform),
+            rows,
+            hits,
+        })
+    }
+
+    pub fn height(&self) -> f32 {
+        self.rows.len() as f32 * ROW_HEIGHT
+    }
+
+    /// Lays out the hits around `time` in a lane of `width` with its top left corner at `position`
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        text: &mut TextRenderer,
+        time: f32,
+        position: [f32; 2],
+        width: f32,
+    ) {
+        let [x, y] = position;
+        let now_x = x + LABEL_WIDTH;
+        let end = time + (width - LABEL_WIDTH) / PIXELS_PER_SECOND;
+
+        let first = self.hits.partition_point(|hit| hit.start < time);
+        let last = self.hits.partition_point(|hit| hit.start < end);
+
+        let mut instances = vec![
+            QuadInstance {
+                position: [x, y],
+                size: [width, self.height()],
+                color: Color::from_rgba8(0, 0, 0, 0.6).into_linear_rgba(),
+                ..Default::default()
+            },
+            QuadInstance {
+                position: [now_x - 1.0, y],
+                size: [2.0, self.height()],
+                color: Color::from_rgba8(255, 255, 255, 0.8).into_linear_rgba(),
+                ..Default::default()
+            },
+        ];
+
+        for hit in self.hits[first..last].iter() {
+            let (r, g, b) = generated_color(self.rows[hit.row] as usize).base;
+
+            instances.push(QuadInstance {
+                position: [
+                    now_x + (hit.start - time) * PIXELS_PER_SECOND,
+                    y + hit.row as f32 * ROW_HEIGHT + 2.0,
+                ],
+                size: [8.0, ROW_HEIGHT - 4.0],
+                color: Color::from_rgba8(r, g, b, 1.0).into_linear_rgba(),
+                border_radius: [3.0; 4],
+            });
+        }
+
+        self.quad_pipeline.update_instance_buffer(queue, instances);
+
+        // Most recently played drum of each row
+        let mut played: Vec<Option<u8>> = vec![None; self.rows.len()];
+        for hit in self.hits[..first].iter().rev() {
+            if hit.start < time - NAME_HOLD {
+                break;
+            }
+            played[hit.row].get_or_insert(hit.note);
+        }
+
+        for (row, group) in self.rows.iter().enumerate() {
+            let name = played[row]
+                .and_then(midi_file::gm::drum_name)
+                .unwrap_or(group.name());
+
+            text.queue_text(wgpu_glyph::Section {
+                screen_position: (x + 4.0, y + row as f32 * ROW_HEIGHT + 2.0),
+                bounds: (LABEL_WIDTH - 8.0, ROW_HEIGHT),
+                text: vec![wgpu_glyph::Text::new(name)
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(13.0)],
+                ..Default::default()
+            });
+        }
+    }
+
+    pub fn render<'rpass>(
+        &'rpass self,
+        transform_uniform: &'rpass Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+    ) {
+        self.quad_pipeline.render(transform_uniform, render_pass);
+    }
+}
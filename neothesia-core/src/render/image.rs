@@ -0,0 +1,27 @@
+use std::path::Path;
+
+use neothesia_pipelines::image::ImagePipeline;
+use wgpu_jumpstart::{Gpu, TransformUniform, Uniform};
+
+/// Decodes the image at `path`, logs and returns `None` on failure
+pub fn load_image(
+    gpu: &Gpu,
+    transform_uniform: &Uniform<TransformUniform>,
+    path: &Path,
+) -> Option<ImagePipeline> {
+    let image = match ::image::open(path) {
+        Ok(image) => image.to_rgba8(),
+        Err(err) => {
+            log::error!("Failed to load image {}: {err}", path.display());
+            return None;
+        }
+    };
+
+    Some(ImagePipeline::new(
+        gpu,
+        transform_uniform,
+        image.as_raw(),
+        image.width(),
+        image.height(),
+    ))
+}
@@ -1,16 +1,29 @@
 use crate::{
-    config::ColorSchema,
+    config::{ColorSchema, KeyColors},
     utils::{Point, Size},
 };
 use neothesia_pipelines::quad::QuadInstance;
+use std::time::Duration;
 use wgpu_jumpstart::Color;
 
+/// Time it takes a key to travel all the way down
+const PRESS_TIME: Duration = Duration::from_millis(40);
+
 #[derive(Debug, Clone)]
 pub struct KeyState {
     is_sharp: bool,
 
     pressed_by_file: Option<Color>,
     pressed_by_user: bool,
+    /// Lit up as a hint of what to play
+    hinted: bool,
+    /// Color of the next note, and how close it is, `1.0` is about to start
+    upcoming: Option<(Color, f32)>,
+    /// Tint of the scale overlay, and how much of it the key gets
+    scale: Option<(Color, f32)>,
+
+    /// `0.0` is at rest, `1.0` fully pressed
+    depth: f32,
 }
 
 impl KeyState {
@@ -20,6 +33,11 @@ impl KeyState {
 
             pressed_by_file: None,
             pressed_by_user: false,
+            hinted: false,
+            upcoming: None,
+            scale: None,
+
+            depth: 0.0,
         }
     }
 
@@ -27,6 +45,33 @@ impl KeyState {
         self.pressed_by_user = is;
     }
 
+    pub fn set_hinted(&mut self, is: bool) {
+        self.hinted = is;
+    }
+
+    pub fn set_upcoming(&mut self, schem: &ColorSchema, closeness: f32) {
+        let (r, g, b) = if self.is_sharp {
+            schem.dark
+        } else {
+            schem.base
+        };
+
+        self.upcoming = Some((Color::from_rgba8(r, g, b, 1.0), closeness.clamp(0.0, 1.0)));
+    }
+
+    pub fn upcoming_off(&mut self) {
+        self.upcoming = None;
+    }
+
+    pub fn is_upcoming(&self) -> bool {
+        self.upcoming.is_some()
+    }
+
+    pub fn set_scale_tint(&mut self, color: (u8, u8, u8), amount: Option<f32>) {
+        let (r, g, b) = color;
+        self.scale = amount.map(|amount| (Color::from_rgba8(r, g, b, 1.0), amount));
+    }
+
     pub fn pressed_by_file_on(&mut self, schem: &ColorSchema) {
         let (r, g, b) = if self.is_sharp {
             schem.dark
@@ -41,16 +86,82 @@ impl KeyState {
         self.pressed_by_file = None;
     }
 
-    pub fn color(&self) -> Color {
+    pub fn is_pressed(&self) -> bool {
+        self.pressed_by_user || self.pressed_by_file.is_some()
+    }
+
+    pub fn depth(&self) -> f32 {
+        self.depth
+    }
+
+    /// Moves the key towards its pressed or released position,
+    /// returns `true` if it moved
+    pub fn animate(&mut self, delta: Duration) -> bool {
+        let target = if self.is_pressed() { 1.0 } else { 0.0 };
+
+        if self.depth == target {
+            return false;
+        }
+
+        let step = delta.as_secs_f32() / PRESS_TIME.as_secs_f32();
+        self.depth = if target > self.depth {
+            (self.depth + step).min(target)
+        } else {
+            (self.depth - step).max(target)
+        };
+
+        true
+    }
+
+    pub fn color(&self, colors: &KeyColors) -> Color {
+        let rgb = |(r, g, b): (u8, u8, u8)| Color::from_rgba8(r, g, b, 1.0);
+
         if self.pressed_by_user {
-            let v = if self.is_sharp { 0.3 } else { 0.5 };
-            Color::new(v, v, v, 1.0)
+            let color = rgb(colors.pressed);
+            if self.is_sharp {
+                Color::new(color.r * 0.6, color.g * 0.6, color.b * 0.6, 1.0)
+            } else {
+                color
+            }
         } else if let Some(color) = self.pressed_by_file {
             color
-        } else if self.is_sharp {
-            Color::new(0.0, 0.0, 0.0, 1.0)
+        } else if self.hinted {
+            if self.is_sharp {
+                Color::new(0.6, 0.45, 0.1, 1.0)
+            } else {
+                Color::new(1.0, 0.85, 0.45, 1.0)
+            }
         } else {
-            Color::new(1.0, 1.0, 1.0, 1.0)
+            let rest = rgb(if self.is_sharp {
+                colors.black
+            } else {
+                colors.white
+            });
+            let rest = match self.scale {
+                Some((color, amount)) => {
+                    let mix = |rest: f32, c: f32| rest + (c - rest) * amount;
+                    Color::new(
+                        mix(rest.r, color.r),
+                        mix(rest.g, color.g),
+                        mix(rest.b, color.b),
+                        1.0,
+                    )
+                }
+                None => rest,
+            };
+            match self.upcoming {
+                // Stays short of the full color, so the moment the note starts still stands out
+                Some((color, closeness)) => {
+                    let mix = |rest: f32, c: f32| rest + (c - rest) * closeness * 0.7;
+                    Color::new(
+                        mix(rest.r, color.r),
+                        mix(rest.g, color.g),
+                        mix(rest.b, color.b),
+                        1.0,
+                    )
+                }
+                None => rest,
+            }
         }
     }
 }
@@ -63,7 +174,8 @@ pub fn border_radius(w: f32, is_sharp: bool) -> f32 {
     radius * kind_multiplier
 }
 
-pub fn to_quad(key: &piano_math::Key, color: Color, origin: Point<f32>) -> QuadInstance {
+/// Top face of the key, `lip` is the height of the front face visible below it
+pub fn to_quad(key: &piano_math::Key, color: Color, origin: Point<f32>, lip: f32) -> QuadInstance {
     let position = [origin.x + key.x(), origin.y];
 
     let mut size: Size<f32> = key.size().into();
@@ -73,11 +185,41 @@ pub fn to_quad(key: &piano_math::Key, color: Color, origin: Point<f32>) -> QuadI
     }
 
     let r = border_radius(size.w, false);
+    size.h -= lip;
 
     QuadInstance {
         position,
         size: size.into(),
         color: color.into_linear_rgba(),
+        border_radius: if lip > 0.0 {
+            [0.0; 4]
+        } else {
+            [0.0, 0.0, r, r]
+        },
+    }
+}
+
+/// Front face of a key that is not fully pressed, it disappears as the key goes down
+pub fn to_front_quad(
+    key: &piano_math::Key,
+    color: Color,
+    origin: Point<f32>,
+    lip: f32,
+) -> QuadInstance {
+    let mut size: Size<f32> = key.size().into();
+
+    if let piano_math::KeyKind::Neutral = key.kind() {
+        size.w -= 1.0;
+    }
+
+    let r = border_radius(size.w, false).min(lip);
+    let position = [origin.x + key.x(), origin.y + size.h - lip];
+    let color = Color::new(color.r * 0.6, color.g * 0.6, color.b * 0.6, color.a);
+
+    QuadInstance {
+        position,
+        size: [size.w, lip],
+        color: color.into_linear_rgba(),
         border_radius: [0.0, 0.0, r, r],
     }
 }
@@ -1,22 +1,45 @@
-use crate::{utils::Point, TransformUniform, Uniform};
+use crate::{
+    config::{KeyColors, NoteLabels, ScaleOverlay},
+    utils::Point,
+    TransformUniform, Uniform,
+};
 
 use neothesia_pipelines::quad::{QuadInstance, QuadPipeline};
 use piano_math::range::KeyboardRange;
+use std::time::Duration;
 use wgpu_glyph::{GlyphBrush, Section};
 
 mod key_state;
 pub use key_state::KeyState;
-use wgpu_jumpstart::Gpu;
+use wgpu_jumpstart::{Color, Gpu};
+
+/// Height of the bar along the top of the keyboard that lights up while the sustain pedal is down
+const SUSTAIN_BAR_HEIGHT: f32 = 4.0;
 
 pub struct KeyboardRenderer {
     pos: Point<f32>,
 
     key_states: Vec<KeyState>,
+    /// Height of the key front face that disappears when the key is pressed
+    key_travel: f32,
+    key_colors: KeyColors,
+    scale_overlay: ScaleOverlay,
+    /// Channels the file holds the sustain pedal down on, one bit each
+    file_sustain: u16,
+    user_sustain: bool,
 
     quad_pipeline: QuadPipeline,
     should_reupload: bool,
 
+    /// Camera zoom and offset, labels are rendered in screen space so they have to follow it
+    view: (f32, Point<f32>),
+
     layout: piano_math::KeyboardLayout,
+
+    /// Text shown on top of a key, by note number
+    hint_labels: Vec<(u8, String)>,
+    /// Names written on every key, in place of the octave names of the C keys
+    note_labels: NoteLabels,
 }
 
 impl KeyboardRenderer {
@@ -24,8 +47,9 @@ impl KeyboardRenderer {
         gpu: &Gpu,
         transform_uniform: &Uniform<TransformUniform>,
         layout: piano_math::KeyboardLayout,
+        sample_count: u32,
     ) -> Self {
-        let quad_pipeline = QuadPipeline::new(gpu, transform_uniform);
+        let quad_pipeline = QuadPipeline::with_sample_count(gpu, transform_uniform, sample_count);
         let key_states: Vec<KeyState> = layout
             .range
             .iter()
@@ -36,11 +60,21 @@ impl KeyboardRenderer {
             pos: Default::default(),
 
             key_states,
+            key_travel: 0.0,
+            key_colors: KeyColors::default(),
+            scale_overlay: ScaleOverlay::default(),
+            file_sustain: 0,
+            user_sustain: false,
 
             quad_pipeline,
             should_reupload: false,
 
+            view: (1.0, Default::default()),
+
             layout,
+
+            hint_labels: Vec::new(),
+            note_labels: NoteLabels::default(),
         }
     }
 
@@ -48,6 +82,7 @@ impl KeyboardRenderer {
         for key in self.key_states.iter_mut() {
             key.pressed_by_file_off();
         }
+        self.file_sustain = 0;
         self.queue_reupload();
     }
 
@@ -84,6 +119,77 @@ impl KeyboardRenderer {
         self.queue_reupload();
     }
 
+    pub fn set_key_travel(&mut self, key_travel: f32) {
+        self.key_travel = key_travel.max(0.0);
+        self.queue_reupload();
+    }
+
+    pub fn set_key_colors(&mut self, colors: KeyColors) {
+        if colors != self.key_colors {
+            self.key_colors = colors;
+            self.queue_reupload();
+        }
+    }
+
+    pub fn set_file_sustain(&mut self, channel: u8, down: bool) {
+        let bit = 1 << (channel % 16);
+        let sustain = if down {
+            self.file_sustain | bit
+        } else {
+            self.file_sustain & !bit
+        };
+
+        if sustain != self.file_sustain {
+            self.file_sustain = sustain;
+            self.queue_reupload();
+        }
+    }
+
+    pub fn set_user_sustain(&mut self, down: bool) {
+        if down != self.user_sustain {
+            self.user_sustain = down;
+            self.queue_reupload();
+        }
+    }
+
+    /// Tints the keys of the scale, leaves them alone if it didn't change
+    pub fn set_scale_overlay(&mut self, overlay: &ScaleOverlay) {
+        if *overlay == self.scale_overlay {
+            return;
+        }
+        self.scale_overlay = overlay.clone();
+
+        let range_start = self.layout.range.start();
+        for (id, state) in self.key_states.iter_mut().enumerate() {
+            state.set_scale_tint(overlay.color, overlay.tint(range_start + id as u8));
+        }
+        self.queue_reupload();
+    }
+
+    /// Advances key press animations
+    pub fn animate(&mut self, delta: Duration) {
+        let mut moved = false;
+        for key in self.key_states.iter_mut() {
+            moved |= key.animate(delta);
+        }
+
+        if moved && self.key_travel > 0.0 {
+            self.queue_reupload();
+        }
+    }
+
+    pub fn set_hint_labels(&mut self, labels: Vec<(u8, String)>) {
+        self.hint_labels = labels;
+    }
+
+    pub fn set_note_labels(&mut self, labels: NoteLabels) {
+        self.note_labels = labels;
+    }
+
+    pub fn set_view(&mut self, zoom: f32, offset: [f32; 2]) {
+        self.view = (zoom, offset.into());
+    }
+
     pub fn queue_reupload(&mut self) {
         self.should_reupload = true;
     }
@@ -101,23 +207,40 @@ impl KeyboardRenderer {
                 ..Default::default()
             });
 
-            for key in self
+            // The pedal of the user in the pressed color, the one of the file fainter
+            let sustain = if self.user_sustain {
+                let (r, g, b) = self.key_colors.pressed;
+                Some(Color::from_rgba8(r, g, b, 1.0))
+            } else if self.file_sustain != 0 {
+                Some(Color::from_rgba8(255, 255, 255, 0.35))
+            } else {
+                None
+            };
+            if let Some(color) = sustain {
+                instances.push(QuadInstance {
+                    position: [self.pos.x, self.pos.y - SUSTAIN_BAR_HEIGHT],
+                    size: [self.layout.width, SUSTAIN_BAR_HEIGHT],
+                    color: color.into_linear_rgba(),
+                    ..Default::default()
+                });
+            }
+
+            let neutral = self
                 .layout
                 .keys
                 .iter()
-                .filter(|key| key.kind().is_neutral())
-            {
-                let id = key.id();
-                let color = self.key_states[id].color();
-
-                instances.push(key_state::to_quad(key, color, self.pos));
-            }
-
-            for key in self.layout.keys.iter().filter(|key| key.kind().is_sharp()) {
-                let id = key.id();
-                let color = self.key_states[id].color();
-
-                instances.push(key_state::to_quad(key, color, self.pos));
+                .filter(|key| key.kind().is_neutral());
+            let sharp = self.layout.keys.iter().filter(|key| key.kind().is_sharp());
+
+            for key in neutral.chain(sharp) {
+                let state = &self.key_states[key.id()];
+                let color = state.color(&self.key_colors);
+                let lip = self.key_travel * (1.0 - state.depth());
+
+                instances.push(key_state::to_quad(key, color, self.pos, lip));
+                if lip > 0.0 {
+                    instances.push(key_state::to_front_quad(key, color, self.pos, lip));
+                }
             }
         });
         self.should_reupload = false;
@@ -128,6 +251,47 @@ impl KeyboardRenderer {
             self.reupload(queue);
         }
 
+        if self.note_labels.keyboard {
+            self.queue_note_labels(brush);
+        } else {
+            self.queue_octave_labels(brush);
+        }
+
+        let range_start = self.layout.range.start() as usize;
+        for (note, label) in self.hint_labels.iter() {
+            if !self.layout.range.contains(*note) {
+                continue;
+            }
+            let key = &self.layout.keys[*note as usize - range_start];
+            let (zoom, offset) = self.view;
+
+            let x = (self.pos.x + key.x() - offset.x) * zoom;
+            let y = (self.pos.y - offset.y) * zoom;
+            let w = key.width() * zoom;
+
+            // Below the sharp keys, or on the sharp key itself
+            let (y, color) = if key.kind().is_sharp() {
+                (y + key.height() * zoom * 0.3, [1.0, 1.0, 1.0, 1.0])
+            } else {
+                (y + self.layout.sharp_height * zoom, [0.1, 0.1, 0.1, 1.0])
+            };
+
+            brush.queue(Section {
+                screen_position: (x + w / 2.0, y),
+                text: vec![wgpu_glyph::Text::new(label)
+                    .with_color(color)
+                    .with_scale(w * 0.45)],
+                // Names of the sharp notes are wider than their keys
+                bounds: (w * 2.0, f32::INFINITY),
+                layout: wgpu_glyph::Layout::default()
+                    .h_align(wgpu_glyph::HorizontalAlign::Center)
+                    .v_align(wgpu_glyph::VerticalAlign::Top),
+            })
+        }
+    }
+
+    /// `C1` to `C8` on the C keys
+    fn queue_octave_labels(&self, brush: &mut GlyphBrush<()>) {
         for (id, key) in self
             .layout
             .keys
@@ -135,11 +299,13 @@ impl KeyboardRenderer {
             .filter(|key| key.note_id() == 0)
             .enumerate()
         {
-            let x = self.pos.x + key.x();
-            let y = self.pos.y;
+            let (zoom, offset) = self.view;
+
+            let x = (self.pos.x + key.x() - offset.x) * zoom;
+            let y = (self.pos.y - offset.y) * zoom;
 
-            let w = key.width();
-            let h = key.height();
+            let w = key.width() * zoom;
+            let h = key.height() * zoom;
 
             let size = w * 0.7;
 
@@ -156,6 +322,41 @@ impl KeyboardRenderer {
         }
     }
 
+    /// Name of every key, at the lower end of it
+    fn queue_note_labels(&self, brush: &mut GlyphBrush<()>) {
+        let (zoom, offset) = self.view;
+        // Same size on every key, the names of black keys are the longest
+        let size = self.layout.neutral_width * zoom * 0.4;
+
+        let range_start = self.layout.range.start();
+        for key in self.layout.keys.iter() {
+            let x = (self.pos.x + key.x() - offset.x) * zoom;
+            let y = (self.pos.y - offset.y) * zoom;
+
+            let w = key.width() * zoom;
+            let h = key.height() * zoom;
+
+            let color = if key.kind().is_sharp() {
+                [0.8, 0.8, 0.8, 1.0]
+            } else {
+                [0.3, 0.3, 0.3, 1.0]
+            };
+
+            brush.queue(Section {
+                screen_position: (x + w / 2.0, y + h - size * 1.4),
+                text: vec![wgpu_glyph::Text::new(
+                    &self.note_labels.name(range_start + key.id() as u8),
+                )
+                .with_color(color)
+                .with_scale(size)],
+                bounds: (w * 2.0, f32::INFINITY),
+                layout: wgpu_glyph::Layout::default()
+                    .h_align(wgpu_glyph::HorizontalAlign::Center)
+                    .v_align(wgpu_glyph::VerticalAlign::Top),
+            })
+        }
+    }
+
     pub fn render<'rpass>(
         &'rpass mut self,
         transform_uniform: &'rpass Uniform<TransformUniform>,
@@ -1,7 +1,25 @@
+mod audio_pane;
+mod background;
+mod camera;
+mod drum_lane;
+mod image;
 mod keyboard;
+mod notation;
+mod particles;
+mod practice_heatmap;
 mod text;
 mod waterfall;
+mod watermark;
 
+pub use audio_pane::AudioPaneRenderer;
+pub use background::BackgroundRenderer;
+pub use camera::Camera;
+pub use drum_lane::DrumLaneRenderer;
+pub use image::load_image;
 pub use keyboard::{KeyState as KeyboardKeyState, KeyboardRenderer};
+pub use notation::NotationRenderer;
+pub use particles::ParticleRenderer;
+pub use practice_heatmap::PracticeHeatmapRenderer;
 pub use text::TextRenderer;
-pub use waterfall::WaterfallRenderer;
+pub use waterfall::{note_instances as waterfall_note_instances, WaterfallRenderer};
+pub use watermark::WatermarkRenderer;
@@ -0,0 +1,318 @@
+use midi_file::Midi;
+use neothesia_pipelines::quad::{QuadInstance, QuadPipeline};
+use piano_math::staff::{self, Accidental, Staff, StaffNote};
+use wgpu_jumpstart::{wgpu, Color, Gpu, TransformUniform, Uniform};
+
+use crate::{
+    config::{ColorSchema, HandColors, PitchColors},
+    utils::palette::note_color,
+};
+
+/// Steps from middle C to the top and bottom edges, room for a few ledger lines
+const HALF_HEIGHT_STEPS: f32 = 16.0;
+/// Room left of the cursor, for the key signature and the notes just played
+const MARGIN: f32 = 130.0;
+/// Width taken by one key signature accidental, in steps
+const SIGNATURE_ACCIDENTAL_WIDTH: f32 = 2.0;
+
+struct StaffedNote {
+    /// Seconds
+    start: f32,
+    end: f32,
+    key: u8,
+    track_color_id: usize,
+    note: StaffNote,
+}
+
+/// Grand staff with the notes coming up, they move left towards a fixed playback cursor
+pub struct NotationRenderer {
+    quad_pipeline: QuadPipeline,
+    /// Sorted by start
+    notes: Vec<StaffedNote>,
+    /// Measure starts, in seconds
+    bars: Vec<f32>,
+    /// Repeat barlines in seconds, and whether the repeated part starts there
+    repeats: Vec<(f32, bool)>,
+    sharps: i8,
+}
+
+impl NotationRenderer {
+    /// Returns `None` when the file has no pitched notes
+    pub fn new(
+        gpu: &Gpu,
+        transform_uniform: &Uniform<TransformUniform>,
+        midi: &Midi,
+    ) -> Option<Self> {
+        let sharps = midi
+            .analysis
+            .key
+            .map_or(0, |key| staff::key_sharps(key.tonic, key.minor));
+
+        let notes: Vec<StaffedNote> = midi
+            .merged_track
+            .notes
+            .iter()
+            .filter(|note| note.channel != 9)
+            .map(|note| StaffedNote {
+                start: note.start.as_secs_f32(),
+                end: note.end.as_secs_f32(),
+                key: note.note,
+                track_color_id: note.track_color_id,
+                note: staff::staff_note(note.note, sharps),
+            })
+            .collect();
+
+        if notes.is_empty() {
+            return None;
+        }
+
+        let bars = midi
+            .analysis
+            .measures
+            .iter()
+            .map(|measure| measure.start.as_secs_f32())
+            .collect();
+        let repeats = midi
+            .analysis
+            .repeats
+            .iter()
+            .map(|repeat| (repeat.time.as_secs_f32(), repeat.start))
+            .collect();
+
+        Some(Self {
+            quad_pipeline: QuadPipeline::new(gpu, transform_uniform),
+            notes,
+            bars,
+            repeats,
+            sharps,
+        })
+    }
+
+    /// Lays out the notes around `time` in a panel of `size` with its top left corner at
+    /// `position`, `seconds` of music fit between the cursor and the right edge
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        color_schema: &[ColorSchema],
+        hand_colors: &HandColors,
+        pitch_colors: &PitchColors,
+        time: f32,
+        seconds: f32,
+        position: [f32; 2],
+        size: [f32; 2],
+    ) {
+        let [x, y] = position;
+        let [width, height] = size;
+
+        let step = height / (HALF_HEIGHT_STEPS * 2.0);
+        let step_y = |s: i32| y + height / 2.0 - s as f32 * step;
+        let line = Color::from_rgba8(255, 255, 255, 0.6).into_linear_rgba();
+
+        let cursor_x = x + MARGIN;
+        let pixels_per_second = (width - MARGIN) / seconds.max(0.5);
+        let note_x = |t: f32| cursor_x + (t - time) * pixels_per_second;
+
+        let mut instances = vec![QuadInstance {
+            position: [x, y],
+            size: [width, height],
+            color: Color::from_rgba8(0, 0, 0, 0.85).into_linear_rgba(),
+            ..Default::default()
+        }];
+
+        for staff in [Staff::TREBLE, Staff::BASS] {
+            for l in staff.lines() {
+                instances.push(QuadInstance {
+                    position: [x, step_y(l) - 0.5],
+                    size: [width, 1.0],
+                    color: line,
+                    ..Default::default()
+                });
+            }
+        }
+        // Brace of the two staves
+        instances.push(QuadInstance {
+            position: [x + 4.0, step_y(Staff::TREBLE.top)],
+            size: [2.0, step_y(Staff::BASS.bottom) - step_y(Staff::TREBLE.top)],
+            color: line,
+            ..Default::default()
+        });
+
+        let signature_width = self.sharps.unsigned_abs() as f32 * SIGNATURE_ACCIDENTAL_WIDTH * step;
+        for staff in [Staff::TREBLE, Staff::BASS] {
+            let accidental = if self.sharps > 0 {
+                Accidental::Sharp
+            } else {
+                Accidental::Flat
+            };
+            for (id, s) in staff.signature(self.sharps).into_iter().enumerate() {
+                let cx = x + 16.0 + (id as f32 + 0.5) * SIGNATURE_ACCIDENTAL_WIDTH * step;
+                accidental_instances(&mut instances, accidental, [cx, step_y(s)], step, line);
+            }
+        }
+
+        // Notes that were just played move on past the cursor, up to the key signature
+        let left = x + 16.0 + signature_width + step * 3.0;
+        let from = time - (cursor_x - left) / pixels_per_second;
+        let to = time + seconds;
+
+        for bar in self.bars.iter().filter(|bar| (from..to).contains(*bar)) {
+            instances.push(QuadInstance {
+                position: [note_x(*bar), step_y(Staff::TREBLE.top)],
+                size: [1.0, step_y(Staff::BASS.bottom) - step_y(Staff::TREBLE.top)],
+                color: Color::from_rgba8(255, 255, 255, 0.35).into_linear_rgba(),
+                ..Default::default()
+            });
+        }
+
+        // Thick and thin line, with the dots on the side of the repeated part
+        for (time, start) in self
+            .repeats
+            .iter()
+            .filter(|(time, _)| (from..to).contains(time))
+        {
+            let side = if *start { 1.0 } else { -1.0 };
+            let bar_x = note_x(*time);
+            let top = step_y(Staff::TREBLE.top);
+            let height = step_y(Staff::BASS.bottom) - top;
+
+            instances.push(QuadInstance {
+                position: [bar_x - 1.5, top],
+                size: [3.0, height],
+                color: line,
+                ..Default::default()
+            });
+            instances.push(QuadInstance {
+                position: [bar_x + side * 5.0 - 0.5, top],
+                size: [1.0, height],
+                color: line,
+                ..Default::default()
+            });
+
+            let dot = step * 0.8;
+            for staff in [Staff::TREBLE, Staff::BASS] {
+                let middle = (staff.top + staff.bottom) / 2;
+                for s in [middle - 1, middle + 1] {
+                    instances.push(QuadInstance {
+                        position: [bar_x + side * 9.0 - dot / 2.0, step_y(s) - dot / 2.0],
+                        size: [dot, dot],
+                        color: line,
+                        border_radius: [dot / 2.0; 4],
+                    });
+                }
+            }
+        }
+
+        let first = self.notes.partition_point(|note| note.start < from);
+        let last = self.notes.partition_point(|note| note.start < to);
+
+        let head = [step * 2.6, step * 2.0];
+        for note in self.notes[first..last].iter() {
+            let (r, g, b) = note_color(
+                color_schema,
+                hand_colors,
+                pitch_colors,
+                note.track_color_id,
+                note.key,
+            )
+            .base;
+            // Played notes fade, so the eye stays on the ones coming up
+            let alpha = if note.end <= time { 0.35 } else { 1.0 };
+            let color = Color::from_rgba8(r, g, b, alpha).into_linear_rgba();
+
+            let cx = note_x(note.start);
+            let cy = step_y(note.note.step);
+
+            // Length of the note, so durations can be read without note values
+            instances.push(QuadInstance {
+                position: [cx, cy - step * 0.3],
+                size: [(note_x(note.end) - cx).max(0.0), step * 0.6],
+                color: Color::from_rgba8(r, g, b, alpha * 0.3).into_linear_rgba(),
+                ..Default::default()
+            });
+
+            for l in Staff::of(note.key).ledger_lines(note.note.step) {
+                instances.push(QuadInstance {
+                    position: [cx - head[0] * 0.9, step_y(l) - 0.5],
+                    size: [head[0] * 1.8, 1.0],
+                    color: line,
+                    ..Default::default()
+                });
+            }
+
+            instances.push(QuadInstance {
+                position: [cx - head[0] / 2.0, cy - head[1] / 2.0],
+                size: head,
+                color,
+                border_radius: [step; 4],
+            });
+
+            if let Some(accidental) = note.note.accidental {
+                let center = [cx - head[0] * 1.3, cy];
+                accidental_instances(&mut instances, accidental, center, step, color);
+            }
+        }
+
+        instances.push(QuadInstance {
+            position: [cursor_x - 1.0, y],
+            size: [2.0, height],
+            color: Color::from_rgba8(56, 145, 255, 0.9).into_linear_rgba(),
+            ..Default::default()
+        });
+
+        self.quad_pipeline.update_instance_buffer(queue, instances);
+    }
+
+    pub fn render<'rpass>(
+        &'rpass self,
+        transform_uniform: &'rpass Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+    ) {
+        self.quad_pipeline.render(transform_uniform, render_pass);
+    }
+}
+
+/// Accidental drawn with thin quads around `center`, sized by the staff `step`
+fn accidental_instances(
+    instances: &mut Vec<QuadInstance>,
+    accidental: Accidental,
+    center: [f32; 2],
+    step: f32,
+    color: [f32; 4],
+) {
+    let [cx, cy] = center;
+    let mut quad = |position: [f32; 2], size: [f32; 2]| {
+        instances.push(QuadInstance {
+            position,
+            size,
+            color,
+            ..Default::default()
+        });
+    };
+
+    match accidental {
+        Accidental::Sharp => {
+            for dx in [-0.35, 0.35] {
+                quad([cx + dx * step - 0.6, cy - step * 1.5], [1.2, step * 3.0]);
+            }
+            for dy in [-0.45, 0.45] {
+                quad([cx - step * 0.7, cy + dy * step - 0.75], [step * 1.4, 1.5]);
+            }
+        }
+        Accidental::Flat => {
+            quad([cx - step * 0.4, cy - step * 2.2], [1.2, step * 3.0]);
+            instances.push(QuadInstance {
+                position: [cx - step * 0.4, cy - step * 0.3],
+                size: [step * 0.9, step * 1.1],
+                color,
+                border_radius: [0.0, step * 0.5, step * 0.5, 0.0],
+            });
+        }
+        Accidental::Natural => {
+            quad([cx - step * 0.35 - 0.6, cy - step * 1.5], [1.2, step * 2.1]);
+            quad([cx + step * 0.35 - 0.6, cy - step * 0.6], [1.2, step * 2.1]);
+            for dy in [-0.35, 0.35] {
+                quad([cx - step * 0.35, cy + dy * step - 0.75], [step * 0.7, 1.5]);
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use neothesia_pipelines::particles::{ParticleInstance, ParticlePipeline};
+use wgpu_jumpstart::{wgpu, Color, Gpu, TransformUniform, Uniform};
+
+/// Oldest particles are replaced once there are this many
+const MAX_PARTICLES: usize = 2048;
+/// Seconds
+const LIFETIME: f32 = 0.7;
+/// Sparks of a note played at full velocity
+const MAX_SPARKS: u32 = 16;
+
+/// Sparks and a flash at the keyboard line where notes are struck
+pub struct ParticleRenderer {
+    pipeline: ParticlePipeline,
+    /// Ring of particles, `next` is the slot the next one takes
+    particles: Vec<ParticleInstance>,
+    next: usize,
+    /// Seconds since the renderer was made, particles move on this clock
+    time: f32,
+    seed: u64,
+    spawned: bool,
+}
+
+impl ParticleRenderer {
+    pub fn new(
+        gpu: &Gpu,
+        transform_uniform: &Uniform<TransformUniform>,
+        sample_count: u32,
+    ) -> Self {
+        Self {
+            pipeline: ParticlePipeline::new(gpu, transform_uniform, MAX_PARTICLES, sample_count),
+            particles: Vec::new(),
+            next: 0,
+            time: 0.0,
+            seed: 0x9e37_79b9_7f4a_7c15,
+            spawned: false,
+        }
+    }
+
+    /// Effects of a note struck at `position`, `width` is the width of its key.
+    /// Louder notes get more sparks that fly further
+    pub fn spawn(&mut self, position: [f32; 2], width: f32, color: (u8, u8, u8), velocity: u8) {
+        let intensity = (velocity.min(127) as f32 / 127.0).max(0.1);
+        let (r, g, b) = color;
+        let color = Color::from_rgba8(r, g, b, 1.0).into_linear_rgba();
+
+        // Flash that stays on the key
+        self.push(ParticleInstance {
+            origin: position,
+            velocity: [0.0, 0.0],
+            color: [color[0], color[1], color[2], 0.8 * intensity],
+            size: width * 3.0,
+            spawn: self.time,
+            lifetime: LIFETIME * 0.5,
+        });
+
+        let sparks = 1 + (MAX_SPARKS as f32 * intensity) as u32;
+        for _ in 0..sparks {
+            // Upwards, within 60 degrees of vertical
+            let angle = (self.random() - 0.5) * 2.0 * std::f32::consts::FRAC_PI_3;
+            let speed = (150.0 + self.random() * 350.0) * intensity;
+            let x = position[0] + (self.random() - 0.5) * width;
+
+            self.push(ParticleInstance {
+                origin: [x, position[1]],
+                velocity: [angle.sin() * speed, -angle.cos() * speed],
+                color,
+                size: 3.0 + self.random() * 4.0,
+                spawn: self.time,
+                lifetime: LIFETIME * (0.6 + self.random() * 0.4),
+            });
+        }
+    }
+
+    fn push(&mut self, particle: ParticleInstance) {
+        if self.particles.len() < MAX_PARTICLES {
+            self.particles.push(particle);
+        } else {
+            self.particles[self.next] = particle;
+        }
+        self.next = (self.next + 1) % MAX_PARTICLES;
+        self.spawned = true;
+    }
+
+    /// From `0.0` to `1.0`, xorshift
+    fn random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (self.seed >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, delta: Duration) {
+        self.time += delta.as_secs_f32();
+
+        if self.spawned {
+            self.spawned = false;
+            self.pipeline
+                .update_instance_buffer(queue, self.particles.clone());
+        }
+        self.pipeline.update_time(queue, self.time);
+    }
+
+    pub fn render<'rpass>(
+        &'rpass self,
+        transform_uniform: &'rpass Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+    ) {
+        self.pipeline.render(transform_uniform, render_pass);
+    }
+}
@@ -0,0 +1,76 @@
+use midi_file::Midi;
+use neothesia_pipelines::waterfall::{NoteInstance, WaterfallPipeline};
+use wgpu_jumpstart::{wgpu, Color, Gpu, TransformUniform, Uniform};
+
+/// Plays after which a measure gets the full practiced tint
+const PRACTICED: u32 = 10;
+const NEVER_PLAYED: [f32; 3] = [0.22, 0.05, 0.05];
+const PRACTICED_COLOR: [f32; 3] = [0.04, 0.16, 0.07];
+
+/// Bands behind the waterfall, one per measure, tinted by how many times it was played
+pub struct PracticeHeatmapRenderer {
+    pipeline: WaterfallPipeline,
+}
+
+impl PracticeHeatmapRenderer {
+    pub fn new(
+        gpu: &Gpu,
+        transform_uniform: &Uniform<TransformUniform>,
+        midi: &Midi,
+        sample_count: u32,
+    ) -> Self {
+        Self {
+            pipeline: WaterfallPipeline::new(
+                gpu,
+                transform_uniform,
+                midi.analysis.measures.len(),
+                sample_count,
+            ),
+        }
+    }
+
+    /// `counts` of every measure of `midi`, the bands span `width`
+    pub fn set_counts(&mut self, queue: &wgpu::Queue, midi: &Midi, counts: &[u32], width: f32) {
+        let instances = midi
+            .analysis
+            .measures
+            .iter()
+            .enumerate()
+            .map(|(id, measure)| {
+                let count = counts.get(id).copied().unwrap_or(0);
+                let t = count.min(PRACTICED) as f32 / PRACTICED as f32;
+                let [r, g, b] = std::array::from_fn(|c| {
+                    NEVER_PLAYED[c] + (PRACTICED_COLOR[c] - NEVER_PLAYED[c]) * t
+                });
+                let color = Color::new(r, g, b, 1.0).into_linear_rgb();
+
+                let start = measure.start.as_secs_f32();
+                NoteInstance {
+                    position: [0.0, start],
+                    // Leaves a line between measures
+                    size: [width, (measure.end.as_secs_f32() - start - 0.01).max(0.0)],
+                    color,
+                    radius: 0.0,
+                    border_color: color,
+                    border_width: 0.0,
+                    gradient: 0.0,
+                    glow: 0.0,
+                }
+            })
+            .collect();
+
+        self.pipeline.update_instance_buffer(queue, instances);
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, time: f32) {
+        self.pipeline.update_time(queue, time);
+    }
+
+    pub fn render<'rpass>(
+        &'rpass self,
+        transform_uniform: &'rpass Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+    ) {
+        self.pipeline.render(transform_uniform, render_pass);
+    }
+}
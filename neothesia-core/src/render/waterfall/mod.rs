@@ -1,13 +1,37 @@
-use crate::config::Config;
+use std::collections::HashSet;
+
+use crate::config::{
+    ColorSchema, Config, HandColors, NoteFill, NoteLabels, NoteStyle, PitchColors,
+};
+use crate::utils::palette::note_color;
+use crate::utils::Point;
 use crate::TransformUniform;
 use crate::Uniform;
 use midi_file::Midi;
 use neothesia_pipelines::waterfall::{NoteInstance, WaterfallPipeline};
+use wgpu_glyph::{GlyphBrush, Section};
 use wgpu_jumpstart::Color;
 use wgpu_jumpstart::Gpu;
 
+/// Seconds from the start of the song shown as loop ghosts
+const LOOP_GHOST_WINDOW: f32 = 10.0;
+const LOOP_GHOST_BRIGHTNESS: f32 = 0.35;
+/// Logical pixels a note falls per second at zoom 1, as in the waterfall shader
+const NOTE_SPEED: f32 = 400.0;
+
 pub struct WaterfallRenderer {
     notes_pipeline: WaterfallPipeline,
+    /// Tracks left out of the waterfall, they can still be heard
+    hidden_tracks: HashSet<usize>,
+    loop_ghosts: bool,
+    transpose: i8,
+    /// Note style of the last resize, a theme can change it while playing
+    note_style: NoteStyle,
+
+    /// Layout of the last resize, note labels are placed by it
+    layout: piano_math::KeyboardLayout,
+    /// Camera zoom and offset, labels are rendered in screen space so they have to follow it
+    view: (f32, Point<f32>),
 }
 
 impl WaterfallRenderer {
@@ -17,10 +41,35 @@ impl WaterfallRenderer {
         config: &Config,
         transform_uniform: &Uniform<TransformUniform>,
         layout: piano_math::KeyboardLayout,
+        hidden_tracks: HashSet<usize>,
+        sample_count: u32,
     ) -> Self {
-        let notes_pipeline =
-            WaterfallPipeline::new(gpu, transform_uniform, midi.merged_track.notes.len());
-        let mut notes = Self { notes_pipeline };
+        let midi_notes = &midi.merged_track.notes;
+        let ghosts = match midi_notes.first() {
+            Some(first) => midi_notes
+                .iter()
+                .take_while(|n| {
+                    n.start.as_secs_f32() < first.start.as_secs_f32() + LOOP_GHOST_WINDOW
+                })
+                .count(),
+            None => 0,
+        };
+
+        let notes_pipeline = WaterfallPipeline::new(
+            gpu,
+            transform_uniform,
+            midi_notes.len() + ghosts,
+            sample_count,
+        );
+        let mut notes = Self {
+            notes_pipeline,
+            hidden_tracks,
+            loop_ghosts: false,
+            transpose: 0,
+            note_style: config.themed_note_style(),
+            layout: layout.clone(),
+            view: (1.0, Default::default()),
+        };
         notes.resize(&gpu.queue, midi, config, layout);
         notes
     }
@@ -32,56 +81,127 @@ impl WaterfallRenderer {
         config: &Config,
         layout: piano_math::KeyboardLayout,
     ) {
-        let range_start = layout.range.start() as usize;
-
-        let mut instances = Vec::new();
-
-        let mut longer_than_range = false;
-        for note in midi.merged_track.notes.iter() {
-            if layout.range.contains(note.note) && note.channel != 9 {
-                let key = &layout.keys[note.note as usize - range_start];
-
-                let color_schema = &config.color_schema;
-
-                let color = &color_schema[note.track_color_id % color_schema.len()];
-                let color = if key.kind().is_sharp() {
-                    color.dark
-                } else {
-                    color.base
-                };
-                let color: Color = color.into();
-
-                let h = if note.duration.as_secs_f32() >= 0.1 {
-                    note.duration.as_secs_f32()
-                } else {
-                    0.1
-                };
-
-                instances.push(NoteInstance {
-                    position: [key.x(), note.start.as_secs_f32()],
-                    size: [key.width() - 1.0, h - 0.01], // h - 0.01 to make a litle gap bettwen successive notes
-                    color: color.into_linear_rgb(),
-                    radius: key.width() * 0.2,
-                });
-            } else {
-                longer_than_range = true;
-            }
-        }
+        let mut instances = note_instances(
+            midi,
+            &config.color_schema,
+            &config.hand_colors,
+            &config.pitch_colors,
+            &config.themed_note_style(),
+            &layout,
+            &self.hidden_tracks,
+            self.transpose,
+        );
 
-        if longer_than_range {
-            log::warn!(
-                "Midi wider than giver range: {range_start}-{}",
-                layout.range.end()
-            );
+        self.loop_ghosts = config.loop_playback.enabled && config.loop_playback.ghost_notes;
+        if self.loop_ghosts {
+            let ghosts = loop_ghost_instances(midi, &instances, config.loop_playback.crossfade);
+            instances.extend(ghosts);
         }
 
         self.notes_pipeline.update_instance_buffer(queue, instances);
+        self.layout = layout;
+        self.note_style = config.themed_note_style();
+    }
+
+    /// Loop playback was toggled since the last resize
+    pub fn loop_ghosts_outdated(&self, config: &Config) -> bool {
+        self.loop_ghosts != (config.loop_playback.enabled && config.loop_playback.ghost_notes)
+    }
+
+    /// Note style changed since the last resize
+    pub fn style_outdated(&self, config: &Config) -> bool {
+        self.note_style != config.themed_note_style()
+    }
+
+    /// Moves the notes by `transpose` semitones, takes effect on the next resize
+    pub fn set_transpose(&mut self, transpose: i8) {
+        self.transpose = transpose;
+    }
+
+    pub fn transpose(&self) -> i8 {
+        self.transpose
+    }
+
+    pub fn set_view(&mut self, zoom: f32, offset: [f32; 2]) {
+        self.view = (zoom, offset.into());
     }
 
     pub fn update(&mut self, queue: &wgpu::Queue, time: f32) {
         self.notes_pipeline.update_time(queue, time);
     }
 
+    /// Names of the notes that are still falling, at their lower end.
+    /// `height` and `scale_factor` are the ones of the window
+    pub fn update_labels(
+        &self,
+        brush: &mut GlyphBrush<()>,
+        midi: &Midi,
+        labels: &NoteLabels,
+        time: f32,
+        height: f32,
+        scale_factor: f32,
+    ) {
+        if !labels.waterfall {
+            return;
+        }
+
+        let (zoom, offset) = self.view;
+        let speed = NOTE_SPEED / (scale_factor * zoom);
+        // Notes reach the keyboard at the top of it
+        let keyboard_y = height - height / 5.0;
+        let size = self.layout.neutral_width * zoom * 0.4;
+
+        let notes = &midi.merged_track.notes;
+        let first = notes.partition_point(|n| n.start.as_secs_f32() < time);
+        let last = time + (keyboard_y - offset.y) / speed;
+
+        let range_start = self.layout.range.start() as usize;
+        for note in notes[first..]
+            .iter()
+            .take_while(|n| n.start.as_secs_f32() < last)
+        {
+            if note.channel == 9 || self.hidden_tracks.contains(&note.track_id) {
+                continue;
+            }
+
+            let key = note.note as i16 + self.transpose as i16;
+            let key = match (0..=127).contains(&key).then_some(key as u8) {
+                Some(key) if self.layout.range.contains(key) => key,
+                _ => continue,
+            };
+
+            // Too short to hold the name
+            if note.duration.as_secs_f32() * speed * zoom < size * 1.4 {
+                continue;
+            }
+
+            let key_layout = &self.layout.keys[key as usize - range_start];
+            let bottom = keyboard_y - (note.start.as_secs_f32() - time) * speed;
+
+            let x = (key_layout.x() - offset.x) * zoom;
+            let y = (bottom - offset.y) * zoom;
+            let w = key_layout.width() * zoom;
+
+            // Sharp notes are drawn in the dark color of the track
+            let color = if key_layout.kind().is_sharp() {
+                [1.0, 1.0, 1.0, 0.9]
+            } else {
+                [0.0, 0.0, 0.0, 0.75]
+            };
+
+            brush.queue(Section {
+                screen_position: (x + w / 2.0, y - size * 1.2),
+                text: vec![wgpu_glyph::Text::new(&labels.name(key))
+                    .with_color(color)
+                    .with_scale(size)],
+                bounds: (w * 2.0, f32::INFINITY),
+                layout: wgpu_glyph::Layout::default()
+                    .h_align(wgpu_glyph::HorizontalAlign::Center)
+                    .v_align(wgpu_glyph::VerticalAlign::Top),
+            })
+        }
+    }
+
     pub fn render<'rpass>(
         &'rpass mut self,
         transform_uniform: &'rpass Uniform<TransformUniform>,
@@ -90,3 +210,125 @@ impl WaterfallRenderer {
         self.notes_pipeline.render(transform_uniform, render_pass);
     }
 }
+
+/// Waterfall geometry of every note in the file, in seconds on the y axis,
+/// moved by `transpose` semitones
+pub fn note_instances(
+    midi: &Midi,
+    color_schema: &[ColorSchema],
+    hand_colors: &HandColors,
+    pitch_colors: &PitchColors,
+    style: &NoteStyle,
+    layout: &piano_math::KeyboardLayout,
+    hidden_tracks: &HashSet<usize>,
+    transpose: i8,
+) -> Vec<NoteInstance> {
+    let border_color = Color::from(style.border_color).into_linear_rgb();
+    let gradient = match style.fill {
+        NoteFill::Flat => 0.0,
+        NoteFill::Gradient => 1.0,
+    };
+
+    let range_start = layout.range.start() as usize;
+
+    let mut instances = Vec::new();
+
+    let track = &midi.merged_track;
+    let ends = if style.sustain {
+        midi_file::sustain::sustained_ends(&track.notes, &track.events)
+    } else {
+        track.notes.iter().map(|note| note.end).collect()
+    };
+
+    let mut longer_than_range = false;
+    for (note, end) in track.notes.iter().zip(ends) {
+        if hidden_tracks.contains(&note.track_id) {
+            continue;
+        }
+
+        let key = note.note as i16 + transpose as i16;
+        let key = (0..=127).contains(&key).then_some(key as u8);
+
+        if let Some(key) = key.filter(|key| layout.range.contains(*key) && note.channel != 9) {
+            let color = note_color(
+                color_schema,
+                hand_colors,
+                pitch_colors,
+                note.track_color_id,
+                key,
+            );
+            let key = &layout.keys[key as usize - range_start];
+            let color = if key.kind().is_sharp() {
+                color.dark
+            } else {
+                color.base
+            };
+            let (brightness, width) = style.velocity_scale(note.velocity);
+            let color: Color = color.into();
+            let color = Color::new(
+                (color.r * brightness).min(1.0),
+                (color.g * brightness).min(1.0),
+                (color.b * brightness).min(1.0),
+                color.a,
+            );
+
+            let duration = end.saturating_sub(note.start).as_secs_f32();
+            let h = if duration >= 0.1 { duration } else { 0.1 };
+
+            let w = (key.width() - 1.0) * width;
+            let x = key.x() + (key.width() - 1.0 - w) / 2.0;
+
+            instances.push(NoteInstance {
+                position: [x, note.start.as_secs_f32()],
+                size: [w, h - 0.01], // h - 0.01 to make a litle gap bettwen successive notes
+                color: color.into_linear_rgb(),
+                radius: key.width() * style.corner_radius,
+                border_color,
+                border_width: style.border_width,
+                gradient,
+                glow: style.glow,
+            });
+        } else {
+            longer_than_range = true;
+        }
+    }
+
+    if longer_than_range {
+        log::warn!(
+            "Midi wider than giver range: {range_start}-{}",
+            layout.range.end()
+        );
+    }
+
+    instances
+}
+
+/// Faint copies of the notes at the start of the song, placed after its end
+/// where the next loop picks them up
+fn loop_ghost_instances(
+    midi: &Midi,
+    instances: &[NoteInstance],
+    crossfade: f32,
+) -> Vec<NoteInstance> {
+    let notes = &midi.merged_track.notes;
+    let (first, last) = match (notes.first(), notes.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Vec::new(),
+    };
+
+    // The next loop takes over at the end of the song, `crossfade` seconds into its first note
+    let first_start = first.start.as_secs_f32();
+    let offset =
+        ((last.start + last.duration).as_secs_f32() - first_start - crossfade.max(0.0)).max(0.0);
+
+    instances
+        .iter()
+        .filter(|note| note.position[1] < first_start + LOOP_GHOST_WINDOW)
+        .map(|note| NoteInstance {
+            position: [note.position[0], note.position[1] + offset],
+            color: note.color.map(|c| c * LOOP_GHOST_BRIGHTNESS),
+            border_color: note.border_color.map(|c| c * LOOP_GHOST_BRIGHTNESS),
+            ..*note
+        })
+        .collect()
+}
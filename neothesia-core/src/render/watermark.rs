@@ -0,0 +1,60 @@
+use neothesia_pipelines::image::ImagePipeline;
+use wgpu_jumpstart::{wgpu, Gpu, TransformUniform, Uniform};
+
+use crate::config::{Corner, Watermark};
+
+/// Distance from the window edges, in logical pixels
+const MARGIN: f32 = 20.0;
+
+pub struct WatermarkRenderer {
+    image: ImagePipeline,
+    corner: Corner,
+    opacity: f32,
+    size: f32,
+}
+
+impl WatermarkRenderer {
+    /// Returns `None` when the watermark is disabled or its image fails to load
+    pub fn new(
+        gpu: &Gpu,
+        transform_uniform: &Uniform<TransformUniform>,
+        watermark: &Watermark,
+    ) -> Option<Self> {
+        let path = watermark.path.as_deref()?;
+        let image = super::load_image(gpu, transform_uniform, path)?;
+
+        Some(Self {
+            image,
+            corner: watermark.corner,
+            opacity: watermark.opacity.clamp(0.0, 1.0),
+            size: watermark.size,
+        })
+    }
+
+    /// Places the logo in its corner of a `width` x `height` view
+    pub fn update(&mut self, queue: &wgpu::Queue, width: f32, height: f32) {
+        let (w, h) = self.image.image_size();
+        let logo_h = height * self.size;
+        let logo_w = logo_h * w as f32 / h.max(1) as f32;
+
+        let x = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => MARGIN,
+            Corner::TopRight | Corner::BottomRight => width - logo_w - MARGIN,
+        };
+        let y = match self.corner {
+            Corner::TopLeft | Corner::TopRight => MARGIN,
+            Corner::BottomLeft | Corner::BottomRight => height - logo_h - MARGIN,
+        };
+
+        self.image
+            .set_rect(queue, [x, y], [logo_w, logo_h], self.opacity);
+    }
+
+    pub fn render<'rpass>(
+        &'rpass self,
+        transform_uniform: &'rpass Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+    ) {
+        self.image.render(transform_uniform, render_pass);
+    }
+}
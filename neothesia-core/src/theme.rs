@@ -0,0 +1,224 @@
+//! Theme files restyle the app, they live in the themes folder as `.toml` or `.json` files.
+//! Everything a theme sets is laid over the settings while rendering, what it leaves out stays
+//! as it is. The settings themselves are left alone, so the theme never ends up saved in them.
+//! The file of the selected theme is watched, so changes show up without a restart
+//!
+//! ```toml
+//! [background]
+//! color = [20, 20, 30]
+//! gradient = [0, 0, 0]
+//!
+//! [notes]
+//! corner_radius = 0.4
+//! glow = 6.0
+//!
+//! [keyboard]
+//! pressed = [160, 81, 255]
+//!
+//! [ui]
+//! accent = [255, 126, 51]
+//! ```
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::Deserialize;
+
+use crate::config::{Config, KeyColors, NoteFill, NoteStyle};
+
+/// How often the file of the selected theme is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Extensions of the theme files
+const EXTENSIONS: &[&str] = &["toml", "json"];
+
+type Rgb = (u8, u8, u8);
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct Theme {
+    pub background: BackgroundTheme,
+    pub notes: NotesTheme,
+    pub keyboard: KeyboardTheme,
+    pub ui: UiTheme,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct BackgroundTheme {
+    pub color: Option<Rgb>,
+    /// Color at the bottom, the background fades to it from `color`
+    pub gradient: Option<Rgb>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct NotesTheme {
+    pub corner_radius: Option<f32>,
+    pub border_width: Option<f32>,
+    pub border_color: Option<Rgb>,
+    pub fill: Option<NoteFill>,
+    pub glow: Option<f32>,
+    pub velocity: Option<bool>,
+    pub sustain: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct KeyboardTheme {
+    pub white: Option<Rgb>,
+    pub black: Option<Rgb>,
+    pub pressed: Option<Rgb>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct UiTheme {
+    pub accent: Option<Rgb>,
+}
+
+impl Theme {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return Err(String::from("Could Not Open Theme")),
+        };
+
+        let json = path.extension().map_or(false, |ext| ext == "json");
+        if json {
+            serde_json::from_str(&text).map_err(|err| err.to_string())
+        } else {
+            toml::from_str(&text).map_err(|err| err.to_string())
+        }
+    }
+}
+
+fn set<T: Copy>(value: Option<T>, to: &mut T) {
+    if let Some(value) = value {
+        *to = value;
+    }
+}
+
+/// The look of the settings with the theme laid over them, this is what gets rendered
+impl Config {
+    pub fn themed_background_color(&self) -> Rgb {
+        self.theme_overlay
+            .background
+            .color
+            .unwrap_or(self.background_color)
+    }
+
+    pub fn themed_background_gradient(&self) -> Option<Rgb> {
+        self.theme_overlay
+            .background
+            .gradient
+            .or(self.background_gradient)
+    }
+
+    pub fn themed_note_style(&self) -> NoteStyle {
+        let notes = &self.theme_overlay.notes;
+        let mut style = self.note_style;
+        set(notes.corner_radius, &mut style.corner_radius);
+        set(notes.border_width, &mut style.border_width);
+        set(notes.border_color, &mut style.border_color);
+        set(notes.fill, &mut style.fill);
+        set(notes.glow, &mut style.glow);
+        set(notes.velocity, &mut style.velocity);
+        set(notes.sustain, &mut style.sustain);
+        style
+    }
+
+    pub fn themed_key_colors(&self) -> KeyColors {
+        let keyboard = &self.theme_overlay.keyboard;
+        let mut colors = self.key_colors;
+        set(keyboard.white, &mut colors.white);
+        set(keyboard.black, &mut colors.black);
+        set(keyboard.pressed, &mut colors.pressed);
+        colors
+    }
+
+    pub fn themed_accent_color(&self) -> Rgb {
+        self.theme_overlay.ui.accent.unwrap_or(self.accent_color)
+    }
+}
+
+/// Names of the themes in the themes folder, sorted
+pub fn available() -> Vec<String> {
+    let entries = crate::utils::resources::themes_dir().and_then(|dir| dir.read_dir().ok());
+
+    let mut names: Vec<String> = entries
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |ext| EXTENSIONS.iter().any(|e| ext == *e))
+        })
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn theme_path(name: &str) -> Option<PathBuf> {
+    let dir = crate::utils::resources::themes_dir()?;
+    EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{name}.{ext}")))
+        .find(|path| path.exists())
+}
+
+/// Loads the selected theme into [`Config::theme_overlay`], again every time its file changes
+#[derive(Default)]
+pub struct ThemeWatcher {
+    name: Option<String>,
+    modified: Option<SystemTime>,
+    last_poll: Option<Instant>,
+}
+
+impl ThemeWatcher {
+    /// Loads the theme of `config` when it was just selected or its file changed,
+    /// returns `true` when the overlay changed
+    pub fn poll(&mut self, config: &mut Config) -> bool {
+        let selected = self.name != config.theme;
+        let due = self
+            .last_poll
+            .map_or(true, |last| last.elapsed() >= POLL_INTERVAL);
+        if !selected && !due {
+            return false;
+        }
+
+        self.last_poll = Some(Instant::now());
+        if selected {
+            self.name = config.theme.clone();
+            self.modified = None;
+            config.theme_overlay = Theme::default();
+        }
+
+        let path = match self.name.as_deref().and_then(theme_path) {
+            Some(path) => path,
+            None => return selected,
+        };
+        let modified = std::fs::metadata(&path)
+            .and_then(|meta| meta.modified())
+            .ok();
+        if modified.is_some() && modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+
+        match Theme::load(&path) {
+            Ok(theme) => {
+                config.theme_overlay = theme;
+                true
+            }
+            Err(err) => {
+                log::error!("Invalid theme {}: {err}", path.display());
+                false
+            }
+        }
+    }
+}
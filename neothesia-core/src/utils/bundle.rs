@@ -0,0 +1,152 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{resources, sync};
+
+const VERSION: u32 = 1;
+/// Bundle entry of the settings file, which doesn't always live in the data directory
+const SETTINGS: &str = "settings.ron";
+/// Subdirectories of the data directory that hold app data
+const DATA_DIRS: [&str; 3] = ["sessions", "practice_counts", "themes"];
+/// Only text data is bundled, SoundFonts and other assets are left out
+const EXTENSIONS: [&str; 4] = ["ron", "csv", "json", "toml"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BundleFile {
+    /// Relative to the data directory, `/` separated so bundles move between platforms
+    pub path: String,
+    pub contents: String,
+}
+
+/// Settings and everything in the data directory, as a single file for backups and
+/// moving to another machine
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DataBundle {
+    pub version: u32,
+    pub files: Vec<BundleFile>,
+}
+
+impl DataBundle {
+    /// Bundles `settings` together with the data directory
+    pub fn collect(settings: String) -> Self {
+        let mut files = vec![BundleFile {
+            path: SETTINGS.to_string(),
+            contents: settings,
+        }];
+
+        if let Some(root) = resources::data_dir() {
+            for dir in DATA_DIRS {
+                collect_dir(&root, &root.join(dir), &mut files);
+            }
+        }
+
+        Self {
+            version: VERSION,
+            files,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let data =
+            ron::ser::to_string_pretty(self, Default::default()).map_err(|err| err.to_string())?;
+        fs::write(path, data).map_err(|err| err.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let bundle: Self = ron::from_str(&data).map_err(|err| err.to_string())?;
+
+        if bundle.version > VERSION {
+            return Err(format!("Unsupported bundle version {}", bundle.version));
+        }
+
+        Ok(bundle)
+    }
+
+    /// Bundled settings, to be applied by the caller
+    pub fn settings(&self) -> Option<&str> {
+        self.files
+            .iter()
+            .find(|file| file.path == SETTINGS)
+            .map(|file| file.contents.as_str())
+    }
+
+    /// Writes every file but the settings into the data directory, overwriting existing ones.
+    /// Returns the number of restored files
+    pub fn restore(&self) -> Result<usize, String> {
+        let dir = resources::data_dir().ok_or("No data directory")?;
+
+        let mut restored = 0;
+        for file in self.files.iter() {
+            if file.path == SETTINGS {
+                continue;
+            }
+
+            let relative: PathBuf = file.path.split('/').collect();
+
+            // Bundles come from the outside, nothing can escape the data directory
+            let is_safe = relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)));
+            if !is_safe {
+                log::warn!("Skipping bundle entry {}", file.path);
+                continue;
+            }
+
+            let path = dir.join(relative);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+            }
+            sync::write_atomic(&path, &file.contents).map_err(|err| err.to_string())?;
+            restored += 1;
+        }
+
+        Ok(restored)
+    }
+}
+
+fn collect_dir(root: &Path, dir: &Path, files: &mut Vec<BundleFile>) {
+    // Missing directories just have nothing to bundle
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            collect_dir(root, &path, files);
+            continue;
+        }
+
+        let is_data = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map_or(false, |ext| EXTENSIONS.contains(&ext));
+        let relative = match path.strip_prefix(root) {
+            Ok(relative) => relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            Err(_) => continue,
+        };
+
+        if !is_data {
+            continue;
+        }
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => files.push(BundleFile {
+                path: relative,
+                contents,
+            }),
+            Err(err) => log::warn!("Skipping {}: {err}", path.display()),
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! Metronome click, shared by the live synth and audio exports, and the key press click
+
+/// Length of a single click, in seconds
+pub const CLICK_LEN: f32 = 0.04;
+
+/// Short decaying sine at `t` seconds into the click, accented clicks are higher and louder
+pub fn click_sample(t: f32, accent: bool) -> f32 {
+    let (freq, gain) = if accent { (1600.0, 1.0) } else { (1000.0, 0.6) };
+
+    let envelope = (-t * 150.0).exp();
+    (std::f32::consts::TAU * freq * t).sin() * envelope * gain
+}
+
+/// Length of a key press click, in seconds
+pub const KEY_CLICK_LEN: f32 = 0.025;
+
+/// Short burst of noise over a low thump at `t` seconds into the click, like a key hitting its bed
+pub fn key_click_sample(t: f32) -> f32 {
+    // Same noise every time, hashed from the time in 1/96000ths of a second
+    let n = ((t * 96000.0) as u32).wrapping_mul(2_654_435_761) >> 16;
+    let noise = n as f32 / 32768.0 - 1.0;
+
+    let thump = (std::f32::consts::TAU * 180.0 * t).sin() * (-t * 120.0).exp();
+    noise * (-t * 400.0).exp() * 0.5 + thump * 0.5
+}
@@ -1,4 +1,9 @@
+pub mod bundle;
+pub mod click;
+pub mod palette;
 pub mod resources;
+pub mod spectrum;
+pub mod sync;
 
 #[derive(Debug, Default, Clone, Copy)]
 pub struct Point<T> {
@@ -12,6 +17,12 @@ impl<T> From<(T, T)> for Point<T> {
     }
 }
 
+impl<T> From<[T; 2]> for Point<T> {
+    fn from([x, y]: [T; 2]) -> Self {
+        Self { x, y }
+    }
+}
+
 impl<T> From<Point<T>> for [T; 2] {
     fn from(p: Point<T>) -> Self {
         [p.x, p.y]
@@ -0,0 +1,79 @@
+use crate::config::{ColorSchema, HandColors, PitchColors};
+
+/// Colors offered by the color pickers
+pub const PRESET_COUNT: usize = 12;
+
+/// Colors of given track, tracks past the end of the schema get generated colors
+/// instead of repeating the schema. Generation depends only on the id, so a track keeps
+/// its color across sessions
+pub fn track_color(color_schema: &[ColorSchema], track_color_id: usize) -> ColorSchema {
+    match color_schema.get(track_color_id) {
+        Some(color) => color.clone(),
+        None => generated_color(track_color_id),
+    }
+}
+
+/// Colors of a note of given track on `key`, the colors of its pitch class or of its hand
+/// when notes are colored by those
+pub fn note_color(
+    color_schema: &[ColorSchema],
+    hand_colors: &HandColors,
+    pitch_colors: &PitchColors,
+    track_color_id: usize,
+    key: u8,
+) -> ColorSchema {
+    if pitch_colors.enabled {
+        pitch_colors.of(key)
+    } else if hand_colors.enabled {
+        hand_colors.of(key).clone()
+    } else {
+        track_color(color_schema, track_color_id)
+    }
+}
+
+/// Colors offered by the color pickers, spaced like the generated track colors
+pub fn presets() -> impl Iterator<Item = ColorSchema> {
+    (0..PRESET_COUNT).map(generated_color)
+}
+
+/// Perceptually spaced color, hues are spread by the golden angle in OKLCH
+pub fn generated_color(id: usize) -> ColorSchema {
+    const GOLDEN_ANGLE: f32 = 137.507_77;
+    let hue = (30.0 + id as f32 * GOLDEN_ANGLE) % 360.0;
+
+    ColorSchema {
+        base: oklch_to_srgb8(0.74, 0.15, hue),
+        dark: oklch_to_srgb8(0.52, 0.13, hue),
+    }
+}
+
+fn oklch_to_srgb8(l: f32, c: f32, hue: f32) -> (u8, u8, u8) {
+    let (a, b) = {
+        let h = hue.to_radians();
+        (c * h.cos(), c * h.sin())
+    };
+
+    // OKLab -> LMS
+    let l_ = l + 0.396_337_78 * a + 0.215_803_76 * b;
+    let m_ = l - 0.105_561_346 * a - 0.063_854_17 * b;
+    let s_ = l - 0.089_484_18 * a - 1.291_485_5 * b;
+
+    let (l_, m_, s_) = (l_.powi(3), m_.powi(3), s_.powi(3));
+
+    // LMS -> linear sRGB
+    let r = 4.076_741_7 * l_ - 3.307_711_6 * m_ + 0.230_969_94 * s_;
+    let g = -1.268_438 * l_ + 2.609_757_4 * m_ - 0.341_319_38 * s_;
+    let b = -0.004_196_086_3 * l_ - 0.703_418_6 * m_ + 1.707_614_7 * s_;
+
+    let encode = |v: f32| {
+        let v = v.clamp(0.0, 1.0);
+        let v = if v <= 0.003_130_8 {
+            v * 12.92
+        } else {
+            1.055 * v.powf(1.0 / 2.4) - 0.055
+        };
+        (v * 255.0).round() as u8
+    };
+
+    (encode(r), encode(g), encode(b))
+}
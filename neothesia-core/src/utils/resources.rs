@@ -14,7 +14,34 @@ fn xdg_config() -> Option<PathBuf> {
         .or_else(|| home().map(|h| h.join(".config").join("neothesia")))
 }
 
+/// Name of the marker file that enables portable mode when placed next to the executable
+const PORTABLE_MARKER: &str = "portable";
+
+/// Directory of the executable, when running in portable mode.
+///
+/// Portable mode keeps settings and data next to the binary instead of the OS config dirs,
+/// so the app can run from a USB stick. It is enabled with a `portable` marker file
+/// next to the executable, or with the `--portable` flag
+pub fn portable_dir() -> Option<PathBuf> {
+    let dir = env::current_exe().ok()?.parent()?.to_owned();
+
+    let enabled =
+        dir.join(PORTABLE_MARKER).exists() || env::args().skip(1).any(|arg| arg == "--portable");
+
+    if enabled {
+        Some(dir)
+    } else {
+        None
+    }
+}
+
 pub fn default_sf2() -> Option<PathBuf> {
+    if let Some(path) = portable_dir().map(|p| p.join("default.sf2")) {
+        if path.exists() {
+            return Some(path);
+        }
+    }
+
     #[cfg(all(target_family = "unix", not(target_os = "macos")))]
     {
         if let Some(path) = xdg_config().map(|p| p.join("default.sf2")) {
@@ -51,6 +78,10 @@ pub fn default_sf2() -> Option<PathBuf> {
 }
 
 pub fn settings_ron() -> Option<PathBuf> {
+    if let Some(dir) = portable_dir() {
+        return Some(dir.join("settings.ron"));
+    }
+
     #[cfg(all(target_family = "unix", not(target_os = "macos")))]
     return xdg_config().map(|p| p.join("settings.ron"));
 
@@ -61,6 +92,56 @@ pub fn settings_ron() -> Option<PathBuf> {
     return bundled_resource_path("settings", "ron").map(PathBuf::from);
 }
 
+/// Directory for data the app writes on its own, next to the settings file
+pub fn data_dir() -> Option<PathBuf> {
+    if let Some(dir) = portable_dir() {
+        return Some(dir);
+    }
+
+    #[cfg(all(target_family = "unix", not(target_os = "macos")))]
+    return xdg_config();
+
+    #[cfg(target_os = "windows")]
+    return Some(PathBuf::from("."));
+
+    #[cfg(target_os = "macos")]
+    return home().map(|h| {
+        h.join("Library")
+            .join("Application Support")
+            .join("neothesia")
+    });
+}
+
+/// Exported practice sessions
+pub fn sessions_dir() -> Option<PathBuf> {
+    data_dir().map(|p| p.join("sessions"))
+}
+
+/// Recordings of the MIDI input
+pub fn recordings_dir() -> Option<PathBuf> {
+    data_dir().map(|p| p.join("recordings"))
+}
+
+/// Videos and audio rendered by the export queue
+pub fn exports_dir() -> Option<PathBuf> {
+    data_dir().map(|p| p.join("exports"))
+}
+
+/// Theme files, see [`crate::theme`]
+pub fn themes_dir() -> Option<PathBuf> {
+    data_dir().map(|p| p.join("themes"))
+}
+
+/// Export jobs that are not finished yet, so they survive a restart
+pub fn export_queue_ron() -> Option<PathBuf> {
+    data_dir().map(|p| p.join("export_queue.ron"))
+}
+
+/// How many times each measure of a song was played, a file per song
+pub fn practice_counts_dir() -> Option<PathBuf> {
+    data_dir().map(|p| p.join("practice_counts"))
+}
+
 #[cfg(target_os = "macos")]
 fn bundled_resource_path(name: &str, extension: &str) -> Option<String> {
     use objc::runtime::{Class, Object};
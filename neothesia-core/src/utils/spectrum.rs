@@ -0,0 +1,131 @@
+use std::{
+    collections::VecDeque,
+    f32::consts::PI,
+    sync::{Arc, Mutex},
+};
+
+/// Number of most recent frames kept for analysis
+const WINDOW: usize = 2048;
+
+/// Lowest and highest analyzed frequency, in Hz
+const MIN_FREQ: f32 = 40.0;
+const MAX_FREQ: f32 = 16_000.0;
+
+/// Levels below this are drawn as silence
+const FLOOR_DB: f32 = -60.0;
+
+struct TapBuffer {
+    frames: VecDeque<(f32, f32)>,
+    sample_rate: f32,
+}
+
+/// Latest stereo frames of the synth output,
+/// written by the audio thread and read by the renderer
+#[derive(Clone)]
+pub struct AudioTap {
+    inner: Arc<Mutex<TapBuffer>>,
+}
+
+impl AudioTap {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(TapBuffer {
+                frames: VecDeque::with_capacity(WINDOW),
+                sample_rate,
+            })),
+        }
+    }
+
+    /// Called from the audio thread, frames are dropped rather than waiting for the lock
+    pub fn push(&self, frames: &[(f32, f32)]) {
+        let mut buffer = match self.inner.try_lock() {
+            Ok(buffer) => buffer,
+            Err(_) => return,
+        };
+
+        let frames = &frames[frames.len().saturating_sub(WINDOW)..];
+        let overflow = (buffer.frames.len() + frames.len()).saturating_sub(WINDOW);
+        buffer.frames.drain(..overflow);
+        buffer.frames.extend(frames);
+    }
+
+    /// Copy of the buffered frames and their sample rate
+    pub fn snapshot(&self) -> (Vec<(f32, f32)>, f32) {
+        let buffer = self.inner.lock().unwrap();
+        (buffer.frames.iter().copied().collect(), buffer.sample_rate)
+    }
+}
+
+/// RMS of the left and right channel, mapped to `0.0..=1.0`
+pub fn levels(frames: &[(f32, f32)]) -> (f32, f32) {
+    if frames.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let (l, r) = frames
+        .iter()
+        .fold((0.0, 0.0), |(l, r), (fl, fr)| (l + fl * fl, r + fr * fr));
+    let len = frames.len() as f32;
+
+    (
+        normalize_db((l / len).sqrt()),
+        normalize_db((r / len).sqrt()),
+    )
+}
+
+/// Magnitudes of `bands` log spaced frequency bands of the mono mix, mapped to `0.0..=1.0`
+pub fn spectrum(frames: &[(f32, f32)], sample_rate: f32, bands: usize) -> Vec<f32> {
+    if frames.is_empty() || bands == 0 {
+        return vec![0.0; bands];
+    }
+
+    // Hann window, to keep loud bands from leaking into their neighbours
+    let len = frames.len();
+    let samples: Vec<f32> = frames
+        .iter()
+        .enumerate()
+        .map(|(id, (l, r))| {
+            let w = 0.5 - 0.5 * (2.0 * PI * id as f32 / len as f32).cos();
+            (l + r) * 0.5 * w
+        })
+        .collect();
+    // Sum of the window
+    let gain = len as f32 * 0.5;
+
+    let max_freq = MAX_FREQ.min(sample_rate * 0.5);
+    let ratio = (max_freq / MIN_FREQ).powf(1.0 / bands as f32);
+
+    (0..bands)
+        .map(|band| {
+            let low = MIN_FREQ * ratio.powi(band as i32);
+            let high = low * ratio;
+
+            // A single bin is too narrow for the upper bands, so probe a few across the band
+            let peak = (0..4)
+                .map(|probe| low + (high - low) * (probe as f32 + 0.5) / 4.0)
+                .map(|freq| goertzel(&samples, freq, sample_rate))
+                .fold(0.0, f32::max);
+
+            normalize_db(2.0 * peak / gain)
+        })
+        .collect()
+}
+
+/// Magnitude of a single frequency
+fn goertzel(samples: &[f32], freq: f32, sample_rate: f32) -> f32 {
+    let coeff = 2.0 * (2.0 * PI * freq / sample_rate).cos();
+
+    let (mut s1, mut s2) = (0.0, 0.0);
+    for sample in samples {
+        let s = sample + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s;
+    }
+
+    (s1 * s1 + s2 * s2 - coeff * s1 * s2).max(0.0).sqrt()
+}
+
+fn normalize_db(amplitude: f32) -> f32 {
+    let db = 20.0 * amplitude.max(f32::EPSILON).log10();
+    ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+}
@@ -0,0 +1,79 @@
+//! Helpers that keep the data directory safe to share between machines
+//! with file sync clients like Dropbox or Syncthing
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Name of this machine, used to keep files written on different machines apart
+pub fn device_name() -> String {
+    let name = std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| {
+            fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|name| name.trim().to_string())
+        })
+        .unwrap_or_default();
+
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+
+    if name.is_empty() {
+        "local".into()
+    } else {
+        name
+    }
+}
+
+/// Writes through a temporary file, so a sync client never uploads a half written file
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
+
+/// Copies of `path` left by sync clients when it was changed on two machines at once,
+/// eg. `settings (conflicted copy).ron` or `settings.sync-conflict-20230101-120000-ABC.ron`
+pub fn conflict_copies(path: &Path) -> Vec<PathBuf> {
+    let (dir, stem) = match (path.parent(), path.file_stem()) {
+        (Some(dir), Some(stem)) => (dir, stem.to_string_lossy()),
+        _ => return Vec::new(),
+    };
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|candidate| {
+            let name = match candidate.file_name() {
+                Some(name) => name.to_string_lossy().to_lowercase(),
+                None => return false,
+            };
+
+            candidate != path
+                && candidate.extension() == path.extension()
+                && name.starts_with(&stem.to_lowercase())
+                && name.contains("conflict")
+        })
+        .collect()
+}
+
+/// Last modification time, `UNIX_EPOCH` when unknown
+pub fn modified(path: &Path) -> SystemTime {
+    fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
@@ -0,0 +1,46 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use midi_file::midly::MidiMessage;
+use neothesia_engine::{KeyPressSource, ManualClock, PlayAlong};
+
+fn play_along(c: &mut Criterion) {
+    let midi = midi_file::stress::black_midi(64, 2_000);
+    let range = piano_math::KeyboardRange::standard_88_keys();
+
+    let mut group = c.benchmark_group("play_along");
+    group.sample_size(20);
+
+    // The user hits every key right before the file asks for it
+    group.bench_function("perfect_user", |b| {
+        b.iter_batched(
+            || {
+                let clock = ManualClock::new();
+                let play_along = PlayAlong::with_clock(range.clone(), Box::new(clock.clone()));
+                (clock, play_along)
+            },
+            |(clock, mut play_along)| {
+                for event in midi.merged_track.events.iter() {
+                    clock.set_elapsed(event.timestamp);
+                    play_along.update();
+
+                    match event.message {
+                        MidiMessage::NoteOn { key, .. } => {
+                            play_along.press_key(KeyPressSource::User, key.as_int(), true);
+                            play_along.press_key(KeyPressSource::File, key.as_int(), true);
+                        }
+                        MidiMessage::NoteOff { key, .. } => {
+                            play_along.press_key(KeyPressSource::File, key.as_int(), false);
+                        }
+                        _ => {}
+                    }
+                }
+                criterion::black_box(play_along.are_required_keys_pressed())
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, play_along);
+criterion_main!(benches);
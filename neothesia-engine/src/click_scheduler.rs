@@ -0,0 +1,237 @@
+use std::time::Duration;
+
+/// Tempo change of a [`ClickPattern`], a step every few measures until the target is reached
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoRamp {
+    pub enabled: bool,
+    /// BPM added every `every` measures, towards `target`
+    pub step: f32,
+    /// Measures
+    pub every: u32,
+    /// BPM where the ramp stops, below the start tempo for a slow down
+    pub target: f32,
+}
+
+impl Default for TempoRamp {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            step: 5.0,
+            every: 4,
+            target: 120.0,
+        }
+    }
+}
+
+/// Beats clicked by a [`ClickScheduler`], without a song
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClickPattern {
+    /// Starting tempo, in beats per minute
+    pub bpm: f32,
+    /// Beats in a measure
+    pub beats: u32,
+    /// Clicks per beat, `1` clicks only the beats
+    pub subdivision: u32,
+    /// Beats of the measure clicked with the accent, `0` is the downbeat
+    pub accents: Vec<u32>,
+    pub ramp: TempoRamp,
+}
+
+impl Default for ClickPattern {
+    fn default() -> Self {
+        Self {
+            bpm: 90.0,
+            beats: 4,
+            subdivision: 1,
+            accents: vec![0],
+            ramp: TempoRamp::default(),
+        }
+    }
+}
+
+impl ClickPattern {
+    pub const MIN_BPM: f32 = 20.0;
+    pub const MAX_BPM: f32 = 300.0;
+    pub const MAX_BEATS: u32 = 12;
+    pub const MAX_SUBDIVISION: u32 = 4;
+
+    pub fn is_accent(&self, beat: u32) -> bool {
+        self.accents.contains(&beat)
+    }
+
+    pub fn toggle_accent(&mut self, beat: u32) {
+        match self.accents.iter().position(|accent| *accent == beat) {
+            Some(id) => {
+                self.accents.remove(id);
+            }
+            None => self.accents.push(beat),
+        }
+    }
+
+    /// Tempo of `measure`, counted from `0`, with the ramp applied
+    pub fn bpm_at(&self, measure: u32) -> f32 {
+        let ramp = &self.ramp;
+        if !ramp.enabled || ramp.every == 0 {
+            return self.bpm;
+        }
+
+        let steps = (measure / ramp.every) as f32;
+        let bpm = if ramp.target >= self.bpm {
+            (self.bpm + ramp.step.abs() * steps).min(ramp.target)
+        } else {
+            (self.bpm - ramp.step.abs() * steps).max(ramp.target)
+        };
+        bpm.clamp(Self::MIN_BPM, Self::MAX_BPM)
+    }
+}
+
+/// Click of a [`ClickScheduler`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Click {
+    pub measure: u32,
+    pub beat: u32,
+    /// Click within the beat, `0` is on the beat
+    pub subdivision: u32,
+    pub accent: bool,
+    /// When it should sound, from now
+    pub delay: Duration,
+}
+
+/// Clock of a [`ClickPattern`], schedules its clicks a little ahead so they can be
+/// played on time between frames
+#[derive(Debug, Default, Clone)]
+pub struct ClickScheduler {
+    /// Since the first click
+    time: Duration,
+    /// Time of the next click
+    next: Duration,
+    measure: u32,
+    beat: u32,
+    subdivision: u32,
+}
+
+impl ClickScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock on by `delta`, returns the clicks due within `ahead` of it
+    pub fn update(
+        &mut self,
+        pattern: &ClickPattern,
+        delta: Duration,
+        ahead: Duration,
+    ) -> Vec<Click> {
+        self.time += delta;
+
+        let beats = pattern.beats.max(1);
+        let subdivision = pattern.subdivision.max(1);
+
+        let mut clicks = Vec::new();
+        while self.next < self.time + ahead {
+            // Changes of the pattern land on the next click
+            if self.beat >= beats {
+                self.beat = 0;
+                self.measure += 1;
+            }
+            self.subdivision = self.subdivision.min(subdivision - 1);
+
+            clicks.push(Click {
+                measure: self.measure,
+                beat: self.beat,
+                subdivision: self.subdivision,
+                accent: self.subdivision == 0 && pattern.is_accent(self.beat),
+                delay: self.next.saturating_sub(self.time),
+            });
+
+            let beat_len = 60.0 / pattern.bpm_at(self.measure) as f64;
+            self.next += Duration::from_secs_f64(beat_len / subdivision as f64);
+
+            self.subdivision += 1;
+            if self.subdivision == subdivision {
+                self.subdivision = 0;
+                self.beat += 1;
+            }
+        }
+
+        clicks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clicks_are_scheduled_ahead() {
+        let ms = Duration::from_millis;
+        let pattern = ClickPattern {
+            bpm: 120.0,
+            beats: 3,
+            subdivision: 2,
+            ..Default::default()
+        };
+        let mut scheduler = ClickScheduler::new();
+
+        let clicks = scheduler.update(&pattern, Duration::ZERO, ms(600));
+        let delays: Vec<_> = clicks.iter().map(|click| click.delay).collect();
+        assert_eq!(delays, [ms(0), ms(250), ms(500)]);
+        assert!(clicks[0].accent);
+        assert_eq!((clicks[1].beat, clicks[1].subdivision), (0, 1));
+        assert!(!clicks[2].accent);
+
+        // Nothing is clicked twice
+        let clicks = scheduler.update(&pattern, ms(200), ms(600));
+        assert_eq!(clicks.len(), 1);
+        assert_eq!(clicks[0].delay, ms(550));
+
+        // Second measure starts after three beats, accented again
+        let clicks = scheduler.update(&pattern, ms(1300), ms(100));
+        assert_eq!(clicks.len(), 3);
+        let downbeat = clicks.iter().find(|click| click.measure == 1).unwrap();
+        assert_eq!(downbeat.beat, 0);
+        assert!(downbeat.accent);
+    }
+
+    #[test]
+    fn ramp_steps_towards_target() {
+        let mut pattern = ClickPattern {
+            bpm: 100.0,
+            ramp: TempoRamp {
+                enabled: true,
+                step: 5.0,
+                every: 2,
+                target: 112.0,
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(pattern.bpm_at(0), 100.0);
+        assert_eq!(pattern.bpm_at(1), 100.0);
+        assert_eq!(pattern.bpm_at(2), 105.0);
+        assert_eq!(pattern.bpm_at(4), 110.0);
+        assert_eq!(pattern.bpm_at(40), 112.0);
+
+        // Slows down when the target is below the start
+        pattern.ramp.target = 90.0;
+        assert_eq!(pattern.bpm_at(2), 95.0);
+        assert_eq!(pattern.bpm_at(40), 90.0);
+
+        pattern.ramp.enabled = false;
+        assert_eq!(pattern.bpm_at(40), 100.0);
+    }
+
+    #[test]
+    fn accents() {
+        let mut pattern = ClickPattern::default();
+        assert!(pattern.is_accent(0) && !pattern.is_accent(2));
+
+        pattern.toggle_accent(2);
+        pattern.toggle_accent(0);
+        assert!(!pattern.is_accent(0) && pattern.is_accent(2));
+    }
+}
@@ -0,0 +1,61 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Time source of the play along logic
+pub trait Clock: Debug + Send {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only moves when told to, for deterministic simulations.
+///
+/// Clones share the same time, so one handle can be given away while another one drives it
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    start: Instant,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: Default::default(),
+        }
+    }
+
+    pub fn advance(&self, delta: Duration) {
+        *self.elapsed.lock().unwrap() += delta;
+    }
+
+    pub fn set_elapsed(&self, elapsed: Duration) {
+        *self.elapsed.lock().unwrap() = elapsed;
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use crate::TapTempo;
+
+/// Tapped beats the song carries on for once the taps stop
+const WAIT_BEATS: f32 = 2.0;
+
+/// Speed of the song following a beat tapped live, every tap lands on a beat of the song.
+/// The song is sped up or slowed down to reach its next beat along with the next tap
+#[derive(Debug, Clone)]
+pub struct Conductor {
+    tap: TapTempo,
+    /// Start of every beat of the song, sorted
+    beats: Vec<Duration>,
+}
+
+impl Conductor {
+    pub fn new(beats: Vec<Duration>) -> Self {
+        Self {
+            tap: TapTempo::new(),
+            beats,
+        }
+    }
+
+    /// Adds a tap at `time` on any steady clock, while the song is at `song_time`.
+    /// Returns the speed to play at, `None` until the tapped tempo is known
+    /// and past the last beat
+    pub fn tap(&mut self, time: Duration, song_time: Duration) -> Option<f32> {
+        let bpm = self.tap.tap(time)?;
+        let period = 60.0 / bpm;
+
+        // The tap means the nearest beat, whether the song is behind or ahead of it
+        let next = self.beats.partition_point(|beat| *beat <= song_time);
+        let nearest = match (next.checked_sub(1), self.beats.get(next)) {
+            (Some(prev), Some(beat)) if *beat - song_time < song_time - self.beats[prev] => next,
+            (Some(prev), _) => prev,
+            (None, _) => next,
+        };
+
+        let target = *self.beats.get(nearest + 1)?;
+        Some((target - song_time).as_secs_f32() / period)
+    }
+
+    /// Whether the song should hold, until the tempo is known and once the taps stopped
+    pub fn is_waiting(&self, time: Duration) -> bool {
+        match (self.tap.bpm(), self.tap.last_tap()) {
+            (Some(bpm), Some(last)) => {
+                time.saturating_sub(last).as_secs_f32() > WAIT_BEATS * 60.0 / bpm
+            }
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_taps() {
+        let ms = Duration::from_millis;
+        // 120 BPM
+        let mut conductor = Conductor::new((0..16).map(|beat| ms(beat * 500)).collect());
+
+        assert!(conductor.is_waiting(ms(0)));
+        assert_eq!(conductor.tap(ms(0), ms(0)), None);
+
+        // Tapped at 60 BPM, on time
+        assert_eq!(conductor.tap(ms(1000), ms(500)), Some(0.5));
+        assert!(!conductor.is_waiting(ms(1500)));
+
+        // Song is behind the tap, it catches up by the next one
+        assert_eq!(conductor.tap(ms(2000), ms(900)), Some(0.6));
+        // Song is ahead of the tap, it waits for the next one
+        assert_eq!(conductor.tap(ms(3000), ms(1600)), Some(0.4));
+
+        // Taps stopped
+        assert!(conductor.is_waiting(ms(5500)));
+
+        // Past the last beat there is nothing to follow
+        assert_eq!(conductor.tap(ms(4000), ms(7600)), None);
+    }
+}
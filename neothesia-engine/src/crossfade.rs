@@ -0,0 +1,352 @@
+use std::{collections::HashSet, time::Duration};
+
+use midi_file::midly::{num::u7, MidiMessage};
+
+/// Drum channel, its notes can't be moved to another channel
+const DRUMS: u8 = 9;
+/// Reset all controllers, clears what the previous loop left on a spare channel
+const RESET_CONTROLLERS: u8 = 121;
+
+/// Overlap of the end of the song with the start of the next loop.
+///
+/// While they overlap the next loop plays on spare channels, the ones the song doesn't use,
+/// so the channel volume of each of the two can be faded on its own and their note-offs
+/// don't end each other's notes. Once the next loop takes over its new notes go back to the
+/// channels of the song, the ones still held on a spare channel end there.
+///
+/// Drums, and channels left without a spare when the song uses most of them, are shared by
+/// both, their note-on velocities are faded instead
+#[derive(Debug, Clone)]
+pub(crate) struct LoopCrossfade {
+    /// Spare channel of each channel of the song
+    spare: [Option<u8>; 16],
+    /// Notes of the next loop held on a spare channel, by channel of the song and key
+    moved: HashSet<(u8, u8)>,
+    /// Everything but notes the next loop sent to the spare channels, repeated on the
+    /// channels of the song when it takes over, so they pick up its programs and pedals
+    controls: Vec<midi_file::MidiEvent>,
+    /// Events of the next loop, published once it takes over
+    pending: Vec<midi_file::MidiEvent>,
+}
+
+impl LoopCrossfade {
+    pub(crate) fn new(midi: &midi_file::Midi) -> Self {
+        let mut used = [false; 16];
+        for event in midi.merged_track.events.iter() {
+            used[event.channel as usize % 16] = true;
+        }
+        Self::with_channels(used)
+    }
+
+    /// Crossfade of a song that plays on the `used` channels
+    fn with_channels(used: [bool; 16]) -> Self {
+        let mut free = (0..16u8).filter(|ch| *ch != DRUMS && !used[*ch as usize]);
+        let mut spare = [None; 16];
+        for channel in (0..16u8).filter(|ch| *ch != DRUMS && used[*ch as usize]) {
+            spare[channel as usize] = free.next();
+        }
+
+        Self {
+            spare,
+            moved: HashSet::new(),
+            controls: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    fn spare(&self, channel: u8) -> Option<u8> {
+        self.spare[channel as usize % 16]
+    }
+
+    /// Channel volume factor of each output channel, from the gains of the mixer.
+    /// `progress` is how far into the crossfade playback is, `None` when there is none
+    pub(crate) fn channel_gains(&self, mixer: [f32; 16], progress: Option<f32>) -> [f32; 16] {
+        let mut gains = mixer;
+        for (channel, spare) in self.spare.iter().enumerate() {
+            if let Some(spare) = *spare {
+                let fade = progress.unwrap_or(1.0);
+                gains[spare as usize] = mixer[channel] * fade;
+                gains[channel] = mixer[channel] * (1.0 - progress.unwrap_or(0.0));
+            }
+        }
+        gains
+    }
+
+    /// Starts over with the next loop, returns the events that clear the spare channels,
+    /// to be sent once they are faded out
+    pub(crate) fn start(&mut self) -> Vec<midi_file::MidiEvent> {
+        let mut events = self.cancel();
+        for spare in self.spare.iter().flatten() {
+            events.push(event(
+                *spare,
+                MidiMessage::Controller {
+                    controller: u7::new(RESET_CONTROLLERS),
+                    value: u7::new(0),
+                },
+            ));
+        }
+        events
+    }
+
+    /// Drops the next loop, returns note-offs for its notes that are still held
+    pub(crate) fn cancel(&mut self) -> Vec<midi_file::MidiEvent> {
+        self.controls.clear();
+        self.pending.clear();
+
+        let spare = self.spare;
+        self.moved
+            .drain()
+            .filter_map(|(channel, key)| {
+                let spare = spare[channel as usize % 16]?;
+                Some(note_off(spare, key))
+            })
+            .collect()
+    }
+
+    /// Event of the next loop while it overlaps the end, as it's sent to the output.
+    /// `progress` is how far into the crossfade playback is
+    pub(crate) fn next_event(
+        &mut self,
+        event: &midi_file::MidiEvent,
+        progress: f32,
+    ) -> midi_file::MidiEvent {
+        let spare = match self.spare(event.channel) {
+            Some(spare) => spare,
+            None => return scale_velocity(event, progress),
+        };
+
+        match note(&event.message) {
+            Some((key, true)) => {
+                self.moved.insert((event.channel, key));
+            }
+            Some((key, false)) => {
+                self.moved.remove(&(event.channel, key));
+            }
+            None => self.controls.push(event.clone()),
+        }
+
+        let mut event = event.clone();
+        event.channel = spare;
+        event
+    }
+
+    /// Keeps an event of the next loop, to publish once it takes over
+    pub(crate) fn defer(&mut self, event: midi_file::MidiEvent) {
+        self.pending.push(event);
+    }
+
+    /// Event of the ending loop while it overlaps the next one, as it's sent to the output.
+    /// `progress` is how far into the crossfade playback is
+    pub(crate) fn ending_event(
+        &self,
+        event: &midi_file::MidiEvent,
+        progress: f32,
+    ) -> midi_file::MidiEvent {
+        match self.spare(event.channel) {
+            Some(_) => event.clone(),
+            None => scale_velocity(event, 1.0 - progress),
+        }
+    }
+
+    /// The next loop took over, returns the events to send to the output
+    /// and the ones to publish
+    pub(crate) fn take_over(&mut self) -> (Vec<midi_file::MidiEvent>, Vec<midi_file::MidiEvent>) {
+        let controls = std::mem::take(&mut self.controls);
+        let pending = std::mem::take(&mut self.pending);
+        (controls, pending)
+    }
+
+    /// Event of the playing loop outside of a crossfade, as it's sent to the output.
+    /// Notes held on a spare channel end there, and hear the controllers of their channel
+    pub(crate) fn event(&mut self, event: &midi_file::MidiEvent) -> Vec<midi_file::MidiEvent> {
+        let spare = match self.spare(event.channel) {
+            Some(spare) if !self.moved.is_empty() => spare,
+            _ => return vec![event.clone()],
+        };
+
+        match note(&event.message) {
+            // Struck again, the one on the spare channel ends first
+            Some((key, true)) => {
+                let mut events = Vec::new();
+                if self.moved.remove(&(event.channel, key)) {
+                    events.push(note_off(spare, key));
+                }
+                events.push(event.clone());
+                events
+            }
+            Some((key, false)) => {
+                if self.moved.remove(&(event.channel, key)) {
+                    let mut event = event.clone();
+                    event.channel = spare;
+                    vec![event]
+                } else {
+                    vec![event.clone()]
+                }
+            }
+            None => {
+                let mut events = vec![event.clone()];
+                if self
+                    .moved
+                    .iter()
+                    .any(|(channel, _)| *channel == event.channel)
+                {
+                    let mut event = event.clone();
+                    event.channel = spare;
+                    events.push(event);
+                }
+                events
+            }
+        }
+    }
+}
+
+/// Key of a note event, and whether it's a note-on. `None` for other events
+fn note(message: &MidiMessage) -> Option<(u8, bool)> {
+    match *message {
+        MidiMessage::NoteOn { key, vel } => Some((key.as_int(), vel.as_int() > 0)),
+        MidiMessage::NoteOff { key, .. } => Some((key.as_int(), false)),
+        _ => None,
+    }
+}
+
+fn event(channel: u8, message: MidiMessage) -> midi_file::MidiEvent {
+    midi_file::MidiEvent {
+        channel,
+        delta: 0,
+        timestamp: Duration::ZERO,
+        message,
+        track_id: 0,
+        track_color_id: 0,
+    }
+}
+
+fn note_off(channel: u8, key: u8) -> midi_file::MidiEvent {
+    event(
+        channel,
+        MidiMessage::NoteOff {
+            key: u7::new(key),
+            vel: u7::new(0),
+        },
+    )
+}
+
+/// Note-on with its velocity multiplied by `gain`, other events are returned unchanged
+fn scale_velocity(event: &midi_file::MidiEvent, gain: f32) -> midi_file::MidiEvent {
+    let mut event = event.clone();
+
+    if gain < 1.0 {
+        if let MidiMessage::NoteOn { key, vel } = event.message {
+            if vel.as_int() > 0 {
+                let vel = (vel.as_int() as f32 * gain).round().max(1.0) as u8;
+                event.message = MidiMessage::NoteOn {
+                    key,
+                    vel: u7::new(vel),
+                };
+            }
+        }
+    }
+
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crossfade(channels: &[u8]) -> LoopCrossfade {
+        let mut used = [false; 16];
+        for channel in channels {
+            used[*channel as usize] = true;
+        }
+        LoopCrossfade::with_channels(used)
+    }
+
+    fn note_on(channel: u8, key: u8) -> midi_file::MidiEvent {
+        event(
+            channel,
+            MidiMessage::NoteOn {
+                key: u7::new(key),
+                vel: u7::new(100),
+            },
+        )
+    }
+
+    fn sustain(channel: u8) -> midi_file::MidiEvent {
+        event(
+            channel,
+            MidiMessage::Controller {
+                controller: u7::new(64),
+                value: u7::new(127),
+            },
+        )
+    }
+
+    #[test]
+    fn fades_each_loop_on_its_own() {
+        // Channel 0 gets spare 1, the drums have none
+        let crossfade = crossfade(&[0, 9]);
+
+        let mut mixer = [1.0; 16];
+        mixer[0] = 0.5;
+        let gains = crossfade.channel_gains(mixer, Some(0.25));
+        assert_eq!(gains[0], 0.375);
+        assert_eq!(gains[1], 0.125);
+        assert_eq!(gains[9], 1.0);
+
+        // Notes held on the spare channel keep the volume of their channel
+        let gains = crossfade.channel_gains(mixer, None);
+        assert_eq!((gains[0], gains[1]), (0.5, 0.5));
+    }
+
+    #[test]
+    fn next_loop_plays_on_spare_channels() {
+        let mut crossfade = crossfade(&[0, 9]);
+        let vel = |event: &midi_file::MidiEvent| match event.message {
+            MidiMessage::NoteOn { vel, .. } => vel.as_int(),
+            _ => unreachable!(),
+        };
+
+        let event = crossfade.next_event(&note_on(0, 60), 0.25);
+        assert_eq!((event.channel, vel(&event)), (1, 100));
+        crossfade.next_event(&sustain(0), 0.25);
+
+        // Shared drums fade by velocity instead
+        assert_eq!(vel(&crossfade.next_event(&note_on(9, 36), 0.25)), 25);
+        assert_eq!(vel(&crossfade.ending_event(&note_on(9, 36), 0.25)), 75);
+        // Notes of the ending loop stay where they are
+        assert_eq!(crossfade.ending_event(&note_on(0, 60), 0.25).channel, 0);
+
+        // The pedal of the next loop is brought over to the channel of the song
+        let (controls, _) = crossfade.take_over();
+        assert_eq!(controls.len(), 1);
+        assert_eq!(controls[0].channel, 0);
+
+        // Pedal reaches the held note, its note-off ends it on the spare channel
+        let channels = |events: Vec<midi_file::MidiEvent>| -> Vec<u8> {
+            events.iter().map(|event| event.channel).collect()
+        };
+        assert_eq!(channels(crossfade.event(&sustain(0))), [0, 1]);
+        assert_eq!(channels(crossfade.event(&note_off(0, 60))), [1]);
+        // New notes are back on the channel of the song
+        assert_eq!(channels(crossfade.event(&note_on(0, 60))), [0]);
+        assert_eq!(channels(crossfade.event(&sustain(0))), [0]);
+    }
+
+    #[test]
+    fn held_notes_end_when_started_again() {
+        let mut crossfade = crossfade(&[0]);
+        crossfade.next_event(&note_on(0, 60), 0.5);
+        crossfade.take_over();
+
+        let events = crossfade.event(&note_on(0, 60));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].channel, 1);
+        assert!(matches!(events[0].message, MidiMessage::NoteOff { .. }));
+        assert_eq!(events[1].channel, 0);
+
+        // Nothing left to end
+        crossfade.next_event(&note_on(0, 62), 0.5);
+        assert_eq!(crossfade.cancel().len(), 1);
+        assert!(crossfade.cancel().is_empty());
+    }
+}
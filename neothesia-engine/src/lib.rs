@@ -0,0 +1,53 @@
+//! Playback and play along engine of Neothesia, without any GUI.
+//!
+//! ```no_run
+//! use std::sync::{Arc, Mutex};
+//! use neothesia_engine::{DummyOutput, MidiPlayer, OutputSink, PlaybackSettings};
+//!
+//! let midi = Arc::new(midi_file::Midi::new("song.mid").unwrap());
+//! let output = Arc::new(Mutex::new(OutputSink::new(Box::new(DummyOutput))));
+//!
+//! let mut player = MidiPlayer::new(
+//!     midi,
+//!     output,
+//!     PlaybackSettings::default(),
+//!     piano_math::KeyboardRange::standard_88_keys(),
+//! );
+//! player.start();
+//!
+//! loop {
+//!     // Events played since the last call, feed them to your renderer
+//!     let _events = player.update();
+//!     std::thread::sleep(std::time::Duration::from_millis(16));
+//! }
+//! ```
+//!
+//! Sound is produced by an [`OutputConnection`], implement it to route the playback
+//! to your own synth or MIDI device.
+
+mod click_scheduler;
+mod clock;
+mod conductor;
+mod crossfade;
+mod metronome;
+mod mixer;
+mod output;
+mod performance;
+mod play_along;
+mod player;
+mod tap_tempo;
+mod velocity;
+
+pub use click_scheduler::{Click, ClickPattern, ClickScheduler, TempoRamp};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use conductor::Conductor;
+pub use metronome::{Beat, MetronomeSettings, MetronomeSound};
+pub use mixer::TrackMixer;
+pub use output::{
+    DummyOutput, MasterVolume, OutputConnection, OutputRemap, OutputSink, SharedOutput,
+};
+pub use performance::{NoteRecord, NoteResult, PerformanceLog};
+pub use play_along::{KeyPressSource, NoteTolerance, PlayAlong, PlayAlongStats};
+pub use player::{MidiPlayer, PlaybackSettings};
+pub use tap_tempo::TapTempo;
+pub use velocity::VelocityCurve;
@@ -0,0 +1,203 @@
+use std::{ops::Range, sync::mpsc::Sender, time::Duration};
+
+use crate::output::OutputSink;
+
+/// How long metronome notes are held
+const NOTE_LEN: Duration = Duration::from_millis(100);
+
+/// Beat of the song, or of the count-in before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Beat {
+    /// Measure of the song, `None` during the count-in
+    pub measure: Option<usize>,
+    /// Beat within the measure, `0` is the downbeat
+    pub beat: usize,
+    /// Beats in the measure
+    pub beats: usize,
+}
+
+impl Beat {
+    pub fn is_downbeat(&self) -> bool {
+        self.beat == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetronomeSound {
+    /// Click sample of the output, outputs that can't play samples stay silent
+    Click,
+    /// Percussion notes on channel 10, `accent` plays on downbeats
+    Note { note: u8, accent: u8 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MetronomeSettings {
+    pub sound: MetronomeSound,
+    /// `0.0..=1.0`
+    pub volume: f32,
+    /// Measures clicked before the song starts, read when the player is created
+    pub count_in: u32,
+}
+
+/// Length of the count-in, the lead-in has to be at least this long
+pub(crate) fn count_in_len(midi: &midi_file::Midi, bars: u32) -> Duration {
+    midi.analysis
+        .measures
+        .first()
+        .map_or(Duration::ZERO, |measure| {
+            (measure.end - measure.start) * bars
+        })
+}
+
+/// Plays the beats of the song as playback passes them, and sends them to the player
+pub(crate) struct Metronome {
+    /// Every beat in playback time, counted from the start of the lead-in, sorted
+    beats: Vec<(Duration, Beat)>,
+    tx: Sender<Beat>,
+}
+
+impl Metronome {
+    pub(crate) fn new(
+        midi: &midi_file::Midi,
+        lead_in: Duration,
+        count_in: u32,
+        tx: Sender<Beat>,
+    ) -> Self {
+        let measures = &midi.analysis.measures;
+        let mut beats = Vec::new();
+
+        // Count-in at the pace of the first measure, ending where the song starts
+        if let Some(first) = measures.first() {
+            let per_measure = first.beats.len().max(1);
+            let beat_len = (first.end - first.start) / per_measure as u32;
+            let total = count_in as usize * per_measure;
+
+            for id in 0..total {
+                let before_start = beat_len * (total - id) as u32;
+                if let Some(time) = lead_in.checked_sub(before_start) {
+                    let beat = Beat {
+                        measure: None,
+                        beat: id % per_measure,
+                        beats: per_measure,
+                    };
+                    beats.push((time, beat));
+                }
+            }
+        }
+
+        for (id, measure) in measures.iter().enumerate() {
+            for (beat, time) in measure.beats.iter().enumerate() {
+                let beat = Beat {
+                    measure: Some(id),
+                    beat,
+                    beats: measure.beats.len(),
+                };
+                beats.push((lead_in + *time, beat));
+            }
+        }
+
+        Self { beats, tx }
+    }
+
+    /// Sounds and sends the beats within `range`, delayed by their offset into it like notes
+    pub(crate) fn play(
+        &self,
+        output: &mut OutputSink,
+        settings: &MetronomeSettings,
+        range: Range<Duration>,
+        speed: f32,
+    ) {
+        use midi_file::midly::{num::u7, MidiMessage};
+
+        let first = self.beats.partition_point(|(time, _)| *time < range.start);
+        let beats = self.beats[first..]
+            .iter()
+            .take_while(|(time, _)| *time < range.end);
+
+        let volume = settings.volume.clamp(0.0, 1.0);
+        for (time, beat) in beats {
+            let delay = (*time - range.start).div_f32(speed);
+
+            match settings.sound {
+                MetronomeSound::Click => output.click(beat.is_downbeat(), volume, delay),
+                MetronomeSound::Note { note, accent } => {
+                    let key = u7::new(if beat.is_downbeat() { accent } else { note }.min(127));
+                    let event = |message| midi_file::MidiEvent {
+                        channel: 9,
+                        delta: 0,
+                        timestamp: *time,
+                        message,
+                        track_id: usize::MAX,
+                        track_color_id: 0,
+                    };
+
+                    let vel = u7::new(((volume * 127.0).round() as u8).max(1));
+                    output.midi_event_delayed(&event(MidiMessage::NoteOn { key, vel }), delay);
+                    output.midi_event_delayed(
+                        &event(MidiMessage::NoteOff {
+                            key,
+                            vel: u7::new(0),
+                        }),
+                        delay + NOTE_LEN,
+                    );
+                }
+            }
+
+            self.tx.send(*beat).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::DummyOutput;
+    use std::sync::mpsc;
+
+    #[test]
+    fn count_in_leads_into_the_song() {
+        let midi = midi_file::stress::black_midi(1, 200);
+        let first = midi.analysis.measures.first().unwrap().clone();
+        let lead_in = count_in_len(&midi, 2).max(Duration::from_secs(3));
+
+        let (tx, _rx) = mpsc::channel();
+        let metronome = Metronome::new(&midi, lead_in, 2, tx);
+
+        let count_in: Vec<_> = metronome
+            .beats
+            .iter()
+            .take_while(|(_, beat)| beat.measure.is_none())
+            .collect();
+        assert_eq!(count_in.len(), first.beats.len() * 2);
+        assert!(count_in[0].1.is_downbeat());
+        assert!(count_in.iter().all(|(time, _)| *time < lead_in));
+
+        let (time, beat) = metronome.beats[count_in.len()];
+        assert_eq!(beat.measure, Some(0));
+        assert_eq!(time, lead_in + first.beats[0]);
+
+        assert!(metronome.beats.windows(2).all(|w| w[0].0 <= w[1].0));
+    }
+
+    #[test]
+    fn play_sends_beats_within_range() {
+        let midi = midi_file::stress::black_midi(1, 200);
+        let (tx, rx) = mpsc::channel();
+        let metronome = Metronome::new(&midi, Duration::ZERO, 0, tx);
+
+        let mut output = OutputSink::new(Box::new(DummyOutput));
+        let settings = MetronomeSettings {
+            sound: MetronomeSound::Click,
+            volume: 1.0,
+            count_in: 0,
+        };
+
+        let end = metronome.beats[3].0;
+        metronome.play(&mut output, &settings, Duration::ZERO..end, 1.0);
+        assert_eq!(rx.try_iter().count(), 3);
+
+        // Nothing is played twice when the next range starts where this one ended
+        metronome.play(&mut output, &settings, end..end, 1.0);
+        assert_eq!(rx.try_iter().count(), 0);
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::{HashMap, HashSet};
+
+/// Live mix of the tracks, changed while the song plays. Unlike silent tracks, tracks left
+/// out of the mix are still published and waited for by [`crate::PlayAlong`]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TrackMixer {
+    /// Amplitude factor of each track, tracks left out are at `1.0`
+    pub volume: HashMap<usize, f32>,
+    pub muted: HashSet<usize>,
+    /// When not empty, only these tracks are heard
+    pub solo: HashSet<usize>,
+}
+
+impl TrackMixer {
+    pub const MAX_VOLUME: f32 = 1.5;
+
+    pub fn volume(&self, track_id: usize) -> f32 {
+        self.volume.get(&track_id).copied().unwrap_or(1.0)
+    }
+
+    pub fn set_volume(&mut self, track_id: usize, volume: f32) {
+        self.volume
+            .insert(track_id, volume.clamp(0.0, Self::MAX_VOLUME));
+    }
+
+    pub fn toggle_mute(&mut self, track_id: usize) {
+        if !self.muted.remove(&track_id) {
+            self.muted.insert(track_id);
+        }
+    }
+
+    pub fn toggle_solo(&mut self, track_id: usize) {
+        if !self.solo.remove(&track_id) {
+            self.solo.insert(track_id);
+        }
+    }
+
+    /// Whether notes of the track are sent to the output
+    pub fn is_heard(&self, track_id: usize) -> bool {
+        !self.muted.contains(&track_id) && (self.solo.is_empty() || self.solo.contains(&track_id))
+    }
+
+    /// Whether switching from `other` to this mix changes which tracks are heard
+    pub fn heard_changed(&self, other: &TrackMixer) -> bool {
+        self.muted != other.muted || self.solo != other.solo
+    }
+
+    /// Gain of each of the 16 channels. Volume is set per channel on the output, tracks
+    /// sharing a channel get the volume of the loudest one
+    pub fn channel_gains(&self, midi: &midi_file::Midi) -> [f32; 16] {
+        let mut gains: [Option<f32>; 16] = [None; 16];
+        for track in midi.tracks.iter() {
+            let volume = self.volume(track.track_id);
+            for note in track.notes.iter() {
+                let gain = &mut gains[note.channel as usize % 16];
+                *gain = Some(gain.map_or(volume, |gain| gain.max(volume)));
+            }
+        }
+        gains.map(|gain| gain.unwrap_or(1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mute_and_solo() {
+        let mut mixer = TrackMixer::default();
+        assert!(mixer.is_heard(0) && mixer.is_heard(1));
+
+        mixer.toggle_mute(1);
+        assert!(mixer.is_heard(0) && !mixer.is_heard(1));
+
+        // Solo leaves out every other track, mute still wins
+        let before = mixer.clone();
+        mixer.toggle_solo(1);
+        assert!(mixer.heard_changed(&before));
+        assert!(!mixer.is_heard(0) && !mixer.is_heard(1));
+        mixer.toggle_mute(1);
+        assert!(!mixer.is_heard(0) && mixer.is_heard(1));
+
+        mixer.toggle_solo(1);
+        assert!(mixer.is_heard(0) && mixer.is_heard(1));
+
+        mixer.set_volume(0, 10.0);
+        assert_eq!(mixer.volume(0), TrackMixer::MAX_VOLUME);
+        assert!(!mixer.heard_changed(&TrackMixer::default()));
+    }
+}
@@ -0,0 +1,405 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Sound destination of the playback, eg. a synth or a MIDI device
+pub trait OutputConnection: Send {
+    fn midi_event(&mut self, _msg: &midi_file::MidiEvent) {}
+    /// Event that should sound `delay` from now, backends that can't schedule play it right away
+    fn midi_event_delayed(&mut self, msg: &midi_file::MidiEvent, _delay: Duration) {
+        self.midi_event(msg);
+    }
+    fn sysex(&mut self, _data: &[u8]) {}
+    /// Metronome click that should sound `delay` from now, outputs without samples ignore it
+    fn click(&mut self, _accent: bool, _volume: f32, _delay: Duration) {}
+    /// Mechanical click of a key press, in place of its note, outputs without samples ignore it
+    fn key_click(&mut self, _volume: f32) {}
+    fn stop_all(&mut self) {}
+    /// Smooth variant of `stop_all`, used when pausing
+    fn fade_out(&mut self) {
+        self.stop_all();
+    }
+    fn fade_in(&mut self) {}
+    /// Amplitude factor of everything the output plays, returns `false` when it has no audio
+    /// of its own, the velocities sent to it are scaled instead
+    fn set_gain(&mut self, _gain: f32) -> bool {
+        false
+    }
+}
+
+/// Output that drops everything
+pub struct DummyOutput;
+impl OutputConnection for DummyOutput {}
+
+/// Device specific adjustments applied to everything sent to an output
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OutputRemap {
+    /// Force all traffic onto a single channel
+    pub channel: Option<u8>,
+    /// Semitones
+    pub transpose: i8,
+}
+
+/// Loudness of everything sent to the output, the synth is turned down,
+/// velocities sent to MIDI devices are scaled
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MasterVolume {
+    /// Decibels, within `MIN_DB..=MAX_DB`
+    pub db: f32,
+    pub muted: bool,
+}
+
+impl MasterVolume {
+    pub const MIN_DB: f32 = -40.0;
+    pub const MAX_DB: f32 = 6.0;
+
+    pub fn step(&mut self, db: f32) {
+        self.db = (self.db + db).clamp(Self::MIN_DB, Self::MAX_DB);
+    }
+
+    /// Amplitude factor
+    pub fn gain(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            10f32.powf(self.db / 20.0)
+        }
+    }
+
+    /// Note-on velocity at this volume, `0` when the note shouldn't be played.
+    /// Loudness follows velocity squared, so a decibel moves it half as far
+    pub fn velocity(&self, vel: u8) -> u8 {
+        if self.muted || vel == 0 {
+            return 0;
+        }
+
+        let vel = vel.min(127) as f32 * 10f32.powf(self.db / 40.0);
+        vel.round().clamp(1.0, 127.0) as u8
+    }
+}
+
+impl fmt::Display for MasterVolume {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.muted {
+            write!(f, "Muted")
+        } else {
+            write!(f, "{:+.0} dB", self.db)
+        }
+    }
+}
+
+/// Current output connection with its per-output settings applied,
+/// shared with the playback thread
+pub struct OutputSink {
+    connection: Box<dyn OutputConnection>,
+    remap: OutputRemap,
+    volume: MasterVolume,
+    /// The connection applies the volume itself, velocities are sent as they are
+    has_gain: bool,
+    /// Factor of the channel volume controller of each channel, set by the mixer
+    channel_gains: [f32; 16],
+    /// Last channel volume the song set on each channel, before the gain
+    channel_volumes: [u8; 16],
+}
+
+/// Channel volume controller
+const CHANNEL_VOLUME: u8 = 7;
+/// Channel volume of General MIDI devices after a reset
+const DEFAULT_CHANNEL_VOLUME: u8 = 100;
+
+pub type SharedOutput = Arc<Mutex<OutputSink>>;
+
+impl OutputSink {
+    pub fn new(connection: Box<dyn OutputConnection>) -> Self {
+        Self {
+            connection,
+            remap: OutputRemap::default(),
+            volume: MasterVolume::default(),
+            has_gain: false,
+            channel_gains: [1.0; 16],
+            channel_volumes: [DEFAULT_CHANNEL_VOLUME; 16],
+        }
+    }
+
+    pub fn set_connection(&mut self, connection: Box<dyn OutputConnection>) {
+        self.connection = connection;
+        self.has_gain = self.connection.set_gain(self.volume.gain());
+    }
+
+    pub fn set_volume(&mut self, volume: MasterVolume) {
+        if volume != self.volume {
+            self.volume = volume;
+            self.has_gain = self.connection.set_gain(volume.gain());
+        }
+    }
+
+    /// Loudness of each channel, applied through the channel volume controller so notes
+    /// already sounding follow it too. Only channels whose volume changed are sent, so it can
+    /// be called on every tick of a fade
+    pub fn set_channel_gains(&mut self, gains: [f32; 16]) {
+        use midi_file::midly::{num::u7, MidiMessage};
+
+        for (channel, gain) in gains.into_iter().enumerate() {
+            let volume = self.channel_volume(channel);
+            self.channel_gains[channel] = gain;
+            if self.channel_volume(channel) == volume {
+                continue;
+            }
+
+            let msg = midi_file::MidiEvent {
+                channel: channel as u8,
+                delta: 0,
+                timestamp: Duration::ZERO,
+                message: MidiMessage::Controller {
+                    controller: u7::new(CHANNEL_VOLUME),
+                    value: u7::new(self.channel_volume(channel)),
+                },
+                track_id: 0,
+                track_color_id: 0,
+            };
+            self.send(&msg, Duration::ZERO);
+        }
+    }
+
+    /// Channel volume sent to the output, the one of the song with the gain applied
+    fn channel_volume(&self, channel: usize) -> u8 {
+        let volume = self.channel_volumes[channel] as f32 * self.channel_gains[channel];
+        volume.round().clamp(0.0, 127.0) as u8
+    }
+
+    /// Channel volume event with the gain applied, `None` for any other event
+    fn channel_volume_event(&mut self, msg: &midi_file::MidiEvent) -> Option<midi_file::MidiEvent> {
+        use midi_file::midly::{num::u7, MidiMessage};
+
+        match msg.message {
+            MidiMessage::Controller { controller, value }
+                if controller.as_int() == CHANNEL_VOLUME =>
+            {
+                let channel = msg.channel as usize % 16;
+                self.channel_volumes[channel] = value.as_int();
+
+                let mut msg = msg.clone();
+                msg.message = MidiMessage::Controller {
+                    controller,
+                    value: u7::new(self.channel_volume(channel)),
+                };
+                Some(msg)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn set_remap(&mut self, remap: OutputRemap) {
+        if remap != self.remap {
+            self.stop_all();
+            self.remap = remap;
+        }
+    }
+
+    pub fn midi_event(&mut self, msg: &midi_file::MidiEvent) {
+        self.midi_event_delayed(msg, Duration::ZERO);
+    }
+
+    pub fn midi_event_delayed(&mut self, msg: &midi_file::MidiEvent, delay: Duration) {
+        if let Some(msg) = self.channel_volume_event(msg) {
+            self.send(&msg, delay);
+        } else if self.has_gain || self.volume == MasterVolume::default() {
+            self.send(msg, delay);
+        } else if let Some(msg) = volume_event(&self.volume, msg) {
+            self.send(&msg, delay);
+        }
+    }
+
+    fn send(&mut self, msg: &midi_file::MidiEvent, delay: Duration) {
+        if self.remap == OutputRemap::default() {
+            self.connection.midi_event_delayed(msg, delay);
+        } else if let Some(msg) = remap_event(&self.remap, msg) {
+            self.connection.midi_event_delayed(&msg, delay);
+        }
+    }
+
+    pub fn sysex(&mut self, data: &[u8]) {
+        self.connection.sysex(data);
+    }
+
+    pub fn click(&mut self, accent: bool, volume: f32, delay: Duration) {
+        self.connection.click(accent, volume, delay);
+    }
+
+    pub fn key_click(&mut self, volume: f32) {
+        self.connection.key_click(volume);
+    }
+
+    pub fn stop_all(&mut self) {
+        self.connection.stop_all();
+    }
+
+    pub fn fade_out(&mut self) {
+        self.connection.fade_out();
+    }
+
+    pub fn fade_in(&mut self) {
+        self.connection.fade_in();
+    }
+}
+
+/// Returns `None` when the transposed key falls out of MIDI range
+fn remap_event(remap: &OutputRemap, msg: &midi_file::MidiEvent) -> Option<midi_file::MidiEvent> {
+    use midi_file::midly::{num::u7, MidiMessage};
+
+    let transpose = |key: u7| -> Option<u7> {
+        let key = key.as_int() as i16 + remap.transpose as i16;
+        (0..=127).contains(&key).then(|| u7::new(key as u8))
+    };
+
+    let mut msg = msg.clone();
+
+    if let Some(channel) = remap.channel {
+        msg.channel = channel.min(15);
+    }
+
+    msg.message = match msg.message {
+        MidiMessage::NoteOn { key, vel } => MidiMessage::NoteOn {
+            key: transpose(key)?,
+            vel,
+        },
+        MidiMessage::NoteOff { key, vel } => MidiMessage::NoteOff {
+            key: transpose(key)?,
+            vel,
+        },
+        MidiMessage::Aftertouch { key, vel } => MidiMessage::Aftertouch {
+            key: transpose(key)?,
+            vel,
+        },
+        message => message,
+    };
+
+    Some(msg)
+}
+
+/// Returns `None` for note-ons that are muted
+fn volume_event(volume: &MasterVolume, msg: &midi_file::MidiEvent) -> Option<midi_file::MidiEvent> {
+    use midi_file::midly::{num::u7, MidiMessage};
+
+    let mut msg = msg.clone();
+    if let MidiMessage::NoteOn { key, vel } = msg.message {
+        if vel.as_int() > 0 {
+            let vel = volume.velocity(vel.as_int());
+            if vel == 0 {
+                return None;
+            }
+            msg.message = MidiMessage::NoteOn {
+                key,
+                vel: u7::new(vel),
+            };
+        }
+    }
+
+    Some(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midi_file::midly::{num::u7, MidiMessage};
+
+    fn note_on(key: u8) -> midi_file::MidiEvent {
+        midi_file::MidiEvent {
+            channel: 2,
+            delta: 0,
+            timestamp: Duration::ZERO,
+            message: MidiMessage::NoteOn {
+                key: u7::new(key),
+                vel: u7::new(100),
+            },
+            track_id: 0,
+            track_color_id: 0,
+        }
+    }
+
+    #[test]
+    fn remap() {
+        let remap = OutputRemap {
+            channel: Some(5),
+            transpose: -12,
+        };
+
+        let msg = remap_event(&remap, &note_on(60)).unwrap();
+        assert_eq!(msg.channel, 5);
+        assert!(matches!(msg.message, MidiMessage::NoteOn { key, .. } if key.as_int() == 48));
+
+        assert!(remap_event(&remap, &note_on(5)).is_none());
+    }
+
+    #[test]
+    fn volume() {
+        let mut volume = MasterVolume {
+            db: -12.0,
+            muted: false,
+        };
+        let vel = |msg: midi_file::MidiEvent| match msg.message {
+            MidiMessage::NoteOn { vel, .. } => vel.as_int(),
+            _ => unreachable!(),
+        };
+
+        assert_eq!(volume_event(&volume, &note_on(60)).map(vel), Some(50));
+        assert_eq!(volume.to_string(), "-12 dB");
+
+        volume.step(100.0);
+        assert_eq!(volume.db, MasterVolume::MAX_DB);
+        assert_eq!(volume_event(&volume, &note_on(60)).map(vel), Some(127));
+
+        volume.muted = true;
+        assert_eq!(volume.gain(), 0.0);
+        assert!(volume_event(&volume, &note_on(60)).is_none());
+        assert_eq!(volume.to_string(), "Muted");
+    }
+
+    struct Log(Arc<Mutex<Vec<midi_file::MidiEvent>>>);
+    impl OutputConnection for Log {
+        fn midi_event(&mut self, msg: &midi_file::MidiEvent) {
+            self.0.lock().unwrap().push(msg.clone());
+        }
+    }
+
+    #[test]
+    fn channel_gains() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut sink = OutputSink::new(Box::new(Log(log.clone())));
+        let sent = || -> Vec<(u8, u8)> {
+            log.lock()
+                .unwrap()
+                .drain(..)
+                .filter_map(|msg| match msg.message {
+                    MidiMessage::Controller { value, .. } => Some((msg.channel, value.as_int())),
+                    _ => None,
+                })
+                .collect()
+        };
+        let channel_volume = |value| midi_file::MidiEvent {
+            message: MidiMessage::Controller {
+                controller: u7::new(CHANNEL_VOLUME),
+                value: u7::new(value),
+            },
+            ..note_on(60)
+        };
+
+        // Only the changed channel, from the default volume
+        let mut gains = [1.0; 16];
+        gains[2] = 0.5;
+        sink.set_channel_gains(gains);
+        assert_eq!(sent(), [(2, 50)]);
+
+        // Volumes of the song are scaled
+        sink.midi_event(&channel_volume(80));
+        assert_eq!(sent(), [(2, 40)]);
+
+        sink.set_channel_gains([1.0; 16]);
+        assert_eq!(sent(), [(2, 80)]);
+    }
+}
@@ -0,0 +1,296 @@
+use std::{fmt::Write, time::Duration};
+
+/// How far apart a user press and a file note can be to still count as the same note
+const MATCH_WINDOW: Duration = Duration::from_millis(300);
+/// Presses closer than this to the file note are on time
+const HIT_WINDOW: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteResult {
+    Hit,
+    Early,
+    Late,
+    /// File note the user didn't play
+    Missed,
+    /// User press without a matching file note
+    Extra,
+}
+
+impl NoteResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hit => "hit",
+            Self::Early => "early",
+            Self::Late => "late",
+            Self::Missed => "missed",
+            Self::Extra => "extra",
+        }
+    }
+}
+
+/// Times are song times, without the lead-in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteRecord {
+    pub note: u8,
+    pub expected: Option<Duration>,
+    pub played: Option<Duration>,
+    pub velocity: Option<u8>,
+    pub result: NoteResult,
+}
+
+impl NoteRecord {
+    /// Positive when played late, in milliseconds
+    pub fn offset_ms(&self) -> Option<i64> {
+        let expected = self.expected?.as_millis() as i64;
+        let played = self.played?.as_millis() as i64;
+        Some(played - expected)
+    }
+
+    fn time(&self) -> Duration {
+        self.expected.or(self.played).unwrap_or_default()
+    }
+}
+
+#[derive(Debug)]
+struct Press {
+    note: u8,
+    velocity: u8,
+    time: Duration,
+}
+
+/// Pairs file notes with user presses, for exporting a practice session
+#[derive(Debug, Default)]
+pub struct PerformanceLog {
+    records: Vec<NoteRecord>,
+    /// Records of file notes that can still be matched
+    pending: Vec<usize>,
+    /// Presses that came before their file note
+    early: Vec<Press>,
+}
+
+impl PerformanceLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// File note-on at `time`
+    pub fn expect(&mut self, note: u8, time: Duration) {
+        self.expire(time);
+
+        if let Some(id) = self.early.iter().position(|press| press.note == note) {
+            let press = self.early.remove(id);
+            self.records.push(NoteRecord {
+                note,
+                expected: Some(time),
+                played: Some(press.time),
+                velocity: Some(press.velocity),
+                result: judge(time, press.time),
+            });
+        } else {
+            self.pending.push(self.records.len());
+            self.records.push(NoteRecord {
+                note,
+                expected: Some(time),
+                played: None,
+                velocity: None,
+                result: NoteResult::Missed,
+            });
+        }
+    }
+
+    /// User note-on at `time`
+    pub fn play(&mut self, note: u8, velocity: u8, time: Duration) {
+        self.expire(time);
+
+        let records = &self.records;
+        if let Some(id) = self.pending.iter().position(|id| records[*id].note == note) {
+            let record = &mut self.records[self.pending.remove(id)];
+            record.played = Some(time);
+            record.velocity = Some(velocity);
+            record.result = judge(record.expected.unwrap_or(time), time);
+        } else {
+            self.early.push(Press {
+                note,
+                velocity,
+                time,
+            });
+        }
+    }
+
+    /// Stops matching notes that are out of the window at `time`
+    fn expire(&mut self, time: Duration) {
+        let records = &self.records;
+        self.pending.retain(|id| {
+            let expected = records[*id].expected.unwrap_or_default();
+            expected + MATCH_WINDOW >= time
+        });
+
+        let (expired, early): (Vec<Press>, Vec<Press>) = std::mem::take(&mut self.early)
+            .into_iter()
+            .partition(|press| press.time + MATCH_WINDOW < time);
+        self.early = early;
+
+        for press in expired {
+            self.push_extra(press);
+        }
+    }
+
+    fn push_extra(&mut self, press: Press) {
+        self.records.push(NoteRecord {
+            note: press.note,
+            expected: None,
+            played: Some(press.time),
+            velocity: Some(press.velocity),
+            result: NoteResult::Extra,
+        });
+    }
+
+    /// Forgets everything that can still be matched, eg. after a seek
+    pub fn interrupt(&mut self) {
+        self.pending.clear();
+        for press in std::mem::take(&mut self.early) {
+            self.push_extra(press);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// All notes so far, in song order
+    pub fn records(&self) -> Vec<NoteRecord> {
+        let mut records = self.records.clone();
+        for press in self.early.iter() {
+            records.push(NoteRecord {
+                note: press.note,
+                expected: None,
+                played: Some(press.time),
+                velocity: Some(press.velocity),
+                result: NoteResult::Extra,
+            });
+        }
+        records.sort_by_key(NoteRecord::time);
+        records
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("note,expected_ms,played_ms,offset_ms,velocity,result\n");
+
+        for record in self.records() {
+            let field = |v: Option<String>| v.unwrap_or_default();
+            writeln!(
+                out,
+                "{},{},{},{},{},{}",
+                record.note,
+                field(record.expected.map(|t| t.as_millis().to_string())),
+                field(record.played.map(|t| t.as_millis().to_string())),
+                field(record.offset_ms().map(|t| t.to_string())),
+                field(record.velocity.map(|v| v.to_string())),
+                record.result.as_str(),
+            )
+            .ok();
+        }
+
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let field = |v: Option<String>| v.unwrap_or_else(|| "null".into());
+
+        let records: Vec<String> = self
+            .records()
+            .iter()
+            .map(|record| {
+                format!(
+                    r#"  {{"note": {}, "expected_ms": {}, "played_ms": {}, "offset_ms": {}, "velocity": {}, "result": "{}"}}"#,
+                    record.note,
+                    field(record.expected.map(|t| t.as_millis().to_string())),
+                    field(record.played.map(|t| t.as_millis().to_string())),
+                    field(record.offset_ms().map(|t| t.to_string())),
+                    field(record.velocity.map(|v| v.to_string())),
+                    record.result.as_str(),
+                )
+            })
+            .collect();
+
+        format!("[\n{}\n]\n", records.join(",\n"))
+    }
+}
+
+fn judge(expected: Duration, played: Duration) -> NoteResult {
+    if played + HIT_WINDOW < expected {
+        NoteResult::Early
+    } else if played > expected + HIT_WINDOW {
+        NoteResult::Late
+    } else {
+        NoteResult::Hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn results(log: &PerformanceLog) -> Vec<NoteResult> {
+        log.records().iter().map(|r| r.result).collect()
+    }
+
+    #[test]
+    fn matches_presses_with_file_notes() {
+        let mut log = PerformanceLog::new();
+
+        log.expect(60, ms(1000));
+        log.play(60, 90, ms(1020));
+
+        log.expect(62, ms(2000));
+        log.play(62, 80, ms(2200));
+
+        log.play(64, 70, ms(2850));
+        log.expect(64, ms(3000));
+
+        log.expect(65, ms(4000));
+        log.play(67, 100, ms(4010));
+        log.interrupt();
+
+        assert_eq!(
+            results(&log),
+            [
+                NoteResult::Hit,
+                NoteResult::Late,
+                NoteResult::Early,
+                NoteResult::Missed,
+                NoteResult::Extra,
+            ]
+        );
+        assert_eq!(log.records()[1].offset_ms(), Some(200));
+    }
+
+    #[test]
+    fn presses_out_of_window_are_extra() {
+        let mut log = PerformanceLog::new();
+
+        log.play(60, 90, ms(0));
+        log.expect(60, ms(1000));
+
+        assert_eq!(results(&log), [NoteResult::Extra, NoteResult::Missed]);
+    }
+
+    #[test]
+    fn csv() {
+        let mut log = PerformanceLog::new();
+        log.expect(60, ms(1000));
+        log.play(60, 90, ms(1050));
+        log.expect(62, ms(2000));
+
+        assert_eq!(
+            log.to_csv(),
+            "note,expected_ms,played_ms,offset_ms,velocity,result\n\
+             60,1000,1050,50,90,hit\n\
+             62,2000,,,,missed\n"
+        );
+    }
+}
@@ -0,0 +1,481 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::clock::{Clock, SystemClock};
+
+/// How long a user press can precede the file note it is matched with
+const LEEWAY: Duration = Duration::from_millis(500);
+/// Presses closer than this to the file note are on time
+const HIT_WINDOW: Duration = Duration::from_millis(100);
+
+pub enum KeyPressSource {
+    File,
+    User,
+}
+
+/// How close a press has to be to the note it is matched with
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NoteTolerance {
+    /// Only the key of the note
+    #[default]
+    Exact,
+    /// The same note in any octave, file notes out of the keyboard range are played too
+    AnyOctave,
+    /// The key of the note or one of its neighbours
+    Semitone,
+}
+
+impl NoteTolerance {
+    pub const ALL: [NoteTolerance; 3] = [Self::Exact, Self::AnyOctave, Self::Semitone];
+
+    pub fn matches(&self, note: u8, pressed: u8) -> bool {
+        match self {
+            Self::Exact => note == pressed,
+            Self::AnyOctave => note % 12 == pressed % 12,
+            Self::Semitone => note.abs_diff(pressed) <= 1,
+        }
+    }
+}
+
+impl std::fmt::Display for NoteTolerance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exact => write!(f, "Exact"),
+            Self::AnyOctave => write!(f, "Any Octave"),
+            Self::Semitone => write!(f, "Within A Semitone"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct UserPress {
+    timestamp: Instant,
+    note_id: u8,
+}
+
+/// How the user did on the notes they had to play
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PlayAlongStats {
+    /// Played within [`HIT_WINDOW`] of the file note
+    pub hits: u32,
+    /// Played before the file note, within the leeway
+    pub early: u32,
+    /// Played after the file note started
+    pub late: u32,
+    /// File notes that ended before the user played them
+    pub missed: u32,
+    /// Presses that never matched a file note
+    pub wrong: u32,
+    /// Notes played in a row, without a miss or a wrong note
+    pub streak: u32,
+    pub best_streak: u32,
+}
+
+impl PlayAlongStats {
+    /// Notes played, early and late ones included
+    pub fn played(&self) -> u32 {
+        self.hits + self.early + self.late
+    }
+
+    /// Share of the judged notes that were played, `None` before any was judged
+    pub fn accuracy(&self) -> Option<f32> {
+        let total = self.played() + self.missed + self.wrong;
+        (total > 0).then(|| self.played() as f32 / total as f32)
+    }
+
+    fn played_note(&mut self, offset: Duration, early: bool) {
+        if offset <= HIT_WINDOW {
+            self.hits += 1;
+        } else if early {
+            self.early += 1;
+        } else {
+            self.late += 1;
+        }
+
+        self.streak += 1;
+        self.best_streak = self.best_streak.max(self.streak);
+    }
+
+    fn missed_note(&mut self) {
+        self.missed += 1;
+        self.streak = 0;
+    }
+
+    fn wrong_note(&mut self) {
+        self.wrong += 1;
+        self.streak = 0;
+    }
+}
+
+/// Tracks which notes of the file the user still has to play
+#[derive(Debug)]
+pub struct PlayAlong {
+    user_keyboard_range: piano_math::KeyboardRange,
+    clock: Box<dyn Clock>,
+
+    required_notes: HashSet<u8>,
+    /// When each of the required notes started
+    required_since: HashMap<u8, Instant>,
+
+    // List of user key press events that happened in last 500ms,
+    // used for play along leeway logic
+    user_pressed_recently: VecDeque<UserPress>,
+
+    /// Keys that don't work on the user's instrument, see [`PlayAlong::set_excused_keys`]
+    excused_keys: HashSet<u8>,
+    octave_substitute: bool,
+    tolerance: NoteTolerance,
+
+    stats: PlayAlongStats,
+}
+
+impl PlayAlong {
+    pub fn new(user_keyboard_range: piano_math::KeyboardRange) -> Self {
+        Self::with_clock(user_keyboard_range, Box::new(SystemClock))
+    }
+
+    pub fn with_clock(
+        user_keyboard_range: piano_math::KeyboardRange,
+        clock: Box<dyn Clock>,
+    ) -> Self {
+        Self {
+            user_keyboard_range,
+            clock,
+            required_notes: Default::default(),
+            required_since: Default::default(),
+            user_pressed_recently: Default::default(),
+            excused_keys: Default::default(),
+            octave_substitute: false,
+            tolerance: NoteTolerance::Exact,
+            stats: Default::default(),
+        }
+    }
+
+    /// Expires old user presses, call it once per frame
+    pub fn update(&mut self) {
+        // Instead of calling .elapsed() per item let's fetch `now` once, and substract it ourselfs
+        let now = self.clock.now();
+
+        while let Some(item) = self.user_pressed_recently.front_mut() {
+            let elapsed = now - item.timestamp;
+
+            if elapsed > LEEWAY {
+                // No file note came for it
+                self.user_pressed_recently.pop_front();
+                self.stats.wrong_note();
+            } else {
+                // All subsequent items will by younger than front item, so we can break
+                break;
+            }
+        }
+    }
+
+    fn user_press_key(&mut self, note_id: u8, active: bool) {
+        let timestamp = self.clock.now();
+
+        if active {
+            // The closest of the notes the press is good for
+            let required = self
+                .required_notes
+                .iter()
+                .filter(|note| self.tolerance.matches(**note, note_id))
+                .min_by_key(|note| (note.abs_diff(note_id), **note))
+                .copied();
+
+            if let Some(required) = required {
+                self.required_notes.remove(&required);
+                let since = self.required_since.remove(&required).unwrap_or(timestamp);
+                self.stats.played_note(timestamp - since, false);
+            } else {
+                self.user_pressed_recently
+                    .push_back(UserPress { timestamp, note_id });
+            }
+        }
+    }
+
+    /// Key the user plays for a file note, `None` when nobody has to play it
+    fn played_key(&self, note_id: u8) -> Option<u8> {
+        if !self.excused_keys.contains(&note_id) {
+            return Some(note_id);
+        }
+        if !self.octave_substitute {
+            return None;
+        }
+
+        // The octave above, or else the one below
+        [note_id.checked_add(12), note_id.checked_sub(12)]
+            .into_iter()
+            .flatten()
+            .find(|key| self.user_keyboard_range.contains(*key) && !self.excused_keys.contains(key))
+    }
+
+    fn file_press_key(&mut self, note_id: u8, active: bool) {
+        let now = self.clock.now();
+        let note_id = match self.played_key(note_id) {
+            Some(note_id) => note_id,
+            None => return,
+        };
+
+        if active {
+            if let Some((id, _)) = self
+                .user_pressed_recently
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| self.tolerance.matches(note_id, item.note_id))
+                .min_by_key(|(_, item)| item.note_id.abs_diff(note_id))
+            {
+                if let Some(press) = self.user_pressed_recently.remove(id) {
+                    self.stats.played_note(now - press.timestamp, true);
+                }
+            } else if self.required_notes.insert(note_id) {
+                self.required_since.insert(note_id, now);
+            }
+        } else if self.required_notes.remove(&note_id) {
+            self.required_since.remove(&note_id);
+            self.stats.missed_note();
+        }
+    }
+
+    pub fn press_key(&mut self, src: KeyPressSource, note_id: u8, active: bool) {
+        // In any octave, every note can be played on the keyboard
+        let any_octave =
+            matches!(src, KeyPressSource::File) && self.tolerance == NoteTolerance::AnyOctave;
+        if !any_octave && !self.user_keyboard_range.contains(note_id) {
+            return;
+        }
+
+        match src {
+            KeyPressSource::User => self.user_press_key(note_id, active),
+            KeyPressSource::File => self.file_press_key(note_id, active),
+        }
+    }
+
+    /// Keys of a keyboard that can't be played, eg. a dead key. Their notes count as played,
+    /// or with `octave_substitute` are waited for an octave away
+    pub fn set_excused_keys(&mut self, keys: &[u8], octave_substitute: bool) {
+        let same_keys = keys.iter().all(|key| self.excused_keys.contains(key))
+            && self.excused_keys.iter().all(|key| keys.contains(key));
+
+        if !same_keys || octave_substitute != self.octave_substitute {
+            self.excused_keys.retain(|key| keys.contains(key));
+            self.excused_keys.extend(keys);
+            self.octave_substitute = octave_substitute;
+            // Waiting for the old keys would never end
+            self.required_notes.clear();
+            self.required_since.clear();
+        }
+    }
+
+    pub fn set_tolerance(&mut self, tolerance: NoteTolerance) {
+        if tolerance != self.tolerance {
+            self.tolerance = tolerance;
+            // Notes kept since before are matched the new way
+            self.user_pressed_recently.clear();
+        }
+    }
+
+    pub fn are_required_keys_pressed(&self) -> bool {
+        self.required_notes.is_empty()
+    }
+
+    /// Keys the file is waiting for the user to press
+    pub fn required_notes(&self) -> &HashSet<u8> {
+        &self.required_notes
+    }
+
+    pub fn stats(&self) -> &PlayAlongStats {
+        &self.stats
+    }
+
+    /// Forgets the notes the file is waiting for and the presses waiting for the file,
+    /// eg. when the song changes key
+    pub fn clear(&mut self) {
+        self.required_notes.clear();
+        self.required_since.clear();
+        self.user_pressed_recently.clear();
+    }
+
+    /// Starts counting from scratch, eg. when the song starts over
+    pub fn reset_stats(&mut self) {
+        self.stats = PlayAlongStats::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    enum Step {
+        User(u8),
+        FileOn(u8),
+        FileOff(u8),
+        /// Expected result of `are_required_keys_pressed`
+        Expect(bool),
+    }
+
+    /// Plays a timeline of `(ms, step)` against a play along driven by a manual clock
+    fn simulate(timeline: &[(u64, Step)]) -> PlayAlong {
+        let clock = ManualClock::new();
+        let mut play_along = PlayAlong::with_clock(
+            piano_math::KeyboardRange::standard_88_keys(),
+            Box::new(clock.clone()),
+        );
+
+        for (id, (ms, step)) in timeline.iter().enumerate() {
+            clock.set_elapsed(Duration::from_millis(*ms));
+            play_along.update();
+
+            match step {
+                Step::User(note) => play_along.press_key(KeyPressSource::User, *note, true),
+                Step::FileOn(note) => play_along.press_key(KeyPressSource::File, *note, true),
+                Step::FileOff(note) => play_along.press_key(KeyPressSource::File, *note, false),
+                Step::Expect(pressed) => assert_eq!(
+                    play_along.are_required_keys_pressed(),
+                    *pressed,
+                    "step {id} at {ms}ms"
+                ),
+            }
+        }
+
+        play_along
+    }
+
+    #[test]
+    fn required_keys() {
+        simulate(&[
+            (0, Step::FileOn(60)),
+            (0, Step::Expect(false)),
+            (100, Step::User(60)),
+            (100, Step::Expect(true)),
+        ]);
+    }
+
+    #[test]
+    fn early_press_within_leeway() {
+        simulate(&[
+            (0, Step::User(62)),
+            (400, Step::FileOn(62)),
+            (400, Step::Expect(true)),
+        ]);
+    }
+
+    #[test]
+    fn early_press_expires() {
+        simulate(&[
+            (0, Step::User(62)),
+            (501, Step::FileOn(62)),
+            (501, Step::Expect(false)),
+            (600, Step::FileOff(62)),
+            (600, Step::Expect(true)),
+        ]);
+    }
+
+    #[test]
+    fn early_press_matches_once() {
+        simulate(&[
+            (0, Step::User(64)),
+            (100, Step::FileOn(64)),
+            (200, Step::FileOff(64)),
+            (300, Step::FileOn(64)),
+            (300, Step::Expect(false)),
+        ]);
+    }
+
+    #[test]
+    fn out_of_range_keys_are_ignored() {
+        simulate(&[(0, Step::FileOn(10)), (0, Step::Expect(true))]);
+    }
+
+    #[test]
+    fn stats_judge_presses() {
+        let play_along = simulate(&[
+            // On time
+            (0, Step::FileOn(60)),
+            (50, Step::User(60)),
+            // Early
+            (100, Step::User(62)),
+            (400, Step::FileOn(62)),
+            // Late
+            (500, Step::FileOn(64)),
+            (800, Step::User(64)),
+            // Missed, breaks the streak
+            (900, Step::FileOn(65)),
+            (1000, Step::FileOff(65)),
+            (1100, Step::FileOn(67)),
+            (1100, Step::User(67)),
+            // Wrong, expires without a file note
+            (1200, Step::User(70)),
+            (1800, Step::Expect(true)),
+        ]);
+
+        let stats = play_along.stats();
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.early, 1);
+        assert_eq!(stats.late, 1);
+        assert_eq!(stats.missed, 1);
+        assert_eq!(stats.wrong, 1);
+        assert_eq!(stats.streak, 0);
+        assert_eq!(stats.best_streak, 3);
+        assert_eq!(stats.accuracy(), Some(4.0 / 6.0));
+    }
+
+    #[test]
+    fn excused_keys() {
+        let mut play_along = PlayAlong::new(piano_math::KeyboardRange::standard_88_keys());
+
+        play_along.set_excused_keys(&[60], false);
+        play_along.press_key(KeyPressSource::File, 60, true);
+        assert!(play_along.are_required_keys_pressed());
+
+        // Played an octave up, or down when that key is excused too
+        play_along.set_excused_keys(&[61, 73], true);
+        play_along.press_key(KeyPressSource::File, 73, true);
+        assert!(play_along.required_notes().contains(&85));
+        play_along.press_key(KeyPressSource::User, 85, true);
+        play_along.press_key(KeyPressSource::File, 61, true);
+        assert!(play_along.required_notes().contains(&49));
+        play_along.press_key(KeyPressSource::User, 49, true);
+        assert!(play_along.are_required_keys_pressed());
+        assert_eq!(play_along.stats().played(), 2);
+    }
+
+    #[test]
+    fn note_tolerance() {
+        let mut play_along = PlayAlong::new(piano_math::KeyboardRange::standard_88_keys());
+        play_along.press_key(KeyPressSource::File, 60, true);
+        play_along.press_key(KeyPressSource::User, 72, true);
+        assert!(!play_along.are_required_keys_pressed());
+
+        play_along.clear();
+        play_along.set_tolerance(NoteTolerance::AnyOctave);
+        play_along.press_key(KeyPressSource::File, 60, true);
+        play_along.press_key(KeyPressSource::User, 72, true);
+        assert!(play_along.are_required_keys_pressed());
+        // Early press a semitone off, matched when the note comes
+        play_along.set_tolerance(NoteTolerance::Semitone);
+        play_along.press_key(KeyPressSource::User, 61, true);
+        play_along.press_key(KeyPressSource::File, 62, true);
+        assert!(play_along.are_required_keys_pressed());
+        // Closest of the required notes is the one played
+        play_along.press_key(KeyPressSource::File, 64, true);
+        play_along.press_key(KeyPressSource::File, 66, true);
+        play_along.press_key(KeyPressSource::User, 65, true);
+        assert_eq!(play_along.required_notes(), &HashSet::from([66]));
+        assert_eq!(play_along.stats().played(), 3);
+    }
+
+    #[test]
+    fn press_of_a_required_note_is_used_once() {
+        simulate(&[
+            (0, Step::FileOn(60)),
+            (100, Step::User(60)),
+            (200, Step::FileOff(60)),
+            (300, Step::FileOn(60)),
+            (300, Step::Expect(false)),
+        ]);
+    }
+}
@@ -0,0 +1,1071 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    crossfade::LoopCrossfade,
+    metronome::{self, Beat, Metronome, MetronomeSettings},
+    mixer::TrackMixer,
+    output::{OutputSink, SharedOutput},
+    play_along::{KeyPressSource, PlayAlong},
+    velocity::VelocityCurve,
+};
+
+/// How often the playback thread advances the clock
+const TICK: Duration = Duration::from_millis(1);
+/// Time before the song starts, longer when the count-in needs it
+const LEAD_IN: Duration = Duration::from_secs(3);
+
+/// Settings used by the playback thread, can be changed at any time with
+/// [`MidiPlayer::set_settings`]
+#[derive(Debug, Clone)]
+pub struct PlaybackSettings {
+    pub speed_multiplier: f32,
+    pub forward_controllers: bool,
+    pub forward_program_changes: bool,
+    pub forward_sysex: bool,
+    /// Sent to the output before the song starts
+    pub system_reset: Option<&'static [u8]>,
+    /// Applied to file notes before they are sent to the output and published
+    pub velocity_curve: VelocityCurve,
+    /// Note-on velocities of a track are multiplied by its value after the velocity curve,
+    /// tracks left out keep theirs
+    pub track_velocity: HashMap<usize, f32>,
+    /// Starts over instead of finishing
+    pub looping: bool,
+    /// How long the end of the song overlaps the start of the next loop, the two are faded
+    /// through the channel volume, the next loop playing on channels the song leaves free
+    pub loop_crossfade: Duration,
+    /// Tracks whose notes are published but never sent to the output
+    pub silent_tracks: HashSet<usize>,
+    /// Tracks the user plays, their notes are never sent to the output and are the only
+    /// ones [`PlayAlong`] waits for. When empty the user plays along with every track
+    pub user_tracks: HashSet<usize>,
+    /// Volume, mute and solo of the tracks, set from the mixer while playing
+    pub mixer: TrackMixer,
+    /// Stops the clock at every note the user has to play, until [`PlayAlong`] has seen
+    /// it played
+    pub wait_for_input: bool,
+    /// Clicks on every beat, `None` turns the metronome off
+    pub metronome: Option<MetronomeSettings>,
+    /// Semitones added to file notes before they are sent to the output, published and
+    /// compared by [`PlayAlong`]. Drums keep their keys, notes pushed out of MIDI range
+    /// are dropped
+    pub transpose: i8,
+}
+
+impl Default for PlaybackSettings {
+    fn default() -> Self {
+        Self {
+            speed_multiplier: 1.0,
+            forward_controllers: true,
+            forward_program_changes: true,
+            forward_sysex: false,
+            system_reset: None,
+            velocity_curve: VelocityCurve::Unchanged,
+            track_velocity: HashMap::new(),
+            looping: false,
+            loop_crossfade: Duration::ZERO,
+            silent_tracks: HashSet::new(),
+            user_tracks: HashSet::new(),
+            mixer: TrackMixer::default(),
+            wait_for_input: false,
+            metronome: None,
+            transpose: 0,
+        }
+    }
+}
+
+impl PlaybackSettings {
+    fn should_forward(&self, event: &midi_file::MidiEvent) -> bool {
+        use midi_file::midly::MidiMessage;
+
+        match event.message {
+            MidiMessage::NoteOn { .. } | MidiMessage::NoteOff { .. } => {
+                self.is_auto_played(event.track_id)
+            }
+            MidiMessage::Controller { .. } => self.forward_controllers,
+            MidiMessage::ProgramChange { .. } => self.forward_program_changes,
+            _ => true,
+        }
+    }
+
+    /// `key` of a file note moved by [`Self::transpose`], `None` when it leaves MIDI range
+    fn transpose_key(&self, channel: u8, key: u8) -> Option<u8> {
+        if channel == 9 {
+            return Some(key);
+        }
+
+        let key = key as i16 + self.transpose as i16;
+        (0..=127).contains(&key).then_some(key as u8)
+    }
+
+    /// Velocity of a note-on of the track, `0` stays `0` as it means note-off
+    fn track_velocity(&self, track_id: usize, vel: u8) -> u8 {
+        match self.track_velocity.get(&track_id) {
+            Some(scale) if vel > 0 => (vel as f32 * scale).round().clamp(1.0, 127.0) as u8,
+            _ => vel,
+        }
+    }
+
+    /// Velocity curve, track velocity and transposition applied to a file event,
+    /// `None` when the note left MIDI range
+    fn apply(&self, event: midi_file::MidiEvent) -> Option<midi_file::MidiEvent> {
+        use midi_file::midly::{num::u7, MidiMessage};
+
+        let mut event = self.velocity_curve.apply(event);
+        event.message = match event.message {
+            MidiMessage::NoteOn { key, vel } => MidiMessage::NoteOn {
+                key: u7::new(self.transpose_key(event.channel, key.as_int())?),
+                vel: u7::new(self.track_velocity(event.track_id, vel.as_int())),
+            },
+            MidiMessage::NoteOff { key, vel } => MidiMessage::NoteOff {
+                key: u7::new(self.transpose_key(event.channel, key.as_int())?),
+                vel,
+            },
+            MidiMessage::Aftertouch { key, vel } => MidiMessage::Aftertouch {
+                key: u7::new(self.transpose_key(event.channel, key.as_int())?),
+                vel,
+            },
+            message => message,
+        };
+
+        Some(event)
+    }
+
+    /// Whether notes of the track are sent to the output
+    fn is_auto_played(&self, track_id: usize) -> bool {
+        !self.silent_tracks.contains(&track_id)
+            && !self.user_tracks.contains(&track_id)
+            && self.mixer.is_heard(track_id)
+    }
+
+    /// Whether the user has to play the notes of the track, muted tracks never are
+    fn is_user_played(&self, track_id: usize) -> bool {
+        !self.silent_tracks.contains(&track_id)
+            && (self.user_tracks.is_empty() || self.user_tracks.contains(&track_id))
+    }
+}
+
+/// Playback state shared between the playback thread and the player
+struct Playback {
+    state: midi_file::PlaybackState,
+    settings: PlaybackSettings,
+    /// Freezes the clock without pausing, while waiting for the user to play
+    hold: bool,
+    /// Notes outside of it are never waited for
+    user_keyboard_range: piano_math::KeyboardRange,
+    /// Start of the song playing over the end of it when looping, takes over from `state`
+    /// once the end is reached
+    next: Option<midi_file::PlaybackState>,
+    crossfade: LoopCrossfade,
+    /// Gain of each channel set by the mixer, before the crossfade
+    mixer_gains: [f32; 16],
+    /// Section played over and over, counted from the start of the lead-in
+    loop_range: Option<Range<Duration>>,
+    metronome: Metronome,
+    /// Times playback started over, either the loop range or the whole song
+    loops: u32,
+}
+
+impl Playback {
+    fn tick(
+        &mut self,
+        midi: &midi_file::Midi,
+        output: &SharedOutput,
+        events_tx: &Sender<midi_file::MidiEvent>,
+        delta: Duration,
+    ) {
+        let delta = if self.hold { Duration::ZERO } else { delta };
+        let mut elapsed = delta.mul_f64(self.settings.speed_multiplier.max(0.0) as f64);
+
+        // Stopping right at the note keeps its note-off from coming before the user played it
+        let mut wait = false;
+        if self.settings.wait_for_input && !self.state.is_paused() && !elapsed.is_zero() {
+            if let Some(until) = self.next_user_note(midi) {
+                if elapsed >= until {
+                    elapsed = until;
+                    wait = true;
+                }
+            }
+        }
+
+        let before = self.state.time();
+        self.play(midi, output, events_tx, elapsed);
+
+        if let Some(settings) = self.settings.metronome {
+            let after = self.state.time();
+            if !self.state.is_paused() && after > before {
+                let speed = self.settings.speed_multiplier.max(0.01);
+                let mut output = output.lock().unwrap();
+                self.metronome
+                    .play(&mut output, &settings, before..after, speed);
+            }
+        }
+
+        if wait {
+            self.hold = true;
+        }
+
+        if let Some(range) = self.loop_range.clone() {
+            // The section takes precedence over looping the whole song
+            if !self.state.is_paused() && self.state.time() >= range.end {
+                self.seek(midi, &mut output.lock().unwrap(), range.start);
+                self.loops += 1;
+            }
+        } else if self.settings.looping {
+            self.play_next(midi, output, events_tx, elapsed);
+        }
+    }
+
+    /// Time left until the next note-on the user is expected to play along with
+    fn next_user_note(&self, midi: &midi_file::Midi) -> Option<Duration> {
+        use midi_file::midly::MidiMessage;
+
+        let time = self.state.time();
+        let leed_in = *self.state.leed_in();
+        let events = &midi.merged_track.events;
+        let start = events.partition_point(|event| event.timestamp + leed_in <= time);
+
+        events[start..]
+            .iter()
+            .find(|event| match event.message {
+                MidiMessage::NoteOn { key, .. } => {
+                    event.channel != 9
+                        && self.settings.is_user_played(event.track_id)
+                        && self
+                            .settings
+                            .transpose_key(event.channel, key.as_int())
+                            .map_or(false, |key| self.user_keyboard_range.contains(key))
+                }
+                _ => false,
+            })
+            .map(|event| event.timestamp + leed_in - time)
+    }
+
+    /// Jumps to `time`, counted from the start of the lead-in
+    fn seek(&mut self, midi: &midi_file::Midi, output: &mut OutputSink, time: Duration) {
+        self.state.set_time(time);
+        self.stop_crossfade(output);
+
+        // Discard all of the events till that point
+        let events = self.state.update(&midi.merged_track, Duration::ZERO);
+        std::mem::drop(events);
+        self.state.sysex_update(&midi.merged_track);
+
+        output.stop_all();
+
+        // When paused this happens on resume
+        if !self.state.is_paused() {
+            retrigger_sounding_notes(self, midi, output);
+        }
+    }
+
+    fn play(
+        &mut self,
+        midi: &midi_file::Midi,
+        output: &SharedOutput,
+        events_tx: &Sender<midi_file::MidiEvent>,
+        elapsed: Duration,
+    ) {
+        let events = self.state.update(&midi.merged_track, elapsed);
+        let sysex_events = self.state.sysex_update(&midi.merged_track);
+
+        if events.is_empty() && sysex_events.is_empty() {
+            return;
+        }
+
+        let mut output = output.lock().unwrap();
+
+        if self.settings.forward_sysex {
+            for event in sysex_events {
+                output.sysex(&event.data);
+            }
+        }
+
+        // Events are collected once per tick, delaying each one by its offset within the
+        // tick keeps their original spacing at the cost of one tick of latency
+        let tick_start = if self.state.is_paused() {
+            self.state.time()
+        } else {
+            self.state.time().saturating_sub(elapsed)
+        };
+        let speed = self.settings.speed_multiplier.max(0.01);
+        let progress = self.next.is_some().then(|| self.crossfade_progress());
+
+        for event in events {
+            let event = match self.settings.apply(event) {
+                Some(event) => event,
+                None => continue,
+            };
+
+            if self.settings.should_forward(&event) {
+                let offset = (event.timestamp + *self.state.leed_in()).saturating_sub(tick_start);
+                let delay = offset.div_f32(speed);
+                match progress {
+                    Some(progress) => output
+                        .midi_event_delayed(&self.crossfade.ending_event(&event, progress), delay),
+                    None => {
+                        for event in self.crossfade.event(&event) {
+                            output.midi_event_delayed(&event, delay);
+                        }
+                    }
+                }
+            }
+
+            events_tx.send(event).ok();
+        }
+    }
+
+    /// How far into the loop crossfade the end of the song is, `0.0..=1.0`
+    fn crossfade_progress(&self) -> f32 {
+        let remaining = self.state.lenght().saturating_sub(self.state.time());
+        let crossfade = self.settings.loop_crossfade.as_secs_f32();
+
+        if crossfade > 0.0 {
+            (1.0 - remaining.as_secs_f32() / crossfade).clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// Gain of each output channel, the mixer with the crossfade on top
+    fn channel_gains(&self) -> [f32; 16] {
+        let progress = self.next.is_some().then(|| self.crossfade_progress());
+        self.crossfade.channel_gains(self.mixer_gains, progress)
+    }
+
+    /// Starts the next loop once the end of the song is within the crossfade,
+    /// and hands playback over to it when the end is reached
+    fn play_next(
+        &mut self,
+        midi: &midi_file::Midi,
+        output: &SharedOutput,
+        events_tx: &Sender<midi_file::MidiEvent>,
+        elapsed: Duration,
+    ) {
+        if self.state.is_paused() {
+            return;
+        }
+
+        let track = &midi.merged_track;
+        let remaining = self.state.lenght().saturating_sub(self.state.time());
+
+        if self.next.is_none() && remaining <= self.settings.loop_crossfade {
+            let mut next = midi_file::PlaybackState::new(*self.state.leed_in(), track);
+            // Silence before the first note would leave a gap in the loop
+            next.set_time(*next.leed_in() + *next.first_note_start());
+            self.next = Some(next);
+
+            // Spare channels are faded out before they are cleared
+            let mut output = output.lock().unwrap();
+            output.set_channel_gains(self.channel_gains());
+            for event in self.crossfade.start() {
+                output.midi_event(&event);
+            }
+        }
+
+        let progress = self.crossfade_progress();
+        let gains = self.channel_gains();
+        let next = match self.next.as_mut() {
+            Some(next) => next,
+            None => return,
+        };
+
+        let mut output = output.lock().unwrap();
+        output.set_channel_gains(gains);
+
+        let events = next.update(track, elapsed);
+        let tick_start = next.time().saturating_sub(elapsed);
+        let leed_in = *next.leed_in();
+        let speed = self.settings.speed_multiplier.max(0.01);
+
+        for event in events
+            .into_iter()
+            .filter_map(|event| self.settings.apply(event))
+        {
+            if self.settings.should_forward(&event) {
+                let offset = (event.timestamp + leed_in).saturating_sub(tick_start);
+                output.midi_event_delayed(
+                    &self.crossfade.next_event(&event, progress),
+                    offset.div_f32(speed),
+                );
+            }
+
+            // Published once the next loop takes over, along with its own events
+            self.crossfade.defer(event);
+        }
+
+        if remaining.is_zero() {
+            if let Some(next) = self.next.take() {
+                self.state = next;
+                self.loops += 1;
+
+                let (controls, events) = self.crossfade.take_over();
+                for event in controls {
+                    output.midi_event(&event);
+                }
+                output.set_channel_gains(self.channel_gains());
+
+                for event in events {
+                    events_tx.send(event).ok();
+                }
+            }
+        }
+    }
+
+    /// Drops the next loop, the end of the song is heard again at full volume
+    fn stop_crossfade(&mut self, output: &mut OutputSink) {
+        self.next = None;
+        for event in self.crossfade.cancel() {
+            output.midi_event(&event);
+        }
+        output.set_channel_gains(self.channel_gains());
+    }
+}
+
+fn playback_thread(
+    playback: Arc<Mutex<Playback>>,
+    midi: Arc<midi_file::Midi>,
+    output: SharedOutput,
+    events_tx: Sender<midi_file::MidiEvent>,
+    running: Arc<AtomicBool>,
+) {
+    let mut last_time = Instant::now();
+
+    while running.load(Ordering::Relaxed) {
+        std::thread::sleep(TICK);
+
+        let delta = last_time.elapsed();
+        last_time = Instant::now();
+
+        playback
+            .lock()
+            .unwrap()
+            .tick(&midi, &output, &events_tx, delta);
+    }
+}
+
+/// Plays the file on a dedicated thread, so render stalls don't affect the music.
+///
+/// The player starts paused, call [`MidiPlayer::start`] to begin playback.
+pub struct MidiPlayer {
+    playback: Arc<Mutex<Playback>>,
+    output: SharedOutput,
+    midi_file: Arc<midi_file::Midi>,
+    play_along: PlayAlong,
+
+    events_rx: Receiver<midi_file::MidiEvent>,
+    beats_rx: Receiver<Beat>,
+    /// Beats passed until the last update
+    beats: Vec<Beat>,
+    /// Loops seen by the last update, and whether it saw a new one
+    loops: u32,
+    looped: bool,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MidiPlayer {
+    pub fn new(
+        midi_file: Arc<midi_file::Midi>,
+        output: SharedOutput,
+        settings: PlaybackSettings,
+        user_keyboard_range: piano_math::KeyboardRange,
+    ) -> Self {
+        reset_output(&mut output.lock().unwrap(), &midi_file, &settings);
+
+        let count_in = settings.metronome.map_or(0, |metronome| metronome.count_in);
+        let lead_in = LEAD_IN.max(metronome::count_in_len(&midi_file, count_in));
+
+        let mut state = midi_file::PlaybackState::new(lead_in, &midi_file.merged_track);
+        // Stays paused until `start`
+        state.pause();
+
+        let (beats_tx, beats_rx) = mpsc::channel();
+        let metronome = Metronome::new(&midi_file, lead_in, count_in, beats_tx);
+
+        let mixer_gains = settings.mixer.channel_gains(&midi_file);
+        output.lock().unwrap().set_channel_gains(mixer_gains);
+
+        let playback = Arc::new(Mutex::new(Playback {
+            state,
+            settings,
+            hold: false,
+            user_keyboard_range: user_keyboard_range.clone(),
+            next: None,
+            crossfade: LoopCrossfade::new(&midi_file),
+            mixer_gains,
+            loop_range: None,
+            metronome,
+            loops: 0,
+        }));
+
+        let (events_tx, events_rx) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+
+        let thread = std::thread::Builder::new().name("playback".into()).spawn({
+            let playback = playback.clone();
+            let midi_file = midi_file.clone();
+            let output = output.clone();
+            let running = running.clone();
+            move || playback_thread(playback, midi_file, output, events_tx, running)
+        });
+
+        let thread = match thread {
+            Ok(thread) => Some(thread),
+            Err(err) => {
+                log::error!("Failed to spawn playback thread: {err}");
+                None
+            }
+        };
+
+        Self {
+            playback,
+            output,
+            midi_file,
+            play_along: PlayAlong::new(user_keyboard_range),
+
+            events_rx,
+            beats_rx,
+            beats: Vec::new(),
+            loops: 0,
+            looped: false,
+            running,
+            thread,
+        }
+    }
+
+    fn playback(&self) -> MutexGuard<Playback> {
+        self.playback.lock().unwrap()
+    }
+
+    pub fn midi_file(&self) -> &Arc<midi_file::Midi> {
+        &self.midi_file
+    }
+
+    pub fn set_settings(&mut self, settings: PlaybackSettings) {
+        let mut playback = self.playback.lock().unwrap();
+        let transposed = playback.settings.transpose != settings.transpose;
+        let heard_changed = playback.settings.mixer.heard_changed(&settings.mixer);
+        let stopped_looping = playback.settings.looping && !settings.looping;
+        if playback.settings.mixer.volume != settings.mixer.volume {
+            playback.mixer_gains = settings.mixer.channel_gains(&self.midi_file);
+            let gains = playback.channel_gains();
+            self.output.lock().unwrap().set_channel_gains(gains);
+        }
+        playback.settings = settings;
+
+        if stopped_looping {
+            playback.stop_crossfade(&mut self.output.lock().unwrap());
+        }
+
+        // Sounding notes move to the new key, instead of hanging on the old one,
+        // and tracks taken out of the mix stop at once
+        if transposed || heard_changed {
+            let mut output = self.output.lock().unwrap();
+            output.stop_all();
+            if !playback.state.is_paused() {
+                retrigger_sounding_notes(&playback, &self.midi_file, &mut output);
+            }
+        }
+        if transposed {
+            self.play_along.clear();
+        }
+    }
+
+    /// Call it once per frame
+    ///
+    /// When playing: returns midi events played since the last call
+    ///
+    /// When paused: returns None
+    pub fn update(&mut self) -> Option<Vec<midi_file::MidiEvent>> {
+        self.play_along.update();
+
+        // Under the lock, so the playback thread can't stop at a note that isn't in `events`
+        let mut playback = self.playback.lock().unwrap();
+        let events: Vec<_> = self.events_rx.try_iter().collect();
+        self.beats = self.beats_rx.try_iter().collect();
+        self.looped = playback.loops != self.loops;
+        self.loops = playback.loops;
+
+        for event in events.iter() {
+            if event.channel == 9 || !playback.settings.is_user_played(event.track_id) {
+                continue;
+            }
+
+            use midi_file::midly::MidiMessage;
+            match event.message {
+                MidiMessage::NoteOn { key, .. } => {
+                    self.play_along
+                        .press_key(KeyPressSource::File, key.as_int(), true);
+                }
+                MidiMessage::NoteOff { key, .. } => {
+                    self.play_along
+                        .press_key(KeyPressSource::File, key.as_int(), false);
+                }
+                _ => {}
+            }
+        }
+
+        playback.hold =
+            playback.settings.wait_for_input && !self.play_along.are_required_keys_pressed();
+        let is_paused = playback.state.is_paused();
+        std::mem::drop(playback);
+
+        if is_paused {
+            None
+        } else {
+            Some(events)
+        }
+    }
+
+    /// Whether the clock is stopped until the user plays the required notes
+    pub fn is_waiting(&self) -> bool {
+        self.playback().hold
+    }
+
+    /// Beats the metronome played until the last update
+    pub fn beats(&self) -> &[Beat] {
+        &self.beats
+    }
+
+    /// Whether playback started over since the previous update, at the end of the loop range
+    /// or of the song when looping
+    pub fn looped(&self) -> bool {
+        self.looped
+    }
+}
+
+/// Puts external sound modules in a known state before the song starts
+fn reset_output(output: &mut OutputSink, midi: &midi_file::Midi, settings: &PlaybackSettings) {
+    use midi_file::midly::{num::u7, MidiMessage};
+
+    let reset = if let Some(reset) = settings.system_reset {
+        reset
+    } else {
+        return;
+    };
+
+    // First program of each channel, and the track that requested it
+    let mut programs = [(0u8, 0usize); 16];
+    if settings.forward_program_changes {
+        for event in midi.merged_track.events.iter().rev() {
+            if let MidiMessage::ProgramChange { program } = event.message {
+                programs[event.channel as usize % 16] = (program.as_int(), event.track_id);
+            }
+        }
+    }
+
+    output.sysex(reset);
+
+    for (channel, (program, track_id)) in programs.into_iter().enumerate() {
+        let event = |message| midi_file::MidiEvent {
+            channel: channel as u8,
+            delta: 0,
+            timestamp: Duration::ZERO,
+            message,
+            track_id,
+            track_color_id: 0,
+        };
+
+        output.midi_event(&event(MidiMessage::Controller {
+            controller: u7::new(7),
+            value: u7::new(100),
+        }));
+        // The device keeps its own instruments when the file's programs aren't forwarded
+        if settings.forward_program_changes {
+            output.midi_event(&event(MidiMessage::ProgramChange {
+                program: u7::new(program),
+            }));
+        }
+    }
+}
+
+/// Sends note-on for every note that should be sounding at the current time
+fn retrigger_sounding_notes(playback: &Playback, midi: &midi_file::Midi, output: &mut OutputSink) {
+    use midi_file::midly::{num::u7, MidiMessage};
+
+    let state = &playback.state;
+    let time = state.time().saturating_sub(*state.leed_in());
+    if time.is_zero() {
+        return;
+    }
+
+    for note in midi.merged_track.notes_at(time) {
+        if !playback.settings.is_auto_played(note.track_id) {
+            continue;
+        }
+
+        let event = midi_file::MidiEvent {
+            channel: note.channel,
+            delta: 0,
+            // Same seed as the original note-on, so humanized notes sound the same
+            timestamp: note.start,
+            message: MidiMessage::NoteOn {
+                key: u7::new(note.note),
+                vel: u7::new(note.velocity),
+            },
+            track_id: note.track_id,
+            track_color_id: note.track_color_id,
+        };
+        if let Some(event) = playback.settings.apply(event) {
+            output.midi_event(&event);
+        }
+    }
+}
+
+impl Drop for MidiPlayer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+
+        let mut output = self.output.lock().unwrap();
+        output.stop_all();
+        output.set_channel_gains([1.0; 16]);
+    }
+}
+
+impl MidiPlayer {
+    pub fn start(&mut self) {
+        self.resume();
+    }
+
+    pub fn pause_resume(&mut self) {
+        if self.is_paused() {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    pub fn pause(&mut self) {
+        let mut playback = self.playback.lock().unwrap();
+        playback.state.pause();
+        self.output.lock().unwrap().fade_out();
+    }
+
+    pub fn resume(&mut self) {
+        let mut playback = self.playback.lock().unwrap();
+        playback.state.resume();
+
+        let mut output = self.output.lock().unwrap();
+        output.fade_in();
+        retrigger_sounding_notes(&playback, &self.midi_file, &mut output);
+    }
+
+    /// Jumps to `time`, counted from the start of the lead-in
+    pub fn set_time(&mut self, time: Duration) {
+        let mut playback = self.playback.lock().unwrap();
+        playback.seek(&self.midi_file, &mut self.output.lock().unwrap(), time);
+
+        // Events from before the jump are stale
+        self.events_rx.try_iter().for_each(drop);
+        self.beats_rx.try_iter().for_each(drop);
+    }
+
+    /// Plays `range` over and over, jumping back to its start once its end is reached.
+    /// Counted from the start of the lead-in like [`Self::time`], `None` plays the whole song
+    pub fn set_loop(&mut self, range: Option<Range<Duration>>) {
+        let range = range.filter(|range| range.start < range.end);
+        let mut playback = self.playback.lock().unwrap();
+        playback.stop_crossfade(&mut self.output.lock().unwrap());
+        playback.loop_range = range;
+    }
+
+    pub fn loop_range(&self) -> Option<Range<Duration>> {
+        self.playback().loop_range.clone()
+    }
+
+    pub fn rewind(&mut self, delta: i64) {
+        let mut time = self.time();
+
+        if delta < 0 {
+            let delta = Duration::from_millis((-delta) as u64);
+            time = time.saturating_sub(delta);
+        } else {
+            let delta = Duration::from_millis(delta as u64);
+            time = time.saturating_add(delta);
+        }
+
+        self.set_time(time);
+    }
+
+    pub fn set_percentage_time(&mut self, p: f32) {
+        let lenght = self.playback().state.lenght();
+        self.set_time(Duration::from_secs_f32((p * lenght.as_secs_f32()).max(0.0)));
+    }
+
+    /// Current time, counted from the start of the lead-in
+    pub fn time(&self) -> Duration {
+        self.playback().state.time()
+    }
+
+    pub fn percentage(&self) -> f32 {
+        self.playback().state.percentage()
+    }
+
+    /// Length of the song with the lead-in
+    pub fn lenght(&self) -> Duration {
+        self.playback().state.lenght()
+    }
+
+    pub fn time_without_lead_in(&self) -> f32 {
+        let playback = self.playback();
+        playback.state.time().as_secs_f32() - playback.state.leed_in().as_secs_f32()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.playback().state.is_paused()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.percentage() >= 1.0
+    }
+}
+
+impl MidiPlayer {
+    pub fn play_along(&self) -> &PlayAlong {
+        &self.play_along
+    }
+
+    pub fn play_along_mut(&mut self) -> &mut PlayAlong {
+        &mut self.play_along
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::DummyOutput;
+
+    impl Playback {
+        /// Playback from the start, without a lead-in
+        fn for_test(midi: &midi_file::Midi, settings: PlaybackSettings) -> Self {
+            Self {
+                state: midi_file::PlaybackState::new(Duration::ZERO, &midi.merged_track),
+                settings,
+                hold: false,
+                user_keyboard_range: piano_math::KeyboardRange::standard_88_keys(),
+                next: None,
+                crossfade: LoopCrossfade::new(midi),
+                mixer_gains: [1.0; 16],
+                loop_range: None,
+                metronome: Metronome::new(midi, Duration::ZERO, 0, mpsc::channel().0),
+                loops: 0,
+            }
+        }
+    }
+
+    #[test]
+    fn loop_range_jumps_back() {
+        let midi = midi_file::stress::black_midi(1, 64);
+        let output: SharedOutput = Arc::new(Mutex::new(OutputSink::new(Box::new(DummyOutput))));
+        let (events_tx, _events_rx) = mpsc::channel();
+
+        let range = Duration::from_millis(200)..Duration::from_millis(400);
+        let mut playback = Playback::for_test(&midi, PlaybackSettings::default());
+        playback.loop_range = Some(range.clone());
+        playback.state.set_time(range.start);
+
+        for _ in 0..100 {
+            playback.tick(&midi, &output, &events_tx, Duration::from_millis(10));
+            assert!(range.contains(&playback.state.time()));
+        }
+        // 1s of playback through a 200ms range
+        assert_eq!(playback.loops, 5);
+    }
+
+    #[test]
+    fn speed_scales_time_exactly() {
+        let midi = midi_file::stress::black_midi(1, 64);
+        let output: SharedOutput = Arc::new(Mutex::new(OutputSink::new(Box::new(DummyOutput))));
+        let (events_tx, _events_rx) = mpsc::channel();
+
+        let settings = PlaybackSettings {
+            speed_multiplier: 1.35,
+            ..Default::default()
+        };
+        let mut playback = Playback::for_test(&midi, settings);
+
+        for _ in 0..100 {
+            playback.tick(&midi, &output, &events_tx, Duration::from_micros(1003));
+        }
+
+        let expected = Duration::from_micros(100_300).mul_f64(1.35);
+        let error = playback.state.time().abs_diff(expected);
+        assert!(error < Duration::from_micros(10), "{error:?}");
+    }
+
+    #[test]
+    fn wait_for_input_stops_at_notes() {
+        let midi = midi_file::stress::black_midi(1, 64);
+        let output: SharedOutput = Arc::new(Mutex::new(OutputSink::new(Box::new(DummyOutput))));
+        let (events_tx, events_rx) = mpsc::channel();
+
+        let lead_in = Duration::from_secs(1);
+        let settings = PlaybackSettings {
+            wait_for_input: true,
+            ..Default::default()
+        };
+        let mut playback = Playback::for_test(&midi, settings);
+        playback.state = midi_file::PlaybackState::new(lead_in, &midi.merged_track);
+        playback.metronome = Metronome::new(&midi, lead_in, 0, mpsc::channel().0);
+
+        for _ in 0..200 {
+            playback.tick(&midi, &output, &events_tx, Duration::from_millis(10));
+        }
+        assert!(playback.hold);
+        assert_eq!(playback.state.time(), lead_in);
+        assert_eq!(events_rx.try_iter().count(), 1);
+
+        // User played it, on to the note-on that follows the note-off
+        let next = midi.merged_track.notes[1].start + lead_in;
+        playback.hold = false;
+        for _ in 0..200 {
+            playback.tick(&midi, &output, &events_tx, Duration::from_millis(10));
+        }
+        assert!(playback.hold);
+        assert_eq!(playback.state.time(), next);
+    }
+
+    #[test]
+    fn user_tracks_are_not_auto_played() {
+        let settings = PlaybackSettings {
+            silent_tracks: HashSet::from([2]),
+            user_tracks: HashSet::from([1]),
+            ..Default::default()
+        };
+
+        // Played for the user
+        assert!(settings.is_auto_played(0));
+        assert!(!settings.is_user_played(0));
+        // Played by the user
+        assert!(!settings.is_auto_played(1));
+        assert!(settings.is_user_played(1));
+        // Muted
+        assert!(!settings.is_auto_played(2));
+        assert!(!settings.is_user_played(2));
+
+        // Without user tracks the user plays along with every audible one
+        let settings = PlaybackSettings {
+            silent_tracks: HashSet::from([2]),
+            ..Default::default()
+        };
+        assert!(settings.is_auto_played(0) && settings.is_user_played(0));
+        assert!(!settings.is_user_played(2));
+
+        // Muted in the mixer, still played along with
+        let settings = PlaybackSettings {
+            mixer: TrackMixer {
+                muted: HashSet::from([0]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(!settings.is_auto_played(0) && settings.is_user_played(0));
+    }
+
+    #[test]
+    fn transpose_moves_notes_but_not_drums() {
+        use midi_file::midly::{num::u7, MidiMessage};
+
+        let settings = PlaybackSettings {
+            transpose: -3,
+            ..Default::default()
+        };
+        let note_on = |channel, key| midi_file::MidiEvent {
+            channel,
+            delta: 0,
+            timestamp: Duration::ZERO,
+            message: MidiMessage::NoteOn {
+                key: u7::new(key),
+                vel: u7::new(100),
+            },
+            track_id: 0,
+            track_color_id: 0,
+        };
+        let key = |event: Option<midi_file::MidiEvent>| match event.map(|event| event.message) {
+            Some(MidiMessage::NoteOn { key, .. }) => Some(key.as_int()),
+            _ => None,
+        };
+
+        assert_eq!(key(settings.apply(note_on(0, 60))), Some(57));
+        assert_eq!(key(settings.apply(note_on(9, 36))), Some(36));
+        // Out of MIDI range
+        assert_eq!(key(settings.apply(note_on(0, 1))), None);
+    }
+
+    #[test]
+    fn track_velocity_scales_note_ons() {
+        use midi_file::midly::{num::u7, MidiMessage};
+
+        let settings = PlaybackSettings {
+            track_velocity: HashMap::from([(1, 1.5), (2, 0.5)]),
+            ..Default::default()
+        };
+        let note_on = |track_id, vel| midi_file::MidiEvent {
+            channel: 0,
+            delta: 0,
+            timestamp: Duration::ZERO,
+            message: MidiMessage::NoteOn {
+                key: u7::new(60),
+                vel: u7::new(vel),
+            },
+            track_id,
+            track_color_id: 0,
+        };
+        let vel = |event: Option<midi_file::MidiEvent>| match event.map(|event| event.message) {
+            Some(MidiMessage::NoteOn { vel, .. }) => Some(vel.as_int()),
+            _ => None,
+        };
+
+        assert_eq!(vel(settings.apply(note_on(0, 100))), Some(100));
+        assert_eq!(vel(settings.apply(note_on(1, 100))), Some(127));
+        assert_eq!(vel(settings.apply(note_on(2, 100))), Some(50));
+        // Still a note-off
+        assert_eq!(vel(settings.apply(note_on(2, 0))), Some(0));
+    }
+
+    #[test]
+    fn reset_keeps_programs_unless_forwarded() {
+        use midi_file::midly::MidiMessage;
+
+        struct Log(Arc<Mutex<Vec<MidiMessage>>>);
+        impl crate::output::OutputConnection for Log {
+            fn midi_event(&mut self, msg: &midi_file::MidiEvent) {
+                self.0.lock().unwrap().push(msg.message);
+            }
+        }
+
+        let midi = midi_file::stress::black_midi(1, 64);
+        let program_changes = |forward_program_changes| {
+            let log = Arc::new(Mutex::new(Vec::new()));
+            let mut output = OutputSink::new(Box::new(Log(log.clone())));
+            let settings = PlaybackSettings {
+                system_reset: Some(&[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]),
+                forward_program_changes,
+                ..Default::default()
+            };
+            reset_output(&mut output, &midi, &settings);
+
+            let log = log.lock().unwrap();
+            log.iter()
+                .filter(|message| matches!(message, MidiMessage::ProgramChange { .. }))
+                .count()
+        };
+
+        assert_eq!(program_changes(true), 16);
+        assert_eq!(program_changes(false), 0);
+    }
+}
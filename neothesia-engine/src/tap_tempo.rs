@@ -0,0 +1,84 @@
+use std::{collections::VecDeque, time::Duration};
+
+/// Taps further apart start a new tempo
+const MAX_GAP: Duration = Duration::from_secs(2);
+/// Taps the tempo is averaged over
+const TAPS: usize = 8;
+
+/// Tempo of a key tapped in time, averaged over the last few taps
+#[derive(Debug, Default, Clone)]
+pub struct TapTempo {
+    taps: VecDeque<Duration>,
+}
+
+impl TapTempo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a tap at `time` on any steady clock, returns the tempo in beats per minute
+    /// once there are two taps
+    pub fn tap(&mut self, time: Duration) -> Option<f32> {
+        if let Some(last) = self.taps.back() {
+            if time <= *last || time - *last > MAX_GAP {
+                self.taps.clear();
+            }
+        }
+
+        self.taps.push_back(time);
+        if self.taps.len() > TAPS {
+            self.taps.pop_front();
+        }
+
+        self.bpm()
+    }
+
+    /// Tempo of the taps so far, `None` before the second one
+    pub fn bpm(&self) -> Option<f32> {
+        let first = self.taps.front()?;
+        let last = self.taps.back()?;
+        let gaps = self.taps.len().checked_sub(1).filter(|gaps| *gaps > 0)?;
+
+        let beat = (*last - *first).as_secs_f32() / gaps as f32;
+        Some(60.0 / beat)
+    }
+
+    /// Time of the last tap, if its tempo is still going
+    pub fn last_tap(&self) -> Option<Duration> {
+        self.taps.back().copied()
+    }
+
+    pub fn clear(&mut self) {
+        self.taps.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_taps() {
+        let ms = Duration::from_millis;
+        let mut tap = TapTempo::new();
+
+        assert_eq!(tap.tap(ms(1000)), None);
+        assert_eq!(tap.tap(ms(1500)), Some(120.0));
+        // Uneven taps are averaged
+        assert_eq!(tap.tap(ms(2100)), Some(60.0 / 0.55));
+
+        // A long pause starts over
+        assert_eq!(tap.tap(ms(5000)), None);
+        assert_eq!(tap.tap(ms(6000)), Some(60.0));
+
+        // Only the last few taps count
+        let mut tap = TapTempo::new();
+        for id in 0..4 {
+            tap.tap(ms(id * 1000));
+        }
+        for id in 1..=8 {
+            tap.tap(ms(3000 + id * 500));
+        }
+        assert_eq!(tap.bpm(), Some(120.0));
+    }
+}
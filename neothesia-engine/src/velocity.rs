@@ -0,0 +1,114 @@
+use midi_file::midly::{num::u7, MidiMessage};
+
+/// Reshapes file velocities before they reach the output,
+/// many files use a single velocity for every note
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VelocityCurve {
+    #[default]
+    Unchanged,
+    /// Quieter, with a narrower range
+    Soften,
+    /// Pushes velocities away from the middle
+    DynamicExpand,
+    /// Small per note variation, stable across replays
+    Humanize,
+}
+
+impl VelocityCurve {
+    pub const ALL: [VelocityCurve; 4] = [
+        Self::Unchanged,
+        Self::Soften,
+        Self::DynamicExpand,
+        Self::Humanize,
+    ];
+
+    /// Reshaped velocity of a note-on, `0` stays `0` as it means note-off
+    pub fn velocity(&self, vel: u8, key: u8, seed: u64) -> u8 {
+        if vel == 0 {
+            return 0;
+        }
+
+        let vel = vel.min(127) as f32;
+        let out = match self {
+            Self::Unchanged => vel,
+            Self::Soften => 20.0 + vel * 0.6,
+            Self::DynamicExpand => 64.0 + (vel - 64.0) * 1.5,
+            Self::Humanize => {
+                let jitter = (hash(seed ^ ((key as u64) << 56)) % 25) as f32 - 12.0;
+                vel + jitter
+            }
+        };
+
+        out.round().clamp(1.0, 127.0) as u8
+    }
+
+    /// Applies the curve to note-on events, other events are returned unchanged
+    pub fn apply(&self, mut event: midi_file::MidiEvent) -> midi_file::MidiEvent {
+        if *self == Self::Unchanged {
+            return event;
+        }
+
+        if let MidiMessage::NoteOn { key, vel } = event.message {
+            let seed = event.timestamp.as_micros() as u64;
+            let vel = self.velocity(vel.as_int(), key.as_int(), seed);
+            event.message = MidiMessage::NoteOn {
+                key,
+                vel: u7::new(vel),
+            };
+        }
+
+        event
+    }
+}
+
+impl std::fmt::Display for VelocityCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unchanged => write!(f, "Unchanged"),
+            Self::Soften => write!(f, "Soften"),
+            Self::DynamicExpand => write!(f, "Dynamic Expand"),
+            Self::Humanize => write!(f, "Humanize"),
+        }
+    }
+}
+
+/// splitmix64
+fn hash(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn note_off_velocity_is_kept() {
+        for curve in VelocityCurve::ALL {
+            assert_eq!(curve.velocity(0, 60, 0), 0);
+        }
+    }
+
+    #[test]
+    fn curves() {
+        assert_eq!(VelocityCurve::Unchanged.velocity(100, 60, 0), 100);
+        assert_eq!(VelocityCurve::Soften.velocity(100, 60, 0), 80);
+        assert_eq!(VelocityCurve::DynamicExpand.velocity(100, 60, 0), 118);
+        assert_eq!(VelocityCurve::DynamicExpand.velocity(20, 60, 0), 1);
+    }
+
+    #[test]
+    fn humanize_is_stable_and_bounded() {
+        let curve = VelocityCurve::Humanize;
+
+        let velocities: Vec<u8> = (0..100).map(|t| curve.velocity(100, 60, t)).collect();
+        assert!(velocities.iter().all(|v| (88..=112).contains(v)));
+        assert!(velocities.iter().any(|v| *v != 100));
+
+        let again: Vec<u8> = (0..100).map(|t| curve.velocity(100, 60, t)).collect();
+        assert_eq!(velocities, again);
+    }
+}
@@ -14,6 +14,11 @@ pub struct BgPipeline {
 
 impl<'a> BgPipeline {
     pub fn new(gpu: &Gpu) -> Self {
+        Self::with_sample_count(gpu, 1)
+    }
+
+    /// For multisampled render targets
+    pub fn with_sample_count(gpu: &Gpu, sample_count: u32) -> Self {
         let shader = gpu
             .device
             .create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -43,6 +48,7 @@ impl<'a> BgPipeline {
             RenderPipelineBuilder::new(render_pipeline_layout, "vs_main", &shader)
                 .fragment("fs_main", &shader, &[Some(target)])
                 .vertex_buffers(&[Shape::layout()])
+                .sample_count(sample_count)
                 .build(&gpu.device);
 
         let fullscreen_quad = Shape::new_fullscreen_quad(&gpu.device);
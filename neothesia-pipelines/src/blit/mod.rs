@@ -0,0 +1,164 @@
+use wgpu_jumpstart::{wgpu, Gpu, RenderPipelineBuilder, Shape, Uniform};
+
+use bytemuck::{Pod, Zeroable};
+
+/// Stretches a texture over the whole render target
+pub struct BlitPipeline {
+    render_pipeline: wgpu::RenderPipeline,
+    brightness_uniform: Uniform<BrightnessUniform>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: Option<wgpu::BindGroup>,
+    sampler: wgpu::Sampler,
+
+    fullscreen_quad: Shape,
+}
+
+impl<'a> BlitPipeline {
+    pub fn new(gpu: &Gpu, filter: wgpu::FilterMode) -> Self {
+        Self::with_format(gpu, filter, gpu.texture_format)
+    }
+
+    /// For render targets in a different format than the rest of the pipelines use
+    pub fn with_format(
+        gpu: &Gpu,
+        filter: wgpu::FilterMode,
+        texture_format: wgpu::TextureFormat,
+    ) -> Self {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("BlitPipeline::shader"),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+                    "./shader.wgsl"
+                ))),
+            });
+
+        let brightness_uniform = Uniform::new(
+            &gpu.device,
+            BrightnessUniform::default(),
+            wgpu::ShaderStages::FRAGMENT,
+        );
+
+        let sampler = gpu.device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: filter,
+            min_filter: filter,
+            ..Default::default()
+        });
+
+        let bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                    ],
+                });
+
+        let render_pipeline_layout =
+            gpu.device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[
+                        &brightness_uniform.bind_group_layout,
+                        &bind_group_layout,
+                    ],
+                    push_constant_ranges: &[],
+                });
+
+        // The source covers everything underneath, no need to blend
+        let target = wgpu::ColorTargetState {
+            format: texture_format,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+        };
+
+        let render_pipeline =
+            RenderPipelineBuilder::new(&render_pipeline_layout, "vs_main", &shader)
+                .fragment("fs_main", &shader, &[Some(target)])
+                .vertex_buffers(&[Shape::layout()])
+                .build(&gpu.device);
+
+        let fullscreen_quad = Shape::new_fullscreen_quad(&gpu.device);
+
+        Self {
+            render_pipeline,
+            brightness_uniform,
+            bind_group_layout,
+            bind_group: None,
+            sampler,
+
+            fullscreen_quad,
+        }
+    }
+
+    /// Has to be called again whenever the source texture gets recreated
+    pub fn set_source(&mut self, device: &wgpu::Device, view: &wgpu::TextureView) {
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        }));
+    }
+
+    /// Multiplier of the linear source colors, `1.0` copies them as they are
+    pub fn set_brightness(&mut self, queue: &wgpu::Queue, brightness: f32) {
+        self.brightness_uniform.data.brightness = brightness;
+        self.brightness_uniform.update(queue);
+    }
+
+    pub fn render(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        let bind_group = match self.bind_group.as_ref() {
+            Some(bind_group) => bind_group,
+            None => return,
+        };
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.brightness_uniform.bind_group, &[]);
+        render_pass.set_bind_group(1, bind_group, &[]);
+
+        render_pass.set_vertex_buffer(0, self.fullscreen_quad.vertex_buffer.slice(..));
+
+        render_pass.set_index_buffer(
+            self.fullscreen_quad.index_buffer.slice(..),
+            wgpu::IndexFormat::Uint16,
+        );
+
+        render_pass.draw_indexed(0..self.fullscreen_quad.indices_len, 0, 0..1);
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct BrightnessUniform {
+    brightness: f32,
+}
+
+impl Default for BrightnessUniform {
+    fn default() -> Self {
+        Self { brightness: 1.0 }
+    }
+}
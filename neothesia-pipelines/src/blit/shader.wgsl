@@ -0,0 +1,35 @@
+struct BrightnessUniform {
+    brightness: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> brightness_uniform: BrightnessUniform;
+
+@group(1) @binding(0)
+var source_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var source_sampler: sampler;
+
+struct Vertex {
+    @location(0) position: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(vertex.position, 0.0, 1.0);
+    // Texture y axis points down
+    out.uv = vec2<f32>(vertex.position.x + 1.0, 1.0 - vertex.position.y) / 2.0;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(source_texture, source_sampler, in.uv);
+    return vec4<f32>(color.rgb * brightness_uniform.brightness, color.a);
+}
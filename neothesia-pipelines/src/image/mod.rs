@@ -0,0 +1,242 @@
+use wgpu_jumpstart::{
+    wgpu, Gpu, Instances, RenderPipelineBuilder, Shape, TransformUniform, Uniform,
+};
+
+use bytemuck::{Pod, Zeroable};
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, Pod, Zeroable)]
+pub struct ImageInstance {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub opacity: f32,
+}
+
+impl ImageInstance {
+    fn attributes() -> [wgpu::VertexAttribute; 3] {
+        wgpu::vertex_attr_array!(1 => Float32x2, 2 => Float32x2, 3 => Float32)
+    }
+
+    fn layout(attributes: &[wgpu::VertexAttribute]) -> wgpu::VertexBufferLayout {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ImageInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes,
+        }
+    }
+}
+
+/// Draws a single RGBA image
+pub struct ImagePipeline {
+    render_pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+
+    quad: Shape,
+    instances: Instances<ImageInstance>,
+
+    texture: wgpu::Texture,
+    image_size: (u32, u32),
+}
+
+impl<'a> ImagePipeline {
+    /// `rgba` are the sRGB pixels of a `width` x `height` image
+    pub fn new(
+        gpu: &Gpu,
+        transform_uniform: &Uniform<TransformUniform>,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self::with_sample_count(gpu, transform_uniform, rgba, width, height, 1)
+    }
+
+    /// For multisampled render targets
+    pub fn with_sample_count(
+        gpu: &Gpu,
+        transform_uniform: &Uniform<TransformUniform>,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Self {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("ImagePipeline::shader"),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+                    "./shader.wgsl"
+                ))),
+            });
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("ImagePipeline::texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        write_pixels(&gpu.queue, &texture, rgba, width, height);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = gpu.device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let texture_bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                    ],
+                });
+
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let render_pipeline_layout =
+            gpu.device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[
+                        &transform_uniform.bind_group_layout,
+                        &texture_bind_group_layout,
+                    ],
+                    push_constant_ranges: &[],
+                });
+
+        let attrs = ImageInstance::attributes();
+
+        let target = wgpu_jumpstart::default_color_target_state(gpu.texture_format);
+
+        let render_pipeline =
+            RenderPipelineBuilder::new(&render_pipeline_layout, "vs_main", &shader)
+                .fragment("fs_main", &shader, &[Some(target)])
+                .vertex_buffers(&[Shape::layout(), ImageInstance::layout(&attrs)])
+                .sample_count(sample_count)
+                .build(&gpu.device);
+
+        let quad = Shape::new_quad(&gpu.device);
+        let instances = Instances::new(&gpu.device, 1);
+
+        Self {
+            render_pipeline,
+            bind_group,
+
+            quad,
+            instances,
+
+            texture,
+            image_size: (width, height),
+        }
+    }
+
+    pub fn image_size(&self) -> (u32, u32) {
+        self.image_size
+    }
+
+    /// Replaces the pixels, eg. with the next frame of an animation, the size stays the same
+    pub fn set_pixels(&self, queue: &wgpu::Queue, rgba: &[u8]) {
+        let (width, height) = self.image_size;
+        write_pixels(queue, &self.texture, rgba, width, height);
+    }
+
+    pub fn set_rect(
+        &mut self,
+        queue: &wgpu::Queue,
+        position: [f32; 2],
+        size: [f32; 2],
+        opacity: f32,
+    ) {
+        self.instances.data = vec![ImageInstance {
+            position,
+            size,
+            opacity,
+        }];
+        self.instances.update(queue);
+    }
+
+    pub fn render(
+        &'a self,
+        transform_uniform: &'a Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &transform_uniform.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+
+        render_pass.set_vertex_buffer(0, self.quad.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instances.buffer.slice(..));
+
+        render_pass.set_index_buffer(self.quad.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        render_pass.draw_indexed(0..self.quad.indices_len, 0, 0..self.instances.len());
+    }
+}
+
+fn write_pixels(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+) {
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        rgba,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * width),
+            rows_per_image: Some(height),
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+}
@@ -0,0 +1,51 @@
+struct ViewUniform {
+    transform: mat4x4<f32>,
+    size: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> view_uniform: ViewUniform;
+
+@group(1) @binding(0)
+var image_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var image_sampler: sampler;
+
+struct Vertex {
+    @location(0) position: vec2<f32>,
+}
+
+struct ImageInstance {
+    @location(1) i_position: vec2<f32>,
+    @location(2) size: vec2<f32>,
+    @location(3) opacity: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) opacity: f32,
+}
+
+@vertex
+fn vs_main(vertex: Vertex, image: ImageInstance) -> VertexOutput {
+    let i_transform: mat4x4<f32> = mat4x4<f32>(
+        vec4<f32>(image.size.x, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, image.size.y, 0.0, 0.0),
+        vec4<f32>(0.0, 0.0, 1.0, 0.0),
+        vec4<f32>(image.i_position, 0.0, 1.0)
+    );
+
+    var out: VertexOutput;
+    out.position = view_uniform.transform * i_transform * vec4<f32>(vertex.position, 0.0, 1.0);
+    out.uv = vertex.position;
+    out.opacity = image.opacity;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(image_texture, image_sampler, in.uv);
+    return vec4<f32>(color.rgb, color.a * in.opacity);
+}
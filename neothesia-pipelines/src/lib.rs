@@ -1,3 +1,6 @@
 pub mod background_animation;
+pub mod blit;
+pub mod image;
+pub mod particles;
 pub mod quad;
 pub mod waterfall;
@@ -0,0 +1,42 @@
+use wgpu_jumpstart::wgpu;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::vertex_attr_array;
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, Pod, Zeroable)]
+pub struct ParticleInstance {
+    /// Where the particle was spawned
+    pub origin: [f32; 2],
+    /// Logical pixels per second, gravity pulls it down over time
+    pub velocity: [f32; 2],
+    /// Alpha is the brightness at spawn, it fades out over the lifetime
+    pub color: [f32; 4],
+    /// Diameter, in logical pixels
+    pub size: f32,
+    /// Time of the spawn, on the clock of [`super::ParticlePipeline::update_time`]
+    pub spawn: f32,
+    /// Seconds
+    pub lifetime: f32,
+}
+
+impl ParticleInstance {
+    pub fn attributes() -> [wgpu::VertexAttribute; 6] {
+        vertex_attr_array!(
+            1 => Float32x2,
+            2 => Float32x2,
+            3 => Float32x4,
+            4 => Float32,
+            5 => Float32,
+            6 => Float32
+        )
+    }
+
+    pub fn layout(attributes: &[wgpu::VertexAttribute]) -> wgpu::VertexBufferLayout {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ParticleInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes,
+        }
+    }
+}
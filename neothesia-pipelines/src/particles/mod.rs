@@ -0,0 +1,122 @@
+mod instance_data;
+
+pub use instance_data::ParticleInstance;
+
+use wgpu_jumpstart::{
+    wgpu, Gpu, Instances, RenderPipelineBuilder, Shape, TransformUniform, Uniform,
+};
+
+use bytemuck::{Pod, Zeroable};
+
+/// Particles move on the GPU, they are only uploaded when spawned
+pub struct ParticlePipeline {
+    render_pipeline: wgpu::RenderPipeline,
+
+    quad: Shape,
+
+    instances: Instances<ParticleInstance>,
+    time_uniform: Uniform<TimeUniform>,
+}
+
+impl<'a> ParticlePipeline {
+    pub fn new(
+        gpu: &Gpu,
+        transform_uniform: &Uniform<TransformUniform>,
+        max_particles: usize,
+        sample_count: u32,
+    ) -> Self {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("ParticlePipeline::shader"),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+                    "./shader.wgsl"
+                ))),
+            });
+
+        let time_uniform = Uniform::new(
+            &gpu.device,
+            TimeUniform::default(),
+            wgpu::ShaderStages::VERTEX,
+        );
+
+        let render_pipeline_layout =
+            &gpu.device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[
+                        &transform_uniform.bind_group_layout,
+                        &time_uniform.bind_group_layout,
+                    ],
+                    push_constant_ranges: &[],
+                });
+
+        let attrs = ParticleInstance::attributes();
+
+        let target = wgpu_jumpstart::default_color_target_state(gpu.texture_format);
+
+        let render_pipeline =
+            RenderPipelineBuilder::new(render_pipeline_layout, "vs_main", &shader)
+                .fragment("fs_main", &shader, &[Some(target)])
+                .vertex_buffers(&[Shape::layout(), ParticleInstance::layout(&attrs)])
+                .sample_count(sample_count)
+                .build(&gpu.device);
+
+        let quad = Shape::new_quad(&gpu.device);
+
+        let instances = Instances::new(&gpu.device, max_particles);
+
+        Self {
+            render_pipeline,
+
+            quad,
+
+            instances,
+
+            time_uniform,
+        }
+    }
+
+    pub fn render(
+        &'a self,
+        transform_uniform: &'a Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &transform_uniform.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.time_uniform.bind_group, &[]);
+
+        render_pass.set_vertex_buffer(0, self.quad.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instances.buffer.slice(..));
+
+        render_pass.set_index_buffer(self.quad.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        render_pass.draw_indexed(0..self.quad.indices_len, 0, 0..self.instances.len());
+    }
+
+    pub fn update_instance_buffer(
+        &mut self,
+        queue: &wgpu::Queue,
+        instances: Vec<ParticleInstance>,
+    ) {
+        self.instances.data = instances;
+        self.instances.update(queue);
+    }
+
+    pub fn update_time(&mut self, queue: &wgpu::Queue, time: f32) {
+        self.time_uniform.data.time = time;
+        self.time_uniform.update(queue);
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct TimeUniform {
+    time: f32,
+}
+
+impl Default for TimeUniform {
+    fn default() -> Self {
+        Self { time: 0.0 }
+    }
+}
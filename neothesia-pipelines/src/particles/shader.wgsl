@@ -0,0 +1,81 @@
+struct ViewUniform {
+    transform: mat4x4<f32>,
+    size: vec2<f32>,
+    scale: f32,
+    offset: vec2<f32>,
+}
+
+struct TimeUniform {
+    time: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> view_uniform: ViewUniform;
+
+@group(1) @binding(0)
+var<uniform> time_uniform: TimeUniform;
+
+struct Vertex {
+    @location(0) position: vec2<f32>,
+}
+
+struct ParticleInstance {
+    @location(1) origin: vec2<f32>,
+    @location(2) velocity: vec2<f32>,
+    @location(3) color: vec4<f32>,
+    @location(4) size: f32,
+    @location(5) spawn: f32,
+    @location(6) lifetime: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+// Logical pixels per second squared, pointing down
+const gravity: f32 = 900.0;
+
+@vertex
+fn vs_main(vertex: Vertex, particle: ParticleInstance) -> VertexOutput {
+    let t = time_uniform.time - particle.spawn;
+    let life = t / particle.lifetime;
+
+    // Dead and not yet spawned particles shrink to nothing
+    var size = particle.size;
+    if (life < 0.0 || life > 1.0) {
+        size = 0.0;
+    }
+
+    let center = particle.origin
+        + particle.velocity * t
+        + vec2<f32>(0.0, 0.5 * gravity * t * t);
+
+    let transform = mat4x4<f32>(
+        vec4<f32>(size, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, size, 0.0, 0.0),
+        vec4<f32>(0.0, 0.0, 1.0, 0.0),
+        vec4<f32>(center - vec2<f32>(size, size) / 2.0, 0.0, 1.0)
+    );
+
+    var out: VertexOutput;
+    out.position = view_uniform.transform * transform * vec4<f32>(vertex.position, 0.0, 1.0);
+    out.uv = vertex.position * 2.0 - 1.0;
+    out.color = vec4<f32>(particle.color.rgb, particle.color.a * (1.0 - clamp(life, 0.0, 1.0)));
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Bright core with a soft edge
+    let falloff = 1.0 - smoothstep(0.0, 1.0, length(in.uv));
+    let alpha = falloff * falloff * in.color.a;
+
+    let core = 1.0 - smoothstep(0.0, 0.35, length(in.uv));
+    let color = mix(in.color.rgb, vec3<f32>(1.0, 1.0, 1.0), core * 0.6);
+
+    return vec4<f32>(color, alpha);
+}
@@ -13,6 +13,15 @@ pub struct QuadPipeline {
 
 impl<'a> QuadPipeline {
     pub fn new(gpu: &Gpu, transform_uniform: &Uniform<TransformUniform>) -> Self {
+        Self::with_sample_count(gpu, transform_uniform, 1)
+    }
+
+    /// For multisampled render targets
+    pub fn with_sample_count(
+        gpu: &Gpu,
+        transform_uniform: &Uniform<TransformUniform>,
+        sample_count: u32,
+    ) -> Self {
         let shader = gpu
             .device
             .create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -38,6 +47,7 @@ impl<'a> QuadPipeline {
             RenderPipelineBuilder::new(&render_pipeline_layout, "vs_main", &shader)
                 .fragment("fs_main", &shader, &[Some(target)])
                 .vertex_buffers(&[Shape::layout(), QuadInstance::layout(&ri_attrs)])
+                .sample_count(sample_count)
                 .build(&gpu.device);
 
         let quad = Shape::new_quad(&gpu.device);
@@ -10,11 +10,26 @@ pub struct NoteInstance {
     pub size: [f32; 2],
     pub color: [f32; 3],
     pub radius: f32,
+    pub border_color: [f32; 3],
+    pub border_width: f32,
+    /// `0.0` is a flat fill, `1.0` fully darkens the tail of the note
+    pub gradient: f32,
+    /// Width of the halo around the note, `0.0` has none
+    pub glow: f32,
 }
 
 impl NoteInstance {
-    pub fn attributes() -> [wgpu::VertexAttribute; 4] {
-        vertex_attr_array!(1 => Float32x2, 2 => Float32x2, 3 => Float32x3, 4 => Float32)
+    pub fn attributes() -> [wgpu::VertexAttribute; 8] {
+        vertex_attr_array!(
+            1 => Float32x2,
+            2 => Float32x2,
+            3 => Float32x3,
+            4 => Float32,
+            5 => Float32x3,
+            6 => Float32,
+            7 => Float32,
+            8 => Float32
+        )
     }
 
     pub fn layout(attributes: &[wgpu::VertexAttribute]) -> wgpu::VertexBufferLayout {
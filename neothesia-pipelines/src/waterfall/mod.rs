@@ -22,6 +22,7 @@ impl<'a> WaterfallPipeline {
         gpu: &Gpu,
         transform_uniform: &Uniform<TransformUniform>,
         notes_count: usize,
+        sample_count: u32,
     ) -> Self {
         let shader = gpu
             .device
@@ -57,6 +58,7 @@ impl<'a> WaterfallPipeline {
             RenderPipelineBuilder::new(render_pipeline_layout, "vs_main", &shader)
                 .fragment("fs_main", &shader, &[Some(target)])
                 .vertex_buffers(&[Shape::layout(), NoteInstance::layout(&ni_attrs)])
+                .sample_count(sample_count)
                 .build(&gpu.device);
 
         let quad = Shape::new_quad(&gpu.device);
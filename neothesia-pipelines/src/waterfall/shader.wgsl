@@ -2,6 +2,7 @@ struct ViewUniform {
     transform: mat4x4<f32>,
     size: vec2<f32>,
     scale: f32,
+    offset: vec2<f32>,
 }
 
 struct TimeUniform {
@@ -23,6 +24,10 @@ struct NoteInstance {
     @location(2) size: vec2<f32>,
     @location(3) color: vec3<f32>,
     @location(4) radius: f32,
+    @location(5) border_color: vec3<f32>,
+    @location(6) border_width: f32,
+    @location(7) gradient: f32,
+    @location(8) glow: f32,
 }
 
 struct VertexOutput {
@@ -33,6 +38,10 @@ struct VertexOutput {
     @location(2) color: vec3<f32>,
     @location(3) radius: f32,
     @location(4) note_pos: vec2<f32>,
+    @location(5) border_color: vec3<f32>,
+    @location(6) border_width: f32,
+    @location(7) gradient: f32,
+    @location(8) glow: f32,
 }
 
 const speed: f32 = 400.0;
@@ -47,21 +56,27 @@ fn vs_main(vertex: Vertex, note: NoteInstance) -> VertexOutput {
 
     let offset = vec2<f32>(0.0, -(note.n_position.y - time_uniform.time) * speed);
 
+    // The quad grows to make room for the glow around the note
+    let glow = vec2<f32>(note.glow, note.glow);
     let transform = mat4x4<f32>(
-        vec4<f32>(size.x, 0.0, 0.0, 0.0),
-        vec4<f32>(0.0, size.y, 0.0, 0.0),
+        vec4<f32>(size.x + glow.x * 2.0, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, size.y + glow.y * 2.0, 0.0, 0.0),
         vec4<f32>(0.0, 0.0, 1.0, 0.0),
-        vec4<f32>(pos + offset, 0.0, 1.0)
+        vec4<f32>(pos + offset - glow, 0.0, 1.0)
     );
 
     var out: VertexOutput;
     out.position = view_uniform.transform * transform * vec4<f32>(vertex.position, 0.0, 1.0);
     out.note_pos = pos + offset;
 
-    out.src_position = vertex.position;
+    out.src_position = (vertex.position * (size + glow * 2.0) - glow) / size;
     out.size = size;
     out.color = note.color;
     out.radius = note.radius;
+    out.border_color = note.border_color;
+    out.border_width = note.border_width;
+    out.gradient = note.gradient;
+    out.glow = note.glow;
 
     return out;
 }
@@ -90,7 +105,7 @@ fn dist(
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let dist: f32 = dist(
-        in.position.xy / view_uniform.scale,
+        in.position.xy / view_uniform.scale + view_uniform.offset,
         in.note_pos,
         in.size,
         in.radius,
@@ -102,5 +117,33 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         dist,
     );
 
-    return vec4<f32>(in.color, alpha);
+    // Leading (bottom) edge keeps the full color, the tail gets darker
+    let shade: f32 = mix(1.0 - 0.5 * in.gradient, 1.0, in.src_position.y);
+    var color: vec3<f32> = in.color * shade;
+
+    if (in.border_width > 0.0) {
+        let inner_radius: f32 = max(in.radius - in.border_width, 0.0);
+        let inner_dist: f32 = dist(
+            in.position.xy / view_uniform.scale + view_uniform.offset,
+            in.note_pos + vec2<f32>(in.border_width, in.border_width),
+            in.size - vec2<f32>(in.border_width, in.border_width) * 2.0,
+            inner_radius,
+        );
+
+        let border: f32 = smoothstep(
+            max(inner_radius - 0.5, 0.0),
+            inner_radius + 0.5,
+            inner_dist,
+        );
+
+        color = mix(color, in.border_color, border);
+    }
+
+    if (in.glow > 0.0) {
+        let halo: f32 = 1.0 - smoothstep(0.0, in.glow, dist - in.radius);
+        let glow_alpha: f32 = halo * halo * 0.6;
+        return vec4<f32>(mix(in.color, color, alpha), max(alpha, glow_alpha));
+    }
+
+    return vec4<f32>(color, alpha);
 }
@@ -0,0 +1,148 @@
+//! Screen reader support through AccessKit.
+//!
+//! iced doesn't expose its widgets to AccessKit, so scenes describe what they show
+//! as a flat list of [`Item`]s instead
+
+use std::num::NonZeroU128;
+
+use accesskit::{Action, ActionRequest, NodeBuilder, NodeClassSet, NodeId, Role, Tree, TreeUpdate};
+use accesskit_winit::Adapter;
+use winit::{event::WindowEvent, event_loop::EventLoopProxy, window::Window};
+
+use crate::NeothesiaEvent;
+
+const TITLE: &str = "Neothesia";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Text(String),
+    /// Pressing it gets reported to the scene with the index of the item
+    Button(String),
+}
+
+impl Item {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    pub fn button(label: impl Into<String>) -> Self {
+        Self::Button(label.into())
+    }
+}
+
+pub struct Accessibility {
+    adapter: Adapter,
+    classes: NodeClassSet,
+    items: Vec<Item>,
+    focus: Option<usize>,
+}
+
+impl Accessibility {
+    /// Has to be created before `window` is shown for the first time
+    pub fn new(window: &Window, proxy: EventLoopProxy<NeothesiaEvent>) -> Self {
+        let adapter = Adapter::new(
+            window,
+            || tree_update(&mut NodeClassSet::new(), &[], None),
+            proxy,
+        );
+
+        Self {
+            adapter,
+            classes: NodeClassSet::new(),
+            items: Vec::new(),
+            focus: None,
+        }
+    }
+
+    pub fn window_event(&self, window: &Window, event: &WindowEvent) {
+        self.adapter.on_event(window, event);
+    }
+
+    /// Replaces the content read by screen readers
+    pub fn set_items(&mut self, items: Vec<Item>) {
+        if self.items == items {
+            return;
+        }
+
+        let is_button = |item: &Item| matches!(item, Item::Button(_));
+
+        // Focus stays in place when only a label changed, eg. a toggled checkbox
+        self.focus = self
+            .focus
+            .filter(|focus| items.get(*focus).map_or(false, is_button))
+            .or_else(|| items.iter().position(is_button));
+        self.items = items;
+        self.push_update();
+    }
+
+    /// Returns the index of the button a screen reader pressed
+    pub fn action_request(&mut self, request: &ActionRequest) -> Option<usize> {
+        let item = (request.target.0.get() as usize).checked_sub(FIRST_ITEM)?;
+        if item >= self.items.len() {
+            return None;
+        }
+
+        match request.action {
+            Action::Default => Some(item),
+            Action::Focus => {
+                self.focus = Some(item);
+                self.push_update();
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn push_update(&mut self) {
+        let classes = &mut self.classes;
+        let items = &self.items;
+        let focus = self.focus;
+        self.adapter
+            .update_if_active(|| tree_update(classes, items, focus));
+    }
+}
+
+const ROOT: u128 = 1;
+/// Node id of the first item, the rest follow in order
+const FIRST_ITEM: usize = 2;
+
+fn node_id(id: u128) -> NodeId {
+    NodeId(NonZeroU128::new(id).unwrap())
+}
+
+fn item_id(item: usize) -> NodeId {
+    node_id((item + FIRST_ITEM) as u128)
+}
+
+fn tree_update(classes: &mut NodeClassSet, items: &[Item], focus: Option<usize>) -> TreeUpdate {
+    let mut root = NodeBuilder::new(Role::Window);
+    root.set_name(TITLE);
+    root.set_children((0..items.len()).map(item_id).collect::<Vec<_>>());
+
+    let mut nodes = vec![(node_id(ROOT), root.build(classes))];
+
+    for (id, item) in items.iter().enumerate() {
+        let node = match item {
+            Item::Text(text) => {
+                let mut node = NodeBuilder::new(Role::StaticText);
+                node.set_name(text.as_str());
+                node
+            }
+            Item::Button(label) => {
+                let mut node = NodeBuilder::new(Role::Button);
+                node.set_name(label.as_str());
+                node.add_action(Action::Focus);
+                node.add_action(Action::Default);
+                node
+            }
+        };
+
+        nodes.push((item_id(id), node.build(classes)));
+    }
+
+    TreeUpdate {
+        nodes,
+        tree: Some(Tree::new(node_id(ROOT))),
+        focus: Some(focus.map_or(node_id(ROOT), item_id)),
+    }
+}
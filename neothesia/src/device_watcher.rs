@@ -0,0 +1,69 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+
+use crate::{target::Target, toast_manager::ToastManager};
+
+/// Listing devices isn't free, no need to do it every frame
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How long a change stays on screen
+const NOTICE_TIME: Duration = Duration::from_secs(4);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    Input,
+    Output,
+}
+
+/// Connected MIDI device went away, or came back and was connected to again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceChange {
+    pub kind: DeviceKind,
+    pub name: String,
+    pub connected: bool,
+}
+
+impl Display for DeviceChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            DeviceKind::Input => "input",
+            DeviceKind::Output => "output",
+        };
+        let state = if self.connected {
+            "reconnected"
+        } else {
+            "disconnected"
+        };
+        write!(f, "MIDI {kind} {} {state}", self.name)
+    }
+}
+
+/// Polls the MIDI input and output for their devices being unplugged and plugged back in,
+/// reconnects them and tells the user, in every scene
+#[derive(Default)]
+pub struct DeviceWatcher {
+    since_poll: Duration,
+    toasts: ToastManager,
+}
+
+impl DeviceWatcher {
+    pub fn update(&mut self, target: &mut Target, delta: Duration) {
+        self.since_poll += delta;
+        if self.since_poll >= POLL_INTERVAL {
+            self.since_poll = Duration::ZERO;
+
+            let output = target.output_manager.borrow_mut().poll_device();
+            let input = target.input_manager.poll_device();
+            for change in output.into_iter().chain(input) {
+                log::info!("{change}");
+                self.toasts.toast_for(change.to_string(), NOTICE_TIME);
+            }
+        }
+
+        if target.config.clean_mode {
+            self.toasts.clear();
+        }
+        self.toasts.update(target);
+    }
+}
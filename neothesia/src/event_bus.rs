@@ -0,0 +1,57 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::midi_event::MidiEvent;
+
+/// Playback and input events, observable by any subsystem
+#[derive(Debug, Clone)]
+pub enum AppEvent {
+    PlaybackStarted,
+    PlaybackPaused,
+    PlaybackResumed,
+    /// Playback jumped to a new position
+    PlaybackSeek(Duration),
+    PlaybackFinished,
+    /// The metronome passed a beat
+    Beat(neothesia_engine::Beat),
+
+    /// Event played from the file
+    FileMidi(midi_file::MidiEvent),
+    /// Event played by the user
+    UserMidi(MidiEvent),
+}
+
+/// Fan-out of `AppEvent`s to every subscriber.
+///
+/// Cloning the bus gives a new handle to the same set of subscribers,
+/// so it can be handed to anything that publishes events
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Sender<AppEvent>>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Events are queued until the receiver is polled,
+    /// dropping the receiver unsubscribes
+    pub fn subscribe(&self) -> Receiver<AppEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    pub fn publish(&self, event: AppEvent) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
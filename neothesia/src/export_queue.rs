@@ -0,0 +1,377 @@
+//! Video and audio exports, rendered one after another by `neothesia-cli` in the background.
+//!
+//! The queue is saved whenever it changes, jobs that didn't finish start over on the next launch
+
+use std::{
+    io::Read,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use neothesia_core::utils::{resources, sync};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum JobState {
+    Pending,
+    Done,
+    Failed(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExportJob {
+    pub song: PathBuf,
+    /// Also renders a WAV file
+    pub audio: bool,
+    /// Mixes the click into the WAV file
+    #[serde(default)]
+    pub click: bool,
+    pub state: JobState,
+    /// How long the render took, in seconds
+    pub duration: Option<f32>,
+}
+
+impl ExportJob {
+    pub fn name(&self) -> String {
+        self.song
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct SavedQueue {
+    jobs: Vec<ExportJob>,
+    paused: bool,
+    audio: bool,
+    click: bool,
+}
+
+/// Render of the job the queue is working on
+struct Running {
+    id: usize,
+    child: Child,
+    /// Progress and last line printed by the cli
+    output: Arc<Mutex<(f32, String)>>,
+    started: Instant,
+}
+
+pub struct ExportQueue {
+    jobs: Vec<ExportJob>,
+    paused: bool,
+    /// Whether new jobs render audio too
+    audio: bool,
+    /// Whether new jobs mix the click into their audio
+    click: bool,
+    running: Option<Running>,
+}
+
+impl ExportQueue {
+    pub fn load() -> Self {
+        let saved: SavedQueue = resources::export_queue_ron()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|file| ron::from_str(&file).ok())
+            .unwrap_or_default();
+
+        Self {
+            jobs: saved.jobs,
+            paused: saved.paused,
+            audio: saved.audio,
+            click: saved.click,
+            running: None,
+        }
+    }
+
+    fn save(&self) {
+        let path = match resources::export_queue_ron() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let saved = SavedQueue {
+            jobs: self.jobs.clone(),
+            paused: self.paused,
+            audio: self.audio,
+            click: self.click,
+        };
+
+        let data = match ron::ser::to_string_pretty(&saved, Default::default()) {
+            Ok(data) => data,
+            Err(err) => {
+                log::error!("Failed to save the export queue: {err}");
+                return;
+            }
+        };
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).ok();
+        }
+        if let Err(err) = sync::write_atomic(&path, data) {
+            log::error!("Failed to save the export queue: {err}");
+        }
+    }
+
+    pub fn jobs(&self) -> &[ExportJob] {
+        &self.jobs
+    }
+
+    pub fn add(&mut self, song: PathBuf) {
+        self.jobs.push(ExportJob {
+            song,
+            audio: self.audio,
+            click: self.click,
+            state: JobState::Pending,
+            duration: None,
+        });
+        self.save();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Stops the running render, its job starts over once resumed
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.stop();
+        self.save();
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.save();
+    }
+
+    pub fn audio(&self) -> bool {
+        self.audio
+    }
+
+    pub fn set_audio(&mut self, audio: bool) {
+        self.audio = audio;
+        self.save();
+    }
+
+    pub fn click(&self) -> bool {
+        self.click
+    }
+
+    pub fn set_click(&mut self, click: bool) {
+        self.click = click;
+        self.save();
+    }
+
+    /// Forgets finished and failed jobs
+    pub fn clear_finished(&mut self) {
+        // The running job is pending, so it stays, only its index moves
+        if let Some(running) = self.running.as_mut() {
+            running.id = self.jobs[..running.id]
+                .iter()
+                .filter(|job| job.state == JobState::Pending)
+                .count();
+        }
+
+        self.jobs.retain(|job| job.state == JobState::Pending);
+        self.save();
+    }
+
+    /// Job being rendered, and how far along it is
+    pub fn progress(&self) -> Option<(usize, f32)> {
+        let running = self.running.as_ref()?;
+        let progress = running.output.lock().unwrap().0;
+        Some((running.id, progress))
+    }
+
+    /// Time left until every job is rendered
+    pub fn eta(&self) -> Option<Duration> {
+        let running = self.running.as_ref()?;
+        let progress = running.output.lock().unwrap().0;
+        if progress <= 0.01 {
+            return None;
+        }
+
+        let elapsed = running.started.elapsed().as_secs_f32();
+        let current_total = elapsed / progress;
+
+        // Finished jobs tell how long a song takes, the running one is the best guess otherwise
+        let finished: Vec<f32> = self.jobs.iter().filter_map(|job| job.duration).collect();
+        let average = if finished.is_empty() {
+            current_total
+        } else {
+            finished.iter().sum::<f32>() / finished.len() as f32
+        };
+
+        let waiting = self
+            .jobs
+            .iter()
+            .enumerate()
+            .filter(|(id, job)| *id != running.id && job.state == JobState::Pending)
+            .count();
+
+        let left = current_total - elapsed + average * waiting as f32;
+        Some(Duration::from_secs_f32(left.max(0.0)))
+    }
+
+    /// Starts the next job, and picks up the result of the running one. Call it once per frame
+    pub fn update(&mut self) {
+        if let Some(running) = self.running.as_mut() {
+            match running.child.try_wait() {
+                Ok(None) => return,
+                Ok(Some(status)) => {
+                    let duration = running.started.elapsed().as_secs_f32();
+                    let last_line = running.output.lock().unwrap().1.clone();
+
+                    if let Some(job) = self.jobs.get_mut(running.id) {
+                        if status.success() {
+                            job.state = JobState::Done;
+                            job.duration = Some(duration);
+                            log::info!("Exported {}", job.name());
+                        } else {
+                            job.state = JobState::Failed(failure(&last_line));
+                            log::error!("Failed to export {}: {last_line}", job.name());
+                        }
+                    }
+                }
+                Err(err) => {
+                    if let Some(job) = self.jobs.get_mut(running.id) {
+                        job.state = JobState::Failed(err.to_string());
+                    }
+                }
+            }
+
+            self.running = None;
+            self.save();
+        }
+
+        if self.paused {
+            return;
+        }
+
+        let id = match self
+            .jobs
+            .iter()
+            .position(|job| job.state == JobState::Pending)
+        {
+            Some(id) => id,
+            None => return,
+        };
+
+        match spawn(&self.jobs[id]) {
+            Ok(running) => {
+                self.running = Some(Running { id, ..running });
+            }
+            Err(err) => {
+                log::error!("Failed to start neothesia-cli: {err}");
+                self.jobs[id].state = JobState::Failed(err);
+                self.save();
+            }
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut running) = self.running.take() {
+            running.child.kill().ok();
+            running.child.wait().ok();
+        }
+    }
+}
+
+impl Drop for ExportQueue {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// The cli lives next to the app
+fn cli_path() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let cli = exe.with_file_name(format!("neothesia-cli{}", std::env::consts::EXE_SUFFIX));
+    cli.exists().then_some(cli)
+}
+
+fn spawn(job: &ExportJob) -> Result<Running, String> {
+    let cli = cli_path().ok_or_else(|| String::from("neothesia-cli not found"))?;
+    let out = resources::exports_dir().ok_or_else(|| String::from("No exports folder"))?;
+
+    let mut command = Command::new(cli);
+    command
+        .arg(&job.song)
+        .arg("--out")
+        .arg(&out)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    if job.audio {
+        command.arg("--audio");
+    }
+    if job.click {
+        command.arg("--click");
+    }
+
+    let mut child = command.spawn().map_err(|err| err.to_string())?;
+
+    let output = Arc::new(Mutex::new((0.0, String::new())));
+    if let Some(stdout) = child.stdout.take() {
+        let output = output.clone();
+        std::thread::Builder::new()
+            .name("export-progress".into())
+            .spawn(move || read_progress(stdout, &output))
+            .ok();
+    }
+
+    Ok(Running {
+        id: 0,
+        child,
+        output,
+        started: Instant::now(),
+    })
+}
+
+/// Follows the progress line the cli keeps rewriting, eg. `[1/1] song.mid: 42%`
+fn read_progress(mut stdout: impl Read, output: &Mutex<(f32, String)>) {
+    let mut line = Vec::new();
+    let mut buf = [0; 256];
+
+    while let Ok(len) = stdout.read(&mut buf) {
+        if len == 0 {
+            break;
+        }
+
+        for byte in &buf[..len] {
+            if *byte != b'\r' && *byte != b'\n' {
+                line.push(*byte);
+                continue;
+            }
+
+            let text = String::from_utf8_lossy(&line).trim().to_string();
+            line.clear();
+            if text.is_empty() {
+                continue;
+            }
+
+            let mut output = output.lock().unwrap();
+            if let Some(progress) = parse_progress(&text) {
+                output.0 = progress;
+            }
+            output.1 = text;
+        }
+    }
+}
+
+fn parse_progress(line: &str) -> Option<f32> {
+    let percent = line.rsplit(": ").next()?.strip_suffix('%')?;
+    percent
+        .parse::<f32>()
+        .ok()
+        .map(|p| (p / 100.0).clamp(0.0, 1.0))
+}
+
+/// Error part of a `[1/1] song.mid: failed, error` line
+fn failure(line: &str) -> String {
+    line.split_once(": failed, ")
+        .map(|(_, err)| err.to_string())
+        .unwrap_or_else(|| String::from("Export failed"))
+}
@@ -0,0 +1,74 @@
+//! System wide shortcuts, see [`crate::config::GlobalHotkeys`]
+
+use std::collections::HashMap;
+
+use global_hotkey::{hotkey::HotKey, GlobalHotKeyEvent, GlobalHotKeyManager};
+
+use crate::config;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    PlayPause,
+    Rewind,
+    Forward,
+    Mute,
+}
+
+pub struct GlobalHotkeys {
+    /// Hotkeys stay registered for as long as the manager lives
+    _manager: GlobalHotKeyManager,
+    actions: HashMap<u32, HotkeyAction>,
+}
+
+impl GlobalHotkeys {
+    /// Returns `None` when disabled, or when the platform doesn't support global hotkeys
+    pub fn new(config: &config::GlobalHotkeys) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+
+        let manager = match GlobalHotKeyManager::new() {
+            Ok(manager) => manager,
+            Err(err) => {
+                log::error!("Global hotkeys are not available: {err}");
+                return None;
+            }
+        };
+
+        let mut actions = HashMap::new();
+        for (shortcut, action) in [
+            (&config.play_pause, HotkeyAction::PlayPause),
+            (&config.rewind, HotkeyAction::Rewind),
+            (&config.forward, HotkeyAction::Forward),
+            (&config.mute, HotkeyAction::Mute),
+        ] {
+            let hotkey: HotKey = match shortcut.parse() {
+                Ok(hotkey) => hotkey,
+                Err(err) => {
+                    log::error!("Invalid hotkey {shortcut}: {err}");
+                    continue;
+                }
+            };
+
+            match manager.register(hotkey) {
+                Ok(()) => {
+                    actions.insert(hotkey.id(), action);
+                }
+                Err(err) => log::error!("Failed to register hotkey {shortcut}: {err}"),
+            }
+        }
+
+        Some(Self {
+            _manager: manager,
+            actions,
+        })
+    }
+
+    /// Hotkeys pressed since the last call
+    pub fn poll(&self) -> Vec<HotkeyAction> {
+        GlobalHotKeyEvent::receiver()
+            .try_iter()
+            .filter_map(|event| self.actions.get(&event.id).copied())
+            .collect()
+    }
+}
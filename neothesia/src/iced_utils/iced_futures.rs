@@ -0,0 +1,45 @@
+use iced_runtime::{command::Action, Command};
+
+/// Runs the futures of iced commands, there is no executor so they are polled every frame
+pub struct CommandFutures<Message> {
+    context: std::task::Context<'static>,
+    futures: Vec<futures::future::BoxFuture<'static, Message>>,
+}
+
+impl<Message> Default for CommandFutures<Message> {
+    fn default() -> Self {
+        Self {
+            context: std::task::Context::from_waker(futures::task::noop_waker_ref()),
+            futures: Vec::new(),
+        }
+    }
+}
+
+impl<Message> CommandFutures<Message> {
+    pub fn push(&mut self, command: Command<Message>) {
+        for a in command.actions() {
+            match a {
+                Action::Future(f) => {
+                    self.futures.push(f);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Messages of the futures that finished
+    pub fn poll(&mut self) -> Vec<Message> {
+        let context = &mut self.context;
+        let mut messages = Vec::new();
+
+        self.futures.retain_mut(|f| match f.as_mut().poll(context) {
+            std::task::Poll::Ready(msg) => {
+                messages.push(msg);
+                false
+            }
+            std::task::Poll::Pending => true,
+        });
+
+        messages
+    }
+}
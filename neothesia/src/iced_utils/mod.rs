@@ -1,7 +1,9 @@
 mod iced_clipboard;
+mod iced_futures;
 mod iced_manager;
 
 pub use iced_clipboard::DummyClipboard;
+pub use iced_futures::CommandFutures;
 pub use iced_manager::IcedManager;
 
 pub mod iced_conversion;
@@ -1,11 +1,41 @@
+use std::collections::HashMap;
+
 use winit::event_loop::EventLoopProxy;
 
-use crate::{midi_event::MidiEvent, NeothesiaEvent};
+use crate::{
+    device_watcher::{DeviceChange, DeviceKind},
+    midi_event::MidiEvent,
+    NeothesiaEvent,
+};
+
+mod pad_grid;
+pub use pad_grid::PadGrid;
+
+mod virtual_piano;
+pub use virtual_piano::VirtualPiano;
+
+/// Input index of the notes of [`VirtualPiano`], it's not one of the connected inputs
+pub const VIRTUAL_PIANO: usize = usize::MAX;
+
+/// Connected input device
+struct Input {
+    port: midi_io::MidiInputPort,
+    connection: Option<midi_io::MidiInputConnection>,
+    /// Device went away, until it's connected to again
+    lost: bool,
+}
 
 pub struct InputManager {
     input: midi_io::MidiInputManager,
     tx: EventLoopProxy<NeothesiaEvent>,
-    current_connection: Option<midi_io::MidiInputConnection>,
+    /// First one is the main input, events of the others are merged into it
+    connections: Vec<Input>,
+    /// Index into `connections` of the input the event being handled came from
+    event_input: usize,
+    /// Main input is a grid controller, its pads get translated to notes
+    pad_grid: Option<PadGrid>,
+    /// Keyed by input name, everything played on the input is moved onto this channel
+    channels: HashMap<String, u8>,
 }
 
 impl InputManager {
@@ -14,7 +44,10 @@ impl InputManager {
         Self {
             input,
             tx,
-            current_connection: None,
+            connections: Vec::new(),
+            event_input: 0,
+            pad_grid: None,
+            channels: HashMap::new(),
         }
     }
 
@@ -22,44 +55,163 @@ impl InputManager {
         self.input.inputs()
     }
 
+    /// Main input
+    pub fn current_port(&self) -> Option<&midi_io::MidiInputPort> {
+        self.connections.first().map(|input| &input.port)
+    }
+
+    /// Input the event being handled came from
+    pub fn event_port(&self) -> Option<&midi_io::MidiInputPort> {
+        self.connections
+            .get(self.event_input)
+            .map(|input| &input.port)
+    }
+
+    /// Called with the input of each event, before it's handled
+    pub fn set_event_input(&mut self, input: usize) {
+        self.event_input = input;
+    }
+
+    pub fn pad_grid(&self) -> Option<PadGrid> {
+        self.pad_grid
+    }
+
+    /// Takes effect on the next connection
+    pub fn set_pad_grid(&mut self, pad_grid: Option<PadGrid>) {
+        self.pad_grid = pad_grid;
+    }
+
+    /// Takes effect on the next connection
+    pub fn set_channels(&mut self, channels: HashMap<String, u8>) {
+        self.channels = channels;
+    }
+
+    /// Whether a connected device went away and hasn't been connected to again yet
+    pub fn is_lost(&self) -> bool {
+        self.connections.iter().any(|input| input.lost)
+    }
+
+    /// Notices connected devices going away, and connects to them again once they're back
+    pub fn poll_device(&mut self) -> Vec<DeviceChange> {
+        let ports = self.inputs();
+        let mut changes = Vec::new();
+
+        for id in 0..self.connections.len() {
+            let input = &self.connections[id];
+            let present = ports.contains(&input.port);
+
+            let connected = if input.lost && present {
+                let connection = self.connect(id);
+                if connection.is_none() {
+                    continue;
+                }
+
+                let input = &mut self.connections[id];
+                input.connection = connection;
+                input.lost = false;
+                true
+            } else if !input.lost && !present {
+                let input = &mut self.connections[id];
+                input.connection = None;
+                input.lost = true;
+                false
+            } else {
+                continue;
+            };
+
+            changes.push(DeviceChange {
+                kind: DeviceKind::Input,
+                name: self.connections[id].port.to_string(),
+                connected,
+            });
+        }
+
+        changes
+    }
+
+    /// Connects to `port` alone
     pub fn connect_input(&mut self, port: midi_io::MidiInputPort) {
+        self.connect_inputs(vec![port]);
+    }
+
+    /// Connects to all of `ports` at once, the first one is the main input
+    pub fn connect_inputs(&mut self, ports: Vec<midi_io::MidiInputPort>) {
+        // Old connections are closed here, before connecting again
+        self.connections = ports
+            .into_iter()
+            .map(|port| Input {
+                port,
+                connection: None,
+                lost: false,
+            })
+            .collect();
+
+        for id in 0..self.connections.len() {
+            self.connections[id].connection = self.connect(id);
+        }
+    }
+
+    fn connect(&self, id: usize) -> Option<midi_io::MidiInputConnection> {
+        let port = self.connections[id].port.clone();
+        let remap = self.channels.get(&port.to_string()).copied();
+        let channel = move |ch: u8| remap.unwrap_or(ch);
+
         let tx = self.tx.clone();
-        self.current_connection = midi_io::MidiInputManager::connect_input(port, move |message| {
-            if message.len() == 3 {
+        let send = move |event| {
+            tx.send_event(NeothesiaEvent::MidiInput(id, event)).ok();
+        };
+
+        // Only the main input can be a grid controller
+        let pad_grid = if id == 0 { self.pad_grid } else { None };
+
+        midi_io::MidiInputManager::connect_input(port, move |message| {
+            if message.len() == 3 && message[0] >= 0xB0 && message[0] <= 0xBF {
+                let (_, ch) = midi::utils::from_status_byte(message[0]);
+                send(MidiEvent::Controller {
+                    channel: channel(ch as u8),
+                    controller: message[1],
+                    value: message[2],
+                });
+            } else if message.len() == 3 {
+                // Pads outside of the grid, eg. scene launch buttons, play nothing
+                let key = match pad_grid {
+                    Some(pad_grid) => match pad_grid.note(message[1]) {
+                        Some(note) => note,
+                        None => return,
+                    },
+                    None => message[1],
+                };
+
                 if message[0] >= 0x90 && message[0] <= 0x9F {
                     let (s, ch) = midi::utils::from_status_byte(message[0]);
                     assert_eq!(s, 9);
 
-                    let key = message[1];
                     let vel = message[2];
 
                     // Some keyboards send NoteOn event with vel 0 instead of NoteOff
                     if vel == 0 {
-                        tx.send_event(NeothesiaEvent::MidiInput(MidiEvent::NoteOff {
-                            channel: ch as u8,
+                        send(MidiEvent::NoteOff {
+                            channel: channel(ch as u8),
                             key,
-                        }))
-                        .ok();
+                        });
                     } else {
-                        tx.send_event(NeothesiaEvent::MidiInput(MidiEvent::NoteOn {
-                            channel: ch as u8,
+                        send(MidiEvent::NoteOn {
+                            channel: channel(ch as u8),
                             track_id: 0,
                             key,
                             vel,
-                        }))
-                        .ok();
+                        });
                     }
                 } else if message[0] >= 0x80 && message[0] <= 0x8F {
                     let (s, ch) = midi::utils::from_status_byte(message[0]);
                     assert_eq!(s, 8);
 
-                    tx.send_event(NeothesiaEvent::MidiInput(MidiEvent::NoteOff {
-                        channel: ch as u8,
-                        key: message[1],
-                    }))
-                    .ok();
+                    send(MidiEvent::NoteOff {
+                        channel: channel(ch as u8),
+                        key,
+                    });
                 }
             }
-        });
+        })
     }
 }
@@ -0,0 +1,97 @@
+//! Grid controllers played as a keyboard, see [`crate::config::PadGrid`]
+
+use crate::config::{self, PadDevice, PadLayout};
+
+const SIZE: u8 = 8;
+
+/// Maps the pads of an 8x8 grid to notes, and back
+#[derive(Debug, Clone, Copy)]
+pub struct PadGrid {
+    device: PadDevice,
+    root_note: u8,
+    row_interval: u8,
+}
+
+impl PadGrid {
+    /// Returns `None` when disabled
+    pub fn new(config: &config::PadGrid) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+
+        let row_interval = match config.layout {
+            PadLayout::Chromatic => SIZE,
+            PadLayout::Isomorphic => config.row_interval.max(1),
+        };
+
+        Some(Self {
+            device: config.device,
+            root_note: config.root_note,
+            row_interval,
+        })
+    }
+
+    /// Note played by the pad that sends `pad`, `None` for anything that's not a grid pad
+    pub fn note(&self, pad: u8) -> Option<u8> {
+        let (row, col) = self.position(pad)?;
+        let note = self.root_note as u16 + row as u16 * self.row_interval as u16 + col as u16;
+        (note <= 127).then_some(note as u8)
+    }
+
+    /// Every pad that plays `note`, isomorphic layouts repeat notes across rows
+    pub fn pads(&self, note: u8) -> Vec<u8> {
+        let mut pads = Vec::new();
+
+        for row in 0..SIZE {
+            let row_start = self.root_note as i16 + (row as i16 * self.row_interval as i16);
+            let col = note as i16 - row_start;
+            if (0..SIZE as i16).contains(&col) {
+                pads.push(self.pad(row, col as u8));
+            }
+        }
+
+        pads
+    }
+
+    /// `(row, col)` of `pad`, counted from the bottom left
+    fn position(&self, pad: u8) -> Option<(u8, u8)> {
+        match self.device {
+            // Programmer mode numbers pads 11..=88, row by row
+            PadDevice::LaunchpadMiniMk3 | PadDevice::LaunchpadX => {
+                let (row, col) = (pad / 10, pad % 10);
+                ((1..=SIZE).contains(&row) && (1..=SIZE).contains(&col)).then(|| (row - 1, col - 1))
+            }
+            PadDevice::ApcMini => (pad < SIZE * SIZE).then_some((pad / SIZE, pad % SIZE)),
+        }
+    }
+
+    fn pad(&self, row: u8, col: u8) -> u8 {
+        match self.device {
+            PadDevice::LaunchpadMiniMk3 | PadDevice::LaunchpadX => (row + 1) * 10 + col + 1,
+            PadDevice::ApcMini => row * SIZE + col,
+        }
+    }
+
+    /// Puts the device in the mode the pads are numbered for
+    pub fn init_message(&self) -> Option<&'static [u8]> {
+        match self.device {
+            PadDevice::LaunchpadMiniMk3 => {
+                Some(&[0xF0, 0x00, 0x20, 0x29, 0x02, 0x0D, 0x0E, 0x01, 0xF7])
+            }
+            PadDevice::LaunchpadX => Some(&[0xF0, 0x00, 0x20, 0x29, 0x02, 0x0C, 0x0E, 0x01, 0xF7]),
+            PadDevice::ApcMini => None,
+        }
+    }
+
+    /// Message that lights `pad`, or turns it off
+    pub fn light_message(&self, pad: u8, on: bool) -> [u8; 3] {
+        let color = match (self.device, on) {
+            (_, false) => 0,
+            // Green of the default palette
+            (PadDevice::LaunchpadMiniMk3 | PadDevice::LaunchpadX, true) => 21,
+            (PadDevice::ApcMini, true) => 1,
+        };
+
+        [0x90, pad, color]
+    }
+}
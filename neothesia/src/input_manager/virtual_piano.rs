@@ -0,0 +1,133 @@
+//! Computer keyboard played as a piano, see [`crate::config::VirtualPiano`]
+
+use std::collections::HashMap;
+
+use winit::event::{ElementState, KeyboardInput, ModifiersState, VirtualKeyCode};
+
+use crate::{config, midi_event::MidiEvent};
+
+/// Turns keys of the computer keyboard into notes, like a MIDI input would
+#[derive(Debug, Default)]
+pub struct VirtualPiano {
+    /// Held keys and the notes they play, so a key ends the note it started
+    held: HashMap<VirtualKeyCode, u8>,
+}
+
+impl VirtualPiano {
+    /// Returns `None` for keys that aren't part of the layout, they are left to the scene.
+    /// So are keys pressed along with Ctrl, Alt or Logo, which keeps the shortcuts of the
+    /// scene in reach. Key repeats are used up without a note. `config` is `None` while the
+    /// piano is off, letting go of a held key still ends its note then
+    pub fn keyboard_input(
+        &mut self,
+        config: Option<&config::VirtualPiano>,
+        modifiers: ModifiersState,
+        input: &KeyboardInput,
+    ) -> Option<Vec<MidiEvent>> {
+        let key = input.virtual_keycode?;
+
+        match input.state {
+            ElementState::Released => {
+                let key = self.held.remove(&key)?;
+                Some(vec![MidiEvent::NoteOff { channel: 0, key }])
+            }
+            ElementState::Pressed => {
+                if modifiers.ctrl() || modifiers.alt() || modifiers.logo() {
+                    return None;
+                }
+
+                let config = config?;
+                let note = note(config, key)?;
+
+                if self.held.contains_key(&key) {
+                    return Some(Vec::new());
+                }
+                self.held.insert(key, note);
+
+                Some(vec![MidiEvent::NoteOn {
+                    channel: 0,
+                    track_id: 0,
+                    key: note,
+                    vel: config.velocity.clamp(1, 127),
+                }])
+            }
+        }
+    }
+
+    /// Ends the notes of every held key, eg. when the window loses focus and their release
+    /// would never arrive
+    pub fn release_all(&mut self) -> Vec<MidiEvent> {
+        self.held
+            .drain()
+            .map(|(_, key)| MidiEvent::NoteOff { channel: 0, key })
+            .collect()
+    }
+}
+
+/// Note of `key` in the layout, the first row it's found in wins
+fn note(config: &config::VirtualPiano, key: VirtualKeyCode) -> Option<u8> {
+    let ch = key_char(key)?;
+
+    config.rows.iter().enumerate().find_map(|(row, keys)| {
+        let id = keys.chars().position(|c| c.to_ascii_lowercase() == ch)?;
+        let note = config.base_note as usize + row * 12 + id;
+        (note <= 127).then_some(note as u8)
+    })
+}
+
+/// Character the key types on a US layout, without shift
+fn key_char(key: VirtualKeyCode) -> Option<char> {
+    use VirtualKeyCode::*;
+
+    let ch = match key {
+        A => 'a',
+        B => 'b',
+        C => 'c',
+        D => 'd',
+        E => 'e',
+        F => 'f',
+        G => 'g',
+        H => 'h',
+        I => 'i',
+        J => 'j',
+        K => 'k',
+        L => 'l',
+        M => 'm',
+        N => 'n',
+        O => 'o',
+        P => 'p',
+        Q => 'q',
+        R => 'r',
+        S => 's',
+        T => 't',
+        U => 'u',
+        V => 'v',
+        W => 'w',
+        X => 'x',
+        Y => 'y',
+        Z => 'z',
+        Key0 => '0',
+        Key1 => '1',
+        Key2 => '2',
+        Key3 => '3',
+        Key4 => '4',
+        Key5 => '5',
+        Key6 => '6',
+        Key7 => '7',
+        Key8 => '8',
+        Key9 => '9',
+        Comma => ',',
+        Period => '.',
+        Semicolon => ';',
+        Apostrophe => '\'',
+        Slash => '/',
+        Backslash => '\\',
+        LBracket => '[',
+        RBracket => ']',
+        Minus => '-',
+        Equals => '=',
+        Grave => '`',
+        _ => return None,
+    };
+    Some(ch)
+}
@@ -0,0 +1,121 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use neothesia_core::utils::sync;
+
+use crate::{midi_event::MidiEvent, target::Target};
+
+/// Records the MIDI input into a `.mid` file, see [`crate::config::InputRecording`].
+///
+/// Whatever was recorded is saved by [`Self::save`], and on drop unless it was made
+/// by [`Self::on_request`]
+pub struct InputRecorder {
+    recording: midi_file::Recording,
+    name: String,
+    directory: Option<PathBuf>,
+    save_on_drop: bool,
+}
+
+impl InputRecorder {
+    /// Returns `None` when input recording is disabled
+    pub fn new(target: &Target, name: &str) -> Option<Self> {
+        let config = &target.config.input_recording;
+        if !config.enabled {
+            return None;
+        }
+
+        let mut recorder = Self::on_request(target, name);
+        recorder.save_on_drop = true;
+        Some(recorder)
+    }
+
+    /// Records even when input recording is disabled, only [`Self::save`] writes the file
+    pub fn on_request(target: &Target, name: &str) -> Self {
+        Self {
+            recording: midi_file::Recording::new(),
+            name: name.to_string(),
+            directory: target
+                .config
+                .input_recording
+                .directory
+                .clone()
+                .or_else(neothesia_core::utils::resources::recordings_dir),
+            save_on_drop: false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recording.is_empty()
+    }
+
+    pub fn recording(&self) -> &midi_file::Recording {
+        &self.recording
+    }
+
+    /// Drops the recording so far without writing it
+    pub fn discard(&mut self) {
+        self.recording = midi_file::Recording::new();
+    }
+
+    /// `time` is where the event goes in the file
+    pub fn record(&mut self, time: Duration, event: &MidiEvent) {
+        let (channel, message) = event.channel_message();
+        self.recording.push(time, channel, message);
+    }
+
+    /// Writes the recording so far and starts a new one, returns where it was written
+    pub fn save(&mut self) -> Option<PathBuf> {
+        let recording = std::mem::take(&mut self.recording);
+        if recording.is_empty() {
+            return None;
+        }
+
+        self.write(recording.to_bytes())
+    }
+
+    /// Writes `layers` as one file with a track for each, instead of the recording
+    pub fn save_layers(&self, layers: &[midi_file::Recording]) -> Option<PathBuf> {
+        if layers.iter().all(|layer| layer.is_empty()) {
+            return None;
+        }
+
+        self.write(midi_file::Recording::layers_to_bytes(layers))
+    }
+
+    fn write(&self, data: Vec<u8>) -> Option<PathBuf> {
+        let directory = self.directory.as_ref()?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        // One file per take and machine, so synced data directories never write the same file
+        let path = directory.join(format!(
+            "{}-{timestamp}-{}.mid",
+            self.name,
+            sync::device_name()
+        ));
+
+        let res = std::fs::create_dir_all(directory).and_then(|_| sync::write_atomic(&path, data));
+        match res {
+            Ok(()) => {
+                log::info!("Recording saved to {}", path.display());
+                Some(path)
+            }
+            Err(err) => {
+                log::error!("Failed to save recording {}: {err}", path.display());
+                None
+            }
+        }
+    }
+}
+
+impl Drop for InputRecorder {
+    fn drop(&mut self) {
+        if self.save_on_drop {
+            self.save();
+        }
+    }
+}
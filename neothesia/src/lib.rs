@@ -6,20 +6,40 @@ pub use wgpu_jumpstart::{Gpu, TransformUniform, Uniform};
 use neothesia_core::{config, render};
 pub mod utils;
 
+pub mod accessibility;
+pub mod device_watcher;
+pub mod event_bus;
+pub mod export_queue;
+pub mod global_hotkeys;
 pub mod iced_utils;
 pub mod input_manager;
+pub mod input_recorder;
 pub mod midi_event;
 pub mod output_manager;
 pub mod scene;
 pub mod target;
+pub mod toast_manager;
+pub mod update_check;
 
 #[derive(Debug)]
 pub enum NeothesiaEvent {
     MainMenu(crate::scene::menu_scene::Event),
-    MidiInput(midi_event::MidiEvent),
+    /// Event of the input at this index of the connected ones, `0` is the main input
+    MidiInput(usize, midi_event::MidiEvent),
+    /// Opens the settings in a separate window, without leaving the current scene
+    OpenSettingsWindow,
+    CloseSettingsWindow,
+    /// Screen reader wants to interact with the window
+    Accessibility(accesskit_winit::ActionRequestEvent),
     GoBack,
 }
 
+impl From<accesskit_winit::ActionRequestEvent> for NeothesiaEvent {
+    fn from(event: accesskit_winit::ActionRequestEvent) -> Self {
+        Self::Accessibility(event)
+    }
+}
+
 pub fn block_on<F>(f: F) -> <F as Future>::Output
 where
     F: Future,
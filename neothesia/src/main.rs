@@ -1,29 +1,58 @@
 use neothesia::{
+    device_watcher::DeviceWatcher,
+    global_hotkeys::GlobalHotkeys,
+    input_manager::{self, VirtualPiano},
+    input_recorder::InputRecorder,
     midi_event::MidiEvent,
-    scene::{menu_scene, playing_scene, Scene, SceneType},
+    scene::{
+        free_play_scene, menu_scene, metronome_scene, playing_scene, transition::Transition, Scene,
+        SceneType,
+    },
     target::Target,
-    utils::window::WindowState,
+    utils::{hdr::HdrOutput, power::PowerMonitor, window::WindowState},
     Gpu, NeothesiaEvent,
 };
+use neothesia_core::theme::ThemeWatcher;
 
 use wgpu_jumpstart::Surface;
 use winit::{
     event::WindowEvent,
-    event_loop::{ControlFlow, EventLoop, EventLoopBuilder},
+    event_loop::{ControlFlow, EventLoop, EventLoopBuilder, EventLoopWindowTarget},
 };
 
+/// Frame rate of low power mode while nothing on screen moves on its own
+const IDLE_FPS: u32 = 5;
+
 pub struct Neothesia {
     pub target: Target,
     surface: Surface,
+    hdr: Option<HdrOutput>,
+    instance: wgpu::Instance,
+    settings_window: Option<menu_scene::SettingsWindow>,
+    global_hotkeys: Option<GlobalHotkeys>,
+    power_monitor: PowerMonitor,
+    theme: ThemeWatcher,
+    device_watcher: DeviceWatcher,
+    virtual_piano: VirtualPiano,
+    /// Input arrived since the last frame, so it should not wait for the idle frame rate
+    input_pending: bool,
+    /// Input played in the menu or in free play, without a song, and when the first note came.
+    /// Saved once the menu is left
+    freestyle: Option<(InputRecorder, std::time::Instant)>,
 
     last_time: std::time::Instant,
     pub fps_timer: fps_ticker::Fps,
     pub game_scene: Box<dyn Scene>,
+    transition: Option<Transition>,
 }
 
 impl Neothesia {
-    pub fn new(mut target: Target, surface: Surface) -> Self {
+    pub fn new(mut target: Target, mut surface: Surface, instance: wgpu::Instance) -> Self {
+        let hdr = HdrOutput::new(&target, &mut surface, &target.config.hdr);
+        let mut theme = ThemeWatcher::default();
+        theme.poll(&mut target.config);
         let mut game_scene = menu_scene::MenuScene::new(&mut target);
+        let global_hotkeys = GlobalHotkeys::new(&target.config.global_hotkeys);
 
         target.resize();
         game_scene.resize(&mut target);
@@ -32,14 +61,33 @@ impl Neothesia {
         Self {
             target,
             surface,
+            hdr,
+            instance,
+            settings_window: None,
+            global_hotkeys,
+            power_monitor: PowerMonitor::default(),
+            theme,
+            device_watcher: DeviceWatcher::default(),
+            virtual_piano: VirtualPiano::default(),
+            input_pending: false,
+            freestyle: None,
             last_time: std::time::Instant::now(),
             fps_timer: Default::default(),
             game_scene: Box::new(game_scene),
+            transition: None,
         }
     }
 
     pub fn window_event(&mut self, event: &WindowEvent, control_flow: &mut ControlFlow) {
         self.target.window_state.window_event(event);
+        self.target
+            .accessibility
+            .window_event(&self.target.window, event);
+        self.input_pending = true;
+
+        if self.virtual_piano_event(event) {
+            return;
+        }
 
         match &event {
             WindowEvent::Resized(_) => {
@@ -49,6 +97,10 @@ impl Neothesia {
                     self.target.window_state.physical_size.height,
                 );
 
+                if let Some(hdr) = self.hdr.as_mut() {
+                    hdr.resize(&self.target);
+                }
+
                 self.target.resize();
                 self.game_scene.resize(&mut self.target);
 
@@ -81,48 +133,250 @@ impl Neothesia {
                     }
                 }
             }
-            WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+            WindowEvent::CloseRequested => {
+                if self.target.config.practice_lock.is_locked() {
+                    log::info!("Practice goal not reached, exit from the menu with the PIN");
+                } else {
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
             _ => {}
         }
 
-        self.game_scene.window_event(&mut self.target, event);
+        // Scene is on its way out, or not fully shown yet
+        if self.transition.is_none() {
+            self.game_scene.window_event(&mut self.target, event);
+        }
+    }
+
+    /// Plays the keys of the computer keyboard piano, returns `true` when the key was one
+    /// of its keys, the scene doesn't get it then
+    fn virtual_piano_event(&mut self, event: &WindowEvent) -> bool {
+        // Keys are typed into the menu, and the metronome is set up with nearly every key
+        let config = &self.target.config.virtual_piano;
+        let config = (config.enabled
+            && self.transition.is_none()
+            && !matches!(
+                self.game_scene.scene_type(),
+                SceneType::MainMenu | SceneType::Metronome
+            ))
+        .then_some(config);
+
+        let (events, used) = match event {
+            WindowEvent::KeyboardInput { input, .. } => {
+                let modifiers = self.target.window_state.modifers_state;
+                match self.virtual_piano.keyboard_input(config, modifiers, input) {
+                    Some(events) => (events, true),
+                    None => return false,
+                }
+            }
+            WindowEvent::Focused(false) => (self.virtual_piano.release_all(), false),
+            _ => return false,
+        };
+
+        self.target
+            .input_manager
+            .set_event_input(input_manager::VIRTUAL_PIANO);
+        for event in events.iter() {
+            self.midi_event(event);
+        }
+
+        used
+    }
+
+    pub fn settings_window_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::CloseRequested = event {
+            self.settings_window = None;
+            return;
+        }
+
+        if let Some(window) = self.settings_window.as_mut() {
+            window.window_event(&mut self.target, event);
+        }
     }
 
     pub fn midi_event(&mut self, event: &MidiEvent) {
+        self.input_pending = true;
         self.game_scene.midi_event(&mut self.target, event);
+
+        // Without a song the input is recorded as a whole, there is no song time to follow
+        if matches!(
+            self.game_scene.scene_type(),
+            SceneType::MainMenu | SceneType::FreePlay | SceneType::Metronome
+        ) {
+            if self.freestyle.is_none() {
+                self.freestyle = InputRecorder::new(&self.target, "freestyle")
+                    .map(|recorder| (recorder, std::time::Instant::now()));
+            }
+            if let Some((recorder, start)) = self.freestyle.as_mut() {
+                recorder.record(start.elapsed(), event);
+            }
+        }
+    }
+
+    pub fn poll_global_hotkeys(&mut self) {
+        let actions = match self.global_hotkeys.as_ref() {
+            Some(hotkeys) => hotkeys.poll(),
+            None => return,
+        };
+
+        if self.transition.is_none() {
+            for action in actions {
+                self.game_scene.global_hotkey(&mut self.target, action);
+            }
+        }
     }
 
-    pub fn neothesia_event(&mut self, event: &NeothesiaEvent, control_flow: &mut ControlFlow) {
+    pub fn neothesia_event(
+        &mut self,
+        event: &NeothesiaEvent,
+        event_loop: &EventLoopWindowTarget<NeothesiaEvent>,
+        control_flow: &mut ControlFlow,
+    ) {
         match event {
             NeothesiaEvent::MainMenu(event) => match event {
                 menu_scene::Event::Play => {
-                    let to = playing_scene::PlayingScene::new(&mut self.target);
-                    self.game_scene = Box::new(to);
+                    if self.transition.is_none() {
+                        self.transition = Some(Transition::new(&self.target, SceneType::Playing));
+                    }
+                }
+                menu_scene::Event::FreePlay => {
+                    if self.transition.is_none() {
+                        self.transition = Some(Transition::new(&self.target, SceneType::FreePlay));
+                    }
+                }
+                menu_scene::Event::Metronome => {
+                    if self.transition.is_none() {
+                        self.transition = Some(Transition::new(&self.target, SceneType::Metronome));
+                    }
                 }
             },
             NeothesiaEvent::GoBack => match self.game_scene.scene_type() {
                 SceneType::MainMenu => {
                     *control_flow = ControlFlow::Exit;
                 }
-                SceneType::Playing => {
-                    let to = menu_scene::MenuScene::new(&mut self.target);
-                    self.game_scene = Box::new(to);
+                SceneType::Playing | SceneType::FreePlay | SceneType::Metronome => {
+                    if self.transition.is_none() {
+                        self.target.window.set_cursor_visible(true);
+                        self.transition = Some(Transition::new(&self.target, SceneType::MainMenu));
+                    }
                 }
             },
-            NeothesiaEvent::MidiInput(event) => self.midi_event(event),
+            NeothesiaEvent::MidiInput(input, event) => {
+                self.target.input_manager.set_event_input(*input);
+                self.midi_event(event);
+            }
+            NeothesiaEvent::OpenSettingsWindow => {
+                if let Some(window) = self.settings_window.as_ref() {
+                    window.focus();
+                } else {
+                    self.settings_window = menu_scene::SettingsWindow::new(
+                        &mut self.target,
+                        &self.instance,
+                        event_loop,
+                    );
+                }
+            }
+            NeothesiaEvent::CloseSettingsWindow => {
+                self.settings_window = None;
+            }
+            NeothesiaEvent::Accessibility(event) => {
+                self.input_pending = true;
+                if let Some(item) = self.target.accessibility.action_request(&event.request) {
+                    if self.transition.is_none() {
+                        self.game_scene.accessibility_action(&mut self.target, item);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Time the next frame is due at, when the frame rate is capped by low power mode
+    pub fn next_frame(&mut self) -> Option<std::time::Instant> {
+        let config = &self.target.config.low_power;
+        if !self.target.low_power {
+            return None;
         }
+
+        let idle = !self.input_pending
+            && self.transition.is_none()
+            && self.settings_window.is_none()
+            && self.game_scene.is_idle();
+
+        let fps = if idle {
+            IDLE_FPS.min(config.max_fps)
+        } else {
+            config.max_fps
+        };
+
+        Some(self.last_time + std::time::Duration::from_secs_f64(1.0 / fps.max(1) as f64))
     }
 
     pub fn update(&mut self) {
         self.fps_timer.tick();
+        self.input_pending = false;
 
         let delta = self.last_time.elapsed();
         self.last_time = std::time::Instant::now();
 
-        self.game_scene.update(&mut self.target, delta);
+        self.theme.poll(&mut self.target.config);
+        self.target.low_power = self.power_monitor.low_power(&self.target.config.low_power);
+
+        let to = match self.transition.as_mut() {
+            Some(transition) => transition.update(&mut self.target, delta),
+            None => None,
+        };
+
+        if let Some(to) = to {
+            // Saved by drop
+            self.freestyle = None;
+
+            self.game_scene = match to {
+                SceneType::Playing => Box::new(playing_scene::PlayingScene::new(&mut self.target)),
+                SceneType::FreePlay => {
+                    Box::new(free_play_scene::FreePlayScene::new(&mut self.target))
+                }
+                SceneType::Metronome => {
+                    Box::new(metronome_scene::MetronomeScene::new(&mut self.target))
+                }
+                SceneType::MainMenu => Box::new(menu_scene::MenuScene::new(&mut self.target)),
+            };
+
+            if let Some(transition) = self.transition.as_mut() {
+                transition.loaded();
+            }
+
+            // Building the scene shouldn't count as time of the fade in
+            self.last_time = std::time::Instant::now();
+        }
+
+        // Old scene is hidden behind the loading screen, no need to keep it going
+        let covered = self
+            .transition
+            .as_ref()
+            .map_or(false, Transition::is_covering);
+        if !covered {
+            self.game_scene.update(&mut self.target, delta);
+        }
+        self.device_watcher.update(&mut self.target, delta);
+
+        self.target
+            .output_manager
+            .borrow_mut()
+            .set_volume(self.target.config.master_volume);
+
+        if self
+            .transition
+            .as_ref()
+            .map_or(false, Transition::is_finished)
+        {
+            self.transition = None;
+        }
 
         #[cfg(debug_assertions)]
-        self.target.text_renderer.queue_fps(self.fps_timer.avg());
+        if !self.target.config.clean_mode {
+            self.target.text_renderer.queue_fps(self.fps_timer.avg());
+        }
     }
 
     pub fn render(&mut self) {
@@ -134,16 +388,25 @@ impl Neothesia {
             }
         };
 
-        let view = &frame
+        let surface_view = &frame
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
+        let view = match self.hdr.as_ref() {
+            Some(hdr) => hdr.view(),
+            None => surface_view,
+        };
+
         self.target
             .gpu
-            .clear(view, self.target.config.background_color.into());
+            .clear(view, self.target.config.themed_background_color().into());
 
         self.game_scene.render(&mut self.target, view);
 
+        if let Some(transition) = self.transition.as_mut() {
+            transition.render(&mut self.target, view);
+        }
+
         self.target.text_renderer.render(
             (
                 self.target.window_state.logical_size.width,
@@ -153,6 +416,10 @@ impl Neothesia {
             view,
         );
 
+        if let Some(hdr) = self.hdr.as_ref() {
+            hdr.present(&mut self.target.gpu.encoder, surface_view);
+        }
+
         self.target.gpu.submit();
         frame.present();
     }
@@ -164,28 +431,53 @@ fn main() {
         height: 720.0,
     });
 
-    let (event_loop, target, surface) = init(builder);
+    let (event_loop, target, surface, instance) = init(builder);
 
-    let mut app = Neothesia::new(target, surface);
+    let mut app = Neothesia::new(target, surface, instance);
 
     // Investigate:
     // https://github.com/gfx-rs/wgpu-rs/pull/306
 
-    event_loop.run(move |event, _, control_flow| {
+    event_loop.run(move |event, event_loop, control_flow| {
         use winit::event::Event;
+
+        let settings_window = app.settings_window.as_ref().map(|window| window.id());
+
         match &event {
             Event::UserEvent(event) => {
-                app.neothesia_event(event, control_flow);
+                app.neothesia_event(event, event_loop, control_flow);
             }
             Event::MainEventsCleared => {
+                if let Some(next_frame) = app.next_frame() {
+                    if std::time::Instant::now() < next_frame {
+                        *control_flow = ControlFlow::WaitUntil(next_frame);
+                        return;
+                    }
+                }
+                *control_flow = ControlFlow::Poll;
+
+                app.poll_global_hotkeys();
+                app.target.export_queue.update();
                 app.game_scene.main_events_cleared(&mut app.target);
 
+                if let Some(window) = app.settings_window.as_mut() {
+                    window.main_events_cleared(&mut app.target);
+                }
+
                 app.update();
                 app.target.window.request_redraw();
             }
+            Event::WindowEvent { event, window_id } if Some(*window_id) == settings_window => {
+                app.settings_window_event(event);
+            }
             Event::WindowEvent { event, .. } => {
                 app.window_event(event, control_flow);
             }
+            Event::RedrawRequested(window_id) if Some(*window_id) == settings_window => {
+                if let Some(window) = app.settings_window.as_mut() {
+                    window.render(&mut app.target);
+                }
+            }
             Event::RedrawRequested(_) => {
                 app.render();
             }
@@ -194,15 +486,25 @@ fn main() {
     });
 }
 
-pub fn init(builder: winit::window::WindowBuilder) -> (EventLoop<NeothesiaEvent>, Target, Surface) {
+pub fn init(
+    builder: winit::window::WindowBuilder,
+) -> (EventLoop<NeothesiaEvent>, Target, Surface, wgpu::Instance) {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("neothesia=info"))
         .init();
 
+    if let Some(dir) = neothesia::utils::resources::portable_dir() {
+        log::info!(
+            "Portable mode, settings and data are kept in {}",
+            dir.display()
+        );
+    }
+
     let event_loop = EventLoopBuilder::with_user_event().build();
     let proxy = event_loop.create_proxy();
 
     let builder = builder
         .with_title("Neothesia")
+        .with_visible(false)
         .with_theme(Some(winit::window::Theme::Dark));
 
     #[cfg(target_os = "windows")]
@@ -225,5 +527,5 @@ pub fn init(builder: winit::window::WindowBuilder) -> (EventLoop<NeothesiaEvent>
 
     let target = Target::new(window, window_state, proxy, gpu);
 
-    (event_loop, target, surface)
+    (event_loop, target, surface, instance)
 }
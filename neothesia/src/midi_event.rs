@@ -1,3 +1,4 @@
+use midi_file::midly::{num::u7, MidiMessage};
 use num::FromPrimitive;
 
 #[derive(Clone, Copy, Debug)]
@@ -12,6 +13,47 @@ pub enum MidiEvent {
         channel: u8,
         key: u8,
     },
+    /// Control change, like the sustain pedal
+    Controller {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+}
+
+impl MidiEvent {
+    /// Channel and message of the event, as they are in MIDI files
+    pub fn channel_message(&self) -> (u8, MidiMessage) {
+        match *self {
+            MidiEvent::NoteOn {
+                channel, key, vel, ..
+            } => (
+                channel,
+                MidiMessage::NoteOn {
+                    key: u7::new(key),
+                    vel: u7::new(vel),
+                },
+            ),
+            MidiEvent::NoteOff { channel, key } => (
+                channel,
+                MidiMessage::NoteOff {
+                    key: u7::new(key),
+                    vel: u7::new(0),
+                },
+            ),
+            MidiEvent::Controller {
+                channel,
+                controller,
+                value,
+            } => (
+                channel,
+                MidiMessage::Controller {
+                    controller: u7::new(controller),
+                    value: u7::new(value),
+                },
+            ),
+        }
+    }
 }
 
 impl From<MidiEvent> for midi::Message {
@@ -23,6 +65,15 @@ impl From<MidiEvent> for midi::Message {
             MidiEvent::NoteOff { channel, key } => {
                 midi::Message::NoteOff(midi::Channel::from_u8(channel).unwrap(), key, 0)
             }
+            MidiEvent::Controller {
+                channel,
+                controller,
+                value,
+            } => midi::Message::ControlChange(
+                midi::Channel::from_u8(channel).unwrap(),
+                controller,
+                value,
+            ),
         }
     }
 }
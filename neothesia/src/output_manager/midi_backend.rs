@@ -39,6 +39,11 @@ impl MidiBackend {
         outs
     }
 
+    /// Whether the device of `port` is still there, it may be listed under another id
+    pub fn has_output(&self, port: &MidiPortInfo) -> bool {
+        self.manager.outputs().contains(&port.port)
+    }
+
     pub fn new_output_connection(port: &MidiPortInfo) -> Option<MidiOutputConnection> {
         midi_io::MidiOutputManager::connect_output(port.port.clone())
             .map(MidiOutputConnection::from)
@@ -78,6 +83,10 @@ impl OutputConnection for MidiOutputConnection {
         }
     }
 
+    fn sysex(&mut self, data: &[u8]) {
+        self.conn.send(data).ok();
+    }
+
     fn stop_all(&mut self) {
         for note in std::mem::take(&mut self.active_notes).iter() {
             use midi::utils::{mask7, status_byte};
@@ -7,9 +7,17 @@ mod synth_backend;
 #[cfg(feature = "synth")]
 use synth_backend::SynthBackend;
 
+use crate::device_watcher::{DeviceChange, DeviceKind};
+use neothesia_core::utils::spectrum::AudioTap;
+pub use neothesia_engine::{
+    DummyOutput, MasterVolume, OutputConnection, OutputRemap, OutputSink, SharedOutput,
+};
+
 use std::{
+    collections::HashMap,
     fmt::{self, Display, Formatter},
     path::PathBuf,
+    sync::{Arc, Mutex},
 };
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -31,23 +39,36 @@ impl Display for OutputDescriptor {
     }
 }
 
-pub trait OutputConnection {
-    fn midi_event(&mut self, _msg: &midi_file::MidiEvent) {}
-    fn stop_all(&mut self) {}
+/// Built-in synth sound override of a single track
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TrackSoundFont {
+    /// SoundFont layered under the main one, `None` means the main SoundFont
+    pub path: Option<PathBuf>,
+    /// Program forced on the track, `None` keeps program changes from the file
+    pub program: Option<u8>,
 }
 
-struct DummyOutput {}
-impl OutputConnection for DummyOutput {}
-
 pub struct OutputManager {
     #[cfg(feature = "synth")]
     synth_backend: Option<SynthBackend>,
     midi_backend: Option<MidiBackend>,
 
-    output_connection: (OutputDescriptor, Box<dyn OutputConnection>),
+    descriptor: OutputDescriptor,
+    output: SharedOutput,
+    /// Audio stream of the synth connection, it has to stay on the main thread
+    #[cfg(feature = "synth")]
+    synth_stream: Option<cpal::Stream>,
+    /// Output of the synth connection, for visualizations
+    audio_tap: Option<AudioTap>,
 
     pub selected_output_id: Option<usize>,
     pub selected_font_path: Option<PathBuf>,
+
+    /// Device of the MIDI output went away, until it's connected to again
+    lost: bool,
+
+    track_soundfonts: HashMap<usize, TrackSoundFont>,
+    track_soundfonts_changed: bool,
 }
 
 impl Default for OutputManager {
@@ -80,14 +101,42 @@ impl OutputManager {
             synth_backend,
             midi_backend,
 
-            output_connection: (OutputDescriptor::DummyOutput, Box::new(DummyOutput {})),
+            descriptor: OutputDescriptor::DummyOutput,
+            output: Arc::new(Mutex::new(OutputSink::new(Box::new(DummyOutput)))),
+            #[cfg(feature = "synth")]
+            synth_stream: None,
+            audio_tap: None,
             selected_output_id: None,
             selected_font_path: None,
+
+            lost: false,
+
+            track_soundfonts: HashMap::new(),
+            track_soundfonts_changed: false,
         }
     }
 
     pub fn current_output(&self) -> &OutputDescriptor {
-        &self.output_connection.0
+        &self.descriptor
+    }
+
+    pub fn shared_output(&self) -> SharedOutput {
+        self.output.clone()
+    }
+
+    /// `None` unless the built-in synth is connected
+    pub fn audio_tap(&self) -> Option<AudioTap> {
+        self.audio_tap.clone()
+    }
+
+    fn output(&self) -> std::sync::MutexGuard<OutputSink> {
+        self.output.lock().unwrap()
+    }
+
+    fn set_connection(&mut self, desc: OutputDescriptor, connection: Box<dyn OutputConnection>) {
+        self.descriptor = desc;
+        self.lost = false;
+        self.output().set_connection(connection);
     }
 
     pub fn outputs(&self) -> Vec<OutputDescriptor> {
@@ -106,42 +155,160 @@ impl OutputManager {
         outs
     }
 
+    fn is_synth_connected(&self) -> bool {
+        #[cfg(feature = "synth")]
+        return matches!(self.descriptor, OutputDescriptor::Synth(_));
+
+        #[cfg(not(feature = "synth"))]
+        return false;
+    }
+
+    pub fn track_soundfonts(&self) -> &HashMap<usize, TrackSoundFont> {
+        &self.track_soundfonts
+    }
+
+    /// Takes effect on the next synth connection
+    pub fn set_track_soundfonts(&mut self, fonts: HashMap<usize, TrackSoundFont>) {
+        if fonts != self.track_soundfonts {
+            self.track_soundfonts = fonts;
+            self.track_soundfonts_changed = true;
+        }
+    }
+
     pub fn connect(&mut self, desc: OutputDescriptor) {
-        if desc != self.output_connection.0 {
+        let synth_changed = self.track_soundfonts_changed && self.is_synth_connected();
+
+        if desc != self.descriptor || synth_changed {
             match desc {
                 #[cfg(feature = "synth")]
                 OutputDescriptor::Synth(ref font) => {
                     if let Some(ref mut synth) = self.synth_backend {
-                        if let Some(font) = font.clone() {
-                            self.output_connection =
-                                (desc, Box::new(synth.new_output_connection(&font)));
-                            self.selected_font_path = Some(font);
-                        } else if let Some(path) = crate::utils::resources::default_sf2() {
-                            if path.exists() {
-                                self.output_connection =
-                                    (desc, Box::new(synth.new_output_connection(&path)));
-                                self.selected_font_path = Some(path);
-                            }
+                        let path = font
+                            .clone()
+                            .or_else(crate::utils::resources::default_sf2)
+                            .filter(|path| font.is_some() || path.exists());
+
+                        if let Some(path) = path {
+                            let (stream, conn, tap) =
+                                synth.new_output_connection(&path, &self.track_soundfonts);
+                            self.set_connection(desc, Box::new(conn));
+                            self.synth_stream = Some(stream);
+                            self.audio_tap = Some(tap);
+                            self.selected_font_path = Some(path);
                         }
+                        self.track_soundfonts_changed = false;
                     }
                 }
                 OutputDescriptor::MidiOut(ref info) => {
                     if let Some(conn) = MidiBackend::new_output_connection(info) {
-                        self.output_connection = (desc, Box::new(conn));
+                        self.set_connection(desc, Box::new(conn));
+                        self.audio_tap = None;
+                        #[cfg(feature = "synth")]
+                        {
+                            self.synth_stream = None;
+                        }
                     }
                 }
                 OutputDescriptor::DummyOutput => {
-                    self.output_connection = (desc, Box::new(DummyOutput {}));
+                    self.set_connection(desc, Box::new(DummyOutput));
+                    self.audio_tap = None;
+                    #[cfg(feature = "synth")]
+                    {
+                        self.synth_stream = None;
+                    }
                 }
             }
         }
     }
 
+    /// Notices the device of the MIDI output going away, and connects to it again once
+    /// it's back
+    pub fn poll_device(&mut self) -> Option<DeviceChange> {
+        let info = match &self.descriptor {
+            OutputDescriptor::MidiOut(info) => info.clone(),
+            _ => return None,
+        };
+        let available = self.midi_backend.as_ref()?.has_output(&info);
+
+        let connected = if self.lost {
+            if !available {
+                return None;
+            }
+            let conn = MidiBackend::new_output_connection(&info)?;
+            self.set_connection(OutputDescriptor::MidiOut(info.clone()), Box::new(conn));
+            true
+        } else if !available {
+            self.lost = true;
+            false
+        } else {
+            return None;
+        };
+
+        Some(DeviceChange {
+            kind: DeviceKind::Output,
+            name: info.to_string(),
+            connected,
+        })
+    }
+
+    pub fn set_remap(&mut self, remap: OutputRemap) {
+        self.output().set_remap(remap);
+    }
+
+    pub fn set_volume(&mut self, volume: MasterVolume) {
+        self.output().set_volume(volume);
+    }
+
     pub fn midi_event(&mut self, msg: &midi_file::MidiEvent) {
-        self.output_connection.1.midi_event(msg);
+        self.output().midi_event(msg);
+    }
+
+    pub fn sysex(&mut self, data: &[u8]) {
+        self.output().sysex(data);
+    }
+
+    pub fn midi_event_delayed(&mut self, msg: &midi_file::MidiEvent, delay: std::time::Duration) {
+        self.output().midi_event_delayed(msg, delay);
+    }
+
+    pub fn click(&mut self, accent: bool, volume: f32, delay: std::time::Duration) {
+        self.output().click(accent, volume, delay);
+    }
+
+    pub fn key_click(&mut self, volume: f32) {
+        self.output().key_click(volume);
     }
 
     pub fn stop_all(&mut self) {
-        self.output_connection.1.stop_all();
+        self.output().stop_all();
+    }
+}
+
+/// Extra connection to a MIDI device next to the main output, eg. for its lights
+pub fn connect_midi_output(name: &str) -> Option<midi_io::MidiOutputConnection> {
+    let manager = match midi_io::MidiOutputManager::new() {
+        Ok(manager) => manager,
+        Err(err) => {
+            log::error!("{err}");
+            return None;
+        }
+    };
+
+    let port = manager
+        .outputs()
+        .into_iter()
+        .find(|port| port.to_string() == name);
+    let port = match port {
+        Some(port) => port,
+        None => {
+            log::warn!("MIDI output {name} not found");
+            return None;
+        }
+    };
+
+    let conn = midi_io::MidiOutputManager::connect_output(port);
+    if conn.is_none() {
+        log::error!("Failed to connect to MIDI output {name}");
     }
+    conn
 }
@@ -1,12 +1,227 @@
-use std::{error::Error, path::Path, sync::mpsc::Receiver};
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+    time::Duration,
+};
 
-use crate::output_manager::{OutputConnection, OutputDescriptor};
+use crate::output_manager::{OutputConnection, OutputDescriptor, TrackSoundFont};
+use neothesia_core::utils::{click, spectrum::AudioTap};
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 
 #[cfg(all(feature = "fluid-synth", not(feature = "oxi-synth")))]
 const SAMPLES_SIZE: usize = 1410;
 
+/// Length of the gain ramp used on pause and resume, in seconds
+const FADE_TIME: f32 = 0.1;
+
+enum SynthCommand {
+    Event(oxisynth::MidiEvent),
+    /// Select `program` from `font` on the channel.
+    /// Font `0` is the main SoundFont, the rest are layers in load order
+    ProgramSelect {
+        channel: u8,
+        font: usize,
+        program: u8,
+    },
+    /// Ramp the gain down, then silence all voices
+    FadeOut,
+    /// Ramp the gain up from silence
+    FadeIn,
+    /// Metronome click, mixed in after the fade so pauses don't swallow it
+    Click {
+        accent: bool,
+        gain: f32,
+    },
+    /// Key press click, mixed in like the metronome click
+    KeyClick {
+        gain: f32,
+    },
+    /// Master volume, applied to everything that's played
+    SetGain {
+        gain: f32,
+    },
+}
+
+/// Command together with its delay in samples
+type ScheduledCommand = (u32, SynthCommand);
+
+/// Holds back commands until their sample offset is reached,
+/// so events keep their spacing within a frame
+struct Scheduler {
+    clock: u64,
+    queue: VecDeque<(u64, SynthCommand)>,
+}
+
+impl Scheduler {
+    fn new() -> Self {
+        Self {
+            clock: 0,
+            queue: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, (delay, command): ScheduledCommand) {
+        // Nothing that was queued before a reset should sound after it
+        if matches!(
+            command,
+            SynthCommand::FadeOut | SynthCommand::Event(oxisynth::MidiEvent::SystemReset)
+        ) {
+            self.queue.clear();
+        }
+
+        let due = self.clock + delay as u64;
+        let id = self.queue.partition_point(|(time, _)| *time <= due);
+        self.queue.insert(id, (due, command));
+    }
+
+    fn pop_due(&mut self) -> Option<SynthCommand> {
+        match self.queue.front() {
+            Some((due, _)) if *due <= self.clock => self.queue.pop_front().map(|(_, c)| c),
+            _ => None,
+        }
+    }
+
+    fn advance(&mut self) {
+        self.clock += 1;
+    }
+}
+
+struct Fader {
+    gain: f32,
+    target: f32,
+    step: f32,
+    /// Voices are silenced once the fade out reaches zero
+    kill_pending: bool,
+}
+
+impl Fader {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            gain: 1.0,
+            target: 1.0,
+            step: 1.0 / (sample_rate * FADE_TIME),
+            kill_pending: false,
+        }
+    }
+
+    fn fade_out(&mut self) {
+        self.target = 0.0;
+        self.kill_pending = true;
+    }
+
+    fn fade_in(&mut self) {
+        self.gain = 0.0;
+        self.target = 1.0;
+    }
+
+    fn finish(&mut self) {
+        self.kill_pending = false;
+        self.gain = 1.0;
+        self.target = 1.0;
+    }
+
+    /// A new command cuts an ongoing fade out short,
+    /// returns `true` if voices have to be silenced before handling it
+    fn interrupt(&mut self, command: &SynthCommand) -> bool {
+        let keeps_fading = matches!(
+            command,
+            SynthCommand::FadeOut
+                | SynthCommand::Click { .. }
+                | SynthCommand::KeyClick { .. }
+                | SynthCommand::SetGain { .. }
+        );
+        if self.kill_pending && !keeps_fading {
+            self.finish();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns `true` if voices have to be silenced
+    fn poll_faded_out(&mut self) -> bool {
+        if self.kill_pending && self.gain <= 0.0 {
+            self.finish();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn apply(&mut self, (l, r): (f32, f32)) -> (f32, f32) {
+        if self.gain < self.target {
+            self.gain = (self.gain + self.step).min(self.target);
+        } else if self.gain > self.target {
+            self.gain = (self.gain - self.step).max(self.target);
+        }
+
+        (l * self.gain, r * self.gain)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ClickSound {
+    Beat { accent: bool },
+    Key,
+}
+
+impl ClickSound {
+    fn sample(self, t: f32) -> f32 {
+        match self {
+            Self::Beat { accent } => click::click_sample(t, accent),
+            Self::Key => click::key_click_sample(t),
+        }
+    }
+
+    fn duration(self) -> f32 {
+        match self {
+            Self::Beat { .. } => click::CLICK_LEN,
+            Self::Key => click::KEY_CLICK_LEN,
+        }
+    }
+}
+
+/// Clicks that are still ringing
+struct ClickVoices {
+    sample_rate: f32,
+    /// Samples since the start, sound and gain of each click
+    voices: Vec<(u32, ClickSound, f32)>,
+}
+
+impl ClickVoices {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            voices: Vec::new(),
+        }
+    }
+
+    fn start(&mut self, sound: ClickSound, gain: f32) {
+        self.voices.push((0, sound, gain));
+    }
+
+    fn mix(&mut self, (l, r): (f32, f32)) -> (f32, f32) {
+        if self.voices.is_empty() {
+            return (l, r);
+        }
+
+        let mut sample = 0.0;
+        for (pos, sound, gain) in self.voices.iter_mut() {
+            sample += sound.sample(*pos as f32 / self.sample_rate) * *gain;
+            *pos += 1;
+        }
+
+        let sample_rate = self.sample_rate;
+        self.voices
+            .retain(|(pos, sound, _)| *pos < (sound.duration() * sample_rate) as u32);
+
+        (l + sample, r + sample)
+    }
+}
+
 pub struct SynthBackend {
     _host: cpal::Host,
     device: cpal::Device,
@@ -39,8 +254,10 @@ impl SynthBackend {
 
     fn run<T: cpal::SizedSample + cpal::FromSample<f32>>(
         &self,
-        rx: Receiver<oxisynth::MidiEvent>,
+        rx: Receiver<ScheduledCommand>,
         path: &Path,
+        layers: &[PathBuf],
+        tap: AudioTap,
     ) -> cpal::Stream {
         #[cfg(all(feature = "fluid-synth", not(feature = "oxi-synth")))]
         let mut next_value = {
@@ -55,12 +272,34 @@ impl SynthBackend {
                 rate.set(sample_rate as f64);
 
                 let synth = fluidlite::Synth::new(settings).unwrap();
-                synth.sfload(path, true).unwrap();
                 synth.set_gain(1.0);
 
                 synth
             };
 
+            // Layers go first, so the main font ends up on top of the stack
+            let mut fonts: Vec<u32> = layers
+                .iter()
+                .filter_map(|layer| match synth.sfload(layer, false) {
+                    Ok(id) => Some(id),
+                    Err(err) => {
+                        log::error!("Failed to load SoundFont layer {layer:?}: {err:?}");
+                        None
+                    }
+                })
+                .collect();
+            fonts.insert(0, synth.sfload(path, true).unwrap());
+
+            let silence = |synth: &fluidlite::Synth| {
+                for channel in 0..16 {
+                    synth.cc(channel, 120, 0).ok();
+                }
+            };
+
+            let mut fader = Fader::new(self.stream_config.sample_rate.0 as f32);
+            let mut clicks = ClickVoices::new(self.stream_config.sample_rate.0 as f32);
+            let mut master = 1.0;
+            let mut scheduler = Scheduler::new();
             let mut sample_clock = 0;
             let mut buff: [f32; SAMPLES_SIZE] = [0.0f32; SAMPLES_SIZE];
 
@@ -75,18 +314,53 @@ impl SynthBackend {
                     sample_clock = 0;
                 }
 
-                if let Ok(e) = rx.try_recv() {
+                while let Ok(command) = rx.try_recv() {
+                    scheduler.push(command);
+                }
+
+                while let Some(e) = scheduler.pop_due() {
+                    if fader.interrupt(&e) {
+                        silence(&synth);
+                    }
+
                     match e {
-                        oxisynth::MidiEvent::NoteOn { channel, key, vel } => {
+                        SynthCommand::Event(oxisynth::MidiEvent::NoteOn { channel, key, vel }) => {
                             synth.note_on(channel as u32, key as u32, vel as u32).ok();
                         }
-                        oxisynth::MidiEvent::NoteOff { channel, key } => {
+                        SynthCommand::Event(oxisynth::MidiEvent::NoteOff { channel, key }) => {
                             synth.note_off(channel as u32, key as u32).ok();
                         }
+                        SynthCommand::Event(_) => {}
+                        SynthCommand::ProgramSelect {
+                            channel,
+                            font,
+                            program,
+                        } => {
+                            if let Some(font) = fonts.get(font) {
+                                let bank = if channel == 9 { 128 } else { 0 };
+                                synth
+                                    .program_select(channel as u32, *font, bank, program as u32)
+                                    .ok();
+                            }
+                        }
+                        SynthCommand::FadeOut => fader.fade_out(),
+                        SynthCommand::FadeIn => fader.fade_in(),
+                        SynthCommand::Click { accent, gain } => {
+                            clicks.start(ClickSound::Beat { accent }, gain)
+                        }
+                        SynthCommand::KeyClick { gain } => clicks.start(ClickSound::Key, gain),
+                        SynthCommand::SetGain { gain } => master = gain,
                     }
                 }
 
-                (l, r)
+                scheduler.advance();
+
+                if fader.poll_faded_out() {
+                    silence(&synth);
+                }
+
+                let (l, r) = clicks.mix(fader.apply((l, r)));
+                (l * master, r * master)
             }
         };
 
@@ -101,34 +375,111 @@ impl SynthBackend {
             })
             .unwrap();
 
+            // Layers go first, so the main font ends up on top of the stack
+            let mut fonts: Vec<oxisynth::SoundFontId> = layers
+                .iter()
+                .filter_map(|layer| {
+                    let font = std::fs::File::open(layer)
+                        .map_err(|err| format!("{err}"))
+                        .and_then(|mut file| {
+                            oxisynth::SoundFont::load(&mut file).map_err(|err| format!("{err:?}"))
+                        });
+
+                    match font {
+                        Ok(font) => Some(synth.add_font(font, false)),
+                        Err(err) => {
+                            log::error!("Failed to load SoundFont layer {layer:?}: {err}");
+                            None
+                        }
+                    }
+                })
+                .collect();
+
             {
                 let mut file = std::fs::File::open(path).unwrap();
                 let font = oxisynth::SoundFont::load(&mut file).unwrap();
-                synth.add_font(font, true);
+                fonts.insert(0, synth.add_font(font, true));
             }
 
+            let silence = |synth: &mut oxisynth::Synth| {
+                for channel in 0..16 {
+                    synth
+                        .send_event(oxisynth::MidiEvent::ControlChange {
+                            channel,
+                            ctrl: 120,
+                            value: 0,
+                        })
+                        .ok();
+                }
+            };
+
+            let mut fader = Fader::new(sample_rate);
+            let mut clicks = ClickVoices::new(sample_rate);
+            let mut master = 1.0;
+            let mut scheduler = Scheduler::new();
+
             move || {
                 let (l, r) = synth.read_next();
 
-                if let Ok(event) = rx.try_recv() {
-                    synth.send_event(event).ok();
+                while let Ok(command) = rx.try_recv() {
+                    scheduler.push(command);
+                }
+
+                while let Some(command) = scheduler.pop_due() {
+                    if fader.interrupt(&command) {
+                        silence(&mut synth);
+                    }
+
+                    match command {
+                        SynthCommand::Event(event) => {
+                            synth.send_event(event).ok();
+                        }
+                        SynthCommand::ProgramSelect {
+                            channel,
+                            font,
+                            program,
+                        } => {
+                            if let Some(font) = fonts.get(font) {
+                                let bank = if channel == 9 { 128 } else { 0 };
+                                synth.program_select(channel, *font, bank, program).ok();
+                            }
+                        }
+                        SynthCommand::FadeOut => fader.fade_out(),
+                        SynthCommand::FadeIn => fader.fade_in(),
+                        SynthCommand::Click { accent, gain } => {
+                            clicks.start(ClickSound::Beat { accent }, gain)
+                        }
+                        SynthCommand::KeyClick { gain } => clicks.start(ClickSound::Key, gain),
+                        SynthCommand::SetGain { gain } => master = gain,
+                    }
+                }
+
+                scheduler.advance();
+
+                if fader.poll_faded_out() {
+                    silence(&mut synth);
                 }
 
-                (l, r)
+                let (l, r) = clicks.mix(fader.apply((l, r)));
+                (l * master, r * master)
             }
         };
 
         let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
 
         let channels = self.stream_config.channels as usize;
+        let mut tap_frames = Vec::new();
 
         let stream = self
             .device
             .build_output_stream(
                 &self.stream_config,
                 move |output: &mut [T], _: &cpal::OutputCallbackInfo| {
+                    tap_frames.clear();
+
                     for frame in output.chunks_mut(channels) {
                         let (l, r) = next_value();
+                        tap_frames.push((l, r));
 
                         let l = T::from_sample(l);
                         let r = T::from_sample(r);
@@ -139,6 +490,8 @@ impl SynthBackend {
                             *sample = channels[id % 2];
                         }
                     }
+
+                    tap.push(&tap_frames);
                 },
                 err_fn,
                 None,
@@ -149,25 +502,63 @@ impl SynthBackend {
         stream
     }
 
-    pub fn new_output_connection(&mut self, path: &Path) -> SynthOutputConnection {
-        let (tx, rx) = std::sync::mpsc::channel::<oxisynth::MidiEvent>();
-        let _stream = match self.sample_format {
-            cpal::SampleFormat::I8 => self.run::<i8>(rx, path),
-            cpal::SampleFormat::I16 => self.run::<i16>(rx, path),
-            cpal::SampleFormat::I32 => self.run::<i32>(rx, path),
-            cpal::SampleFormat::I64 => self.run::<i64>(rx, path),
+    pub fn new_output_connection(
+        &mut self,
+        path: &Path,
+        track_fonts: &HashMap<usize, TrackSoundFont>,
+    ) -> (cpal::Stream, SynthOutputConnection, AudioTap) {
+        let mut layers: Vec<PathBuf> = Vec::new();
+        let mut track_sounds = HashMap::new();
+
+        for (track_id, sound) in track_fonts {
+            let font = if let Some(layer) = &sound.path {
+                if let Some(id) = layers.iter().position(|p| p == layer) {
+                    id + 1
+                } else {
+                    layers.push(layer.clone());
+                    layers.len()
+                }
+            } else {
+                0
+            };
 
-            cpal::SampleFormat::U8 => self.run::<u8>(rx, path),
-            cpal::SampleFormat::U16 => self.run::<u16>(rx, path),
-            cpal::SampleFormat::U32 => self.run::<u32>(rx, path),
-            cpal::SampleFormat::U64 => self.run::<u64>(rx, path),
+            track_sounds.insert(
+                *track_id,
+                TrackSound {
+                    font,
+                    program: sound.program,
+                },
+            );
+        }
 
-            cpal::SampleFormat::F32 => self.run::<f32>(rx, path),
-            cpal::SampleFormat::F64 => self.run::<f64>(rx, path),
+        let (tx, rx) = std::sync::mpsc::channel::<ScheduledCommand>();
+        let tap = AudioTap::new(self.stream_config.sample_rate.0 as f32);
+        let stream = match self.sample_format {
+            cpal::SampleFormat::I8 => self.run::<i8>(rx, path, &layers, tap.clone()),
+            cpal::SampleFormat::I16 => self.run::<i16>(rx, path, &layers, tap.clone()),
+            cpal::SampleFormat::I32 => self.run::<i32>(rx, path, &layers, tap.clone()),
+            cpal::SampleFormat::I64 => self.run::<i64>(rx, path, &layers, tap.clone()),
+
+            cpal::SampleFormat::U8 => self.run::<u8>(rx, path, &layers, tap.clone()),
+            cpal::SampleFormat::U16 => self.run::<u16>(rx, path, &layers, tap.clone()),
+            cpal::SampleFormat::U32 => self.run::<u32>(rx, path, &layers, tap.clone()),
+            cpal::SampleFormat::U64 => self.run::<u64>(rx, path, &layers, tap.clone()),
+
+            cpal::SampleFormat::F32 => self.run::<f32>(rx, path, &layers, tap.clone()),
+            cpal::SampleFormat::F64 => self.run::<f64>(rx, path, &layers, tap.clone()),
             sample_format => unimplemented!("Unsupported sample format '{sample_format}'"),
         };
 
-        SynthOutputConnection { _stream, tx }
+        let conn = SynthOutputConnection {
+            tx,
+            sample_rate: self.stream_config.sample_rate.0,
+            delay: 0,
+            track_sounds,
+            channel_programs: [0; 16],
+            channel_selection: [None; 16],
+        };
+
+        (stream, conn, tap)
     }
 
     pub fn get_outputs(&self) -> Vec<OutputDescriptor> {
@@ -175,19 +566,117 @@ impl SynthBackend {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TrackSound {
+    font: usize,
+    program: Option<u8>,
+}
+
 pub struct SynthOutputConnection {
-    _stream: cpal::Stream,
-    tx: std::sync::mpsc::Sender<oxisynth::MidiEvent>,
+    tx: std::sync::mpsc::Sender<ScheduledCommand>,
+    sample_rate: u32,
+    /// Delay in samples applied to commands of the event being handled
+    delay: u32,
+
+    track_sounds: HashMap<usize, TrackSound>,
+    /// Last program requested by the file for each channel
+    channel_programs: [u8; 16],
+    /// Font and program currently forced on each channel by a track override
+    channel_selection: [Option<(usize, u8)>; 16],
+}
+
+impl SynthOutputConnection {
+    fn send(&self, command: SynthCommand) {
+        self.tx.send((self.delay, command)).ok();
+    }
+
+    fn select_program(&mut self, channel: u8, selection: Option<(usize, u8)>) {
+        let ch = channel as usize % 16;
+        if self.channel_selection[ch] == selection {
+            return;
+        }
+
+        // Going back to the main font when a channel is no longer overridden
+        let (font, program) = selection.unwrap_or((0, self.channel_programs[ch]));
+        self.send(SynthCommand::ProgramSelect {
+            channel,
+            font,
+            program,
+        });
+        self.channel_selection[ch] = selection;
+    }
 }
 
 impl OutputConnection for SynthOutputConnection {
     fn midi_event(&mut self, msg: &midi_file::MidiEvent) {
+        use midi_file::midly::MidiMessage;
+
+        let ch = msg.channel as usize % 16;
+        let sound = self.track_sounds.get(&msg.track_id).copied();
+
+        match (msg.message, sound) {
+            (MidiMessage::ProgramChange { program }, sound) => {
+                self.channel_programs[ch] = program.as_int();
+
+                if let Some(sound) = sound {
+                    let program = sound.program.unwrap_or(program.as_int());
+                    self.select_program(msg.channel, Some((sound.font, program)));
+                    return;
+                } else if self.channel_selection[ch].is_some() {
+                    self.select_program(msg.channel, None);
+                    return;
+                }
+            }
+            (MidiMessage::NoteOn { .. }, Some(sound)) => {
+                let program = sound.program.unwrap_or(self.channel_programs[ch]);
+                self.select_program(msg.channel, Some((sound.font, program)));
+            }
+            (MidiMessage::NoteOn { .. }, None) => {
+                self.select_program(msg.channel, None);
+            }
+            _ => {}
+        }
+
         let event = libmidi_to_oxisynth_event(msg);
-        self.tx.send(event).ok();
+        self.send(SynthCommand::Event(event));
+    }
+
+    fn midi_event_delayed(&mut self, msg: &midi_file::MidiEvent, delay: Duration) {
+        self.delay = (delay.as_secs_f64() * self.sample_rate as f64) as u32;
+        self.midi_event(msg);
+        self.delay = 0;
+    }
+
+    fn fade_out(&mut self) {
+        self.send(SynthCommand::FadeOut);
+    }
+
+    fn fade_in(&mut self) {
+        self.send(SynthCommand::FadeIn);
+    }
+
+    fn click(&mut self, accent: bool, volume: f32, delay: Duration) {
+        self.delay = (delay.as_secs_f64() * self.sample_rate as f64) as u32;
+        self.send(SynthCommand::Click {
+            accent,
+            gain: volume,
+        });
+        self.delay = 0;
+    }
+
+    fn key_click(&mut self, volume: f32) {
+        self.send(SynthCommand::KeyClick { gain: volume });
+    }
+
+    fn set_gain(&mut self, gain: f32) -> bool {
+        self.send(SynthCommand::SetGain { gain });
+        true
     }
 
     fn stop_all(&mut self) {
-        self.tx.send(oxisynth::MidiEvent::SystemReset).ok();
+        self.send(SynthCommand::Event(oxisynth::MidiEvent::SystemReset));
+        self.channel_programs = [0; 16];
+        self.channel_selection = [None; 16];
     }
 }
 
@@ -0,0 +1,305 @@
+use std::{collections::HashSet, time::Duration};
+
+use midi_file::midly::{num::u7, MidiMessage};
+
+/// Event of a layer, at its time in the loop
+type LoopEvent = (Duration, u8, MidiMessage);
+
+/// Layer being recorded
+struct Take {
+    events: Vec<LoopEvent>,
+    /// Notes still held, released when the take stops
+    held: HashSet<(u8, u8)>,
+}
+
+impl Take {
+    fn new() -> Self {
+        Self {
+            events: Vec::new(),
+            held: HashSet::new(),
+        }
+    }
+
+    fn push(&mut self, time: Duration, channel: u8, message: MidiMessage) {
+        match message {
+            MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                self.held.insert((channel, key.as_int()));
+            }
+            MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                self.held.remove(&(channel, key.as_int()));
+            }
+            _ => {}
+        }
+        self.events.push((time, channel, message));
+    }
+
+    /// Events of the take sorted by time, held notes are released at `end`
+    fn finish(mut self, end: Duration) -> Vec<LoopEvent> {
+        let mut held: Vec<_> = self.held.drain().collect();
+        held.sort_unstable();
+        for (channel, key) in held {
+            self.events.push((end, channel, note_off(key)));
+        }
+
+        // Stable, events at the same time keep the order they were played in
+        self.events.sort_by_key(|(time, _, _)| *time);
+        self.events
+    }
+}
+
+fn note_off(key: u8) -> MidiMessage {
+    MidiMessage::NoteOff {
+        key: u7::new(key),
+        vel: u7::new(0),
+    }
+}
+
+/// Phrases played in free play, looped on the output. The first layer sets the length of
+/// the loop, the layers recorded over it later keep playing along with it
+#[derive(Default)]
+pub struct Looper {
+    layers: Vec<Vec<LoopEvent>>,
+    take: Option<Take>,
+    /// Set once the first layer is done
+    length: Option<Duration>,
+    /// Time in the loop, or into the first layer while it's recorded
+    position: Duration,
+}
+
+impl Looper {
+    pub fn is_recording(&self) -> bool {
+        self.take.is_some()
+    }
+
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn length(&self) -> Option<Duration> {
+        self.length
+    }
+
+    /// Starts recording a layer, or stops the one being recorded and loops it
+    pub fn toggle_recording(&mut self) {
+        let take = match self.take.take() {
+            Some(take) => take,
+            None => {
+                if self.length.is_none() {
+                    self.position = Duration::ZERO;
+                }
+                self.take = Some(Take::new());
+                return;
+            }
+        };
+
+        match self.length {
+            Some(length) => {
+                // Released just before the loop starts over, not on top of its first notes
+                let end = self
+                    .position
+                    .min(length.saturating_sub(Duration::from_millis(1)));
+                let events = take.finish(end);
+                if !events.is_empty() {
+                    self.layers.push(events);
+                }
+            }
+            None => {
+                if take.events.is_empty() || self.position.is_zero() {
+                    return;
+                }
+
+                let length = self.position;
+                self.layers
+                    .push(take.finish(length.saturating_sub(Duration::from_millis(1))));
+                self.length = Some(length);
+                self.position = Duration::ZERO;
+            }
+        }
+    }
+
+    /// Adds an event played by the user to the layer being recorded
+    pub fn record(&mut self, channel: u8, message: MidiMessage) {
+        if let Some(take) = self.take.as_mut() {
+            take.push(self.position, channel, message);
+        }
+    }
+
+    /// Moves the loop on by `delta`, returns the events of the layers that are due
+    pub fn update(&mut self, delta: Duration) -> Vec<(u8, MidiMessage)> {
+        let length = match self.length {
+            Some(length) => length,
+            None => {
+                if self.take.is_some() {
+                    self.position += delta;
+                }
+                return Vec::new();
+            }
+        };
+
+        let from = self.position;
+        let to = from + delta;
+
+        let mut due = Vec::new();
+        let mut push_range = |from: Duration, to: Duration| {
+            for layer in self.layers.iter() {
+                let first = layer.partition_point(|(time, _, _)| *time < from);
+                let last = layer.partition_point(|(time, _, _)| *time < to);
+                due.extend(
+                    layer[first..last]
+                        .iter()
+                        .map(|(_, channel, message)| (*channel, *message)),
+                );
+            }
+        };
+
+        if to < length {
+            push_range(from, to);
+            self.position = to;
+        } else {
+            // Starts over, a frame longer than the loop only plays it once
+            push_range(from, length);
+            let to = Duration::from_secs_f64((to - length).as_secs_f64() % length.as_secs_f64());
+            push_range(Duration::ZERO, to);
+            self.position = to;
+        }
+
+        due
+    }
+
+    /// Drops the last layer, returns note offs for the notes it may have left sounding
+    pub fn undo(&mut self) -> Vec<(u8, MidiMessage)> {
+        let layer = self.layers.pop().unwrap_or_default();
+        if self.layers.is_empty() && self.take.is_none() {
+            self.length = None;
+            self.position = Duration::ZERO;
+        }
+        release(&layer)
+    }
+
+    /// Drops every layer, returns note offs for the notes they may have left sounding
+    pub fn clear(&mut self) -> Vec<(u8, MidiMessage)> {
+        let layers = std::mem::take(&mut self.layers);
+        *self = Self::default();
+        layers.iter().flat_map(|layer| release(layer)).collect()
+    }
+
+    /// Layers as recordings, for saving them as the tracks of a file
+    pub fn recordings(&self) -> Vec<midi_file::Recording> {
+        self.layers
+            .iter()
+            .map(|layer| {
+                let mut recording = midi_file::Recording::new();
+                for (time, channel, message) in layer.iter() {
+                    recording.push(*time, *channel, *message);
+                }
+                recording
+            })
+            .collect()
+    }
+}
+
+/// Note offs for every note of `layer`
+fn release(layer: &[LoopEvent]) -> Vec<(u8, MidiMessage)> {
+    let keys: HashSet<(u8, u8)> = layer
+        .iter()
+        .filter_map(|(_, channel, message)| match message {
+            MidiMessage::NoteOn { key, .. } => Some((*channel, key.as_int())),
+            _ => None,
+        })
+        .collect();
+
+    keys.into_iter()
+        .map(|(channel, key)| (channel, note_off(key)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn note_on(key: u8) -> MidiMessage {
+        MidiMessage::NoteOn {
+            key: u7::new(key),
+            vel: u7::new(100),
+        }
+    }
+
+    /// Looper with a 1s loop of one note at its start, released at 500ms
+    fn looper() -> Looper {
+        let mut looper = Looper::default();
+        looper.toggle_recording();
+        looper.record(0, note_on(60));
+        looper.update(ms(500));
+        looper.record(0, note_off(60));
+        looper.update(ms(500));
+        looper.toggle_recording();
+        looper
+    }
+
+    #[test]
+    fn first_layer_sets_the_length() {
+        let looper = looper();
+        assert_eq!(looper.length(), Some(ms(1000)));
+        assert_eq!(looper.layer_count(), 1);
+        assert!(!looper.is_recording());
+    }
+
+    #[test]
+    fn update_wraps_around_the_loop() {
+        let mut looper = looper();
+
+        assert_eq!(looper.update(ms(400)), vec![(0, note_on(60))]);
+        assert_eq!(looper.update(ms(400)), vec![(0, note_off(60))]);
+        assert!(looper.update(ms(100)).is_empty());
+
+        // Crosses the end, the note off of the previous time round isn't played again
+        assert_eq!(looper.update(ms(200)), vec![(0, note_on(60))]);
+        assert!(looper.update(ms(300)).is_empty());
+        assert_eq!(looper.update(ms(200)), vec![(0, note_off(60))]);
+    }
+
+    #[test]
+    fn overdub_plays_along_with_the_first_layer() {
+        let mut looper = looper();
+
+        looper.toggle_recording();
+        looper.update(ms(250));
+        looper.record(0, note_on(64));
+        looper.update(ms(500));
+        // Still held when the take stops, released where it stopped
+        looper.toggle_recording();
+        assert_eq!(looper.layer_count(), 2);
+        assert_eq!(looper.length(), Some(ms(1000)));
+
+        assert_eq!(looper.update(ms(250)), vec![(0, note_off(64))]);
+        assert_eq!(looper.update(ms(100)), vec![(0, note_on(60))]);
+        assert_eq!(looper.update(ms(200)), vec![(0, note_on(64))]);
+        assert_eq!(looper.update(ms(300)), vec![(0, note_off(60))]);
+        assert_eq!(looper.update(ms(200)), vec![(0, note_off(64))]);
+    }
+
+    #[test]
+    fn undo_and_clear() {
+        let mut looper = looper();
+        looper.toggle_recording();
+        looper.record(0, note_on(64));
+        looper.update(ms(100));
+        looper.toggle_recording();
+
+        assert_eq!(looper.undo(), vec![(0, note_off(64))]);
+        assert_eq!(looper.layer_count(), 1);
+        assert_eq!(looper.length(), Some(ms(1000)));
+
+        assert_eq!(looper.clear(), vec![(0, note_off(60))]);
+        assert_eq!(looper.layer_count(), 0);
+        assert_eq!(looper.length(), None);
+        assert!(looper.update(ms(2000)).is_empty());
+
+        // Nothing left to undo
+        assert!(looper.undo().is_empty());
+    }
+}
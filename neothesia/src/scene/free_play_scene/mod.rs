@@ -0,0 +1,328 @@
+use std::{
+    collections::BTreeSet,
+    time::{Duration, Instant},
+};
+
+use midi_file::midly::MidiMessage;
+use winit::event::WindowEvent;
+
+use super::{playing_scene::keyboard_events, Scene, SceneType};
+use crate::{
+    accessibility::Item, event_bus::AppEvent, input_recorder::InputRecorder, midi_event::MidiEvent,
+    render::KeyboardRenderer, target::Target, NeothesiaEvent,
+};
+
+mod looper;
+mod rising_notes;
+use looper::Looper;
+use rising_notes::RisingNotes;
+
+/// How long the path of a saved jam stays on screen
+const SAVED_MESSAGE_TIME: Duration = Duration::from_secs(4);
+
+fn get_layout(width: f32, height: f32) -> piano_math::KeyboardLayout {
+    let range = piano_math::KeyboardRange::standard_88_keys();
+    let white_count = range.white_count();
+    let neutral_width = width / white_count as f32;
+    let neutral_height = height * 0.2;
+
+    piano_math::KeyboardLayout::from_range(neutral_width, neutral_height, range)
+}
+
+/// Playing without a song, the input is played on the output and rises up from the keyboard.
+/// The chord held is named above it, and the jam is saved to a `.mid` file on request.
+/// Phrases can be looped and layered with the [`Looper`]
+pub struct FreePlayScene {
+    keyboard_layout: piano_math::KeyboardLayout,
+    piano_keyboard: KeyboardRenderer,
+    notes: RisingNotes,
+    /// Keys held down, for naming the chord
+    held: BTreeSet<u8>,
+    jam: InputRecorder,
+    /// First note of the jam, it starts on it
+    jam_start: Option<Instant>,
+    /// Shown until the instant passes
+    message: Option<(String, Instant)>,
+    looper: Looper,
+    /// Saves the layers of the looper
+    loop_recorder: InputRecorder,
+}
+
+impl FreePlayScene {
+    pub fn new(target: &mut Target) -> Self {
+        let keyboard_layout = get_layout(
+            target.window_state.logical_size.width,
+            target.window_state.logical_size.height,
+        );
+
+        let mut piano_keyboard = KeyboardRenderer::new(
+            &target.gpu,
+            &target.transform_uniform,
+            keyboard_layout.clone(),
+            1,
+        );
+        piano_keyboard.position_on_bottom_of_parent(target.window_state.logical_size.height);
+        piano_keyboard.set_key_travel(target.config.key_travel);
+        piano_keyboard.set_key_colors(target.config.themed_key_colors());
+        piano_keyboard.set_scale_overlay(&target.config.scale_overlay);
+
+        target.accessibility.set_items(vec![
+            Item::text("Free play, the keyboard plays on the output"),
+            Item::text("S saves what was played as a MIDI file"),
+            Item::text("L records a phrase to loop, and more layers over it"),
+            Item::text("Backspace drops the last layer, Delete all of them"),
+            Item::text("E saves the layers as the tracks of a MIDI file"),
+            Item::text("Escape goes back to the menu"),
+        ]);
+
+        Self {
+            keyboard_layout,
+            piano_keyboard,
+            notes: RisingNotes::new(target),
+            held: BTreeSet::new(),
+            jam: InputRecorder::on_request(target, "jam"),
+            jam_start: None,
+            message: None,
+            looper: Looper::default(),
+            loop_recorder: InputRecorder::on_request(target, "loop"),
+        }
+    }
+
+    fn show_message(&mut self, message: String) {
+        self.message = Some((message, Instant::now() + SAVED_MESSAGE_TIME));
+    }
+
+    fn save_loop(&mut self) {
+        let message = if self.looper.layer_count() == 0 {
+            String::from("No layers to save yet")
+        } else {
+            match self.loop_recorder.save_layers(&self.looper.recordings()) {
+                Some(path) => format!("Saved to {}", path.display()),
+                None => String::from("Failed to save"),
+            }
+        };
+        self.show_message(message);
+    }
+
+    /// Plays events of the looper on the output, they rise up from the keyboard too
+    fn play_loop_events(&mut self, target: &Target, events: Vec<(u8, MidiMessage)>) {
+        for (channel, message) in events {
+            match message {
+                MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                    self.notes.note_on(key.as_int(), vel.as_int());
+                }
+                MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                    self.notes.note_off(key.as_int());
+                }
+                _ => {}
+            }
+
+            target
+                .output_manager
+                .borrow_mut()
+                .midi_event(&midi_file::MidiEvent {
+                    channel,
+                    delta: 0,
+                    timestamp: Duration::ZERO,
+                    message,
+                    track_id: usize::MAX,
+                    track_color_id: 0,
+                });
+        }
+    }
+
+    fn save_jam(&mut self) {
+        let message = if self.jam.is_empty() {
+            String::from("Nothing to save yet")
+        } else {
+            match self.jam.save() {
+                Some(path) => format!("Saved to {}", path.display()),
+                None => String::from("Failed to save"),
+            }
+        };
+        self.jam_start = None;
+        self.show_message(message);
+    }
+
+    /// Name of the chord held, and what the jam recording and the looper are up to
+    fn queue_text(&mut self, target: &mut Target) {
+        let keys: Vec<u8> = self.held.iter().copied().collect();
+        let flats = target.config.note_labels.flats;
+        let x = target.window_state.logical_size.width / 2.0;
+
+        if let Some(chord) = piano_math::chord::identify(&keys) {
+            target.text_renderer.queue_text(wgpu_glyph::Section {
+                screen_position: (x, 60.0),
+                text: vec![wgpu_glyph::Text::new(&chord.name(flats))
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(64.0)],
+                layout: wgpu_glyph::Layout::default_single_line()
+                    .h_align(wgpu_glyph::HorizontalAlign::Center),
+                ..Default::default()
+            });
+        }
+
+        if self
+            .message
+            .as_ref()
+            .map_or(false, |(_, until)| Instant::now() >= *until)
+        {
+            self.message = None;
+        }
+        let status = match self.message.as_ref() {
+            Some((message, _)) => message.clone(),
+            None if self.jam_start.is_some() => String::from("Recording, S saves the jam"),
+            None => String::from("Play to start recording"),
+        };
+
+        target.text_renderer.queue_text(wgpu_glyph::Section {
+            screen_position: (x, 10.0),
+            text: vec![wgpu_glyph::Text::new(&status)
+                .with_color([0.7, 0.7, 0.7, 1.0])
+                .with_scale(18.0)],
+            layout: wgpu_glyph::Layout::default_single_line()
+                .h_align(wgpu_glyph::HorizontalAlign::Center),
+            ..Default::default()
+        });
+
+        let layers = self.looper.layer_count();
+        let looper = match self.looper.length() {
+            None if self.looper.is_recording() => {
+                String::from("Loop: Recording the first layer, L starts looping it")
+            }
+            None => String::from("Loop: L records a phrase to loop"),
+            Some(length) => format!(
+                "Loop: {layers} layer{}, {:.1} s, {}",
+                if layers == 1 { "" } else { "s" },
+                length.as_secs_f32(),
+                if self.looper.is_recording() {
+                    "Recording a layer, L stops"
+                } else {
+                    "L records a layer"
+                }
+            ),
+        };
+
+        target.text_renderer.queue_text(wgpu_glyph::Section {
+            screen_position: (x, 32.0),
+            text: vec![wgpu_glyph::Text::new(&looper)
+                .with_color([0.7, 0.7, 0.7, 1.0])
+                .with_scale(18.0)],
+            layout: wgpu_glyph::Layout::default_single_line()
+                .h_align(wgpu_glyph::HorizontalAlign::Center),
+            ..Default::default()
+        });
+    }
+}
+
+impl Scene for FreePlayScene {
+    fn scene_type(&self) -> SceneType {
+        SceneType::FreePlay
+    }
+
+    fn resize(&mut self, target: &mut Target) {
+        self.keyboard_layout = get_layout(
+            target.window_state.logical_size.width,
+            target.window_state.logical_size.height,
+        );
+
+        self.piano_keyboard.set_layout(self.keyboard_layout.clone());
+        self.piano_keyboard
+            .position_on_bottom_of_parent(target.window_state.logical_size.height);
+    }
+
+    fn update(&mut self, target: &mut Target, delta: Duration) {
+        let due = self.looper.update(delta);
+        self.play_loop_events(target, due);
+
+        let top = self.piano_keyboard.pos().y;
+        self.notes.update(target, &self.keyboard_layout, top, delta);
+
+        self.piano_keyboard.animate(delta);
+        self.piano_keyboard
+            .set_key_colors(target.config.themed_key_colors());
+        self.piano_keyboard
+            .set_scale_overlay(&target.config.scale_overlay);
+        self.piano_keyboard
+            .update(&target.gpu.queue, target.text_renderer.glyph_brush());
+        self.queue_text(target);
+    }
+
+    fn render(&mut self, target: &mut Target, view: &wgpu::TextureView) {
+        let mut render_pass = target
+            .gpu
+            .encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+        self.notes
+            .render(&target.transform_uniform, &mut render_pass);
+        self.piano_keyboard
+            .render(&target.transform_uniform, &mut render_pass);
+    }
+
+    fn window_event(&mut self, target: &mut Target, event: &WindowEvent) {
+        use winit::event::{ElementState, VirtualKeyCode};
+
+        if let WindowEvent::KeyboardInput { input, .. } = event {
+            if input.state != ElementState::Released {
+                return;
+            }
+
+            match input.virtual_keycode {
+                Some(VirtualKeyCode::Escape) => {
+                    let release = self.looper.clear();
+                    self.play_loop_events(target, release);
+                    target.proxy.send_event(NeothesiaEvent::GoBack).ok();
+                }
+                Some(VirtualKeyCode::S) => self.save_jam(),
+                Some(VirtualKeyCode::L) => self.looper.toggle_recording(),
+                Some(VirtualKeyCode::Back) => {
+                    let release = self.looper.undo();
+                    self.play_loop_events(target, release);
+                }
+                Some(VirtualKeyCode::Delete) => {
+                    let release = self.looper.clear();
+                    self.play_loop_events(target, release);
+                }
+                Some(VirtualKeyCode::E) => self.save_loop(),
+                _ => {}
+            }
+        }
+    }
+
+    fn midi_event(&mut self, target: &mut Target, event: &MidiEvent) {
+        target.event_bus.publish(AppEvent::UserMidi(*event));
+
+        match *event {
+            MidiEvent::NoteOn { key, vel, .. } => {
+                self.notes.note_on(key, vel);
+                self.held.insert(key);
+            }
+            MidiEvent::NoteOff { key, .. } => {
+                self.notes.note_off(key);
+                self.held.remove(&key);
+            }
+            MidiEvent::Controller { .. } => {}
+        }
+
+        let start = *self.jam_start.get_or_insert_with(Instant::now);
+        self.jam.record(start.elapsed(), event);
+
+        let (channel, message) = event.channel_message();
+        self.looper.record(channel, message);
+
+        keyboard_events::play_user_note(target, event);
+        keyboard_events::user_midi_event(&mut self.piano_keyboard, event);
+    }
+}
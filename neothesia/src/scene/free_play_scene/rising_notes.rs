@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use neothesia_pipelines::quad::{QuadInstance, QuadPipeline};
+use wgpu_jumpstart::{Color, TransformUniform, Uniform};
+
+use crate::{target::Target, utils::palette::note_color};
+
+/// Same speed the waterfall falls at
+const SPEED: f32 = 400.0;
+
+struct RisingNote {
+    key: u8,
+    velocity: u8,
+    start: Duration,
+    /// `None` while the key is held
+    end: Option<Duration>,
+}
+
+/// Notes played on the keyboard, growing out of it and rising up the screen
+pub struct RisingNotes {
+    quad_pipeline: QuadPipeline,
+    notes: Vec<RisingNote>,
+    time: Duration,
+}
+
+impl RisingNotes {
+    pub fn new(target: &Target) -> Self {
+        Self {
+            quad_pipeline: QuadPipeline::new(&target.gpu, &target.transform_uniform),
+            notes: Vec::new(),
+            time: Duration::ZERO,
+        }
+    }
+
+    pub fn note_on(&mut self, key: u8, velocity: u8) {
+        // Retriggered without a note off in between
+        self.note_off(key);
+        self.notes.push(RisingNote {
+            key,
+            velocity,
+            start: self.time,
+            end: None,
+        });
+    }
+
+    pub fn note_off(&mut self, key: u8) {
+        for note in self.notes.iter_mut() {
+            if note.key == key && note.end.is_none() {
+                note.end = Some(self.time);
+            }
+        }
+    }
+
+    /// `top` is the top edge of the keyboard, where the notes rise from
+    pub fn update(
+        &mut self,
+        target: &Target,
+        layout: &piano_math::KeyboardLayout,
+        top: f32,
+        delta: Duration,
+    ) {
+        self.time += delta;
+
+        let time = self.time;
+        let height = |since: Duration| (time - since).as_secs_f32() * SPEED;
+        // Released notes that left the screen are gone for good
+        self.notes
+            .retain(|note| note.end.map_or(true, |end| height(end) < top));
+
+        let config = &target.config;
+        let range_start = layout.range.start() as usize;
+
+        let quads = self
+            .notes
+            .iter()
+            .filter(|note| layout.range.contains(note.key))
+            .map(|note| {
+                let color = note_color(
+                    &config.color_schema,
+                    &config.hand_colors,
+                    &config.pitch_colors,
+                    0,
+                    note.key,
+                );
+                let key = &layout.keys[note.key as usize - range_start];
+                let color = if key.kind().is_sharp() {
+                    color.dark
+                } else {
+                    color.base
+                };
+                let (brightness, width) = config.themed_note_style().velocity_scale(note.velocity);
+                let color = Color::from(color);
+                let color = Color::new(
+                    (color.r * brightness).min(1.0),
+                    (color.g * brightness).min(1.0),
+                    (color.b * brightness).min(1.0),
+                    color.a,
+                );
+
+                let y = top - height(note.start);
+                let bottom = top - note.end.map_or(0.0, height);
+                let w = (key.width() - 1.0) * width;
+
+                QuadInstance {
+                    position: [key.x() + (key.width() - 1.0 - w) / 2.0, y],
+                    size: [w, bottom - y],
+                    color: color.into_linear_rgba(),
+                    border_radius: [key.width() * 0.2; 4],
+                }
+            })
+            .collect();
+
+        self.quad_pipeline
+            .update_instance_buffer(&target.gpu.queue, quads);
+    }
+
+    pub fn render<'rpass>(
+        &'rpass self,
+        transform_uniform: &'rpass Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+    ) {
+        self.quad_pipeline.render(transform_uniform, render_pass);
+    }
+}
@@ -1,4 +1,9 @@
-use std::{path::PathBuf, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 
 use super::Renderer;
 use iced_core::{
@@ -8,15 +13,21 @@ use iced_core::{
 };
 use iced_runtime::Command;
 use iced_widget::{
-    button, checkbox, column as col, container, image, pick_list, row, text, vertical_space,
+    button, checkbox, column as col, container, image, pick_list, row, text, text_input,
+    vertical_space,
 };
-use neothesia_core::config;
+use neothesia_core::{config, utils::bundle::DataBundle};
 
 use crate::{
+    accessibility::Item,
+    export_queue::{ExportJob, JobState},
     iced_utils::iced_state::{Element, Program},
-    output_manager::OutputDescriptor,
+    input_manager::PadGrid,
+    output_manager::{OutputDescriptor, TrackSoundFont},
     scene::menu_scene::neo_btn::neo_button,
     target::Target,
+    update_check::{self, Release},
+    utils::palette::{self, track_color},
     NeothesiaEvent,
 };
 
@@ -32,6 +43,14 @@ pub enum Message {
 
     SelectOutput(OutputDescriptor),
     SelectInput(InputDescriptor),
+    InputMuted(bool),
+    PlayInputNotes(bool),
+    /// Connects the input along with the selected one
+    ExtraInput(InputDescriptor, bool),
+    SelectInputChannel(InputDescriptor, InputChannel),
+    VirtualPiano(bool),
+    SelectVirtualPianoLayout(config::VirtualPianoLayout),
+    VirtualPianoBaseNote(u8),
 
     OpenMidiFilePicker,
     MidiFileLoaded(Option<midi_file::Midi>),
@@ -39,9 +58,97 @@ pub enum Message {
     OpenSoundFontPicker,
     SoundFontFileLoaded(Option<PathBuf>),
 
+    ExportData,
+    DataExportPathPicked(Option<PathBuf>),
+    ImportData,
+    DataImportPathPicked(Option<PathBuf>),
+
+    CheckForUpdates(bool),
+    UpdateChecked(Option<Release>),
+    OpenDownloadPage,
+    SkipUpdate,
+
+    PinInput(String),
+    PracticeLock(bool),
+    PracticeMinutes(f32),
+    LockPinInput(String),
+    NewPinInput(String),
+    /// Sets the typed PIN, an empty one clears it
+    SetPin,
+
     Play,
+    FreePlay,
+    Metronome,
 
     PlayAlongCheckbox(bool),
+    MelodyBoost(bool),
+
+    ForwardControllers(bool),
+    ForwardProgramChanges(bool),
+    ForwardSysEx(bool),
+    ExpandRepeats(bool),
+    SelectSystemReset(config::SystemReset),
+    SelectVelocityCurve(config::VelocityCurve),
+    SelectTheme(String),
+    SelectBackgroundKind(config::BackgroundKind),
+    ImpactParticles(bool),
+    SustainedNotes(bool),
+    OpenBackgroundPicker,
+    BackgroundFileLoaded(Option<PathBuf>),
+    BackgroundDim(Amount),
+    BackgroundBlur(Amount),
+    SelectOutputChannel(OutputChannel),
+    OutputTranspose(i8),
+    DeadKeys(String),
+    DeadKeyOctave(bool),
+    SelectNoteTolerance(config::NoteTolerance),
+    ScaleOverlay(bool),
+    SelectScaleTonic(Tonic),
+    SelectScaleMode(config::ScaleMode),
+
+    SelectMsaa(config::Msaa),
+    SelectResolutionScale(ResolutionScale),
+    SelectTextureFilter(config::TextureFilter),
+
+    HandColors(bool),
+    LeftHandColor(config::ColorSchema),
+    RightHandColor(config::ColorSchema),
+    PitchColors(bool),
+    NoteLabelsWaterfall(bool),
+    NoteLabelsKeyboard(bool),
+    NoteLabelsFlats(bool),
+    SelectNoteNaming(config::NoteNaming),
+    /// Pitch class, `0` is `C`
+    PitchColor(usize, (u8, u8, u8)),
+
+    TrackProgramSelected(usize, TrackProgram),
+    OpenTrackSoundFontPicker(usize),
+    TrackSoundFontLoaded(usize, Option<PathBuf>),
+    ResetTrackSound(usize),
+    TrackVisible(usize, bool),
+    TrackModeSelected(usize, TrackMode),
+    MelodyTrack(usize, bool),
+    /// Color id of the track, and the preset picked for it
+    TrackColorSelected(usize, config::ColorSchema),
+    ColorPartSelected(ColorPart),
+    ResetTrackColors,
+    TrackMergeSelected(usize, bool),
+    MergeSelectedTracks,
+    SplitPointSelected(usize, u8),
+    SplitTrack(usize),
+    MergeSimilarTracks,
+
+    MetadataTitle(String),
+    MetadataComposer(String),
+    MetadataCopyright(String),
+    SaveMetadata,
+
+    ExportSong,
+    ExportAudio(bool),
+    ExportClick(bool),
+    PauseExports,
+    ResumeExports,
+    ClearFinishedExports,
 
     GoToPage(Step),
     ExitApp,
@@ -51,73 +158,847 @@ struct Data {
     outputs: Vec<OutputDescriptor>,
     selected_output: Option<OutputDescriptor>,
     font_path: Option<PathBuf>,
-    midi_file: Option<Rc<midi_file::Midi>>,
+    midi_file: Option<Arc<midi_file::Midi>>,
+    /// Song info being edited, saved into the file on request
+    metadata: midi_file::Metadata,
 
     inputs: Vec<InputDescriptor>,
     selected_input: Option<InputDescriptor>,
+    /// Selected input makes its own sound, see [`config::Config::muted_inputs`]
+    input_muted: bool,
+    play_input_notes: bool,
+    /// See [`config::Config::extra_inputs`]
+    extra_inputs: Vec<String>,
+    /// See [`config::Config::input_channels`]
+    input_channels: HashMap<String, u8>,
+    virtual_piano: config::VirtualPiano,
 
     play_along: bool,
+    melody_boost: bool,
     is_loading: bool,
 
+    forward_controllers: bool,
+    forward_program_changes: bool,
+    forward_sysex: bool,
+    expand_repeats: bool,
+    system_reset: config::SystemReset,
+    velocity_curve: config::VelocityCurve,
+    output_remap: config::OutputRemap,
+    /// Names of the dead keys as typed, see [`config::DeadKeys`]
+    dead_keys: String,
+    dead_key_octave: bool,
+    /// Of the selected input, see [`config::Config::note_tolerances`]
+    note_tolerance: config::NoteTolerance,
+    scale_overlay: config::ScaleOverlay,
+    render_quality: config::RenderQuality,
+    /// Names of the theme files, after [`BUILT_IN_THEME`]
+    themes: Vec<String>,
+    theme: String,
+    background_image: config::BackgroundImage,
+    impact_particles: bool,
+    sustained_notes: bool,
+
     logo_handle: ImageHandle,
     color_schema: Vec<config::ColorSchema>,
+    hand_colors: config::HandColors,
+    pitch_colors: config::PitchColors,
+    note_labels: config::NoteLabels,
+    /// Notes the color pickers change
+    color_part: ColorPart,
+
+    track_soundfonts: HashMap<usize, TrackSoundFont>,
+    hidden_tracks: HashSet<usize>,
+    silent_tracks: HashSet<usize>,
+    user_tracks: HashSet<usize>,
+    /// Marked by the user, see [`Target::melody_track`]
+    melody_track: Option<usize>,
+    /// Tracks picked to be merged into one
+    merge_selection: HashSet<usize>,
+    /// See [`midi_file::Midi::similar_tracks`]
+    similar_tracks: Vec<Vec<usize>>,
+    /// Pitch each track would be split at, middle C when not moved
+    split_points: HashMap<usize, u8>,
+
+    check_for_updates: bool,
+    update: Option<Release>,
+
+    exports: Vec<ExportJob>,
+    /// Job being rendered, and how far along it is
+    export_progress: Option<(usize, f32)>,
+    export_eta: Option<Duration>,
+    exports_paused: bool,
+    export_audio: bool,
+    export_click: bool,
+
+    /// Practice left today in seconds, while the practice lock keeps the app open
+    practice_remaining: Option<u32>,
+    pin: String,
+    pin_unlocked: bool,
+    practice_lock: config::PracticeLock,
+    /// PIN typed on the settings page, the practice lock can't be changed without it
+    lock_pin: String,
+    new_pin: String,
+}
+
+impl Data {
+    fn track_mode(&self, track_id: usize) -> TrackMode {
+        if self.silent_tracks.contains(&track_id) {
+            TrackMode::Mute
+        } else if self.user_tracks.contains(&track_id) {
+            TrackMode::Human
+        } else {
+            TrackMode::Auto
+        }
+    }
+
+    fn split_point(&self, track_id: usize) -> u8 {
+        self.split_points.get(&track_id).copied().unwrap_or(60)
+    }
+
+    /// Songs loaded from a file can be rendered by the export queue
+    fn can_export(&self) -> bool {
+        self.midi_file
+            .as_ref()
+            .map_or(false, |midi| midi.path.is_some())
+    }
+
+    fn export_status(&self, id: usize, job: &ExportJob) -> String {
+        match &job.state {
+            JobState::Done => String::from("Done"),
+            JobState::Failed(err) => format!("Failed, {err}"),
+            JobState::Pending => match self.export_progress {
+                Some((running, progress)) if running == id => {
+                    format!("{:.0}%", progress * 100.0)
+                }
+                _ => String::from("Waiting"),
+            },
+        }
+    }
 }
 
 pub struct AppUi {
     data: Data,
     current: Step,
+    /// Shown in the settings window, which only has the settings page
+    in_window: bool,
 }
 
 impl AppUi {
     pub fn new(target: &mut Target) -> Self {
         Self {
             current: Step::Main,
+            in_window: false,
             data: Data {
                 outputs: Vec::new(),
                 selected_output: None,
                 font_path: target.config.soundfont_path.clone(),
                 midi_file: target.midi_file.clone(),
+                metadata: target
+                    .midi_file
+                    .as_ref()
+                    .map(|midi| midi.metadata.clone())
+                    .unwrap_or_default(),
 
                 inputs: Vec::new(),
                 selected_input: None,
+                input_muted: false,
+                play_input_notes: target.config.play_input_notes,
+                extra_inputs: target.config.extra_inputs.clone(),
+                input_channels: target.config.input_channels.clone(),
+                virtual_piano: target.config.virtual_piano.clone(),
 
                 play_along: target.config.play_along,
+                melody_boost: target.config.melody_boost.enabled,
                 is_loading: false,
 
+                forward_controllers: target.config.forward_controllers,
+                forward_program_changes: target.config.forward_program_changes,
+                forward_sysex: target.config.forward_sysex,
+                expand_repeats: target.config.expand_repeats,
+                system_reset: target.config.system_reset,
+                velocity_curve: target.config.velocity_curve,
+                output_remap: config::OutputRemap::default(),
+                dead_keys: dead_key_names(&target.config.dead_keys),
+                dead_key_octave: target.config.dead_keys.octave_substitute,
+                note_tolerance: config::NoteTolerance::default(),
+                scale_overlay: target.config.scale_overlay.clone(),
+                render_quality: target.config.render_quality.clone(),
+                themes: theme_names(),
+                theme: target
+                    .config
+                    .theme
+                    .clone()
+                    .unwrap_or_else(|| BUILT_IN_THEME.to_string()),
+                background_image: target.config.background_image.clone(),
+                impact_particles: target.config.impact_particles,
+                sustained_notes: target.config.note_style.sustain,
+
                 logo_handle: ImageHandle::from_memory(include_bytes!("../img/banner.png").to_vec()),
                 color_schema: target.config.color_schema.clone(),
+                hand_colors: target.config.hand_colors.clone(),
+                note_labels: target.config.note_labels.clone(),
+                pitch_colors: target.config.pitch_colors.clone(),
+                color_part: ColorPart::Both,
+
+                track_soundfonts: target.output_manager.borrow().track_soundfonts().clone(),
+                hidden_tracks: target.hidden_tracks.clone(),
+                silent_tracks: target.silent_tracks.clone(),
+                user_tracks: target.user_tracks.clone(),
+                melody_track: target.melody_track,
+                merge_selection: HashSet::new(),
+                similar_tracks: target
+                    .midi_file
+                    .as_ref()
+                    .map(|midi| midi.similar_tracks())
+                    .unwrap_or_default(),
+                split_points: HashMap::new(),
+
+                check_for_updates: target.config.update_check.enabled,
+                update: None,
+
+                exports: target.export_queue.jobs().to_vec(),
+                export_progress: target.export_queue.progress(),
+                export_eta: target.export_queue.eta(),
+                exports_paused: target.export_queue.is_paused(),
+                export_audio: target.export_queue.audio(),
+                export_click: target.export_queue.click(),
+
+                practice_remaining: None,
+                pin: String::new(),
+                pin_unlocked: false,
+                practice_lock: target.config.practice_lock.clone(),
+                lock_pin: String::new(),
+                new_pin: String::new(),
             },
         }
     }
 }
 
+impl AppUi {
+    /// Settings page for the settings window, leaving it closes the window
+    pub fn settings_window(target: &mut Target) -> Self {
+        let mut ui = Self::new(target);
+        ui.current = Step::Settings;
+        ui.in_window = true;
+        ui
+    }
+
+    fn import_data(&mut self, target: &mut Target, path: &std::path::Path) {
+        let bundle = match DataBundle::load(path) {
+            Ok(bundle) => bundle,
+            Err(err) => {
+                log::error!("Failed to import {}: {err}", path.display());
+                return;
+            }
+        };
+
+        match bundle.restore() {
+            Ok(count) => log::info!("Imported {count} files from {}", path.display()),
+            Err(err) => log::error!("Failed to import {}: {err}", path.display()),
+        }
+
+        if let Some(settings) = bundle.settings() {
+            match config::Config::from_ron(settings) {
+                Ok(mut config) => {
+                    // Importing doesn't get around the PIN of the practice lock
+                    let lock = &target.config.practice_lock;
+                    if !lock.can_change_with(&self.data.lock_pin) {
+                        config.practice_lock = lock.clone();
+                    }
+                    target.config = config;
+
+                    self.data.font_path = target.config.soundfont_path.clone();
+                    self.data.play_along = target.config.play_along;
+                    self.data.forward_controllers = target.config.forward_controllers;
+                    self.data.forward_program_changes = target.config.forward_program_changes;
+                    self.data.forward_sysex = target.config.forward_sysex;
+                    self.data.expand_repeats = target.config.expand_repeats;
+                    self.data.play_input_notes = target.config.play_input_notes;
+                    self.data.system_reset = target.config.system_reset;
+                    self.data.velocity_curve = target.config.velocity_curve;
+                    self.data.dead_keys = dead_key_names(&target.config.dead_keys);
+                    self.data.dead_key_octave = target.config.dead_keys.octave_substitute;
+                    if let Some(input) = self.data.selected_input.as_ref() {
+                        self.data.note_tolerance = target.config.note_tolerance(&input.to_string());
+                    }
+                    self.data.scale_overlay = target.config.scale_overlay.clone();
+                    self.data.render_quality = target.config.render_quality.clone();
+                    self.data.color_schema = target.config.color_schema.clone();
+                    self.data.hand_colors = target.config.hand_colors.clone();
+                    self.data.pitch_colors = target.config.pitch_colors.clone();
+                    self.data.theme = target
+                        .config
+                        .theme
+                        .clone()
+                        .unwrap_or_else(|| BUILT_IN_THEME.to_string());
+                    self.data.background_image = target.config.background_image.clone();
+                    self.data.impact_particles = target.config.impact_particles;
+                    self.data.virtual_piano = target.config.virtual_piano.clone();
+                    self.data.sustained_notes = target.config.note_style.sustain;
+                    self.data.note_labels = target.config.note_labels.clone();
+                    self.data.check_for_updates = target.config.update_check.enabled;
+                    self.data.practice_lock = target.config.practice_lock.clone();
+                }
+                Err(err) => log::error!("Invalid settings in {}: {err}", path.display()),
+            }
+        }
+    }
+
+    /// What the current page shows, for screen readers, with the message sent by each button
+    pub fn accessible_items(&self) -> Vec<(Item, Option<Message>)> {
+        let data = &self.data;
+        let mut items = Vec::new();
+
+        if data.is_loading {
+            items.push((Item::text("Loading..."), None));
+            return items;
+        }
+
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+
+        match self.current {
+            Step::Main => {
+                items.push((
+                    Item::button("Select File"),
+                    Some(Message::OpenMidiFilePicker),
+                ));
+                if let Some(midi) = data.midi_file.as_ref() {
+                    items.push((Item::text(format!("Selected song: {}", midi.name)), None));
+                }
+                items.push((Item::button("Free Play"), Some(Message::FreePlay)));
+                items.push((Item::button("Metronome"), Some(Message::Metronome)));
+                items.push((
+                    Item::button("Settings"),
+                    Some(Message::GoToPage(Step::Settings)),
+                ));
+                if !data.exports.is_empty() {
+                    items.push((
+                        Item::button("Exports"),
+                        Some(Message::GoToPage(Step::Exports)),
+                    ));
+                }
+                items.push((Item::button("Exit"), Some(Message::GoToPage(Step::Exit))));
+
+                if let Some(remaining) = data.practice_remaining {
+                    items.push((
+                        Item::text(format!(
+                            "Practice left today: {} minutes",
+                            (remaining + 59) / 60
+                        )),
+                        None,
+                    ));
+                }
+
+                if data.midi_file.is_some() {
+                    items.push((
+                        Item::button(format!("Play along: {}", on_off(data.play_along))),
+                        Some(Message::PlayAlongCheckbox(!data.play_along)),
+                    ));
+                    items.push((
+                        Item::button(format!("Melody boost: {}", on_off(data.melody_boost))),
+                        Some(Message::MelodyBoost(!data.melody_boost)),
+                    ));
+                    items.push((Item::button("Play"), Some(Message::Play)));
+                }
+                if data.can_export() {
+                    items.push((Item::button("Export"), Some(Message::ExportSong)));
+                }
+            }
+            Step::Exports => {
+                for (id, job) in data.exports.iter().enumerate() {
+                    items.push((
+                        Item::text(format!("{}: {}", job.name(), data.export_status(id, job))),
+                        None,
+                    ));
+                }
+
+                if let Some(eta) = data.export_eta {
+                    items.push((Item::text(format!("Time left: {}", format_eta(eta))), None));
+                }
+
+                if data.exports_paused {
+                    items.push((Item::button("Resume"), Some(Message::ResumeExports)));
+                } else {
+                    items.push((Item::button("Pause"), Some(Message::PauseExports)));
+                }
+                items.push((
+                    Item::button(format!("Export audio: {}", on_off(data.export_audio))),
+                    Some(Message::ExportAudio(!data.export_audio)),
+                ));
+                items.push((
+                    Item::button(format!("Click track: {}", on_off(data.export_click))),
+                    Some(Message::ExportClick(!data.export_click)),
+                ));
+                items.push((
+                    Item::button("Clear finished"),
+                    Some(Message::ClearFinishedExports),
+                ));
+                items.push((Item::button("Back"), Some(Message::GoToPage(Step::Main))));
+            }
+            Step::Settings => {
+                for output in data.outputs.iter() {
+                    let selected = data.selected_output.as_ref() == Some(output);
+                    items.push((
+                        Item::button(format!(
+                            "Output: {output}{}",
+                            if selected { ", selected" } else { "" }
+                        )),
+                        Some(Message::SelectOutput(output.clone())),
+                    ));
+                }
+
+                if let Some(OutputDescriptor::Synth(_)) = data.selected_output {
+                    items.push((
+                        Item::button("Select SoundFont"),
+                        Some(Message::OpenSoundFontPicker),
+                    ));
+                }
+
+                for input in data.inputs.iter() {
+                    let selected = data.selected_input.as_ref() == Some(input);
+                    items.push((
+                        Item::button(format!(
+                            "Input: {input}{}",
+                            if selected { ", selected" } else { "" }
+                        )),
+                        Some(Message::SelectInput(input.clone())),
+                    ));
+                }
+                if data.selected_input.is_some() {
+                    items.push((
+                        Item::button(format!(
+                            "Input has its own sound: {}",
+                            on_off(data.input_muted)
+                        )),
+                        Some(Message::InputMuted(!data.input_muted)),
+                    ));
+                }
+                items.push((
+                    Item::button(format!(
+                        "Play the input on the output during songs: {}",
+                        on_off(data.play_input_notes)
+                    )),
+                    Some(Message::PlayInputNotes(!data.play_input_notes)),
+                ));
+                for input in data.inputs.iter() {
+                    if data.selected_input.as_ref() == Some(input) {
+                        continue;
+                    }
+                    let enabled = data.extra_inputs.contains(&input.to_string());
+                    items.push((
+                        Item::button(format!("Also use input {input}: {}", on_off(enabled))),
+                        Some(Message::ExtraInput(input.clone(), !enabled)),
+                    ));
+                }
+                items.push((
+                    Item::button(format!(
+                        "Play notes with the computer keyboard: {}",
+                        on_off(data.virtual_piano.enabled)
+                    )),
+                    Some(Message::VirtualPiano(!data.virtual_piano.enabled)),
+                ));
+                items.push((
+                    Item::button(format!(
+                        "Play the repeats of scores: {}",
+                        on_off(data.expand_repeats)
+                    )),
+                    Some(Message::ExpandRepeats(!data.expand_repeats)),
+                ));
+
+                items.push((
+                    Item::button(format!(
+                        "Play dead keys an octave away: {}",
+                        on_off(data.dead_key_octave)
+                    )),
+                    Some(Message::DeadKeyOctave(!data.dead_key_octave)),
+                ));
+
+                if data.selected_input.is_some() {
+                    for tolerance in config::NoteTolerance::ALL {
+                        let selected = tolerance == data.note_tolerance;
+                        items.push((
+                            Item::button(format!(
+                                "Note matching on the input: {tolerance}{}",
+                                if selected { ", selected" } else { "" }
+                            )),
+                            Some(Message::SelectNoteTolerance(tolerance)),
+                        ));
+                    }
+                }
+
+                let scale = &data.scale_overlay;
+                items.push((
+                    Item::button(format!("Scale overlay: {}", on_off(scale.enabled))),
+                    Some(Message::ScaleOverlay(!scale.enabled)),
+                ));
+                if scale.enabled {
+                    for tonic in Tonic::ALL {
+                        let selected = tonic.0 == scale.tonic;
+                        items.push((
+                            Item::button(format!(
+                                "Scale tonic: {tonic}{}",
+                                if selected { ", selected" } else { "" }
+                            )),
+                            Some(Message::SelectScaleTonic(tonic)),
+                        ));
+                    }
+                    for mode in config::ScaleMode::ALL {
+                        let selected = mode == scale.mode;
+                        items.push((
+                            Item::button(format!(
+                                "Scale: {mode}{}",
+                                if selected { ", selected" } else { "" }
+                            )),
+                            Some(Message::SelectScaleMode(mode)),
+                        ));
+                    }
+                }
+
+                for name in data.themes.iter() {
+                    let selected = *name == data.theme;
+                    items.push((
+                        Item::button(format!(
+                            "Theme: {name}{}",
+                            if selected { ", selected" } else { "" }
+                        )),
+                        Some(Message::SelectTheme(name.clone())),
+                    ));
+                }
+
+                for kind in config::BackgroundKind::ALL {
+                    let selected = kind == data.background_image.kind;
+                    items.push((
+                        Item::button(format!(
+                            "Background: {kind}{}",
+                            if selected { ", selected" } else { "" }
+                        )),
+                        Some(Message::SelectBackgroundKind(kind)),
+                    ));
+                }
+                if data.background_image.kind == config::BackgroundKind::Image {
+                    items.push((
+                        Item::button("Choose background image"),
+                        Some(Message::OpenBackgroundPicker),
+                    ));
+                }
+
+                items.push((
+                    Item::button(format!(
+                        "Sparks where notes hit the keyboard: {}",
+                        on_off(data.impact_particles)
+                    )),
+                    Some(Message::ImpactParticles(!data.impact_particles)),
+                ));
+                items.push((
+                    Item::button(format!(
+                        "Notes last while the sustain pedal holds them: {}",
+                        on_off(data.sustained_notes)
+                    )),
+                    Some(Message::SustainedNotes(!data.sustained_notes)),
+                ));
+
+                let by_hand = data.hand_colors.enabled;
+                items.push((
+                    Item::button(format!("Color notes by hand: {}", on_off(by_hand))),
+                    Some(Message::HandColors(!by_hand)),
+                ));
+                let by_pitch = data.pitch_colors.enabled;
+                items.push((
+                    Item::button(format!("Color notes by pitch: {}", on_off(by_pitch))),
+                    Some(Message::PitchColors(!by_pitch)),
+                ));
+
+                let labels = &data.note_labels;
+                items.push((
+                    Item::button(format!(
+                        "Note names on falling notes: {}",
+                        on_off(labels.waterfall)
+                    )),
+                    Some(Message::NoteLabelsWaterfall(!labels.waterfall)),
+                ));
+                items.push((
+                    Item::button(format!(
+                        "Note names on the keyboard: {}",
+                        on_off(labels.keyboard)
+                    )),
+                    Some(Message::NoteLabelsKeyboard(!labels.keyboard)),
+                ));
+                for naming in config::NoteNaming::ALL {
+                    let selected = naming == labels.naming;
+                    items.push((
+                        Item::button(format!(
+                            "Note names: {naming}{}",
+                            if selected { ", selected" } else { "" }
+                        )),
+                        Some(Message::SelectNoteNaming(naming)),
+                    ));
+                }
+                items.push((
+                    Item::button(format!(
+                        "Name black keys as flats: {}",
+                        on_off(labels.flats)
+                    )),
+                    Some(Message::NoteLabelsFlats(!labels.flats)),
+                ));
+
+                let lock = &data.practice_lock;
+                if lock.can_change_with(&data.lock_pin) {
+                    items.push((
+                        Item::button(format!("Practice lock: {}", on_off(lock.enabled))),
+                        Some(Message::PracticeLock(!lock.enabled)),
+                    ));
+                    items.push((
+                        Item::button(format!("Practice {:.0} minutes a day, less", lock.minutes)),
+                        Some(Message::PracticeMinutes(lock.minutes - 5.0)),
+                    ));
+                    items.push((
+                        Item::button(format!("Practice {:.0} minutes a day, more", lock.minutes)),
+                        Some(Message::PracticeMinutes(lock.minutes + 5.0)),
+                    ));
+                } else {
+                    items.push((Item::text("Changing the practice lock needs the PIN"), None));
+                }
+
+                items.push((Item::button("Back"), Some(Message::GoToPage(Step::Main))));
+            }
+            Step::TrackSelection => {
+                if let Some(midi) = data.midi_file.as_ref() {
+                    for track in midi.tracks.iter().filter(|t| !t.notes.is_empty()) {
+                        let id = track.track_id;
+                        let visible = !data.hidden_tracks.contains(&id);
+                        let mode = data.track_mode(id);
+
+                        items.push((Item::text(format!("Track {}", id + 1)), None));
+                        items.push((
+                            Item::button(format!("Visible: {}", on_off(visible))),
+                            Some(Message::TrackVisible(id, !visible)),
+                        ));
+                        let melody = data.melody_track == Some(id);
+                        items.push((
+                            Item::button(format!("Melody: {}", on_off(melody))),
+                            Some(Message::MelodyTrack(id, !melody)),
+                        ));
+                        let selected = data.merge_selection.contains(&id);
+                        items.push((
+                            Item::button(format!("Selected for merging: {}", on_off(selected))),
+                            Some(Message::TrackMergeSelected(id, !selected)),
+                        ));
+                        let pitch = data.split_point(id);
+                        let (high, low) = split_preview(track, pitch);
+                        if high > 0 && low > 0 {
+                            items.push((
+                                Item::button(format!(
+                                    "Split at {}, {high} notes above and {low} below",
+                                    piano_math::names::letter_name(pitch, false)
+                                )),
+                                Some(Message::SplitTrack(id)),
+                            ));
+                        }
+                        for option in TrackMode::ALL {
+                            items.push((
+                                Item::button(format!(
+                                    "{}{}",
+                                    option.description(),
+                                    if option == mode { ", selected" } else { "" }
+                                )),
+                                Some(Message::TrackModeSelected(id, option)),
+                            ));
+                        }
+                    }
+                }
+
+                items.push((
+                    Item::button("Reset track colors"),
+                    Some(Message::ResetTrackColors),
+                ));
+                if data.merge_selection.len() > 1 {
+                    items.push((
+                        Item::button("Merge selected tracks"),
+                        Some(Message::MergeSelectedTracks),
+                    ));
+                }
+                if !data.similar_tracks.is_empty() {
+                    items.push((
+                        Item::button("Merge similar tracks"),
+                        Some(Message::MergeSimilarTracks),
+                    ));
+                }
+
+                items.push((Item::button("Back"), Some(Message::GoToPage(Step::Main))));
+                items.push((Item::button("Play"), Some(Message::Play)));
+            }
+            Step::Exit => {
+                items.push((Item::text("Do you want to exit?"), None));
+                items.push((Item::button("No"), Some(Message::GoToPage(Step::Main))));
+
+                if data.practice_remaining.is_none() || data.pin_unlocked {
+                    items.push((Item::button("Yes"), Some(Message::ExitApp)));
+                } else {
+                    items.push((
+                        Item::text("Practice goal not reached, exiting needs the PIN"),
+                        None,
+                    ));
+                }
+            }
+            Step::Update => {
+                if let Some(release) = data.update.as_ref() {
+                    items.push((
+                        Item::text(format!("Neothesia {} is available", release.version)),
+                        None,
+                    ));
+                }
+                items.push((Item::button("Skip"), Some(Message::SkipUpdate)));
+                items.push((Item::button("Later"), Some(Message::GoToPage(Step::Main))));
+                items.push((Item::button("Download"), Some(Message::OpenDownloadPage)));
+            }
+        }
+
+        items
+    }
+
+    /// Connects the selected output and input, before leaving the menu
+    fn connect_devices(&self, target: &mut Target) {
+        if let Some(out) = self.data.selected_output.clone() {
+            let out = match out {
+                #[cfg(feature = "synth")]
+                OutputDescriptor::Synth(_) => OutputDescriptor::Synth(self.data.font_path.clone()),
+                o => o,
+            };
+
+            let remap = target.config.output_remap(&out.to_string());
+
+            let mut output_manager = target.output_manager.borrow_mut();
+            output_manager.connect(out);
+            output_manager.set_remap(remap);
+        }
+
+        if let Some(port) = self.data.selected_input.clone() {
+            let extra = self
+                .data
+                .inputs
+                .iter()
+                .filter(|input| **input != port && target.config.is_extra_input(&input.to_string()))
+                .cloned();
+            let ports = std::iter::once(port).chain(extra).collect();
+
+            let pad_grid = PadGrid::new(&target.config.pad_grid);
+            target.input_manager.set_pad_grid(pad_grid);
+            target
+                .input_manager
+                .set_channels(target.config.input_channels.clone());
+            target.input_manager.connect_inputs(ports);
+        }
+    }
+
+    /// Merges `tracks` into the first of them, tracks merged away lose their settings
+    fn merge_tracks(&mut self, tracks: &[usize]) {
+        let data = &mut self.data;
+        let (target, rest) = match (data.midi_file.as_mut(), tracks.split_first()) {
+            (Some(midi), Some((target, rest))) if !rest.is_empty() => {
+                Arc::make_mut(midi).merge_tracks(tracks);
+                (*target, rest)
+            }
+            _ => return,
+        };
+
+        for id in rest {
+            data.track_soundfonts.remove(id);
+            data.hidden_tracks.remove(id);
+            data.silent_tracks.remove(id);
+            data.user_tracks.remove(id);
+            data.merge_selection.remove(id);
+        }
+        if data.melody_track.map_or(false, |id| rest.contains(&id)) {
+            data.melody_track = Some(target);
+        }
+
+        data.similar_tracks = data
+            .midi_file
+            .as_ref()
+            .map(|midi| midi.similar_tracks())
+            .unwrap_or_default();
+    }
+
+    /// Splits a track that mixes both hands, the new track of its lower notes is set up
+    /// the way the track was
+    fn split_track(&mut self, track_id: usize) {
+        let data = &mut self.data;
+        let pitch = data.split_point(track_id);
+        let split = match data.midi_file.as_mut() {
+            Some(midi) => Arc::make_mut(midi).split_track(track_id, pitch),
+            None => None,
+        };
+        let split = match split {
+            Some(split) => split,
+            None => return,
+        };
+
+        if let Some(sound) = data.track_soundfonts.get(&track_id).cloned() {
+            data.track_soundfonts.insert(split, sound);
+        }
+        for tracks in [
+            &mut data.hidden_tracks,
+            &mut data.silent_tracks,
+            &mut data.user_tracks,
+        ] {
+            if tracks.contains(&track_id) {
+                tracks.insert(split);
+            }
+        }
+        data.split_points.remove(&track_id);
+
+        data.similar_tracks = data
+            .midi_file
+            .as_ref()
+            .map(|midi| midi.similar_tracks())
+            .unwrap_or_default();
+    }
+
+    fn store_output_remap(&self, target: &mut Target) {
+        if let Some(output) = self.data.selected_output.as_ref() {
+            target
+                .config
+                .set_output_remap(output.to_string(), self.data.output_remap);
+        }
+    }
+}
+
 impl Program for AppUi {
     type Message = Message;
 
     fn update(&mut self, target: &mut Target, message: Message) -> Command<Self::Message> {
         match message {
             Message::GoToPage(page) => {
-                self.current = page;
+                // Practice lock settings lock again once the page is left
+                self.data.lock_pin.clear();
+
+                if self.in_window && !matches!(page, Step::Settings) {
+                    target
+                        .proxy
+                        .send_event(NeothesiaEvent::CloseSettingsWindow)
+                        .ok();
+                } else {
+                    if matches!(page, Step::Settings) {
+                        // Theme files may have been added since
+                        self.data.themes = theme_names();
+                    }
+                    self.current = page;
+                }
             }
             Message::Play => {
                 if self.data.midi_file.is_some() {
                     target.midi_file = self.data.midi_file.take();
 
-                    if let Some(out) = self.data.selected_output.clone() {
-                        let out = match out {
-                            #[cfg(feature = "synth")]
-                            OutputDescriptor::Synth(_) => {
-                                OutputDescriptor::Synth(self.data.font_path.clone())
-                            }
-                            o => o,
-                        };
-
-                        target.output_manager.borrow_mut().connect(out)
-                    }
+                    target
+                        .output_manager
+                        .borrow_mut()
+                        .set_track_soundfonts(self.data.track_soundfonts.clone());
+                    target.hidden_tracks = self.data.hidden_tracks.clone();
+                    target.silent_tracks = self.data.silent_tracks.clone();
+                    target.user_tracks = self.data.user_tracks.clone();
+                    target.melody_track = self.data.melody_track;
 
-                    if let Some(port) = self.data.selected_input.clone() {
-                        target.input_manager.connect_input(port);
-                    }
+                    self.connect_devices(target);
 
                     target
                         .proxy
@@ -125,13 +1006,38 @@ impl Program for AppUi {
                         .ok();
                 }
             }
+            Message::FreePlay => {
+                self.connect_devices(target);
+
+                target
+                    .proxy
+                    .send_event(NeothesiaEvent::MainMenu(super::Event::FreePlay))
+                    .ok();
+            }
+            Message::Metronome => {
+                self.connect_devices(target);
+
+                target
+                    .proxy
+                    .send_event(NeothesiaEvent::MainMenu(super::Event::Metronome))
+                    .ok();
+            }
             Message::OpenMidiFilePicker => {
                 self.data.is_loading = true;
-                return open_midi_file_picker(Message::MidiFileLoaded);
+                let expand_repeats = target.config.expand_repeats;
+                return open_midi_file_picker(expand_repeats, Message::MidiFileLoaded);
             }
             Message::MidiFileLoaded(midi) => {
                 if let Some(midi) = midi {
-                    self.data.midi_file = Some(Rc::new(midi));
+                    self.data.metadata = midi.metadata.clone();
+                    self.data.similar_tracks = midi.similar_tracks();
+                    self.data.midi_file = Some(Arc::new(midi));
+                    self.data.track_soundfonts.clear();
+                    self.data.hidden_tracks.clear();
+                    self.data.silent_tracks.clear();
+                    self.data.user_tracks.clear();
+                    self.data.melody_track = None;
+                    self.data.merge_selection.clear();
                 }
                 self.data.is_loading = false;
             }
@@ -146,6 +1052,93 @@ impl Program for AppUi {
                 }
                 self.data.is_loading = false;
             }
+            Message::ResetTrackSound(track_id) => {
+                self.data.track_soundfonts.remove(&track_id);
+            }
+            Message::TrackVisible(track_id, visible) => {
+                if visible {
+                    self.data.hidden_tracks.remove(&track_id);
+                } else {
+                    self.data.hidden_tracks.insert(track_id);
+                }
+            }
+            Message::TrackModeSelected(track_id, mode) => {
+                self.data.silent_tracks.remove(&track_id);
+                self.data.user_tracks.remove(&track_id);
+                match mode {
+                    TrackMode::Mute => self.data.silent_tracks.insert(track_id),
+                    TrackMode::Auto => false,
+                    TrackMode::Human => self.data.user_tracks.insert(track_id),
+                };
+            }
+            Message::MelodyTrack(track_id, melody) => {
+                if melody {
+                    self.data.melody_track = Some(track_id);
+                } else if self.data.melody_track == Some(track_id) {
+                    self.data.melody_track = None;
+                }
+            }
+            Message::TrackMergeSelected(track_id, selected) => {
+                if selected {
+                    self.data.merge_selection.insert(track_id);
+                } else {
+                    self.data.merge_selection.remove(&track_id);
+                }
+            }
+            Message::MergeSelectedTracks => {
+                let mut tracks: Vec<usize> = self.data.merge_selection.drain().collect();
+                tracks.sort();
+                self.merge_tracks(&tracks);
+            }
+            Message::TrackColorSelected(color_id, preset) => {
+                let schema = &mut target.config.color_schema;
+                // Tracks past the end of the schema keep their generated colors until picked
+                while schema.len() <= color_id {
+                    schema.push(palette::generated_color(schema.len()));
+                }
+                schema[color_id] = self.data.color_part.apply(&schema[color_id], &preset);
+                self.data.color_schema = schema.clone();
+            }
+            Message::ColorPartSelected(part) => {
+                self.data.color_part = part;
+            }
+            Message::ResetTrackColors => {
+                target.config.color_schema = config::default_color_schema();
+                self.data.color_schema = target.config.color_schema.clone();
+            }
+            Message::HandColors(v) => {
+                target.config.hand_colors.enabled = v;
+                self.data.hand_colors.enabled = v;
+            }
+            Message::LeftHandColor(preset) => {
+                let colors = &mut target.config.hand_colors;
+                colors.left = self.data.color_part.apply(&colors.left, &preset);
+                self.data.hand_colors = colors.clone();
+            }
+            Message::RightHandColor(preset) => {
+                let colors = &mut target.config.hand_colors;
+                colors.right = self.data.color_part.apply(&colors.right, &preset);
+                self.data.hand_colors = colors.clone();
+            }
+            Message::PitchColors(v) => {
+                target.config.pitch_colors.enabled = v;
+                self.data.pitch_colors.enabled = v;
+            }
+            Message::PitchColor(class, color) => {
+                target.config.pitch_colors.colors[class % 12] = color;
+                self.data.pitch_colors = target.config.pitch_colors.clone();
+            }
+            Message::SplitPointSelected(track_id, pitch) => {
+                self.data.split_points.insert(track_id, pitch.min(127));
+            }
+            Message::SplitTrack(track_id) => {
+                self.split_track(track_id);
+            }
+            Message::MergeSimilarTracks => {
+                for tracks in std::mem::take(&mut self.data.similar_tracks) {
+                    self.merge_tracks(&tracks);
+                }
+            }
             Message::SelectOutput(output) => {
                 target
                     .config
@@ -154,17 +1147,215 @@ impl Program for AppUi {
                     } else {
                         Some(output.to_string())
                     });
+                self.data.output_remap = target.config.output_remap(&output.to_string());
                 self.data.selected_output = Some(output);
             }
             Message::SelectInput(input) => {
                 target.config.set_input(Some(&input));
+                self.data.input_muted = target.config.is_input_muted(&input.to_string());
+                self.data.note_tolerance = target.config.note_tolerance(&input.to_string());
                 self.data.selected_input = Some(input);
             }
+            Message::InputMuted(v) => {
+                if let Some(input) = self.data.selected_input.as_ref() {
+                    target.config.set_input_muted(input.to_string(), v);
+                    self.data.input_muted = v;
+                }
+            }
+            Message::ExtraInput(input, v) => {
+                target.config.set_extra_input(input.to_string(), v);
+                self.data.extra_inputs = target.config.extra_inputs.clone();
+            }
+            Message::VirtualPiano(v) => {
+                target.config.virtual_piano.enabled = v;
+                self.data.virtual_piano.enabled = v;
+            }
+            Message::SelectVirtualPianoLayout(layout) => {
+                target.config.virtual_piano.rows = layout.rows();
+                self.data.virtual_piano.rows = layout.rows();
+            }
+            Message::VirtualPianoBaseNote(note) => {
+                let note = note.min(108);
+                target.config.virtual_piano.base_note = note;
+                self.data.virtual_piano.base_note = note;
+            }
+            Message::SelectInputChannel(input, channel) => {
+                target
+                    .config
+                    .set_input_channel(input.to_string(), channel.0);
+                self.data.input_channels = target.config.input_channels.clone();
+            }
             Message::PlayAlongCheckbox(v) => {
                 target.config.play_along = v;
                 self.data.play_along = v;
             }
+            Message::DeadKeys(names) => {
+                // Names that don't parse are skipped, they may still be being typed
+                target.config.dead_keys.keys = names
+                    .split([',', ' '])
+                    .filter_map(piano_math::names::parse_letter_name)
+                    .collect();
+                self.data.dead_keys = names;
+            }
+            Message::DeadKeyOctave(v) => {
+                target.config.dead_keys.octave_substitute = v;
+                self.data.dead_key_octave = v;
+            }
+            Message::SelectNoteTolerance(tolerance) => {
+                if let Some(input) = self.data.selected_input.as_ref() {
+                    target
+                        .config
+                        .set_note_tolerance(input.to_string(), tolerance);
+                    self.data.note_tolerance = tolerance;
+                }
+            }
+            Message::ScaleOverlay(v) => {
+                target.config.scale_overlay.enabled = v;
+                self.data.scale_overlay.enabled = v;
+            }
+            Message::SelectScaleTonic(tonic) => {
+                target.config.scale_overlay.tonic = tonic.0;
+                self.data.scale_overlay.tonic = tonic.0;
+            }
+            Message::SelectScaleMode(mode) => {
+                target.config.scale_overlay.mode = mode;
+                self.data.scale_overlay.mode = mode;
+            }
+            Message::MelodyBoost(v) => {
+                target.config.melody_boost.enabled = v;
+                self.data.melody_boost = v;
+            }
+            Message::ForwardControllers(v) => {
+                target.config.forward_controllers = v;
+                self.data.forward_controllers = v;
+            }
+            Message::ForwardProgramChanges(v) => {
+                target.config.forward_program_changes = v;
+                self.data.forward_program_changes = v;
+            }
+            Message::ForwardSysEx(v) => {
+                target.config.forward_sysex = v;
+                self.data.forward_sysex = v;
+            }
+            Message::PlayInputNotes(v) => {
+                target.config.play_input_notes = v;
+                self.data.play_input_notes = v;
+            }
+            Message::ExpandRepeats(v) => {
+                target.config.expand_repeats = v;
+                self.data.expand_repeats = v;
+            }
+            Message::SelectSystemReset(reset) => {
+                target.config.system_reset = reset;
+                self.data.system_reset = reset;
+            }
+            Message::SelectVelocityCurve(curve) => {
+                target.config.velocity_curve = curve;
+                self.data.velocity_curve = curve;
+            }
+            Message::SelectTheme(name) => {
+                target.config.theme = (name != BUILT_IN_THEME).then(|| name.clone());
+                self.data.theme = name;
+            }
+            Message::SelectBackgroundKind(kind) => {
+                target.config.background_image.kind = kind;
+                self.data.background_image.kind = kind;
+                if kind == config::BackgroundKind::Image
+                    && target.config.background_image.path.is_none()
+                {
+                    self.data.is_loading = true;
+                    return open_image_picker(Message::BackgroundFileLoaded);
+                }
+            }
+            Message::ImpactParticles(v) => {
+                target.config.impact_particles = v;
+                self.data.impact_particles = v;
+            }
+            Message::SustainedNotes(v) => {
+                target.config.note_style.sustain = v;
+                self.data.sustained_notes = v;
+            }
+            Message::NoteLabelsWaterfall(v) => {
+                target.config.note_labels.waterfall = v;
+                self.data.note_labels.waterfall = v;
+            }
+            Message::NoteLabelsKeyboard(v) => {
+                target.config.note_labels.keyboard = v;
+                self.data.note_labels.keyboard = v;
+            }
+            Message::NoteLabelsFlats(v) => {
+                target.config.note_labels.flats = v;
+                self.data.note_labels.flats = v;
+            }
+            Message::SelectNoteNaming(naming) => {
+                target.config.note_labels.naming = naming;
+                self.data.note_labels.naming = naming;
+            }
+            Message::OpenBackgroundPicker => {
+                self.data.is_loading = true;
+                return open_image_picker(Message::BackgroundFileLoaded);
+            }
+            Message::BackgroundFileLoaded(path) => {
+                if let Some(path) = path {
+                    target.config.background_image.path = Some(path);
+                    self.data.background_image = target.config.background_image.clone();
+                }
+                self.data.is_loading = false;
+            }
+            Message::BackgroundDim(amount) => {
+                target.config.background_image.dim = amount.fraction();
+                self.data.background_image.dim = amount.fraction();
+            }
+            Message::BackgroundBlur(amount) => {
+                target.config.background_image.blur = amount.fraction();
+                self.data.background_image.blur = amount.fraction();
+            }
+            Message::SelectMsaa(msaa) => {
+                target.config.render_quality.msaa = msaa;
+                self.data.render_quality.msaa = msaa;
+            }
+            Message::SelectResolutionScale(scale) => {
+                target.config.render_quality.resolution_scale = scale.scale();
+                self.data.render_quality.resolution_scale = scale.scale();
+            }
+            Message::SelectTextureFilter(filter) => {
+                target.config.render_quality.filter = filter;
+                self.data.render_quality.filter = filter;
+            }
+            Message::SelectOutputChannel(channel) => {
+                self.data.output_remap.channel = channel.0;
+                self.store_output_remap(target);
+            }
+            Message::OutputTranspose(transpose) => {
+                self.data.output_remap.transpose = transpose.clamp(-24, 24);
+                self.store_output_remap(target);
+            }
+            Message::TrackProgramSelected(track_id, program) => {
+                let sound = self.data.track_soundfonts.entry(track_id).or_default();
+                sound.program = match program {
+                    TrackProgram::FromFile => None,
+                    TrackProgram::Program(program) => Some(program),
+                };
+
+                if *sound == TrackSoundFont::default() {
+                    self.data.track_soundfonts.remove(&track_id);
+                }
+            }
+            Message::OpenTrackSoundFontPicker(track_id) => {
+                self.data.is_loading = true;
+                return open_sound_font_picker(move |font| {
+                    Message::TrackSoundFontLoaded(track_id, font)
+                });
+            }
+            Message::TrackSoundFontLoaded(track_id, font) => {
+                if let Some(font) = font {
+                    self.data.track_soundfonts.entry(track_id).or_default().path = Some(font);
+                }
+                self.data.is_loading = false;
+            }
             Message::Tick => {
+                // Follows the theme, which can change at any time
+                theme::set_accent(target.config.themed_accent_color());
                 self.data.outputs = target.output_manager.borrow().outputs();
                 self.data.inputs = target.input_manager.inputs();
 
@@ -179,6 +1370,10 @@ impl Program for AppUi {
                     } else {
                         self.data.selected_output = self.data.outputs.first().cloned();
                     }
+
+                    if let Some(out) = self.data.selected_output.as_ref() {
+                        self.data.output_remap = target.config.output_remap(&out.to_string());
+                    }
                 }
 
                 if self.data.selected_input.is_none() {
@@ -192,26 +1387,199 @@ impl Program for AppUi {
                     } else {
                         self.data.selected_input = self.data.inputs.first().cloned();
                     }
+
+                    if let Some(input) = self.data.selected_input.as_ref() {
+                        self.data.input_muted = target.config.is_input_muted(&input.to_string());
+                        self.data.note_tolerance = target.config.note_tolerance(&input.to_string());
+                    }
+                }
+
+                let queue = &target.export_queue;
+                self.data.exports = queue.jobs().to_vec();
+                self.data.export_progress = queue.progress();
+                self.data.export_eta = queue.eta();
+
+                let lock = &target.config.practice_lock;
+                self.data.practice_remaining = if lock.is_locked() {
+                    Some(lock.remaining() as u32)
+                } else {
+                    None
+                };
+
+                if update_check::is_due(&target.config.update_check) {
+                    target.config.update_check.last_check = update_check::now();
+                    return check_for_updates(Message::UpdateChecked);
                 }
             }
-            Message::ExitApp => {
-                target.proxy.send_event(NeothesiaEvent::GoBack).ok();
+            Message::ExportData => {
+                return save_data_bundle_picker(Message::DataExportPathPicked);
             }
-        }
-
-        Command::none()
-    }
+            Message::DataExportPathPicked(path) => {
+                if let Some(path) = path {
+                    let settings = target.config.to_ron().unwrap_or_default();
+                    match DataBundle::collect(settings).save(&path) {
+                        Ok(()) => log::info!("Data exported to {}", path.display()),
+                        Err(err) => log::error!("Failed to export data: {err}"),
+                    }
+                }
+            }
+            Message::ImportData => {
+                return open_data_bundle_picker(Message::DataImportPathPicked);
+            }
+            Message::DataImportPathPicked(path) => {
+                if let Some(path) = path {
+                    self.import_data(target, &path);
+                }
+            }
+            Message::CheckForUpdates(v) => {
+                target.config.update_check.enabled = v;
+                self.data.check_for_updates = v;
+            }
+            Message::UpdateChecked(release) => {
+                let release = release.filter(|release| {
+                    Some(&release.version) != target.config.update_check.skipped_version.as_ref()
+                });
 
-    fn keyboard_input(&self, event: &iced_runtime::keyboard::Event) -> Option<Message> {
-        use iced_runtime::keyboard::{Event, KeyCode};
+                if let Some(release) = release {
+                    log::info!("Neothesia {} is available", release.version);
+                    self.data.update = Some(release);
 
-        if let Event::KeyPressed { key_code, .. } = event {
-            match key_code {
-                KeyCode::Tab => match self.current {
-                    Step::Main => Some(Message::OpenMidiFilePicker),
-                    Step::Settings => Some(Message::OpenSoundFontPicker),
-                    _ => None,
-                },
+                    // Don't pull the user out of the settings or track pages
+                    if let Step::Main = self.current {
+                        self.current = Step::Update;
+                    }
+                }
+            }
+            Message::OpenDownloadPage => {
+                if let Some(release) = self.data.update.as_ref() {
+                    update_check::open_url(release.download_url());
+                }
+            }
+            Message::SkipUpdate => {
+                if let Some(release) = self.data.update.take() {
+                    target.config.update_check.skipped_version = Some(release.version);
+                }
+                self.current = Step::Main;
+            }
+            Message::MetadataTitle(title) => {
+                self.data.metadata.title = non_empty(title);
+            }
+            Message::MetadataComposer(composer) => {
+                self.data.metadata.composer = non_empty(composer);
+            }
+            Message::MetadataCopyright(copyright) => {
+                self.data.metadata.copyright = non_empty(copyright);
+            }
+            Message::SaveMetadata => {
+                if let Some(midi) = self.data.midi_file.as_mut() {
+                    let midi = Arc::make_mut(midi);
+                    match midi.save_metadata(self.data.metadata.clone()) {
+                        Ok(()) => log::info!("Song info saved to {}", midi.name),
+                        Err(err) => log::error!("Failed to save song info: {err}"),
+                    }
+                }
+            }
+            Message::ExportSong => {
+                let path = self
+                    .data
+                    .midi_file
+                    .as_ref()
+                    .and_then(|midi| midi.path.clone());
+
+                if let Some(path) = path {
+                    target.export_queue.add(path);
+                    self.data.exports = target.export_queue.jobs().to_vec();
+                    self.current = Step::Exports;
+                }
+            }
+            Message::ExportAudio(v) => {
+                target.export_queue.set_audio(v);
+                self.data.export_audio = v;
+            }
+            Message::ExportClick(v) => {
+                target.export_queue.set_click(v);
+                self.data.export_click = v;
+            }
+            Message::PauseExports => {
+                target.export_queue.pause();
+                self.data.exports_paused = true;
+            }
+            Message::ResumeExports => {
+                target.export_queue.resume();
+                self.data.exports_paused = false;
+            }
+            Message::ClearFinishedExports => {
+                target.export_queue.clear_finished();
+                self.data.exports = target.export_queue.jobs().to_vec();
+                self.data.export_progress = target.export_queue.progress();
+            }
+            Message::PinInput(pin) => {
+                self.data.pin_unlocked = target.config.practice_lock.unlocks_with(&pin);
+                self.data.pin = pin;
+            }
+            Message::PracticeLock(v) => {
+                if target
+                    .config
+                    .practice_lock
+                    .can_change_with(&self.data.lock_pin)
+                {
+                    target.config.practice_lock.enabled = v;
+                    self.data.practice_lock.enabled = v;
+                }
+            }
+            Message::PracticeMinutes(minutes) => {
+                if target
+                    .config
+                    .practice_lock
+                    .can_change_with(&self.data.lock_pin)
+                {
+                    let minutes = minutes.clamp(5.0, 240.0);
+                    target.config.practice_lock.minutes = minutes;
+                    self.data.practice_lock.minutes = minutes;
+                }
+            }
+            Message::LockPinInput(pin) => {
+                self.data.lock_pin = pin;
+            }
+            Message::NewPinInput(pin) => {
+                self.data.new_pin = pin;
+            }
+            Message::SetPin => {
+                if target
+                    .config
+                    .practice_lock
+                    .can_change_with(&self.data.lock_pin)
+                {
+                    let pin = std::mem::take(&mut self.data.new_pin);
+                    target.config.practice_lock.pin = pin.clone();
+                    self.data.practice_lock.pin = pin.clone();
+                    // Stays open to changes until the page is left
+                    self.data.lock_pin = pin;
+                }
+            }
+            Message::ExitApp => {
+                let lock = &target.config.practice_lock;
+                if lock.is_locked() && !lock.unlocks_with(&self.data.pin) {
+                    return Command::none();
+                }
+
+                target.proxy.send_event(NeothesiaEvent::GoBack).ok();
+            }
+        }
+
+        Command::none()
+    }
+
+    fn keyboard_input(&self, event: &iced_runtime::keyboard::Event) -> Option<Message> {
+        use iced_runtime::keyboard::{Event, KeyCode};
+
+        if let Event::KeyPressed { key_code, .. } = event {
+            match key_code {
+                KeyCode::Tab => match self.current {
+                    Step::Main => Some(Message::OpenMidiFilePicker),
+                    Step::Settings => Some(Message::OpenSoundFontPicker),
+                    _ => None,
+                },
                 KeyCode::S => match self.current {
                     Step::Main => Some(Message::GoToPage(Step::Settings)),
                     _ => None,
@@ -235,6 +1603,8 @@ impl Program for AppUi {
                     Step::Main => Message::GoToPage(Step::Exit),
                     Step::Settings => Message::GoToPage(Step::Main),
                     Step::TrackSelection => Message::GoToPage(Step::Main),
+                    Step::Update => Message::GoToPage(Step::Main),
+                    Step::Exports => Message::GoToPage(Step::Main),
                 }),
                 _ => None,
             }
@@ -254,6 +1624,8 @@ pub enum Step {
     Main,
     Settings,
     TrackSelection,
+    Update,
+    Exports,
 }
 
 impl<'a> Step {
@@ -263,10 +1635,12 @@ impl<'a> Step {
         }
 
         match self {
-            Self::Exit => Self::exit(),
+            Self::Exit => Self::exit(data),
             Self::Main => Self::main(data),
             Self::Settings => Self::settings(data),
             Self::TrackSelection => Self::track_selection(data),
+            Self::Update => Self::update(data),
+            Self::Exports => Self::exports(data),
         }
     }
 
@@ -278,21 +1652,77 @@ impl<'a> Step {
         center_x(top_padded(column)).into()
     }
 
-    fn exit() -> Element<'a, Message> {
+    fn exit(data: &'a Data) -> Element<'a, Message> {
         let output = centered_text("Do you want to exit?").size(30);
 
+        let can_exit = data.practice_remaining.is_none() || data.pin_unlocked;
+
         let select_row = row![
             neo_button("No")
                 .width(Length::Fill)
                 .on_press(Message::GoToPage(Step::Main)),
             neo_button("Yes")
                 .width(Length::Fill)
+                .disabled(!can_exit)
                 .on_press(Message::ExitApp),
         ]
         .spacing(5)
         .height(Length::Fixed(50.0));
 
-        let controls = col![output, select_row]
+        let mut controls = col![output]
+            .align_items(Alignment::Center)
+            .width(Length::Fixed(650.0))
+            .spacing(30);
+
+        if let Some(remaining) = data.practice_remaining {
+            let info = centered_text(format!(
+                "{}:{:02} of practice left today, enter the PIN to exit anyway",
+                remaining / 60,
+                remaining % 60
+            ))
+            .size(20);
+
+            let pin = text_input("PIN", &data.pin)
+                .on_input(Message::PinInput)
+                .password()
+                .padding(10)
+                .width(Length::Fixed(200.0));
+
+            controls = controls.push(info).push(pin);
+        }
+
+        let controls = controls.push(select_row);
+
+        center_x(controls).center_y().into()
+    }
+
+    fn update(data: &'a Data) -> Element<'a, Message> {
+        let release = match data.update.as_ref() {
+            Some(release) => release,
+            None => return Self::main(data),
+        };
+
+        let title = centered_text(format!("Neothesia {} is available", release.version)).size(30);
+
+        let notes = iced_widget::scrollable(text(&release.notes).size(16))
+            .height(Length::Fixed(300.0))
+            .width(Length::Fill);
+
+        let select_row = row![
+            neo_button("Skip")
+                .width(Length::Fill)
+                .on_press(Message::SkipUpdate),
+            neo_button("Later")
+                .width(Length::Fill)
+                .on_press(Message::GoToPage(Step::Main)),
+            neo_button("Download")
+                .width(Length::Fill)
+                .on_press(Message::OpenDownloadPage),
+        ]
+        .spacing(5)
+        .height(Length::Fixed(50.0));
+
+        let controls = col![title, notes, select_row]
             .align_items(Alignment::Center)
             .width(Length::Fixed(650.0))
             .spacing(30);
@@ -301,41 +1731,83 @@ impl<'a> Step {
     }
 
     fn main(data: &'a Data) -> Element<'a, Message> {
-        let buttons = col![
+        let mut buttons = col![
             neo_button("Select File")
                 .on_press(Message::OpenMidiFilePicker)
                 .width(Length::Fill)
                 .height(Length::Fixed(80.0)),
+            neo_button("Free Play")
+                .on_press(Message::FreePlay)
+                .width(Length::Fill)
+                .height(Length::Fixed(80.0)),
+            neo_button("Metronome")
+                .on_press(Message::Metronome)
+                .width(Length::Fill)
+                .height(Length::Fixed(80.0)),
             neo_button("Settings")
                 .on_press(Message::GoToPage(Step::Settings))
                 .width(Length::Fill)
                 .height(Length::Fixed(80.0)),
-            neo_button("Exit")
-                .on_press(Message::GoToPage(Step::Exit))
-                .width(Length::Fill)
-                .height(Length::Fixed(80.0))
         ]
         .width(Length::Fixed(450.0))
         .spacing(10);
 
-        let column = col![image(data.logo_handle.clone()), buttons]
+        if !data.exports.is_empty() {
+            buttons = buttons.push(
+                neo_button("Exports")
+                    .on_press(Message::GoToPage(Step::Exports))
+                    .width(Length::Fill)
+                    .height(Length::Fixed(80.0)),
+            );
+        }
+
+        let buttons = buttons.push(
+            neo_button("Exit")
+                .on_press(Message::GoToPage(Step::Exit))
+                .width(Length::Fill)
+                .height(Length::Fixed(80.0)),
+        );
+
+        let mut column = col![image(data.logo_handle.clone()), buttons]
             .spacing(40)
             .align_items(Alignment::Center);
 
+        if let Some(remaining) = data.practice_remaining {
+            column = column.push(
+                centered_text(format!(
+                    "Practice left today: {}:{:02}",
+                    remaining / 60,
+                    remaining % 60
+                ))
+                .size(24),
+            );
+        }
+
         let mut content = top_padded(column);
 
         if data.midi_file.is_some() {
             let play_along = checkbox("PlayAlong", data.play_along, Message::PlayAlongCheckbox)
                 .style(theme::checkbox());
+            let melody_boost = checkbox("Melody Boost", data.melody_boost, Message::MelodyBoost)
+                .style(theme::checkbox());
 
             let play = neo_button("Play")
                 .height(Length::Fixed(60.0))
                 .min_width(80.0)
                 .on_press(Message::Play);
 
-            let row = row![play_along, play]
+            let mut row = row![melody_boost, play_along]
                 .spacing(20)
                 .align_items(Alignment::Center);
+            if data.can_export() {
+                row = row.push(
+                    neo_button("Export")
+                        .height(Length::Fixed(60.0))
+                        .min_width(80.0)
+                        .on_press(Message::ExportSong),
+                );
+            }
+            let row = row.push(play);
 
             let container = container(row)
                 .width(Length::Fill)
@@ -353,6 +1825,60 @@ impl<'a> Step {
         center_x(content).into()
     }
 
+    fn exports(data: &'a Data) -> Element<'a, Message> {
+        let title = centered_text("Exports").size(30);
+
+        let mut jobs = col![].spacing(5).width(Length::Fill);
+        for (id, job) in data.exports.iter().enumerate() {
+            let name = text(job.name()).width(Length::Fill);
+            let status = text(data.export_status(id, job));
+            jobs = jobs.push(row![name, status].spacing(10));
+        }
+        let jobs = iced_widget::scrollable(jobs)
+            .height(Length::Fixed(300.0))
+            .width(Length::Fill);
+
+        let eta = match data.export_eta {
+            Some(eta) => format!("Time left: {}", format_eta(eta)),
+            None if data.exports_paused => String::from("Paused"),
+            None => String::new(),
+        };
+
+        let options = row![
+            checkbox("Export audio", data.export_audio, Message::ExportAudio)
+                .style(theme::checkbox()),
+            checkbox("Click track", data.export_click, Message::ExportClick)
+                .style(theme::checkbox()),
+            button(centered_text("Clear finished"))
+                .on_press(Message::ClearFinishedExports)
+                .style(theme::button()),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let pause = if data.exports_paused {
+            neo_button("Resume").on_press(Message::ResumeExports)
+        } else {
+            neo_button("Pause").on_press(Message::PauseExports)
+        };
+
+        let select_row = row![
+            neo_button("Back")
+                .width(Length::Fill)
+                .on_press(Message::GoToPage(Step::Main)),
+            pause.width(Length::Fill),
+        ]
+        .spacing(5)
+        .height(Length::Fixed(50.0));
+
+        let controls = col![title, jobs, centered_text(eta), options, select_row]
+            .align_items(Alignment::Center)
+            .width(Length::Fixed(650.0))
+            .spacing(30);
+
+        center_x(controls).center_y().into()
+    }
+
     fn settings(data: &'a Data) -> Element<'a, Message> {
         let output_list = {
             let outputs = &data.outputs;
@@ -385,6 +1911,43 @@ impl<'a> Step {
             .spacing(10)
         };
 
+        let remap = if let Some(OutputDescriptor::MidiOut(_)) = data.selected_output {
+            let remap = &data.output_remap;
+
+            let channel_title = text("Channel:")
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Fixed(30.0));
+            let channel_list = pick_list(
+                OutputChannel::all(),
+                Some(OutputChannel(remap.channel)),
+                Message::SelectOutputChannel,
+            )
+            .width(Length::Fill)
+            .style(theme::pick_list());
+
+            let transpose_title = text(format!("Transpose: {:+}", remap.transpose))
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Fixed(30.0));
+
+            Some(
+                row![
+                    channel_title,
+                    channel_list,
+                    transpose_title,
+                    button(centered_text("-"))
+                        .on_press(Message::OutputTranspose(remap.transpose - 1))
+                        .style(theme::button()),
+                    button(centered_text("+"))
+                        .on_press(Message::OutputTranspose(remap.transpose + 1))
+                        .style(theme::button()),
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center),
+            )
+        } else {
+            None
+        };
+
         let input_list = {
             let inputs = &data.inputs;
             let selected_input = data.selected_input.clone();
@@ -397,26 +1960,574 @@ impl<'a> Step {
                 .vertical_alignment(Vertical::Center)
                 .height(Length::Fixed(30.0));
 
-            row![
+            let muted = checkbox("Has its own sound", data.input_muted, Message::InputMuted)
+                .style(theme::checkbox());
+
+            let main = row![
                 input_title.width(Length::Fixed(60.0)),
                 input_list.width(Length::FillPortion(3)),
+                muted,
+            ];
+            let main = match data.selected_input.clone() {
+                Some(input) => main.push(input_channel_list(data, input)),
+                None => main,
+            };
+
+            // Other inputs can be played along with the selected one, eg. for duets
+            let mut list = col![main.spacing(10).align_items(Alignment::Center)].spacing(10);
+            for input in data.inputs.iter() {
+                if data.selected_input.as_ref() == Some(input) {
+                    continue;
+                }
+
+                let enabled = data.extra_inputs.contains(&input.to_string());
+                let toggle = {
+                    let input = input.clone();
+                    checkbox(format!("Also use {input}"), enabled, move |v| {
+                        Message::ExtraInput(input.clone(), v)
+                    })
+                    .style(theme::checkbox())
+                    .width(Length::Fill)
+                };
+
+                list = list.push(
+                    row![toggle, input_channel_list(data, input.clone())]
+                        .spacing(10)
+                        .align_items(Alignment::Center),
+                );
+            }
+
+            list
+        };
+
+        let virtual_piano = {
+            let piano = &data.virtual_piano;
+
+            let toggle = checkbox(
+                "Computer keyboard piano",
+                piano.enabled,
+                Message::VirtualPiano,
+            )
+            .style(theme::checkbox())
+            .width(Length::Fill);
+
+            let layout_list = pick_list(
+                &config::VirtualPianoLayout::ALL[..],
+                piano.layout(),
+                Message::SelectVirtualPianoLayout,
+            )
+            .placeholder("Custom")
+            .width(Length::Fixed(130.0))
+            .style(theme::pick_list());
+
+            // First key of the layout, a C as long as it's moved by octaves
+            let octave = text(format!(
+                "From {}",
+                piano_math::names::letter_name(piano.base_note, false)
+            ))
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Fixed(30.0));
+
+            row![
+                toggle,
+                layout_list,
+                octave,
+                button(centered_text("-"))
+                    .on_press(Message::VirtualPianoBaseNote(
+                        piano.base_note.saturating_sub(12)
+                    ))
+                    .style(theme::button()),
+                button(centered_text("+"))
+                    .on_press(Message::VirtualPianoBaseNote(piano.base_note + 12))
+                    .style(theme::button()),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+        };
+
+        let passthrough = {
+            let title = text("Forward from file:")
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Fixed(30.0));
+
+            let controllers = checkbox(
+                "Controllers",
+                data.forward_controllers,
+                Message::ForwardControllers,
+            )
+            .style(theme::checkbox());
+            let programs = checkbox(
+                "Program Changes",
+                data.forward_program_changes,
+                Message::ForwardProgramChanges,
+            )
+            .style(theme::checkbox());
+            let sysex = checkbox("SysEx", data.forward_sysex, Message::ForwardSysEx)
+                .style(theme::checkbox());
+
+            row![title, controllers, programs, sysex]
+                .spacing(10)
+                .align_items(Alignment::Center)
+        };
+
+        let reset_list = {
+            let reset_list = pick_list(
+                &config::SystemReset::ALL[..],
+                Some(data.system_reset),
+                Message::SelectSystemReset,
+            )
+            .width(Length::Fill)
+            .style(theme::pick_list());
+
+            let reset_title = text("Reset on start:")
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Fixed(30.0));
+
+            row![reset_title, reset_list].spacing(10)
+        };
+
+        let velocity_list = {
+            let velocity_list = pick_list(
+                &config::VelocityCurve::ALL[..],
+                Some(data.velocity_curve),
+                Message::SelectVelocityCurve,
+            )
+            .width(Length::Fill)
+            .style(theme::pick_list());
+
+            let velocity_title = text("Velocity:")
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Fixed(30.0));
+
+            row![velocity_title, velocity_list].spacing(10)
+        };
+
+        let theme_list = {
+            let theme_list = pick_list(
+                &data.themes[..],
+                Some(data.theme.clone()),
+                Message::SelectTheme,
+            )
+            .width(Length::Fill)
+            .style(theme::pick_list());
+
+            let theme_title = text("Theme:")
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Fixed(30.0));
+
+            row![theme_title, theme_list].spacing(10)
+        };
+
+        let background = {
+            let image = &data.background_image;
+
+            let kind_title = text("Background:")
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Fixed(30.0));
+            let kind_list = pick_list(
+                &config::BackgroundKind::ALL[..],
+                Some(image.kind),
+                Message::SelectBackgroundKind,
+            )
+            .width(Length::Fill)
+            .style(theme::pick_list());
+
+            let mut row = row![kind_title, kind_list]
+                .spacing(10)
+                .align_items(Alignment::Center);
+
+            if image.kind == config::BackgroundKind::Image {
+                let name = image
+                    .path
+                    .as_ref()
+                    .and_then(|path| path.file_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| String::from("No image"));
+                let choose = button(centered_text("Choose"))
+                    .on_press(Message::OpenBackgroundPicker)
+                    .style(theme::button());
+                let blur = pick_list(
+                    &Amount::ALL[..],
+                    Some(Amount::from_fraction(image.blur)),
+                    Message::BackgroundBlur,
+                )
+                .style(theme::pick_list());
+
+                row = row
+                    .push(text(name))
+                    .push(choose)
+                    .push(text("Blur:"))
+                    .push(blur);
+            }
+
+            if image.kind != config::BackgroundKind::Color {
+                let dim = pick_list(
+                    &Amount::ALL[..],
+                    Some(Amount::from_fraction(image.dim)),
+                    Message::BackgroundDim,
+                )
+                .style(theme::pick_list());
+
+                row = row.push(text("Dim:")).push(dim);
+            }
+
+            row
+        };
+
+        let tolerance_list = data.selected_input.as_ref().map(|_| {
+            let tolerance_list = pick_list(
+                &config::NoteTolerance::ALL[..],
+                Some(data.note_tolerance),
+                Message::SelectNoteTolerance,
+            )
+            .width(Length::Fill)
+            .style(theme::pick_list());
+
+            let tolerance_title = text("Note matching on the input:")
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Fixed(30.0));
+
+            row![tolerance_title, tolerance_list].spacing(10)
+        });
+
+        let scale_overlay = {
+            let scale = &data.scale_overlay;
+            let enabled = checkbox("Scale overlay", scale.enabled, Message::ScaleOverlay)
+                .style(theme::checkbox());
+
+            let mut row = row![enabled].spacing(10).align_items(Alignment::Center);
+            if scale.enabled {
+                let tonic = pick_list(
+                    &Tonic::ALL[..],
+                    Some(Tonic(scale.tonic % 12)),
+                    Message::SelectScaleTonic,
+                )
+                .style(theme::pick_list());
+                let mode = pick_list(
+                    &config::ScaleMode::ALL[..],
+                    Some(scale.mode),
+                    Message::SelectScaleMode,
+                )
+                .width(Length::Fill)
+                .style(theme::pick_list());
+
+                row = row.push(tonic).push(mode);
+            }
+            row
+        };
+
+        let dead_keys = {
+            let title = text("Dead keys:")
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Fixed(30.0));
+            let keys = text_input("C4, F#5", &data.dead_keys)
+                .on_input(Message::DeadKeys)
+                .padding(5)
+                .width(Length::Fill);
+            let octave = checkbox(
+                "Play an octave away",
+                data.dead_key_octave,
+                Message::DeadKeyOctave,
+            )
+            .style(theme::checkbox());
+
+            row![title, keys, octave]
+                .spacing(10)
+                .align_items(Alignment::Center)
+        };
+
+        let quality = {
+            let quality = &data.render_quality;
+
+            let msaa_title = text("Anti-aliasing:")
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Fixed(30.0));
+            let msaa_list = pick_list(
+                &config::Msaa::ALL[..],
+                Some(quality.msaa),
+                Message::SelectMsaa,
+            )
+            .width(Length::Fill)
+            .style(theme::pick_list());
+
+            let scale_title = text("Resolution:")
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Fixed(30.0));
+            let scale_list = pick_list(
+                &ResolutionScale::ALL[..],
+                Some(ResolutionScale::from_scale(quality.resolution_scale())),
+                Message::SelectResolutionScale,
+            )
+            .width(Length::Fill)
+            .style(theme::pick_list());
+
+            let filter_title = text("Scaling:")
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Fixed(30.0));
+            let filter_list = pick_list(
+                &config::TextureFilter::ALL[..],
+                Some(quality.filter),
+                Message::SelectTextureFilter,
+            )
+            .width(Length::Fill)
+            .style(theme::pick_list());
+
+            row![
+                msaa_title,
+                msaa_list,
+                scale_title,
+                scale_list,
+                filter_title,
+                filter_list
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+        };
+
+        let hand_colors = {
+            let colors = &data.hand_colors;
+            let enabled = checkbox("Color notes by hand", colors.enabled, Message::HandColors)
+                .style(theme::checkbox());
+
+            let mut column = col![row![enabled, color_part_picker(data.color_part)]
+                .spacing(10)
+                .align_items(Alignment::Center)]
+            .spacing(10);
+            if colors.enabled {
+                let hand = |label, picker| {
+                    row![
+                        text(label)
+                            .vertical_alignment(Vertical::Center)
+                            .width(Length::Fixed(50.0)),
+                        picker
+                    ]
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                };
+                column = column
+                    .push(hand(
+                        "Left:",
+                        color_picker(&colors.left, data.color_part, Message::LeftHandColor),
+                    ))
+                    .push(hand(
+                        "Right:",
+                        color_picker(&colors.right, data.color_part, Message::RightHandColor),
+                    ));
+            }
+            column
+        };
+
+        let note_labels = {
+            let labels = &data.note_labels;
+            let naming_list = pick_list(
+                &config::NoteNaming::ALL[..],
+                Some(labels.naming),
+                Message::SelectNoteNaming,
+            )
+            .style(theme::pick_list());
+
+            col![
+                row![
+                    checkbox(
+                        "Note names on falling notes",
+                        labels.waterfall,
+                        Message::NoteLabelsWaterfall
+                    )
+                    .style(theme::checkbox()),
+                    checkbox(
+                        "On the keyboard",
+                        labels.keyboard,
+                        Message::NoteLabelsKeyboard
+                    )
+                    .style(theme::checkbox()),
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center),
+                row![
+                    naming_list,
+                    checkbox("Flats", labels.flats, Message::NoteLabelsFlats)
+                        .style(theme::checkbox()),
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center),
             ]
             .spacing(10)
         };
 
+        let pitch_colors = {
+            let colors = &data.pitch_colors;
+            let enabled = checkbox("Color notes by pitch", colors.enabled, Message::PitchColors)
+                .style(theme::checkbox());
+
+            let mut column = col![enabled].spacing(10);
+            if colors.enabled {
+                for class in 0..12 {
+                    let name = piano_math::names::pitch_class_name(class as u8, false);
+                    let picker =
+                        color_picker(&colors.of(class as u8), ColorPart::Both, move |preset| {
+                            Message::PitchColor(class, preset.base)
+                        });
+                    column = column.push(
+                        row![
+                            text(name)
+                                .vertical_alignment(Vertical::Center)
+                                .width(Length::Fixed(50.0)),
+                            picker
+                        ]
+                        .spacing(10)
+                        .align_items(Alignment::Center),
+                    );
+                }
+            }
+            column
+        };
+
+        let data_buttons = {
+            let export = button(centered_text("Export my data"))
+                .on_press(Message::ExportData)
+                .style(theme::button());
+            let import = button(centered_text("Import"))
+                .on_press(Message::ImportData)
+                .style(theme::button());
+
+            let updates = checkbox(
+                "Check for updates",
+                data.check_for_updates,
+                Message::CheckForUpdates,
+            )
+            .style(theme::checkbox());
+
+            row![export, import, updates]
+                .spacing(10)
+                .align_items(Alignment::Center)
+        };
+
+        let practice_lock = {
+            let lock = &data.practice_lock;
+
+            if lock.can_change_with(&data.lock_pin) {
+                let enabled = checkbox("Practice lock", lock.enabled, Message::PracticeLock)
+                    .style(theme::checkbox());
+                let minutes = text(format!("{:.0} min a day", lock.minutes))
+                    .vertical_alignment(Vertical::Center)
+                    .height(Length::Fixed(30.0));
+
+                let pin = text_input(
+                    if lock.pin.is_empty() {
+                        "PIN"
+                    } else {
+                        "New PIN"
+                    },
+                    &data.new_pin,
+                )
+                .on_input(Message::NewPinInput)
+                .password()
+                .padding(5)
+                .width(Length::Fill);
+                let set = button(centered_text(
+                    if data.new_pin.is_empty() && !lock.pin.is_empty() {
+                        "Clear PIN"
+                    } else {
+                        "Set PIN"
+                    },
+                ))
+                .on_press(Message::SetPin)
+                .style(theme::button());
+
+                row![
+                    enabled,
+                    minutes,
+                    button(centered_text("-"))
+                        .on_press(Message::PracticeMinutes(lock.minutes - 5.0))
+                        .style(theme::button()),
+                    button(centered_text("+"))
+                        .on_press(Message::PracticeMinutes(lock.minutes + 5.0))
+                        .style(theme::button()),
+                    pin,
+                    set,
+                ]
+            } else {
+                let title = text("Practice lock, enter the PIN to change it:")
+                    .vertical_alignment(Vertical::Center)
+                    .height(Length::Fixed(30.0));
+                let pin = text_input("PIN", &data.lock_pin)
+                    .on_input(Message::LockPinInput)
+                    .password()
+                    .padding(5)
+                    .width(Length::Fill);
+
+                row![title, pin]
+            }
+            .spacing(10)
+            .align_items(Alignment::Center)
+        };
+
         let buttons = row![neo_button("Back")
             .on_press(Message::GoToPage(Step::Main))
             .width(Length::Fill),]
         .width(Length::Shrink)
         .height(Length::Fixed(50.0));
 
-        let column = col![
-            image(data.logo_handle.clone()),
-            col![output_list, input_list].spacing(10),
-            buttons,
-        ]
-        .spacing(40)
-        .align_items(Alignment::Center);
+        let mut options = col![output_list].spacing(10);
+        if let Some(remap) = remap {
+            options = options.push(remap);
+        }
+        let mut options = options
+            .push(input_list)
+            .push(virtual_piano)
+            .push(passthrough)
+            .push(reset_list)
+            .push(velocity_list)
+            .push(dead_keys);
+        if let Some(tolerance_list) = tolerance_list {
+            options = options.push(tolerance_list);
+        }
+        let options = options
+            .push(scale_overlay)
+            .push(quality)
+            .push(theme_list)
+            .push(background)
+            .push(
+                checkbox(
+                    "Play the input on the output during songs",
+                    data.play_input_notes,
+                    Message::PlayInputNotes,
+                )
+                .style(theme::checkbox()),
+            )
+            .push(
+                checkbox(
+                    "Play the repeats of scores",
+                    data.expand_repeats,
+                    Message::ExpandRepeats,
+                )
+                .style(theme::checkbox()),
+            )
+            .push(
+                checkbox(
+                    "Sparks where notes hit the keyboard",
+                    data.impact_particles,
+                    Message::ImpactParticles,
+                )
+                .style(theme::checkbox()),
+            )
+            .push(
+                checkbox(
+                    "Notes last while the sustain pedal holds them",
+                    data.sustained_notes,
+                    Message::SustainedNotes,
+                )
+                .style(theme::checkbox()),
+            )
+            .push(hand_colors)
+            .push(pitch_colors)
+            .push(note_labels)
+            .push(practice_lock)
+            .push(data_buttons);
+
+        let column = col![image(data.logo_handle.clone()), options, buttons]
+            .spacing(40)
+            .align_items(Alignment::Center);
 
         center_x(top_padded(column)).into()
     }
@@ -425,18 +2536,102 @@ impl<'a> Step {
         let mut tracks = Vec::new();
         if let Some(midi) = data.midi_file.as_ref() {
             for track in midi.tracks.iter().filter(|t| !t.notes.is_empty()) {
-                let color = &data.color_schema[track.track_color_id % data.color_schema.len()].base;
+                let color = track_color(&data.color_schema, track.track_color_id).base;
                 let color = iced_core::Color::from_rgb8(color.0, color.1, color.2);
 
-                let body = segment_button::segment_button()
-                    .button("Mute", Message::Tick)
-                    .button("Auto", Message::Tick)
-                    .button("Human", Message::Tick)
-                    .active(1)
+                let sound = data.track_soundfonts.get(&track.track_id);
+                let program = sound.and_then(|sound| sound.program);
+
+                let title = if track.notes.iter().all(|n| n.channel == 9) {
+                    "Drums"
+                } else {
+                    midi_file::gm::program_name(program.unwrap_or_else(|| track.program()))
+                };
+
+                let track_id = track.track_id;
+
+                let mode = segment_button::segment_button()
+                    .button(
+                        "Mute",
+                        Message::TrackModeSelected(track_id, TrackMode::Mute),
+                    )
+                    .button(
+                        "Auto",
+                        Message::TrackModeSelected(track_id, TrackMode::Auto),
+                    )
+                    .button(
+                        "Human",
+                        Message::TrackModeSelected(track_id, TrackMode::Human),
+                    )
+                    .active(data.track_mode(track_id) as usize)
                     .active_color(color)
                     .build();
+
+                let selected = program
+                    .map(TrackProgram::Program)
+                    .unwrap_or(TrackProgram::FromFile);
+                let program_list = pick_list(TrackProgram::all(), Some(selected), move |p| {
+                    Message::TrackProgramSelected(track_id, p)
+                })
+                .width(Length::Fill)
+                .style(theme::pick_list());
+
+                let font_name = sound
+                    .and_then(|sound| sound.path.as_ref())
+                    .and_then(|path| path.file_stem())
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "Main SoundFont".into());
+
+                let font_row = row![
+                    button(centered_text(font_name).size(14))
+                        .width(Length::Fill)
+                        .on_press(Message::OpenTrackSoundFontPicker(track_id))
+                        .style(theme::button()),
+                    button(centered_text("Reset").size(14))
+                        .on_press(Message::ResetTrackSound(track_id))
+                        .style(theme::button()),
+                ]
+                .spacing(5);
+
+                let visible = checkbox(
+                    "Visible",
+                    !data.hidden_tracks.contains(&track_id),
+                    move |v| Message::TrackVisible(track_id, v),
+                )
+                .style(theme::checkbox());
+                let melody = checkbox("Melody", data.melody_track == Some(track_id), move |v| {
+                    Message::MelodyTrack(track_id, v)
+                })
+                .style(theme::checkbox());
+                let merge = checkbox(
+                    "Merge",
+                    data.merge_selection.contains(&track_id),
+                    move |v| Message::TrackMergeSelected(track_id, v),
+                )
+                .style(theme::checkbox());
+                let visibility_row = row![visible, melody, merge].spacing(10);
+
+                let color_id = track.track_color_id;
+                let colors = color_picker(
+                    &track_color(&data.color_schema, color_id),
+                    data.color_part,
+                    move |preset| Message::TrackColorSelected(color_id, preset),
+                );
+
+                let split_row = Self::split_row(data, track);
+
+                let body = col![
+                    mode,
+                    program_list,
+                    font_row,
+                    visibility_row,
+                    split_row,
+                    colors
+                ]
+                .spacing(8);
+
                 let card = track_card::track_card()
-                    .title("Grand Piano")
+                    .title(title)
                     .subtitle(format!("{} Notes", track.notes.len()))
                     .track_color(color)
                     .body(body)
@@ -451,12 +2646,411 @@ impl<'a> Step {
             .padding(20.0)
             .align_items(Alignment::Center);
 
-        let column = col![vertical_space(Length::Fixed(30.0)), column]
-            .align_items(Alignment::Center)
-            .width(Length::Fill);
+        let column = col![
+            vertical_space(Length::Fixed(30.0)),
+            Self::song_info(data),
+            Self::merge_buttons(data),
+            Self::color_buttons(data),
+            column
+        ]
+        .align_items(Alignment::Center)
+        .width(Length::Fill);
 
         iced_widget::scrollable(column).into()
     }
+
+    /// Fragmented files spread one instrument over many tracks, merging makes them one
+    fn merge_buttons(data: &'a Data) -> Element<'a, Message> {
+        let mut selected = button(centered_text("Merge Selected").size(14)).style(theme::button());
+        if data.merge_selection.len() > 1 {
+            selected = selected.on_press(Message::MergeSelectedTracks);
+        }
+
+        let mut similar = button(centered_text("Merge Similar").size(14)).style(theme::button());
+        if !data.similar_tracks.is_empty() {
+            similar = similar.on_press(Message::MergeSimilarTracks);
+        }
+
+        row![selected, similar]
+            .spacing(5)
+            .align_items(Alignment::Center)
+            .into()
+    }
+
+    /// Split point of a track with how many notes end up on each side, before splitting it
+    fn split_row(data: &'a Data, track: &midi_file::MidiTrack) -> Element<'a, Message> {
+        let track_id = track.track_id;
+        let pitch = data.split_point(track_id);
+        let (high, low) = split_preview(track, pitch);
+
+        let preview = text(format!(
+            "Split at {}: {high} / {low}",
+            piano_math::names::letter_name(pitch, false)
+        ))
+        .size(14)
+        .vertical_alignment(Vertical::Center)
+        .width(Length::Fill);
+
+        let mut split = button(centered_text("Split").size(14)).style(theme::button());
+        if high > 0 && low > 0 {
+            split = split.on_press(Message::SplitTrack(track_id));
+        }
+
+        row![
+            preview,
+            button(centered_text("-").size(14))
+                .on_press(Message::SplitPointSelected(
+                    track_id,
+                    pitch.saturating_sub(1)
+                ))
+                .style(theme::button()),
+            button(centered_text("+").size(14))
+                .on_press(Message::SplitPointSelected(track_id, pitch + 1))
+                .style(theme::button()),
+            split,
+        ]
+        .spacing(5)
+        .align_items(Alignment::Center)
+        .into()
+    }
+
+    /// Which notes the track color pickers change, and a way back to the default colors
+    fn color_buttons(data: &'a Data) -> Element<'a, Message> {
+        let reset = button(centered_text("Reset Colors").size(14))
+            .on_press(Message::ResetTrackColors)
+            .style(theme::button());
+
+        row![color_part_picker(data.color_part), reset]
+            .spacing(10)
+            .padding(Padding::from([10, 0, 0, 0]))
+            .align_items(Alignment::Center)
+            .into()
+    }
+
+    /// Title, composer and copyright stored in the file
+    fn song_info(data: &'a Data) -> Element<'a, Message> {
+        let midi = match data.midi_file.as_ref() {
+            Some(midi) if midi.path.is_some() => midi,
+            _ => return col![].into(),
+        };
+
+        let field = |placeholder: &str, value: &'a Option<String>| {
+            text_input(placeholder, value.as_deref().unwrap_or_default())
+                .padding(10)
+                .width(Length::Fill)
+        };
+
+        let mut save = button(centered_text("Save").size(14)).style(theme::button());
+        if data.metadata != midi.metadata {
+            save = save.on_press(Message::SaveMetadata);
+        }
+
+        row![
+            field("Title", &data.metadata.title).on_input(Message::MetadataTitle),
+            field("Composer", &data.metadata.composer).on_input(Message::MetadataComposer),
+            field("Copyright", &data.metadata.copyright).on_input(Message::MetadataCopyright),
+            save,
+        ]
+        .spacing(5)
+        .align_items(Alignment::Center)
+        .width(Length::Fixed(650.0))
+        .into()
+    }
+}
+
+/// Who plays the notes of a track, in the order of its segment button
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackMode {
+    /// Neither played nor waited for
+    Mute,
+    /// Played for the user
+    Auto,
+    /// Played by the user, play along waits for its notes
+    Human,
+}
+
+impl TrackMode {
+    const ALL: [Self; 3] = [Self::Mute, Self::Auto, Self::Human];
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Mute => "Mute",
+            Self::Auto => "Play for me",
+            Self::Human => "I play this",
+        }
+    }
+}
+
+/// Notes a color picker changes, notes on black keys use the darker color of a schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorPart {
+    Both,
+    White,
+    Black,
+}
+
+impl ColorPart {
+    const ALL: [Self; 3] = [Self::Both, Self::White, Self::Black];
+
+    /// `current` with the colors of `preset` this part covers
+    fn apply(
+        self,
+        current: &config::ColorSchema,
+        preset: &config::ColorSchema,
+    ) -> config::ColorSchema {
+        match self {
+            Self::Both => preset.clone(),
+            Self::White => config::ColorSchema {
+                base: preset.base,
+                dark: current.dark,
+            },
+            Self::Black => config::ColorSchema {
+                base: current.base,
+                dark: preset.dark,
+            },
+        }
+    }
+
+    /// Color of `schema` shown for this part
+    fn shown(self, schema: &config::ColorSchema) -> (u8, u8, u8) {
+        match self {
+            Self::Both | Self::White => schema.base,
+            Self::Black => schema.dark,
+        }
+    }
+}
+
+impl std::fmt::Display for ColorPart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Both => write!(f, "All Notes"),
+            Self::White => write!(f, "White Keys"),
+            Self::Black => write!(f, "Black Keys"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackProgram {
+    FromFile,
+    Program(u8),
+}
+
+impl TrackProgram {
+    fn all() -> Vec<Self> {
+        std::iter::once(Self::FromFile)
+            .chain((0..128).map(Self::Program))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for TrackProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FromFile => write!(f, "Program From File"),
+            Self::Program(program) => write!(f, "{}", midi_file::gm::program_name(*program)),
+        }
+    }
+}
+
+/// Output channel override, `None` keeps channels from the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputChannel(Option<u8>);
+
+impl OutputChannel {
+    fn all() -> Vec<Self> {
+        std::iter::once(Self(None))
+            .chain((0..16).map(|ch| Self(Some(ch))))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for OutputChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            None => write!(f, "From File"),
+            Some(ch) => write!(f, "{}", ch + 1),
+        }
+    }
+}
+
+/// Input channel override, `None` keeps the channels the device sends on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputChannel(Option<u8>);
+
+impl InputChannel {
+    fn all() -> Vec<Self> {
+        std::iter::once(Self(None))
+            .chain((0..16).map(|ch| Self(Some(ch))))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for InputChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            None => write!(f, "As Played"),
+            Some(ch) => write!(f, "Channel {}", ch + 1),
+        }
+    }
+}
+
+/// Resolution of the waterfall and the keyboard, in percent of the window resolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolutionScale(u32);
+
+impl ResolutionScale {
+    const ALL: [Self; 5] = [Self(50), Self(75), Self(100), Self(150), Self(200)];
+
+    fn from_scale(scale: f32) -> Self {
+        Self((scale * 100.0).round() as u32)
+    }
+
+    fn scale(&self) -> f32 {
+        self.0 as f32 / 100.0
+    }
+}
+
+impl std::fmt::Display for ResolutionScale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+/// Strength of a background effect, in percent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Amount(u32);
+
+impl Amount {
+    const ALL: [Self; 5] = [Self(0), Self(25), Self(50), Self(75), Self(100)];
+
+    fn from_fraction(fraction: f32) -> Self {
+        Self((fraction * 100.0).round() as u32)
+    }
+
+    fn fraction(&self) -> f32 {
+        self.0 as f32 / 100.0
+    }
+}
+
+impl std::fmt::Display for Amount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+/// Pitch class the scale overlay starts on, `0` is C
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tonic(u8);
+
+impl Tonic {
+    const ALL: [Self; 12] = [
+        Self(0),
+        Self(1),
+        Self(2),
+        Self(3),
+        Self(4),
+        Self(5),
+        Self(6),
+        Self(7),
+        Self(8),
+        Self(9),
+        Self(10),
+        Self(11),
+    ];
+}
+
+impl std::fmt::Display for Tonic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", piano_math::names::pitch_class_name(self.0, false))
+    }
+}
+
+/// Entry of the theme list that uses no theme file
+const BUILT_IN_THEME: &str = "Built-in";
+
+/// [`BUILT_IN_THEME`] and the theme files
+fn theme_names() -> Vec<String> {
+    let mut names = vec![BUILT_IN_THEME.to_string()];
+    names.extend(neothesia_core::theme::available());
+    names
+}
+
+/// Dead keys as they are typed, `C4, F#5`
+fn dead_key_names(dead_keys: &config::DeadKeys) -> String {
+    dead_keys
+        .keys
+        .iter()
+        .map(|key| piano_math::names::letter_name(*key, false))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Notes of `track` that stay in it and that move to a new track, when split at `pitch`
+fn split_preview(track: &midi_file::MidiTrack, pitch: u8) -> (usize, usize) {
+    let low = track.notes.iter().filter(|n| n.note < pitch).count();
+    (track.notes.len() - low, low)
+}
+
+fn format_eta(eta: Duration) -> String {
+    let secs = eta.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+fn non_empty(text: String) -> Option<String> {
+    (!text.is_empty()).then_some(text)
+}
+
+fn color_part_picker<'a>(part: ColorPart) -> Element<'a, Message> {
+    let mut picker = segment_button::segment_button();
+    for option in ColorPart::ALL {
+        picker = picker.button(&option.to_string(), Message::ColorPartSelected(option));
+    }
+    picker
+        .active(part as usize)
+        .build()
+        .width(Length::Fixed(300.0))
+        .into()
+}
+
+/// Channel everything played on `input` is moved onto
+fn input_channel_list<'a>(data: &Data, input: InputDescriptor) -> Element<'a, Message> {
+    let channel = data.input_channels.get(&input.to_string()).copied();
+    pick_list(
+        InputChannel::all(),
+        Some(InputChannel(channel)),
+        move |ch| Message::SelectInputChannel(input.clone(), ch),
+    )
+    .width(Length::Fixed(130.0))
+    .style(theme::pick_list())
+    .into()
+}
+
+/// Row of preset colors, `current` is marked when it matches one
+fn color_picker<'a>(
+    current: &config::ColorSchema,
+    part: ColorPart,
+    on_pick: impl Fn(config::ColorSchema) -> Message,
+) -> Element<'a, Message> {
+    let current = part.shown(current);
+    let swatches = palette::presets().map(|preset| {
+        let (r, g, b) = part.shown(&preset);
+        button(vertical_space(Length::Fixed(0.0)))
+            .width(Length::Fixed(20.0))
+            .height(Length::Fixed(20.0))
+            .on_press(on_pick(preset))
+            .style(theme::swatch(
+                iced_core::Color::from_rgb8(r, g, b),
+                (r, g, b) == current,
+            ))
+            .into()
+    });
+
+    row(swatches.collect()).spacing(4).into()
 }
 
 fn centered_text<'a>(label: impl ToString) -> iced_widget::Text<'a, Renderer> {
@@ -490,13 +3084,14 @@ fn center_x<'a, MSG: 'a>(
 }
 
 fn open_midi_file_picker(
+    expand_repeats: bool,
     f: impl FnOnce(Option<midi_file::Midi>) -> Message + 'static + Send,
 ) -> Command<Message> where
 {
     Command::perform(
         async {
             let file = rfd::AsyncFileDialog::new()
-                .add_filter("midi", &["mid", "midi"])
+                .add_filter("midi", midi_file::SONG_EXTENSIONS)
                 .pick_file()
                 .await;
 
@@ -506,7 +3101,7 @@ fn open_midi_file_picker(
                 let thread = async_thread::Builder::new()
                     .name("midi-loader".into())
                     .spawn(move || {
-                        let midi = midi_file::Midi::new(file.path());
+                        let midi = midi_file::Midi::with_repeats(file.path(), expand_repeats);
 
                         if let Err(e) = &midi {
                             log::error!("{}", e);
@@ -551,3 +3146,77 @@ fn open_sound_font_picker(
         f,
     )
 }
+
+fn open_image_picker(
+    f: impl FnOnce(Option<PathBuf>) -> Message + 'static + Send,
+) -> Command<Message> {
+    Command::perform(
+        async {
+            let file = rfd::AsyncFileDialog::new()
+                .add_filter("Image", &["png", "jpg", "jpeg", "gif"])
+                .pick_file()
+                .await;
+
+            file.map(|f| f.path().to_owned())
+        },
+        f,
+    )
+}
+
+fn save_data_bundle_picker(
+    f: impl FnOnce(Option<PathBuf>) -> Message + 'static + Send,
+) -> Command<Message> {
+    Command::perform(
+        async {
+            let file = rfd::AsyncFileDialog::new()
+                .add_filter("Neothesia data", &["ron"])
+                .set_file_name("neothesia-data.ron")
+                .save_file()
+                .await;
+
+            file.map(|f| f.path().to_owned())
+        },
+        f,
+    )
+}
+
+fn open_data_bundle_picker(
+    f: impl FnOnce(Option<PathBuf>) -> Message + 'static + Send,
+) -> Command<Message> {
+    Command::perform(
+        async {
+            let file = rfd::AsyncFileDialog::new()
+                .add_filter("Neothesia data", &["ron"])
+                .pick_file()
+                .await;
+
+            file.map(|f| f.path().to_owned())
+        },
+        f,
+    )
+}
+
+fn check_for_updates(
+    f: impl FnOnce(Option<Release>) -> Message + 'static + Send,
+) -> Command<Message> {
+    Command::perform(
+        async {
+            let thread = async_thread::Builder::new()
+                .name("update-check".into())
+                .spawn(|| match update_check::fetch_newer_release() {
+                    Ok(release) => release,
+                    Err(err) => {
+                        log::warn!("Update check failed: {err}");
+                        None
+                    }
+                });
+
+            if let Ok(thread) = thread {
+                thread.join().await.ok().flatten()
+            } else {
+                None
+            }
+        },
+        f,
+    )
+}
@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::{cell::Cell, rc::Rc};
 
 use iced_graphics::core::Color;
 use iced_style::{button, pick_list};
@@ -9,6 +9,19 @@ const SURFACE: Color = Color::from_rgb(
     0x3B as f32 / 255.0,
 );
 
+thread_local! {
+    static ACCENT: Cell<Color> = Cell::new(Color::from_rgba8(160, 81, 255, 1.0));
+}
+
+/// Accent color of the widgets, set by the theme
+pub fn accent() -> Color {
+    ACCENT.with(Cell::get)
+}
+
+pub fn set_accent((r, g, b): (u8, u8, u8)) {
+    ACCENT.with(|accent| accent.set(Color::from_rgba8(r, g, b, 1.0)));
+}
+
 pub fn pick_list() -> iced_style::theme::PickList {
     iced_style::theme::PickList::Custom(Rc::new(PickListStyle), Rc::new(MenuStyle))
 }
@@ -31,7 +44,7 @@ impl iced_style::pick_list::StyleSheet for PickListStyle {
     }
 
     fn hovered(&self, _style: &Self::Style) -> pick_list::Appearance {
-        let accent = Color::from_rgba8(160, 81, 255, 1.0);
+        let accent = accent();
         pick_list::Appearance {
             text_color: Color::WHITE,
             background: iced_core::Background::Color(Color::BLACK),
@@ -52,7 +65,7 @@ impl iced_style::menu::StyleSheet for MenuStyle {
     type Style = iced_style::Theme;
 
     fn appearance(&self, _style: &Self::Style) -> iced_style::menu::Appearance {
-        let accent = Color::from_rgba8(160, 81, 255, 1.0);
+        let accent = accent();
         iced_style::menu::Appearance {
             text_color: Color::WHITE,
             background: iced_core::Background::Color(Color::BLACK),
@@ -85,7 +98,7 @@ impl iced_style::button::StyleSheet for ButtonStyle {
     }
 
     fn hovered(&self, _style: &Self::Style) -> button::Appearance {
-        let accent = Color::from_rgba8(160, 81, 255, 1.0);
+        let accent = accent();
         button::Appearance {
             text_color: Color::WHITE,
             border_color: accent,
@@ -106,7 +119,7 @@ impl iced_style::checkbox::StyleSheet for CheckboxStyle {
     type Style = iced_style::Theme;
 
     fn active(&self, _style: &Self::Style, is_checked: bool) -> iced_style::checkbox::Appearance {
-        let active = Color::from_rgba8(160, 81, 255, 1.0);
+        let active = accent();
         iced_style::checkbox::Appearance {
             background: if is_checked { active } else { SURFACE }.into(),
             text_color: Some(Color::WHITE),
@@ -118,7 +131,7 @@ impl iced_style::checkbox::StyleSheet for CheckboxStyle {
     }
 
     fn hovered(&self, style: &Self::Style, is_checked: bool) -> iced_style::checkbox::Appearance {
-        let active = Color::from_rgba8(160, 81, 255, 1.0);
+        let active = accent();
         iced_style::checkbox::Appearance {
             background: Color {
                 a: 0.8,
@@ -129,3 +142,35 @@ impl iced_style::checkbox::StyleSheet for CheckboxStyle {
         }
     }
 }
+
+/// Color picker button filled with `color`, the picked one has a white border
+pub fn swatch(color: Color, selected: bool) -> iced_style::theme::Button {
+    iced_style::theme::Button::Custom(Box::new(SwatchStyle { color, selected }))
+}
+
+struct SwatchStyle {
+    color: Color,
+    selected: bool,
+}
+
+impl iced_style::button::StyleSheet for SwatchStyle {
+    type Style = iced_style::Theme;
+
+    fn active(&self, _style: &Self::Style) -> button::Appearance {
+        button::Appearance {
+            border_color: if self.selected { Color::WHITE } else { SURFACE },
+            border_width: if self.selected { 2.0 } else { 1.0 },
+            border_radius: iced_core::BorderRadius::from(4.0),
+            background: Some(iced_core::Background::Color(self.color)),
+            ..Default::default()
+        }
+    }
+
+    fn hovered(&self, style: &Self::Style) -> button::Appearance {
+        let accent = accent();
+        button::Appearance {
+            border_color: if self.selected { Color::WHITE } else { accent },
+            ..self.active(style)
+        }
+    }
+}
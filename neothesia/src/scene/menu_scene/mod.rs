@@ -2,9 +2,12 @@ mod iced_menu;
 
 mod neo_btn;
 mod segment_button;
+mod settings_window;
 mod track_card;
 mod wrap;
 
+pub use settings_window::SettingsWindow;
+
 use std::time::Duration;
 
 use iced_menu::AppUi;
@@ -17,6 +20,7 @@ use crate::{
     iced_utils::{
         iced_conversion,
         iced_state::{self, Program},
+        CommandFutures,
     },
     scene::{Scene, SceneType},
     target::Target,
@@ -27,14 +31,19 @@ type Renderer = iced_wgpu::Renderer<Theme>;
 #[derive(Debug)]
 pub enum Event {
     Play,
+    /// Play without a song, see [`crate::scene::free_play_scene`]
+    FreePlay,
+    /// Metronome without a song, see [`crate::scene::metronome_scene`]
+    Metronome,
 }
 
 pub struct MenuScene {
     bg_pipeline: BgPipeline,
     iced_state: iced_state::State<AppUi>,
+    futures: CommandFutures<iced_menu::Message>,
 
-    context: std::task::Context<'static>,
-    futures: Vec<futures::future::BoxFuture<'static, iced_menu::Message>>,
+    /// Sent when a screen reader presses the accessibility item at the same index
+    accessible_messages: Vec<Option<iced_menu::Message>>,
 }
 
 impl MenuScene {
@@ -49,9 +58,9 @@ impl MenuScene {
         let mut scene = Self {
             bg_pipeline: BgPipeline::new(&target.gpu),
             iced_state,
+            futures: CommandFutures::default(),
 
-            context: std::task::Context::from_waker(futures::task::noop_waker_ref()),
-            futures: Vec::new(),
+            accessible_messages: Vec::new(),
         };
 
         scene.resize(target);
@@ -145,30 +154,27 @@ impl Scene for MenuScene {
     fn main_events_cleared(&mut self, target: &mut Target) {
         if !self.iced_state.is_queue_empty() {
             if let Some(command) = self.iced_state.update(target) {
-                for a in command.actions() {
-                    match a {
-                        iced_runtime::command::Action::Future(f) => {
-                            self.futures.push(f);
-                        }
-                        _ => {}
-                    }
-                }
+                self.futures.push(command);
             }
         }
 
-        let context = &mut self.context;
-        let mut messages = Vec::new();
+        for msg in self.futures.poll() {
+            self.iced_state.queue_message(msg);
+        }
 
-        self.futures.retain_mut(|f| match f.as_mut().poll(context) {
-            std::task::Poll::Ready(msg) => {
-                messages.push(msg);
-                false
-            }
-            std::task::Poll::Pending => true,
-        });
+        let (items, messages) = self
+            .iced_state
+            .program()
+            .accessible_items()
+            .into_iter()
+            .unzip();
+        target.accessibility.set_items(items);
+        self.accessible_messages = messages;
+    }
 
-        for msg in messages {
-            self.iced_state.queue_message(msg);
+    fn accessibility_action(&mut self, _target: &mut Target, item: usize) {
+        if let Some(Some(msg)) = self.accessible_messages.get(item) {
+            self.iced_state.queue_message(msg.clone());
         }
     }
 }
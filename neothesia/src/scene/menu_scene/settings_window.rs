@@ -0,0 +1,183 @@
+use wgpu_jumpstart::Surface;
+use winit::{
+    event::WindowEvent,
+    event_loop::EventLoopWindowTarget,
+    window::{Window, WindowBuilder, WindowId},
+};
+
+use super::iced_menu::{self, AppUi};
+use crate::{
+    iced_utils::{
+        iced_conversion,
+        iced_state::{self, Program},
+        CommandFutures, IcedManager,
+    },
+    target::Target,
+    utils::window::WindowState,
+    NeothesiaEvent,
+};
+
+/// Settings page in its own window, so it can be used while a song keeps playing in the main one
+pub struct SettingsWindow {
+    window: Window,
+    surface: Surface,
+    window_state: WindowState,
+    iced_manager: IcedManager,
+    iced_state: iced_state::State<AppUi>,
+    futures: CommandFutures<iced_menu::Message>,
+}
+
+impl SettingsWindow {
+    pub fn new(
+        target: &mut Target,
+        instance: &wgpu::Instance,
+        event_loop: &EventLoopWindowTarget<NeothesiaEvent>,
+    ) -> Option<Self> {
+        let window = WindowBuilder::new()
+            .with_title("Neothesia Settings")
+            .with_inner_size(winit::dpi::LogicalSize {
+                width: 800.0,
+                height: 720.0,
+            })
+            .with_theme(Some(winit::window::Theme::Dark))
+            .build(event_loop)
+            .map_err(|err| log::error!("Failed to open settings window: {err}"))
+            .ok()?;
+
+        let surface = unsafe { instance.create_surface(&window) }
+            .map_err(|err| log::error!("Failed to open settings window: {err}"))
+            .ok()?;
+
+        let window_state = WindowState::new(&window);
+        let size = window_state.physical_size;
+
+        let surface = Surface::new(
+            &target.gpu.device,
+            surface,
+            target.gpu.texture_format,
+            size.width,
+            size.height,
+        );
+
+        let mut iced_manager = IcedManager::new(
+            &target.gpu.device,
+            &target.gpu.queue,
+            target.gpu.texture_format,
+            (size.width, size.height),
+            window_state.scale_factor,
+        );
+
+        let iced_state = iced_state::State::new(
+            AppUi::settings_window(target),
+            iced_manager.viewport.logical_size(),
+            &mut iced_manager.renderer,
+        );
+
+        Some(Self {
+            window,
+            surface,
+            window_state,
+            iced_manager,
+            iced_state,
+            futures: CommandFutures::default(),
+        })
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    pub fn focus(&self) {
+        self.window.focus_window();
+    }
+
+    /// The iced program only knows how to work with the main window,
+    /// so the state of this one is swapped in for the duration of a call
+    fn swap_into(&mut self, target: &mut Target) {
+        std::mem::swap(&mut self.window_state, &mut target.window_state);
+        std::mem::swap(&mut self.iced_manager, &mut target.iced_manager);
+    }
+
+    pub fn window_event(&mut self, target: &mut Target, event: &WindowEvent) {
+        self.window_state.window_event(event);
+
+        match event {
+            WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                let size = self.window_state.physical_size;
+                self.surface
+                    .resize_swap_chain(&target.gpu.device, size.width, size.height);
+                self.iced_manager
+                    .resize((size.width, size.height), self.window_state.scale_factor);
+            }
+            _ => {}
+        }
+
+        if let Some(event) = iced_conversion::window_event(
+            event,
+            self.iced_manager.viewport.scale_factor(),
+            self.window_state.modifers_state,
+        ) {
+            self.iced_state.queue_event(event.clone());
+
+            if let iced_core::event::Event::Keyboard(event) = &event {
+                if let Some(msg) = self.iced_state.program().keyboard_input(event) {
+                    self.iced_state.queue_message(msg);
+                }
+            }
+        }
+    }
+
+    pub fn main_events_cleared(&mut self, target: &mut Target) {
+        self.iced_state.queue_message(iced_menu::Message::Tick);
+
+        self.swap_into(target);
+        let command = self.iced_state.update(target);
+        self.swap_into(target);
+
+        if let Some(command) = command {
+            self.futures.push(command);
+        }
+
+        for msg in self.futures.poll() {
+            self.iced_state.queue_message(msg);
+        }
+
+        self.window.request_redraw();
+    }
+
+    pub fn render(&mut self, target: &mut Target) {
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(err) => {
+                log::warn!("{:?}", err);
+                return;
+            }
+        };
+
+        let view = &frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        target
+            .gpu
+            .clear(view, target.config.themed_background_color().into());
+
+        self.iced_manager
+            .renderer
+            .with_primitives(|backend, primitive| {
+                backend.present(
+                    &target.gpu.device,
+                    &target.gpu.queue,
+                    &mut target.gpu.encoder,
+                    None,
+                    view,
+                    primitive,
+                    &self.iced_manager.viewport,
+                    &self.iced_manager.debug.overlay(),
+                )
+            });
+
+        target.gpu.submit();
+        frame.present();
+    }
+}
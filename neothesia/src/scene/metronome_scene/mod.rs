@@ -0,0 +1,359 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use neothesia_core::config::{ClickPattern, MetronomeSound};
+use neothesia_engine::{Click, ClickScheduler, TapTempo};
+use neothesia_pipelines::quad::{QuadInstance, QuadPipeline};
+use wgpu_jumpstart::Color;
+use winit::event::{ElementState, VirtualKeyCode, WindowEvent};
+
+use super::{playing_scene::keyboard_events, Scene, SceneType};
+use crate::{
+    accessibility::Item, event_bus::AppEvent, midi_event::MidiEvent, target::Target, NeothesiaEvent,
+};
+
+/// Clicks are handed to the output this far ahead, so they sound on time between frames
+const LOOKAHEAD: Duration = Duration::from_millis(50);
+/// How long metronome notes are held
+const NOTE_LEN: Duration = Duration::from_millis(100);
+const BEAT_SIZE: f32 = 40.0;
+const BEAT_GAP: f32 = 16.0;
+
+/// Metronome without a song, with tap tempo, subdivisions, accents and a tempo ramp.
+/// The keyboard plays on the output meanwhile, and its pedal taps the tempo
+pub struct MetronomeScene {
+    quad_pipeline: QuadPipeline,
+    scheduler: ClickScheduler,
+    running: bool,
+    /// Handed to the output, waiting to be heard. Delays count down every frame
+    pending: VecDeque<Click>,
+    /// Last click heard
+    current: Option<Click>,
+    tap: TapTempo,
+    /// Clock of the taps
+    start: Instant,
+    pedal_down: bool,
+}
+
+impl MetronomeScene {
+    pub fn new(target: &mut Target) -> Self {
+        target.accessibility.set_items(vec![
+            Item::text("Metronome, Space starts and stops it"),
+            Item::text("T or the sustain pedal taps the tempo, Up and Down change it"),
+            Item::text("Left and Right change the beats of a measure, S the clicks per beat"),
+            Item::text("Number keys accent their beat"),
+            Item::text("R turns the tempo ramp on, the brackets set where it stops"),
+            Item::text("Minus and Plus set the ramp step, Comma and Period how often it steps"),
+            Item::text("Escape goes back to the menu"),
+        ]);
+
+        Self {
+            quad_pipeline: QuadPipeline::new(&target.gpu, &target.transform_uniform),
+            scheduler: ClickScheduler::new(),
+            running: false,
+            pending: VecDeque::new(),
+            current: None,
+            tap: TapTempo::new(),
+            start: Instant::now(),
+            pedal_down: false,
+        }
+    }
+
+    fn toggle_running(&mut self) {
+        self.running = !self.running;
+        self.scheduler = ClickScheduler::new();
+        self.pending.clear();
+        self.current = None;
+    }
+
+    fn tap(&mut self, target: &mut Target) {
+        if let Some(bpm) = self.tap.tap(self.start.elapsed()) {
+            target.config.metronome_practice.bpm = bpm
+                .round()
+                .clamp(ClickPattern::MIN_BPM, ClickPattern::MAX_BPM);
+        }
+    }
+
+    /// Sounds like the metronome of the playing scene, subdivisions are quieter
+    fn play(&self, target: &Target, click: &Click) {
+        use midi_file::midly::{num::u7, MidiMessage};
+
+        let metronome = &target.config.metronome;
+        let volume = metronome.volume.clamp(0.0, 1.0);
+        let volume = if click.subdivision == 0 {
+            volume
+        } else {
+            volume * 0.5
+        };
+
+        let mut output = target.output_manager.borrow_mut();
+        match metronome.sound {
+            MetronomeSound::Click => output.click(click.accent, volume, click.delay),
+            MetronomeSound::Note => {
+                let key = if click.accent {
+                    metronome.accent_note
+                } else {
+                    metronome.note
+                };
+                let key = u7::new(key.min(127));
+                let event = |message| midi_file::MidiEvent {
+                    channel: 9,
+                    delta: 0,
+                    timestamp: Duration::ZERO,
+                    message,
+                    track_id: usize::MAX,
+                    track_color_id: 0,
+                };
+
+                let vel = u7::new(((volume * 127.0).round() as u8).max(1));
+                output.midi_event_delayed(&event(MidiMessage::NoteOn { key, vel }), click.delay);
+                output.midi_event_delayed(
+                    &event(MidiMessage::NoteOff {
+                        key,
+                        vel: u7::new(0),
+                    }),
+                    click.delay + NOTE_LEN,
+                );
+            }
+        }
+    }
+
+    fn settings_keyboard_input(&mut self, target: &mut Target, key: VirtualKeyCode) {
+        let pattern = &mut target.config.metronome_practice;
+        let ramp = &mut pattern.ramp;
+        let bpm = |bpm: f32| bpm.clamp(ClickPattern::MIN_BPM, ClickPattern::MAX_BPM);
+
+        match key {
+            VirtualKeyCode::Up => pattern.bpm = bpm(pattern.bpm + 1.0),
+            VirtualKeyCode::Down => pattern.bpm = bpm(pattern.bpm - 1.0),
+            VirtualKeyCode::PageUp => pattern.bpm = bpm(pattern.bpm + 10.0),
+            VirtualKeyCode::PageDown => pattern.bpm = bpm(pattern.bpm - 10.0),
+            VirtualKeyCode::Right => {
+                pattern.beats = (pattern.beats + 1).min(ClickPattern::MAX_BEATS);
+            }
+            VirtualKeyCode::Left => pattern.beats = pattern.beats.saturating_sub(1).max(1),
+            VirtualKeyCode::S => {
+                pattern.subdivision = pattern.subdivision % ClickPattern::MAX_SUBDIVISION + 1;
+            }
+            VirtualKeyCode::R => ramp.enabled = !ramp.enabled,
+            VirtualKeyCode::LBracket => ramp.target = bpm(ramp.target - 5.0),
+            VirtualKeyCode::RBracket => ramp.target = bpm(ramp.target + 5.0),
+            VirtualKeyCode::Minus => ramp.step = (ramp.step - 1.0).max(1.0),
+            VirtualKeyCode::Plus | VirtualKeyCode::Equals => {
+                ramp.step = (ramp.step + 1.0).min(20.0)
+            }
+            VirtualKeyCode::Comma => ramp.every = ramp.every.saturating_sub(1).max(1),
+            VirtualKeyCode::Period => ramp.every = (ramp.every + 1).min(32),
+            _ => {
+                if let Some(beat) = number_key(key) {
+                    if beat < pattern.beats {
+                        pattern.toggle_accent(beat);
+                    }
+                }
+            }
+        }
+    }
+
+    fn queue_text(&self, target: &mut Target) {
+        let pattern = &target.config.metronome_practice;
+        let width = target.window_state.logical_size.width;
+        let height = target.window_state.logical_size.height;
+
+        let measure = self.current.map_or(0, |click| click.measure);
+        let bpm = pattern.bpm_at(measure);
+
+        let subdivision = match pattern.subdivision {
+            1 => String::from("Beats only"),
+            clicks => format!("{clicks} clicks per beat"),
+        };
+        let ramp = &pattern.ramp;
+        let ramp = if ramp.enabled {
+            let sign = if ramp.target >= pattern.bpm { "+" } else { "-" };
+            format!(
+                "Ramp: {sign}{:.0} BPM every {} measure{} until {:.0} BPM",
+                ramp.step,
+                ramp.every,
+                if ramp.every == 1 { "" } else { "s" },
+                ramp.target,
+            )
+        } else {
+            String::from("Ramp: Off")
+        };
+        let status = if self.running {
+            "Space stops"
+        } else {
+            "Space starts, T taps the tempo"
+        };
+
+        target.text_renderer.queue_text(wgpu_glyph::Section {
+            screen_position: (width / 2.0, height * 0.2),
+            text: vec![
+                wgpu_glyph::Text::new(&format!("{bpm:.0} BPM\n"))
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(96.0),
+                wgpu_glyph::Text::new(&format!(
+                    "{} beats, {subdivision}\n{ramp}\n{status}",
+                    pattern.beats
+                ))
+                .with_color([0.7, 0.7, 0.7, 1.0])
+                .with_scale(22.0),
+            ],
+            layout: wgpu_glyph::Layout::default_wrap().h_align(wgpu_glyph::HorizontalAlign::Center),
+            ..Default::default()
+        });
+    }
+
+    /// Row of beats, the one heard last is lit and accents stand out
+    fn update_beats(&mut self, target: &Target) {
+        let pattern = &target.config.metronome_practice;
+        let width = target.window_state.logical_size.width;
+        let height = target.window_state.logical_size.height;
+
+        let row = pattern.beats as f32 * (BEAT_SIZE + BEAT_GAP) - BEAT_GAP;
+        let x = (width - row) / 2.0;
+        let y = height * 0.6;
+
+        let quads = (0..pattern.beats)
+            .map(|beat| {
+                let lit = self.current.map_or(false, |click| click.beat == beat);
+                let accent = pattern.is_accent(beat);
+                let color = match (lit, accent) {
+                    (true, true) => Color::new(1.0, 0.3, 0.3, 1.0),
+                    (true, false) => Color::new(1.0, 1.0, 1.0, 1.0),
+                    (false, true) => Color::new(1.0, 0.3, 0.3, 0.3),
+                    (false, false) => Color::new(1.0, 1.0, 1.0, 0.15),
+                };
+
+                QuadInstance {
+                    position: [x + beat as f32 * (BEAT_SIZE + BEAT_GAP), y],
+                    size: [BEAT_SIZE, BEAT_SIZE],
+                    color: color.into_linear_rgba(),
+                    border_radius: [BEAT_SIZE / 2.0; 4],
+                }
+            })
+            .collect();
+
+        self.quad_pipeline
+            .update_instance_buffer(&target.gpu.queue, quads);
+    }
+}
+
+/// Beat of a number key, `1` is the downbeat and `0` the tenth beat
+fn number_key(key: VirtualKeyCode) -> Option<u32> {
+    let beat = match key {
+        VirtualKeyCode::Key1 => 0,
+        VirtualKeyCode::Key2 => 1,
+        VirtualKeyCode::Key3 => 2,
+        VirtualKeyCode::Key4 => 3,
+        VirtualKeyCode::Key5 => 4,
+        VirtualKeyCode::Key6 => 5,
+        VirtualKeyCode::Key7 => 6,
+        VirtualKeyCode::Key8 => 7,
+        VirtualKeyCode::Key9 => 8,
+        VirtualKeyCode::Key0 => 9,
+        _ => return None,
+    };
+    Some(beat)
+}
+
+impl Scene for MetronomeScene {
+    fn scene_type(&self) -> SceneType {
+        SceneType::Metronome
+    }
+
+    fn update(&mut self, target: &mut Target, delta: Duration) {
+        for click in self.pending.iter_mut() {
+            click.delay = click.delay.saturating_sub(delta);
+        }
+        while let Some(click) = self.pending.front() {
+            if !click.delay.is_zero() {
+                break;
+            }
+            self.current = self.pending.pop_front();
+        }
+
+        if self.running {
+            let clicks = self
+                .scheduler
+                .update(&target.config.metronome_practice, delta, LOOKAHEAD);
+            for click in clicks {
+                self.play(target, &click);
+                self.pending.push_back(click);
+            }
+        }
+
+        self.update_beats(target);
+        self.queue_text(target);
+    }
+
+    fn render(&mut self, target: &mut Target, view: &wgpu::TextureView) {
+        let mut render_pass = target
+            .gpu
+            .encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+        self.quad_pipeline
+            .render(&target.transform_uniform, &mut render_pass);
+    }
+
+    fn window_event(&mut self, target: &mut Target, event: &WindowEvent) {
+        if let WindowEvent::KeyboardInput { input, .. } = event {
+            let key = match input.virtual_keycode {
+                Some(key) => key,
+                None => return,
+            };
+
+            // Taps count when the key goes down, not when it's let go of
+            if input.state == ElementState::Pressed {
+                if key == VirtualKeyCode::T {
+                    self.tap(target);
+                }
+                return;
+            }
+
+            match key {
+                VirtualKeyCode::Escape => {
+                    target.proxy.send_event(NeothesiaEvent::GoBack).ok();
+                }
+                VirtualKeyCode::Space => self.toggle_running(),
+                key => self.settings_keyboard_input(target, key),
+            }
+        }
+    }
+
+    fn midi_event(&mut self, target: &mut Target, event: &MidiEvent) {
+        target.event_bus.publish(AppEvent::UserMidi(*event));
+
+        if let MidiEvent::Controller {
+            controller, value, ..
+        } = *event
+        {
+            if controller == midi_file::sustain::SUSTAIN_CONTROLLER {
+                let down = midi_file::sustain::is_down(value);
+                if down && !self.pedal_down {
+                    self.tap(target);
+                }
+                self.pedal_down = down;
+            }
+        }
+
+        keyboard_events::play_user_note(target, event);
+    }
+
+    fn is_idle(&self) -> bool {
+        !self.running
+    }
+}
@@ -1,7 +1,10 @@
+pub mod free_play_scene;
 pub mod menu_scene;
+pub mod metronome_scene;
 pub mod playing_scene;
+pub mod transition;
 
-use crate::{midi_event::MidiEvent, target::Target};
+use crate::{global_hotkeys::HotkeyAction, midi_event::MidiEvent, target::Target};
 use std::time::Duration;
 use winit::event::WindowEvent;
 
@@ -13,11 +16,20 @@ pub trait Scene {
     fn render(&mut self, target: &mut Target, view: &wgpu::TextureView);
     fn window_event(&mut self, _target: &mut Target, _event: &WindowEvent) {}
     fn midi_event(&mut self, _target: &mut Target, _event: &MidiEvent) {}
+    fn global_hotkey(&mut self, _target: &mut Target, _action: HotkeyAction) {}
+    /// Screen reader pressed the button at `item` of the accessibility items set by the scene
+    fn accessibility_action(&mut self, _target: &mut Target, _item: usize) {}
     fn main_events_cleared(&mut self, _target: &mut Target) {}
+    /// Nothing on screen moves on its own, so frames can be rendered less often
+    fn is_idle(&self) -> bool {
+        false
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum SceneType {
     MainMenu,
     Playing,
+    FreePlay,
+    Metronome,
 }
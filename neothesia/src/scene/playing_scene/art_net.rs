@@ -0,0 +1,211 @@
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::mpsc::Receiver,
+    time::Duration,
+};
+
+use crate::{
+    config::{DmxChannel, DmxSource},
+    event_bus::AppEvent,
+    midi_event::MidiEvent,
+    target::Target,
+};
+
+const ART_NET_PORT: u16 = 6454;
+const DMX_CHANNELS: usize = 512;
+
+/// DMX refreshes at most 44 times a second
+const MIN_INTERVAL: Duration = Duration::from_millis(23);
+/// Nodes fall back to their own state when nothing arrives for a while
+const KEEP_ALIVE: Duration = Duration::from_secs(1);
+
+/// Sum of velocities, relative to 127, that counts as full energy
+const FULL_ENERGY: f32 = 6.0;
+/// How quickly energy follows the music, in seconds
+const ENERGY_SMOOTHING: f32 = 0.25;
+
+/// Sends DMX frames that follow the notes played by the song and the user,
+/// see [`crate::config::ArtNet`]
+pub struct ArtNetOutput {
+    events: Receiver<AppEvent>,
+    socket: UdpSocket,
+    address: SocketAddr,
+    universe: u16,
+    channels: Vec<DmxChannel>,
+
+    /// Velocity of held notes, keyed by `(channel, key)`
+    held: HashMap<(u8, u8), u8>,
+    energy: f32,
+
+    dmx: [u8; DMX_CHANNELS],
+    sequence: u8,
+    since_send: Duration,
+    dirty: bool,
+}
+
+impl ArtNetOutput {
+    /// Returns `None` when disabled, or when the node can't be reached
+    pub fn new(target: &Target) -> Option<Self> {
+        let config = &target.config.art_net;
+        if !config.enabled {
+            return None;
+        }
+
+        let address = (config.address.as_str(), ART_NET_PORT)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next());
+        let address = match address {
+            Some(address) => address,
+            None => {
+                log::error!("Art-Net: invalid address {}", config.address);
+                return None;
+            }
+        };
+
+        let socket = UdpSocket::bind(("0.0.0.0", 0)).and_then(|socket| {
+            socket.set_broadcast(true)?;
+            socket.set_nonblocking(true)?;
+            Ok(socket)
+        });
+        let socket = match socket {
+            Ok(socket) => socket,
+            Err(err) => {
+                log::error!("Art-Net: {err}");
+                return None;
+            }
+        };
+
+        Some(Self {
+            events: target.event_bus.subscribe(),
+            socket,
+            address,
+            universe: config.universe & 0x7FFF,
+            channels: config.channels.clone(),
+
+            held: HashMap::new(),
+            energy: 0.0,
+
+            dmx: [0; DMX_CHANNELS],
+            sequence: 0,
+            since_send: KEEP_ALIVE,
+            dirty: true,
+        })
+    }
+
+    pub fn update(&mut self, delta: Duration) {
+        use midi_file::midly::MidiMessage;
+
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                AppEvent::FileMidi(event) => match event.message {
+                    MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                        self.held
+                            .insert((event.channel, key.as_int()), vel.as_int());
+                    }
+                    MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                        self.held.remove(&(event.channel, key.as_int()));
+                    }
+                    _ => {}
+                },
+                AppEvent::UserMidi(MidiEvent::NoteOn {
+                    channel, key, vel, ..
+                }) => {
+                    self.held.insert((channel, key), vel);
+                }
+                AppEvent::UserMidi(MidiEvent::NoteOff { channel, key }) => {
+                    self.held.remove(&(channel, key));
+                }
+                AppEvent::PlaybackSeek(_)
+                | AppEvent::PlaybackPaused
+                | AppEvent::PlaybackFinished => self.held.clear(),
+                _ => {}
+            }
+        }
+
+        let loudness = self
+            .held
+            .values()
+            .map(|vel| *vel as f32 / 127.0)
+            .sum::<f32>();
+        let energy = (loudness / FULL_ENERGY).min(1.0);
+        let t = 1.0 - (-delta.as_secs_f32() / ENERGY_SMOOTHING).exp();
+        self.energy += (energy - self.energy) * t;
+
+        for channel in self.channels.iter() {
+            let id = match (channel.channel as usize).checked_sub(1) {
+                Some(id) if id < DMX_CHANNELS => id,
+                _ => continue,
+            };
+
+            let value = match channel.source {
+                DmxSource::Key(key) => self.loudest(key, key),
+                DmxSource::Keys(low, high) => self.loudest(low, high),
+                DmxSource::Energy => (self.energy * 255.0).round() as u8,
+            };
+
+            if self.dmx[id] != value {
+                self.dmx[id] = value;
+                self.dirty = true;
+            }
+        }
+
+        self.since_send += delta;
+        if (self.dirty && self.since_send >= MIN_INTERVAL) || self.since_send >= KEEP_ALIVE {
+            self.send();
+        }
+    }
+
+    /// Velocity of the loudest held key in `low..=high`, scaled to a DMX value
+    fn loudest(&self, low: u8, high: u8) -> u8 {
+        let vel = self
+            .held
+            .iter()
+            .filter(|((_, key), _)| (low..=high).contains(key))
+            .map(|(_, vel)| *vel)
+            .max()
+            .unwrap_or(0);
+
+        (vel as u16 * 255 / 127).min(255) as u8
+    }
+
+    fn send(&mut self) {
+        // Sequence 0 disables reordering on the node
+        self.sequence = self.sequence.checked_add(1).unwrap_or(1);
+
+        let packet = art_dmx_packet(self.sequence, self.universe, &self.dmx);
+        if let Err(err) = self.socket.send_to(&packet, self.address) {
+            log::warn!("Art-Net: {err}");
+        }
+
+        self.since_send = Duration::ZERO;
+        self.dirty = false;
+    }
+}
+
+impl Drop for ArtNetOutput {
+    /// Leaves the lights dark
+    fn drop(&mut self) {
+        self.dmx = [0; DMX_CHANNELS];
+        self.send();
+    }
+}
+
+/// ArtDmx packet, as defined by the Art-Net 4 specification
+fn art_dmx_packet(sequence: u8, universe: u16, dmx: &[u8; DMX_CHANNELS]) -> Vec<u8> {
+    const OP_DMX: u16 = 0x5000;
+    const PROTOCOL_VERSION: u16 = 14;
+
+    let mut packet = Vec::with_capacity(18 + DMX_CHANNELS);
+    packet.extend_from_slice(b"Art-Net\0");
+    packet.extend_from_slice(&OP_DMX.to_le_bytes());
+    packet.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    packet.push(sequence);
+    // Physical port
+    packet.push(0);
+    packet.extend_from_slice(&universe.to_le_bytes());
+    packet.extend_from_slice(&(DMX_CHANNELS as u16).to_be_bytes());
+    packet.extend_from_slice(dmx);
+    packet
+}
@@ -0,0 +1,101 @@
+use std::{sync::mpsc::Receiver, time::Duration};
+
+use neothesia_engine::Beat;
+use neothesia_pipelines::quad::{QuadInstance, QuadPipeline};
+use wgpu_jumpstart::{Color, TransformUniform, Uniform};
+
+use crate::{event_bus::AppEvent, target::Target};
+
+/// How long a beat stays lit
+const FLASH: Duration = Duration::from_millis(150);
+const SIZE: f32 = 14.0;
+const GAP: f32 = 6.0;
+const MARGIN: f32 = 10.0;
+
+/// Row of squares in the top-right corner, one per beat of the measure,
+/// the current one flashes whenever the metronome plays it
+pub struct BeatIndicator {
+    events: Receiver<AppEvent>,
+    quad_pipeline: QuadPipeline,
+    beat: Option<Beat>,
+    /// Time since the last beat
+    since_beat: Duration,
+}
+
+impl BeatIndicator {
+    pub fn new(target: &Target) -> Self {
+        Self {
+            events: target.event_bus.subscribe(),
+            quad_pipeline: QuadPipeline::new(&target.gpu, &target.transform_uniform),
+            beat: None,
+            since_beat: FLASH,
+        }
+    }
+
+    pub fn update(&mut self, target: &Target, delta: Duration) {
+        self.since_beat += delta;
+
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                AppEvent::Beat(beat) => {
+                    self.beat = Some(beat);
+                    self.since_beat = Duration::ZERO;
+                }
+                AppEvent::PlaybackSeek(_) | AppEvent::PlaybackFinished => self.beat = None,
+                _ => {}
+            }
+        }
+
+        let beat = match self.beat {
+            Some(beat) if target.config.metronome.enabled && !target.config.clean_mode => beat,
+            _ => {
+                self.quad_pipeline
+                    .update_instance_buffer(&target.gpu.queue, Vec::new());
+                return;
+            }
+        };
+
+        let flash = 1.0 - (self.since_beat.as_secs_f32() / FLASH.as_secs_f32()).min(1.0);
+        // The count-in is told apart from the song by its color
+        let lit = if beat.measure.is_none() {
+            [1.0, 0.6, 0.2]
+        } else if beat.is_downbeat() {
+            [1.0, 0.3, 0.3]
+        } else {
+            [1.0, 1.0, 1.0]
+        };
+
+        let width = target.window_state.logical_size.width;
+        let x = width - MARGIN - beat.beats as f32 * (SIZE + GAP) + GAP;
+        let y = target.config.progress_bar.height + MARGIN;
+
+        let quads = (0..beat.beats)
+            .map(|id| {
+                let color = if id == beat.beat {
+                    let [r, g, b] = lit;
+                    Color::new(r, g, b, 0.4 + 0.6 * flash)
+                } else {
+                    Color::new(1.0, 1.0, 1.0, 0.15)
+                };
+
+                QuadInstance {
+                    position: [x + id as f32 * (SIZE + GAP), y],
+                    size: [SIZE, SIZE],
+                    color: color.into_linear_rgba(),
+                    border_radius: [3.0; 4],
+                }
+            })
+            .collect();
+
+        self.quad_pipeline
+            .update_instance_buffer(&target.gpu.queue, quads);
+    }
+
+    pub fn render<'rpass>(
+        &'rpass self,
+        transform_uniform: &'rpass Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+    ) {
+        self.quad_pipeline.render(transform_uniform, render_pass);
+    }
+}
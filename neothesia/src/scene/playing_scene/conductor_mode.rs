@@ -0,0 +1,87 @@
+use std::time::{Duration, Instant};
+
+use neothesia_engine::Conductor;
+
+use super::{midi_player::MidiPlayer, MAX_SPEED, MIN_SPEED};
+use crate::{midi_event::MidiEvent, target::Target};
+
+/// Song following a beat tapped live on Tab or the sustain pedal, like an orchestra follows
+/// its conductor. Turned on and off with C while playing
+pub struct ConductorMode {
+    conductor: Conductor,
+    /// Clock of the taps
+    start: Instant,
+    /// Held by the conductor until the next taps, rather than by the user
+    paused: bool,
+    pedal_down: bool,
+}
+
+impl ConductorMode {
+    /// The song holds until the first taps
+    pub fn new(target: &Target, player: &mut MidiPlayer) -> Self {
+        let midi = target.midi_file.as_ref().unwrap();
+        let beats = midi
+            .analysis
+            .measures
+            .iter()
+            .flat_map(|measure| measure.beats.iter().copied())
+            .collect();
+
+        let paused = !player.is_paused();
+        player.pause();
+
+        Self {
+            conductor: Conductor::new(beats),
+            start: Instant::now(),
+            paused,
+            pedal_down: false,
+        }
+    }
+
+    /// The speed of the taps overrides the one of the settings, which are left as they are
+    pub fn tap(&mut self, player: &mut MidiPlayer) {
+        let song_time = Duration::from_secs_f32(player.time_without_lead_in().max(0.0));
+        let speed = match self.conductor.tap(self.start.elapsed(), song_time) {
+            Some(speed) => speed,
+            None => return,
+        };
+
+        player.set_speed_override(Some(speed.clamp(MIN_SPEED, MAX_SPEED)));
+        if self.paused {
+            self.paused = false;
+            player.resume();
+        }
+    }
+
+    /// The sustain pedal going down is a tap
+    pub fn midi_event(&mut self, player: &mut MidiPlayer, event: &MidiEvent) {
+        if let MidiEvent::Controller {
+            controller, value, ..
+        } = *event
+        {
+            if controller == midi_file::sustain::SUSTAIN_CONTROLLER {
+                let down = midi_file::sustain::is_down(value);
+                if down && !self.pedal_down {
+                    self.tap(player);
+                }
+                self.pedal_down = down;
+            }
+        }
+    }
+
+    /// Holds the song once the taps stopped
+    pub fn update(&mut self, player: &mut MidiPlayer) {
+        if !player.is_paused() && self.conductor.is_waiting(self.start.elapsed()) {
+            self.paused = true;
+            player.pause();
+        }
+    }
+
+    /// Gives back the speed of the settings, and the song if it's held
+    pub fn finish(self, player: &mut MidiPlayer) {
+        player.set_speed_override(None);
+        if self.paused {
+            player.resume();
+        }
+    }
+}
@@ -1,17 +1,32 @@
-use crate::{config::Config, render::KeyboardRenderer};
+use std::collections::HashSet;
+
+use crate::{
+    config::Config,
+    render::{KeyboardRenderer, ParticleRenderer},
+    target::Target,
+    utils::palette::note_color,
+};
 
 pub fn user_midi_event(keyboard: &mut KeyboardRenderer, event: &crate::midi_event::MidiEvent) {
     use crate::midi_event::MidiEvent;
 
     let range_start = keyboard.range().start() as usize;
 
-    let (is_on, key) = match event {
+    let (is_on, key) = match *event {
         MidiEvent::NoteOn { key, .. } => (true, key),
         MidiEvent::NoteOff { key, .. } => (false, key),
+        MidiEvent::Controller {
+            controller, value, ..
+        } => {
+            if controller == midi_file::sustain::SUSTAIN_CONTROLLER {
+                keyboard.set_user_sustain(midi_file::sustain::is_down(value));
+            }
+            return;
+        }
     };
 
-    if keyboard.range().contains(*key) {
-        let id = *key as usize - range_start;
+    if keyboard.range().contains(key) {
+        let id = key as usize - range_start;
         let key = &mut keyboard.key_states_mut()[id];
 
         key.set_pressed_by_user(is_on);
@@ -19,29 +34,84 @@ pub fn user_midi_event(keyboard: &mut KeyboardRenderer, event: &crate::midi_even
     }
 }
 
+/// Clicks for key presses when [`crate::config::KeyClick`] is enabled
+pub fn key_click(target: &Target, event: &crate::midi_event::MidiEvent) {
+    use crate::midi_event::MidiEvent;
+
+    let config = &target.config.key_click;
+    if let MidiEvent::NoteOn { vel, .. } = event {
+        if config.enabled {
+            let volume = config.volume.clamp(0.0, 1.0) * *vel as f32 / 127.0;
+            target.output_manager.borrow_mut().key_click(volume);
+        }
+    }
+}
+
+/// Plays a note of the user on the output, at the velocity it was played with
+pub fn play_user_note(target: &Target, event: &crate::midi_event::MidiEvent) {
+    let (channel, message) = event.channel_message();
+
+    // An input with its own sound would be heard twice
+    let muted = target.input_manager.event_port().map_or(false, |port| {
+        target.config.is_input_muted(&port.to_string())
+    });
+
+    // The click stands in for the note
+    key_click(target, event);
+    if !target.config.key_click.enabled && !muted {
+        target
+            .output_manager
+            .borrow_mut()
+            .midi_event(&midi_file::MidiEvent {
+                channel,
+                delta: 0,
+                timestamp: std::time::Duration::ZERO,
+                message,
+                track_id: usize::MAX,
+                track_color_id: 0,
+            });
+    }
+}
+
 pub fn file_midi_events(
     keyboard: &mut KeyboardRenderer,
     config: &Config,
+    hidden_tracks: &HashSet<usize>,
     events: &[midi_file::MidiEvent],
 ) {
     use midi_file::midly::MidiMessage;
 
     let range_start = keyboard.range().start() as usize;
 
-    for e in events {
+    for e in events
+        .iter()
+        .filter(|e| !hidden_tracks.contains(&e.track_id))
+    {
         let (is_on, key) = match e.message {
             MidiMessage::NoteOn { key, .. } => (true, key.as_int()),
             MidiMessage::NoteOff { key, .. } => (false, key.as_int()),
+            MidiMessage::Controller { controller, value }
+                if controller.as_int() == midi_file::sustain::SUSTAIN_CONTROLLER =>
+            {
+                keyboard.set_file_sustain(e.channel, midi_file::sustain::is_down(value.as_int()));
+                continue;
+            }
             _ => continue,
         };
 
         if keyboard.range().contains(key) && e.channel != 9 {
+            let color = note_color(
+                &config.color_schema,
+                &config.hand_colors,
+                &config.pitch_colors,
+                e.track_color_id,
+                key,
+            );
             let id = key as usize - range_start;
             let key = &mut keyboard.key_states_mut()[id];
 
             if is_on {
-                let color = &config.color_schema[e.track_color_id % config.color_schema.len()];
-                key.pressed_by_file_on(color);
+                key.pressed_by_file_on(&color);
             } else {
                 key.pressed_by_file_off();
             }
@@ -50,3 +120,102 @@ pub fn file_midi_events(
         }
     }
 }
+
+/// Sparks on the keys of the notes of `events` that start, where they hit the keyboard
+pub fn impact_particles(
+    particles: &mut ParticleRenderer,
+    keyboard: &KeyboardRenderer,
+    layout: &piano_math::KeyboardLayout,
+    config: &Config,
+    hidden_tracks: &HashSet<usize>,
+    events: &[midi_file::MidiEvent],
+) {
+    use midi_file::midly::MidiMessage;
+
+    let range_start = keyboard.range().start() as usize;
+    let pos = keyboard.pos();
+
+    for e in events
+        .iter()
+        .filter(|e| !hidden_tracks.contains(&e.track_id) && e.channel != 9)
+    {
+        let (key, vel) = match e.message {
+            MidiMessage::NoteOn { key, vel } if vel > 0 => (key.as_int(), vel.as_int()),
+            _ => continue,
+        };
+        if !keyboard.range().contains(key) {
+            continue;
+        }
+
+        let color = note_color(
+            &config.color_schema,
+            &config.hand_colors,
+            &config.pitch_colors,
+            e.track_color_id,
+            key,
+        );
+        let key = &layout.keys[key as usize - range_start];
+        let color = if key.kind().is_sharp() {
+            color.dark
+        } else {
+            color.base
+        };
+
+        let x = pos.x + key.x() + key.width() / 2.0;
+        particles.spawn([x, pos.y], key.width(), color, vel);
+    }
+}
+
+/// Lights up the keys of the notes starting within `config.key_lookahead` after `time`,
+/// brighter the closer they are
+pub fn upcoming_notes(
+    keyboard: &mut KeyboardRenderer,
+    config: &Config,
+    hidden_tracks: &HashSet<usize>,
+    midi: &midi_file::Midi,
+    time: f32,
+) {
+    let range_start = keyboard.range().start() as usize;
+    let lookahead = config.key_lookahead.max(0.0);
+
+    let mut changed = false;
+    for key in keyboard.key_states_mut().iter_mut() {
+        changed |= key.is_upcoming();
+        key.upcoming_off();
+    }
+
+    if lookahead > 0.0 {
+        let notes = &midi.merged_track.notes;
+        let first = notes.partition_point(|note| note.start.as_secs_f32() <= time);
+
+        let upcoming = notes[first..]
+            .iter()
+            .take_while(|note| note.start.as_secs_f32() <= time + lookahead)
+            .filter(|note| !hidden_tracks.contains(&note.track_id) && note.channel != 9);
+
+        // Latest first, so the nearest note of a key sets its color last
+        let upcoming: Vec<_> = upcoming.collect();
+        for note in upcoming.into_iter().rev() {
+            let key = note.note as i16 + config.transpose as i16;
+            let key = match u8::try_from(key) {
+                Ok(key) if keyboard.range().contains(key) => key,
+                _ => continue,
+            };
+
+            let color = note_color(
+                &config.color_schema,
+                &config.hand_colors,
+                &config.pitch_colors,
+                note.track_color_id,
+                key,
+            );
+            let closeness = 1.0 - (note.start.as_secs_f32() - time) / lookahead;
+            keyboard.key_states_mut()[key as usize - range_start].set_upcoming(&color, closeness);
+            changed = true;
+        }
+    }
+
+    if changed {
+        keyboard.queue_reupload();
+    }
+}
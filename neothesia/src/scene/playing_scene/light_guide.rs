@@ -0,0 +1,94 @@
+use std::collections::HashSet;
+
+use crate::{
+    config::LightGuideProtocol,
+    output_manager::{self, OutputDescriptor, SharedOutput},
+    target::Target,
+};
+
+enum Connection {
+    Port(midi_io::MidiOutputConnection),
+    /// The MIDI backend sends sysex data as it is, so any message can go through it
+    Main(SharedOutput),
+}
+
+/// Mirrors the keys play along waits for on the light guide of the keyboard,
+/// see [`crate::config::LightGuide`]
+pub struct LightGuide {
+    connection: Connection,
+    protocol: LightGuideProtocol,
+    lit: HashSet<u8>,
+}
+
+impl LightGuide {
+    /// Returns `None` when disabled, or when the keyboard can't be reached
+    pub fn new(target: &Target) -> Option<Self> {
+        let config = &target.config.light_guide;
+        if !config.enabled {
+            return None;
+        }
+
+        let connection = match config.output.as_ref() {
+            Some(name) => Connection::Port(output_manager::connect_midi_output(name)?),
+            None => {
+                let output_manager = target.output_manager.borrow();
+                if !matches!(
+                    output_manager.current_output(),
+                    OutputDescriptor::MidiOut(_)
+                ) {
+                    log::warn!("Light guide: the main output is not a MIDI device");
+                    return None;
+                }
+                Connection::Main(output_manager.shared_output())
+            }
+        };
+
+        Some(Self {
+            connection,
+            protocol: config.protocol.clone(),
+            lit: HashSet::new(),
+        })
+    }
+
+    /// Lights `keys`, and turns off the ones lit before that are not in it anymore
+    pub fn update(&mut self, keys: &HashSet<u8>) {
+        if &self.lit == keys {
+            return;
+        }
+
+        let off: Vec<u8> = self.lit.difference(keys).copied().collect();
+        let on: Vec<u8> = keys.difference(&self.lit).copied().collect();
+
+        for key in off {
+            self.send(key, false);
+        }
+        for key in on {
+            self.send(key, true);
+        }
+
+        self.lit = keys.clone();
+    }
+
+    fn send(&mut self, key: u8, on: bool) {
+        let message = match self.protocol.message(key, on) {
+            Some(message) => message,
+            None => {
+                log::error!("Light guide: invalid SysEx template");
+                return;
+            }
+        };
+
+        match &mut self.connection {
+            Connection::Port(conn) => {
+                conn.send(&message).ok();
+            }
+            Connection::Main(output) => output.lock().unwrap().sysex(&message),
+        }
+    }
+}
+
+impl Drop for LightGuide {
+    fn drop(&mut self) {
+        self.update(&HashSet::new());
+    }
+}
@@ -0,0 +1,107 @@
+use std::{ops::Range, sync::Arc, time::Duration};
+
+use wgpu_jumpstart::Color;
+
+use crate::target::Target;
+
+/// Share of the window height the current line is drawn at
+const TOP: f32 = 0.12;
+
+/// Line of the lyrics being sung, at the top of the window. Syllables light up as they are
+/// reached, the next line is shown below in a smaller size
+pub struct Lyrics {
+    midi: Arc<midi_file::Midi>,
+}
+
+impl Lyrics {
+    /// Returns `None` when lyrics are disabled, or the song has none
+    pub fn new(target: &Target) -> Option<Self> {
+        if !target.config.lyrics.enabled {
+            return None;
+        }
+
+        let midi = target.midi_file.as_ref()?.clone();
+        if midi.analysis.lyrics.is_empty() {
+            return None;
+        }
+
+        Some(Self { midi })
+    }
+
+    /// Syllables of the line `id` belongs to
+    fn line(&self, id: usize) -> Range<usize> {
+        let lyrics = &self.midi.analysis.lyrics;
+        let start = lyrics[..=id].iter().rposition(|l| l.new_line).unwrap_or(0);
+        let end = lyrics[id + 1..]
+            .iter()
+            .position(|l| l.new_line)
+            .map_or(lyrics.len(), |len| id + 1 + len);
+        start..end
+    }
+
+    /// `time` is the current song time, without the lead-in
+    pub fn update(&self, target: &mut Target, time: f32) {
+        if target.config.clean_mode {
+            return;
+        }
+
+        let analysis = &self.midi.analysis;
+        let time = Duration::from_secs_f32(time.max(0.0));
+        // Before the first syllable its line is already shown, nothing sung yet
+        let sung = analysis.lyric_at(time);
+        let line = self.line(sung.unwrap_or(0));
+        let next = (line.end < analysis.lyrics.len()).then(|| self.line(line.end));
+
+        let config = &target.config.lyrics;
+        let color = {
+            let (r, g, b) = config.color;
+            Color::from_rgba8(r, g, b, 1.0).into_linear_rgba()
+        };
+        let sung_color = {
+            let (r, g, b) = config.sung_color;
+            Color::from_rgba8(r, g, b, 1.0).into_linear_rgba()
+        };
+
+        let text = line
+            .map(|id| {
+                let color = if sung.map_or(false, |sung| id <= sung) {
+                    sung_color
+                } else {
+                    color
+                };
+                wgpu_glyph::Text::new(&analysis.lyrics[id].text)
+                    .with_color(color)
+                    .with_scale(config.size)
+            })
+            .collect();
+
+        let x = target.window_state.logical_size.width / 2.0;
+        let y = target.window_state.logical_size.height * TOP;
+
+        target.text_renderer.queue_text(wgpu_glyph::Section {
+            screen_position: (x, y),
+            text,
+            layout: wgpu_glyph::Layout::default_single_line()
+                .h_align(wgpu_glyph::HorizontalAlign::Center),
+            ..Default::default()
+        });
+
+        if let Some(next) = next {
+            let text = next
+                .map(|id| {
+                    wgpu_glyph::Text::new(&analysis.lyrics[id].text)
+                        .with_color([color[0], color[1], color[2], 0.6])
+                        .with_scale(config.size * 0.75)
+                })
+                .collect();
+
+            target.text_renderer.queue_text(wgpu_glyph::Section {
+                screen_position: (x, y + config.size * 1.2),
+                text,
+                layout: wgpu_glyph::Layout::default_single_line()
+                    .h_align(wgpu_glyph::HorizontalAlign::Center),
+                ..Default::default()
+            });
+        }
+    }
+}
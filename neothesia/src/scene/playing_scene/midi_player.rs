@@ -1,96 +1,189 @@
-use crate::{output_manager::OutputManager, target::Target};
-use std::{
-    cell::RefCell,
-    collections::{HashSet, VecDeque},
-    rc::Rc,
-    time::{Duration, Instant},
+use crate::{
+    event_bus::{AppEvent, EventBus},
+    target::Target,
 };
+use neothesia_core::config::MetronomeSound;
+use neothesia_engine::{MetronomeSettings, PlaybackSettings, TrackMixer};
+use std::{collections::HashMap, ops::Range, time::Duration};
+
+pub use neothesia_engine::{KeyPressSource, PlayAlong};
+
+fn playback_settings(
+    target: &Target,
+    wait_for_input: bool,
+    mixer: &TrackMixer,
+) -> PlaybackSettings {
+    let config = &target.config;
+
+    PlaybackSettings {
+        speed_multiplier: config.speed_multiplier,
+        forward_controllers: config.forward_controllers,
+        forward_program_changes: config.forward_program_changes,
+        forward_sysex: config.forward_sysex,
+        system_reset: config.system_reset.sysex(),
+        velocity_curve: config.velocity_curve,
+        track_velocity: track_velocity(target),
+        looping: config.loop_playback.enabled,
+        loop_crossfade: Duration::from_secs_f32(config.loop_playback.crossfade.max(0.0)),
+        silent_tracks: target.silent_tracks.clone(),
+        user_tracks: target.user_tracks.clone(),
+        mixer: mixer.clone(),
+        wait_for_input: config.play_along && wait_for_input,
+        metronome: config.metronome.enabled.then(|| MetronomeSettings {
+            sound: match config.metronome.sound {
+                MetronomeSound::Click => neothesia_engine::MetronomeSound::Click,
+                MetronomeSound::Note => neothesia_engine::MetronomeSound::Note {
+                    note: config.metronome.note,
+                    accent: config.metronome.accent_note,
+                },
+            },
+            volume: config.metronome.volume,
+            count_in: config.metronome.count_in,
+        }),
+        transpose: config.transpose,
+    }
+}
+
+/// Melody boost velocities of every track, empty when it's off or there's no melody
+fn track_velocity(target: &Target) -> HashMap<usize, f32> {
+    let boost = &target.config.melody_boost;
+    let midi = target.midi_file.as_ref().unwrap();
+
+    let melody = match target.melody_track.or(midi.analysis.melody_track) {
+        Some(melody) if boost.enabled => melody,
+        _ => return HashMap::new(),
+    };
+
+    midi.tracks
+        .iter()
+        .filter(|track| !track.notes.is_empty())
+        .map(|track| {
+            let scale = if track.track_id == melody {
+                boost.melody
+            } else {
+                boost.accompaniment
+            };
+            (track.track_id, scale.max(0.0))
+        })
+        .collect()
+}
 
+/// Engine player that keeps its settings in sync with the config
+/// and publishes playback events on the app bus
 pub struct MidiPlayer {
-    playback: midi_file::PlaybackState,
-    output_manager: Rc<RefCell<OutputManager>>,
-    midi_file: Rc<midi_file::Midi>,
-    play_along: PlayAlong,
+    player: neothesia_engine::MidiPlayer,
+    event_bus: EventBus,
+    finished: bool,
+    /// A marker of the A–B loop, waiting for the B one
+    loop_start: Option<Duration>,
+    /// Lets play along wait for the user, off while nobody is playing
+    wait_for_input: bool,
+    /// Set from the mixer panel, for this song only
+    mixer: TrackMixer,
+    /// Speed that takes the place of the one from the settings, without changing them
+    speed_override: Option<f32>,
 }
 
 impl MidiPlayer {
     pub fn new(target: &mut Target, user_keyboard_range: piano_math::KeyboardRange) -> Self {
-        let midi_file = target.midi_file.as_ref().unwrap();
+        let midi_file = target.midi_file.as_ref().unwrap().clone();
+        let output = target.output_manager.borrow().shared_output();
 
-        let mut player = Self {
-            playback: midi_file::PlaybackState::new(
-                Duration::from_secs(3),
-                &midi_file.merged_track,
+        Self {
+            player: neothesia_engine::MidiPlayer::new(
+                midi_file,
+                output,
+                playback_settings(target, true, &TrackMixer::default()),
+                user_keyboard_range,
             ),
-            output_manager: target.output_manager.clone(),
-            midi_file: midi_file.clone(),
-            play_along: PlayAlong::new(user_keyboard_range),
-        };
-        player.update(target, Duration::ZERO);
-
-        player
+            event_bus: target.event_bus.clone(),
+            finished: false,
+            loop_start: None,
+            wait_for_input: true,
+            mixer: TrackMixer::default(),
+            speed_override: None,
+        }
     }
 
     /// When playing: returns midi events
     ///
     /// When paused: returns None
-    pub fn update(
-        &mut self,
-        target: &mut Target,
-        delta: Duration,
-    ) -> Option<Vec<midi_file::MidiEvent>> {
-        self.play_along.update();
-
-        let elapsed = (delta / 10) * (target.config.speed_multiplier * 10.0) as u32;
-
-        let events = self.playback.update(&self.midi_file.merged_track, elapsed);
-
-        events.iter().for_each(|event| {
-            self.output_manager.borrow_mut().midi_event(event);
-
-            if event.channel == 9 {
-                return;
-            }
-
-            use midi_file::midly::MidiMessage;
-            match event.message {
-                MidiMessage::NoteOn { key, .. } => {
-                    self.play_along
-                        .press_key(KeyPressSource::File, key.as_int(), true);
-                }
-                MidiMessage::NoteOff { key, .. } => {
-                    self.play_along
-                        .press_key(KeyPressSource::File, key.as_int(), false);
-                }
-                _ => {}
-            }
-        });
-
-        if self.playback.is_paused() {
-            None
-        } else {
-            Some(events)
+    pub fn update(&mut self, target: &mut Target) -> Option<Vec<midi_file::MidiEvent>> {
+        let mut settings = playback_settings(target, self.wait_for_input, &self.mixer);
+        if let Some(speed) = self.speed_override {
+            settings.speed_multiplier = speed;
+        }
+        self.player.set_settings(settings);
+
+        let dead_keys = &target.config.dead_keys;
+        self.player
+            .play_along_mut()
+            .set_excused_keys(&dead_keys.keys, dead_keys.octave_substitute);
+        self.player.play_along_mut().set_tolerance(
+            target
+                .config
+                .input
+                .as_deref()
+                .map(|input| target.config.note_tolerance(input))
+                .unwrap_or_default(),
+        );
+
+        let events = self.player.update();
+
+        let is_finished = self.player.is_finished();
+        if is_finished && !self.finished {
+            self.event_bus.publish(AppEvent::PlaybackFinished);
+        }
+        self.finished = is_finished;
+
+        for event in events.iter().flatten() {
+            self.event_bus.publish(AppEvent::FileMidi(event.clone()));
+        }
+        for beat in self.player.beats() {
+            self.event_bus.publish(AppEvent::Beat(*beat));
         }
+
+        events
     }
 
-    fn clear(&mut self) {
-        self.output_manager.borrow_mut().stop_all();
+    pub fn mixer(&self) -> &TrackMixer {
+        &self.mixer
     }
-}
 
-impl Drop for MidiPlayer {
-    fn drop(&mut self) {
-        self.clear();
+    /// Changes reach the output on the next update
+    pub fn mixer_mut(&mut self) -> &mut TrackMixer {
+        &mut self.mixer
+    }
+
+    /// Plays at `speed` until it's set back to `None`, the settings keep their own speed
+    pub fn set_speed_override(&mut self, speed: Option<f32>) {
+        self.speed_override = speed;
+    }
+
+    /// With play along on, stops at every note until the user plays it
+    pub fn set_wait_for_input(&mut self, wait: bool) {
+        self.wait_for_input = wait;
+    }
+
+    /// Whether the song is stopped until the user plays the required notes
+    pub fn is_waiting(&self) -> bool {
+        self.player.is_waiting()
+    }
+
+    /// Whether the loop started over during the last update
+    pub fn looped(&self) -> bool {
+        self.player.looped()
     }
 }
 
 impl MidiPlayer {
     pub fn start(&mut self) {
-        self.resume();
+        self.player.start();
+        self.event_bus.publish(AppEvent::PlaybackStarted);
     }
 
     pub fn pause_resume(&mut self) {
-        if self.playback.is_paused() {
+        if self.is_paused() {
             self.resume();
         } else {
             self.pause();
@@ -98,156 +191,122 @@ impl MidiPlayer {
     }
 
     pub fn pause(&mut self) {
-        self.clear();
-        self.playback.pause();
+        self.player.pause();
+        self.event_bus.publish(AppEvent::PlaybackPaused);
     }
 
     pub fn resume(&mut self) {
-        self.playback.resume();
+        self.player.resume();
+        self.event_bus.publish(AppEvent::PlaybackResumed);
     }
 
-    fn set_time(&mut self, time: Duration) {
-        self.playback.set_time(time);
-
-        // Discard all of the events till that point
-        let events = self
-            .playback
-            .update(&self.midi_file.merged_track, Duration::ZERO);
-        std::mem::drop(events);
-
-        self.clear();
+    fn seeked(&self) {
+        self.event_bus
+            .publish(AppEvent::PlaybackSeek(self.player.time()));
     }
 
     pub fn rewind(&mut self, delta: i64) {
-        let mut time = self.playback.time();
-
-        if delta < 0 {
-            let delta = Duration::from_millis((-delta) as u64);
-            time = time.saturating_sub(delta);
-        } else {
-            let delta = Duration::from_millis(delta as u64);
-            time = time.saturating_add(delta);
-        }
-
-        self.set_time(time);
+        self.player.rewind(delta);
+        self.seeked();
     }
 
     pub fn set_percentage_time(&mut self, p: f32) {
-        self.set_time(Duration::from_secs_f32(
-            (p * self.playback.lenght().as_secs_f32()).max(0.0),
-        ));
+        self.player.set_percentage_time(p);
+        self.seeked();
     }
 
     pub fn percentage(&self) -> f32 {
-        self.playback.percentage()
-    }
-
-    pub fn time_without_lead_in(&self) -> f32 {
-        self.playback.time().as_secs_f32() - self.playback.leed_in().as_secs_f32()
+        self.player.percentage()
     }
 
-    pub fn is_paused(&self) -> bool {
-        self.playback.is_paused()
+    /// Where `time` of the song, without the lead-in, is on the progress bar
+    pub fn percentage_at(&self, time: Duration) -> f32 {
+        (time.as_secs_f32() + self.lead_in()) / self.player.lenght().as_secs_f32()
     }
-}
 
-impl MidiPlayer {
-    pub fn play_along(&self) -> &PlayAlong {
-        &self.play_along
+    /// Seconds of lead-in before the song starts
+    fn lead_in(&self) -> f32 {
+        self.player.time().as_secs_f32() - self.player.time_without_lead_in()
     }
 
-    pub fn play_along_mut(&mut self) -> &mut PlayAlong {
-        &mut self.play_along
+    /// Jumps to `time` of the song, without the lead-in
+    pub fn seek(&mut self, time: Duration) {
+        let lead_in = Duration::from_secs_f32(self.lead_in().max(0.0));
+        self.player.set_time(time + lead_in);
+        self.seeked();
     }
-}
-
-pub enum KeyPressSource {
-    File,
-    User,
-}
 
-#[derive(Debug)]
-struct UserPress {
-    timestamp: Instant,
-    note_id: u8,
-}
+    /// Loops `range` of the song, without the lead-in, like the A–B markers do
+    pub fn loop_range(&mut self, range: Range<Duration>) {
+        let lead_in = Duration::from_secs_f32(self.lead_in().max(0.0));
+        let range = range.start + lead_in..range.end + lead_in;
 
-#[derive(Debug)]
-pub struct PlayAlong {
-    user_keyboard_range: piano_math::KeyboardRange,
+        self.loop_start = Some(range.start);
+        self.player.set_loop(Some(range.clone()));
+        self.player.set_time(range.start);
+        self.seeked();
+    }
 
-    required_notes: HashSet<u8>,
+    /// Puts the A marker at the current time, the loop starts once B is set
+    pub fn set_loop_start(&mut self) {
+        self.loop_start = Some(self.player.time());
+        self.player.set_loop(None);
+    }
 
-    // List of user key press events that happened in last 500ms,
-    // used for play along leeway logic
-    user_pressed_recently: VecDeque<UserPress>,
-}
+    /// Puts the B marker at the current time, and starts looping from A.
+    /// Returns `false` when there is no A marker before it
+    pub fn set_loop_end(&mut self) -> bool {
+        let start = match self.loop_start {
+            Some(start) => start,
+            None => return false,
+        };
 
-impl PlayAlong {
-    fn new(user_keyboard_range: piano_math::KeyboardRange) -> Self {
-        Self {
-            user_keyboard_range,
-            required_notes: Default::default(),
-            user_pressed_recently: Default::default(),
+        let end = self.player.time();
+        if end <= start {
+            return false;
         }
+
+        self.player.set_loop(Some(start..end));
+        self.player.set_time(start);
+        self.seeked();
+        true
     }
 
-    fn update(&mut self) {
-        // Instead of calling .elapsed() per item let's fetch `now` once, and substract it ourselfs
-        let now = Instant::now();
+    pub fn clear_loop(&mut self) {
+        self.loop_start = None;
+        self.player.set_loop(None);
+    }
 
-        while let Some(item) = self.user_pressed_recently.front_mut() {
-            let elapsed = now - item.timestamp;
+    /// A–B loop in seconds without the lead-in, or the A marker alone while B is not set
+    pub fn loop_markers(&self) -> Option<(f32, Option<f32>)> {
+        let lead_in = self.lead_in();
+        let secs = |time: Duration| time.as_secs_f32() - lead_in;
 
-            // If older than 500ms
-            if elapsed.as_millis() > 500 {
-                self.user_pressed_recently.pop_front();
-            } else {
-                // All subsequent items will by younger than front item, so we can break
-                break;
-            }
+        match self.player.loop_range() {
+            Some(range) => Some((secs(range.start), Some(secs(range.end)))),
+            None => self.loop_start.map(|start| (secs(start), None)),
         }
     }
 
-    fn user_press_key(&mut self, note_id: u8, active: bool) {
-        let timestamp = Instant::now();
-
-        if active {
-            self.user_pressed_recently
-                .push_back(UserPress { timestamp, note_id });
-            self.required_notes.remove(&note_id);
-        }
+    pub fn time_without_lead_in(&self) -> f32 {
+        self.player.time_without_lead_in()
     }
 
-    fn file_press_key(&mut self, note_id: u8, active: bool) {
-        if active {
-            if let Some((id, _)) = self
-                .user_pressed_recently
-                .iter()
-                .enumerate()
-                .find(|(_, item)| item.note_id == note_id)
-            {
-                self.user_pressed_recently.remove(id);
-            } else {
-                self.required_notes.insert(note_id);
-            }
-        } else {
-            self.required_notes.remove(&note_id);
-        }
+    pub fn is_paused(&self) -> bool {
+        self.player.is_paused()
     }
 
-    pub fn press_key(&mut self, src: KeyPressSource, note_id: u8, active: bool) {
-        if !self.user_keyboard_range.contains(note_id) {
-            return;
-        }
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
 
-        match src {
-            KeyPressSource::User => self.user_press_key(note_id, active),
-            KeyPressSource::File => self.file_press_key(note_id, active),
-        }
+impl MidiPlayer {
+    pub fn play_along(&self) -> &PlayAlong {
+        self.player.play_along()
     }
 
-    pub fn are_required_keys_pressed(&self) -> bool {
-        self.required_notes.is_empty()
+    pub fn play_along_mut(&mut self) -> &mut PlayAlong {
+        self.player.play_along_mut()
     }
 }
@@ -0,0 +1,268 @@
+use neothesia_engine::TrackMixer;
+use neothesia_pipelines::quad::{QuadInstance, QuadPipeline};
+use wgpu_jumpstart::{Color, TransformUniform, Uniform};
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode};
+
+use crate::{target::Target, utils::palette::track_color};
+
+/// Volume changes in steps of this size
+const VOLUME_STEP: f32 = 0.1;
+const ROW_HEIGHT: f32 = 28.0;
+const WIDTH: f32 = 420.0;
+const PADDING: f32 = 12.0;
+/// Width of the volume bar at full volume
+const BAR_WIDTH: f32 = 120.0;
+
+struct Strip {
+    track_id: usize,
+    name: String,
+    color: (u8, u8, u8),
+}
+
+/// Volume, mute and solo of each track, opened over the waterfall with X while playing
+pub struct MixerPanel {
+    quad_pipeline: QuadPipeline,
+    state: PanelState,
+}
+
+/// What the keys change, kept apart from the rendering
+struct PanelState {
+    /// Tracks with notes
+    strips: Vec<Strip>,
+    open: bool,
+    selected: usize,
+}
+
+impl MixerPanel {
+    pub fn new(target: &Target) -> Self {
+        let midi = target.midi_file.as_ref().unwrap();
+        let strips = midi
+            .tracks
+            .iter()
+            .filter(|track| !track.notes.is_empty())
+            .map(|track| {
+                let instrument = if track.notes.iter().all(|note| note.channel == 9) {
+                    "Drums"
+                } else {
+                    midi_file::gm::program_name(track.program())
+                };
+                let color = track_color(&target.config.color_schema, track.track_color_id).base;
+
+                Strip {
+                    track_id: track.track_id,
+                    name: format!("{}. {instrument}", track.track_id + 1),
+                    color,
+                }
+            })
+            .collect();
+
+        Self {
+            quad_pipeline: QuadPipeline::new(&target.gpu, &target.transform_uniform),
+            state: PanelState {
+                strips,
+                open: false,
+                selected: 0,
+            },
+        }
+    }
+
+    /// Returns `true` when the panel used the key, it's left alone by the scene then
+    pub fn handle_keyboard_input(&mut self, mixer: &mut TrackMixer, input: &KeyboardInput) -> bool {
+        match input.virtual_keycode {
+            Some(key) => {
+                let pressed = input.state == ElementState::Pressed;
+                self.state.handle_key(mixer, key, pressed)
+            }
+            None => false,
+        }
+    }
+
+    pub fn update(&mut self, target: &mut Target, mixer: &TrackMixer) {
+        let state = &self.state;
+        if !state.open || target.config.clean_mode {
+            self.quad_pipeline
+                .update_instance_buffer(&target.gpu.queue, Vec::new());
+            return;
+        }
+
+        let x = PADDING;
+        let y = target.config.progress_bar.height + 40.0;
+        let height = ROW_HEIGHT * (state.strips.len() + 1) as f32 + PADDING * 2.0;
+
+        let mut quads = vec![QuadInstance {
+            position: [x, y],
+            size: [WIDTH, height],
+            color: Color::new(0.0, 0.0, 0.0, 0.75).into_linear_rgba(),
+            border_radius: [6.0; 4],
+        }];
+
+        target.text_renderer.queue_text(wgpu_glyph::Section {
+            screen_position: (x + PADDING, y + PADDING),
+            text: vec![wgpu_glyph::Text::new(
+                "Mixer   Up/Down select, Left/Right volume, M mute, S solo",
+            )
+            .with_color([1.0, 1.0, 1.0, 0.6])
+            .with_scale(14.0)],
+            ..Default::default()
+        });
+
+        for (id, strip) in state.strips.iter().enumerate() {
+            let row_y = y + PADDING + ROW_HEIGHT * (id + 1) as f32;
+            let heard = mixer.is_heard(strip.track_id);
+
+            if id == state.selected {
+                quads.push(QuadInstance {
+                    position: [x + 4.0, row_y - 4.0],
+                    size: [WIDTH - 8.0, ROW_HEIGHT],
+                    color: Color::new(1.0, 1.0, 1.0, 0.1).into_linear_rgba(),
+                    border_radius: [4.0; 4],
+                });
+            }
+
+            let alpha = if heard { 1.0 } else { 0.35 };
+            let (r, g, b) = strip.color;
+            let volume = mixer.volume(strip.track_id);
+            let bar_x = x + WIDTH - PADDING - BAR_WIDTH;
+            quads.push(QuadInstance {
+                position: [bar_x, row_y + 4.0],
+                size: [BAR_WIDTH, 10.0],
+                color: Color::new(1.0, 1.0, 1.0, 0.15).into_linear_rgba(),
+                border_radius: [3.0; 4],
+            });
+            quads.push(QuadInstance {
+                position: [bar_x, row_y + 4.0],
+                size: [BAR_WIDTH * volume / TrackMixer::MAX_VOLUME, 10.0],
+                color: Color::from_rgba8(r, g, b, alpha).into_linear_rgba(),
+                border_radius: [3.0; 4],
+            });
+
+            let mut flags = String::new();
+            if mixer.muted.contains(&strip.track_id) {
+                flags.push_str(" M");
+            }
+            if mixer.solo.contains(&strip.track_id) {
+                flags.push_str(" S");
+            }
+
+            target.text_renderer.queue_text(wgpu_glyph::Section {
+                screen_position: (x + PADDING, row_y),
+                text: vec![
+                    wgpu_glyph::Text::new(&strip.name)
+                        .with_color([1.0, 1.0, 1.0, alpha])
+                        .with_scale(18.0),
+                    wgpu_glyph::Text::new(&format!("  {:.0}%{flags}", volume * 100.0))
+                        .with_color([1.0, 1.0, 1.0, 0.6 * alpha])
+                        .with_scale(16.0),
+                ],
+                ..Default::default()
+            });
+        }
+
+        self.quad_pipeline
+            .update_instance_buffer(&target.gpu.queue, quads);
+    }
+
+    pub fn render<'rpass>(
+        &'rpass self,
+        transform_uniform: &'rpass Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+    ) {
+        self.quad_pipeline.render(transform_uniform, render_pass);
+    }
+}
+
+impl PanelState {
+    fn handle_key(&mut self, mixer: &mut TrackMixer, key: VirtualKeyCode, pressed: bool) -> bool {
+        if key == VirtualKeyCode::X {
+            if pressed {
+                self.open = !self.open;
+            }
+            return true;
+        }
+
+        let strip = match self.strips.get(self.selected) {
+            Some(strip) if self.open => strip,
+            _ => return false,
+        };
+
+        // Acted on when pressed, the release is swallowed so the scene doesn't act on it.
+        // Escape closes on release instead, or the scene would go back on its release
+        match key {
+            VirtualKeyCode::Up if pressed => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            VirtualKeyCode::Down if pressed => {
+                self.selected = (self.selected + 1).min(self.strips.len() - 1);
+            }
+            VirtualKeyCode::Left | VirtualKeyCode::Right if pressed => {
+                let step = if key == VirtualKeyCode::Left {
+                    -VOLUME_STEP
+                } else {
+                    VOLUME_STEP
+                };
+                let volume = mixer.volume(strip.track_id) + step;
+                mixer.set_volume(strip.track_id, (volume * 10.0).round() / 10.0);
+            }
+            VirtualKeyCode::M if pressed => mixer.toggle_mute(strip.track_id),
+            VirtualKeyCode::S if pressed => mixer.toggle_solo(strip.track_id),
+            VirtualKeyCode::Escape if !pressed => self.open = false,
+            VirtualKeyCode::Up
+            | VirtualKeyCode::Down
+            | VirtualKeyCode::Left
+            | VirtualKeyCode::Right
+            | VirtualKeyCode::M
+            | VirtualKeyCode::S
+            | VirtualKeyCode::Escape => {}
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn panel() -> PanelState {
+        let strip = |track_id| Strip {
+            track_id,
+            name: String::new(),
+            color: (0, 0, 0),
+        };
+
+        PanelState {
+            strips: vec![strip(0), strip(2)],
+            open: true,
+            selected: 0,
+        }
+    }
+
+    #[test]
+    fn escape_closes_on_release() {
+        let mut panel = panel();
+        let mut mixer = TrackMixer::default();
+
+        assert!(panel.handle_key(&mut mixer, VirtualKeyCode::Escape, true));
+        assert!(panel.open);
+        assert!(panel.handle_key(&mut mixer, VirtualKeyCode::Escape, false));
+        assert!(!panel.open);
+
+        // Closed, so the next Escape goes to the scene
+        assert!(!panel.handle_key(&mut mixer, VirtualKeyCode::Escape, true));
+        assert!(!panel.handle_key(&mut mixer, VirtualKeyCode::Escape, false));
+    }
+
+    #[test]
+    fn keys_change_the_selected_track() {
+        let mut panel = panel();
+        let mut mixer = TrackMixer::default();
+
+        panel.handle_key(&mut mixer, VirtualKeyCode::Down, true);
+        panel.handle_key(&mut mixer, VirtualKeyCode::Down, false);
+        panel.handle_key(&mut mixer, VirtualKeyCode::M, true);
+        panel.handle_key(&mut mixer, VirtualKeyCode::M, false);
+        assert!(mixer.muted.contains(&2));
+        assert!(!mixer.muted.contains(&0));
+    }
+}
@@ -1,37 +1,187 @@
+use neothesia_engine::TapTempo;
 use neothesia_pipelines::quad::{QuadInstance, QuadPipeline};
-use std::time::Duration;
-use wgpu_jumpstart::Color;
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+use wgpu_jumpstart::{Color, TransformUniform, Uniform};
 use winit::event::{KeyboardInput, WindowEvent};
 
 use super::{Scene, SceneType};
 use crate::{
+    accessibility::Item,
+    config::NotationView,
+    event_bus::AppEvent,
+    global_hotkeys::HotkeyAction,
     midi_event::MidiEvent,
-    render::{KeyboardRenderer, WaterfallRenderer},
+    render::{
+        AudioPaneRenderer, BackgroundRenderer, Camera, DrumLaneRenderer, KeyboardRenderer,
+        NotationRenderer, ParticleRenderer, PracticeHeatmapRenderer, WaterfallRenderer,
+        WatermarkRenderer,
+    },
     target::Target,
+    toast_manager::ToastManager,
     NeothesiaEvent,
 };
 
-mod keyboard_events;
+pub(crate) mod keyboard_events;
 
 mod midi_player;
 use midi_player::MidiPlayer;
 
+mod session_recorder;
+use session_recorder::SessionRecorder;
+
+mod take_recorder;
+use take_recorder::TakeRecorder;
+
+mod practice_counts;
+use practice_counts::PracticeCounts;
+
 mod rewind_controller;
 use rewind_controller::RewindController;
 
-mod toast_manager;
-use toast_manager::ToastManager;
+mod scene_buffer;
+use scene_buffer::SceneBuffer;
+
+mod light_guide;
+use light_guide::LightGuide;
+
+mod art_net;
+use art_net::ArtNetOutput;
+
+mod pad_lights;
+use pad_lights::PadLights;
+
+mod pitch_hint;
+use pitch_hint::PitchHint;
+
+mod wait_hints;
+use wait_hints::WaitHints;
+
+mod beat_indicator;
+use beat_indicator::BeatIndicator;
+
+mod phrase_window;
+use phrase_window::PhraseWindow;
+
+mod lyrics;
+use lyrics::Lyrics;
+
+mod mixer_panel;
+use mixer_panel::MixerPanel;
+
+mod quick_settings;
+use quick_settings::QuickSettings;
+
+mod conductor_mode;
+use conductor_mode::ConductorMode;
 
 pub struct PlayingScene {
     keyboard_layout: piano_math::KeyboardLayout,
 
+    /// Gradient of the theme or the background image, drawn under everything else
+    background: BackgroundRenderer,
     piano_keyboard: KeyboardRenderer,
     notes: WaterfallRenderer,
+    /// Drawn behind the waterfall
+    practice_heatmap: Option<PracticeHeatmapRenderer>,
+    /// Drawn over the keyboard, where the notes hit it
+    particles: ParticleRenderer,
+
+    camera: Camera,
+    /// Transform of the keyboard and the waterfall, follows the camera
+    camera_uniform: Uniform<TransformUniform>,
 
     player: MidiPlayer,
     rewind_controler: RewindController,
     quad_pipeline: QuadPipeline,
+    watermark: Option<WatermarkRenderer>,
+    audio_pane: Option<AudioPaneRenderer>,
+    drum_lane: Option<DrumLaneRenderer>,
+    /// Shown by the notation view setting, which can change while playing
+    notation: Option<NotationRenderer>,
+    session_recorder: Option<SessionRecorder>,
+    take_recorder: Option<TakeRecorder>,
+    practice_counts: PracticeCounts,
+    beat_indicator: BeatIndicator,
+    /// Stills the waterfall on one phrase at a time, when phrase practice is on
+    phrase_window: Option<PhraseWindow>,
     toast_manager: ToastManager,
+    /// Waterfall and keyboard are drawn here first, when render quality settings ask for it
+    scene_buffer: Option<SceneBuffer>,
+    light_guide: Option<LightGuide>,
+    art_net: Option<ArtNetOutput>,
+    pad_lights: Option<PadLights>,
+    pitch_hint: Option<PitchHint>,
+    wait_hints: Option<WaitHints>,
+    lyrics: Option<Lyrics>,
+    mixer_panel: MixerPanel,
+    quick_settings: QuickSettings,
+
+    /// Time without input since the song ended
+    idle: Duration,
+    /// Song is replayed by the idle mode, nobody is expected to play along
+    attract: bool,
+    /// Song time of the previous update, without the lead-in
+    song_time: f32,
+
+    /// Keys held on the input, by channel
+    held_keys: HashSet<(u8, u8)>,
+    /// Input device went away mid-song, playback is paused until it's back
+    input_lost: bool,
+    /// Paused because the input device went away, resumed once it's back
+    paused_by_input: bool,
+    /// Paused because the window went to the background, resumed once it's back
+    paused_by_focus: bool,
+    /// Window has the focus, it's in the background while unfocused or occluded
+    focused: bool,
+    occluded: bool,
+
+    tap_tempo: TapTempo,
+    /// Clock of the taps
+    tap_start: Instant,
+    /// Held keys repeat, only the first press is a tap
+    tap_held: bool,
+    conductor: Option<ConductorMode>,
+}
+
+/// Speed changes in steps of this size, within `MIN_SPEED..=MAX_SPEED`
+const SPEED_STEP: f32 = 0.05;
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 2.0;
+
+/// `speed` moved by `steps` of [`SPEED_STEP`], snapped to a step
+fn step_speed(speed: f32, steps: f32) -> f32 {
+    let speed = ((speed / SPEED_STEP).round() + steps) * SPEED_STEP;
+    speed.clamp(MIN_SPEED, MAX_SPEED)
+}
+
+/// Speed that makes the song go at `bpm` at `time` in seconds, rounded to a percent
+fn tap_tempo_speed(analysis: &midi_file::SongAnalysis, time: f32, bpm: f32) -> f32 {
+    let time = Duration::from_secs_f32(time.max(0.0));
+    let song_bpm = analysis.bpm_at(time) as f32;
+
+    let speed = (bpm / song_bpm).clamp(MIN_SPEED, MAX_SPEED);
+    (speed * 100.0).round() / 100.0
+}
+
+/// Furthest the song can be transposed, in semitones either way
+const MAX_TRANSPOSE: i16 = 24;
+
+/// Width left for the keyboard and the waterfall, next to the audio pane
+fn scene_width(target: &Target, audio_pane: bool) -> f32 {
+    let width = target.window_state.logical_size.width;
+
+    if audio_pane {
+        width * (1.0 - target.config.audio_pane.width.clamp(0.0, 0.5))
+    } else {
+        width
+    }
+}
+
+fn notation_visible(target: &Target) -> bool {
+    target.config.notation.view != NotationView::Off && !target.config.clean_mode
 }
 
 fn get_layout(width: f32, height: f32) -> piano_math::KeyboardLayout {
@@ -45,56 +195,548 @@ fn get_layout(width: f32, height: f32) -> piano_math::KeyboardLayout {
 
 impl PlayingScene {
     pub fn new(target: &mut Target) -> Self {
+        let audio_pane = if target.config.audio_pane.enabled {
+            let tap = target.output_manager.borrow().audio_tap();
+            tap.map(|tap| AudioPaneRenderer::new(&target.gpu, &target.transform_uniform, tap))
+        } else {
+            None
+        };
+
         let keyboard_layout = get_layout(
-            target.window_state.logical_size.width,
+            scene_width(target, audio_pane.is_some()),
             target.window_state.logical_size.height,
         );
 
+        let camera_uniform = Uniform::new(
+            &target.gpu.device,
+            target.transform_uniform.data,
+            wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+        );
+
+        let scene_buffer = SceneBuffer::new(target, &target.config.render_quality);
+        let sample_count = scene_buffer.as_ref().map_or(1, SceneBuffer::sample_count);
+
         let mut piano_keyboard = KeyboardRenderer::new(
             &target.gpu,
-            &target.transform_uniform,
+            &camera_uniform,
             keyboard_layout.clone(),
+            sample_count,
         );
 
         piano_keyboard.position_on_bottom_of_parent(target.window_state.logical_size.height);
+        piano_keyboard.set_key_travel(target.config.key_travel);
+        piano_keyboard.set_key_colors(target.config.themed_key_colors());
+        piano_keyboard.set_scale_overlay(&target.config.scale_overlay);
+
+        let mut background_image = target.config.background_image.clone();
+        if target.low_power {
+            background_image.blur = 0.0;
+        }
+        let background = BackgroundRenderer::new(
+            &target.gpu,
+            &target.transform_uniform,
+            sample_count,
+            &background_image,
+        );
 
         let mut notes = WaterfallRenderer::new(
             &target.gpu,
             target.midi_file.as_ref().unwrap(),
             &target.config,
-            &target.transform_uniform,
+            &camera_uniform,
             keyboard_layout.clone(),
+            target.hidden_tracks.clone(),
+            sample_count,
         );
 
+        let practice_heatmap = target.config.practice_heatmap.then(|| {
+            PracticeHeatmapRenderer::new(
+                &target.gpu,
+                &camera_uniform,
+                target.midi_file.as_ref().unwrap(),
+                sample_count,
+            )
+        });
+
+        let particles = ParticleRenderer::new(&target.gpu, &camera_uniform, sample_count);
+
+        // Subscribed before the player starts, so no event is missed
+        let session_recorder = SessionRecorder::new(target);
+        let take_recorder = TakeRecorder::new(target);
+        let practice_counts = PracticeCounts::new(target);
+        let beat_indicator = BeatIndicator::new(target);
+        let phrase_window = PhraseWindow::new(target);
+
         let mut player = MidiPlayer::new(target, keyboard_layout.range.clone());
         notes.update(&target.gpu.queue, player.time_without_lead_in());
 
         player.start();
 
+        target.window.set_cursor_visible(!target.config.clean_mode);
+
+        let song = &target.midi_file.as_ref().unwrap().name;
+        target.accessibility.set_items(vec![
+            Item::text(format!("Playing {song}")),
+            Item::text("Space pauses, Escape goes back to the menu"),
+            Item::text("X opens the mixer, to change the volume of each track or mute and solo it"),
+            Item::text("Q shows the speed, transpose, volume and the other quick settings"),
+            Item::text("Tap Tab in time to set the speed to the tapped tempo"),
+            Item::text("C turns on the conductor, the song follows Tab or the pedal on every beat"),
+        ]);
+
         Self {
             keyboard_layout,
 
+            background,
             piano_keyboard,
             notes,
+            practice_heatmap,
+            particles,
+            camera: Camera::new(),
+            camera_uniform,
             player,
             rewind_controler: RewindController::new(),
             quad_pipeline: QuadPipeline::new(&target.gpu, &target.transform_uniform),
+            watermark: WatermarkRenderer::new(
+                &target.gpu,
+                &target.transform_uniform,
+                &target.config.watermark,
+            ),
+            audio_pane,
+            drum_lane: target
+                .config
+                .drum_lane
+                .then(|| {
+                    DrumLaneRenderer::new(
+                        &target.gpu,
+                        &target.transform_uniform,
+                        target.midi_file.as_ref().unwrap(),
+                    )
+                })
+                .flatten(),
+            notation: NotationRenderer::new(
+                &target.gpu,
+                &target.transform_uniform,
+                target.midi_file.as_ref().unwrap(),
+            ),
+            session_recorder,
+            take_recorder,
+            practice_counts,
+            beat_indicator,
+            phrase_window,
 
             toast_manager: ToastManager::default(),
+            scene_buffer,
+            light_guide: LightGuide::new(target),
+            art_net: ArtNetOutput::new(target),
+            pad_lights: PadLights::new(target),
+            pitch_hint: PitchHint::new(target),
+            wait_hints: WaitHints::new(&target.config.wait_hints),
+            lyrics: Lyrics::new(target),
+            mixer_panel: MixerPanel::new(target),
+            quick_settings: QuickSettings::new(target),
+
+            idle: Duration::ZERO,
+            song_time: 0.0,
+            attract: false,
+
+            held_keys: HashSet::new(),
+            input_lost: false,
+            paused_by_input: false,
+            paused_by_focus: false,
+            focused: true,
+            occluded: false,
+
+            tap_tempo: TapTempo::new(),
+            tap_start: Instant::now(),
+            tap_held: false,
+            conductor: None,
+        }
+    }
+
+    fn update_camera(&mut self, target: &mut Target, delta: Duration) {
+        let width = target.window_state.logical_size.width;
+        let height = target.window_state.logical_size.height;
+
+        self.camera.update(
+            &target.config.camera,
+            target.midi_file.as_ref().unwrap(),
+            &self.keyboard_layout,
+            self.player.time_without_lead_in(),
+            delta,
+        );
+
+        let layout_width = self.keyboard_layout.width;
+        self.camera.apply(
+            &mut self.camera_uniform.data,
+            width,
+            height,
+            layout_width,
+            target.window_state.scale_factor as f32,
+        );
+        self.camera_uniform.update(&target.gpu.queue);
+
+        let offset = self.camera.offset(layout_width, height);
+        self.piano_keyboard.set_view(self.camera.zoom(), offset);
+        self.notes.set_view(self.camera.zoom(), offset);
+    }
+
+    /// Leaves the finished song once nobody touched anything for a while
+    fn update_idle(&mut self, target: &mut Target, delta: Duration) {
+        let idle_mode = &target.config.idle_mode;
+
+        if !idle_mode.enabled || !self.player.is_finished() {
+            self.idle = Duration::ZERO;
+            return;
+        }
+
+        self.idle += delta;
+        if self.idle.as_secs_f32() < idle_mode.timeout {
+            return;
+        }
+        self.idle = Duration::ZERO;
+
+        if idle_mode.attract {
+            self.attract = true;
+            self.player.play_along_mut().reset_stats();
+            self.player.set_percentage_time(0.0);
+        } else {
+            target.proxy.send_event(NeothesiaEvent::GoBack).ok();
+        }
+    }
+
+    /// Every run of the song is scored on its own, jumping back to the start or looping the
+    /// whole song starts the stats over. Looping a passage keeps them
+    fn update_restart(&mut self) {
+        let time = self.player.time_without_lead_in();
+        let song_looped = self.player.looped() && self.player.loop_markers().is_none();
+        if song_looped || (self.song_time > 0.0 && time <= 0.0) {
+            self.player.play_along_mut().reset_stats();
+        }
+        self.song_time = time;
+    }
+
+    /// Pauses play along when the input device goes away, and carries on once it's back
+    fn update_input_connection(&mut self, target: &mut Target) {
+        // The device watcher looks for the device and connects to it again
+        let lost = target.input_manager.is_lost();
+        if lost {
+            // Their note offs went away with the device
+            self.release_held_keys(target);
+        }
+
+        if !target.config.play_along {
+            return;
+        }
+
+        if lost != self.input_lost {
+            self.input_lost = lost;
+            if lost {
+                if !self.player.is_paused() {
+                    self.paused_by_input = true;
+                    self.player.pause();
+                }
+            } else {
+                self.resume_held();
+            }
+        }
+
+        if self.input_lost {
+            let width = target.window_state.logical_size.width;
+            let height = target.window_state.logical_size.height;
+
+            target.text_renderer.queue_text(wgpu_glyph::Section {
+                screen_position: (width / 2.0, height / 2.0),
+                bounds: (width, height),
+                text: vec![
+                    wgpu_glyph::Text::new("MIDI input disconnected\n")
+                        .with_color([1.0, 1.0, 1.0, 1.0])
+                        .with_scale(36.0),
+                    wgpu_glyph::Text::new("Reconnect it to continue")
+                        .with_color([1.0, 1.0, 1.0, 0.8])
+                        .with_scale(24.0),
+                ],
+                layout: wgpu_glyph::Layout::default_wrap()
+                    .h_align(wgpu_glyph::HorizontalAlign::Center)
+                    .v_align(wgpu_glyph::VerticalAlign::Center),
+            });
+        }
+    }
+
+    /// Counts practice towards the daily goal and keeps the time left in sight
+    fn update_practice_lock(&mut self, target: &mut Target, delta: Duration) {
+        if !target.config.practice_lock.enabled {
+            return;
+        }
+
+        if !self.player.is_paused() && !self.player.is_finished() && !self.attract {
+            target.config.practice_lock.add_practice(delta);
+        }
+
+        if target.config.clean_mode {
+            return;
+        }
+
+        let remaining = target.config.practice_lock.remaining() as u32;
+        let text = if remaining > 0 {
+            format!("Practice: {}:{:02} left", remaining / 60, remaining % 60)
+        } else {
+            "Practice goal reached".to_string()
+        };
+
+        target.text_renderer.queue_text(wgpu_glyph::Section {
+            screen_position: (target.window_state.logical_size.width / 2.0, 10.0),
+            text: vec![wgpu_glyph::Text::new(&text)
+                .with_color([1.0, 1.0, 1.0, 1.0])
+                .with_scale(28.0)],
+            layout: wgpu_glyph::Layout::default_single_line()
+                .h_align(wgpu_glyph::HorizontalAlign::Center),
+            ..Default::default()
+        });
+    }
+
+    /// Score of the play along, once the song is over
+    fn update_score_screen(&mut self, target: &mut Target) {
+        if !target.config.play_along || self.attract || !self.player.is_finished() {
+            return;
+        }
+
+        let stats = self.player.play_along().stats();
+        let accuracy = match stats.accuracy() {
+            Some(accuracy) => accuracy,
+            None => return,
+        };
+
+        let width = target.window_state.logical_size.width;
+        let height = target.window_state.logical_size.height;
+
+        let details = format!(
+            "Hits: {}   Early: {}   Late: {}   Missed: {}   Wrong: {}\n\
+             Best streak: {}",
+            stats.hits, stats.early, stats.late, stats.missed, stats.wrong, stats.best_streak,
+        );
+
+        target.text_renderer.queue_text(wgpu_glyph::Section {
+            screen_position: (width / 2.0, height / 2.0),
+            bounds: (width, height),
+            text: vec![
+                wgpu_glyph::Text::new(&format!("Accuracy: {:.0}%\n", accuracy * 100.0))
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(48.0),
+                wgpu_glyph::Text::new(&details)
+                    .with_color([1.0, 1.0, 1.0, 0.8])
+                    .with_scale(24.0),
+            ],
+            layout: wgpu_glyph::Layout::default_wrap()
+                .h_align(wgpu_glyph::HorizontalAlign::Center)
+                .v_align(wgpu_glyph::VerticalAlign::Center),
+        });
+    }
+
+    /// Pauses while the window is in the background, if enabled
+    fn background_changed(&mut self, target: &mut Target) {
+        if self.focused && !self.occluded {
+            self.resume_held();
+        } else if target.config.pause_on_focus_loss && !self.player.is_paused() {
+            self.paused_by_focus = true;
+            self.player.pause();
+        }
+    }
+
+    /// Resumes the song paused by the window going to the background or the input going
+    /// away, once neither of them holds it any more
+    fn resume_held(&mut self) {
+        let background = !self.focused || self.occluded;
+        if background || self.input_lost {
+            return;
+        }
+
+        if self.paused_by_focus || self.paused_by_input {
+            self.paused_by_focus = false;
+            self.paused_by_input = false;
+            self.player.resume();
+        }
+    }
+
+    /// Any input means somebody is here again
+    fn user_input(&mut self) {
+        self.idle = Duration::ZERO;
+        self.attract = false;
+    }
+
+    /// Lets go of the keys whose note off won't come anymore, so they don't stay stuck
+    fn release_held_keys(&mut self, target: &mut Target) {
+        let keys: Vec<_> = self.held_keys.drain().collect();
+        for (channel, key) in keys {
+            self.midi_event(target, &MidiEvent::NoteOff { channel, key });
         }
     }
 
     fn update_progresbar(&mut self, target: &mut Target) {
-        let size_x = target.window_state.logical_size.width * self.player.percentage();
-        self.quad_pipeline.update_instance_buffer(
-            &target.gpu.queue,
-            vec![QuadInstance {
-                position: [0.0, 0.0],
-                size: [size_x, 5.0],
-                color: Color::from_rgba8(56, 145, 255, 1.0).into_linear_rgba(),
-                ..Default::default()
-            }],
+        let style = &target.config.progress_bar;
+
+        if style.hidden || target.config.clean_mode {
+            self.quad_pipeline
+                .update_instance_buffer(&target.gpu.queue, Vec::new());
+            return;
+        }
+
+        let (r, g, b) = style.color;
+        let width = target.window_state.logical_size.width;
+        let size_x = width * self.player.percentage();
+
+        let mut quads = vec![QuadInstance {
+            position: [0.0, 0.0],
+            size: [size_x, style.height],
+            color: Color::from_rgba8(r, g, b, 1.0).into_linear_rgba(),
+            ..Default::default()
+        }];
+
+        // Sections found by the analysis, a tick and a name at the start of each one
+        let sections = &target.midi_file.as_ref().unwrap().analysis.sections;
+        if sections.len() > 1 {
+            for section in sections.iter() {
+                let x = width * self.player.percentage_at(section.start);
+
+                quads.push(QuadInstance {
+                    position: [x, 0.0],
+                    size: [2.0, style.height],
+                    color: Color::new(1.0, 1.0, 1.0, 0.6).into_linear_rgba(),
+                    ..Default::default()
+                });
+
+                target.text_renderer.queue_text(wgpu_glyph::Section {
+                    screen_position: (x + 4.0, style.height + 2.0),
+                    text: vec![wgpu_glyph::Text::new(&section.name)
+                        .with_color([1.0, 1.0, 1.0, 0.6])
+                        .with_scale(14.0)],
+                    ..Default::default()
+                });
+            }
+        }
+
+        self.quad_pipeline
+            .update_instance_buffer(&target.gpu.queue, quads);
+    }
+
+    /// Speeds the song up on every loop while the speed trainer is on
+    fn update_speed_trainer(&mut self, target: &mut Target) {
+        let trainer = &target.config.speed_trainer;
+        if !trainer.enabled || !self.player.looped() {
+            return;
+        }
+
+        let speed = target.config.speed_multiplier;
+        if speed < trainer.target {
+            let speed = (speed + trainer.step.max(0.0)).min(trainer.target);
+            target.config.speed_multiplier = speed;
+            self.toast_manager.speed_toast(speed);
+        }
+    }
+
+    fn toggle_phrase_practice(&mut self, target: &mut Target) {
+        let config = &mut target.config.phrase_practice;
+        config.enabled = !config.enabled;
+
+        self.phrase_window = PhraseWindow::new(target);
+        if let Some(phrase_window) = self.phrase_window.as_mut() {
+            // Starts over from the phrase playing now
+            let time = Duration::from_secs_f32(self.player.time_without_lead_in().max(0.0));
+            phrase_window.place(time, *self.player.play_along().stats());
+            self.player.seek(phrase_window.start());
+        }
+
+        let enabled = target.config.phrase_practice.enabled;
+        self.toast_manager.toast(format!(
+            "Phrase practice: {}",
+            if enabled { "On" } else { "Off" }
+        ));
+    }
+
+    /// Section of the song playing now
+    fn current_section(&self, target: &Target) -> Option<usize> {
+        let time = Duration::from_secs_f32(self.player.time_without_lead_in().max(0.0));
+        target
+            .midi_file
+            .as_ref()
+            .and_then(|midi| midi.analysis.section_at(time))
+    }
+
+    /// Jumps to the next section, or back to the start of the current one
+    fn jump_to_section(&mut self, target: &Target, forward: bool) {
+        let midi = match target.midi_file.as_ref() {
+            Some(midi) => midi,
+            None => return,
+        };
+        let sections = &midi.analysis.sections;
+        if sections.is_empty() {
+            return;
+        }
+        let current = self.current_section(target).unwrap_or(0);
+
+        let id = if forward {
+            current + 1
+        } else {
+            // Like media players, a second into the section goes back to its start
+            let into = self.player.time_without_lead_in() - sections[current].start.as_secs_f32();
+            if into > 1.0 {
+                current
+            } else {
+                current.saturating_sub(1)
+            }
+        };
+
+        if let Some(section) = sections.get(id) {
+            self.player.seek(section.start);
+            self.toast_manager
+                .toast(format!("Section: {}", section.name));
+        }
+    }
+
+    /// Loops the section playing now, eg. to practice the chorus
+    fn loop_current_section(&mut self, target: &Target) {
+        let section = match self.current_section(target) {
+            Some(id) => target.midi_file.as_ref().unwrap().analysis.sections[id].clone(),
+            None => return,
+        };
+
+        self.player.loop_range(section.start..section.end);
+        self.toast_manager.toast(format!("Loop: {}", section.name));
+    }
+
+    /// Sets the speed so the song goes at the tapped tempo where it is now
+    fn tap_tempo(&mut self, target: &mut Target) {
+        let bpm = match self.tap_tempo.tap(self.tap_start.elapsed()) {
+            Some(bpm) => bpm,
+            None => {
+                self.toast_manager
+                    .toast(String::from("Tap tempo: Keep tapping in time"));
+                return;
+            }
+        };
+
+        target.config.speed_multiplier = tap_tempo_speed(
+            &target.midi_file.as_ref().unwrap().analysis,
+            self.player.time_without_lead_in(),
+            bpm,
         );
+        self.toast_manager
+            .tap_tempo_toast(bpm, target.config.speed_multiplier);
+    }
+
+    fn toggle_conductor(&mut self, target: &mut Target) {
+        match self.conductor.take() {
+            Some(conductor) => {
+                conductor.finish(&mut self.player);
+                self.toast_manager.toast(String::from("Conductor: Off"));
+            }
+            None => {
+                self.conductor = Some(ConductorMode::new(target, &mut self.player));
+                self.toast_manager.toast(String::from(
+                    "Conductor: On, tap Tab or the pedal on every beat",
+                ));
+            }
+        }
     }
 }
 
@@ -105,7 +747,7 @@ impl Scene for PlayingScene {
 
     fn resize(&mut self, target: &mut Target) {
         self.keyboard_layout = get_layout(
-            target.window_state.logical_size.width,
+            scene_width(target, self.audio_pane.is_some()),
             target.window_state.logical_size.height,
         );
 
@@ -119,35 +761,292 @@ impl Scene for PlayingScene {
             &target.config,
             self.keyboard_layout.clone(),
         );
+
+        if let Some(heatmap) = self.practice_heatmap.as_mut() {
+            heatmap.set_counts(
+                &target.gpu.queue,
+                target.midi_file.as_ref().unwrap(),
+                self.practice_counts.counts(),
+                self.keyboard_layout.width,
+            );
+        }
+
+        if let Some(scene_buffer) = self.scene_buffer.as_mut() {
+            scene_buffer.resize(target);
+        }
     }
 
     fn update(&mut self, target: &mut Target, delta: Duration) {
-        if self.player.play_along().are_required_keys_pressed() || !target.config.play_along {
+        // Nobody to wait for while the song plays on its own
+        self.player.set_wait_for_input(!self.attract);
+
+        if !self.player.is_waiting() {
             self.rewind_controler.update(&mut self.player, target);
-            if let Some(midi_events) = self.player.update(target, delta) {
-                keyboard_events::file_midi_events(
-                    &mut self.piano_keyboard,
+        }
+
+        let midi_events = self.player.update(target);
+        self.update_speed_trainer(target);
+        if let Some(conductor) = self.conductor.as_mut() {
+            conductor.update(&mut self.player);
+        }
+
+        if let Some(midi_events) = midi_events {
+            keyboard_events::file_midi_events(
+                &mut self.piano_keyboard,
+                &target.config,
+                &target.hidden_tracks,
+                &midi_events,
+            );
+            if target.config.impact_particles && !target.config.clean_mode && !target.low_power {
+                keyboard_events::impact_particles(
+                    &mut self.particles,
+                    &self.piano_keyboard,
+                    &self.keyboard_layout,
                     &target.config,
+                    &target.hidden_tracks,
                     &midi_events,
                 );
-            } else {
-                self.piano_keyboard.reset_notes();
             }
+        } else {
+            self.piano_keyboard.reset_notes();
         }
 
+        keyboard_events::upcoming_notes(
+            &mut self.piano_keyboard,
+            &target.config,
+            &target.hidden_tracks,
+            target.midi_file.as_ref().unwrap(),
+            self.player.time_without_lead_in() + target.config.playback_offset,
+        );
+
         self.update_progresbar(target);
 
+        let transposed = self.notes.transpose() != target.config.transpose;
+        if transposed
+            || self.notes.loop_ghosts_outdated(&target.config)
+            || self.notes.style_outdated(&target.config)
+        {
+            self.notes.set_transpose(target.config.transpose);
+            self.notes.resize(
+                &target.gpu.queue,
+                target.midi_file.as_ref().unwrap(),
+                &target.config,
+                self.keyboard_layout.clone(),
+            );
+        }
+        self.update_idle(target, delta);
+        self.update_restart();
+        self.update_practice_lock(target, delta);
+        self.update_input_connection(target);
+        self.update_score_screen(target);
+
+        if let Some(recorder) = self.session_recorder.as_mut() {
+            recorder.update(self.player.time_without_lead_in());
+        }
+        if let Some(recorder) = self.take_recorder.as_mut() {
+            recorder.update();
+        }
+
+        if self.attract {
+            self.practice_counts.interrupt();
+        } else {
+            self.practice_counts
+                .update(self.player.time_without_lead_in());
+        }
+
+        let waiting_for = if target.config.play_along && !self.attract {
+            self.player.play_along().required_notes().clone()
+        } else {
+            Default::default()
+        };
+        if let Some(light_guide) = self.light_guide.as_mut() {
+            light_guide.update(&waiting_for);
+        }
+        if let Some(pad_lights) = self.pad_lights.as_mut() {
+            pad_lights.update(&waiting_for);
+        }
+        // Only while the song stands still, waiting for the user
+        let stuck = self.player.is_waiting() && !self.player.is_paused();
+        let stuck_on = if stuck {
+            waiting_for.clone()
+        } else {
+            Default::default()
+        };
+        if let Some(pitch_hint) = self.pitch_hint.as_mut() {
+            pitch_hint.update(&target.config.pitch_hint, &stuck_on, delta);
+        }
+        if let Some(wait_hints) = self.wait_hints.as_mut() {
+            wait_hints.update(
+                &target.config.wait_hints,
+                &target.config.note_labels,
+                &mut self.piano_keyboard,
+                &stuck_on,
+                delta,
+            );
+        }
+
+        if let Some(art_net) = self.art_net.as_mut() {
+            art_net.update(delta);
+        }
+
+        if let Some(audio_pane) = self.audio_pane.as_mut() {
+            let x = scene_width(target, true);
+            audio_pane.update(
+                &target.gpu.queue,
+                &target.config.audio_pane,
+                delta,
+                [x, 0.0],
+                [
+                    target.window_state.logical_size.width - x,
+                    target.window_state.logical_size.height,
+                ],
+            );
+        }
+
+        if let Some(drum_lane) = self.drum_lane.as_mut() {
+            let height = target.window_state.logical_size.height;
+            let y = height - self.keyboard_layout.height - drum_lane.height();
+            drum_lane.update(
+                &target.gpu.queue,
+                &mut target.text_renderer,
+                self.player.time_without_lead_in() + target.config.playback_offset,
+                [0.0, y],
+                scene_width(target, self.audio_pane.is_some()),
+            );
+        }
+
+        if let Some(notation) = self.notation.as_mut().filter(|_| notation_visible(target)) {
+            let config = &target.config.notation;
+            let height = match config.view {
+                NotationView::Only => {
+                    target.window_state.logical_size.height - self.keyboard_layout.height
+                }
+                _ => config.height,
+            };
+            notation.update(
+                &target.gpu.queue,
+                &target.config.color_schema,
+                &target.config.hand_colors,
+                &target.config.pitch_colors,
+                self.player.time_without_lead_in() + target.config.playback_offset,
+                config.seconds,
+                [0.0, 0.0],
+                [scene_width(target, self.audio_pane.is_some()), height],
+            );
+        }
+
+        self.beat_indicator.update(target, delta);
+        self.mixer_panel.update(target, self.player.mixer());
+        self.quick_settings.update(target);
+
+        if let Some(lyrics) = self.lyrics.as_ref() {
+            lyrics.update(target, self.player.time_without_lead_in());
+        }
+
+        if let Some(watermark) = self.watermark.as_mut() {
+            watermark.update(
+                &target.gpu.queue,
+                target.window_state.logical_size.width,
+                target.window_state.logical_size.height,
+            );
+        }
+
+        let mut waterfall_time = self.player.time_without_lead_in();
+        if let Some(phrase_window) = self.phrase_window.as_mut() {
+            // The song plays on its own in attract mode, nobody practices phrases
+            if !self.attract {
+                phrase_window.update(&mut self.player, target, &mut self.toast_manager);
+                waterfall_time = phrase_window.start().as_secs_f32();
+            }
+        }
+
         self.notes.update(
             &target.gpu.queue,
-            self.player.time_without_lead_in() + target.config.playback_offset,
+            waterfall_time + target.config.playback_offset,
         );
+        // Nothing to label while the staves take the place of the falling notes
+        let notation_only = self.notation.is_some()
+            && notation_visible(target)
+            && target.config.notation.view == NotationView::Only;
+        if !notation_only {
+            self.notes.update_labels(
+                target.text_renderer.glyph_brush(),
+                target.midi_file.as_ref().unwrap(),
+                &target.config.note_labels,
+                waterfall_time + target.config.playback_offset,
+                target.window_state.logical_size.height,
+                target.window_state.scale_factor as f32,
+            );
+        }
+
+        if let Some(heatmap) = self.practice_heatmap.as_mut() {
+            if self.practice_counts.take_changed() {
+                heatmap.set_counts(
+                    &target.gpu.queue,
+                    target.midi_file.as_ref().unwrap(),
+                    self.practice_counts.counts(),
+                    self.keyboard_layout.width,
+                );
+            }
+            heatmap.update(
+                &target.gpu.queue,
+                waterfall_time + target.config.playback_offset,
+            );
+        }
+
+        self.update_camera(target, delta);
 
+        self.piano_keyboard.animate(delta);
+        self.particles.update(&target.gpu.queue, delta);
+        self.piano_keyboard
+            .set_note_labels(target.config.note_labels.clone());
+        self.piano_keyboard
+            .set_key_colors(target.config.themed_key_colors());
+        self.piano_keyboard
+            .set_scale_overlay(&target.config.scale_overlay);
+        self.background.animate(&mut target.gpu, delta);
+        self.background.update(
+            &target.gpu.queue,
+            target.config.themed_background_color(),
+            target.config.themed_background_gradient(),
+            [
+                target.window_state.logical_size.width,
+                target.window_state.logical_size.height,
+            ],
+        );
         self.piano_keyboard
             .update(&target.gpu.queue, target.text_renderer.glyph_brush());
+        if target.config.clean_mode {
+            self.toast_manager.clear();
+        }
         self.toast_manager.update(target);
     }
 
     fn render(&mut self, target: &mut Target, view: &wgpu::TextureView) {
+        let notation = self.notation.as_ref().filter(|_| notation_visible(target));
+        // The staves take the place of the falling notes
+        let waterfall = !(notation.is_some() && target.config.notation.view == NotationView::Only);
+
+        if let Some(scene_buffer) = self.scene_buffer.as_ref() {
+            let mut render_pass = scene_buffer.begin_render_pass(
+                &mut target.gpu.encoder,
+                target.config.themed_background_color().into(),
+            );
+
+            self.background
+                .render(&target.transform_uniform, &mut render_pass);
+            if waterfall {
+                if let Some(heatmap) = self.practice_heatmap.as_ref() {
+                    heatmap.render(&self.camera_uniform, &mut render_pass);
+                }
+                self.notes.render(&self.camera_uniform, &mut render_pass);
+            }
+            self.piano_keyboard
+                .render(&self.camera_uniform, &mut render_pass);
+            self.particles
+                .render(&self.camera_uniform, &mut render_pass);
+        }
+
         let mut render_pass = target
             .gpu
             .encoder
@@ -164,35 +1063,137 @@ impl Scene for PlayingScene {
                 depth_stencil_attachment: None,
             });
 
-        self.notes
-            .render(&target.transform_uniform, &mut render_pass);
+        match self.scene_buffer.as_ref() {
+            Some(scene_buffer) => scene_buffer.present(&mut render_pass),
+            None => {
+                self.background
+                    .render(&target.transform_uniform, &mut render_pass);
+                if waterfall {
+                    if let Some(heatmap) = self.practice_heatmap.as_ref() {
+                        heatmap.render(&self.camera_uniform, &mut render_pass);
+                    }
+                    self.notes.render(&self.camera_uniform, &mut render_pass);
+                }
 
-        self.piano_keyboard
-            .render(&target.transform_uniform, &mut render_pass);
+                self.piano_keyboard
+                    .render(&self.camera_uniform, &mut render_pass);
+                self.particles
+                    .render(&self.camera_uniform, &mut render_pass);
+            }
+        }
+
+        if let Some(drum_lane) = self.drum_lane.as_ref() {
+            drum_lane.render(&target.transform_uniform, &mut render_pass);
+        }
+
+        if let Some(notation) = notation {
+            notation.render(&target.transform_uniform, &mut render_pass);
+        }
+
+        if let Some(audio_pane) = self.audio_pane.as_ref() {
+            audio_pane.render(&target.transform_uniform, &mut render_pass);
+        }
 
         self.quad_pipeline
-            .render(&target.transform_uniform, &mut render_pass)
+            .render(&target.transform_uniform, &mut render_pass);
+        self.beat_indicator
+            .render(&target.transform_uniform, &mut render_pass);
+        self.mixer_panel
+            .render(&target.transform_uniform, &mut render_pass);
+        self.quick_settings
+            .render(&target.transform_uniform, &mut render_pass);
+
+        if let Some(watermark) = self.watermark.as_ref() {
+            watermark.render(&target.transform_uniform, &mut render_pass);
+        }
     }
 
     fn window_event(&mut self, target: &mut Target, event: &WindowEvent) {
         use winit::event::WindowEvent::*;
         use winit::event::{ElementState, VirtualKeyCode};
 
+        if let KeyboardInput { .. } | MouseInput { .. } = event {
+            self.user_input();
+        }
+
         match &event {
             KeyboardInput { input, .. } => {
+                if self
+                    .mixer_panel
+                    .handle_keyboard_input(self.player.mixer_mut(), input)
+                {
+                    return;
+                }
+
                 self.rewind_controler
                     .handle_keyboard_input(&mut self.player, input);
 
                 settings_keyboard_input(target, &mut self.toast_manager, input);
 
+                if input.virtual_keycode == Some(VirtualKeyCode::Tab) {
+                    let pressed = input.state == ElementState::Pressed;
+                    if pressed && !self.tap_held {
+                        match self.conductor.as_mut() {
+                            Some(conductor) => conductor.tap(&mut self.player),
+                            None => self.tap_tempo(target),
+                        }
+                    }
+                    self.tap_held = pressed;
+                }
+
                 if input.state == ElementState::Released {
                     match input.virtual_keycode {
                         Some(VirtualKeyCode::Escape) => {
                             target.proxy.send_event(NeothesiaEvent::GoBack).ok();
                         }
                         Some(VirtualKeyCode::Space) => {
+                            self.paused_by_focus = false;
+                            self.paused_by_input = false;
                             self.player.pause_resume();
                         }
+                        Some(VirtualKeyCode::LBracket) => {
+                            self.player.set_loop_start();
+                            self.toast_manager
+                                .loop_markers_toast(self.player.loop_markers());
+                        }
+                        Some(VirtualKeyCode::RBracket) => {
+                            if self.player.set_loop_end() {
+                                self.toast_manager
+                                    .loop_markers_toast(self.player.loop_markers());
+                            } else {
+                                self.toast_manager.toast(String::from(
+                                    "Loop: Set the start with [ before the end",
+                                ));
+                            }
+                        }
+                        Some(VirtualKeyCode::Back) => {
+                            self.player.clear_loop();
+                            self.toast_manager.loop_markers_toast(None);
+                        }
+                        Some(VirtualKeyCode::Comma) => {
+                            self.jump_to_section(target, false);
+                        }
+                        Some(VirtualKeyCode::Period) => {
+                            self.jump_to_section(target, true);
+                        }
+                        Some(VirtualKeyCode::Slash) => {
+                            self.loop_current_section(target);
+                        }
+                        Some(VirtualKeyCode::P) => {
+                            self.toggle_phrase_practice(target);
+                        }
+                        Some(VirtualKeyCode::C) => {
+                            self.toggle_conductor(target);
+                        }
+                        Some(VirtualKeyCode::Q) => {
+                            self.quick_settings.open = !self.quick_settings.open;
+                        }
+                        Some(VirtualKeyCode::S) => {
+                            target
+                                .proxy
+                                .send_event(NeothesiaEvent::OpenSettingsWindow)
+                                .ok();
+                        }
                         _ => {}
                     }
                 }
@@ -205,11 +1206,40 @@ impl Scene for PlayingScene {
                 self.rewind_controler
                     .handle_cursor_moved(&mut self.player, target, position);
             }
+            Focused(focused) => {
+                if !focused {
+                    self.release_held_keys(target);
+                }
+                self.focused = *focused;
+                self.background_changed(target);
+            }
+            Occluded(occluded) => {
+                self.occluded = *occluded;
+                self.background_changed(target);
+            }
             _ => {}
         }
     }
 
-    fn midi_event(&mut self, _target: &mut Target, event: &MidiEvent) {
+    fn midi_event(&mut self, target: &mut Target, event: &MidiEvent) {
+        self.user_input();
+        target.event_bus.publish(AppEvent::UserMidi(*event));
+
+        // Stamped right away, the bus is only read once per frame
+        if let Some(recorder) = self.take_recorder.as_mut() {
+            recorder.record(self.player.time_without_lead_in(), event);
+        }
+
+        match event {
+            MidiEvent::NoteOn { channel, key, .. } => {
+                self.held_keys.insert((*channel, *key));
+            }
+            MidiEvent::NoteOff { channel, key } => {
+                self.held_keys.remove(&(*channel, *key));
+            }
+            MidiEvent::Controller { .. } => {}
+        }
+
         match event {
             MidiEvent::NoteOn { key, .. } => self.player.play_along_mut().press_key(
                 midi_player::KeyPressSource::User,
@@ -221,10 +1251,41 @@ impl Scene for PlayingScene {
                 *key,
                 false,
             ),
+            MidiEvent::Controller { .. } => {}
+        }
+
+        if let Some(conductor) = self.conductor.as_mut() {
+            conductor.midi_event(&mut self.player, event);
         }
 
+        if target.config.play_input_notes {
+            keyboard_events::play_user_note(target, event);
+        } else {
+            keyboard_events::key_click(target, event);
+        }
         keyboard_events::user_midi_event(&mut self.piano_keyboard, event);
     }
+
+    fn is_idle(&self) -> bool {
+        self.player.is_paused()
+    }
+
+    fn global_hotkey(&mut self, target: &mut Target, action: HotkeyAction) {
+        self.user_input();
+        self.paused_by_focus = false;
+        self.paused_by_input = false;
+
+        match action {
+            HotkeyAction::PlayPause => self.player.pause_resume(),
+            HotkeyAction::Rewind => self.player.rewind(-5000),
+            HotkeyAction::Forward => self.player.rewind(5000),
+            HotkeyAction::Mute => {
+                let volume = &mut target.config.master_volume;
+                volume.muted = !volume.muted;
+                self.toast_manager.volume_toast(*volume);
+            }
+        }
+    }
 }
 
 fn settings_keyboard_input(
@@ -246,22 +1307,48 @@ fn settings_keyboard_input(
 
     match virtual_keycode {
         VirtualKeyCode::Up | VirtualKeyCode::Down => {
-            let amount = if target.window_state.modifers_state.shift() {
-                0.5
+            let steps = if target.window_state.modifers_state.shift() {
+                5.0
             } else {
-                0.1
+                1.0
             };
 
-            if virtual_keycode == VirtualKeyCode::Up {
-                target.config.speed_multiplier += amount;
+            let speed = target.config.speed_multiplier;
+            target.config.speed_multiplier = if virtual_keycode == VirtualKeyCode::Up {
+                step_speed(speed, steps)
             } else {
-                target.config.speed_multiplier -= amount;
-                target.config.speed_multiplier = target.config.speed_multiplier.max(0.0);
-            }
+                step_speed(speed, -steps)
+            };
 
             toast_manager.speed_toast(target.config.speed_multiplier);
         }
 
+        VirtualKeyCode::T => {
+            let trainer = &mut target.config.speed_trainer;
+            trainer.enabled = !trainer.enabled;
+            toast_manager.speed_trainer_toast(trainer.enabled, trainer.target);
+        }
+
+        VirtualKeyCode::Minus | VirtualKeyCode::Plus | VirtualKeyCode::Equals
+            if target.window_state.modifers_state.ctrl() =>
+        {
+            // An octave at once with shift
+            let amount = if target.window_state.modifers_state.shift() {
+                12
+            } else {
+                1
+            };
+
+            let transpose = if virtual_keycode == VirtualKeyCode::Minus {
+                target.config.transpose as i16 - amount
+            } else {
+                target.config.transpose as i16 + amount
+            };
+            target.config.transpose = transpose.clamp(-MAX_TRANSPOSE, MAX_TRANSPOSE) as i8;
+
+            toast_manager.transpose_toast(target.config.transpose);
+        }
+
         VirtualKeyCode::Minus | VirtualKeyCode::Plus | VirtualKeyCode::Equals => {
             let amount = if target.window_state.modifers_state.shift() {
                 0.1
@@ -278,6 +1365,88 @@ fn settings_keyboard_input(
             toast_manager.offset_toast(target.config.playback_offset);
         }
 
+        VirtualKeyCode::PageUp | VirtualKeyCode::PageDown => {
+            let steps = if target.window_state.modifers_state.shift() {
+                6.0
+            } else {
+                1.0
+            };
+
+            let volume = &mut target.config.master_volume;
+            if virtual_keycode == VirtualKeyCode::PageUp {
+                volume.step(steps);
+            } else {
+                volume.step(-steps);
+            }
+            volume.muted = false;
+
+            toast_manager.volume_toast(*volume);
+        }
+
+        VirtualKeyCode::Key0 | VirtualKeyCode::Mute => {
+            let volume = &mut target.config.master_volume;
+            volume.muted = !volume.muted;
+            toast_manager.volume_toast(*volume);
+        }
+
+        VirtualKeyCode::L => {
+            target.config.loop_playback.enabled = !target.config.loop_playback.enabled;
+            toast_manager.loop_toast(target.config.loop_playback.enabled);
+        }
+
+        VirtualKeyCode::B => {
+            let boost = &mut target.config.melody_boost;
+            boost.enabled = !boost.enabled;
+            toast_manager.melody_boost_toast(boost.enabled);
+        }
+
+        VirtualKeyCode::M => {
+            target.config.metronome.enabled = !target.config.metronome.enabled;
+            toast_manager.metronome_toast(target.config.metronome.enabled);
+        }
+
+        VirtualKeyCode::N => {
+            let notation = &mut target.config.notation;
+            notation.view = notation.view.next();
+            toast_manager.toast(format!("Notation: {}", notation.view));
+        }
+
+        VirtualKeyCode::H => {
+            target.config.clean_mode = !target.config.clean_mode;
+            target.window.set_cursor_visible(!target.config.clean_mode);
+        }
+
         _ => {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midi_file::analysis::TempoChange;
+
+    #[test]
+    fn tap_tempo_follows_the_tempo_map() {
+        let analysis = midi_file::SongAnalysis {
+            tempo_map: vec![
+                TempoChange {
+                    time: Duration::ZERO,
+                    bpm: 100.0,
+                },
+                TempoChange {
+                    time: Duration::from_secs(10),
+                    bpm: 150.0,
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(tap_tempo_speed(&analysis, 5.0, 80.0), 0.8);
+        assert_eq!(tap_tempo_speed(&analysis, 12.0, 80.0), 0.53);
+        // Lead-in is before the first tempo
+        assert_eq!(tap_tempo_speed(&analysis, -2.0, 50.0), 0.5);
+
+        assert_eq!(tap_tempo_speed(&analysis, 5.0, 10.0), MIN_SPEED);
+        assert_eq!(tap_tempo_speed(&analysis, 5.0, 400.0), MAX_SPEED);
+    }
+}
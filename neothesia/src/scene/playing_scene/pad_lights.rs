@@ -0,0 +1,64 @@
+use std::collections::HashSet;
+
+use crate::{input_manager::PadGrid, output_manager, target::Target};
+
+/// Lights the pads of the notes play along waits for, see [`crate::config::PadGrid`]
+pub struct PadLights {
+    conn: midi_io::MidiOutputConnection,
+    pad_grid: PadGrid,
+    lit: HashSet<u8>,
+}
+
+impl PadLights {
+    /// Returns `None` unless a grid controller is the input, and its output can be reached
+    pub fn new(target: &Target) -> Option<Self> {
+        let pad_grid = target.input_manager.pad_grid()?;
+
+        let name = match target.config.pad_grid.output.clone() {
+            Some(name) => name,
+            None => target.input_manager.current_port()?.to_string(),
+        };
+        let mut conn = output_manager::connect_midi_output(&name)?;
+
+        if let Some(init) = pad_grid.init_message() {
+            conn.send(init).ok();
+        }
+
+        Some(Self {
+            conn,
+            pad_grid,
+            lit: HashSet::new(),
+        })
+    }
+
+    /// Lights the pads of `notes`, and turns off the ones lit before that are not in it anymore
+    pub fn update(&mut self, notes: &HashSet<u8>) {
+        if &self.lit == notes {
+            return;
+        }
+
+        let off: Vec<u8> = self.lit.difference(notes).copied().collect();
+        let on: Vec<u8> = notes.difference(&self.lit).copied().collect();
+
+        for note in off {
+            self.send(note, false);
+        }
+        for note in on {
+            self.send(note, true);
+        }
+
+        self.lit = notes.clone();
+    }
+
+    fn send(&mut self, note: u8, on: bool) {
+        for pad in self.pad_grid.pads(note) {
+            self.conn.send(&self.pad_grid.light_message(pad, on)).ok();
+        }
+    }
+}
+
+impl Drop for PadLights {
+    fn drop(&mut self) {
+        self.update(&HashSet::new());
+    }
+}
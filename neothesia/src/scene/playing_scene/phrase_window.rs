@@ -0,0 +1,124 @@
+use std::{ops::Range, sync::mpsc::Receiver, sync::Arc, time::Duration};
+
+use neothesia_engine::PlayAlongStats;
+
+use super::midi_player::MidiPlayer;
+use crate::{event_bus::AppEvent, target::Target, toast_manager::ToastManager};
+
+/// Shows the song a phrase at a time, see [`crate::config::PhrasePractice`].
+///
+/// The waterfall stands still at the start of the phrase while it's played, once its end is
+/// reached the phrase either starts over or the waterfall snaps to the next one
+pub struct PhraseWindow {
+    events: Receiver<AppEvent>,
+    midi: Arc<midi_file::Midi>,
+    bars: usize,
+    /// First measure of the phrase on screen
+    first: usize,
+    /// Play along stats when the phrase started, the phrase is judged on what changed since
+    stats: PlayAlongStats,
+    /// The last phrase was played, the song carries on to its end
+    done: bool,
+}
+
+impl PhraseWindow {
+    /// Returns `None` unless phrase practice is enabled, and the song has measures
+    pub fn new(target: &Target) -> Option<Self> {
+        let config = &target.config.phrase_practice;
+        if !config.enabled {
+            return None;
+        }
+
+        let midi = target.midi_file.as_ref()?.clone();
+        if midi.analysis.measures.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            events: target.event_bus.subscribe(),
+            midi,
+            bars: config.bars.clamp(2, 4) as usize,
+            first: 0,
+            stats: PlayAlongStats::default(),
+            done: false,
+        })
+    }
+
+    fn measures(&self) -> Range<usize> {
+        let len = self.midi.analysis.measures.len();
+        self.first..(self.first + self.bars).min(len)
+    }
+
+    fn is_last(&self) -> bool {
+        self.measures().end == self.midi.analysis.measures.len()
+    }
+
+    /// Start of the phrase, where the waterfall stands still
+    pub fn start(&self) -> Duration {
+        self.midi.analysis.measures[self.first].start
+    }
+
+    fn end(&self) -> Duration {
+        self.midi.analysis.measures[self.measures().end - 1].end
+    }
+
+    /// Puts the phrase around `time` on screen, and judges it from now on
+    pub fn place(&mut self, time: Duration, stats: PlayAlongStats) {
+        let measure = self.midi.analysis.measure_at(time).unwrap_or(0);
+        self.first = measure - measure % self.bars;
+        self.stats = stats;
+        self.done = false;
+    }
+
+    pub fn update(&mut self, player: &mut MidiPlayer, target: &Target, toasts: &mut ToastManager) {
+        let time = Duration::from_secs_f32(player.time_without_lead_in().max(0.0));
+        let stats = *player.play_along().stats();
+
+        let seeked = self
+            .events
+            .try_iter()
+            .filter(|event| matches!(event, AppEvent::PlaybackSeek(_)))
+            .count()
+            > 0;
+
+        // Jumps pick the phrase they land in, the phrase repeating included
+        if seeked {
+            self.place(time, stats);
+            return;
+        }
+
+        if self.done || time < self.end() {
+            return;
+        }
+
+        // Stats start over with attract mode
+        let played = PlayAlongStats {
+            hits: stats.hits.saturating_sub(self.stats.hits),
+            early: stats.early.saturating_sub(self.stats.early),
+            late: stats.late.saturating_sub(self.stats.late),
+            missed: stats.missed.saturating_sub(self.stats.missed),
+            wrong: stats.wrong.saturating_sub(self.stats.wrong),
+            ..Default::default()
+        };
+        // A phrase without notes to play is always played well
+        let accuracy = played.accuracy().unwrap_or(1.0);
+        let percent = (accuracy * 100.0).round();
+
+        if accuracy < target.config.phrase_practice.accuracy {
+            toasts.toast(format!("Phrase: {percent}%, once more"));
+            player.play_along_mut().clear();
+            player.seek(self.start());
+        } else if self.is_last() {
+            toasts.toast(format!("Phrase: {percent}%, that's the whole song"));
+            self.done = true;
+        } else {
+            let len = self.midi.analysis.measures.len();
+            let phrases = (len + self.bars - 1) / self.bars;
+            let next = self.first / self.bars + 2;
+            toasts.toast(format!("Phrase: {percent}%, on to {next}/{phrases}"));
+
+            self.first += self.bars;
+            self.stats = stats;
+        }
+    }
+}
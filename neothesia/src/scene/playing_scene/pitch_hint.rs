@@ -0,0 +1,72 @@
+use std::{collections::HashSet, time::Duration};
+
+use midi_file::midly::{num::u7, MidiMessage};
+
+use crate::{config, output_manager::SharedOutput, target::Target};
+
+/// How long a hint note is held
+const HINT_LEN: Duration = Duration::from_millis(600);
+
+/// Sounds the notes play along waits for, see [`config::PitchHint`]
+pub struct PitchHint {
+    output: SharedOutput,
+    /// Time waited since the wait started, or since the last hint
+    waited: Duration,
+}
+
+impl PitchHint {
+    /// Returns `None` unless pitch hints are enabled
+    pub fn new(target: &Target) -> Option<Self> {
+        if !target.config.pitch_hint.enabled {
+            return None;
+        }
+
+        Some(Self {
+            output: target.output_manager.borrow().shared_output(),
+            waited: Duration::ZERO,
+        })
+    }
+
+    /// `waiting_for` is empty unless playback waits for the user
+    pub fn update(
+        &mut self,
+        config: &config::PitchHint,
+        waiting_for: &HashSet<u8>,
+        delta: Duration,
+    ) {
+        if waiting_for.is_empty() {
+            self.waited = Duration::ZERO;
+            return;
+        }
+
+        self.waited += delta;
+        if self.waited.as_secs_f32() < config.delay.max(0.5) {
+            return;
+        }
+        self.waited = Duration::ZERO;
+
+        let vel = u7::new(((config.volume.clamp(0.0, 1.0) * 127.0).round() as u8).max(1));
+        let mut output = self.output.lock().unwrap();
+
+        for key in waiting_for.iter().map(|key| u7::new(*key)) {
+            // The file doesn't say which sound the user should hear, channel 1 is usually a piano
+            let event = |message| midi_file::MidiEvent {
+                channel: 0,
+                delta: 0,
+                timestamp: Duration::ZERO,
+                message,
+                track_id: usize::MAX,
+                track_color_id: 0,
+            };
+
+            output.midi_event(&event(MidiMessage::NoteOn { key, vel }));
+            output.midi_event_delayed(
+                &event(MidiMessage::NoteOff {
+                    key,
+                    vel: u7::new(0),
+                }),
+                HINT_LEN,
+            );
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::{path::PathBuf, sync::mpsc::Receiver, sync::Arc, time::Duration};
+
+use neothesia_core::utils::{resources, sync};
+use serde::{Deserialize, Serialize};
+
+use crate::{event_bus::AppEvent, target::Target};
+
+/// Part of a measure that has to be played through for it to count
+const PLAYED_THROUGH: f32 = 0.75;
+
+#[derive(Serialize, Deserialize, Default)]
+struct SavedCounts {
+    /// Only there to tell the files apart by hand
+    song: String,
+    counts: Vec<u32>,
+}
+
+/// File of the counts of a song, named by a hash of its contents so songs that share a
+/// name don't share counts, and a song keeps its counts when it's moved or renamed.
+/// `None` for songs parsed from memory, their counts aren't kept
+fn song_file(midi: &midi_file::Midi) -> Option<PathBuf> {
+    let data = std::fs::read(midi.path.as_ref()?).ok()?;
+    let dir = resources::practice_counts_dir()?;
+    Some(dir.join(format!("{:016x}.ron", fnv1a(&data))))
+}
+
+/// 64-bit FNV-1a, unlike the std hasher it stays the same between Rust versions
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Counts how many times each measure of the song was played through, in any mode.
+/// Jumps don't count, so the measures that always get skipped stay at zero
+pub struct PracticeCounts {
+    events: Receiver<AppEvent>,
+    midi: Arc<midi_file::Midi>,
+    file: Option<PathBuf>,
+    /// The song can end before its last measure does
+    song_end: Duration,
+    counts: Vec<u32>,
+
+    /// Measure being played, and the time playback entered it at
+    current: Option<(usize, Duration)>,
+    last_time: Duration,
+    /// Counts changed since the last call to `take_changed`
+    changed: bool,
+}
+
+impl PracticeCounts {
+    pub fn new(target: &Target) -> Self {
+        let midi = target.midi_file.as_ref().unwrap().clone();
+        let file = song_file(&midi);
+        let song_end = midi
+            .merged_track
+            .notes
+            .iter()
+            .map(|note| note.end)
+            .max()
+            .unwrap_or_default();
+
+        let mut counts = file
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|data| ron::from_str::<SavedCounts>(&data).ok())
+            .unwrap_or_default()
+            .counts;
+        counts.resize(midi.analysis.measures.len(), 0);
+
+        Self {
+            events: target.event_bus.subscribe(),
+            midi,
+            file,
+            song_end,
+            counts,
+            current: None,
+            last_time: Duration::ZERO,
+            changed: true,
+        }
+    }
+
+    pub fn counts(&self) -> &[u32] {
+        &self.counts
+    }
+
+    /// Whether the counts changed since the last call
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Nobody is practicing, the measure being played doesn't count
+    pub fn interrupt(&mut self) {
+        self.current = None;
+    }
+
+    /// `time` is the current song time, without the lead-in
+    pub fn update(&mut self, time: f32) {
+        let time = Duration::from_secs_f32(time.max(0.0));
+
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                // Whatever was played before the jump doesn't add up to a measure
+                AppEvent::PlaybackSeek(_) => {
+                    self.current = None;
+                    self.last_time = time;
+                }
+                AppEvent::PlaybackFinished => {
+                    self.finish_measure(self.last_time.max(time));
+                }
+                _ => {}
+            }
+        }
+
+        // Loops jump back without a seek
+        if time < self.last_time {
+            self.finish_measure(self.last_time);
+        }
+        self.last_time = time;
+
+        let measures = &self.midi.analysis.measures;
+        let measure = self
+            .midi
+            .analysis
+            .measure_at(time)
+            .filter(|id| time < measures[*id].end);
+
+        if self.current.map(|(id, _)| id) != measure {
+            self.finish_measure(time);
+            self.current = measure.map(|id| (id, time));
+        }
+    }
+
+    /// Leaves the current measure at `time`, counting it when it was played through
+    fn finish_measure(&mut self, time: Duration) {
+        let (id, entered) = match self.current.take() {
+            Some(current) => current,
+            None => return,
+        };
+
+        let measure = &self.midi.analysis.measures[id];
+        let end = measure.end.min(self.song_end).max(measure.start);
+
+        let played = time.min(end).saturating_sub(entered);
+        let len = end.saturating_sub(measure.start);
+
+        if played.as_secs_f32() >= len.as_secs_f32() * PLAYED_THROUGH {
+            self.counts[id] += 1;
+            self.changed = true;
+        }
+    }
+
+    fn save(&self) {
+        let path = match self.file.as_ref() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let saved = SavedCounts {
+            song: self.midi.name.clone(),
+            counts: self.counts.clone(),
+        };
+
+        let data = match ron::ser::to_string_pretty(&saved, Default::default()) {
+            Ok(data) => data,
+            Err(err) => {
+                log::error!("Failed to save practice counts: {err}");
+                return;
+            }
+        };
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).ok();
+        }
+        if let Err(err) = sync::write_atomic(path, data) {
+            log::error!("Failed to save practice counts: {err}");
+        }
+    }
+}
+
+impl Drop for PracticeCounts {
+    fn drop(&mut self) {
+        self.save();
+    }
+}
@@ -0,0 +1,95 @@
+use neothesia_pipelines::quad::{QuadInstance, QuadPipeline};
+use wgpu_jumpstart::{Color, TransformUniform, Uniform};
+
+use crate::{config::Config, target::Target};
+
+const ROW_HEIGHT: f32 = 26.0;
+const WIDTH: f32 = 340.0;
+const PADDING: f32 = 12.0;
+
+/// Settings changed by keys while playing, opened over the waterfall with Q
+pub struct QuickSettings {
+    quad_pipeline: QuadPipeline,
+    pub open: bool,
+}
+
+impl QuickSettings {
+    pub fn new(target: &Target) -> Self {
+        Self {
+            quad_pipeline: QuadPipeline::new(&target.gpu, &target.transform_uniform),
+            open: false,
+        }
+    }
+
+    pub fn update(&mut self, target: &mut Target) {
+        if !self.open || target.config.clean_mode {
+            self.quad_pipeline
+                .update_instance_buffer(&target.gpu.queue, Vec::new());
+            return;
+        }
+
+        let rows = rows(&target.config);
+        let x = target.window_state.logical_size.width - WIDTH - PADDING;
+        let y = target.config.progress_bar.height + 40.0;
+        let height = ROW_HEIGHT * rows.len() as f32 + PADDING * 2.0;
+
+        let quads = vec![QuadInstance {
+            position: [x, y],
+            size: [WIDTH, height],
+            color: Color::new(0.0, 0.0, 0.0, 0.75).into_linear_rgba(),
+            border_radius: [6.0; 4],
+        }];
+
+        for (id, (name, value, keys)) in rows.iter().enumerate() {
+            let row_y = y + PADDING + ROW_HEIGHT * id as f32;
+            target.text_renderer.queue_text(wgpu_glyph::Section {
+                screen_position: (x + PADDING, row_y),
+                text: vec![
+                    wgpu_glyph::Text::new(&format!("{name}: {value}"))
+                        .with_color([1.0, 1.0, 1.0, 1.0])
+                        .with_scale(18.0),
+                    wgpu_glyph::Text::new(&format!("  {keys}"))
+                        .with_color([1.0, 1.0, 1.0, 0.5])
+                        .with_scale(14.0),
+                ],
+                ..Default::default()
+            });
+        }
+
+        self.quad_pipeline
+            .update_instance_buffer(&target.gpu.queue, quads);
+    }
+
+    pub fn render<'rpass>(
+        &'rpass self,
+        transform_uniform: &'rpass Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+    ) {
+        self.quad_pipeline.render(transform_uniform, render_pass);
+    }
+}
+
+fn on_off(enabled: bool) -> String {
+    String::from(if enabled { "On" } else { "Off" })
+}
+
+/// Name, value and the keys that change it
+fn rows(config: &Config) -> Vec<(&'static str, String, &'static str)> {
+    vec![
+        (
+            "Speed",
+            format!("{}%", (config.speed_multiplier * 100.0).round()),
+            "Up/Down",
+        ),
+        ("Transpose", format!("{:+}", config.transpose), "Ctrl -/+"),
+        ("Volume", config.master_volume.to_string(), "PgUp/PgDn, 0"),
+        (
+            "Offset",
+            format!("{}", (config.playback_offset * 100.0).round() / 100.0),
+            "-/+",
+        ),
+        ("Loop", on_off(config.loop_playback.enabled), "L"),
+        ("Metronome", on_off(config.metronome.enabled), "M"),
+        ("Melody boost", on_off(config.melody_boost.enabled), "B"),
+    ]
+}
@@ -0,0 +1,111 @@
+use neothesia_core::config::{RenderQuality, TextureFilter};
+use neothesia_pipelines::blit::BlitPipeline;
+use wgpu_jumpstart::{wgpu, Color, Framebuffer};
+
+use crate::target::Target;
+
+/// Offscreen target the waterfall and the keyboard are drawn into, so they can be
+/// multisampled and rendered at a different resolution than the window
+pub struct SceneBuffer {
+    framebuffer: Framebuffer,
+    blit: BlitPipeline,
+    resolution_scale: f32,
+}
+
+impl SceneBuffer {
+    /// Returns `None` when the render quality settings don't need an offscreen pass
+    pub fn new(target: &Target, quality: &RenderQuality) -> Option<Self> {
+        if quality.is_native() {
+            return None;
+        }
+
+        let filter = match quality.filter {
+            TextureFilter::Linear => wgpu::FilterMode::Linear,
+            TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+        };
+
+        let samples = quality.msaa.samples();
+        let sample_count = if target.gpu.supports_sample_count(samples) {
+            samples
+        } else {
+            log::warn!("{samples}x MSAA is not supported by this GPU");
+            1
+        };
+
+        let resolution_scale = quality.resolution_scale();
+        let (width, height) = scaled_size(target, resolution_scale);
+        let framebuffer = Framebuffer::new(
+            &target.gpu.device,
+            target.gpu.texture_format,
+            width,
+            height,
+            sample_count,
+        );
+
+        let mut blit = BlitPipeline::new(&target.gpu, filter);
+        blit.set_source(&target.gpu.device, framebuffer.view());
+
+        Some(Self {
+            framebuffer,
+            blit,
+            resolution_scale,
+        })
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.framebuffer.sample_count()
+    }
+
+    pub fn resize(&mut self, target: &Target) {
+        let (width, height) = scaled_size(target, self.resolution_scale);
+        if self.framebuffer.size() == (width, height) {
+            return;
+        }
+
+        self.framebuffer = Framebuffer::new(
+            &target.gpu.device,
+            target.gpu.texture_format,
+            width,
+            height,
+            self.framebuffer.sample_count(),
+        );
+        self.blit
+            .set_source(&target.gpu.device, self.framebuffer.view());
+    }
+
+    /// Render pass into the buffer, cleared to `color`
+    pub fn begin_render_pass<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        color: Color,
+    ) -> wgpu::RenderPass<'a> {
+        let rgb = color.into_linear_rgb();
+        let color = wgpu::Color {
+            r: rgb[0] as f64,
+            g: rgb[1] as f64,
+            b: rgb[2] as f64,
+            a: 1.0,
+        };
+
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(
+                self.framebuffer
+                    .color_attachment(wgpu::LoadOp::Clear(color)),
+            )],
+            depth_stencil_attachment: None,
+        })
+    }
+
+    /// Draws the buffer over the whole `render_pass` target
+    pub fn present<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        self.blit.render(render_pass);
+    }
+}
+
+fn scaled_size(target: &Target, scale: f32) -> (u32, u32) {
+    let size = target.window_state.physical_size;
+    let width = (size.width as f32 * scale).round() as u32;
+    let height = (size.height as f32 * scale).round() as u32;
+    (width.max(1), height.max(1))
+}
@@ -0,0 +1,116 @@
+use std::{
+    path::PathBuf,
+    sync::mpsc::Receiver,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use neothesia_core::utils::sync;
+use neothesia_engine::PerformanceLog;
+
+use crate::{config::SessionFormat, event_bus::AppEvent, midi_event::MidiEvent, target::Target};
+
+/// Collects per-note results of the session from the app bus,
+/// and saves them when the song finishes or the scene is left
+pub struct SessionRecorder {
+    events: Receiver<AppEvent>,
+    log: PerformanceLog,
+
+    song: String,
+    format: SessionFormat,
+    directory: Option<PathBuf>,
+}
+
+impl SessionRecorder {
+    /// Returns `None` when session export is disabled
+    pub fn new(target: &Target) -> Option<Self> {
+        let export = &target.config.session_export;
+        if !export.enabled {
+            return None;
+        }
+
+        let song = target
+            .midi_file
+            .as_ref()
+            .map(|midi| midi.name.clone())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "session".into());
+
+        Some(Self {
+            events: target.event_bus.subscribe(),
+            log: PerformanceLog::new(),
+            song,
+            format: export.format,
+            directory: export
+                .directory
+                .clone()
+                .or_else(neothesia_core::utils::resources::sessions_dir),
+        })
+    }
+
+    /// `time` is the current song time, user events are stamped with it
+    pub fn update(&mut self, time: f32) {
+        use midi_file::midly::MidiMessage;
+
+        let now = Duration::from_secs_f32(time.max(0.0));
+
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                AppEvent::FileMidi(event) => {
+                    if let MidiMessage::NoteOn { key, vel } = event.message {
+                        if event.channel != 9 && vel.as_int() > 0 {
+                            self.log.expect(key.as_int(), event.timestamp);
+                        }
+                    }
+                }
+                AppEvent::UserMidi(MidiEvent::NoteOn { key, vel, .. }) => {
+                    self.log.play(key, vel, now);
+                }
+                AppEvent::PlaybackSeek(_) => self.log.interrupt(),
+                AppEvent::PlaybackFinished => self.save(),
+                _ => {}
+            }
+        }
+    }
+
+    /// Writes the session so far and starts a new one
+    fn save(&mut self) {
+        let log = std::mem::take(&mut self.log);
+        if log.is_empty() {
+            return;
+        }
+
+        let directory = match self.directory.as_ref() {
+            Some(directory) => directory,
+            None => return,
+        };
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        // One file per session and machine, so synced data directories never write the same file
+        let path = directory.join(format!(
+            "{}-{timestamp}-{}.{}",
+            self.song,
+            sync::device_name(),
+            self.format.extension()
+        ));
+
+        let data = match self.format {
+            SessionFormat::Csv => log.to_csv(),
+            SessionFormat::Json => log.to_json(),
+        };
+
+        let res = std::fs::create_dir_all(directory).and_then(|_| sync::write_atomic(&path, data));
+        match res {
+            Ok(()) => log::info!("Session saved to {}", path.display()),
+            Err(err) => log::error!("Failed to save session {}: {err}", path.display()),
+        }
+    }
+}
+
+impl Drop for SessionRecorder {
+    fn drop(&mut self) {
+        self.save();
+    }
+}
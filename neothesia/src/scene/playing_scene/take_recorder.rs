@@ -0,0 +1,58 @@
+use std::{sync::mpsc::Receiver, time::Duration};
+
+use crate::{
+    event_bus::AppEvent, input_recorder::InputRecorder, midi_event::MidiEvent, target::Target,
+};
+
+/// Takes cut short by a jump are dropped, jumping around to practice a passage
+/// would write a file every time otherwise
+const MIN_TAKE: Duration = Duration::from_secs(10);
+
+/// Records the input in time with the song, so the file lines up with it.
+/// Every jump starts a new take, they are saved when the song finishes or the scene is left,
+/// or on the jump if they have at least [`MIN_TAKE`] of notes
+pub struct TakeRecorder {
+    events: Receiver<AppEvent>,
+    recorder: InputRecorder,
+}
+
+impl TakeRecorder {
+    /// Returns `None` when input recording is disabled
+    pub fn new(target: &Target) -> Option<Self> {
+        let name = target
+            .midi_file
+            .as_ref()
+            .map(|midi| midi.name.clone())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "recording".into());
+
+        Some(Self {
+            events: target.event_bus.subscribe(),
+            recorder: InputRecorder::new(target, &name)?,
+        })
+    }
+
+    /// `time` is the current song time, without the lead-in
+    pub fn record(&mut self, time: f32, event: &MidiEvent) {
+        let time = Duration::from_secs_f32(time.max(0.0));
+        self.recorder.record(time, event);
+    }
+
+    pub fn update(&mut self) {
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                AppEvent::PlaybackSeek(_) => {
+                    if self.recorder.recording().notes_length() >= MIN_TAKE {
+                        self.recorder.save();
+                    } else {
+                        self.recorder.discard();
+                    }
+                }
+                AppEvent::PlaybackFinished => {
+                    self.recorder.save();
+                }
+                _ => {}
+            }
+        }
+    }
+}
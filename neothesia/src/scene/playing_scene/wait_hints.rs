@@ -0,0 +1,111 @@
+use std::{collections::HashSet, time::Duration};
+
+use crate::{config, render::KeyboardRenderer};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Stage {
+    #[default]
+    None,
+    Highlight,
+    NoteNames,
+    Fingering,
+}
+
+/// Shows more and more about the chord play along is stuck on, see [`config::WaitHints`]
+pub struct WaitHints {
+    /// Chord waited on, and how long for
+    chord: HashSet<u8>,
+    waited: Duration,
+    stage: Stage,
+    /// Keys lit up on the keyboard
+    lit: HashSet<u8>,
+}
+
+impl WaitHints {
+    /// Returns `None` unless wait hints are enabled
+    pub fn new(config: &config::WaitHints) -> Option<Self> {
+        config.enabled.then(|| Self {
+            chord: HashSet::new(),
+            waited: Duration::ZERO,
+            stage: Stage::None,
+            lit: HashSet::new(),
+        })
+    }
+
+    /// `waiting_for` is empty unless playback waits for the user
+    pub fn update(
+        &mut self,
+        config: &config::WaitHints,
+        labels: &config::NoteLabels,
+        keyboard: &mut KeyboardRenderer,
+        waiting_for: &HashSet<u8>,
+        delta: Duration,
+    ) {
+        // Playing part of the chord doesn't start the wait over
+        if waiting_for.is_empty() || !waiting_for.is_subset(&self.chord) {
+            self.chord = waiting_for.clone();
+            self.waited = Duration::ZERO;
+        } else {
+            self.waited += delta;
+        }
+
+        let delays = config.delays();
+        let waited = self.waited.as_secs_f32();
+        let stage = if waiting_for.is_empty() {
+            Stage::None
+        } else if waited >= delays.fingering {
+            Stage::Fingering
+        } else if waited >= delays.note_names {
+            Stage::NoteNames
+        } else if waited >= delays.highlight {
+            Stage::Highlight
+        } else {
+            Stage::None
+        };
+
+        let lit = if stage >= Stage::Highlight {
+            waiting_for.clone()
+        } else {
+            HashSet::new()
+        };
+
+        if stage == self.stage && lit == self.lit {
+            return;
+        }
+
+        let range_start = keyboard.range().start() as usize;
+        for key in self.lit.union(&lit) {
+            if keyboard.range().contains(*key) {
+                keyboard.key_states_mut()[*key as usize - range_start]
+                    .set_hinted(lit.contains(key));
+            }
+        }
+        keyboard.queue_reupload();
+
+        let mut notes: Vec<u8> = lit.iter().copied().collect();
+        notes.sort_unstable();
+
+        // Fingering is suggested for the whole chord, keys already played included
+        let mut chord: Vec<u8> = self.chord.iter().copied().collect();
+        chord.sort_unstable();
+        let fingering = piano_math::fingering::chord_fingering(&chord);
+
+        let labels = notes
+            .iter()
+            .filter_map(|key| {
+                let name = labels.name(*key);
+                let finger = fingering.iter().find(|(note, _)| note == key);
+                let label = match (stage, finger) {
+                    (Stage::Fingering, Some((_, finger))) => format!("{name}\n{finger}"),
+                    (Stage::Fingering | Stage::NoteNames, _) => name,
+                    _ => return None,
+                };
+                Some((*key, label))
+            })
+            .collect();
+        keyboard.set_hint_labels(labels);
+
+        self.stage = stage;
+        self.lit = lit;
+    }
+}
@@ -0,0 +1,158 @@
+use std::time::Duration;
+
+use neothesia_core::config::SceneTransition;
+use neothesia_pipelines::quad::{QuadInstance, QuadPipeline};
+use wgpu_jumpstart::Color;
+
+use super::SceneType;
+use crate::target::Target;
+
+const DURATION: Duration = Duration::from_millis(250);
+
+enum Phase {
+    /// Old scene is being covered
+    Out(Duration),
+    /// Screen is covered, the new scene gets built once this was on screen for a frame
+    Loading { shown: bool },
+    /// New scene is being uncovered
+    In(Duration),
+}
+
+/// Covers the screen while one scene replaces another, with a loading screen in between,
+/// so building the new scene doesn't look like a frozen frame
+pub struct Transition {
+    quad_pipeline: QuadPipeline,
+    style: SceneTransition,
+    to: SceneType,
+    phase: Phase,
+}
+
+impl Transition {
+    pub fn new(target: &Target, to: SceneType) -> Self {
+        let style = target.config.scene_transition;
+
+        let phase = if style == SceneTransition::None {
+            Phase::Loading { shown: false }
+        } else {
+            Phase::Out(Duration::ZERO)
+        };
+
+        Self {
+            quad_pipeline: QuadPipeline::new(&target.gpu, &target.transform_uniform),
+            style,
+            to,
+            phase,
+        }
+    }
+
+    /// Returns the scene to build, once the loading screen was shown
+    pub fn update(&mut self, target: &mut Target, delta: Duration) -> Option<SceneType> {
+        match &mut self.phase {
+            Phase::Out(elapsed) => {
+                *elapsed += delta;
+                if *elapsed >= DURATION {
+                    self.phase = Phase::Loading { shown: false };
+                }
+            }
+            Phase::Loading { shown: true } => return Some(self.to),
+            Phase::Loading { shown: false } => {}
+            Phase::In(elapsed) => *elapsed += delta,
+        }
+
+        self.update_overlay(target);
+
+        None
+    }
+
+    /// New scene is built, start uncovering it
+    pub fn loaded(&mut self) {
+        self.phase = if self.style == SceneTransition::None {
+            Phase::In(DURATION)
+        } else {
+            Phase::In(Duration::ZERO)
+        };
+    }
+
+    /// Nothing but the loading screen is visible
+    pub fn is_covering(&self) -> bool {
+        matches!(self.phase, Phase::Loading { .. })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.phase, Phase::In(elapsed) if elapsed >= DURATION)
+    }
+
+    fn update_overlay(&mut self, target: &mut Target) {
+        let width = target.window_state.logical_size.width;
+        let height = target.window_state.logical_size.height;
+
+        let progress = |elapsed: Duration| {
+            let t = (elapsed.as_secs_f32() / DURATION.as_secs_f32()).min(1.0);
+            t * t * (3.0 - 2.0 * t)
+        };
+
+        // How much of the screen is covered
+        let cover = match self.phase {
+            Phase::Out(elapsed) => progress(elapsed),
+            Phase::Loading { .. } => 1.0,
+            Phase::In(elapsed) => 1.0 - progress(elapsed),
+        };
+
+        let (x, alpha) = match self.style {
+            SceneTransition::Slide => match self.phase {
+                Phase::In(_) => (-width * (1.0 - cover), 1.0),
+                _ => (width * (1.0 - cover), 1.0),
+            },
+            SceneTransition::Fade | SceneTransition::None => (0.0, cover),
+        };
+
+        let (r, g, b) = target.config.themed_background_color();
+        self.quad_pipeline.update_instance_buffer(
+            &target.gpu.queue,
+            vec![QuadInstance {
+                position: [x, 0.0],
+                size: [width, height],
+                color: Color::from_rgba8(r, g, b, alpha).into_linear_rgba(),
+                ..Default::default()
+            }],
+        );
+
+        if self.is_covering() {
+            target.text_renderer.queue_text(wgpu_glyph::Section {
+                screen_position: (width / 2.0, height / 2.0),
+                text: vec![wgpu_glyph::Text::new("Loading...")
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(30.0)],
+                layout: wgpu_glyph::Layout::default_single_line()
+                    .h_align(wgpu_glyph::HorizontalAlign::Center)
+                    .v_align(wgpu_glyph::VerticalAlign::Center),
+                ..Default::default()
+            });
+        }
+    }
+
+    pub fn render(&mut self, target: &mut Target, view: &wgpu::TextureView) {
+        if let Phase::Loading { shown } = &mut self.phase {
+            *shown = true;
+        }
+
+        let mut render_pass = target
+            .gpu
+            .encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+        self.quad_pipeline
+            .render(&target.transform_uniform, &mut render_pass);
+    }
+}
@@ -1,7 +1,12 @@
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::rc::Rc;
+use std::sync::Arc;
 
+use crate::accessibility::Accessibility;
 use crate::config::Config;
+use crate::event_bus::EventBus;
+use crate::export_queue::ExportQueue;
 use crate::input_manager::InputManager;
 use crate::render::TextRenderer;
 use crate::utils::window::WindowState;
@@ -25,8 +30,21 @@ pub struct Target {
 
     pub output_manager: Rc<RefCell<OutputManager>>,
     pub input_manager: InputManager,
-    pub midi_file: Option<Rc<midi_file::Midi>>,
+    pub midi_file: Option<Arc<midi_file::Midi>>,
+    /// Tracks of `midi_file` left out of the waterfall and the keyboard
+    pub hidden_tracks: HashSet<usize>,
+    /// Tracks of `midi_file` that aren't sent to the output
+    pub silent_tracks: HashSet<usize>,
+    /// Tracks of `midi_file` the user plays, play along waits for them instead of playing them
+    pub user_tracks: HashSet<usize>,
+    /// Track of `midi_file` marked as the melody, the melody boost guesses one when `None`
+    pub melody_track: Option<usize>,
     pub config: Config,
+    pub event_bus: EventBus,
+    pub accessibility: Accessibility,
+    pub export_queue: ExportQueue,
+    /// Low power mode is active, particles and blur are left out then
+    pub low_power: bool,
 
     pub proxy: EventLoopProxy<NeothesiaEvent>,
 }
@@ -46,6 +64,10 @@ impl Target {
 
         let text_renderer = TextRenderer::new(&gpu);
 
+        // Window is created hidden, AccessKit has to be set up before it is shown
+        let accessibility = Accessibility::new(&window, proxy.clone());
+        window.set_visible(true);
+
         let iced_manager = IcedManager::new(
             &gpu.device,
             &gpu.queue,
@@ -57,11 +79,15 @@ impl Target {
             window_state.scale_factor,
         );
 
-        let args: Vec<String> = std::env::args().collect();
+        // Flags like `--portable` are handled elsewhere
+        let args: Vec<String> = std::env::args()
+            .filter(|arg| !arg.starts_with("--"))
+            .collect();
 
+        let config = Config::new();
         let midi_file = if args.len() > 1 {
-            if let Ok(midi) = midi_file::Midi::new(&args[1]) {
-                Some(Rc::new(midi))
+            if let Ok(midi) = midi_file::Midi::with_repeats(&args[1], config.expand_repeats) {
+                Some(Arc::new(midi))
             } else {
                 None
             }
@@ -82,7 +108,15 @@ impl Target {
             output_manager: Default::default(),
             input_manager: InputManager::new(proxy.clone()),
             midi_file,
-            config: Config::new(),
+            hidden_tracks: HashSet::new(),
+            silent_tracks: HashSet::new(),
+            user_tracks: HashSet::new(),
+            melody_track: None,
+            config,
+            event_bus: EventBus::new(),
+            accessibility,
+            export_queue: ExportQueue::load(),
+            low_power: false,
             proxy,
         }
     }
@@ -0,0 +1,137 @@
+use std::time::{Duration, Instant};
+
+use crate::{output_manager::MasterVolume, target::Target};
+
+/// How long a toast stays on screen, unless it was made with [`ToastManager::toast_for`]
+const TOAST_TIME: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+pub struct ToastManager {
+    toast: Option<Toast>,
+}
+
+impl ToastManager {
+    pub fn update(&mut self, target: &mut Target) {
+        if let Some(mut toast) = self.toast.take() {
+            self.toast = if toast.draw(target) {
+                Some(toast)
+            } else {
+                None
+            };
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.toast = None;
+    }
+
+    pub fn toast(&mut self, text: String) {
+        self.toast_for(text, TOAST_TIME);
+    }
+
+    pub fn toast_for(&mut self, text: String, duration: Duration) {
+        self.toast = Some(Toast::new(duration, move |target| {
+            let text = vec![wgpu_glyph::Text::new(&text)
+                .with_color([1.0, 1.0, 1.0, 1.0])
+                .with_scale(20.0)];
+
+            target.text_renderer.queue_text(wgpu_glyph::Section {
+                text,
+                screen_position: (0.0, 20.0),
+                layout: wgpu_glyph::Layout::Wrap {
+                    line_breaker: Default::default(),
+                    h_align: wgpu_glyph::HorizontalAlign::Left,
+                    v_align: wgpu_glyph::VerticalAlign::Top,
+                },
+                ..Default::default()
+            });
+        }));
+    }
+
+    pub fn speed_toast(&mut self, speed: f32) {
+        self.toast(format!("Speed: {}%", (speed * 100.0).round()));
+    }
+
+    pub fn tap_tempo_toast(&mut self, bpm: f32, speed: f32) {
+        self.toast(format!(
+            "Tap tempo: {} BPM, speed {}%",
+            bpm.round(),
+            (speed * 100.0).round()
+        ));
+    }
+
+    pub fn speed_trainer_toast(&mut self, enabled: bool, target: f32) {
+        self.toast(if enabled {
+            format!("Speed trainer: On, up to {}%", (target * 100.0).round())
+        } else {
+            String::from("Speed trainer: Off")
+        });
+    }
+
+    pub fn loop_toast(&mut self, enabled: bool) {
+        self.toast(format!("Loop: {}", if enabled { "On" } else { "Off" }));
+    }
+
+    pub fn volume_toast(&mut self, volume: MasterVolume) {
+        self.toast(format!("Volume: {volume}"));
+    }
+
+    pub fn transpose_toast(&mut self, semitones: i8) {
+        self.toast(format!("Transpose: {semitones:+}"));
+    }
+
+    pub fn melody_boost_toast(&mut self, enabled: bool) {
+        self.toast(format!(
+            "Melody boost: {}",
+            if enabled { "On" } else { "Off" }
+        ));
+    }
+
+    pub fn metronome_toast(&mut self, enabled: bool) {
+        self.toast(format!("Metronome: {}", if enabled { "On" } else { "Off" }));
+    }
+
+    /// `markers` as returned by `MidiPlayer::loop_markers` of the playing scene
+    pub fn loop_markers_toast(&mut self, markers: Option<(f32, Option<f32>)>) {
+        let time = |secs: f32| {
+            let secs = secs.max(0.0);
+            format!("{}:{:04.1}", (secs / 60.0) as u32, secs % 60.0)
+        };
+
+        self.toast(match markers {
+            Some((start, Some(end))) => format!("Loop: {} - {}", time(start), time(end)),
+            Some((start, None)) => format!("Loop start: {}", time(start)),
+            None => String::from("Loop: Cleared"),
+        });
+    }
+
+    pub fn offset_toast(&mut self, offset: f32) {
+        self.toast(format!("Offset: {}", (offset * 100.0).round() / 100.0));
+    }
+}
+
+struct Toast {
+    start_time: Instant,
+    duration: Duration,
+    inner_draw: Box<dyn Fn(&mut Target)>,
+}
+
+impl Toast {
+    fn new(duration: Duration, draw: impl Fn(&mut Target) + 'static) -> Self {
+        Self {
+            start_time: Instant::now(),
+            duration,
+            inner_draw: Box::new(draw),
+        }
+    }
+
+    fn draw(&mut self, target: &mut Target) -> bool {
+        if self.start_time.elapsed() < self.duration {
+            (*self.inner_draw)(target);
+
+            true
+        } else {
+            false
+        }
+    }
+}
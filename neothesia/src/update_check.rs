@@ -0,0 +1,142 @@
+//! Opt-in check for new releases published on GitHub
+
+use std::{
+    process::Command,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use neothesia_core::config::UpdateCheck;
+use serde::Deserialize;
+
+const LATEST_RELEASE: &str = "https://api.github.com/repos/PolyMeilex/Neothesia/releases/latest";
+/// GitHub is asked at most once a day
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone)]
+pub struct Release {
+    /// Release tag, eg. `v0.2.0`
+    pub version: String,
+    /// Changelog, as written on GitHub
+    pub notes: String,
+    /// Release page
+    pub url: String,
+    /// Direct download for this platform, when the release has one
+    pub download: Option<String>,
+}
+
+impl Release {
+    /// Download for this platform, or the release page
+    pub fn download_url(&self) -> &str {
+        self.download.as_deref().unwrap_or(&self.url)
+    }
+}
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    #[serde(default)]
+    body: Option<String>,
+    html_url: String,
+    #[serde(default)]
+    assets: Vec<GithubAsset>,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+}
+
+#[derive(Deserialize)]
+struct GithubAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// Unix time in seconds
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+pub fn is_due(config: &UpdateCheck) -> bool {
+    config.enabled && now().saturating_sub(config.last_check) >= CHECK_INTERVAL.as_secs()
+}
+
+/// Blocking, returns the latest release when it is newer than this build
+pub fn fetch_newer_release() -> Result<Option<Release>, String> {
+    let release: GithubRelease = ureq::get(LATEST_RELEASE)
+        .set("User-Agent", "neothesia")
+        .timeout(Duration::from_secs(10))
+        .call()
+        .map_err(|err| err.to_string())?
+        .into_json()
+        .map_err(|err| err.to_string())?;
+
+    if release.draft || release.prerelease {
+        return Ok(None);
+    }
+
+    if !is_newer(&release.tag_name, env!("CARGO_PKG_VERSION")) {
+        return Ok(None);
+    }
+
+    let download = release
+        .assets
+        .iter()
+        .find(|asset| is_for_this_platform(&asset.name))
+        .map(|asset| asset.browser_download_url.clone());
+
+    Ok(Some(Release {
+        version: release.tag_name,
+        notes: release.body.unwrap_or_default(),
+        url: release.html_url,
+        download,
+    }))
+}
+
+/// `major.minor.patch`, with an optional `v` prefix and pre-release suffix
+fn parse_version(version: &str) -> Option<[u64; 3]> {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+
+    let mut out = [0; 3];
+    for (id, part) in version.split('.').enumerate() {
+        *out.get_mut(id)? = part.parse().ok()?;
+    }
+    Some(out)
+}
+
+fn is_newer(tag: &str, current: &str) -> bool {
+    match (parse_version(tag), parse_version(current)) {
+        (Some(tag), Some(current)) => tag > current,
+        _ => false,
+    }
+}
+
+fn is_for_this_platform(name: &str) -> bool {
+    let name = name.to_lowercase();
+
+    if cfg!(target_os = "windows") {
+        name.contains("windows") || name.ends_with(".exe") || name.ends_with(".msi")
+    } else if cfg!(target_os = "macos") {
+        name.contains("macos") || name.ends_with(".dmg")
+    } else {
+        name.contains("linux") || name.ends_with(".appimage") || name.ends_with(".flatpak")
+    }
+}
+
+/// Opens `url` in the default browser
+pub fn open_url(url: &str) {
+    let res = if cfg!(target_os = "windows") {
+        Command::new("cmd").args(["/C", "start", "", url]).spawn()
+    } else if cfg!(target_os = "macos") {
+        Command::new("open").arg(url).spawn()
+    } else {
+        Command::new("xdg-open").arg(url).spawn()
+    };
+
+    if let Err(err) = res {
+        log::error!("Failed to open {url}: {err}");
+    }
+}
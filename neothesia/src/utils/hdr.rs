@@ -0,0 +1,93 @@
+use neothesia_core::config::Hdr;
+use neothesia_pipelines::blit::BlitPipeline;
+use wgpu_jumpstart::{wgpu, Framebuffer, Surface};
+
+use crate::target::Target;
+
+/// Extended range linear format, with sRGB primaries.
+/// Platforms that offer it for a surface treat it as scRGB
+const SCRGB_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Brightness of `1.0` in scRGB
+const SCRGB_WHITE_NITS: f32 = 80.0;
+
+/// Frames are rendered as usual into an SDR framebuffer, which then gets copied to
+/// the scRGB surface with white mapped to the configured paper white
+pub struct HdrOutput {
+    framebuffer: Framebuffer,
+    blit: BlitPipeline,
+}
+
+impl HdrOutput {
+    /// Switches `surface` to scRGB, returns `None` when the platform doesn't offer it
+    pub fn new(target: &Target, surface: &mut Surface, config: &Hdr) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+
+        if !surface.supports_format(&target.gpu.adapter, SCRGB_FORMAT) {
+            log::warn!("HDR output is not supported on this display");
+            return None;
+        }
+
+        surface.set_format(&target.gpu.device, SCRGB_FORMAT);
+
+        let mut blit =
+            BlitPipeline::with_format(&target.gpu, wgpu::FilterMode::Nearest, SCRGB_FORMAT);
+        blit.set_brightness(
+            &target.gpu.queue,
+            config.paper_white.max(1.0) / SCRGB_WHITE_NITS,
+        );
+
+        let mut output = Self {
+            framebuffer: Self::create_framebuffer(target),
+            blit,
+        };
+        output
+            .blit
+            .set_source(&target.gpu.device, output.framebuffer.view());
+
+        log::info!("HDR output enabled");
+
+        Some(output)
+    }
+
+    fn create_framebuffer(target: &Target) -> Framebuffer {
+        let size = target.window_state.physical_size;
+        Framebuffer::new(
+            &target.gpu.device,
+            target.gpu.texture_format,
+            size.width,
+            size.height,
+            1,
+        )
+    }
+
+    pub fn resize(&mut self, target: &Target) {
+        self.framebuffer = Self::create_framebuffer(target);
+        self.blit
+            .set_source(&target.gpu.device, self.framebuffer.view());
+    }
+
+    /// Frames get rendered here instead of the surface
+    pub fn view(&self) -> &wgpu::TextureView {
+        self.framebuffer.view()
+    }
+
+    /// Copies the rendered frame to `surface_view`
+    pub fn present(&self, encoder: &mut wgpu::CommandEncoder, surface_view: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: surface_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        self.blit.render(&mut render_pass);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod hdr;
+pub mod power;
 pub mod window;
 
 pub use neothesia_core::utils::*;
@@ -0,0 +1,66 @@
+use std::time::{Duration, Instant};
+
+use neothesia_core::config::{LowPower, LowPowerMode};
+
+/// Power source doesn't change often, and asking for it may spawn a process
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Decides whether low power mode should be active
+#[derive(Default)]
+pub struct PowerMonitor {
+    on_battery: bool,
+    last_check: Option<Instant>,
+}
+
+impl PowerMonitor {
+    pub fn low_power(&mut self, config: &LowPower) -> bool {
+        match config.mode {
+            LowPowerMode::On => true,
+            LowPowerMode::Off => false,
+            LowPowerMode::Auto => {
+                let due = self
+                    .last_check
+                    .map_or(true, |last| last.elapsed() >= CHECK_INTERVAL);
+
+                if due {
+                    self.on_battery = on_battery();
+                    self.last_check = Some(Instant::now());
+                }
+
+                self.on_battery
+            }
+        }
+    }
+}
+
+/// Any battery being drained means we are not plugged in
+#[cfg(target_os = "linux")]
+fn on_battery() -> bool {
+    let entries = match std::fs::read_dir("/sys/class/power_supply") {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+
+    entries.flatten().any(|entry| {
+        let read =
+            |name: &str| std::fs::read_to_string(entry.path().join(name)).unwrap_or_default();
+        read("type").trim() == "Battery" && read("status").trim() == "Discharging"
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn on_battery() -> bool {
+    match std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+    {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"),
+        Err(_) => false,
+    }
+}
+
+/// Not detected, low power mode has to be turned on by hand
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn on_battery() -> bool {
+    false
+}
@@ -0,0 +1,142 @@
+//! Names of the chords held on the keyboard, like `Cmaj7` or `Am/C`
+
+use crate::names::pitch_class_name;
+
+/// Suffixes of the chord qualities and their semitones above the root,
+/// earlier ones are preferred when the same keys spell more than one chord
+const QUALITIES: &[(&str, &[u8])] = &[
+    ("", &[0, 4, 7]),
+    ("m", &[0, 3, 7]),
+    ("7", &[0, 4, 7, 10]),
+    ("maj7", &[0, 4, 7, 11]),
+    ("m7", &[0, 3, 7, 10]),
+    ("dim", &[0, 3, 6]),
+    ("aug", &[0, 4, 8]),
+    ("sus4", &[0, 5, 7]),
+    ("sus2", &[0, 2, 7]),
+    ("6", &[0, 4, 7, 9]),
+    ("m6", &[0, 3, 7, 9]),
+    ("m7b5", &[0, 3, 6, 10]),
+    ("dim7", &[0, 3, 6, 9]),
+    ("mMaj7", &[0, 3, 7, 11]),
+    ("add9", &[0, 2, 4, 7]),
+    ("9", &[0, 2, 4, 7, 10]),
+    ("maj9", &[0, 2, 4, 7, 11]),
+    ("m9", &[0, 2, 3, 7, 10]),
+    ("5", &[0, 7]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chord {
+    /// Pitch class, `0` is C
+    pub root: u8,
+    pub quality: &'static str,
+    /// Pitch class of the lowest key, the root unless the chord is inverted
+    pub bass: u8,
+}
+
+impl Chord {
+    /// `C`, `F#m7`, or `C/E` for inversions. Black keys are sharps unless `flats`
+    pub fn name(&self, flats: bool) -> String {
+        let name = format!("{}{}", pitch_class_name(self.root, flats), self.quality);
+        if self.bass == self.root {
+            name
+        } else {
+            format!("{name}/{}", pitch_class_name(self.bass, flats))
+        }
+    }
+}
+
+/// Chord spelled by `keys` in any octave and order, `None` for single notes
+/// and for keys that make no chord this knows of
+pub fn identify(keys: &[u8]) -> Option<Chord> {
+    let bass = keys.iter().min()? % 12;
+    let classes: u16 = keys
+        .iter()
+        .fold(0, |classes, key| classes | 1 << (key % 12));
+    if classes.count_ones() < 2 {
+        return None;
+    }
+
+    let mask = |root: u8, intervals: &[u8]| -> u16 {
+        intervals
+            .iter()
+            .fold(0, |mask, interval| mask | 1 << ((root + interval) % 12))
+    };
+
+    // The bass is the most likely root, C6 and Am7 share their keys
+    let roots = std::iter::once(bass).chain((0..12).filter(|root| *root != bass));
+    let mut best: Option<(usize, Chord)> = None;
+    for root in roots.filter(|root| classes & 1 << root != 0) {
+        let found = QUALITIES
+            .iter()
+            .position(|(_, intervals)| mask(root, intervals) == classes);
+
+        if let Some(id) = found {
+            // Root position wins over any inversion
+            if root == bass {
+                return Some(Chord {
+                    root,
+                    quality: QUALITIES[id].0,
+                    bass,
+                });
+            }
+            if best.map_or(true, |(best, _)| id < best) {
+                let quality = QUALITIES[id].0;
+                best = Some((
+                    id,
+                    Chord {
+                        root,
+                        quality,
+                        bass,
+                    },
+                ));
+            }
+        }
+    }
+
+    best.map(|(_, chord)| chord)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(keys: &[u8]) -> Option<String> {
+        identify(keys).map(|chord| chord.name(false))
+    }
+
+    #[test]
+    fn chords() {
+        assert_eq!(name(&[60, 64, 67]).as_deref(), Some("C"));
+        assert_eq!(name(&[57, 60, 64]).as_deref(), Some("Am"));
+        assert_eq!(name(&[55, 59, 62, 65]).as_deref(), Some("G7"));
+        assert_eq!(name(&[66, 70, 73, 76]).as_deref(), Some("F#7"));
+        // Doubled keys and spread voicings
+        assert_eq!(name(&[48, 60, 64, 67, 72, 76]).as_deref(), Some("C"));
+        assert_eq!(name(&[36, 55, 64, 71]).as_deref(), Some("Cmaj7"));
+    }
+
+    #[test]
+    fn inversions() {
+        assert_eq!(name(&[64, 67, 72]).as_deref(), Some("C/E"));
+        assert_eq!(name(&[60, 64, 69]).as_deref(), Some("Am/C"));
+        // Same keys as Am7, the bass decides
+        assert_eq!(name(&[60, 64, 67, 69]).as_deref(), Some("C6"));
+        assert_eq!(name(&[57, 60, 64, 67]).as_deref(), Some("Am7"));
+        assert_eq!(
+            identify(&[70, 74, 77])
+                .map(|chord| chord.name(true))
+                .as_deref(),
+            Some("Bb")
+        );
+    }
+
+    #[test]
+    fn not_chords() {
+        assert_eq!(name(&[]), None);
+        assert_eq!(name(&[60]), None);
+        assert_eq!(name(&[60, 72]), None);
+        assert_eq!(name(&[60, 61, 62]), None);
+    }
+}
@@ -0,0 +1,92 @@
+//! Fingering suggestions for a chord, from where its notes sit in the hand
+
+/// Notes below it are played by the left hand
+const MIDDLE_C: u8 = 60;
+/// Widest chord a hand is expected to reach, in semitones
+const MAX_SPAN: u8 = 12;
+
+/// Suggested finger for the notes of a chord, `1` is the thumb and `5` the little finger.
+///
+/// Notes from middle C up go to the right hand, the rest to the left one.
+/// A hand with more than five notes, or wider than an octave, gets no suggestion
+pub fn chord_fingering(keys: &[u8]) -> Vec<(u8, u8)> {
+    let mut left: Vec<u8> = keys.iter().copied().filter(|key| *key < MIDDLE_C).collect();
+    let mut right: Vec<u8> = keys
+        .iter()
+        .copied()
+        .filter(|key| *key >= MIDDLE_C)
+        .collect();
+
+    left.sort_unstable();
+    left.dedup();
+    right.sort_unstable();
+    right.dedup();
+
+    // The left thumb plays the highest note, so it's fingered top down
+    left.reverse();
+
+    let mut fingering = hand_fingering(&left);
+    fingering.extend(hand_fingering(&right));
+    fingering.sort_unstable();
+    fingering
+}
+
+/// `keys` go from the thumb outwards
+fn hand_fingering(keys: &[u8]) -> Vec<(u8, u8)> {
+    let n = keys.len();
+    let (first, last) = match (keys.first(), keys.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return Vec::new(),
+    };
+    if n > 5 || first.abs_diff(last) > MAX_SPAN {
+        return Vec::new();
+    }
+
+    let mut prev = 0;
+    keys.iter()
+        .enumerate()
+        .map(|(id, key)| {
+            // A finger reaches about a whole tone further than the one before it
+            let guess = 1 + (first.abs_diff(*key) + 1) / 2;
+            // Leaves a finger for each note still to come
+            let finger = guess.clamp(prev + 1, (5 - (n - 1 - id)) as u8);
+            prev = finger;
+            (*key, finger)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triads_are_played_with_1_3_5() {
+        assert_eq!(
+            chord_fingering(&[67, 60, 64]),
+            vec![(60, 1), (64, 3), (67, 5)]
+        );
+        // Left hand, thumb on top
+        assert_eq!(
+            chord_fingering(&[48, 52, 55]),
+            vec![(48, 5), (52, 3), (55, 1)]
+        );
+    }
+
+    #[test]
+    fn every_note_gets_its_own_finger() {
+        assert_eq!(
+            chord_fingering(&[60, 61, 62, 63, 64]),
+            vec![(60, 1), (61, 2), (62, 3), (63, 4), (64, 5)]
+        );
+        assert_eq!(chord_fingering(&[60, 72]), vec![(60, 1), (72, 5)]);
+    }
+
+    #[test]
+    fn unplayable_hands_get_no_fingering() {
+        assert_eq!(chord_fingering(&[60, 74]), vec![]);
+        assert_eq!(chord_fingering(&[60, 62, 64, 65, 67, 69]), vec![]);
+        // The other hand is still fingered
+        assert_eq!(chord_fingering(&[40, 60, 74]), vec![(40, 1)]);
+    }
+}
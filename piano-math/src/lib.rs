@@ -1,4 +1,9 @@
+pub mod chord;
+pub mod fingering;
+pub mod names;
 pub mod range;
+pub mod scale;
+pub mod staff;
 pub use range::KeyboardRange;
 
 #[derive(Debug, Clone)]
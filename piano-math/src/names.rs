@@ -0,0 +1,86 @@
+//! Names of the keys, as letters with an octave or as fixed do solfège
+
+const LETTERS_SHARP: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+const LETTERS_FLAT: [&str; 12] = [
+    "C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B",
+];
+const SOLFEGE_SHARP: [&str; 12] = [
+    "Do", "Do#", "Re", "Re#", "Mi", "Fa", "Fa#", "Sol", "Sol#", "La", "La#", "Si",
+];
+const SOLFEGE_FLAT: [&str; 12] = [
+    "Do", "Reb", "Re", "Mib", "Mi", "Fa", "Solb", "Sol", "Lab", "La", "Sib", "Si",
+];
+
+/// Scientific pitch notation, `60` is `C4`. Black keys are sharps unless `flats`
+pub fn letter_name(note: u8, flats: bool) -> String {
+    let names = if flats { &LETTERS_FLAT } else { &LETTERS_SHARP };
+    format!("{}{}", names[note as usize % 12], note as i32 / 12 - 1)
+}
+
+/// Letter name without the octave, every C is `C`. Black keys are sharps unless `flats`
+pub fn pitch_class_name(note: u8, flats: bool) -> &'static str {
+    let names = if flats { &LETTERS_FLAT } else { &LETTERS_SHARP };
+    names[note as usize % 12]
+}
+
+/// Fixed do, every C is `Do`. Black keys are sharps unless `flats`
+pub fn solfege_name(note: u8, flats: bool) -> &'static str {
+    let names = if flats { &SOLFEGE_FLAT } else { &SOLFEGE_SHARP };
+    names[note as usize % 12]
+}
+
+/// Key of a name in scientific pitch notation, like `C4`, `F#5` or `Bb2`.
+/// Letters may be lower case, `None` for anything else or keys out of MIDI range
+pub fn parse_letter_name(name: &str) -> Option<u8> {
+    let name = name.trim();
+    let mut chars = name.chars();
+    let class: i32 = match chars.next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+
+    let rest = chars.as_str();
+    let (alter, octave) = match rest.chars().next() {
+        Some('#') => (1, &rest[1..]),
+        Some('b') => (-1, &rest[1..]),
+        _ => (0, rest),
+    };
+    let octave: i32 = octave.parse().ok()?;
+
+    let key = (octave + 1) * 12 + class + alter;
+    u8::try_from(key).ok().filter(|key| *key <= 127)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(letter_name(60, false), "C4");
+        assert_eq!(letter_name(75, false), "D#5");
+        assert_eq!(letter_name(75, true), "Eb5");
+        assert_eq!(letter_name(21, false), "A0");
+        assert_eq!(pitch_class_name(61, false), "C#");
+        assert_eq!(pitch_class_name(61, true), "Db");
+
+        assert_eq!(solfege_name(60, false), "Do");
+        assert_eq!(solfege_name(66, false), "Fa#");
+        assert_eq!(solfege_name(66, true), "Solb");
+
+        assert_eq!(parse_letter_name("C4"), Some(60));
+        assert_eq!(parse_letter_name(" eb5"), Some(75));
+        assert_eq!(parse_letter_name("F#-1"), Some(6));
+        assert_eq!(parse_letter_name("G9"), Some(127));
+        assert_eq!(parse_letter_name("G#9"), None);
+        assert_eq!(parse_letter_name("H2"), None);
+    }
+}
@@ -0,0 +1,50 @@
+//! Pitch classes of scales and modes, built on a tonic
+
+/// Semitones above the tonic of each degree
+pub const MAJOR: &[u8] = &[0, 2, 4, 5, 7, 9, 11];
+pub const NATURAL_MINOR: &[u8] = &[0, 2, 3, 5, 7, 8, 10];
+pub const HARMONIC_MINOR: &[u8] = &[0, 2, 3, 5, 7, 8, 11];
+pub const MELODIC_MINOR: &[u8] = &[0, 2, 3, 5, 7, 9, 11];
+pub const DORIAN: &[u8] = &[0, 2, 3, 5, 7, 9, 10];
+pub const PHRYGIAN: &[u8] = &[0, 1, 3, 5, 7, 8, 10];
+pub const LYDIAN: &[u8] = &[0, 2, 4, 6, 7, 9, 11];
+pub const MIXOLYDIAN: &[u8] = &[0, 2, 4, 5, 7, 9, 10];
+pub const LOCRIAN: &[u8] = &[0, 1, 3, 5, 6, 8, 10];
+pub const MAJOR_PENTATONIC: &[u8] = &[0, 2, 4, 7, 9];
+pub const MINOR_PENTATONIC: &[u8] = &[0, 3, 5, 7, 10];
+pub const BLUES: &[u8] = &[0, 3, 5, 6, 7, 10];
+
+/// Whether `key` is in the scale of `intervals` starting on the pitch class of `tonic`
+pub fn contains(tonic: u8, intervals: &[u8], key: u8) -> bool {
+    let degree = (key % 12 + 12 - tonic % 12) % 12;
+    intervals.contains(&degree)
+}
+
+/// Whether `key` has the pitch class of `tonic`, in any octave
+pub fn is_tonic(tonic: u8, key: u8) -> bool {
+    key % 12 == tonic % 12
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales() {
+        // C major is the white keys
+        let white: Vec<u8> = (60..72).filter(|key| contains(0, MAJOR, *key)).collect();
+        assert_eq!(white, [60, 62, 64, 65, 67, 69, 71]);
+
+        // F sharp in G major, not F
+        assert!(contains(7, MAJOR, 66));
+        assert!(!contains(7, MAJOR, 65));
+        // A minor and C major share their keys
+        assert!((0..128).all(|key| contains(9, NATURAL_MINOR, key) == contains(0, MAJOR, key)));
+        // Flat five of E blues
+        assert!(contains(4, BLUES, 70));
+        assert!(!contains(4, MINOR_PENTATONIC, 70));
+
+        assert!(is_tonic(2, 74));
+        assert!(!is_tonic(2, 75));
+    }
+}
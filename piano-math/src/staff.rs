@@ -0,0 +1,160 @@
+//! Where notes go on a grand staff, the treble staff holds middle C and up, the bass one the rest
+
+/// Pitch class of each letter, `C` to `B`
+const LETTERS: [u8; 7] = [0, 2, 4, 5, 7, 9, 11];
+/// Letters the sharps of a key signature are added to, in order
+const SHARP_ORDER: [usize; 7] = [3, 0, 4, 1, 5, 2, 6];
+/// Letters the flats of a key signature are added to, in order
+const FLAT_ORDER: [usize; 7] = [6, 2, 5, 1, 4, 0, 3];
+/// Treble staff steps of the key signature sharps, `F5 C5 G5 D5 A4 E5 B4`
+const SHARP_STEPS: [i32; 7] = [10, 7, 11, 8, 5, 9, 6];
+/// Treble staff steps of the key signature flats, `B4 E5 A4 D5 G4 C5 F4`
+const FLAT_STEPS: [i32; 7] = [6, 9, 5, 8, 4, 7, 3];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Accidental {
+    Sharp,
+    Flat,
+    Natural,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaffNote {
+    /// Diatonic steps above middle C, every line and every space is a step
+    pub step: i32,
+    /// `None` when the key signature already gives the note its pitch
+    pub accidental: Option<Accidental>,
+}
+
+/// Lowest and highest line of a staff, in steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Staff {
+    pub bottom: i32,
+    pub top: i32,
+}
+
+impl Staff {
+    pub const TREBLE: Self = Self { bottom: 2, top: 10 };
+    pub const BASS: Self = Self {
+        bottom: -10,
+        top: -2,
+    };
+
+    /// Staff `key` is written on
+    pub fn of(key: u8) -> Self {
+        if key >= 60 {
+            Self::TREBLE
+        } else {
+            Self::BASS
+        }
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = i32> {
+        (self.bottom..=self.top).step_by(2)
+    }
+
+    /// Short lines a note above or below the staff is drawn across
+    pub fn ledger_lines(&self, step: i32) -> Vec<i32> {
+        let range = if step < self.bottom {
+            step..self.bottom
+        } else if step > self.top {
+            self.top + 1..step + 1
+        } else {
+            return Vec::new();
+        };
+        range.filter(|step| step % 2 == 0).collect()
+    }
+
+    /// Steps of the key signature accidentals, in the order they are drawn
+    pub fn signature(&self, sharps: i8) -> Vec<i32> {
+        // Two octaves down on the bass staff
+        let shift = if *self == Staff::TREBLE { 0 } else { -14 };
+        let count = sharps.unsigned_abs().min(7) as usize;
+        let steps = if sharps >= 0 {
+            &SHARP_STEPS
+        } else {
+            &FLAT_STEPS
+        };
+        steps[..count].iter().map(|step| step + shift).collect()
+    }
+}
+
+/// Sharps of the key signature of a key, flats are negative
+pub fn key_sharps(tonic: u8, minor: bool) -> i8 {
+    let major = if minor { (tonic + 3) % 12 } else { tonic % 12 };
+    // Every sharp moves the tonic up a fifth, D flat is preferred over C sharp
+    (-5..=6)
+        .find(|sharps: &i8| (*sharps as i32 * 7).rem_euclid(12) == major as i32)
+        .unwrap_or(0)
+}
+
+/// Spelling of `key` in the key with `sharps`, black keys are sharps in sharp keys
+/// and flats in flat ones. Accidentals are drawn on every note the signature doesn't cover
+pub fn staff_note(key: u8, sharps: i8) -> StaffNote {
+    let octave = key as i32 / 12 - 1;
+    let class = key % 12;
+
+    let (letter, alter) = match LETTERS.iter().position(|l| *l == class) {
+        Some(letter) => (letter, 0),
+        None if sharps >= 0 => (LETTERS.iter().rposition(|l| *l < class).unwrap(), 1),
+        None => (LETTERS.iter().position(|l| *l > class).unwrap(), -1),
+    };
+
+    let count = sharps.unsigned_abs().min(7) as usize;
+    let signature = if sharps >= 0 {
+        SHARP_ORDER[..count].contains(&letter) as i32
+    } else {
+        -(FLAT_ORDER[..count].contains(&letter) as i32)
+    };
+
+    let accidental = match alter {
+        _ if alter == signature => None,
+        1 => Some(Accidental::Sharp),
+        -1 => Some(Accidental::Flat),
+        _ => Some(Accidental::Natural),
+    };
+
+    StaffNote {
+        step: (octave - 4) * 7 + letter as i32,
+        accidental,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spelling() {
+        let note = |step, accidental| StaffNote { step, accidental };
+
+        // Middle C and the F sharp of G major
+        assert_eq!(staff_note(60, 0), note(0, None));
+        assert_eq!(staff_note(66, 1), note(3, None));
+        assert_eq!(staff_note(66, 0), note(3, Some(Accidental::Sharp)));
+        // F natural in G major, B flat in F major, the same key is A sharp in C major
+        assert_eq!(staff_note(65, 1), note(3, Some(Accidental::Natural)));
+        assert_eq!(staff_note(70, -1), note(6, None));
+        assert_eq!(staff_note(70, 0), note(5, Some(Accidental::Sharp)));
+        assert_eq!(staff_note(43, 0), note(-10, None));
+    }
+
+    #[test]
+    fn key_signatures() {
+        assert_eq!(key_sharps(7, false), 1);
+        assert_eq!(key_sharps(5, false), -1);
+        assert_eq!(key_sharps(9, true), 0);
+        assert_eq!(key_sharps(1, false), -5);
+
+        assert_eq!(Staff::TREBLE.signature(2), [10, 7]);
+        assert_eq!(Staff::BASS.signature(-1), [-8]);
+    }
+
+    #[test]
+    fn ledger_lines() {
+        assert_eq!(Staff::of(60).ledger_lines(0), [0]);
+        assert_eq!(Staff::TREBLE.ledger_lines(13), [12]);
+        assert_eq!(Staff::BASS.ledger_lines(-15), [-14, -12]);
+        assert!(Staff::BASS.ledger_lines(-5).is_empty());
+    }
+}
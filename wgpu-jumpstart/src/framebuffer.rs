@@ -0,0 +1,83 @@
+/// Offscreen render target, optionally multisampled.
+///
+/// Multisampled content gets resolved into [`Framebuffer::view`] at the end of every render pass
+pub struct Framebuffer {
+    view: wgpu::TextureView,
+    msaa_view: Option<wgpu::TextureView>,
+    sample_count: u32,
+    size: (u32, u32),
+}
+
+impl Framebuffer {
+    pub fn new(
+        device: &wgpu::Device,
+        texture_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        };
+
+        let create_view = |sample_count: u32, usage: wgpu::TextureUsages| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Framebuffer"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: texture_format,
+                    usage,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+
+        let view = create_view(
+            1,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+        let msaa_view = (sample_count > 1)
+            .then(|| create_view(sample_count, wgpu::TextureUsages::RENDER_ATTACHMENT));
+
+        Self {
+            view,
+            msaa_view,
+            sample_count,
+            size: (size.width, size.height),
+        }
+    }
+
+    /// Resolved content, ready to be sampled
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn color_attachment(
+        &self,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'_> {
+        let (view, resolve_target) = match self.msaa_view.as_ref() {
+            Some(msaa_view) => (msaa_view, Some(&self.view)),
+            None => (&self.view, None),
+        };
+
+        wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target,
+            ops: wgpu::Operations { load, store: true },
+        }
+    }
+}
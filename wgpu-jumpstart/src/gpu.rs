@@ -50,7 +50,9 @@ impl Gpu {
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::empty(),
+                    // Needed for sample counts other than 1 and 4
+                    features: adapter.features()
+                        & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
                     limits: wgpu::Limits {
                         max_compute_workgroup_storage_size: 0,
                         max_compute_invocations_per_workgroup: 0,
@@ -93,6 +95,23 @@ impl Gpu {
         })
     }
 
+    /// Whether render targets of `texture_format` can be multisampled with `count` samples
+    pub fn supports_sample_count(&self, count: u32) -> bool {
+        // Guaranteed by WebGPU
+        if count == 1 || count == 4 {
+            return true;
+        }
+
+        self.device
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+            && self
+                .adapter
+                .get_texture_format_features(self.texture_format)
+                .flags
+                .sample_count_supported(count)
+    }
+
     pub fn clear(&mut self, view: &wgpu::TextureView, color: Color) {
         let rgb = color.into_linear_rgb();
         self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -161,6 +180,24 @@ impl Surface {
         }
     }
 
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.surface_configuration.format
+    }
+
+    pub fn supports_format(&self, adapter: &wgpu::Adapter, format: wgpu::TextureFormat) -> bool {
+        self.surface
+            .get_capabilities(adapter)
+            .formats
+            .contains(&format)
+    }
+
+    pub fn set_format(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        self.surface_configuration.format = format;
+        self.surface_configuration.view_formats = vec![format];
+
+        self.surface.configure(device, &self.surface_configuration);
+    }
+
     #[inline]
     pub fn get_current_texture(&mut self) -> Result<wgpu::SurfaceTexture, wgpu::SurfaceError> {
         self.surface.get_current_texture()
@@ -4,6 +4,7 @@ mod error;
 pub use error::GpuInitError;
 
 mod color;
+mod framebuffer;
 mod gpu;
 mod instances;
 mod render_pipeline_builder;
@@ -15,6 +16,7 @@ mod transform_uniform;
 pub use wgpu;
 pub use {
     color::Color,
+    framebuffer::Framebuffer,
     gpu::{default_backends, Gpu, Surface},
     instances::Instances,
     render_pipeline_builder::{default_color_target_state, RenderPipelineBuilder},
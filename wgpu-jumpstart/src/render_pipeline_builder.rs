@@ -68,6 +68,12 @@ impl<'a> RenderPipelineBuilder<'a> {
         self
     }
 
+    /// Has to match the sample count of the render target, see [`crate::Framebuffer`]
+    pub fn sample_count(mut self, count: u32) -> Self {
+        self.render_pipeline_descriptor.multisample.count = count;
+        self
+    }
+
     pub fn build(self, device: &wgpu::Device) -> wgpu::RenderPipeline {
         device.create_render_pipeline(&self.render_pipeline_descriptor)
     }
@@ -6,34 +6,55 @@ pub struct TransformUniform {
     transform: [f32; 16],
     size: [f32; 2],
     scale: f32,
-    // must be aligned to largest member (vec4),
+    // vec2 offset has to be aligned to 8 bytes
     _padding: [f32; 1],
+    /// Top left corner of the view, in logical pixels
+    offset: [f32; 2],
+    // must be aligned to largest member (vec4),
+    _padding2: [f32; 2],
 }
 impl Default for TransformUniform {
     fn default() -> Self {
         Self {
-            transform: orthographic_projection(1080.0, 720.0),
+            transform: orthographic_projection(1080.0, 720.0, 1.0, [0.0, 0.0]),
             size: [1080.0, 720.0],
             scale: 1.0,
             _padding: [0.0; 1],
+            offset: [0.0; 2],
+            _padding2: [0.0; 2],
         }
     }
 }
 impl TransformUniform {
     pub fn update(&mut self, width: f32, height: f32, scale: f32) {
-        self.transform = orthographic_projection(width, height);
+        self.update_with_camera(width, height, scale, 1.0, [0.0, 0.0]);
+    }
+
+    /// View magnified by `zoom`, with `offset` in the top left corner
+    pub fn update_with_camera(
+        &mut self,
+        width: f32,
+        height: f32,
+        scale: f32,
+        zoom: f32,
+        offset: [f32; 2],
+    ) {
+        self.transform = orthographic_projection(width, height, zoom, offset);
         self.size = [width, height];
-        self.scale = scale;
+        self.scale = scale * zoom;
+        self.offset = offset;
     }
 }
 
-fn orthographic_projection(width: f32, height: f32) -> [f32; 16] {
+fn orthographic_projection(width: f32, height: f32, zoom: f32, offset: [f32; 2]) -> [f32; 16] {
+    let [x, y] = offset;
+
     #[rustfmt::skip]
     let out = [
-        2.0 / width, 0.0, 0.0, 0.0,
-        0.0, -2.0 / height, 0.0, 0.0,
+        2.0 * zoom / width, 0.0, 0.0, 0.0,
+        0.0, -2.0 * zoom / height, 0.0, 0.0,
         0.0, 0.0, 1.0, 0.0,
-        -1.0, 1.0, 0.0, 1.0,
+        -1.0 - 2.0 * zoom * x / width, 1.0 + 2.0 * zoom * y / height, 0.0, 1.0,
     ];
 
     out